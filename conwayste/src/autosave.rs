@@ -0,0 +1,474 @@
+/*  Copyright 2021 the Conwayste Developers.
+ *
+ *  This file is part of conwayste.
+ *
+ *  conwayste is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  conwayste is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with conwayste.  If not, see
+ *  <http://www.gnu.org/licenses/>. */
+
+//! Periodic saving of the single-player universe, so that it can be resumed after a crash.
+//!
+//! An autosave file is a bincode-encoded `AUTOSAVE_FORMAT_VERSION` followed by an `Autosave`. It
+//! is always replaced by writing a temporary file and renaming it over the old one, so a crash in
+//! the middle of a write leaves the previous autosave intact. Nothing in here depends on ggez.
+
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use conway::error::ConwayError;
+use conway::grids::CharGrid;
+use conway::rle::Pattern;
+use conway::universe::{GenStateDiff, Universe};
+use conway::ConwayResult;
+
+/// Must be bumped whenever `Autosave` or `Camera` change.
+pub const AUTOSAVE_FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug)]
+pub enum AutosaveError {
+    Io(io::Error),
+    Encoding(bincode::Error),
+    UnsupportedVersion { found: u32 },
+}
+
+impl fmt::Display for AutosaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AutosaveError::Io(e) => write!(f, "I/O error: {}", e),
+            AutosaveError::Encoding(e) => write!(f, "Invalid autosave data: {}", e),
+            AutosaveError::UnsupportedVersion { found } => write!(
+                f,
+                "Autosave format version {} is not supported (expected {})",
+                found, AUTOSAVE_FORMAT_VERSION
+            ),
+        }
+    }
+}
+
+impl Error for AutosaveError {
+}
+
+impl From<io::Error> for AutosaveError {
+    fn from(e: io::Error) -> Self {
+        AutosaveError::Io(e)
+    }
+}
+
+impl From<bincode::Error> for AutosaveError {
+    fn from(e: bincode::Error) -> Self {
+        AutosaveError::Encoding(e)
+    }
+}
+
+/// Which part of the universe was on screen, and how zoomed in.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Camera {
+    pub cell_size: f32,
+    pub origin_x:  f32, // window coordinates of the universe's top left corner
+    pub origin_y:  f32,
+}
+
+/// A snapshot of a single-player game.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Autosave {
+    pub generation: usize,
+    pub pattern:    String, // RLE of every cell in the universe (including dead ones), without fog
+    pub camera:     Camera,
+}
+
+impl Autosave {
+    pub fn from_universe(uni: &Universe, camera: Camera) -> Self {
+        Autosave {
+            generation: uni.latest_gen(),
+            pattern: full_pattern(uni),
+            camera,
+        }
+    }
+
+    /// Writes the saved cells and generation into `uni`, which must be freshly created in client
+    /// mode (`BigBang::server_mode(false)`) with the same dimensions and players as the saved one.
+    /// Fog is not saved, so it is cleared for all players.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the pattern is invalid, or `uni` already has a generation.
+    pub fn restore(&self, uni: &mut Universe) -> ConwayResult<()> {
+        let diff = GenStateDiff {
+            gen0:    0,
+            gen1:    self.generation,
+            pattern: Pattern(self.pattern.clone()),
+        };
+        match uni.apply(&diff, None)? {
+            Some(_) => Ok(()),
+            None => Err(ConwayError::InvalidData {
+                reason: format!(
+                    "Cannot resume generation {} in a universe already at generation {}",
+                    self.generation,
+                    uni.latest_gen()
+                ),
+            }),
+        }
+    }
+
+    pub fn read_from_file(path: &Path) -> Result<Self, AutosaveError> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let version: u32 = bincode::deserialize_from(&mut reader)?;
+        if version != AUTOSAVE_FORMAT_VERSION {
+            return Err(AutosaveError::UnsupportedVersion { found: version });
+        }
+        Ok(bincode::deserialize_from(&mut reader)?)
+    }
+
+    /// Replaces the file at `path` with this autosave. The file is either completely replaced or
+    /// left as it was.
+    pub fn write_to_file(&self, path: &Path) -> Result<(), AutosaveError> {
+        let tmp_path = temp_path(path);
+        {
+            let mut writer = BufWriter::new(File::create(&tmp_path)?);
+            bincode::serialize_into(&mut writer, &AUTOSAVE_FORMAT_VERSION)?;
+            bincode::serialize_into(&mut writer, self)?;
+            let file = writer.into_inner().map_err(|e| e.into_error())?;
+            file.sync_all()?;
+        }
+        fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+}
+
+/// Like `CharGrid::to_pattern`, but dead cells at the end of a row or at the bottom of the universe
+/// are written out too. When applied to a fresh client-mode universe, a cell not mentioned in the
+/// pattern would be left unknown rather than dead.
+fn full_pattern(uni: &Universe) -> String {
+    let mut pattern = String::new();
+    for row in 0..uni.height() {
+        let mut col = 0;
+        while col < uni.width() {
+            let (run, ch) = uni.get_run(col, row, None);
+            if run > 1 {
+                pattern.push_str(&run.to_string());
+            }
+            pattern.push(ch);
+            col += run;
+        }
+        pattern.push(if row + 1 < uni.height() { '$' } else { '!' });
+    }
+    pattern
+}
+
+/// The file an autosave is written to before being renamed over `path`.
+fn temp_path(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().map(|name| name.to_os_string()).unwrap_or_default();
+    file_name.push(".tmp");
+    path.with_file_name(file_name)
+}
+
+/// Decides when the next autosave is due: every `every_generations` generations, or after `every`
+/// has passed if anything changed in the meantime. Either can be disabled by setting it to zero.
+#[derive(Debug, Clone)]
+pub struct AutosaveSchedule {
+    every_generations: usize,
+    every:             Duration,
+    last_generation:   usize,
+    last_time:         Instant,
+    changed:           bool,
+}
+
+impl AutosaveSchedule {
+    pub fn new(every_generations: usize, every: Duration, generation: usize, now: Instant) -> Self {
+        AutosaveSchedule {
+            every_generations,
+            every,
+            last_generation: generation,
+            last_time: now,
+            changed: false,
+        }
+    }
+
+    /// Call when the universe was changed other than by calculating a generation.
+    pub fn mark_changed(&mut self) {
+        self.changed = true;
+    }
+
+    pub fn is_due(&self, generation: usize, now: Instant) -> bool {
+        let changed = self.changed || generation != self.last_generation;
+        if !changed {
+            return false;
+        }
+        let gens_due = self.every_generations > 0 && generation >= self.last_generation + self.every_generations;
+        let time_due = self.every > Duration::from_secs(0) && now.duration_since(self.last_time) >= self.every;
+        gens_due || time_due
+    }
+
+    pub fn mark_saved(&mut self, generation: usize, now: Instant) {
+        self.last_generation = generation;
+        self.last_time = now;
+        self.changed = false;
+    }
+}
+
+enum AutosaveRequest {
+    Write(Autosave),
+    Remove,
+}
+
+/// Writes autosaves on a worker thread so that the game loop never waits on the disk.
+pub struct Autosaver {
+    path:   PathBuf,
+    sender: Option<Sender<AutosaveRequest>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl Autosaver {
+    pub fn new(path: PathBuf) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let worker_path = path.clone();
+        let worker = thread::spawn(move || autosave_worker(worker_path, receiver));
+        Autosaver {
+            path,
+            sender: Some(sender),
+            worker: Some(worker),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Queues `autosave` to be written. If the worker falls behind, only the latest snapshot is
+    /// written.
+    pub fn save(&self, autosave: Autosave) {
+        self.send(AutosaveRequest::Write(autosave));
+    }
+
+    /// Queues removal of the autosave file, after any writes already queued.
+    pub fn remove(&self) {
+        self.send(AutosaveRequest::Remove);
+    }
+
+    fn send(&self, request: AutosaveRequest) {
+        if let Some(sender) = self.sender.as_ref() {
+            if sender.send(request).is_err() {
+                error!("Autosave worker for {:?} has stopped", self.path);
+            }
+        }
+    }
+}
+
+impl Drop for Autosaver {
+    /// Waits for queued requests to finish, so the last snapshot isn't lost on a clean exit.
+    fn drop(&mut self) {
+        self.sender = None; // hang up, so the worker exits once the queue is empty
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn autosave_worker(path: PathBuf, receiver: Receiver<AutosaveRequest>) {
+    while let Ok(first) = receiver.recv() {
+        let mut requests = vec![first];
+        requests.extend(receiver.try_iter());
+        // Only the latest snapshot is worth writing
+        let latest_write = requests
+            .iter()
+            .rposition(|request| matches!(request, AutosaveRequest::Write(_)));
+        for (i, request) in requests.into_iter().enumerate() {
+            match request {
+                AutosaveRequest::Write(autosave) if Some(i) == latest_write => {
+                    if let Err(e) = autosave.write_to_file(&path) {
+                        error!("Failed to autosave to {:?}: {}", path, e);
+                    } else {
+                        debug!("Autosaved generation {} to {:?}", autosave.generation, path);
+                    }
+                }
+                AutosaveRequest::Write(_) => {}
+                AutosaveRequest::Remove => match fs::remove_file(&path) {
+                    Ok(()) => info!("Removed autosave {:?}", path),
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                    Err(e) => error!("Failed to remove autosave {:?}: {}", path, e),
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::{live_cells, test_dir};
+    use conway::universe::{BigBang, CellState, PlayerBuilder, Region};
+
+    const CAMERA: Camera = Camera {
+        cell_size: 10.0,
+        origin_x:  -20.0,
+        origin_y:  5.0,
+    };
+
+    fn bigbang(server_mode: bool) -> BigBang {
+        BigBang::new()
+            .width(128)
+            .height(64)
+            .server_mode(server_mode)
+            .history(4)
+            .fog_radius(4)
+            .add_players(vec![
+                PlayerBuilder::new(Region::new(0, 0, 64, 64)),
+                PlayerBuilder::new(Region::new(64, 0, 64, 64)),
+            ])
+    }
+
+    fn make_universe() -> Universe {
+        let mut uni = bigbang(true).birth().unwrap();
+        // glider for player 0, blinker for player 1, and an unowned block
+        for &(col, row) in [(11, 10), (12, 11), (10, 12), (11, 12), (12, 12)].iter() {
+            uni.toggle(col, row, 0).unwrap();
+        }
+        for &(col, row) in [(80, 30), (81, 30), (82, 30)].iter() {
+            uni.toggle(col, row, 1).unwrap();
+        }
+        for &(col, row) in [(40, 50), (41, 50), (40, 51), (41, 51)].iter() {
+            uni.set_unchecked(col, row, CellState::Alive(None));
+        }
+        uni
+    }
+
+    fn save_at(generation: usize) -> Autosave {
+        let mut uni = make_universe();
+        while uni.latest_gen() < generation {
+            uni.next();
+        }
+        Autosave::from_universe(&uni, CAMERA)
+    }
+
+    #[test]
+    fn test_resume_has_identical_live_cells() {
+        let mut uni = make_universe();
+        for _ in 0..7 {
+            uni.next();
+        }
+        let path = test_dir("autosave", "resume").join("autosave.bin");
+        Autosave::from_universe(&uni, CAMERA).write_to_file(&path).unwrap();
+
+        let save = Autosave::read_from_file(&path).unwrap();
+        assert_eq!(save.camera, CAMERA);
+        let mut resumed = bigbang(false).birth().unwrap();
+        save.restore(&mut resumed).unwrap();
+        assert_eq!(resumed.latest_gen(), uni.latest_gen());
+        assert_eq!(live_cells(&resumed), live_cells(&uni));
+
+        // and the game carries on the same way
+        for _ in 0..5 {
+            uni.next();
+            resumed.next();
+        }
+        assert_eq!(live_cells(&resumed), live_cells(&uni));
+    }
+
+    #[test]
+    fn test_resume_first_generation() {
+        let uni = make_universe();
+        let save = Autosave::from_universe(&uni, CAMERA);
+        let mut resumed = bigbang(false).birth().unwrap();
+        save.restore(&mut resumed).unwrap();
+        assert_eq!(resumed.latest_gen(), 1);
+        assert_eq!(live_cells(&resumed), live_cells(&uni));
+    }
+
+    #[test]
+    fn test_resume_into_used_universe_fails() {
+        let save = save_at(1);
+        let mut uni = bigbang(true).birth().unwrap();
+        assert!(save.restore(&mut uni).is_err());
+    }
+
+    #[test]
+    fn test_write_replaces_previous_autosave() {
+        let path = test_dir("autosave", "replace").join("autosave.bin");
+        save_at(3).write_to_file(&path).unwrap();
+        save_at(9).write_to_file(&path).unwrap();
+        assert_eq!(Autosave::read_from_file(&path).unwrap(), save_at(9));
+        assert!(!temp_path(&path).exists());
+    }
+
+    #[test]
+    fn test_interrupted_write_keeps_previous_autosave() {
+        let path = test_dir("autosave", "interrupted").join("autosave.bin");
+        save_at(3).write_to_file(&path).unwrap();
+
+        // a crash partway through writing leaves a truncated temporary file behind
+        fs::write(temp_path(&path), &[1, 0, 0]).unwrap();
+        assert_eq!(Autosave::read_from_file(&path).unwrap(), save_at(3));
+
+        // which the next autosave simply overwrites
+        save_at(5).write_to_file(&path).unwrap();
+        assert_eq!(Autosave::read_from_file(&path).unwrap(), save_at(5));
+    }
+
+    #[test]
+    fn test_unsupported_version() {
+        let path = test_dir("autosave", "version").join("autosave.bin");
+        fs::write(&path, bincode::serialize(&(AUTOSAVE_FORMAT_VERSION + 1)).unwrap()).unwrap();
+        match Autosave::read_from_file(&path) {
+            Err(AutosaveError::UnsupportedVersion { found }) => assert_eq!(found, AUTOSAVE_FORMAT_VERSION + 1),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_autosaver_writes_latest_and_removes() {
+        let path = test_dir("autosave", "worker").join("autosave.bin");
+        {
+            let autosaver = Autosaver::new(path.clone());
+            autosaver.save(save_at(2));
+            autosaver.save(save_at(4));
+        }
+        assert_eq!(Autosave::read_from_file(&path).unwrap(), save_at(4));
+
+        {
+            let autosaver = Autosaver::new(path.clone());
+            autosaver.remove();
+        }
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_schedule_by_generation() {
+        let now = Instant::now();
+        let mut schedule = AutosaveSchedule::new(100, Duration::from_secs(0), 1, now);
+        assert!(!schedule.is_due(100, now));
+        assert!(schedule.is_due(101, now));
+        schedule.mark_saved(101, now);
+        assert!(!schedule.is_due(101, now));
+        assert!(schedule.is_due(201, now));
+    }
+
+    #[test]
+    fn test_schedule_by_time_only_when_changed() {
+        let now = Instant::now();
+        let later = now + Duration::from_secs(60);
+        let mut schedule = AutosaveSchedule::new(0, Duration::from_secs(60), 1, now);
+        assert!(!schedule.is_due(1, later)); // nothing changed
+        schedule.mark_changed();
+        assert!(!schedule.is_due(1, now + Duration::from_secs(59)));
+        assert!(schedule.is_due(1, later));
+        schedule.mark_saved(1, later);
+        assert!(!schedule.is_due(1, later + Duration::from_secs(60)));
+        assert!(schedule.is_due(2, later + Duration::from_secs(60)));
+    }
+}
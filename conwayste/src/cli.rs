@@ -0,0 +1,376 @@
+/*  Copyright 2021 the Conwayste Developers.
+ *
+ *  This file is part of conwayste.
+ *
+ *  conwayste is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  conwayste is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with conwayste.  If not, see
+ *  <http://www.gnu.org/licenses/>. */
+
+//! Command line options for the client.
+//!
+//! Options that correspond to a config setting override it for this run only, unless
+//! `--save-config` is also given.
+
+use std::net::Ipv6Addr;
+use std::path::PathBuf;
+
+use crate::config::{Config, Settings};
+
+pub const USAGE: &str = "\
+Usage: conwayste [OPTIONS]
+
+Options:
+    --server <host:port>   connect to this server right away; IPv6 addresses with a port go in
+                           brackets, like [::1]:2016
+    --name <name>          player name
+    --width <pixels>       window width
+    --height <pixels>      window height
+    --fullscreen           start in fullscreen
+    --load <file.rle>      pick up this pattern, ready to be placed in the game
+    --record <file>        record the single player game to a replay file
+    --replay <file>        play back a replay file
+    --save-config          also save the options above to the config file
+    -h, --help             print this message";
+
+custom_error! {pub CliError
+    UnknownOption {option: String} = "unknown option '{option}'",
+    MissingValue {option: String} = "{option} requires a value",
+    InvalidValue {option: String, value: String, reason: String} = "invalid value '{value}' for {option}: {reason}",
+    Conflict {option: String, other: String} = "{option} cannot be used with {other}",
+}
+
+/// What to do with the single-player session, as requested on the command line.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SessionReplay {
+    Record(PathBuf), // write all input to a new replay file
+    Play(PathBuf),   // play back an existing replay file instead of taking input
+}
+
+/// The options given on the command line. Anything not given is `None` or `false`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CliOptions {
+    pub server:         Option<String>, // host name or address, optionally followed by a port
+    pub name:           Option<String>,
+    pub width:          Option<u32>,
+    pub height:         Option<u32>,
+    pub fullscreen:     bool,
+    pub load:           Option<PathBuf>, // RLE file
+    pub session_replay: Option<SessionReplay>,
+    pub save_config:    bool,
+    pub help:           bool,
+}
+
+impl CliOptions {
+    /// Parses `args`, which should not include the program name.
+    pub fn parse<I>(args: I) -> Result<CliOptions, CliError>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut opts = CliOptions::default();
+        let mut args = args.into_iter();
+        while let Some(option) = args.next() {
+            match option.as_str() {
+                "-h" | "--help" => opts.help = true,
+                "--fullscreen" => opts.fullscreen = true,
+                "--save-config" => opts.save_config = true,
+                "--server" => opts.server = Some(parse_server(&option, next_value(&option, &mut args)?)?),
+                "--name" => {
+                    let name = next_value(&option, &mut args)?;
+                    if name.trim().is_empty() {
+                        return Err(invalid_value(&option, name, "must not be blank"));
+                    }
+                    opts.name = Some(name);
+                }
+                "--width" => opts.width = Some(parse_dimension(&option, next_value(&option, &mut args)?)?),
+                "--height" => opts.height = Some(parse_dimension(&option, next_value(&option, &mut args)?)?),
+                "--load" => opts.load = Some(next_value(&option, &mut args)?.into()),
+                "--record" => {
+                    let path = next_value(&option, &mut args)?.into();
+                    opts.set_session_replay(SessionReplay::Record(path))?;
+                }
+                "--replay" => {
+                    let path = next_value(&option, &mut args)?.into();
+                    opts.set_session_replay(SessionReplay::Play(path))?;
+                }
+                _ => return Err(CliError::UnknownOption { option }),
+            }
+        }
+
+        // Playing back a replay replaces the single player game, so nothing else can go in it
+        if let Some(SessionReplay::Play(_)) = opts.session_replay {
+            if opts.server.is_some() {
+                return Err(conflict("--replay", "--server"));
+            }
+            if opts.load.is_some() {
+                return Err(conflict("--replay", "--load"));
+            }
+        }
+        Ok(opts)
+    }
+
+    fn set_session_replay(&mut self, session_replay: SessionReplay) -> Result<(), CliError> {
+        if let Some(ref existing) = self.session_replay {
+            return Err(conflict(replay_option(&session_replay), replay_option(existing)));
+        }
+        self.session_replay = Some(session_replay);
+        Ok(())
+    }
+
+    /// Applies the options that correspond to config settings. These are only saved to disk if
+    /// `--save-config` was given.
+    pub fn apply_to(&self, config: &mut Config) {
+        let apply = |settings: &mut Settings| {
+            if let Some(ref server) = self.server {
+                settings.user.server = server.clone();
+            }
+            if let Some(ref name) = self.name {
+                settings.user.name = name.clone();
+            }
+            if let Some(width) = self.width {
+                settings.video.resolution_x = width as f32;
+            }
+            if let Some(height) = self.height {
+                settings.video.resolution_y = height as f32;
+            }
+            if self.fullscreen {
+                settings.video.fullscreen = true;
+            }
+        };
+        if self.save_config {
+            config.modify(apply);
+        } else {
+            config.override_for_run(apply);
+        }
+    }
+}
+
+/// Takes the value following `option`. Another option in its place counts as missing.
+fn next_value<I>(option: &str, args: &mut I) -> Result<String, CliError>
+where
+    I: Iterator<Item = String>,
+{
+    match args.next() {
+        Some(value) if !value.starts_with("--") => Ok(value),
+        _ => Err(CliError::MissingValue {
+            option: option.to_owned(),
+        }),
+    }
+}
+
+fn parse_server(option: &str, value: String) -> Result<String, CliError> {
+    // A bare IPv6 address has no port; with one, it has to be in brackets
+    if value.parse::<Ipv6Addr>().is_ok() {
+        return Ok(value);
+    }
+    let host = match value.rfind(':') {
+        Some(i) => {
+            let port = &value[i + 1..];
+            match port.parse::<u16>() {
+                Ok(p) if p != 0 => {}
+                _ => {
+                    return Err(invalid_value(
+                        option,
+                        value.clone(),
+                        "port must be a number from 1 to 65535",
+                    ))
+                }
+            }
+            &value[..i]
+        }
+        None => value.as_str(),
+    };
+    if host.is_empty() {
+        return Err(invalid_value(option, value.clone(), "missing host"));
+    }
+    Ok(value)
+}
+
+fn parse_dimension(option: &str, value: String) -> Result<u32, CliError> {
+    match value.parse::<u32>() {
+        Ok(pixels) if pixels > 0 => Ok(pixels),
+        _ => Err(invalid_value(option, value, "must be a positive number of pixels")),
+    }
+}
+
+fn replay_option(session_replay: &SessionReplay) -> &'static str {
+    match session_replay {
+        SessionReplay::Record(_) => "--record",
+        SessionReplay::Play(_) => "--replay",
+    }
+}
+
+fn invalid_value(option: &str, value: String, reason: &str) -> CliError {
+    CliError::InvalidValue {
+        option: option.to_owned(),
+        value,
+        reason: reason.to_owned(),
+    }
+}
+
+fn conflict(option: &str, other: &str) -> CliError {
+    CliError::Conflict {
+        option: option.to_owned(),
+        other:  other.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<CliOptions, CliError> {
+        CliOptions::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_parse_nothing() {
+        assert_eq!(parse(&[]).unwrap(), CliOptions::default());
+    }
+
+    #[test]
+    fn test_parse_everything() {
+        let opts = parse(&[
+            "--server",
+            "example.com:2016",
+            "--name",
+            "Gosper",
+            "--width",
+            "1280",
+            "--height",
+            "720",
+            "--fullscreen",
+            "--load",
+            "glider.rle",
+            "--record",
+            "session.replay",
+            "--save-config",
+        ])
+        .unwrap();
+        assert_eq!(opts.server, Some("example.com:2016".to_owned()));
+        assert_eq!(opts.name, Some("Gosper".to_owned()));
+        assert_eq!((opts.width, opts.height), (Some(1280), Some(720)));
+        assert!(opts.fullscreen);
+        assert_eq!(opts.load, Some(PathBuf::from("glider.rle")));
+        assert_eq!(
+            opts.session_replay,
+            Some(SessionReplay::Record(PathBuf::from("session.replay")))
+        );
+        assert!(opts.save_config);
+        assert!(!opts.help);
+    }
+
+    #[test]
+    fn test_parse_server_without_port() {
+        assert_eq!(
+            parse(&["--server", "localhost"]).unwrap().server,
+            Some("localhost".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_parse_server_ipv6() {
+        for server in &["::1", "2001:db8::7", "[::1]:2016"] {
+            assert_eq!(parse(&["--server", server]).unwrap().server, Some(server.to_string()));
+        }
+    }
+
+    #[test]
+    fn test_parse_bad_ports() {
+        for server in &[
+            "localhost:",
+            "localhost:0",
+            "localhost:65536",
+            "localhost:http",
+            ":2016",
+        ] {
+            match parse(&["--server", server]) {
+                Err(CliError::InvalidValue { option, value, .. }) => {
+                    assert_eq!(option, "--server");
+                    assert_eq!(&value, server);
+                }
+                other => panic!("expected an invalid value for {:?} but got {:?}", server, other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_missing_values() {
+        for option in &[
+            "--server", "--name", "--width", "--height", "--load", "--record", "--replay",
+        ] {
+            match parse(&[option]) {
+                Err(CliError::MissingValue { option: missing }) => assert_eq!(&missing, option),
+                other => panic!("expected a missing value for {} but got {:?}", option, other),
+            }
+        }
+        match parse(&["--name", "--fullscreen"]) {
+            Err(CliError::MissingValue { option }) => assert_eq!(option, "--name"),
+            other => panic!("expected a missing value but got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_bad_dimensions() {
+        assert!(matches!(parse(&["--width", "0"]), Err(CliError::InvalidValue { .. })));
+        assert!(matches!(parse(&["--height", "-5"]), Err(CliError::InvalidValue { .. })));
+        assert!(matches!(
+            parse(&["--width", "wide"]),
+            Err(CliError::InvalidValue { .. })
+        ));
+    }
+
+    #[test]
+    fn test_parse_conflicts() {
+        match parse(&["--replay", "a.replay", "--server", "localhost:2016"]) {
+            Err(CliError::Conflict { option, other }) => {
+                assert_eq!((option.as_str(), other.as_str()), ("--replay", "--server"))
+            }
+            other => panic!("expected a conflict but got {:?}", other),
+        }
+        assert!(matches!(
+            parse(&["--load", "glider.rle", "--replay", "a.replay"]),
+            Err(CliError::Conflict { .. })
+        ));
+        assert!(matches!(
+            parse(&["--record", "a.replay", "--replay", "b.replay"]),
+            Err(CliError::Conflict { .. })
+        ));
+        // recording a single player game doesn't get in the way of anything else
+        assert!(parse(&["--record", "a.replay", "--server", "localhost:2016"]).is_ok());
+    }
+
+    #[test]
+    fn test_parse_unknown_option() {
+        match parse(&["--fulscreen"]) {
+            Err(CliError::UnknownOption { option }) => assert_eq!(option, "--fulscreen"),
+            other => panic!("expected an unknown option but got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_apply_to_config() {
+        let opts = parse(&["--name", "Gosper", "--width", "1280", "--fullscreen"]).unwrap();
+        let mut config = Config::new();
+        opts.apply_to(&mut config);
+        assert_eq!(config.get().user.name, "Gosper");
+        assert_eq!(config.get_resolution().0, 1280.0);
+        assert!(config.get().video.fullscreen);
+        assert!(!config.is_dirty()); // only for this run
+
+        let opts = parse(&["--name", "Gosper", "--save-config"]).unwrap();
+        let mut config = Config::new();
+        opts.apply_to(&mut config);
+        assert_eq!(config.get().user.name, "Gosper");
+        assert!(config.is_dirty());
+    }
+}
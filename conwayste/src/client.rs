@@ -42,12 +42,22 @@ extern crate rand;
 extern crate lazy_static;
 extern crate chromatica;
 
+mod autosave;
+mod cli;
 mod config;
 mod constants;
 #[macro_use]
 mod error;
+mod export;
+mod history;
 mod input;
+mod keybindings;
 mod network;
+mod prediction;
+mod replay;
+#[cfg(test)]
+mod test_util;
+mod theme;
 mod ui;
 mod uilayout;
 mod video;
@@ -57,8 +67,9 @@ use chrono::Local;
 use log::LevelFilter;
 
 use conway::grids::CharGrid;
+use conway::rle::PatternFile;
 use conway::universe::{BigBang, CellState, PlayerBuilder, Region, Universe};
-use netwayste::net::NetwaysteEvent;
+use netwayste::net::{ConnectionStatus, NetwaysteEvent, VERSION as NETWAYSTE_VERSION};
 
 use ggez::conf;
 use ggez::event::*;
@@ -67,25 +78,26 @@ use ggez::mint::{Point2, Vector2};
 use ggez::timer;
 use ggez::{Context, ContextBuilder, GameError, GameResult};
 
-use rand::Rng;
-
-use std::collections::BTreeMap;
 use std::env;
 use std::error::Error;
-use std::io::Write; // For env logger
+use std::fs::{self, File};
+use std::io::{self, BufReader, Write}; // Write for env logger
 use std::path;
 use std::sync::{Arc, Mutex};
 
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
+use autosave::{Autosave, AutosaveError, Autosaver};
+use cli::{CliOptions, SessionReplay};
 use constants::{
-    colors::*, DrawStyle, DEFAULT_SCREEN_HEIGHT, DEFAULT_SCREEN_WIDTH, DEFAULT_ZOOM_LEVEL, GRID_DRAW_STYLE,
-    INTRO_DURATION, INTRO_PAUSE_DURATION,
+    colors::*, AUTOSAVE_FILE_PATH, CONNECTION_ICON_MARGIN, CONNECTION_ICON_RADIUS, DEFAULT_SCREEN_HEIGHT,
+    DEFAULT_SCREEN_WIDTH, DEFAULT_ZOOM_LEVEL, GRID_DRAW_STYLE, INTRO_DURATION, INTRO_PAUSE_DURATION,
 };
 use input::{MouseAction, ScrollEvent};
 use ui::{
     context::{EmitEvent, Event, Handled, Handler, UIContext},
-    Chatbox, ChatboxPublishHandle, EventType, GameArea, GameAreaState, TextField,
+    ChatEntry, ChatHistory, Chatbox, ChatboxPublishHandle, ColorSettings, EventType, GameArea, GameAreaState, Minimap,
+    ServerList, ServerListEntry, Severity, TextField, Toasts, Widget,
 };
 use uilayout::{StaticNodeIds, UILayout};
 
@@ -96,8 +108,10 @@ pub enum Screen {
     Options,
     ServerList,
     InRoom,
-    Run,  // TODO: break it out more to indicate whether waiting for game or playing game
-    Exit, // We're getting ready to quit the game, WRAP IT UP SON
+    Run,         // TODO: break it out more to indicate whether waiting for game or playing game
+    InGameMenu,  // Shown over the paused game; Escape returns to it
+    QuitConfirm, // Shown over the main menu when going back from it
+    Exit,        // We're getting ready to quit the game, WRAP IT UP SON
 }
 
 // All game state
@@ -110,10 +124,12 @@ struct MainState {
     uni_draw_params:    UniDrawParams,
     video_settings:     video::VideoSettings,
     config:             config::Config,
-    viewport:           viewport::GridView,
+    theme_watcher:      theme::ThemeWatcher, // switches themes when the config's theme settings change
     intro_viewport:     viewport::GridView,
     inputs:             input::InputManager,
     net_worker:         Arc<Mutex<Option<network::ConwaysteNetWorker>>>,
+    // As last heard from netwayste; shown on the Run screen
+    connection_status:  ConnectionStatus,
     recvd_first_resize: bool, // work around an apparent ggez bug where the first resize event is bogus
 
     // if Some(...), dragging doesn't draw anything
@@ -121,36 +137,22 @@ struct MainState {
 
     ui_layout:       UILayout,
     static_node_ids: StaticNodeIds,
-}
 
-// Support non-alive/dead/bg colors
-struct ColorSettings {
-    cell_colors: BTreeMap<CellState, Color>,
-    background:  Color,
-}
+    // An autosave found at startup, which is resumed or discarded when the game is started
+    pending_autosave:          Option<Autosave>,
+    resume_autosave_requested: Arc<Mutex<bool>>, // set by the main menu's resume button
 
-impl ColorSettings {
-    fn get_color(&self, cell_or_none: Option<CellState>) -> Color {
-        match cell_or_none {
-            Some(cell) => self.cell_colors[&cell],
-            None => self.background,
-        }
-    }
+    // Every chat message, kept here so that it can be put back into the Chatbox when the Run
+    // screen is shown again
+    chat_history: Arc<Mutex<ChatHistory>>,
 
-    fn get_random_color(&self) -> Color {
-        let mut colors = vec![1.0, 2.0, 3.0];
-        let mut rng = rand::thread_rng();
-
-        for x in colors.iter_mut() {
-            *x = rng.gen_range(0.0..1.0);
-        }
-        let mut iter = colors.into_iter();
-        Color::new(iter.next().unwrap(), iter.next().unwrap(), iter.next().unwrap(), 1.0)
-    }
+    // Drawn over every screen
+    toasts: Toasts,
 }
 
 fn get_text_entered_handler(
     mut chatbox_pub_handle: ChatboxPublishHandle,
+    chat_history: Arc<Mutex<ChatHistory>>,
     net_worker: Arc<Mutex<Option<network::ConwaysteNetWorker>>>,
 ) -> Handler {
     Box::new(
@@ -160,8 +162,9 @@ fn get_text_entered_handler(
             if text.is_empty() {
                 return Ok(Handled::NotHandled);
             }
-            let msg = format!("{}: {}", username, text);
+            let msg = ChatEntry::chat(username, text.clone());
 
+            chat_history.lock().unwrap().push(msg.clone());
             chatbox_pub_handle.add_message(msg);
 
             if let Some(ref mut netwayste) = *(net_worker.lock().unwrap()) {
@@ -172,6 +175,32 @@ fn get_text_entered_handler(
     )
 }
 
+/// Returns a handler for the main menu button that resumes the autosaved game.
+fn get_resume_autosave_handler(resume_autosave_requested: Arc<Mutex<bool>>) -> Handler {
+    Box::new(
+        move |_obj: &mut dyn EmitEvent, uictx: &mut UIContext, _evt: &Event| -> Result<Handled, Box<dyn Error>> {
+            *resume_autosave_requested.lock().unwrap() = true;
+            uictx.push_screen(Screen::Run);
+            Ok(Handled::Handled)
+        },
+    )
+}
+
+/// Reads the autosave left behind by a previous session, if there is one.
+fn load_autosave(path: &path::Path) -> Option<Autosave> {
+    match Autosave::read_from_file(path) {
+        Ok(save) => {
+            info!("Found autosave of generation {} at {:?}", save.generation, path);
+            Some(save)
+        }
+        Err(AutosaveError::Io(ref e)) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => {
+            warn!("Ignoring unreadable autosave {:?}: {}", path, e);
+            None
+        }
+    }
+}
+
 // Then we implement the `ggez::game::GameState` trait on it, which
 // requires callbacks for creating the game state, updating it each
 // frame, and drawing it.
@@ -179,12 +208,23 @@ fn get_text_entered_handler(
 // The `GameState` trait also contains callbacks for event handling
 // that you can override if you wish, but the defaults are fine.
 impl MainState {
-    fn new(ctx: &mut Context) -> GameResult<MainState> {
+    fn new(ctx: &mut Context, cli_options: CliOptions) -> GameResult<MainState> {
         let mut config = config::Config::new();
         config.load_or_create_default().map_err(|e| {
             let msg = format!("Error while loading config: {:?}", e);
-            GameError::FilesystemError(msg)
+            GameError::ConfigError(msg)
         })?;
+        cli_options.apply_to(&mut config);
+        if cli_options.save_config {
+            config.force_flush().map_err(|e| {
+                let msg = format!("Error while saving config: {:?}", e);
+                GameError::ConfigError(msg)
+            })?;
+        }
+        let mut theme_watcher = theme::ThemeWatcher::default();
+        if let Some(theme) = theme_watcher.changed(config.get()) {
+            theme::set_current(theme);
+        }
 
         let mut vs = video::VideoSettings::new();
         graphics::set_resizable(ctx, true)?;
@@ -203,40 +243,7 @@ impl MainState {
             constants::INTRO_UNIVERSE_HEIGHT_IN_CELLS,
         );
 
-        let viewport = viewport::GridView::new(
-            config.get().gameplay.zoom,
-            constants::UNIVERSE_WIDTH_IN_CELLS,
-            constants::UNIVERSE_HEIGHT_IN_CELLS,
-        );
-
-        let mut color_settings = ColorSettings {
-            cell_colors: BTreeMap::new(),
-            background:  *UNIVERSE_BG_COLOR,
-        };
-        color_settings
-            .cell_colors
-            .insert(CellState::Dead, *CELL_STATE_DEAD_COLOR);
-        if GRID_DRAW_STYLE == DrawStyle::Line {
-            // black background - for a "tetris-like" effect
-            color_settings
-                .cell_colors
-                .insert(CellState::Alive(None), *CELL_STATE_BG_FILL_HOLLOW_COLOR);
-        } else {
-            // light background - default setting
-            color_settings
-                .cell_colors
-                .insert(CellState::Alive(None), *CELL_STATE_BG_FILL_SOLID_COLOR);
-        }
-        color_settings
-            .cell_colors
-            .insert(CellState::Alive(Some(0)), *CELL_STATE_ALIVE_PLAYER_0_COLOR); // 0 is red
-        color_settings
-            .cell_colors
-            .insert(CellState::Alive(Some(1)), *CELL_STATE_ALIVE_PLAYER_1_COLOR); // 1 is blue
-        color_settings
-            .cell_colors
-            .insert(CellState::Wall, *CELL_STATE_WALL_COLOR);
-        color_settings.cell_colors.insert(CellState::Fog, *CELL_STATE_FOG_COLOR);
+        let color_settings = ColorSettings::from_theme(&theme::current());
 
         // Note: fixed-width fonts are required!
         let font = Font::new(ctx, path::Path::new("/telegrama_render.ttf"))
@@ -252,13 +259,69 @@ impl MainState {
                 .birth()
         };
 
-        let mut config = config::Config::new();
-        config.load_or_create_default().map_err(|e| {
-            let msg = format!("Error while loading config: {:?}", e);
-            GameError::ConfigError(msg)
-        })?;
+        // Playing back a replay must not touch the autosave
+        let is_playback = matches!(cli_options.session_replay, Some(SessionReplay::Play(_)));
+        let autosave_path = path::PathBuf::from(AUTOSAVE_FILE_PATH);
+        let pending_autosave = if is_playback {
+            None
+        } else {
+            load_autosave(&autosave_path)
+        };
+        let resume_autosave_requested = Arc::new(Mutex::new(false));
+        let resume_autosave_handler = pending_autosave
+            .as_ref()
+            .map(|_| get_resume_autosave_handler(resume_autosave_requested.clone()));
 
-        let (mut ui_layout, static_node_ids) = UILayout::new(ctx, &config, font.clone()).unwrap(); // TODO: unwrap not OK!
+        let (mut ui_layout, static_node_ids) =
+            UILayout::new(ctx, &config, font.clone(), resume_autosave_handler).unwrap(); // TODO: unwrap not OK!
+
+        let toasts = Toasts::new(ui::common::FontInfo::new(ctx, font.clone(), None));
+        ui_layout.set_notifier(&toasts.notifier());
+
+        {
+            let keybindings = keybindings::KeyBindings::from_settings(&config.get().keybindings);
+            let game_area_id = static_node_ids.game_area_id.clone();
+            let game_area = GameArea::widget_from_screen_and_id_mut(&mut ui_layout, Screen::Run, &game_area_id)
+                .map_err(|e| GameError::ResourceLoadError(format!("Could not find the game area: {:?}", e)))?;
+            game_area.set_keybindings(keybindings);
+        }
+
+        if !is_playback {
+            let gameplay = &config.get().gameplay;
+            let game_area_id = static_node_ids.game_area_id.clone();
+            let game_area = GameArea::widget_from_screen_and_id_mut(&mut ui_layout, Screen::Run, &game_area_id)
+                .map_err(|e| GameError::ResourceLoadError(format!("Could not find the game area: {:?}", e)))?;
+            game_area.start_autosaving(
+                Autosaver::new(autosave_path),
+                gameplay.autosave_generations,
+                Duration::from_secs(gameplay.autosave_seconds),
+            );
+        }
+
+        if let Some(ref pattern_path) = cli_options.load {
+            let game_area_id = static_node_ids.game_area_id.clone();
+            let game_area = GameArea::widget_from_screen_and_id_mut(&mut ui_layout, Screen::Run, &game_area_id)
+                .map_err(|e| GameError::ResourceLoadError(format!("Could not find the game area: {:?}", e)))?;
+            let result: Result<(), Box<dyn Error>> = fs::read_to_string(pattern_path)
+                .map_err(|e| e.into())
+                .and_then(|contents| Ok(contents.parse::<PatternFile>()?))
+                .and_then(|pattern| Ok(game_area.start_inserting(&pattern)?));
+            result.map_err(|e| GameError::ResourceLoadError(format!("Could not load {:?}: {}", pattern_path, e)))?;
+        }
+
+        if let Some(session_replay) = cli_options.session_replay.clone() {
+            let game_area_id = static_node_ids.game_area_id.clone();
+            let game_area = GameArea::widget_from_screen_and_id_mut(&mut ui_layout, Screen::Run, &game_area_id)
+                .map_err(|e| GameError::ResourceLoadError(format!("Could not find the game area: {:?}", e)))?;
+            let result: Result<(), Box<dyn Error>> = match session_replay {
+                SessionReplay::Record(path) => game_area.start_recording(&path),
+                SessionReplay::Play(path) => File::open(&path)
+                    .map_err(|e| e.into())
+                    .and_then(|f| Ok(replay::Replay::read_from(BufReader::new(f))?))
+                    .and_then(|r| game_area.start_playback(r)),
+            };
+            result.map_err(|e| GameError::FilesystemError(format!("Could not set up replay: {}", e)))?;
+        }
 
         // Update universe draw parameters for intro
         let intro_uni_draw_params = UniDrawParams {
@@ -279,10 +342,13 @@ impl MainState {
                 .unwrap()
                 .get_widget_mut(&chatbox_id)
                 .unwrap();
-            let chatbox = w.downcast_ref::<Chatbox>().unwrap(); // unwrap OK because we know this ID is for a Chatbox
+            let chatbox = w.downcast_mut::<Chatbox>().unwrap(); // unwrap OK because we know this ID is for a Chatbox
+            chatbox.set_show_timestamps(config.get().gameplay.chat_timestamps);
             chatbox.new_handle()
         };
-        let text_entered_handler = get_text_entered_handler(chatbox_pub_handle, net_worker.clone());
+        let chat_history = Arc::new(Mutex::new(ChatHistory::new(constants::CHATBOX_HISTORY)));
+        let text_entered_handler =
+            get_text_entered_handler(chatbox_pub_handle, chat_history.clone(), net_worker.clone());
         {
             let textfield_id = static_node_ids.chatbox_tf_id.clone();
             let w = ui_layout
@@ -301,19 +367,28 @@ impl MainState {
             uni_draw_params: intro_uni_draw_params,
             color_settings: color_settings,
             video_settings: vs,
+            theme_watcher,
             config: config,
-            viewport: viewport,
             intro_viewport: intro_viewport,
             inputs: input::InputManager::new(),
             net_worker,
+            connection_status: ConnectionStatus::Connected,
             recvd_first_resize: false,
             current_intro_duration: 0.0,
             ui_layout: ui_layout,
             static_node_ids: static_node_ids,
+            pending_autosave,
+            resume_autosave_requested,
+            chat_history,
+            toasts,
         };
 
         init_intro_screen(&mut s).unwrap();
 
+        if cli_options.server.is_some() {
+            s.connect_to_server();
+        }
+
         Ok(s)
     }
 }
@@ -322,10 +397,12 @@ impl EventHandler for MainState {
     fn update(&mut self, ctx: &mut Context) -> GameResult<()> {
         let duration = timer::duration_to_f64(timer::delta(ctx)); // seconds
 
-        self.receive_net_updates()?;
-
+        // Captured before network updates, since they can change the screen too
         let screen = self.get_current_screen();
 
+        self.receive_net_updates()?;
+        self.toasts.update(Instant::now());
+
         // Handle special case screens
         // NOTE: each match arm except default must return
         match screen {
@@ -335,6 +412,14 @@ impl EventHandler for MainState {
                     self.screen_stack.pop();
                     self.screen_stack.push(Screen::Menu);
                     self.inputs.key_info.key = None;
+                    let mut game_area_state = self.get_gamearea_state().unwrap_or_else(|e| {
+                        error!("Could not get game area state: {}", e);
+                        GameAreaState::default()
+                    });
+                    self.transition_screen(ctx, Screen::Intro, Screen::Menu, &mut game_area_state)
+                        .unwrap_or_else(|e| {
+                            error!("Failed to transition_screen: {:?}", e);
+                        });
 
                     // update universe draw params now that intro is gone
                     self.uni_draw_params = UniDrawParams {
@@ -361,6 +446,7 @@ impl EventHandler for MainState {
         let key = self.inputs.key_info.key;
         let keymods = self.inputs.key_info.modifier;
         let is_shift = keymods & KeyMods::SHIFT > KeyMods::default();
+        let is_ctrl = keymods & KeyMods::CTRL > KeyMods::default();
         let is_repeating = self.inputs.key_info.repeating;
 
         let mouse_point = self.inputs.mouse_info.position;
@@ -382,7 +468,6 @@ impl EventHandler for MainState {
                     &mut self.screen_stack,
                     &mut game_area_state,
                     &mut self.static_node_ids,
-                    &mut self.viewport,
                 )
                 .unwrap_or_else(|e| {
                     error!("Error from layer.emit on update: {:?}", e);
@@ -403,7 +488,6 @@ impl EventHandler for MainState {
                         &mut self.screen_stack,
                         &mut game_area_state,
                         &mut self.static_node_ids,
-                        &mut self.viewport,
                     )
                     .unwrap_or_else(|e| {
                         error!("Error from layer.emit on mouse move: {:?}", e);
@@ -411,6 +495,22 @@ impl EventHandler for MainState {
                 self.inputs.mouse_info.prev_position = self.inputs.mouse_info.position;
             }
 
+            if let Some(scroll) = self.inputs.mouse_info.scroll_event {
+                let scroll_event = Event::new_scroll(mouse_point, scroll);
+                layer
+                    .emit(
+                        &scroll_event,
+                        ctx,
+                        &mut self.config,
+                        &mut self.screen_stack,
+                        &mut game_area_state,
+                        &mut self.static_node_ids,
+                    )
+                    .unwrap_or_else(|e| {
+                        error!("Error from layer.emit on scroll: {:?}", e);
+                    });
+            }
+
             if let Some(action) = mouse_action {
                 match action {
                     MouseAction::Drag => {
@@ -423,14 +523,18 @@ impl EventHandler for MainState {
                                 &mut self.screen_stack,
                                 &mut game_area_state,
                                 &mut self.static_node_ids,
-                                &mut self.viewport,
                             )
                             .unwrap_or_else(|e| {
                                 error!("Error from layer.emit on left click: {:?}", e);
                             });
                     }
+                    MouseAction::Click if self.toasts.click(mouse_point) => {} // only dismisses the toast
                     MouseAction::Click => {
-                        let click_event = Event::new_click(mouse_point, self.inputs.mouse_info.mousebutton, is_shift);
+                        let click_event = if self.inputs.mouse_info.mousebutton == MouseButton::Right {
+                            Event::new_right_click(mouse_point, is_shift)
+                        } else {
+                            Event::new_click(mouse_point, self.inputs.mouse_info.mousebutton, is_shift)
+                        };
                         layer
                             .emit(
                                 &click_event,
@@ -439,7 +543,6 @@ impl EventHandler for MainState {
                                 &mut self.screen_stack,
                                 &mut game_area_state,
                                 &mut self.static_node_ids,
-                                &mut self.viewport,
                             )
                             .unwrap_or_else(|e| {
                                 error!("Error from layer.emit on left click: {:?}", e);
@@ -456,7 +559,6 @@ impl EventHandler for MainState {
                                 &mut self.screen_stack,
                                 &mut game_area_state,
                                 &mut self.static_node_ids,
-                                &mut self.viewport,
                             )
                             .unwrap_or_else(|e| {
                                 error!("Error from layer.emit on left click: {:?}", e);
@@ -470,7 +572,7 @@ impl EventHandler for MainState {
             }
 
             if let Some(key) = key {
-                let key_event = Event::new_key_press(mouse_point, key, is_shift, is_repeating);
+                let key_event = Event::new_key_press(mouse_point, key, is_shift, is_ctrl, is_repeating);
                 layer
                     .emit(
                         &key_event,
@@ -479,7 +581,6 @@ impl EventHandler for MainState {
                         &mut self.screen_stack,
                         &mut game_area_state,
                         &mut self.static_node_ids,
-                        &mut self.viewport,
                     )
                     .unwrap_or_else(|e| {
                         error!("Error from layer.emit on key press: {:?}", e);
@@ -498,7 +599,6 @@ impl EventHandler for MainState {
                         &mut self.screen_stack,
                         &mut game_area_state,
                         &mut self.static_node_ids,
-                        &mut self.viewport,
                     )
                     .unwrap_or_else(|e| {
                         error!("Error from layer.emit on key press (text input): {:?}", e);
@@ -515,7 +615,6 @@ impl EventHandler for MainState {
                         &mut self.screen_stack,
                         &mut game_area_state,
                         &mut self.static_node_ids,
-                        &mut self.viewport,
                         &game_area_id,
                     )
                     .unwrap_or_else(|e| {
@@ -540,8 +639,17 @@ impl EventHandler for MainState {
 
             if !is_shift {
                 // Arrow keys (but not Shift-<Arrow>!) move the player's view of the universe around
-                self.viewport.update(game_area_state.arrow_input);
+                let arrow_input = game_area_state.arrow_input;
+                self.modify_game_area(Box::new(move |game_area| {
+                    game_area.view.update(arrow_input);
+                }));
             }
+
+            self.update_minimap(game_area_state.show_minimap);
+        }
+
+        if screen == Screen::ServerList {
+            self.update_server_list();
         }
 
         // Event processing may have updated the state of the current screen
@@ -558,13 +666,20 @@ impl EventHandler for MainState {
             self.video_settings.update_fullscreen(ctx)?;
         }
 
+        // Likewise for the theme; the game area picks up the new cell colors when it next draws
+        if let Some(theme) = self.theme_watcher.changed(self.config.get()) {
+            self.color_settings = ColorSettings::from_theme(&theme);
+            theme::set_current(theme);
+        }
+
         self.post_update()?;
 
         Ok(())
     }
 
     fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
-        graphics::clear(ctx, [0.0, 0.0, 0.0, 1.0].into());
+        let theme = theme::current();
+        graphics::clear(ctx, theme.window_background);
 
         let current_screen = self.get_current_screen();
 
@@ -579,21 +694,54 @@ impl EventHandler for MainState {
                 ui::draw_text(
                     ctx,
                     self.system_font.clone(),
-                    *MENU_TEXT_COLOR,
+                    theme.menu_text,
                     String::from("Main Menu"),
                     &Point2 { x: 500.0, y: 100.0 },
                 )?;
             }
-            Screen::Run => {
-                self.draw_universe(ctx).unwrap_or_else(|e| {
-                    error!("Error from draw_universe: {}", e);
-                });
+            Screen::Run => {} // the GameArea widget draws the universe
+            Screen::InGameMenu => {
+                // The game stays on screen, frozen, behind a translucent film
+                if let Some(layering) = self.ui_layout.get_screen_layering_mut(Screen::Run) {
+                    layering.draw(ctx).unwrap_or_else(|e| {
+                        error!("Error received during layering draw of the paused game: {:?}", e);
+                    });
+                }
+                let film = graphics::Mesh::new_rectangle(
+                    ctx,
+                    graphics::DrawMode::fill(),
+                    graphics::screen_coordinates(ctx),
+                    *INGAME_MENU_FILM_COLOR,
+                )?;
+                graphics::draw(ctx, &film, DrawParam::default())?;
+            }
+            Screen::QuitConfirm => {
+                // The main menu stays on screen behind a translucent film
+                ui::draw_text(
+                    ctx,
+                    self.system_font.clone(),
+                    theme.menu_text,
+                    String::from("Main Menu"),
+                    &Point2 { x: 500.0, y: 100.0 },
+                )?;
+                if let Some(layering) = self.ui_layout.get_screen_layering_mut(Screen::Menu) {
+                    layering.draw(ctx).unwrap_or_else(|e| {
+                        error!("Error received during layering draw of the main menu: {:?}", e);
+                    });
+                }
+                let film = graphics::Mesh::new_rectangle(
+                    ctx,
+                    graphics::DrawMode::fill(),
+                    graphics::screen_coordinates(ctx),
+                    *INGAME_MENU_FILM_COLOR,
+                )?;
+                graphics::draw(ctx, &film, DrawParam::default())?;
             }
             Screen::InRoom => {
                 ui::draw_text(
                     ctx,
                     self.system_font.clone(),
-                    *MENU_TEXT_COLOR,
+                    theme.menu_text,
                     String::from("In Room"),
                     &Point2 { x: 100.0, y: 100.0 },
                 )?;
@@ -602,7 +750,7 @@ impl EventHandler for MainState {
                 ui::draw_text(
                     ctx,
                     self.system_font.clone(),
-                    *MENU_TEXT_COLOR,
+                    theme.menu_text,
                     String::from("Server List"),
                     &Point2 { x: 100.0, y: 100.0 },
                 )?;
@@ -611,7 +759,7 @@ impl EventHandler for MainState {
                 ui::draw_text(
                     ctx,
                     self.system_font.clone(),
-                    *MENU_TEXT_COLOR,
+                    theme.menu_text,
                     String::from("Options"),
                     &Point2 { x: 100.0, y: 100.0 },
                 )?;
@@ -625,6 +773,14 @@ impl EventHandler for MainState {
             });
         }
 
+        if current_screen == Screen::Run {
+            self.draw_connection_status(ctx)?;
+        }
+
+        self.toasts.draw(ctx).unwrap_or_else(|e| {
+            error!("Error received during toast draw: {:?}", e);
+        });
+
         graphics::present(ctx)?;
         timer::yield_now();
         Ok(())
@@ -722,7 +878,10 @@ impl EventHandler for MainState {
                 || key_as_int32 == KeyCode::Tab as i32)
         {
             // NOTE: we need to exclude modifiers we are using below.
-            let is_modifier_key = keycode == KeyCode::LShift || keycode == KeyCode::RShift;
+            let is_modifier_key = keycode == KeyCode::LShift
+                || keycode == KeyCode::RShift
+                || keycode == KeyCode::LControl
+                || keycode == KeyCode::RControl;
             if self.inputs.key_info.key.is_none() && !is_modifier_key {
                 self.inputs.key_info.key = Some(keycode);
             }
@@ -761,6 +920,10 @@ impl EventHandler for MainState {
         if character.is_control() {
             return;
         }
+        // Ctrl shortcuts like Ctrl-C are handled as key presses, so don't also type the letter
+        if self.inputs.key_info.modifier & KeyMods::CTRL > KeyMods::default() {
+            return;
+        }
 
         self.inputs.text_input.push(character);
     }
@@ -779,7 +942,7 @@ impl EventHandler for MainState {
             self.center_intro_viewport(width, height);
         }
         graphics::set_screen_coordinates(ctx, new_rect).unwrap();
-        self.viewport.set_size(width, height);
+        self.reflow_ui(ctx, width, height);
         if self.video_settings.is_fullscreen {
             debug!("not saving resolution to config because is_fullscreen is true");
         } else {
@@ -834,18 +997,36 @@ struct UniDrawParams {
 }
 
 impl MainState {
+    /// Lets the widgets on every screen lay themselves out again for a window of the new size.
+    fn reflow_ui(&mut self, ctx: &mut Context, width: f32, height: f32) {
+        let mut game_area_state = self.get_gamearea_state().unwrap_or_else(|e| {
+            error!("Could not get game area state: {}", e);
+            GameAreaState::default()
+        });
+        let resize = Event::new_resize(width, height);
+        for layer in self.ui_layout.layers.values_mut() {
+            layer
+                .emit(
+                    &resize,
+                    ctx,
+                    &mut self.config,
+                    &mut self.screen_stack,
+                    &mut game_area_state,
+                    &mut self.static_node_ids,
+                )
+                .unwrap_or_else(|e| {
+                    error!("Error from layer.emit on resize: {:?}", e);
+                });
+        }
+    }
+
     fn get_gamearea_state(&mut self) -> ui::UIResult<GameAreaState> {
         GameArea::widget_from_screen_and_id_mut(&mut self.ui_layout, Screen::Run, &self.static_node_ids.game_area_id)
             .map(|gs| gs.get_game_area_state())
     }
 
     fn draw_game_of_life(&self, ctx: &mut Context, universe: &Universe) -> Result<(), Box<dyn Error>> {
-        let viewport = if self.uni_draw_params.player_id >= 0 {
-            &self.viewport
-        } else {
-            // intro
-            &self.intro_viewport
-        };
+        let viewport = &self.intro_viewport;
         let viewport_rect = viewport.get_rect();
 
         // grid background
@@ -861,24 +1042,12 @@ impl MainState {
         let full_rect = viewport.get_rect_from_origin();
 
         let image = graphics::Image::solid(ctx, 1u16, WHITE)?; // 1x1 square
-        let mut main_spritebatch = graphics::spritebatch::SpriteBatch::new(image.clone());
-        let mut overlay_spritebatch = graphics::spritebatch::SpriteBatch::new(image);
-
-        // grid non-dead cells (walls, players, etc.)
-        let visibility = if self.uni_draw_params.player_id >= 0 {
-            Some(self.uni_draw_params.player_id as usize)
-        } else {
-            // used for random coloring in intro
-            Some(0)
-        };
+        let mut main_spritebatch = graphics::spritebatch::SpriteBatch::new(image);
 
+        // grid non-dead cells, randomly colored
         // TODO: call each_non_dead with visible region (add method to viewport)
-        universe.each_non_dead_full(visibility, &mut |col, row, state| {
-            let color = if self.uni_draw_params.player_id >= 0 {
-                self.color_settings.get_color(Some(state))
-            } else {
-                self.color_settings.get_random_color()
-            };
+        universe.each_non_dead_full(Some(0), &mut |col, row, _state| {
+            let color = self.color_settings.get_random_color();
 
             if let Some(rect) = viewport.window_coords_from_game(viewport::Cell::new(col, row)) {
                 let p = graphics::DrawParam::new()
@@ -890,59 +1059,6 @@ impl MainState {
             }
         });
 
-        let mut insert_mode = None;
-        GameArea::widget_from_screen_and_id(&self.ui_layout, Screen::Run, &self.static_node_ids.game_area_id).map(
-            |gamearea| {
-                insert_mode = gamearea.insert_mode();
-            },
-        )?;
-
-        // TODO: truncate if outside of writable region
-        // TODO: move to new function
-        if let Some((ref grid, width, height)) = insert_mode {
-            let unwritable_flash_on = timer::time_since_start(ctx).subsec_millis() % 250 < 125; // 50% duty cycle, 250ms period
-
-            if self.uni_draw_params.player_id < 0 {
-                return Err(format!("Unexpected player ID {}", self.uni_draw_params.player_id).into());
-            }
-            let player_cell_state = CellState::Alive(Some(self.uni_draw_params.player_id as usize));
-            let player_color = self.color_settings.get_color(Some(player_cell_state));
-            if let Some(cursor_cell) = viewport.game_coords_from_window(self.inputs.mouse_info.position) {
-                let (cursor_col, cursor_row) = (cursor_cell.col, cursor_cell.row);
-                grid.each_set(|grid_col, grid_row| {
-                    let col = (grid_col + cursor_col) as isize - width as isize / 2;
-                    let row = (grid_row + cursor_row) as isize - height as isize / 2;
-                    if col < 0 || row < 0 {
-                        // out of range
-                        return;
-                    }
-                    let (col, row) = (col as usize, row as usize);
-                    if let Some(rect) = viewport.window_coords_from_game(viewport::Cell::new(col, row)) {
-                        let mut color = player_color;
-                        // only error is due to player_id out of range, so unwrap OK here
-                        if !universe
-                            .writable(col, row, self.uni_draw_params.player_id as usize)
-                            .unwrap()
-                        {
-                            // not writable, so draw flashing red cells
-                            if unwritable_flash_on {
-                                color = *constants::colors::INSERT_PATTERN_UNWRITABLE;
-                            } else {
-                                return;
-                            }
-                        }
-                        color.a = 0.5; // semi-transparent since this is an overlay
-                        let p = graphics::DrawParam::new()
-                            .dest(Point2 { x: rect.x, y: rect.y })
-                            .scale(Vector2 { x: rect.w, y: rect.h })
-                            .color(color);
-
-                        overlay_spritebatch.add(p);
-                    }
-                });
-            }
-        }
-
         if let Some(clipped_rect) = ui::intersection(full_rect, viewport_rect) {
             let origin = graphics::DrawParam::new().dest(Point2 { x: 0.0, y: 0.0 });
             let rectangle = graphics::Mesh::new_rectangle(
@@ -954,12 +1070,10 @@ impl MainState {
 
             graphics::draw(ctx, &rectangle, origin)?;
             graphics::draw(ctx, &main_spritebatch, origin)?;
-            graphics::draw(ctx, &overlay_spritebatch, origin)?;
         }
 
         // TODO: see if we need to do this
         main_spritebatch.clear();
-        overlay_spritebatch.clear();
 
         ////////// draw generation counter
         if self.uni_draw_params.draw_counter {
@@ -967,7 +1081,7 @@ impl MainState {
             ui::draw_text(
                 ctx,
                 self.system_font.clone(),
-                *GEN_COUNTER_COLOR,
+                theme::current().gen_counter,
                 gen_counter,
                 &Point2 { x: 0.0, y: 0.0 },
             )?;
@@ -987,29 +1101,35 @@ impl MainState {
         });
     }
 
-    fn draw_intro(&mut self, ctx: &mut Context) -> Result<(), Box<dyn Error>> {
-        self.draw_game_of_life(ctx, &self.intro_uni)
-    }
-
-    /// Draws the GameArea's universe to the screen.
-    fn draw_universe(&mut self, ctx: &mut Context) -> Result<(), Box<dyn Error>> {
-        // A mutable reference is used to notify the first generation is drawn
-        GameArea::widget_from_screen_and_id_mut(&mut self.ui_layout, Screen::Run, &self.static_node_ids.game_area_id)
-            .map(|gamearea| {
-            gamearea.first_gen_drawn();
-        })?;
-
-        // A non-mutable reference is used to draw the universe
-        match GameArea::widget_from_screen_and_id(&self.ui_layout, Screen::Run, &self.static_node_ids.game_area_id) {
-            Ok(gamearea) => {
-                self.draw_game_of_life(ctx, &gamearea.uni)?;
-            }
-            Err(e) => {
-                error!("failed to look up GameArea widget: {:?}", e);
-            }
+    /// Draws a dot in the top left corner that is green, yellow, or red depending on how the
+    /// connection to the server is doing. Nothing is drawn if there is no connection at all.
+    fn draw_connection_status(&mut self, ctx: &mut Context) -> GameResult<()> {
+        if self.net_worker.lock().unwrap().is_none() {
+            return Ok(());
         }
+        let color = match self.connection_status {
+            ConnectionStatus::Connected => *CONNECTION_OK_COLOR,
+            ConnectionStatus::Degraded => *CONNECTION_DEGRADED_COLOR,
+            ConnectionStatus::Disconnected => *CONNECTION_LOST_COLOR,
+        };
+        let window = graphics::screen_coordinates(ctx);
+        let center = Point2 {
+            x: window.x + CONNECTION_ICON_MARGIN,
+            y: window.y + CONNECTION_ICON_MARGIN,
+        };
+        let dot = graphics::Mesh::new_circle(
+            ctx,
+            graphics::DrawMode::fill(),
+            center,
+            CONNECTION_ICON_RADIUS,
+            0.5,
+            color,
+        )?;
+        graphics::draw(ctx, &dot, DrawParam::default())
+    }
 
-        Ok(())
+    fn draw_intro(&mut self, ctx: &mut Context) -> Result<(), Box<dyn Error>> {
+        self.draw_game_of_life(ctx, &self.intro_uni)
     }
 
     fn transition_screen(
@@ -1023,6 +1143,19 @@ impl MainState {
             Screen::Menu => {
                 if new_screen == Screen::Run {
                     let game_area_id = self.static_node_ids.game_area_id.clone();
+                    if let Some(save) = self.pending_autosave.take() {
+                        // The autosave is only offered once: either resume it now or start over
+                        let resume = *self.resume_autosave_requested.lock().unwrap();
+                        let game_area =
+                            GameArea::widget_from_screen_and_id_mut(&mut self.ui_layout, Screen::Run, &game_area_id)?;
+                        if resume {
+                            game_area.resume_autosave(&save).unwrap_or_else(|e| {
+                                error!("Could not resume the autosaved game: {}", e);
+                            });
+                        } else {
+                            game_area.discard_autosave();
+                        }
+                    }
                     if let Some(layering) = self.ui_layout.get_screen_layering_mut(Screen::Run) {
                         layering.enter_focus(
                             ggez_ctx,
@@ -1030,7 +1163,6 @@ impl MainState {
                             &mut self.screen_stack,
                             game_area_state,
                             &mut self.static_node_ids,
-                            &mut self.viewport,
                             &game_area_id,
                         )?;
                     }
@@ -1038,44 +1170,88 @@ impl MainState {
                 }
             }
             Screen::Run => {
-                if new_screen == Screen::Menu {
+                if !self.screen_stack.contains(&Screen::Run) {
+                    // Popped rather than covered: pause, but keep the universe so the game can be
+                    // resumed
                     game_area_state.running = false;
                 }
             }
+            Screen::InGameMenu => {
+                if new_screen == Screen::Menu {
+                    // Quit to Menu ends the game, so starting again starts over
+                    let game_area_id = self.static_node_ids.game_area_id.clone();
+                    let game_area =
+                        GameArea::widget_from_screen_and_id_mut(&mut self.ui_layout, Screen::Run, &game_area_id)?;
+                    game_area.reset()?;
+                    *game_area_state = game_area.get_game_area_state();
+                }
+            }
             _ => {}
         }
 
         if old_screen != new_screen {
-            // Emit a Save event on the old screen
-            if let Some(layering) = self.ui_layout.get_screen_layering_mut(old_screen) {
-                layering.emit(
-                    &Event::new_save(),
-                    ggez_ctx,
-                    &mut self.config,
-                    &mut self.screen_stack,
-                    game_area_state,
-                    &mut self.static_node_ids,
-                    &mut self.viewport,
-                )?;
+            if new_screen == Screen::Run {
+                // Show the chat messages from before, including any received since leaving
+                let chatbox_id = self.static_node_ids.chatbox_id.clone();
+                let chatbox = Chatbox::widget_from_screen_and_id_mut(&mut self.ui_layout, Screen::Run, &chatbox_id)?;
+                chatbox.restore_history(&self.chat_history.lock().unwrap());
             }
 
-            // Emit a Load event on the new screen
-            if let Some(layering) = self.ui_layout.get_screen_layering_mut(new_screen) {
-                layering.emit(
-                    &Event::new_load(),
-                    ggez_ctx,
-                    &mut self.config,
-                    &mut self.screen_stack,
-                    game_area_state,
-                    &mut self.static_node_ids,
-                    &mut self.viewport,
-                )?;
+            if new_screen == Screen::ServerList {
+                self.connect_to_server();
+            }
+
+            if old_screen == Screen::Run && new_screen == Screen::InGameMenu {
+                if let Some(layering) = self.ui_layout.get_screen_layering_mut(Screen::InGameMenu) {
+                    uilayout::slide_in_ingame_menu(layering)?;
+                }
+            }
+
+            // Save the screen being left, then load the screen being entered
+            for (screen, event) in uilayout::screen_change_events(old_screen, new_screen) {
+                if let Some(layering) = self.ui_layout.get_screen_layering_mut(screen) {
+                    layering.emit(
+                        &event,
+                        ggez_ctx,
+                        &mut self.config,
+                        &mut self.screen_stack,
+                        game_area_state,
+                        &mut self.static_node_ids,
+                    )?;
+                }
             }
         }
 
         Ok(())
     }
 
+    /// Connects to the server in the config if not already connected, or else asks for a fresh list
+    /// of servers. Called each time the ServerList screen is shown.
+    fn connect_to_server(&mut self) {
+        let mut net_worker_guard = self.net_worker.lock().unwrap();
+        if let Some(ref mut net_worker) = *net_worker_guard {
+            if !net_worker.has_stopped() {
+                net_worker.try_send(NetwaysteEvent::List);
+                return;
+            }
+        }
+
+        let user = &self.config.get().user;
+        info!("Connecting to {:?} as {:?}", user.server, user.name);
+        match network::ConwaysteNetWorker::new(user.server.clone()) {
+            Ok(mut net_worker) => {
+                // The list is requested once logged in
+                net_worker.try_send(NetwaysteEvent::Connect(user.name.clone(), NETWAYSTE_VERSION.to_owned()));
+                *net_worker_guard = Some(net_worker);
+                self.connection_status = ConnectionStatus::Connected;
+            }
+            Err(e) => {
+                error!("Could not start networking: {}", e);
+                self.toasts.notify(Severity::Error, format!("Could not connect: {}", e));
+            }
+        }
+    }
+
     // update
     fn receive_net_updates(&mut self) -> GameResult<()> {
         let mut net_worker_guard = self.net_worker.lock().unwrap();
@@ -1090,40 +1266,193 @@ impl MainState {
             match e {
                 NetwaysteEvent::LoggedIn(server_version) => {
                     info!("Logged in! Server version: v{}", server_version);
-                    self.screen_stack.push(Screen::ServerList); // XXX
-                                                                // do other stuff
+                    match GameArea::widget_from_screen_and_id_mut(
+                        &mut self.ui_layout,
+                        Screen::Run,
+                        &self.static_node_ids.game_area_id,
+                    ) {
+                        Ok(gamearea) => gamearea.predict_actions = true,
+                        Err(e) => error!("failed to look up GameArea widget: {:?}", e),
+                    }
                     net_worker.try_send(NetwaysteEvent::List);
-                    net_worker.try_send(NetwaysteEvent::JoinRoom("general".to_owned()));
                 }
                 NetwaysteEvent::JoinedRoom(room_name) => {
                     println!("Joined Room: {}", room_name);
                     self.screen_stack.push(Screen::InRoom); // XXX
                 }
-                NetwaysteEvent::PlayerList(list) => {
-                    println!("PlayerList: {:?}", list);
+                NetwaysteEvent::PlayerList(list, muted, teams) => {
+                    println!("PlayerList: {:?} (muted: {:?}, teams: {:?})", list, muted, teams);
                 }
                 NetwaysteEvent::RoomList(list) => {
                     println!("RoomList: {:?}", list);
+                    let entries = list
+                        .into_iter()
+                        .map(|room| ServerListEntry::new(room.room_name, room.player_count as usize))
+                        .collect();
+                    match ServerList::widget_from_screen_and_name_mut(
+                        &mut self.ui_layout,
+                        Screen::ServerList,
+                        "server list",
+                    ) {
+                        Ok(server_list) => server_list.set_entries(entries),
+                        Err(e) => error!("failed to look up ServerList widget: {:?}", e),
+                    }
+                }
+                NetwaysteEvent::UniverseUpdate(diff) => {
+                    debug!("Universe update: generation {} -> {}", diff.gen0, diff.gen1);
+                    match GameArea::widget_from_screen_and_id_mut(
+                        &mut self.ui_layout,
+                        Screen::Run,
+                        &self.static_node_ids.game_area_id,
+                    ) {
+                        Ok(gamearea) => match gamearea.apply_server_diff(&diff) {
+                            Ok(Some(_)) => {}
+                            Ok(None) => {
+                                warn!(
+                                    "Can't apply universe diff based on generation {}; requesting the full state",
+                                    diff.gen0
+                                );
+                                request_full_state(gamearea, net_worker);
+                            }
+                            Err(e) => error!("failed to apply universe diff: {:?}", e),
+                        },
+                        Err(e) => error!("failed to look up GameArea widget: {:?}", e),
+                    }
                 }
-                NetwaysteEvent::UniverseUpdate => {
-                    println!("Universe update");
+                NetwaysteEvent::UniverseChecksum(gen_checksum) => {
+                    match GameArea::widget_from_screen_and_id_mut(
+                        &mut self.ui_layout,
+                        Screen::Run,
+                        &self.static_node_ids.game_area_id,
+                    ) {
+                        Ok(gamearea) => {
+                            let generation = gen_checksum.generation as usize;
+                            if gamearea.matches_server_checksum(generation, gen_checksum.checksum) == Some(false) {
+                                warn!(
+                                    "Universe is out of sync with the server in generation {}; requesting the full state",
+                                    generation
+                                );
+                                request_full_state(gamearea, net_worker);
+                            }
+                        }
+                        Err(e) => error!("failed to look up GameArea widget: {:?}", e),
+                    }
                 }
                 NetwaysteEvent::ChatMessages(msgs) => {
                     for m in msgs {
-                        let msg = format!("{}: {}", m.0, m.1);
                         println!("{:?}", m); // print to stdout for dbg
-
-                        incoming_messages.push(msg);
+                        let (name, msg) = m;
+                        if name == "Server" {
+                            incoming_messages.push(ChatEntry::system(msg));
+                        } else {
+                            incoming_messages.push(ChatEntry::chat(name, msg));
+                        }
                     }
                 }
                 NetwaysteEvent::LeftRoom => {
                     println!("Left Room");
                 }
+                NetwaysteEvent::CellsPlaced(generation) => {
+                    debug!("Cells placed in generation {}", generation);
+                }
                 NetwaysteEvent::BadRequest(error) => {
                     println!("Server responded with Bad Request: {:?}", error);
+                    self.toasts
+                        .notify(Severity::Warn, format!("Server refused a request: {}", error));
                 }
                 NetwaysteEvent::ServerError(error) => {
                     println!("Server encountered an error: {:?}", error);
+                    self.toasts.notify(Severity::Error, format!("Server error: {}", error));
+                }
+                NetwaysteEvent::ChatThrottled(error, muted_secs) => {
+                    let msg = if muted_secs > 0 {
+                        format!("{} ({} seconds left)", error, muted_secs)
+                    } else {
+                        error
+                    };
+                    incoming_messages.push(ChatEntry::system(msg));
+                }
+                NetwaysteEvent::BanList(addrs, names) => {
+                    println!("Banned addresses: {:?}; banned names: {:?}", addrs, names);
+                }
+                NetwaysteEvent::Invited(inviter, room_name, _token) => {
+                    self.toasts
+                        .notify(Severity::Info, format!("{} invited you to room {}", inviter, room_name));
+                }
+                NetwaysteEvent::InviteFailed(reason) => {
+                    incoming_messages.push(ChatEntry::system(format!("Invitation failed: {:?}", reason)));
+                }
+                NetwaysteEvent::InviteAnswered(name, answer) => {
+                    incoming_messages.push(ChatEntry::system(format!("Invitation to {}: {:?}", name, answer)));
+                }
+                NetwaysteEvent::RoomNameTaken(room_name) => {
+                    self.toasts
+                        .notify(Severity::Warn, format!("There is already a room named {}", room_name));
+                }
+                NetwaysteEvent::GameStarting(room_name, seconds_left) => {
+                    self.toasts.notify(
+                        Severity::Info,
+                        format!("The game in room {} starts in {}...", room_name, seconds_left),
+                    );
+                }
+                NetwaysteEvent::GameStartCancelled(room_name) => {
+                    self.toasts.notify(
+                        Severity::Warn,
+                        format!("The game in room {} is not starting after all", room_name),
+                    );
+                }
+                NetwaysteEvent::OwnerChanged(room_name, owner) => {
+                    self.toasts.notify(
+                        Severity::Info,
+                        format!("{} is now the owner of room {}", owner, room_name),
+                    );
+                }
+                NetwaysteEvent::RoomFull(room_name, max_players) => {
+                    self.toasts.notify(
+                        Severity::Warn,
+                        format!("Room {} is full; it takes {} players", room_name, max_players),
+                    );
+                }
+                NetwaysteEvent::SetTeamFailed(reason) => {
+                    incoming_messages.push(ChatEntry::system(format!("Could not change teams: {:?}", reason)));
+                }
+                NetwaysteEvent::GameStarted(room_name, teams) => {
+                    println!("GameStarted in {}: {:?}", room_name, teams);
+                }
+                NetwaysteEvent::GameEnded(room_name, generations, results) => {
+                    self.toasts.notify(
+                        Severity::Info,
+                        format!("The game in room {} ended after {} generations", room_name, generations),
+                    );
+                    for result in results {
+                        incoming_messages.push(ChatEntry::system(format!(
+                            "{} (team {}): {} cells at the end, alive until generation {}{}",
+                            result.name,
+                            result.team,
+                            result.cells_at_end,
+                            result.generations,
+                            if result.disconnected { " (disconnected)" } else { "" }
+                        )));
+                    }
+                }
+                NetwaysteEvent::ConnectionStatus(status) => {
+                    let (severity, message) = match status {
+                        ConnectionStatus::Connected if self.connection_status == ConnectionStatus::Disconnected => (
+                            Severity::Info,
+                            "Reconnected to the server; join the room again to keep playing",
+                        ),
+                        ConnectionStatus::Connected => (Severity::Info, "Connection to the server recovered"),
+                        ConnectionStatus::Degraded => (Severity::Warn, "Connection to the server is unstable"),
+                        ConnectionStatus::Disconnected => (
+                            Severity::Error,
+                            "Lost the connection to the server; trying to reconnect",
+                        ),
+                    };
+                    self.toasts.notify(severity, message);
+                    self.connection_status = status;
+                }
+                NetwaysteEvent::RequestNotSent(reason) => {
+                    self.toasts.notify(Severity::Warn, reason);
                 }
                 _ => {
                     panic!(
@@ -1136,6 +1465,7 @@ impl MainState {
 
         let id = self.static_node_ids.chatbox_id.clone();
         for msg in incoming_messages {
+            self.chat_history.lock().unwrap().push(msg.clone());
             match Chatbox::widget_from_screen_and_id_mut(&mut self.ui_layout, Screen::Run, &id) {
                 Ok(cb) => cb.add_message(msg),
                 Err(e) => error!("Could not add message to Chatbox on network message receive: {:?}", e),
@@ -1173,6 +1503,88 @@ impl MainState {
         Ok(())
     }
 
+    /// Joins the server whose Join button was clicked in the server list, if any.
+    fn update_server_list(&mut self) {
+        let join_request =
+            match ServerList::widget_from_screen_and_name_mut(&mut self.ui_layout, Screen::ServerList, "server list") {
+                Ok(server_list) => server_list.take_join_request(),
+                Err(e) => {
+                    error!("failed to look up ServerList widget: {:?}", e);
+                    return;
+                }
+            };
+        if let Some(room_name) = join_request {
+            if let Some(ref mut net_worker) = *self.net_worker.lock().unwrap() {
+                net_worker.try_send(NetwaysteEvent::JoinRoom(room_name, None));
+            } else {
+                warn!("Cannot join {:?}: not connected to a server", room_name);
+            }
+        }
+    }
+
+    /// Keeps the minimap's visibility, size, live cells, and outline of the main view up to date.
+    /// The live cells are only refreshed every few frames.
+    fn update_minimap(&mut self, visible: bool) {
+        let minimap_id = self.static_node_ids.minimap_id.clone();
+        let game_area_id = self.static_node_ids.game_area_id.clone();
+        let resolution = self.video_settings.get_resolution();
+        let minimap_rect = UILayout::minimap_rect((resolution.w, resolution.h));
+        let view = match GameArea::widget_from_screen_and_id(&self.ui_layout, Screen::Run, &game_area_id) {
+            Ok(gamearea) => gamearea.view.visible_cells(),
+            Err(e) => {
+                error!("failed to look up GameArea widget: {:?}", e);
+                return;
+            }
+        };
+        let mut recenter = None;
+        let needs_refresh = match Minimap::widget_from_screen_and_id_mut(&mut self.ui_layout, Screen::Run, &minimap_id)
+        {
+            Ok(minimap) => {
+                minimap.visible = visible;
+                minimap.set_view(view);
+                recenter = minimap.take_recenter();
+                if minimap.rect() != minimap_rect && visible {
+                    minimap.set_rect(minimap_rect).unwrap_or_else(|e| {
+                        error!("Could not resize minimap: {:?}", e);
+                    });
+                }
+                minimap.tick() && visible
+            }
+            Err(e) => {
+                error!("failed to look up Minimap widget: {:?}", e);
+                return;
+            }
+        };
+        if let Some((col, row)) = recenter {
+            self.modify_game_area(Box::new(move |game_area| {
+                game_area.view.center_on_cell(col, row);
+            }));
+        }
+        if !needs_refresh {
+            return;
+        }
+
+        let mut live_cells = vec![];
+        match GameArea::widget_from_screen_and_id(&self.ui_layout, Screen::Run, &self.static_node_ids.game_area_id) {
+            Ok(gamearea) => {
+                gamearea
+                    .uni
+                    .each_non_dead_full(Some(constants::CURRENT_PLAYER_ID), &mut |col, row, state| {
+                        if let CellState::Alive(_) = state {
+                            live_cells.push((col, row));
+                        }
+                    });
+            }
+            Err(e) => {
+                error!("failed to look up GameArea widget: {:?}", e);
+                return;
+            }
+        }
+        if let Ok(minimap) = Minimap::widget_from_screen_and_id_mut(&mut self.ui_layout, Screen::Run, &minimap_id) {
+            minimap.set_live_cells(&live_cells);
+        }
+    }
+
     fn get_current_screen(&self) -> Screen {
         match self.screen_stack.last() {
             Some(screen) => *screen,
@@ -1202,6 +1614,14 @@ enum Orientation {
 // Toggle a horizontal, vertical, or diagonal line, as player with index 0. This is only used for
 // the intro currently. Part or all of the line can be outside of the Universe; if this is the
 // case, only the parts inside the Universe are toggled.
+/// Starts over with an empty universe, and asks the server for all of its universe.
+fn request_full_state(gamearea: &mut GameArea, net_worker: &mut network::ConwaysteNetWorker) {
+    if let Err(e) = gamearea.discard_universe() {
+        error!("failed to discard the universe: {:?}", e);
+    }
+    net_worker.try_send(NetwaysteEvent::RequestFullState);
+}
+
 fn toggle_line(s: &mut MainState, orientation: Orientation, col: isize, row: isize, width: isize, height: isize) {
     let player_id = 0; // hardcode player ID, since this is just for the intro
     match orientation {
@@ -1567,6 +1987,15 @@ pub fn main() {
 
     color_backtrace::install();
 
+    let cli_options = CliOptions::parse(env::args().skip(1)).unwrap_or_else(|e| {
+        eprintln!("conwayste: {}\n\n{}", e, cli::USAGE);
+        std::process::exit(2);
+    });
+    if cli_options.help {
+        println!("{}", cli::USAGE);
+        return;
+    }
+
     let mut cb = ContextBuilder::new("conwayste", "Aaronm04|Manghi")
         .window_setup(
             conf::WindowSetup::default()
@@ -1595,7 +2024,7 @@ pub fn main() {
         std::process::exit(1);
     });
 
-    match MainState::new(&mut ctx) {
+    match MainState::new(&mut ctx, cli_options) {
         Err(e) => {
             println!("Could not load Conwayste!");
             println!("Error: {}", e);
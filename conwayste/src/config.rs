@@ -19,6 +19,7 @@
 extern crate toml;
 
 use crate::constants::{CONFIG_FILE_PATH, DEFAULT_ZOOM_LEVEL, MIN_CONFIG_FLUSH_TIME};
+use crate::theme::{Theme, DARK_THEME_NAME};
 use std::error::Error;
 use std::fmt;
 use std::time::Instant;
@@ -79,23 +80,27 @@ lazy_static! {
 // Top-level view of config toml file
 #[derive(Debug, Deserialize, Serialize, Clone, Default)]
 pub struct Settings {
-    pub user:     UserNetSettings,
-    pub gameplay: GamePlaySettings,
-    pub video:    VideoSettings,
-    pub audio:    AudioSettings,
+    pub user:        UserNetSettings,
+    pub gameplay:    GamePlaySettings,
+    pub video:       VideoSettings,
+    pub audio:       AudioSettings,
+    pub keybindings: KeyBindingSettings,
+    pub theme:       Theme, // only used if the video theme is "custom"; see `theme.rs`
 }
 
 /// This will decode from the [user] section and contains settings for this user relevant to
 /// network (multiplayer) game play.
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct UserNetSettings {
-    pub name: String,
+    pub name:   String,
+    pub server: String, // host name or address, optionally followed by a port
 }
 
 impl Default for UserNetSettings {
     fn default() -> Self {
         UserNetSettings {
-            name: "JohnConway".to_owned(),
+            name:   "JohnConway".to_owned(),
+            server: "localhost".to_owned(),
         }
     }
 }
@@ -103,17 +108,23 @@ impl Default for UserNetSettings {
 /// Graphics-related settings like resolution, fullscreen, and more!
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct VideoSettings {
-    pub resolution_x: f32,
-    pub resolution_y: f32,
-    pub fullscreen:   bool,
+    pub resolution_x:    f32,
+    pub resolution_y:    f32,
+    pub fullscreen:      bool,
+    pub theme:           String, // "dark", "light", or "custom"
+    pub grid_lines:      bool,   // draw lines between cells, when zoomed in far enough
+    pub grid_line_width: f32,    // in pixels
 }
 
 impl Default for VideoSettings {
     fn default() -> Self {
         VideoSettings {
-            resolution_x: 1024.0,
-            resolution_y: 768.0,
-            fullscreen:   false,
+            resolution_x:    1024.0,
+            resolution_y:    768.0,
+            fullscreen:      false,
+            theme:           DARK_THEME_NAME.to_owned(),
+            grid_lines:      false,
+            grid_line_width: 1.0,
         }
     }
 }
@@ -134,31 +145,101 @@ impl Default for AudioSettings {
     }
 }
 
+/// Which key performs each action in the game, by `KeyCode` name. See `keybindings.rs`.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct KeyBindingSettings {
+    pub pause:           String,
+    pub step:            String,
+    pub zoom_in:         String,
+    pub zoom_out:        String,
+    pub pan_left:        String,
+    pub pan_right:       String,
+    pub pan_up:          String,
+    pub pan_down:        String,
+    pub clear:           String,
+    pub minimap:         String,
+    pub chat:            String,
+    pub wall:            String,
+    pub seek_back:       String,
+    pub seek_forward:    String,
+    pub edges:           String,
+    pub screenshot:      String,
+    pub export_universe: String,
+    pub grid_lines:      String,
+    pub stats:           String,
+}
+
+impl Default for KeyBindingSettings {
+    fn default() -> Self {
+        KeyBindingSettings {
+            pause:           "R".to_owned(),
+            step:            "Space".to_owned(),
+            zoom_in:         "Equals".to_owned(),
+            zoom_out:        "Minus".to_owned(),
+            pan_left:        "Left".to_owned(),
+            pan_right:       "Right".to_owned(),
+            pan_up:          "Up".to_owned(),
+            pan_down:        "Down".to_owned(),
+            clear:           "Key1".to_owned(),
+            minimap:         "M".to_owned(),
+            chat:            "Return".to_owned(),
+            wall:            "W".to_owned(),
+            seek_back:       "PageUp".to_owned(),
+            seek_forward:    "PageDown".to_owned(),
+            edges:           "E".to_owned(),
+            screenshot:      "F12".to_owned(),
+            export_universe: "Ctrl+F12".to_owned(),
+            grid_lines:      "G".to_owned(),
+            stats:           "F3".to_owned(),
+        }
+    }
+}
+
 /// Gameplay-related settings. Pretty empty for now.
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct GamePlaySettings {
-    pub zoom:     f32,
-    pub pattern2: String,
-    pub pattern3: String,
-    pub pattern4: String,
-    pub pattern5: String,
-    pub pattern6: String,
-    pub pattern7: String,
-    pub pattern8: String,
-    pub pattern9: String,
-    pub pattern0: String,
+    pub zoom:                 f32,
+    pub autosave_generations: usize, // autosave the single player game every this many generations; 0 to disable
+    pub autosave_seconds:     u64,   // also autosave this often if anything changed; 0 to disable
+    pub undo_depth:           usize, // how many cell edits can be undone
+    pub chat_timestamps:      bool,  // show the time each chat message arrived
+    pub pattern2:             String,
+    pub pattern3:             String,
+    pub pattern4:             String,
+    pub pattern5:             String,
+    pub pattern6:             String,
+    pub pattern7:             String,
+    pub pattern8:             String,
+    pub pattern9:             String,
+    pub pattern0:             String,
 }
 
 impl Default for GamePlaySettings {
     fn default() -> Self {
         GamePlaySettings {
-            zoom:     DEFAULT_ZOOM_LEVEL,
-            pattern2: "bob$2bo$3o!".to_owned(),          // SE glider
-            pattern3: "4bo$5bo$o4bo$b5o!".to_owned(),    // E LWSS
-            pattern4: "2o2b$obob$2bob$2b2o!".to_owned(), // NW eater
-            pattern5: "2o$2o!".to_owned(),               // block
-            pattern6: "b2o$2ob$bo!".to_owned(),          // R-pentomino
-            pattern7: "10o!".to_owned(),                 // flashy thingy idk the name
+            zoom:                 DEFAULT_ZOOM_LEVEL,
+            autosave_generations: 100,
+            autosave_seconds:     60,
+            undo_depth:           100,
+            chat_timestamps:      true,
+            pattern2:             "bob$2bo$3o!".to_owned(),          // SE glider
+            pattern3:             "4bo$5bo$o4bo$b5o!".to_owned(),    // E LWSS
+            pattern4:             "2o2b$obob$2bob$2b2o!".to_owned(), // NW eater
+
+            // Pulsar, the most common period 3 oscillator. https://www.conwaylife.com/wiki/Pulsar
+            pattern5: concat!(
+                "2b3o3b3o2$o4bobo4bo$o4bobo4bo$o4bobo4bo$2b3o3b3o2$2b3o3b3o$o4bobo4bo$",
+                "o4bobo4bo$o4bobo4bo2$2b3o3b3o!"
+            )
+            .to_owned(),
+            pattern6: "b2o$2ob$bo!".to_owned(), // R-pentomino
+
+            // Gosper glider gun, which shoots SE gliders. https://www.conwaylife.com/wiki/Gosper_glider_gun
+            pattern7: concat!(
+                "24bo$22bobo$12b2o6b2o12b2o$11bo3bo4b2o12b2o$2o8bo5bo3b2o$2o8bo3bob2o4bobo$",
+                "10bo5bo7bo$11bo3bo$12b2o!"
+            )
+            .to_owned(),
 
             // First-ever P23 oscillator "David Hilbert", discovered 2019-11-23.
             // https://www.conwaylife.com/wiki/David_Hilbert
@@ -204,6 +285,7 @@ impl Settings {
 /// Config manages how Settings are loaded and stored to the filesystem.
 pub struct Config {
     settings:            Settings,        // The actual settings
+    saved_settings:      Settings,        // Same as `settings` minus any overrides for this run only
     path:                String,          // Path to config file. `conwayste.toml` by default.
     dirty:               bool,            // Config needs to be flushed to disk?
     flush_time:          Option<Instant>, // Last time (if any) that we flushed to disk.
@@ -217,7 +299,8 @@ impl Config {
         let config = Settings::new();
 
         Config {
-            settings: config,
+            settings: config.clone(),
+            saved_settings: config,
             path: String::from(CONFIG_FILE_PATH),
             dirty: false,
             flush_time: None,
@@ -278,6 +361,11 @@ impl Config {
                             .ok_or_else(|| new_config_error(format!("unexpected section: {}", section_name)))?;
                         match table_ref {
                             Value::Table(ref mut result_table) => {
+                                if section_name == "keybindings" && !result_table.contains_key(field) {
+                                    // perhaps from a newer or older version that has other actions
+                                    warn!("Ignoring key binding for unknown action: {}", field);
+                                    continue;
+                                }
                                 let value_ref: &mut Value = result_table.get_mut(field).ok_or_else(|| {
                                     new_config_error(format!(
                                         "in section {}: unexpected field: {}",
@@ -305,6 +393,7 @@ impl Config {
         }
         let result_string = toml::to_string(&result_map)?;
         self.settings = toml::from_str(result_string.as_str())?;
+        self.saved_settings = self.settings.clone();
         Ok(())
     }
 
@@ -330,9 +419,9 @@ impl Config {
         Ok(())
     }
 
-    /// Save to file unconditionally.
+    /// Save to file unconditionally. Overrides made with `override_for_run` are left out.
     pub fn force_flush(&mut self) -> Result<(), Box<dyn Error>> {
-        let full_toml_str = toml::to_string(&self.settings)?;
+        let full_toml_str = toml::to_string(&self.saved_settings)?;
         let settings_map: TomlMap = toml::from_str(full_toml_str.as_str())?;
         let mut result_map = TomlMap::new();
         // compare each thing in DEFAULT_MAP vs settings_map; if different, add the latter to
@@ -421,11 +510,21 @@ impl Config {
         F: FnMut(&mut Settings),
     {
         f(&mut self.settings);
+        f(&mut self.saved_settings);
         self.set_dirty();
         // TODO: pass a clone of the settings above, and then validate afterwards. If validation
         // passes, then save the clone.
     }
 
+    /// Like `modify`, except that the changes only last until the game exits; they are never
+    /// written to disk. Used for command line options.
+    pub fn override_for_run<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&mut Settings),
+    {
+        f(&mut self.settings);
+    }
+
     /////////// Convenience Methods ///////////
     pub fn get_resolution(&self) -> (f32, f32) {
         (self.settings.video.resolution_x, self.settings.video.resolution_y)
@@ -534,6 +633,38 @@ mod test {
         assert_eq!(config.get().audio.master, 69);
     }
 
+    #[test]
+    fn test_load_or_create_default_keybindings() {
+        let mut config = Config::new();
+        config.dummy_file_data = Some("[keybindings]\npause = \"P\"\n".to_owned());
+        config.load_or_create_default().unwrap();
+
+        assert_eq!(config.get().keybindings.pause, "P");
+        assert_eq!(config.get().keybindings.step, "Space"); // unchanged from the default
+    }
+
+    #[test]
+    fn test_load_or_create_default_unknown_keybinding_action() {
+        let mut config = Config::new();
+        config.dummy_file_data = Some("[keybindings]\nteleport = \"T\"\npause = \"Ctrl+P\"\n".to_owned());
+        config.load_or_create_default().unwrap();
+
+        assert_eq!(config.get().keybindings.pause, "Ctrl+P");
+        assert_eq!(config.get().keybindings.screenshot, "F12"); // unchanged from the default
+    }
+
+    #[test]
+    fn test_load_or_create_default_custom_theme() {
+        let mut config = Config::new();
+        config.dummy_file_data = Some("[video]\ntheme = \"custom\"\n[theme]\nmenu_text = \"#FFFF00\"\n".to_owned());
+        config.load_or_create_default().unwrap();
+
+        let theme = Theme::from_settings(config.get());
+        let yellow = ggez::graphics::Color::from_rgba(0xFF, 0xFF, 0x00, 0xFF);
+        assert_eq!(theme.menu_text, yellow);
+        assert_eq!(theme.border, Theme::dark().border); // unchanged from the default
+    }
+
     #[test]
     fn test_load_or_create_default_invalid_section_name() {
         let mut config = Config::new();
@@ -657,4 +788,24 @@ mod test {
         let commented_default_lines: Vec<&str> = COMMENTED_DEFAULT_STRING.split("\n").collect();
         assert_eq!(&filedata_lines[3..], &commented_default_lines[..]);
     }
+
+    #[test]
+    fn test_override_for_run_is_not_saved() {
+        let mut config = Config::new();
+        config.override_for_run(|settings: &mut Settings| {
+            settings.video.fullscreen = true;
+        });
+        assert_eq!(config.get().video.fullscreen, true);
+        assert_eq!(config.is_dirty(), false);
+
+        // a later change from within the game is saved, but the override still isn't
+        config.modify(|settings: &mut Settings| {
+            settings.gameplay.zoom = 10.0;
+        });
+        assert!(config.force_flush().is_ok());
+        let filedata = config.dummy_file_data.take().unwrap();
+        let filedata_lines: Vec<&str> = filedata.as_str().split("\n").collect();
+        assert_eq!(&filedata_lines[0..2], &["[gameplay]", "zoom = 10.0",]);
+        assert_eq!(config.get().video.fullscreen, true);
+    }
 }
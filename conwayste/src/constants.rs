@@ -29,6 +29,11 @@ pub const INTRO_UNIVERSE_HEIGHT_IN_CELLS: usize = 256;
 pub const CURRENT_PLAYER_ID: usize = 1; // TODO:  get the player ID from server rather than hardcoding
 pub const FOG_RADIUS: usize = 4; // cells
 pub const HISTORY_SIZE: usize = 16;
+pub const PENDING_ACTION_EXPIRY: Duration = Duration::from_millis(2000); // drop unconfirmed cell changes after this
+pub const REJECTED_ACTION_FLASH_DURATION: Duration = Duration::from_millis(500);
+pub const REPLAY_CHECKPOINT_INTERVAL: usize = 16; // generations between live cell hashes in a replay file
+pub const REPLAY_SEEK_GENERATIONS: usize = 100; // how far the seek keys jump in a replay
+pub const GENERATION_RATE_WINDOW: Duration = Duration::from_secs(1); // the stats overlay averages over this long
 
 // Colors
 pub mod colors {
@@ -39,33 +44,46 @@ pub mod colors {
     lazy_static! {
         // To see what the colors look like: https://developer.mozilla.org/en-US/docs/Web/CSS/color_value#Color_keywords
         // TODO: probably can consoldate/remove many of these once the design is fleshed out more
-        pub static ref INPUT_TEXT_COLOR: Color = Color::from(css::DARKRED);
-        pub static ref CHATBOX_TEXT_COLOR: Color = Color::from(css::DARKRED);
-        pub static ref CHATBOX_BORDER_COLOR: Color = Color::from(css::FIREBRICK);
         pub static ref CHATBOX_INACTIVE_BORDER_COLOR: Color = color_with_alpha(css::VIOLET, 0.5);
-        pub static ref CHATBOX_BORDER_ON_HOVER_COLOR: Color = Color::from(css::TEAL);
-        pub static ref MENU_TEXT_COLOR: Color = Color::from(css::WHITE);
         pub static ref MENU_TEXT_SELECTED_COLOR: Color = Color::from(css::LIME);
-        pub static ref CHECKBOX_TEXT_COLOR: Color = Color::from(css::WHITE);
-        pub static ref CHECKBOX_BORDER_ON_HOVER_COLOR: Color = Color::from(css::VIOLET);
-        pub static ref CHECKBOX_TOGGLED_FILL_COLOR: Color = Color::from(css::AZURE);
-        pub static ref CHAT_PANE_FILL_COLOR: Color = color_with_alpha(css::TURQUOISE, 0.33);
-        pub static ref PANE_BORDER_COLOR: Color = Color::from(css::FIREBRICK);
-        pub static ref CELL_STATE_DEAD_COLOR: Color = Color::new(0.875, 0.875, 0.875, 1.0);
-        pub static ref CELL_STATE_BG_FILL_SOLID_COLOR: Color = Color::from(css::WHITE);
         pub static ref CELL_STATE_BG_FILL_HOLLOW_COLOR: Color = Color::from(css::BLACK);
-        pub static ref CELL_STATE_ALIVE_PLAYER_0_COLOR: Color = Color::from(css::RED);
-        pub static ref CELL_STATE_ALIVE_PLAYER_1_COLOR: Color = Color::from(css::BLUE);
-        pub static ref CELL_STATE_WALL_COLOR: Color = Color::new(0.617, 0.55, 0.41, 1.0);
-        pub static ref CELL_STATE_FOG_COLOR: Color = Color::new(0.780, 0.780, 0.780, 1.0);
-        pub static ref GEN_COUNTER_COLOR: Color = Color::from(css::RED);
-        pub static ref UNIVERSE_BG_COLOR: Color = Color::new( 0.25,  0.25,  0.25, 1.0);
         pub static ref LAYER_TRANSPARENCY_BG_COLOR: Color = color_with_alpha(css::HONEYDEW, 0.4);
         pub static ref OPTIONS_TEXT_FILL_COLOR: Color = Color::from(css::YELLOW);
         pub static ref OPTIONS_LABEL_TEXT_COLOR: Color = Color::from(css::WHITE);
         pub static ref INSERT_PATTERN_UNWRITABLE: Color = Color::from(css::RED);
+        pub static ref REJECTED_CELL_FLASH_COLOR: Color = color_with_alpha(css::ORANGERED, 0.7);
+        pub static ref SELECTION_COLOR: Color = color_with_alpha(css::DEEPSKYBLUE, 0.3);
+        pub static ref MINIMAP_BG_COLOR: Color = color_with_alpha(css::BLACK, 0.75);
+        pub static ref MINIMAP_LIVE_COLOR: Color = Color::from(css::WHITE);
+        pub static ref MINIMAP_VIEW_COLOR: Color = Color::from(css::YELLOW);
+        pub static ref INGAME_MENU_FILM_COLOR: Color = color_with_alpha(css::BLACK, 0.6);
+        pub static ref SERVER_LIST_ROW_COLOR: Color = color_with_alpha(css::WHITE, 0.1);
+        pub static ref SERVER_LIST_JOIN_COLOR: Color = Color::from(css::DARKGREEN);
+        pub static ref SERVER_LIST_JOIN_HOVER_COLOR: Color = Color::from(css::FORESTGREEN);
+        pub static ref SERVER_LIST_SCROLLBAR_COLOR: Color = color_with_alpha(css::WHITE, 0.5);
+        pub static ref TOAST_INFO_COLOR: Color = color_with_alpha(css::DARKSLATEGRAY, 0.9);
+        pub static ref TOAST_WARN_COLOR: Color = color_with_alpha(css::DARKORANGE, 0.9);
+        pub static ref TOAST_ERROR_COLOR: Color = color_with_alpha(css::FIREBRICK, 0.9);
+        pub static ref TOAST_TEXT_COLOR: Color = Color::from(css::WHITE);
+        pub static ref CONNECTION_OK_COLOR: Color = Color::from(css::LIMEGREEN);
+        pub static ref CONNECTION_DEGRADED_COLOR: Color = Color::from(css::GOLD);
+        pub static ref CONNECTION_LOST_COLOR: Color = Color::from(css::RED);
+        pub static ref CHAT_TIMESTAMP_COLOR: Color = Color::from(css::GRAY);
+        pub static ref CHAT_SYSTEM_TEXT_COLOR: Color = Color::from(css::DARKCYAN);
+        // Senders' names are drawn in one of these, picked by hashing the name
+        pub static ref CHAT_SENDER_COLORS: [Color; 6] = [
+            Color::from(css::CRIMSON),
+            Color::from(css::ROYALBLUE),
+            Color::from(css::DARKGREEN),
+            Color::from(css::DARKORANGE),
+            Color::from(css::DARKMAGENTA),
+            Color::from(css::SADDLEBROWN),
+        ];
     }
 
+    // Unconfirmed cell changes are drawn in the player's color at this opacity
+    pub const PENDING_CELL_ALPHA: f32 = 0.4;
+
     pub const BLACK: Color = Color {
         r: 0.0,
         g: 0.0,
@@ -90,11 +108,15 @@ pub const INTRO_DURATION: f64 = 8.0; // seconds
 pub const INTRO_PAUSE_DURATION: f64 = 3.0; // seconds
 pub const MAX_CELL_SIZE: f32 = 40.0; // pixels
 pub const MIN_CELL_SIZE: f32 = 5.0; // pixels
+pub const MIN_GRID_LINE_CELL_SIZE: f32 = 10.0; // grid lines are hidden when cells are smaller than this, in pixels
 pub const PIXELS_SCROLLED_PER_FRAME: f32 = 50.0; // pixels
 
 // persistent configuration
 pub const CONFIG_FILE_PATH: &str = "conwayste.toml";
 pub const MIN_CONFIG_FLUSH_TIME: Duration = Duration::from_millis(5000);
+pub const AUTOSAVE_FILE_PATH: &str = "autosave.bin";
+pub const SCREENSHOT_DIR: &str = "screenshots"; // screenshots and universe images are saved here
+pub const EXPORT_CELL_PIXELS: usize = 4; // width and height of each cell in a universe image
 
 // user interface
 lazy_static! {
@@ -104,6 +126,8 @@ lazy_static! {
     // elements for experimentation.
     pub static ref DEFAULT_CHATBOX_FONT_SCALE: PxScale = PxScale::from(15.0);
     pub static ref DEFAULT_CHATBOX_RECT: Rect =  Rect::new(30.0, 40.0, 300.0, 175.0);
+    // Where the in-game menu's pane rests once it has slid in
+    pub static ref INGAME_MENU_RECT: Rect = Rect::new(20.0, 20.0, 370.0, 210.0);
 
 }
 // Border thickness of chatbox in pixels.
@@ -111,6 +135,29 @@ pub const CHATBOX_BORDER_PIXELS: f32 = 1.0;
 pub const CHATBOX_LINE_SPACING: f32 = 2.0;
 pub const CHATBOX_HISTORY: usize = 20;
 pub const CHAT_TEXTFIELD_HEIGHT: f32 = 25.0;
+pub const MINIMAP_CELLS_PER_BLOCK: usize = 2; // each minimap block covers this many cells squared
+pub const MINIMAP_REFRESH_FRAMES: usize = 10; // refresh the minimap's live cells every this many frames
+pub const MINIMAP_WIDTH_FRACTION: f32 = 0.2; // fraction of the window width
+pub const MINIMAP_MARGIN: f32 = 10.0; // pixels from the window's bottom right corner
+pub const CHAT_PANE_MARGIN_X: f32 = 30.0; // pixels from the window's left edge
+pub const CHAT_PANE_MARGIN_Y: f32 = 40.0; // pixels from the window's bottom edge
+pub const MIN_UI_WIDTH: f32 = 320.0; // the UI is laid out for a window at least this wide, in pixels
+pub const MIN_UI_HEIGHT: f32 = 240.0; // and at least this tall
+pub const SERVER_LIST_ROW_HEIGHT: f32 = 40.0;
+pub const SERVER_LIST_JOIN_BUTTON_WIDTH: f32 = 80.0;
+pub const SERVER_LIST_PADDING: f32 = 10.0; // around the text and the Join button of each row
+pub const SERVER_LIST_SCROLLBAR_WIDTH: f32 = 6.0;
+pub const TOAST_LIFETIME: Duration = Duration::from_secs(4); // how long each toast is shown
+pub const TOAST_FADE: Duration = Duration::from_millis(750); // toasts fade out over the end of their lifetime
+pub const MAX_TOASTS_SHOWN: usize = 4; // any more wait until one goes away
+pub const TOAST_MAX_WIDTH: f32 = 320.0; // longer messages are wrapped
+pub const TOAST_PADDING: f32 = 8.0; // around the text of each toast
+pub const TOAST_SPACING: f32 = 6.0; // between toasts
+pub const TOAST_MARGIN: f32 = 10.0; // pixels from the window's top right corner
+pub const CONNECTION_ICON_RADIUS: f32 = 5.0; // the dot showing how the connection to the server is doing
+pub const CONNECTION_ICON_MARGIN: f32 = 15.0; // pixels from the window's top left corner to the dot's center
+pub const CONTEXT_MENU_PADDING: f32 = 6.0; // around the label of each item in a right-click menu
+pub const INGAME_MENU_SLIDE_DURATION: Duration = Duration::from_millis(200); // the in-game menu slides in over this long
 
 // Layering's tree data structure capacities. Arbitrarily chosen.
 pub const LAYERING_NODE_CAPACITY: usize = 100;
@@ -0,0 +1,178 @@
+/*  Copyright 2021 the Conwayste Developers.
+ *
+ *  This file is part of conwayste.
+ *
+ *  conwayste is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  conwayste is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with conwayste.  If not, see
+ *  <http://www.gnu.org/licenses/>. */
+
+//! Saving pictures of the game as PNG files: screenshots of the window, and images of the whole
+//! universe regardless of what is scrolled into view.
+//!
+//! Encoding a PNG takes long enough to drop frames, so it is done on a thread of its own, which
+//! posts a toast once the file is saved or couldn't be.
+
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread::{self, JoinHandle};
+
+use chrono::Local;
+use conway::universe::{CellState, Universe};
+
+use crate::constants::SCREENSHOT_DIR;
+use crate::ui::{ColorSettings, Notifier, Severity};
+
+/// Pixels of an image, as RGBA bytes row by row from the top.
+pub struct Pixels {
+    pub width:  usize,
+    pub height: usize,
+    pub rgba:   Vec<u8>,
+}
+
+/// Draws every cell of the universe, as seen by the player `visibility` (or without fog if
+/// `None`), as a square `cell_pixels` wide in the colors of `color_settings`.
+pub fn rasterize_universe(
+    uni: &Universe,
+    visibility: Option<usize>,
+    color_settings: &ColorSettings,
+    cell_pixels: usize,
+) -> Pixels {
+    let region = uni.region();
+    let width = region.width() * cell_pixels;
+    let height = region.height() * cell_pixels;
+    let (r, g, b, a) = color_settings.get_color(Some(CellState::Dead)).to_rgba();
+    let mut rgba = [r, g, b, a].repeat(width * height);
+
+    uni.each_non_dead_full(visibility, &mut |col, row, state| {
+        let (r, g, b, a) = color_settings.get_color(Some(state)).to_rgba();
+        for y in row * cell_pixels..(row + 1) * cell_pixels {
+            for x in col * cell_pixels..(col + 1) * cell_pixels {
+                let i = (y * width + x) * 4;
+                rgba[i..i + 4].copy_from_slice(&[r, g, b, a]);
+            }
+        }
+    });
+    Pixels { width, height, rgba }
+}
+
+/// Where to save a picture of the given kind, like "screenshot", named after the current time.
+pub fn picture_path(kind: &str) -> PathBuf {
+    Path::new(SCREENSHOT_DIR).join(format!("{}-{}.png", kind, Local::now().format("%Y%m%d-%H%M%S")))
+}
+
+/// Saves `pixels` as a PNG at `path` on another thread, creating its directory if needed. A toast
+/// is posted through `notifier` with where it was saved, or why it couldn't be.
+pub fn save_png_in_background(path: PathBuf, pixels: Pixels, notifier: Notifier) -> JoinHandle<()> {
+    thread::spawn(move || match save_png(&path, &pixels) {
+        Ok(()) => {
+            info!("Saved {:?}", path);
+            notifier.notify(Severity::Info, format!("Saved {}", path.display()));
+        }
+        Err(e) => {
+            error!("Failed to save {:?}: {}", path, e);
+            notifier.notify(Severity::Error, format!("Failed to save {}: {}", path.display(), e));
+        }
+    })
+}
+
+fn save_png(path: &Path, pixels: &Pixels) -> Result<(), Box<dyn Error>> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    image::save_buffer_with_format(
+        path,
+        &pixels.rgba,
+        pixels.width as u32,
+        pixels.height as u32,
+        image::ColorType::Rgba8,
+        image::ImageFormat::Png,
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::{test_dir, two_player_universe};
+    use crate::theme::Theme;
+
+    const WIDTH: usize = 128;
+    const HEIGHT: usize = 64;
+
+    fn pixel(pixels: &Pixels, x: usize, y: usize) -> [u8; 4] {
+        let i = (y * pixels.width + x) * 4;
+        let mut color = [0; 4];
+        color.copy_from_slice(&pixels.rgba[i..i + 4]);
+        color
+    }
+
+    fn rgba(color_settings: &ColorSettings, state: CellState) -> [u8; 4] {
+        let (r, g, b, a) = color_settings.get_color(Some(state)).to_rgba();
+        [r, g, b, a]
+    }
+
+    #[test]
+    fn test_rasterize_universe() {
+        let mut uni = two_player_universe(WIDTH, HEIGHT);
+        uni.set_unchecked(0, 0, CellState::Alive(Some(0)));
+        uni.set_unchecked(WIDTH - 1, HEIGHT - 1, CellState::Alive(Some(1)));
+        uni.set_unchecked(10, 20, CellState::Wall);
+        let color_settings = ColorSettings::from_theme(&Theme::dark());
+
+        let pixels = rasterize_universe(&uni, None, &color_settings, 3);
+        assert_eq!((pixels.width, pixels.height), (WIDTH * 3, HEIGHT * 3));
+        assert_eq!(pixels.rgba.len(), WIDTH * 3 * HEIGHT * 3 * 4);
+
+        let player_0 = rgba(&color_settings, CellState::Alive(Some(0)));
+        let player_1 = rgba(&color_settings, CellState::Alive(Some(1)));
+        let wall = rgba(&color_settings, CellState::Wall);
+        let dead = rgba(&color_settings, CellState::Dead);
+        assert_eq!(pixel(&pixels, 0, 0), player_0);
+        assert_eq!(pixel(&pixels, 2, 2), player_0); // same cell
+        assert_eq!(pixel(&pixels, 3, 0), dead); // next cell over
+        assert_eq!(pixel(&pixels, WIDTH * 3 - 1, HEIGHT * 3 - 1), player_1);
+        assert_eq!(pixel(&pixels, 10 * 3 + 1, 20 * 3 + 1), wall);
+        assert_eq!(pixel(&pixels, 50, 50), dead);
+    }
+
+    #[test]
+    fn test_save_png() {
+        let dir = test_dir("export", "save");
+        let path = dir.join("pictures").join("universe.png");
+        let uni = two_player_universe(WIDTH, HEIGHT);
+        let pixels = rasterize_universe(&uni, None, &ColorSettings::from_theme(&Theme::dark()), 1);
+
+        // the directories are made along the way
+        save_png_in_background(path.clone(), pixels, Notifier::default())
+            .join()
+            .unwrap();
+        let saved = image::open(&path).unwrap().to_rgba8();
+        assert_eq!(saved.dimensions(), (WIDTH as u32, HEIGHT as u32));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_save_png_where_it_cannot_be_saved() {
+        let dir = test_dir("export", "unwritable");
+        let not_a_dir = dir.join("file");
+        fs::write(&not_a_dir, b"").unwrap();
+        let pixels = Pixels {
+            width:  1,
+            height: 1,
+            rgba:   vec![0, 0, 0, 255],
+        };
+        assert!(save_png(&not_a_dir.join("screenshot.png"), &pixels).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
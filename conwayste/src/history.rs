@@ -0,0 +1,335 @@
+/*  Copyright 2021 the Conwayste Developers.
+ *
+ *  This file is part of conwayste.
+ *
+ *  conwayste is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  conwayste is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with conwayste.  If not, see
+ *  <http://www.gnu.org/licenses/>. */
+
+//! Undo and redo of the player's cell edits. Nothing in here depends on ggez.
+//!
+//! Each `Edit` remembers every cell it changed, both before and after, so undoing it is a matter of
+//! writing the old states back. Edits only make sense against the generation they were made in, so
+//! the owner is expected to `clear` the history whenever a new generation is calculated.
+
+use std::collections::VecDeque;
+
+use conway::grids::CharGrid;
+use conway::universe::{CellState, Universe};
+
+/// Returns the state of a cell in the latest generation of `uni`, including which player owns it.
+pub fn cell_state(uni: &Universe, col: usize, row: usize) -> CellState {
+    let (_, ch) = uni.get_run(col, row, None);
+    CellState::from_char(ch).unwrap_or(CellState::Dead)
+}
+
+/// A single cell changed by an edit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CellChange {
+    pub col:    usize,
+    pub row:    usize,
+    pub before: CellState,
+    pub after:  CellState,
+}
+
+/// Every cell changed by one user action: a toggle, a whole drag-paint stroke, or a pattern
+/// insertion.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Edit {
+    changes: Vec<CellChange>,
+}
+
+impl Edit {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Notes that the cell at `(col, row)` went from `before` to `after`. If this edit already
+    /// changed the cell, the original `before` is kept. Changes that leave a cell as it was are
+    /// forgotten.
+    pub fn record(&mut self, col: usize, row: usize, before: CellState, after: CellState) {
+        if let Some(i) = self.changes.iter().position(|c| c.col == col && c.row == row) {
+            self.changes[i].after = after;
+            if self.changes[i].before == after {
+                self.changes.remove(i);
+            }
+        } else if before != after {
+            self.changes.push(CellChange {
+                col,
+                row,
+                before,
+                after,
+            });
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn changes(&self) -> &[CellChange] {
+        &self.changes
+    }
+
+    /// Returns the edit that undoes this one.
+    pub fn inverse(&self) -> Edit {
+        let changes = self
+            .changes
+            .iter()
+            .rev()
+            .map(|c| CellChange {
+                col:    c.col,
+                row:    c.row,
+                before: c.after,
+                after:  c.before,
+            })
+            .collect();
+        Edit { changes }
+    }
+
+    /// Sets every changed cell in the latest generation of `uni` to its `after` state. No checks
+    /// are made, so that exactly the old states come back even if they can't be set by the player.
+    pub fn apply(&self, uni: &mut Universe) {
+        for c in self.changes.iter() {
+            uni.set_unchecked(c.col, c.row, c.after);
+        }
+    }
+}
+
+/// Edits that can be undone, most recent last, along with undone edits that can be redone. At
+/// most `max_depth` edits are kept for undoing; the oldest are forgotten first.
+#[derive(Debug)]
+pub struct EditHistory {
+    undo:      VecDeque<Edit>,
+    redo:      Vec<Edit>,
+    max_depth: usize,
+}
+
+impl EditHistory {
+    pub fn new(max_depth: usize) -> Self {
+        EditHistory {
+            undo: VecDeque::new(),
+            redo: vec![],
+            max_depth,
+        }
+    }
+
+    /// Adds an edit that was just made. Anything that could have been redone is discarded, since
+    /// it no longer follows on from the current state. Empty edits are ignored.
+    pub fn push(&mut self, edit: Edit) {
+        if edit.is_empty() || self.max_depth == 0 {
+            return;
+        }
+        self.redo.clear();
+        self.undo.push_back(edit);
+        while self.undo.len() > self.max_depth {
+            self.undo.pop_front();
+        }
+    }
+
+    /// Undoes the most recent edit on `uni`, returning the changes made to undo it, or `None` if
+    /// there is nothing to undo.
+    pub fn undo(&mut self, uni: &mut Universe) -> Option<Edit> {
+        let edit = self.undo.pop_back()?;
+        let inverse = edit.inverse();
+        inverse.apply(uni);
+        self.redo.push(edit);
+        Some(inverse)
+    }
+
+    /// Redoes the most recently undone edit on `uni`, returning it, or `None` if there is nothing
+    /// to redo.
+    pub fn redo(&mut self, uni: &mut Universe) -> Option<Edit> {
+        let edit = self.redo.pop()?;
+        edit.apply(uni);
+        self.undo.push_back(edit.clone());
+        Some(edit)
+    }
+
+    /// Forgets everything. Called when the universe moves on to a new generation.
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+
+    pub fn undo_len(&self) -> usize {
+        self.undo.len()
+    }
+
+    pub fn redo_len(&self) -> usize {
+        self.redo.len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::{live_cells, one_player_universe};
+    use std::collections::BTreeSet;
+
+    const PLAYER_ID: usize = 0;
+
+    /// Toggles a cell the way the client does, returning the edit.
+    fn toggle(uni: &mut Universe, col: usize, row: usize) -> Edit {
+        let before = cell_state(uni, col, row);
+        let after = uni.toggle(col, row, PLAYER_ID).unwrap();
+        let mut edit = Edit::new();
+        edit.record(col, row, before, after);
+        edit
+    }
+
+    /// Paints a horizontal stroke of live cells as one edit.
+    fn paint(uni: &mut Universe, cols: std::ops::Range<usize>, row: usize) -> Edit {
+        let mut edit = Edit::new();
+        let after = CellState::Alive(Some(PLAYER_ID));
+        for col in cols {
+            let before = cell_state(uni, col, row);
+            uni.set(col, row, after, PLAYER_ID);
+            edit.record(col, row, before, after);
+        }
+        edit
+    }
+
+    #[test]
+    fn test_record_keeps_original_state() {
+        let alive = CellState::Alive(Some(PLAYER_ID));
+        let mut edit = Edit::new();
+        edit.record(1, 2, CellState::Dead, alive);
+        edit.record(1, 2, alive, CellState::Wall);
+        assert_eq!(
+            edit.changes(),
+            &[CellChange {
+                col:    1,
+                row:    2,
+                before: CellState::Dead,
+                after:  CellState::Wall,
+            }]
+        );
+
+        // changing it back means the cell wasn't changed at all
+        edit.record(1, 2, CellState::Wall, CellState::Dead);
+        assert!(edit.is_empty());
+        edit.record(3, 4, CellState::Dead, CellState::Dead);
+        assert!(edit.is_empty());
+    }
+
+    #[test]
+    fn test_inverse_of_inverse() {
+        let mut edit = Edit::new();
+        edit.record(1, 1, CellState::Dead, CellState::Alive(Some(PLAYER_ID)));
+        edit.record(2, 1, CellState::Alive(None), CellState::Dead);
+        assert_eq!(edit.inverse().inverse(), edit);
+        assert_eq!(edit.inverse().changes()[0].col, 2);
+    }
+
+    #[test]
+    fn test_interleaved_undo_redo() {
+        let mut uni = one_player_universe(64, 32);
+        let mut history = EditHistory::new(100);
+        let empty = live_cells(&uni);
+
+        let edit = toggle(&mut uni, 5, 5);
+        history.push(edit);
+        let after_toggle = live_cells(&uni);
+        let edit = paint(&mut uni, 10..15, 7);
+        history.push(edit);
+        let after_stroke = live_cells(&uni);
+        assert_eq!(after_stroke.len(), 6);
+
+        // the whole stroke is undone at once
+        assert_eq!(history.undo(&mut uni).unwrap().changes().len(), 5);
+        assert_eq!(live_cells(&uni), after_toggle);
+        assert!(history.undo(&mut uni).is_some());
+        assert_eq!(live_cells(&uni), empty);
+        assert!(history.undo(&mut uni).is_none());
+
+        assert!(history.redo(&mut uni).is_some());
+        assert_eq!(live_cells(&uni), after_toggle);
+        assert!(history.undo(&mut uni).is_some());
+        assert!(history.redo(&mut uni).is_some());
+        assert!(history.redo(&mut uni).is_some());
+        assert_eq!(live_cells(&uni), after_stroke);
+        assert!(history.redo(&mut uni).is_none());
+        assert_eq!((history.undo_len(), history.redo_len()), (2, 0));
+    }
+
+    #[test]
+    fn test_new_edit_clears_redo() {
+        let mut uni = one_player_universe(64, 32);
+        let mut history = EditHistory::new(100);
+
+        let edit = toggle(&mut uni, 5, 5);
+        history.push(edit);
+        let edit = toggle(&mut uni, 6, 5);
+        history.push(edit);
+        history.undo(&mut uni).unwrap();
+        assert_eq!(history.redo_len(), 1);
+
+        let edit = toggle(&mut uni, 7, 5);
+        history.push(edit);
+        assert_eq!(history.redo_len(), 0);
+        assert!(history.redo(&mut uni).is_none());
+        let expected: BTreeSet<_> = vec![(5, 5, 'A'), (7, 5, 'A')].into_iter().collect();
+        assert_eq!(live_cells(&uni), expected);
+    }
+
+    #[test]
+    fn test_depth_cap_forgets_oldest() {
+        let mut uni = one_player_universe(64, 32);
+        let mut history = EditHistory::new(3);
+
+        for col in 0..5 {
+            let edit = toggle(&mut uni, col, 0);
+            history.push(edit);
+        }
+        assert_eq!(history.undo_len(), 3);
+        while history.undo(&mut uni).is_some() {}
+
+        // the first two toggles can no longer be undone
+        let expected: BTreeSet<_> = vec![(0, 0, 'A'), (1, 0, 'A')].into_iter().collect();
+        assert_eq!(live_cells(&uni), expected);
+        assert_eq!(history.redo_len(), 3);
+
+        // redoing refills up to the cap, but no further
+        while history.redo(&mut uni).is_some() {}
+        assert_eq!(history.undo_len(), 3);
+        assert_eq!(live_cells(&uni).len(), 5);
+    }
+
+    #[test]
+    fn test_empty_edits_and_zero_depth_are_ignored() {
+        let mut uni = one_player_universe(64, 32);
+        let mut history = EditHistory::new(100);
+        history.push(Edit::new());
+        assert_eq!(history.undo_len(), 0);
+
+        let mut history = EditHistory::new(0);
+        let edit = toggle(&mut uni, 1, 1);
+        history.push(edit);
+        assert!(history.undo(&mut uni).is_none());
+    }
+
+    #[test]
+    fn test_clear() {
+        let mut uni = one_player_universe(64, 32);
+        let mut history = EditHistory::new(100);
+        let edit = toggle(&mut uni, 1, 1);
+        history.push(edit);
+        let edit = toggle(&mut uni, 2, 1);
+        history.push(edit);
+        history.undo(&mut uni).unwrap();
+        history.clear();
+        assert_eq!((history.undo_len(), history.redo_len()), (0, 0));
+        assert!(history.undo(&mut uni).is_none());
+    }
+}
@@ -0,0 +1,658 @@
+/*  Copyright 2021 the Conwayste Developers.
+ *
+ *  This file is part of conwayste.
+ *
+ *  conwayste is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  conwayste is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with conwayste.  If not, see
+ *  <http://www.gnu.org/licenses/>. */
+
+//! Rebindable keyboard controls for the game area.
+//!
+//! The `[keybindings]` section of the config maps each action to the name of a key, as spelled in
+//! `KeyCode` (for example `"R"`, `"Space"`, or `"Key1"`), optionally after `Ctrl+` and/or `Shift+`
+//! (for example `"Ctrl+S"`). Names of actions that don't exist are ignored. Escape, Tab, the
+//! pattern keys (2 through 0), and the Ctrl shortcuts for undoing, redoing, copying and pasting are
+//! not rebindable. While a pattern is selected for inserting, R rotates it and F flips it, ahead of
+//! any binding.
+//!
+//! A key pressed with Shift does whatever it does without Shift, unless Shift and the key are bound
+//! to something else. Ctrl always has to match.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use ggez::event::KeyCode;
+
+use crate::config::KeyBindingSettings;
+
+/// Something the player can do by pressing a rebindable key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyAction {
+    Pause, // pause or resume the simulation
+    Step,  // calculate a single generation
+    ZoomIn,
+    ZoomOut,
+    PanLeft,
+    PanRight,
+    PanUp,
+    PanDown,
+    Clear, // stop inserting a pattern
+    Minimap,
+    Chat,        // focus the chat box
+    Wall,        // switch between drawing cells and building walls
+    SeekBack,    // jump back in a replay
+    SeekForward, // jump ahead in a replay
+    Edges,       // switch between a wrapping and a bounded universe, and start over
+    Screenshot,
+    ExportUniverse, // save an image of the whole universe
+    GridLines,      // show or hide the lines between cells
+    Stats,          // show or hide the generation rate and frame rate
+}
+
+impl KeyAction {
+    pub const ALL: [KeyAction; 19] = [
+        KeyAction::Pause,
+        KeyAction::Step,
+        KeyAction::ZoomIn,
+        KeyAction::ZoomOut,
+        KeyAction::PanLeft,
+        KeyAction::PanRight,
+        KeyAction::PanUp,
+        KeyAction::PanDown,
+        KeyAction::Clear,
+        KeyAction::Minimap,
+        KeyAction::Chat,
+        KeyAction::Wall,
+        KeyAction::SeekBack,
+        KeyAction::SeekForward,
+        KeyAction::Edges,
+        KeyAction::Screenshot,
+        KeyAction::ExportUniverse,
+        KeyAction::GridLines,
+        KeyAction::Stats,
+    ];
+
+    /// The name of this action in the config file.
+    pub fn name(self) -> &'static str {
+        match self {
+            KeyAction::Pause => "pause",
+            KeyAction::Step => "step",
+            KeyAction::ZoomIn => "zoom_in",
+            KeyAction::ZoomOut => "zoom_out",
+            KeyAction::PanLeft => "pan_left",
+            KeyAction::PanRight => "pan_right",
+            KeyAction::PanUp => "pan_up",
+            KeyAction::PanDown => "pan_down",
+            KeyAction::Clear => "clear",
+            KeyAction::Minimap => "minimap",
+            KeyAction::Chat => "chat",
+            KeyAction::Wall => "wall",
+            KeyAction::SeekBack => "seek_back",
+            KeyAction::SeekForward => "seek_forward",
+            KeyAction::Edges => "edges",
+            KeyAction::Screenshot => "screenshot",
+            KeyAction::ExportUniverse => "export_universe",
+            KeyAction::GridLines => "grid_lines",
+            KeyAction::Stats => "stats",
+        }
+    }
+
+    /// How this action is described on the Options screen.
+    pub fn description(self) -> &'static str {
+        match self {
+            KeyAction::Pause => "Pause",
+            KeyAction::Step => "Step",
+            KeyAction::ZoomIn => "Zoom In",
+            KeyAction::ZoomOut => "Zoom Out",
+            KeyAction::PanLeft => "Pan Left",
+            KeyAction::PanRight => "Pan Right",
+            KeyAction::PanUp => "Pan Up",
+            KeyAction::PanDown => "Pan Down",
+            KeyAction::Clear => "Stop Inserting",
+            KeyAction::Minimap => "Minimap",
+            KeyAction::Chat => "Chat",
+            KeyAction::Wall => "Walls",
+            KeyAction::SeekBack => "Replay Back",
+            KeyAction::SeekForward => "Replay Forward",
+            KeyAction::Edges => "Edges",
+            KeyAction::Screenshot => "Screenshot",
+            KeyAction::ExportUniverse => "Export Universe",
+            KeyAction::GridLines => "Grid Lines",
+            KeyAction::Stats => "Stats",
+        }
+    }
+
+    pub fn default_key(self) -> KeyChord {
+        let key = match self {
+            KeyAction::Pause => KeyCode::R,
+            KeyAction::Step => KeyCode::Space,
+            KeyAction::ZoomIn => KeyCode::Equals,
+            KeyAction::ZoomOut => KeyCode::Minus,
+            KeyAction::PanLeft => KeyCode::Left,
+            KeyAction::PanRight => KeyCode::Right,
+            KeyAction::PanUp => KeyCode::Up,
+            KeyAction::PanDown => KeyCode::Down,
+            KeyAction::Clear => KeyCode::Key1,
+            KeyAction::Minimap => KeyCode::M,
+            KeyAction::Chat => KeyCode::Return,
+            KeyAction::Wall => KeyCode::W,
+            KeyAction::SeekBack => KeyCode::PageUp,
+            KeyAction::SeekForward => KeyCode::PageDown,
+            KeyAction::Edges => KeyCode::E,
+            KeyAction::Screenshot => KeyCode::F12,
+            KeyAction::GridLines => KeyCode::G,
+            KeyAction::Stats => KeyCode::F3,
+            KeyAction::ExportUniverse => return KeyChord::new(KeyCode::F12).with_ctrl(),
+        };
+        KeyChord::new(key)
+    }
+
+    /// The key name configured for this action.
+    fn configured<'a>(self, settings: &'a KeyBindingSettings) -> &'a str {
+        match self {
+            KeyAction::Pause => &settings.pause,
+            KeyAction::Step => &settings.step,
+            KeyAction::ZoomIn => &settings.zoom_in,
+            KeyAction::ZoomOut => &settings.zoom_out,
+            KeyAction::PanLeft => &settings.pan_left,
+            KeyAction::PanRight => &settings.pan_right,
+            KeyAction::PanUp => &settings.pan_up,
+            KeyAction::PanDown => &settings.pan_down,
+            KeyAction::Clear => &settings.clear,
+            KeyAction::Minimap => &settings.minimap,
+            KeyAction::Chat => &settings.chat,
+            KeyAction::Wall => &settings.wall,
+            KeyAction::SeekBack => &settings.seek_back,
+            KeyAction::SeekForward => &settings.seek_forward,
+            KeyAction::Edges => &settings.edges,
+            KeyAction::Screenshot => &settings.screenshot,
+            KeyAction::ExportUniverse => &settings.export_universe,
+            KeyAction::GridLines => &settings.grid_lines,
+            KeyAction::Stats => &settings.stats,
+        }
+    }
+
+    /// The key name configured for this action, for changing it.
+    pub fn configured_mut(self, settings: &mut KeyBindingSettings) -> &mut String {
+        match self {
+            KeyAction::Pause => &mut settings.pause,
+            KeyAction::Step => &mut settings.step,
+            KeyAction::ZoomIn => &mut settings.zoom_in,
+            KeyAction::ZoomOut => &mut settings.zoom_out,
+            KeyAction::PanLeft => &mut settings.pan_left,
+            KeyAction::PanRight => &mut settings.pan_right,
+            KeyAction::PanUp => &mut settings.pan_up,
+            KeyAction::PanDown => &mut settings.pan_down,
+            KeyAction::Clear => &mut settings.clear,
+            KeyAction::Minimap => &mut settings.minimap,
+            KeyAction::Chat => &mut settings.chat,
+            KeyAction::Wall => &mut settings.wall,
+            KeyAction::SeekBack => &mut settings.seek_back,
+            KeyAction::SeekForward => &mut settings.seek_forward,
+            KeyAction::Edges => &mut settings.edges,
+            KeyAction::Screenshot => &mut settings.screenshot,
+            KeyAction::ExportUniverse => &mut settings.export_universe,
+            KeyAction::GridLines => &mut settings.grid_lines,
+            KeyAction::Stats => &mut settings.stats,
+        }
+    }
+}
+
+/// A key, along with the modifiers that have to be held down with it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyChord {
+    pub key:   KeyCode,
+    pub ctrl:  bool,
+    pub shift: bool,
+}
+
+impl KeyChord {
+    /// The key on its own.
+    pub fn new(key: KeyCode) -> Self {
+        KeyChord {
+            key,
+            ctrl: false,
+            shift: false,
+        }
+    }
+
+    pub fn with_ctrl(self) -> Self {
+        KeyChord { ctrl: true, ..self }
+    }
+
+    pub fn with_shift(self) -> Self {
+        KeyChord { shift: true, ..self }
+    }
+
+    /// Parses a key name like `"R"` or `"Ctrl+Shift+Z"`, ignoring case.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let mut parts: Vec<&str> = name.split('+').map(|part| part.trim()).collect();
+        let key = keycode_from_name(parts.pop()?)?;
+        let mut chord = KeyChord::new(key);
+        for modifier in parts {
+            if modifier.eq_ignore_ascii_case("ctrl") && !chord.ctrl {
+                chord.ctrl = true;
+            } else if modifier.eq_ignore_ascii_case("shift") && !chord.shift {
+                chord.shift = true;
+            } else {
+                return None;
+            }
+        }
+        Some(chord)
+    }
+
+    /// Whether this has a fixed meaning, so that it can't be bound to an action.
+    pub fn is_reserved(self) -> bool {
+        RESERVED_KEYS.contains(&self.key) || (self.ctrl && CTRL_SHORTCUT_KEYS.contains(&self.key))
+    }
+}
+
+/// Formats the chord the way it is written in the config, like `"Ctrl+S"`.
+impl fmt::Display for KeyChord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.ctrl {
+            write!(f, "Ctrl+")?;
+        }
+        if self.shift {
+            write!(f, "Shift+")?;
+        }
+        write!(f, "{}", key_name(self.key).unwrap_or("?"))
+    }
+}
+
+/// Keys with fixed meanings, which can't be bound to an action, whatever modifiers are held.
+const RESERVED_KEYS: &[KeyCode] = &[
+    KeyCode::Escape,
+    KeyCode::Tab,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+    KeyCode::Key0,
+];
+
+/// Keys that undo, redo, copy and paste while Ctrl is held down.
+const CTRL_SHORTCUT_KEYS: &[KeyCode] = &[KeyCode::Z, KeyCode::Y, KeyCode::C, KeyCode::V];
+
+macro_rules! key_names {
+    ($($key:ident),* $(,)?) => {
+        /// Every key that an action can be bound to, along with its name.
+        const KEY_NAMES: &[(&str, KeyCode)] = &[$((stringify!($key), KeyCode::$key)),*];
+    };
+}
+
+key_names!(
+    Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9, Key0, A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R,
+    S, T, U, V, W, X, Y, Z, Escape, F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12, Insert, Home, Delete, End,
+    PageDown, PageUp, Left, Up, Right, Down, Back, Return, Space, Tab, Minus, Equals,
+);
+
+/// Looks up a key by its `KeyCode` name, ignoring case.
+pub fn keycode_from_name(name: &str) -> Option<KeyCode> {
+    KEY_NAMES
+        .iter()
+        .find(|(key_name, _)| key_name.eq_ignore_ascii_case(name))
+        .map(|&(_, key)| key)
+}
+
+/// The name of `key`, if an action can be bound to it.
+fn key_name(key: KeyCode) -> Option<&'static str> {
+    KEY_NAMES
+        .iter()
+        .find(|&&(_, named)| named == key)
+        .map(|&(name, _)| name)
+}
+
+/// Why an action couldn't be bound to a key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RebindError {
+    Unsupported, // the key has no name, so it can't be written to the config
+    Reserved,
+    AlreadyBound(KeyAction), // to this other action
+}
+
+impl fmt::Display for RebindError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RebindError::Unsupported => write!(f, "that key isn't supported"),
+            RebindError::Reserved => write!(f, "that key can't be rebound"),
+            RebindError::AlreadyBound(other) => write!(f, "that key is already bound to {}", other.description()),
+        }
+    }
+}
+
+/// Which action, if any, each key performs.
+#[derive(Debug, Clone)]
+pub struct KeyBindings {
+    keys:     HashMap<KeyAction, KeyChord>,
+    settings: KeyBindingSettings, // what these were read from
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        let keys = KeyAction::ALL
+            .iter()
+            .map(|&action| (action, action.default_key()))
+            .collect();
+        KeyBindings {
+            keys,
+            settings: KeyBindingSettings::default(),
+        }
+    }
+}
+
+impl KeyBindings {
+    /// Reads the bindings from the config. An action whose key is unknown, reserved, or also bound
+    /// to another action falls back to its default key, with a warning.
+    pub fn from_settings(settings: &KeyBindingSettings) -> Self {
+        let mut keys = HashMap::new();
+        for &action in KeyAction::ALL.iter() {
+            let name = action.configured(settings);
+            let chord = match KeyChord::from_name(name) {
+                Some(chord) if chord.is_reserved() => {
+                    warn!(
+                        "Key {:?} bound to {:?} is reserved; using the default of {}",
+                        name,
+                        action.name(),
+                        action.default_key()
+                    );
+                    action.default_key()
+                }
+                Some(chord) => chord,
+                None => {
+                    warn!(
+                        "Unknown key {:?} bound to {:?}; using the default of {}",
+                        name,
+                        action.name(),
+                        action.default_key()
+                    );
+                    action.default_key()
+                }
+            };
+            keys.insert(action, chord);
+        }
+
+        // Falling back to a default can cause a new conflict with another rebound action, so keep
+        // going until there are none. Defaults never conflict with each other, so this ends.
+        loop {
+            let mut fell_back = false;
+            for &action in KeyAction::ALL.iter() {
+                let chord = keys[&action];
+                if chord == action.default_key() {
+                    continue;
+                }
+                let conflict = KeyAction::ALL
+                    .iter()
+                    .find(|&&other| other != action && keys[&other] == chord);
+                if let Some(other) = conflict {
+                    warn!(
+                        "Key {} is bound to both {:?} and {:?}; using the default of {} for {:?}",
+                        chord,
+                        action.name(),
+                        other.name(),
+                        action.default_key(),
+                        action.name()
+                    );
+                    keys.insert(action, action.default_key());
+                    fell_back = true;
+                }
+            }
+            if !fell_back {
+                break;
+            }
+        }
+        KeyBindings {
+            keys,
+            settings: settings.clone(),
+        }
+    }
+
+    /// Whether these bindings were read from `settings`. If not, the config has changed since, and
+    /// they should be read again.
+    pub fn is_from(&self, settings: &KeyBindingSettings) -> bool {
+        self.settings == *settings
+    }
+
+    /// Returns the action that pressing `chord` performs, if any. If nothing is bound to a chord
+    /// with Shift, it does what the same chord without Shift does.
+    pub fn action(&self, chord: KeyChord) -> Option<KeyAction> {
+        let bound = |chord| {
+            self.keys
+                .iter()
+                .find(|&(_, &bound)| bound == chord)
+                .map(|(&action, _)| action)
+        };
+        match bound(chord) {
+            None if chord.shift => bound(KeyChord { shift: false, ..chord }),
+            found => found,
+        }
+    }
+
+    pub fn key(&self, action: KeyAction) -> KeyChord {
+        self.keys[&action]
+    }
+
+    /// Binds `action` to `chord`, unless it's reserved or bound to some other action already. The
+    /// config isn't changed; write `chord` to it to keep the new binding.
+    pub fn rebind(&mut self, action: KeyAction, chord: KeyChord) -> Result<(), RebindError> {
+        if key_name(chord.key).is_none() {
+            return Err(RebindError::Unsupported);
+        }
+        if chord.is_reserved() {
+            return Err(RebindError::Reserved);
+        }
+        if let Some(other) = KeyAction::ALL
+            .iter()
+            .find(|&&other| other != action && self.keys[&other] == chord)
+        {
+            return Err(RebindError::AlreadyBound(*other));
+        }
+        self.keys.insert(action, chord);
+        *action.configured_mut(&mut self.settings) = chord.to_string();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn key(key: KeyCode) -> KeyChord {
+        KeyChord::new(key)
+    }
+
+    #[test]
+    fn test_default_settings_give_default_keys() {
+        let bindings = KeyBindings::from_settings(&KeyBindingSettings::default());
+        for &action in KeyAction::ALL.iter() {
+            assert_eq!(bindings.key(action), action.default_key());
+            assert_eq!(bindings.action(action.default_key()), Some(action));
+            assert_eq!(
+                KeyChord::from_name(action.configured(&KeyBindingSettings::default())),
+                Some(action.default_key())
+            );
+        }
+        assert_eq!(bindings.action(key(KeyCode::Q)), None);
+    }
+
+    #[test]
+    fn test_key_names() {
+        assert_eq!(keycode_from_name("Space"), Some(KeyCode::Space));
+        assert_eq!(keycode_from_name("p"), Some(KeyCode::P));
+        assert_eq!(keycode_from_name("key1"), Some(KeyCode::Key1));
+        assert_eq!(keycode_from_name("Hyperspace"), None);
+    }
+
+    #[test]
+    fn test_chord_names() {
+        assert_eq!(KeyChord::from_name("R"), Some(key(KeyCode::R)));
+        assert_eq!(KeyChord::from_name("Ctrl+S"), Some(key(KeyCode::S).with_ctrl()));
+        assert_eq!(
+            KeyChord::from_name("shift + ctrl + f5"),
+            Some(key(KeyCode::F5).with_ctrl().with_shift())
+        );
+        assert_eq!(KeyChord::from_name("Ctrl+"), None);
+        assert_eq!(KeyChord::from_name("Ctrl+Ctrl+S"), None);
+        assert_eq!(KeyChord::from_name("Alt+S"), None);
+        assert_eq!(KeyChord::from_name("Ctrl+Hyperspace"), None);
+
+        for &name in &["R", "Ctrl+S", "Shift+PageUp", "Ctrl+Shift+Key1"] {
+            assert_eq!(KeyChord::from_name(name).unwrap().to_string(), name);
+        }
+    }
+
+    #[test]
+    fn test_rebinding() {
+        let mut settings = KeyBindingSettings::default();
+        settings.pause = "P".to_owned();
+        settings.step = "R".to_owned(); // the default key for pause, which is no longer using it
+        let bindings = KeyBindings::from_settings(&settings);
+        assert_eq!(bindings.action(key(KeyCode::P)), Some(KeyAction::Pause));
+        assert_eq!(bindings.action(key(KeyCode::R)), Some(KeyAction::Step));
+        assert_eq!(bindings.action(key(KeyCode::Space)), None);
+    }
+
+    #[test]
+    fn test_modifiers_have_to_match() {
+        let mut settings = KeyBindingSettings::default();
+        settings.screenshot = "Ctrl+S".to_owned();
+        settings.wall = "S".to_owned();
+        let bindings = KeyBindings::from_settings(&settings);
+        assert_eq!(
+            bindings.action(key(KeyCode::S).with_ctrl()),
+            Some(KeyAction::Screenshot)
+        );
+        assert_eq!(bindings.action(key(KeyCode::S)), Some(KeyAction::Wall));
+        assert_eq!(bindings.action(key(KeyCode::F12)), None);
+        // Ctrl has to be held for a Ctrl binding, and not held for the others
+        assert_eq!(bindings.action(key(KeyCode::M).with_ctrl()), None);
+    }
+
+    #[test]
+    fn test_shift_falls_back_to_the_unshifted_binding() {
+        let mut settings = KeyBindingSettings::default();
+        settings.edges = "Shift+M".to_owned();
+        let bindings = KeyBindings::from_settings(&settings);
+        assert_eq!(bindings.action(key(KeyCode::M).with_shift()), Some(KeyAction::Edges));
+        assert_eq!(bindings.action(key(KeyCode::M)), Some(KeyAction::Minimap));
+        assert_eq!(
+            bindings.action(key(KeyCode::Left).with_shift()),
+            Some(KeyAction::PanLeft)
+        );
+        assert_eq!(bindings.action(key(KeyCode::E).with_shift()), None);
+        assert_eq!(bindings.action(key(KeyCode::Left).with_ctrl().with_shift()), None);
+    }
+
+    #[test]
+    fn test_unknown_key_falls_back_to_default() {
+        let mut settings = KeyBindingSettings::default();
+        settings.minimap = "NotAKey".to_owned();
+        let bindings = KeyBindings::from_settings(&settings);
+        assert_eq!(bindings.key(KeyAction::Minimap), key(KeyCode::M));
+    }
+
+    #[test]
+    fn test_reserved_key_falls_back_to_default() {
+        let mut settings = KeyBindingSettings::default();
+        settings.zoom_in = "Escape".to_owned();
+        settings.zoom_out = "Shift+Key3".to_owned();
+        settings.pan_up = "Ctrl+Z".to_owned();
+        let bindings = KeyBindings::from_settings(&settings);
+        assert_eq!(bindings.key(KeyAction::ZoomIn), key(KeyCode::Equals));
+        assert_eq!(bindings.key(KeyAction::ZoomOut), key(KeyCode::Minus));
+        assert_eq!(bindings.key(KeyAction::PanUp), key(KeyCode::Up));
+        assert_eq!(bindings.action(key(KeyCode::Escape)), None);
+    }
+
+    #[test]
+    fn test_conflicting_keys_fall_back_to_defaults() {
+        let mut settings = KeyBindingSettings::default();
+        settings.pause = "P".to_owned();
+        settings.step = "P".to_owned();
+        let bindings = KeyBindings::from_settings(&settings);
+        assert_eq!(bindings.key(KeyAction::Pause), key(KeyCode::R));
+        assert_eq!(bindings.key(KeyAction::Step), key(KeyCode::Space));
+    }
+
+    #[test]
+    fn test_same_key_with_other_modifiers_is_no_conflict() {
+        let mut settings = KeyBindingSettings::default();
+        settings.pause = "P".to_owned();
+        settings.step = "Shift+P".to_owned();
+        settings.clear = "Ctrl+P".to_owned();
+        let bindings = KeyBindings::from_settings(&settings);
+        assert_eq!(bindings.action(key(KeyCode::P)), Some(KeyAction::Pause));
+        assert_eq!(bindings.action(key(KeyCode::P).with_shift()), Some(KeyAction::Step));
+        assert_eq!(bindings.action(key(KeyCode::P).with_ctrl()), Some(KeyAction::Clear));
+    }
+
+    #[test]
+    fn test_conflict_with_a_default_key() {
+        let mut settings = KeyBindingSettings::default();
+        settings.clear = "M".to_owned(); // still used by the minimap
+        let bindings = KeyBindings::from_settings(&settings);
+        assert_eq!(bindings.key(KeyAction::Clear), key(KeyCode::Key1));
+        assert_eq!(bindings.action(key(KeyCode::M)), Some(KeyAction::Minimap));
+    }
+
+    #[test]
+    fn test_fallback_that_causes_another_conflict() {
+        let mut settings = KeyBindingSettings::default();
+        settings.chat = "R".to_owned(); // pause's default...
+        settings.pause = "Hyperspace".to_owned(); // ...which pause falls back to
+        let bindings = KeyBindings::from_settings(&settings);
+        assert_eq!(bindings.key(KeyAction::Pause), key(KeyCode::R));
+        assert_eq!(bindings.key(KeyAction::Chat), key(KeyCode::Return));
+    }
+
+    #[test]
+    fn test_rebind() {
+        let mut bindings = KeyBindings::default();
+        assert_eq!(
+            bindings.rebind(KeyAction::Pause, key(KeyCode::Space)),
+            Err(RebindError::AlreadyBound(KeyAction::Step))
+        );
+        assert_eq!(
+            bindings.rebind(KeyAction::Pause, key(KeyCode::C).with_ctrl()),
+            Err(RebindError::Reserved)
+        );
+        assert_eq!(
+            bindings.rebind(KeyAction::Pause, key(KeyCode::LAlt)),
+            Err(RebindError::Unsupported)
+        );
+        assert_eq!(bindings.rebind(KeyAction::Pause, key(KeyCode::R)), Ok(())); // its own key
+        assert_eq!(bindings.key(KeyAction::Pause), key(KeyCode::R));
+
+        assert_eq!(bindings.rebind(KeyAction::Pause, key(KeyCode::P).with_ctrl()), Ok(()));
+        assert_eq!(bindings.action(key(KeyCode::P).with_ctrl()), Some(KeyAction::Pause));
+        assert_eq!(bindings.action(key(KeyCode::R)), None);
+    }
+
+    #[test]
+    fn test_bindings_know_when_the_config_changed() {
+        let mut settings = KeyBindingSettings::default();
+        let mut bindings = KeyBindings::from_settings(&settings);
+        assert!(bindings.is_from(&settings));
+
+        settings.pause = "P".to_owned();
+        assert!(!bindings.is_from(&settings));
+
+        // rebinding the same way as the config doesn't need reading it again
+        bindings.rebind(KeyAction::Pause, key(KeyCode::P)).unwrap();
+        assert!(bindings.is_from(&settings));
+    }
+}
@@ -22,7 +22,10 @@ extern crate ggez;
 extern crate netwayste;
 extern crate tokio;
 
+use std::io;
+
 use futures as Fut;
+use tokio::runtime::Runtime;
 
 use netwayste::client::ClientNetState;
 use netwayste::net::NetwaysteEvent;
@@ -30,26 +33,35 @@ use netwayste::net::NetwaysteEvent;
 pub struct ConwaysteNetWorker {
     sender:   Fut::channel::mpsc::UnboundedSender<NetwaysteEvent>,
     receiver: Fut::channel::mpsc::Receiver<NetwaysteEvent>,
+    // The ggez event loop isn't async, so netwayste gets a runtime of its own. Dropping the worker
+    // shuts it down, along with the connection.
+    _runtime: Runtime,
+    stopped:  bool,
 }
 
 impl ConwaysteNetWorker {
-    // TODO: This will likely be refactored after the networking architecture update soon coming
-    #[allow(unused)]
-    pub fn new() -> Self {
+    /// Starts netwayste in the background, talking to the server at `server_str` (a host name or
+    /// address, optionally followed by a port). Nothing is sent until the first request, which
+    /// should be a `NetwaysteEvent::Connect`.
+    pub fn new(server_str: String) -> io::Result<Self> {
         let (netwayste_request_sender, netwayste_request_receiver) = Fut::channel::mpsc::unbounded::<NetwaysteEvent>();
         let (netwayste_response_sender, netwayste_response_receiver) = Fut::channel::mpsc::channel::<NetwaysteEvent>(5);
 
-        tokio::spawn(async {
-            match ClientNetState::start_network(netwayste_response_sender, netwayste_request_receiver).await {
+        let runtime = Runtime::new()?;
+        runtime.spawn(async {
+            match ClientNetState::start_network(server_str, netwayste_response_sender, netwayste_request_receiver).await
+            {
                 Ok(()) => {}
                 Err(e) => error!("Error during ClientNetState: {}", e),
             }
         });
 
-        ConwaysteNetWorker {
+        Ok(ConwaysteNetWorker {
             sender:   netwayste_request_sender,
             receiver: netwayste_response_receiver,
-        }
+            _runtime: runtime,
+            stopped:  false,
+        })
     }
 
     pub fn try_send(&mut self, nw_event: NetwaysteEvent) {
@@ -60,7 +72,7 @@ impl ConwaysteNetWorker {
     }
 
     /// Update handler call from Conwayste's main event hander.
-    /// Manages all received network packets and sets them up to be handled as needed.AsMut
+    /// Manages all received network packets and sets them up to be handled as needed.
     ///
     /// Must not block or delay in any way as this will hold up the main event update loop!
     pub fn try_receive(&mut self) -> Vec<NetwaysteEvent> {
@@ -71,18 +83,25 @@ impl ConwaysteNetWorker {
                     new_events.push(response);
                 }
                 Ok(None) => {
-                    // do nothing
+                    // netwayste has stopped and dropped its end of the channel
+                    if !self.stopped {
+                        error!("Communications channel link with netwayste disconnected unexpectedly.");
+                        self.stopped = true;
+                    }
                     break;
                 }
-                Err(e) => {
-                    error!(
-                        "Communications channel link with netwayste disconnected unexpectedly. {} Shutting down...",
-                        e
-                    );
+                Err(_) => {
+                    // nothing more has been received yet
                     break;
                 }
             }
         }
         new_events
     }
+
+    /// Whether netwayste has stopped, for example because the server could not be resolved. A new
+    /// worker is needed to connect again.
+    pub fn has_stopped(&self) -> bool {
+        self.stopped
+    }
 }
@@ -0,0 +1,298 @@
+/*  Copyright 2021 the Conwayste Developers.
+ *
+ *  This file is part of conwayste.
+ *
+ *  conwayste is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  conwayste is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with conwayste.  If not, see
+ *  <http://www.gnu.org/licenses/>. */
+
+use conway::universe::CellState;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Identifies a locally requested cell change: `(col, row, generation requested)`.
+pub type PendingKey = (usize, usize, usize);
+
+/// A cell change the player requested locally but which the server has not yet confirmed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PendingCell {
+    pub requested:    CellState, // the state the player set the cell to
+    pub requested_at: Instant,
+}
+
+/// What happened to a pending cell change after reconciling it against an authoritative update.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reconciliation {
+    /// The authoritative update has not yet covered this generation; keep waiting.
+    Pending,
+    /// The server's state matches what we requested.
+    Confirmed,
+    /// The server's state differs from what we requested; the local change must be reverted.
+    Rejected,
+    /// No authoritative update arrived in time.
+    Expired,
+}
+
+/// Decide the fate of a single pending cell change.
+///
+/// `confirmed_gen` is the latest generation covered by an authoritative update, if any has been
+/// received. `authoritative` returns the server's state for a `(col, row)` at that generation.
+pub fn reconcile<F>(
+    key: PendingKey,
+    pending: &PendingCell,
+    confirmed_gen: Option<usize>,
+    now: Instant,
+    expiry: Duration,
+    mut authoritative: F,
+) -> Reconciliation
+where
+    F: FnMut(usize, usize) -> CellState,
+{
+    let (col, row, generation) = key;
+    if let Some(confirmed_gen) = confirmed_gen {
+        if confirmed_gen >= generation {
+            return if authoritative(col, row) == pending.requested {
+                Reconciliation::Confirmed
+            } else {
+                Reconciliation::Rejected
+            };
+        }
+    }
+    if now.duration_since(pending.requested_at) >= expiry {
+        return Reconciliation::Expired;
+    }
+    Reconciliation::Pending
+}
+
+/// Tracks cell changes that are awaiting confirmation from the server, along with recently
+/// rejected ones so that they can be briefly flashed on screen.
+#[derive(Debug, Default)]
+pub struct PendingActions {
+    pending:  HashMap<PendingKey, PendingCell>,
+    rejected: Vec<(usize, usize, Instant)>, // (col, row, time of rejection)
+}
+
+impl PendingActions {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Record a cell change requested at `generation`. A later request for the same cell and
+    /// generation replaces the earlier one.
+    pub fn insert(&mut self, col: usize, row: usize, generation: usize, requested: CellState, now: Instant) {
+        self.pending.insert(
+            (col, row, generation),
+            PendingCell {
+                requested,
+                requested_at: now,
+            },
+        );
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Returns true if there is any unconfirmed change for the cell at `(col, row)`.
+    pub fn is_pending(&self, col: usize, row: usize) -> bool {
+        self.pending.keys().any(|&(c, r, _)| c == col && r == row)
+    }
+
+    /// Calls `callback` with the column, row, and requested state of each pending cell change.
+    pub fn each_pending(&self, callback: &mut dyn FnMut(usize, usize, CellState)) {
+        for (&(col, row, _), pending) in self.pending.iter() {
+            callback(col, row, pending.requested);
+        }
+    }
+
+    /// Calls `callback` with the column and row of each rejected cell change that was rejected
+    /// less than `flash_duration` ago.
+    pub fn each_rejected(&self, now: Instant, flash_duration: Duration, callback: &mut dyn FnMut(usize, usize)) {
+        for &(col, row, rejected_at) in self.rejected.iter() {
+            if now.duration_since(rejected_at) < flash_duration {
+                callback(col, row);
+            }
+        }
+    }
+
+    /// Reconcile all pending cell changes against an authoritative update. Confirmed and expired
+    /// entries are dropped. Rejected entries are dropped and remembered for flashing.
+    ///
+    /// # Return value
+    ///
+    /// The `(col, row)` of each rejected change, so that the caller can revert it locally.
+    pub fn reconcile<F>(
+        &mut self,
+        confirmed_gen: Option<usize>,
+        now: Instant,
+        expiry: Duration,
+        mut authoritative: F,
+    ) -> Vec<(usize, usize)>
+    where
+        F: FnMut(usize, usize) -> CellState,
+    {
+        let mut rejected = vec![];
+        let rejected_flashes = &mut self.rejected;
+        self.pending.retain(|&key, pending| {
+            match reconcile(key, pending, confirmed_gen, now, expiry, &mut authoritative) {
+                Reconciliation::Pending => true,
+                Reconciliation::Confirmed => false,
+                Reconciliation::Rejected => {
+                    let (col, row, _) = key;
+                    rejected.push((col, row));
+                    rejected_flashes.push((col, row, now));
+                    false
+                }
+                Reconciliation::Expired => {
+                    debug!("Pending cell change at {:?} expired without a server response", key);
+                    false
+                }
+            }
+        });
+        rejected
+    }
+
+    /// Forget rejected cell changes that are done flashing.
+    pub fn expire_rejected(&mut self, now: Instant, flash_duration: Duration) {
+        self.rejected
+            .retain(|&(_, _, rejected_at)| now.duration_since(rejected_at) < flash_duration);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const EXPIRY: Duration = Duration::from_millis(2000);
+    const FLASH: Duration = Duration::from_millis(500);
+
+    fn alive() -> CellState {
+        CellState::Alive(Some(1))
+    }
+
+    #[test]
+    fn test_reconcile_waits_for_covering_generation() {
+        let now = Instant::now();
+        let pending = PendingCell {
+            requested:    alive(),
+            requested_at: now,
+        };
+        assert_eq!(
+            reconcile((3, 4, 10), &pending, None, now, EXPIRY, |_, _| alive()),
+            Reconciliation::Pending
+        );
+        assert_eq!(
+            reconcile((3, 4, 10), &pending, Some(9), now, EXPIRY, |_, _| alive()),
+            Reconciliation::Pending
+        );
+        assert_eq!(
+            reconcile((3, 4, 10), &pending, Some(10), now, EXPIRY, |_, _| alive()),
+            Reconciliation::Confirmed
+        );
+    }
+
+    #[test]
+    fn test_reconcile_rejects_mismatch() {
+        let now = Instant::now();
+        let pending = PendingCell {
+            requested:    alive(),
+            requested_at: now,
+        };
+        assert_eq!(
+            reconcile((3, 4, 10), &pending, Some(11), now, EXPIRY, |_, _| CellState::Dead),
+            Reconciliation::Rejected
+        );
+    }
+
+    #[test]
+    fn test_reconcile_expires() {
+        let now = Instant::now();
+        let pending = PendingCell {
+            requested:    alive(),
+            requested_at: now,
+        };
+        let later = now + EXPIRY;
+        assert_eq!(
+            reconcile((3, 4, 10), &pending, Some(9), later, EXPIRY, |_, _| alive()),
+            Reconciliation::Expired
+        );
+    }
+
+    #[test]
+    fn test_pending_actions_confirm_then_reject_sequence() {
+        let now = Instant::now();
+        let mut pa = PendingActions::new();
+        pa.insert(1, 1, 5, alive(), now);
+        pa.insert(2, 2, 6, alive(), now);
+        pa.insert(3, 3, 7, CellState::Dead, now);
+        assert_eq!(pa.len(), 3);
+        assert!(pa.is_pending(2, 2));
+
+        // Update covering gen 6: (1,1) confirmed, (2,2) rejected, (3,3) still pending
+        let rejected = pa.reconcile(Some(6), now, EXPIRY, |col, _| {
+            if col == 1 {
+                alive()
+            } else {
+                CellState::Dead
+            }
+        });
+        assert_eq!(rejected, vec![(2, 2)]);
+        assert_eq!(pa.len(), 1);
+        assert!(!pa.is_pending(1, 1));
+        assert!(!pa.is_pending(2, 2));
+        assert!(pa.is_pending(3, 3));
+
+        let mut flashing = vec![];
+        pa.each_rejected(now, FLASH, &mut |col, row| flashing.push((col, row)));
+        assert_eq!(flashing, vec![(2, 2)]);
+
+        // Update covering gen 7 confirms the requested kill
+        let rejected = pa.reconcile(Some(7), now, EXPIRY, |_, _| CellState::Dead);
+        assert!(rejected.is_empty());
+        assert!(pa.is_empty());
+    }
+
+    #[test]
+    fn test_pending_actions_expire_without_response() {
+        let now = Instant::now();
+        let mut pa = PendingActions::new();
+        pa.insert(1, 1, 5, alive(), now);
+
+        let rejected = pa.reconcile(None, now + EXPIRY / 2, EXPIRY, |_, _| alive());
+        assert!(rejected.is_empty());
+        assert_eq!(pa.len(), 1);
+
+        let rejected = pa.reconcile(None, now + EXPIRY, EXPIRY, |_, _| alive());
+        assert!(rejected.is_empty());
+        assert!(pa.is_empty());
+    }
+
+    #[test]
+    fn test_rejected_flash_expires() {
+        let now = Instant::now();
+        let mut pa = PendingActions::new();
+        pa.insert(1, 1, 5, alive(), now);
+        pa.reconcile(Some(5), now, EXPIRY, |_, _| CellState::Dead);
+
+        let mut count = 0;
+        pa.each_rejected(now + FLASH, FLASH, &mut |_, _| count += 1);
+        assert_eq!(count, 0);
+
+        pa.expire_rejected(now + FLASH, FLASH);
+        assert!(pa.rejected.is_empty());
+    }
+}
@@ -0,0 +1,695 @@
+/*  Copyright 2021 the Conwayste Developers.
+ *
+ *  This file is part of conwayste.
+ *
+ *  conwayste is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  conwayste is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with conwayste.  If not, see
+ *  <http://www.gnu.org/licenses/>. */
+
+//! Recording and playback of single-player sessions.
+//!
+//! A replay file is a bincode-encoded `REPLAY_FORMAT_VERSION`, followed by a `ReplayHeader`,
+//! followed by any number of `ReplayEntry`s appended as the session goes on. Along with what the
+//! player did, every generation's changes are recorded, so a replay can be played back, and jumped
+//! around in, without calculating anything. Nothing in here depends on ggez.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read, Write};
+
+use conway::error::ConwayError;
+use conway::grids::CharGrid;
+use conway::rle::{Pattern, NO_OP_CHAR};
+use conway::universe::{CellState, GenStateDiff, Region, Universe};
+use conway::ConwayResult;
+
+/// Must be bumped whenever `ReplayHeader`, `ReplayEntry`, or `ReplayAction` change, so that old
+/// replays are rejected rather than misparsed.
+pub const REPLAY_FORMAT_VERSION: u32 = 2;
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(io::Error),
+    Encoding(bincode::Error),
+    UnsupportedVersion { found: u32 },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReplayError::Io(e) => write!(f, "I/O error: {}", e),
+            ReplayError::Encoding(e) => write!(f, "Invalid replay data: {}", e),
+            ReplayError::UnsupportedVersion { found } => write!(
+                f,
+                "Replay format version {} is not supported (expected {})",
+                found, REPLAY_FORMAT_VERSION
+            ),
+        }
+    }
+}
+
+impl Error for ReplayError {}
+
+impl From<io::Error> for ReplayError {
+    fn from(e: io::Error) -> Self {
+        ReplayError::Io(e)
+    }
+}
+
+impl From<bincode::Error> for ReplayError {
+    fn from(e: bincode::Error) -> Self {
+        ReplayError::Encoding(e)
+    }
+}
+
+/// Describes the universe at the start of a recorded session.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReplayHeader {
+    pub width:               usize,
+    pub height:              usize,
+    pub player_id:           usize,                     // player who performed the recorded actions
+    pub generation:          usize,                     // generation at which recording started
+    pub initial_cells:       Vec<(usize, usize, char)>, // (col, row, `CellState::to_char`) of non-dead cells
+    pub checkpoint_interval: usize,                     // generations between live cell hashes
+}
+
+impl ReplayHeader {
+    /// Captures the current state of `uni` as the starting point of a recording.
+    pub fn from_universe(uni: &Universe, player_id: usize, checkpoint_interval: usize) -> Self {
+        let mut initial_cells = vec![];
+        uni.each_non_dead_full(None, &mut |col, row, state| {
+            initial_cells.push((col, row, state.to_char()));
+        });
+        ReplayHeader {
+            width: uni.width(),
+            height: uni.height(),
+            player_id,
+            generation: uni.latest_gen(),
+            initial_cells,
+            checkpoint_interval: checkpoint_interval.max(1),
+        }
+    }
+
+    /// Writes the recorded initial cells into `uni`, which should be freshly created with the same
+    /// dimensions and players as the recorded one.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the generation or dimensions don't match, or the header contains an
+    /// invalid cell.
+    pub fn restore(&self, uni: &mut Universe) -> ConwayResult<()> {
+        if uni.latest_gen() != self.generation {
+            return Err(ConwayError::InvalidData {
+                reason: format!(
+                    "Replay starts at generation {}, but this universe is at generation {}",
+                    self.generation,
+                    uni.latest_gen()
+                ),
+            });
+        }
+        if uni.width() != self.width || uni.height() != self.height {
+            return Err(ConwayError::InvalidData {
+                reason: format!(
+                    "Replay is for a {}x{} universe, but this universe is {}x{}",
+                    self.width,
+                    self.height,
+                    uni.width(),
+                    uni.height()
+                ),
+            });
+        }
+        for &(col, row, ch) in self.initial_cells.iter() {
+            let state = CellState::from_char(ch).ok_or_else(|| ConwayError::InvalidData {
+                reason: format!("Invalid cell {:?} at col={}, row={} in replay", ch, col, row),
+            })?;
+            if col >= self.width || row >= self.height {
+                return Err(ConwayError::InvalidData {
+                    reason: format!("Cell at col={}, row={} is outside of the universe", col, row),
+                });
+            }
+            match state {
+                // Toggling also clears the player's fog around the cell, as when it was first drawn
+                CellState::Alive(Some(player_id)) if uni.toggle(col, row, player_id).is_ok() => {}
+                _ => uni.set_unchecked(col, row, state),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Something the player did during a recorded session.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ReplayAction {
+    Toggle {
+        col: usize,
+        row: usize,
+    },
+    Set {
+        col:   usize,
+        row:   usize,
+        state: char, // `CellState::to_char` of the new state
+    },
+    Insert {
+        left:    isize,
+        top:     isize,
+        width:   usize,
+        height:  usize,
+        pattern: String, // RLE
+    },
+    Pause,
+    Resume,
+    Step,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ReplayEntry {
+    Action { generation: usize, action: ReplayAction },
+    Checkpoint { generation: usize, hash: u64 },
+    // Diff RLE of the cells that changed to get to `generation`
+    Frame { generation: usize, pattern: String },
+}
+
+/// Makes a diff pattern that sets each of `cells` to its new state, leaving every other cell as it
+/// was. Unknown cells are left out. `cells` must be in row-major order, as returned by
+/// `Universe::changed_cells`.
+pub fn frame_pattern(cells: &[(usize, usize, CellState)]) -> Pattern {
+    fn push_run(rle: &mut String, len: usize, ch: char) {
+        if len > 1 {
+            rle.push_str(&len.to_string());
+        }
+        if len > 0 {
+            rle.push(ch);
+        }
+    }
+
+    let mut rle = String::new();
+    let (mut col, mut row) = (0, 0);
+    for &(cell_col, cell_row, state) in cells {
+        if state == CellState::Fog {
+            continue;
+        }
+        if cell_row > row {
+            push_run(&mut rle, cell_row - row, '$');
+            row = cell_row;
+            col = 0;
+        }
+        push_run(&mut rle, cell_col - col, NO_OP_CHAR);
+        rle.push(state.to_char());
+        col = cell_col + 1;
+    }
+    rle.push('!');
+    Pattern(rle)
+}
+
+/// Hashes the live cells of the latest generation of `uni` (including which player owns each), so
+/// that a replayed simulation can be checked against the recorded one. Uses FNV-1a, since the
+/// result must not change between builds.
+pub fn live_cells_hash(uni: &Universe) -> u64 {
+    const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+    let mut hash = FNV_OFFSET_BASIS;
+    let mut feed = |bytes: &[u8]| {
+        for byte in bytes {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(FNV_PRIME);
+        }
+    };
+    uni.each_non_dead_full(None, &mut |col, row, state| {
+        if let CellState::Alive(_) = state {
+            feed(&(col as u64).to_le_bytes());
+            feed(&(row as u64).to_le_bytes());
+            feed(&[state.to_char() as u8]);
+        }
+    });
+    hash
+}
+
+/// Performs a recorded action on `uni` on behalf of `player_id`. `Pause`, `Resume`, and `Step` do
+/// not change the universe, so they are ignored here.
+///
+/// # Errors
+///
+/// Returns an error if the action contains invalid data.
+pub fn apply_action(uni: &mut Universe, player_id: usize, action: &ReplayAction) -> ConwayResult<()> {
+    match action {
+        ReplayAction::Toggle { col, row } => {
+            uni.toggle(*col, *row, player_id)?;
+        }
+        ReplayAction::Set { col, row, state } => {
+            let state = CellState::from_char(*state).ok_or_else(|| ConwayError::InvalidData {
+                reason: format!("Invalid cell state {:?} in replay", state),
+            })?;
+            uni.set(*col, *row, state, player_id);
+        }
+        ReplayAction::Insert {
+            left,
+            top,
+            width,
+            height,
+            pattern,
+        } => {
+            let grid = Pattern(pattern.clone()).to_new_bit_grid(*width, *height)?;
+            uni.copy_from_bit_grid(&grid, Region::new(*left, *top, *width, *height), Some(player_id));
+        }
+        ReplayAction::Pause | ReplayAction::Resume | ReplayAction::Step => {}
+    }
+    Ok(())
+}
+
+/// Appends a session to a replay file as it happens.
+pub struct Recorder<W: Write> {
+    writer:              W,
+    checkpoint_interval: usize,
+}
+
+impl<W: Write> Recorder<W> {
+    /// Starts a recording by writing the format version and `header` to `writer`.
+    pub fn new(mut writer: W, header: &ReplayHeader) -> Result<Self, ReplayError> {
+        bincode::serialize_into(&mut writer, &REPLAY_FORMAT_VERSION)?;
+        bincode::serialize_into(&mut writer, header)?;
+        Ok(Recorder {
+            writer,
+            checkpoint_interval: header.checkpoint_interval.max(1),
+        })
+    }
+
+    /// Records an action that was performed while `generation` was the latest generation.
+    pub fn record(&mut self, generation: usize, action: ReplayAction) -> Result<(), ReplayError> {
+        bincode::serialize_into(&mut self.writer, &ReplayEntry::Action { generation, action })?;
+        Ok(())
+    }
+
+    /// Must be called each time a new generation of `uni` is calculated, to record the cells that
+    /// changed. Every `checkpoint_interval` generations, a hash of the live cells is recorded too.
+    pub fn generation_done(&mut self, uni: &Universe) -> Result<(), ReplayError> {
+        let generation = uni.latest_gen();
+        let pattern = frame_pattern(uni.changed_cells()).0;
+        bincode::serialize_into(&mut self.writer, &ReplayEntry::Frame { generation, pattern })?;
+        if generation % self.checkpoint_interval == 0 {
+            let hash = live_cells_hash(uni);
+            bincode::serialize_into(&mut self.writer, &ReplayEntry::Checkpoint { generation, hash })?;
+            self.writer.flush()?;
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<(), ReplayError> {
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// The contents of a replay file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replay {
+    pub header:  ReplayHeader,
+    pub entries: Vec<ReplayEntry>,
+}
+
+impl Replay {
+    /// Reads a whole replay. A truncated final entry, as left behind if the recording client
+    /// crashed, is ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the format version is not `REPLAY_FORMAT_VERSION`, or if the data
+    /// cannot be read or decoded.
+    pub fn read_from<R: Read>(mut reader: R) -> Result<Self, ReplayError> {
+        let version: u32 = bincode::deserialize_from(&mut reader)?;
+        if version != REPLAY_FORMAT_VERSION {
+            return Err(ReplayError::UnsupportedVersion { found: version });
+        }
+        let header: ReplayHeader = bincode::deserialize_from(&mut reader)?;
+        let mut entries = vec![];
+        loop {
+            match bincode::deserialize_from(&mut reader) {
+                Ok(entry) => entries.push(entry),
+                Err(e) => match *e {
+                    bincode::ErrorKind::Io(ref io_err) if io_err.kind() == io::ErrorKind::UnexpectedEof => break,
+                    _ => return Err(e.into()),
+                },
+            }
+        }
+        Ok(Replay { header, entries })
+    }
+}
+
+/// The first generation at which a replayed simulation did not match the recording.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Divergence {
+    pub generation: usize,
+    pub expected:   u64,
+    pub actual:     u64,
+}
+
+/// Feeds the actions of a `Replay` back at the generations they were recorded at, moves the
+/// universe from one recorded generation to the next, and checks the result against the recorded
+/// checkpoints.
+#[derive(Debug)]
+pub struct Playback {
+    header:      ReplayHeader,
+    actions:     Vec<(usize, ReplayAction)>, // (generation, action) in recorded order
+    next_action: usize,                      // index into `actions` of the first one not yet played
+    frames:      HashMap<usize, Pattern>,    // generation -> diff from the generation before
+    checkpoints: HashMap<usize, u64>,        // generation -> live cells hash
+    divergence:  Option<Divergence>,
+}
+
+impl Playback {
+    pub fn new(replay: Replay) -> Self {
+        let mut actions = vec![];
+        let mut frames = HashMap::new();
+        let mut checkpoints = HashMap::new();
+        for entry in replay.entries {
+            match entry {
+                ReplayEntry::Action { generation, action } => actions.push((generation, action)),
+                ReplayEntry::Checkpoint { generation, hash } => {
+                    checkpoints.insert(generation, hash);
+                }
+                ReplayEntry::Frame { generation, pattern } => {
+                    frames.insert(generation, Pattern(pattern));
+                }
+            }
+        }
+        Playback {
+            header: replay.header,
+            actions,
+            next_action: 0,
+            frames,
+            checkpoints,
+            divergence: None,
+        }
+    }
+
+    pub fn header(&self) -> &ReplayHeader {
+        &self.header
+    }
+
+    /// Goes back to the start, to be played again into a universe freshly restored from the header.
+    pub fn rewind(&mut self) {
+        self.next_action = 0;
+        self.divergence = None;
+    }
+
+    /// Returns the not yet played actions that were recorded at or before `generation`, in
+    /// recorded order. They count as played from then on.
+    pub fn take_actions(&mut self, generation: usize) -> Vec<ReplayAction> {
+        let mut due = vec![];
+        while let Some(&(action_gen, ref action)) = self.actions.get(self.next_action) {
+            if action_gen > generation {
+                break;
+            }
+            due.push(action.clone());
+            self.next_action += 1;
+        }
+        due
+    }
+
+    /// Returns true once every recorded action has been played.
+    pub fn is_finished(&self) -> bool {
+        self.next_action >= self.actions.len()
+    }
+
+    /// Moves `uni` on to its next generation as recorded, without calculating it.
+    ///
+    /// # Return value
+    ///
+    /// `Ok(false)`, leaving `uni` as it was, if the recording ends at the latest generation.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the recorded changes are invalid.
+    pub fn play_frame(&self, uni: &mut Universe) -> ConwayResult<bool> {
+        let generation = uni.latest_gen();
+        let pattern = match self.frames.get(&(generation + 1)) {
+            Some(pattern) => pattern.clone(),
+            None => return Ok(false),
+        };
+        let diff = GenStateDiff {
+            gen0: generation,
+            gen1: generation + 1,
+            pattern,
+        };
+        match uni.apply(&diff, None)? {
+            Some(_) => Ok(true),
+            None => Err(ConwayError::InvalidData {
+                reason: format!("Could not play the recorded changes for generation {}", generation + 1),
+            }),
+        }
+    }
+
+    /// Compares the latest generation of `uni` against the recorded checkpoint for it, if any.
+    ///
+    /// # Return value
+    ///
+    /// The divergence, only the first time one is found. Later mismatches are expected once the
+    /// simulation has diverged, so they are not reported.
+    pub fn verify(&mut self, uni: &Universe) -> Option<Divergence> {
+        if self.divergence.is_some() {
+            return None;
+        }
+        let generation = uni.latest_gen();
+        let expected = *self.checkpoints.get(&generation)?;
+        let actual = live_cells_hash(uni);
+        if actual == expected {
+            return None;
+        }
+        self.divergence = Some(Divergence {
+            generation,
+            expected,
+            actual,
+        });
+        self.divergence
+    }
+
+    /// The first generation found not to match the recording, if any.
+    pub fn first_divergence(&self) -> Option<Divergence> {
+        self.divergence
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::one_player_universe;
+
+    const PLAYER_ID: usize = 0;
+
+    /// Records a short session: a blinker, a few generations, and a glider inserted later.
+    fn record_session(uni: &mut Universe) -> Vec<u8> {
+        uni.set_unchecked(10, 10, CellState::Wall);
+        uni.toggle(20, 20, PLAYER_ID).unwrap();
+        let header = ReplayHeader::from_universe(uni, PLAYER_ID, 2);
+        let mut recorder = Recorder::new(vec![], &header).unwrap();
+
+        for &(col, row) in [(30, 5), (31, 5), (32, 5)].iter() {
+            uni.toggle(col, row, PLAYER_ID).unwrap();
+            recorder
+                .record(uni.latest_gen(), ReplayAction::Toggle { col, row })
+                .unwrap();
+        }
+        recorder.record(uni.latest_gen(), ReplayAction::Resume).unwrap();
+        for _ in 0..3 {
+            uni.next();
+            recorder.generation_done(uni).unwrap();
+        }
+        let action = ReplayAction::Insert {
+            left:    2,
+            top:     2,
+            width:   3,
+            height:  3,
+            pattern: "bo$2bo$3o!".to_owned(),
+        };
+        apply_action(uni, PLAYER_ID, &action).unwrap();
+        recorder.record(uni.latest_gen(), action).unwrap();
+        for _ in 0..5 {
+            uni.next();
+            recorder.generation_done(uni).unwrap();
+        }
+        recorder.into_inner()
+    }
+
+    /// Plays back `replay` into a fresh universe for as many generations as were recorded.
+    fn play_back(replay: Replay, generations: usize) -> (Universe, Playback) {
+        let mut uni = one_player_universe(64, 32);
+        replay.header.restore(&mut uni).unwrap();
+        let mut playback = Playback::new(replay);
+        for _ in 0..generations {
+            for action in playback.take_actions(uni.latest_gen()) {
+                apply_action(&mut uni, PLAYER_ID, &action).unwrap();
+            }
+            uni.next();
+            playback.verify(&uni);
+        }
+        (uni, playback)
+    }
+
+    /// Plays back the recorded changes into a fresh universe, without calculating any generations,
+    /// until `generation` or the end of the recording.
+    fn play_frames(playback: &mut Playback, generation: usize) -> Universe {
+        let mut uni = one_player_universe(64, 32);
+        playback.header().restore(&mut uni).unwrap();
+        playback.rewind();
+        while uni.latest_gen() < generation {
+            for action in playback.take_actions(uni.latest_gen()) {
+                apply_action(&mut uni, PLAYER_ID, &action).unwrap();
+            }
+            if !playback.play_frame(&mut uni).unwrap() {
+                break;
+            }
+            playback.verify(&uni);
+        }
+        uni
+    }
+
+    #[test]
+    fn test_frame_pattern() {
+        let cells = vec![
+            (2, 0, CellState::Alive(None)),
+            (3, 0, CellState::Dead),
+            (0, 2, CellState::Wall),
+            (5, 2, CellState::Fog), // left out
+            (1, 3, CellState::Alive(Some(1))),
+        ];
+        assert_eq!(frame_pattern(&cells), Pattern("2\"ob2$W$\"B!".to_owned()));
+        assert_eq!(frame_pattern(&[]), Pattern("!".to_owned()));
+    }
+
+    #[test]
+    fn test_frames_play_back_without_calculating() {
+        let mut recorded_uni = one_player_universe(64, 32);
+        let bytes = record_session(&mut recorded_uni);
+
+        let mut playback = Playback::new(Replay::read_from(&bytes[..]).unwrap());
+        let uni = play_frames(&mut playback, usize::MAX);
+
+        // stops at the end of the recording
+        assert_eq!(uni.latest_gen(), recorded_uni.latest_gen());
+        assert_eq!(live_cells_hash(&uni), live_cells_hash(&recorded_uni));
+        assert!(playback.is_finished());
+        assert_eq!(playback.first_divergence(), None);
+    }
+
+    #[test]
+    fn test_seek_back_after_rewinding() {
+        let mut recorded_uni = one_player_universe(64, 32);
+        let bytes = record_session(&mut recorded_uni);
+        let replay = Replay::read_from(&bytes[..]).unwrap();
+        let start = replay.header.generation;
+
+        let mut playback = Playback::new(replay.clone());
+        play_frames(&mut playback, usize::MAX);
+        let uni = play_frames(&mut playback, start + 5);
+
+        let (calculated, _) = play_back(replay, 5);
+        assert_eq!(uni.latest_gen(), calculated.latest_gen());
+        assert_eq!(live_cells_hash(&uni), live_cells_hash(&calculated));
+        assert_eq!(playback.first_divergence(), None);
+    }
+
+    #[test]
+    fn test_record_then_replay_matches() {
+        let mut recorded_uni = one_player_universe(64, 32);
+        let bytes = record_session(&mut recorded_uni);
+
+        let replay = Replay::read_from(&bytes[..]).unwrap();
+        assert_eq!(replay.header.initial_cells, vec![(10, 10, 'W'), (20, 20, 'A')]);
+        let (uni, playback) = play_back(replay, 8);
+
+        assert!(playback.is_finished());
+        assert_eq!(playback.first_divergence(), None);
+        assert_eq!(uni.latest_gen(), recorded_uni.latest_gen());
+        assert_eq!(live_cells_hash(&uni), live_cells_hash(&recorded_uni));
+    }
+
+    #[test]
+    fn test_replay_reports_first_divergent_generation() {
+        let mut recorded_uni = one_player_universe(64, 32);
+        let bytes = record_session(&mut recorded_uni);
+
+        let mut replay = Replay::read_from(&bytes[..]).unwrap();
+        // Drop the glider, which was inserted at generation 4
+        replay.entries.retain(|entry| {
+            !matches!(
+                entry,
+                ReplayEntry::Action {
+                    action: ReplayAction::Insert { .. },
+                    ..
+                }
+            )
+        });
+        let (_, playback) = play_back(replay, 8);
+
+        let divergence = playback.first_divergence().unwrap();
+        assert_eq!(divergence.generation, 6); // first checkpoint after the insert
+        assert_ne!(divergence.expected, divergence.actual);
+    }
+
+    #[test]
+    fn test_take_actions_returns_due_actions_in_order() {
+        let header = ReplayHeader::from_universe(&one_player_universe(64, 32), PLAYER_ID, 10);
+        let replay = Replay {
+            header,
+            entries: vec![
+                ReplayEntry::Action {
+                    generation: 1,
+                    action:     ReplayAction::Pause,
+                },
+                ReplayEntry::Action {
+                    generation: 1,
+                    action:     ReplayAction::Toggle { col: 1, row: 2 },
+                },
+                ReplayEntry::Checkpoint { generation: 3, hash: 0 },
+                ReplayEntry::Action {
+                    generation: 3,
+                    action:     ReplayAction::Step,
+                },
+            ],
+        };
+        let mut playback = Playback::new(replay);
+        assert_eq!(playback.take_actions(0), vec![]);
+        assert_eq!(
+            playback.take_actions(2),
+            vec![ReplayAction::Pause, ReplayAction::Toggle { col: 1, row: 2 }]
+        );
+        assert!(!playback.is_finished());
+        assert_eq!(playback.take_actions(3), vec![ReplayAction::Step]);
+        assert!(playback.is_finished());
+    }
+
+    #[test]
+    fn test_unsupported_version_is_rejected() {
+        let mut bytes = vec![];
+        bincode::serialize_into(&mut bytes, &(REPLAY_FORMAT_VERSION + 1)).unwrap();
+        match Replay::read_from(&bytes[..]) {
+            Err(ReplayError::UnsupportedVersion { found }) => assert_eq!(found, REPLAY_FORMAT_VERSION + 1),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_truncated_entry_is_ignored() {
+        let mut uni = one_player_universe(64, 32);
+        let mut bytes = record_session(&mut uni);
+        let complete = Replay::read_from(&bytes[..]).unwrap();
+        bytes.pop();
+        let truncated = Replay::read_from(&bytes[..]).unwrap();
+        assert_eq!(truncated.entries.len(), complete.entries.len() - 1);
+    }
+}
@@ -0,0 +1,71 @@
+/*  Copyright 2021 the Conwayste Developers.
+ *
+ *  This file is part of conwayste.
+ *
+ *  conwayste is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  conwayste is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with conwayste.  If not, see
+ *  <http://www.gnu.org/licenses/>. */
+
+//! Fixtures shared by the unit tests of more than one module.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::PathBuf;
+
+use conway::universe::{BigBang, PlayerBuilder, Region, Universe};
+
+/// A `width` by `height` universe, as the server runs it, with one player who can write anywhere.
+pub fn one_player_universe(width: usize, height: usize) -> Universe {
+    BigBang::new()
+        .width(width)
+        .height(height)
+        .server_mode(true)
+        .history(4)
+        .add_players(vec![PlayerBuilder::new(Region::new(0, 0, width, height))])
+        .birth()
+        .unwrap()
+}
+
+/// Every cell of the latest generation that isn't dead, as its column, row, and `to_char` state.
+pub fn live_cells(uni: &Universe) -> BTreeSet<(usize, usize, char)> {
+    let mut cells = BTreeSet::new();
+    uni.each_non_dead_full(None, &mut |col, row, state| {
+        cells.insert((col, row, state.to_char()));
+    });
+    cells
+}
+
+/// A `width` by `height` universe, as the server runs it, split down the middle between two players:
+/// player 0 writes on the left half, and player 1 on the right.
+pub fn two_player_universe(width: usize, height: usize) -> Universe {
+    BigBang::new()
+        .width(width)
+        .height(height)
+        .server_mode(true)
+        .history(4)
+        .add_players(vec![
+            PlayerBuilder::new(Region::new(0, 0, width / 2, height)),
+            PlayerBuilder::new(Region::new((width / 2) as isize, 0, width / 2, height)),
+        ])
+        .birth()
+        .unwrap()
+}
+
+/// An empty directory for a test to write files in, named after `prefix`, this process, and `name`.
+/// Whatever an earlier run left in it is removed.
+pub fn test_dir(prefix: &str, name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("conwayste-{}-{}-{}", prefix, std::process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
@@ -0,0 +1,320 @@
+/*  Copyright 2021 the Conwayste Developers.
+ *
+ *  This file is part of conwayste.
+ *
+ *  conwayste is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  conwayste is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with conwayste.  If not, see
+ *  <http://www.gnu.org/licenses/>. */
+
+//! Color themes for the game area and the rest of the UI.
+//!
+//! The `theme` field of the `[video]` section of the config picks a theme by name: `"dark"` (the
+//! default), `"light"`, or `"custom"`, which uses the colors in the `[theme]` section. Colors are
+//! written as `"#RRGGBB"` or `"#RRGGBBAA"`. Widgets look up the current theme when they draw, so
+//! switching themes takes effect on the next frame.
+
+use std::sync::{Arc, RwLock};
+
+use chromatica::css;
+use ggez::graphics::Color;
+use serde::{Deserialize, Serialize};
+
+use crate::config::Settings;
+use crate::ui::color_with_alpha;
+
+pub const DARK_THEME_NAME: &str = "dark";
+pub const LIGHT_THEME_NAME: &str = "light";
+pub const CUSTOM_THEME_NAME: &str = "custom";
+
+/// Every color that changes with the theme.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Theme {
+    #[serde(with = "hex_color")]
+    pub window_background:   Color, // behind everything, on every screen
+    #[serde(with = "hex_color")]
+    pub universe_background: Color, // behind the grid of cells
+    #[serde(with = "hex_color")]
+    pub cell_dead:           Color,
+    #[serde(with = "hex_color")]
+    pub cell_alive:          Color, // alive, but not owned by any player
+    #[serde(with = "hex_color")]
+    pub cell_player_0:       Color,
+    #[serde(with = "hex_color")]
+    pub cell_player_1:       Color,
+    #[serde(with = "hex_color")]
+    pub cell_wall:           Color,
+    #[serde(with = "hex_color")]
+    pub cell_fog:            Color,
+    #[serde(with = "hex_color")]
+    pub grid_line:           Color, // between cells, if grid lines are turned on
+    #[serde(with = "hex_color")]
+    pub gen_counter:         Color,
+    #[serde(with = "hex_color")]
+    pub menu_text:           Color,
+    #[serde(with = "hex_color")]
+    pub label_text:          Color,
+    #[serde(with = "hex_color")]
+    pub button:              Color,
+    #[serde(with = "hex_color")]
+    pub button_text:         Color,
+    #[serde(with = "hex_color")]
+    pub checkbox_fill:       Color,
+    #[serde(with = "hex_color")]
+    pub focus_highlight:     Color, // border of whatever the mouse is over
+    #[serde(with = "hex_color")]
+    pub border:              Color,
+    #[serde(with = "hex_color")]
+    pub menu_pane_fill:      Color,
+    #[serde(with = "hex_color")]
+    pub chat_pane_fill:      Color,
+    #[serde(with = "hex_color")]
+    pub chat_text:           Color,
+    #[serde(with = "hex_color")]
+    pub input_text:          Color,
+    #[serde(with = "hex_color")]
+    pub input_selection:     Color,
+}
+
+/// Colors that a widget can be told to take from the theme, for widgets such as labels and panes
+/// that are used in more than one role.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThemeColor {
+    MenuText,
+    LabelText,
+    ButtonText,
+    MenuPaneFill,
+    ChatPaneFill,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::dark()
+    }
+}
+
+impl Theme {
+    pub fn dark() -> Self {
+        Theme {
+            window_background:   Color::from(css::BLACK),
+            universe_background: Color::new(0.25, 0.25, 0.25, 1.0),
+            cell_dead:           Color::new(0.875, 0.875, 0.875, 1.0),
+            cell_alive:          Color::from(css::WHITE),
+            cell_player_0:       Color::from(css::RED),
+            cell_player_1:       Color::from(css::BLUE),
+            cell_wall:           Color::new(0.617, 0.55, 0.41, 1.0),
+            cell_fog:            Color::new(0.780, 0.780, 0.780, 1.0),
+            grid_line:           color_with_alpha(css::DIMGRAY, 0.6),
+            gen_counter:         Color::from(css::RED),
+            menu_text:           Color::from(css::WHITE),
+            label_text:          Color::from(css::WHITE),
+            button:              color_with_alpha(css::DARKCYAN, 0.8),
+            button_text:         color_with_alpha(css::WHITE, 0.1),
+            checkbox_fill:       Color::from(css::AZURE),
+            focus_highlight:     Color::from(css::VIOLET),
+            border:              Color::from(css::FIREBRICK),
+            menu_pane_fill:      color_with_alpha(css::DARKSLATEGRAY, 0.9),
+            chat_pane_fill:      color_with_alpha(css::TURQUOISE, 0.33),
+            chat_text:           Color::from(css::DARKRED),
+            input_text:          Color::from(css::DARKRED),
+            input_selection:     color_with_alpha(css::LIGHTSKYBLUE, 0.6),
+        }
+    }
+
+    pub fn light() -> Self {
+        Theme {
+            window_background:   Color::from(css::WHITESMOKE),
+            universe_background: Color::from(css::GAINSBORO),
+            cell_dead:           Color::from(css::WHITE),
+            cell_alive:          Color::from(css::DIMGRAY),
+            cell_player_0:       Color::from(css::CRIMSON),
+            cell_player_1:       Color::from(css::ROYALBLUE),
+            cell_wall:           Color::from(css::SADDLEBROWN),
+            cell_fog:            Color::from(css::SILVER),
+            grid_line:           color_with_alpha(css::SILVER, 0.6),
+            gen_counter:         Color::from(css::DARKRED),
+            menu_text:           Color::from(css::BLACK),
+            label_text:          Color::from(css::BLACK),
+            button:              color_with_alpha(css::STEELBLUE, 0.8),
+            button_text:         Color::from(css::WHITE),
+            checkbox_fill:       Color::from(css::STEELBLUE),
+            focus_highlight:     Color::from(css::DARKORANGE),
+            border:              Color::from(css::DIMGRAY),
+            menu_pane_fill:      color_with_alpha(css::LIGHTSTEELBLUE, 0.95),
+            chat_pane_fill:      color_with_alpha(css::LIGHTBLUE, 0.5),
+            chat_text:           Color::from(css::BLACK),
+            input_text:          Color::from(css::BLACK),
+            input_selection:     color_with_alpha(css::LIGHTSKYBLUE, 0.6),
+        }
+    }
+
+    /// The theme named in the config. An unknown name falls back to the dark theme, with a warning.
+    pub fn from_settings(settings: &Settings) -> Self {
+        match settings.video.theme.as_str() {
+            DARK_THEME_NAME => Theme::dark(),
+            LIGHT_THEME_NAME => Theme::light(),
+            CUSTOM_THEME_NAME => settings.theme.clone(),
+            name => {
+                warn!("Unknown theme {:?}; using the {:?} theme", name, DARK_THEME_NAME);
+                Theme::dark()
+            }
+        }
+    }
+
+    pub fn color(&self, which: ThemeColor) -> Color {
+        match which {
+            ThemeColor::MenuText => self.menu_text,
+            ThemeColor::LabelText => self.label_text,
+            ThemeColor::ButtonText => self.button_text,
+            ThemeColor::MenuPaneFill => self.menu_pane_fill,
+            ThemeColor::ChatPaneFill => self.chat_pane_fill,
+        }
+    }
+}
+
+lazy_static! {
+    static ref CURRENT_THEME: RwLock<Arc<Theme>> = RwLock::new(Arc::new(Theme::dark()));
+}
+
+/// The theme that everything is drawn with.
+pub fn current() -> Arc<Theme> {
+    CURRENT_THEME.read().unwrap().clone() // unwrap OK because set_current can't panic while holding the lock
+}
+
+/// Switches to `theme`, starting with the next frame drawn.
+pub fn set_current(theme: Theme) {
+    *CURRENT_THEME.write().unwrap() = Arc::new(theme); // unwrap OK; see current()
+}
+
+/// Notices when the theme settings in the config change, so that a change made through
+/// `Config::modify` anywhere, such as the Options screen, takes effect without a restart.
+#[derive(Debug, Default)]
+pub struct ThemeWatcher {
+    seen: Option<(String, Theme)>, // the video theme name and the custom theme, as last checked
+}
+
+impl ThemeWatcher {
+    /// The theme to switch to if the theme settings changed since the last call, or `None` if they
+    /// didn't. The first call always returns the theme.
+    pub fn changed(&mut self, settings: &Settings) -> Option<Theme> {
+        if let Some((ref name, ref custom)) = self.seen {
+            if *name == settings.video.theme && *custom == settings.theme {
+                return None;
+            }
+        }
+        self.seen = Some((settings.video.theme.clone(), settings.theme.clone()));
+        Some(Theme::from_settings(settings))
+    }
+}
+
+/// (De)serializes a `Color` as `"#RRGGBBAA"`. The alpha can be left off when deserializing.
+mod hex_color {
+    use ggez::graphics::Color;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(color: &Color, serializer: S) -> Result<S::Ok, S::Error> {
+        let (r, g, b, a) = color.to_rgba();
+        serializer.serialize_str(&format!("#{:02X}{:02X}{:02X}{:02X}", r, g, b, a))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        parse(&s).ok_or_else(|| D::Error::custom(format!("invalid color {:?}; expected \"#RRGGBB[AA]\"", s)))
+    }
+
+    fn parse(s: &str) -> Option<Color> {
+        let hex = s.strip_prefix('#')?;
+        if !(hex.len() == 6 || hex.len() == 8) || !hex.is_ascii() {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+        let alpha = if hex.len() == 8 { channel(6)? } else { 0xFF };
+        Some(Color::from_rgba(channel(0)?, channel(2)?, channel(4)?, alpha))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        for theme in [Theme::dark(), Theme::light()].iter() {
+            let toml_str = toml::to_string(theme).unwrap();
+            let loaded: Theme = toml::from_str(&toml_str).unwrap();
+            // colors are stored to the nearest 1/255, so compare what would be stored
+            assert_eq!(toml::to_string(&loaded).unwrap(), toml_str);
+            assert_eq!(loaded.cell_player_0.to_rgba(), theme.cell_player_0.to_rgba());
+        }
+    }
+
+    #[test]
+    fn test_color_formats() {
+        let mut toml_str = toml::to_string(&Theme::dark()).unwrap();
+        toml_str = toml_str.replace(
+            &format!("cell_wall = {:?}", hex(Theme::dark().cell_wall)),
+            "cell_wall = \"#00ff80\"",
+        );
+        let loaded: Theme = toml::from_str(&toml_str).unwrap();
+        assert_eq!(loaded.cell_wall, Color::from_rgba(0, 0xFF, 0x80, 0xFF));
+
+        let bad_toml_str = toml_str.replace("cell_wall = \"#00ff80\"", "cell_wall = \"green\"");
+        assert!(toml::from_str::<Theme>(&bad_toml_str).is_err());
+    }
+
+    fn hex(color: Color) -> String {
+        let (r, g, b, a) = color.to_rgba();
+        format!("#{:02X}{:02X}{:02X}{:02X}", r, g, b, a)
+    }
+
+    #[test]
+    fn test_from_settings() {
+        let mut settings = Settings::default();
+        assert_eq!(Theme::from_settings(&settings), Theme::dark());
+
+        settings.video.theme = LIGHT_THEME_NAME.to_owned();
+        assert_eq!(Theme::from_settings(&settings), Theme::light());
+
+        settings.video.theme = CUSTOM_THEME_NAME.to_owned();
+        settings.theme.menu_text = Color::from(css::YELLOW);
+        assert_eq!(Theme::from_settings(&settings).menu_text, Color::from(css::YELLOW));
+
+        settings.video.theme = "solarized".to_owned();
+        assert_eq!(Theme::from_settings(&settings), Theme::dark());
+    }
+
+    #[test]
+    fn test_themes_differ() {
+        let (dark, light) = (Theme::dark(), Theme::light());
+        assert_ne!(dark.color(ThemeColor::MenuText), light.color(ThemeColor::MenuText));
+        assert_ne!(dark.window_background, light.window_background);
+    }
+
+    #[test]
+    fn test_theme_watcher() {
+        let mut watcher = ThemeWatcher::default();
+        let mut settings = Settings::default();
+        assert_eq!(watcher.changed(&settings), Some(Theme::dark()));
+        assert_eq!(watcher.changed(&settings), None);
+
+        settings.video.theme = LIGHT_THEME_NAME.to_owned();
+        assert_eq!(watcher.changed(&settings), Some(Theme::light()));
+        assert_eq!(watcher.changed(&settings), None);
+
+        // custom colors count even while another theme is picked, and show up once it is custom
+        settings.theme.cell_dead = Color::from(css::NAVY);
+        assert_eq!(watcher.changed(&settings), Some(Theme::light()));
+        settings.video.theme = CUSTOM_THEME_NAME.to_owned();
+        assert_eq!(watcher.changed(&settings).unwrap().cell_dead, Color::from(css::NAVY));
+        assert_eq!(watcher.changed(&settings), None);
+    }
+}
@@ -0,0 +1,175 @@
+/*  Copyright 2021 the Conwayste Developers.
+ *
+ *  This file is part of conwayste.
+ *
+ *  conwayste is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  conwayste is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with conwayste.  If not, see
+ *  <http://www.gnu.org/licenses/>. */
+
+//! Keeps widgets in the same place relative to a corner of the window when the window is resized.
+
+use std::error::Error;
+
+use ggez::graphics::Rect;
+use ggez::mint::Vector2;
+
+use super::context::{EmitEvent, Event, Handled, Handler, UIContext};
+use super::widget::Widget;
+
+/// The corner of the window that an anchored widget keeps its distance from.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+/// Where a widget of a fixed size goes in a window of any size.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Anchor {
+    pub corner:   Corner,
+    pub margin_x: f32, // from the left or right edge of the window, depending on the corner
+    pub margin_y: f32, // from the top or bottom edge of the window, depending on the corner
+    pub w:        f32,
+    pub h:        f32,
+}
+
+impl Anchor {
+    pub fn new(corner: Corner, margin_x: f32, margin_y: f32, w: f32, h: f32) -> Self {
+        Anchor {
+            corner,
+            margin_x,
+            margin_y,
+            w,
+            h,
+        }
+    }
+
+    /// Returns the widget's rectangle in a window of the given size. If the window is too small
+    /// for the widget and its margins, the widget keeps its size and stays `margin_x` from the left
+    /// and `margin_y` from the top, running off the right or bottom of the window instead.
+    pub fn rect(&self, (win_w, win_h): (f32, f32)) -> Rect {
+        let left = self.margin_x;
+        let top = self.margin_y;
+        let right = f32::max(win_w - self.margin_x - self.w, left);
+        let bottom = f32::max(win_h - self.margin_y - self.h, top);
+        let (x, y) = match self.corner {
+            Corner::TopLeft => (left, top),
+            Corner::TopRight => (right, top),
+            Corner::BottomLeft => (left, bottom),
+            Corner::BottomRight => (right, bottom),
+        };
+        Rect::new(x, y, self.w, self.h)
+    }
+}
+
+/// Returns a Resize handler that moves a widget of type `W` to where `anchor` puts it in the
+/// resized window. Any widgets nested in it are moved along with it, so they should not be
+/// anchored themselves.
+pub fn resize_handler<W: Widget + EmitEvent>(anchor: Anchor) -> Handler {
+    Box::new(move |obj: &mut dyn EmitEvent, uictx: &mut UIContext, event: &Event| {
+        move_to_anchor::<W>(anchor, obj, uictx, event)
+    })
+}
+
+fn move_to_anchor<W: Widget + EmitEvent>(
+    anchor: Anchor,
+    obj: &mut dyn EmitEvent,
+    uictx: &mut UIContext,
+    event: &Event,
+) -> Result<Handled, Box<dyn Error>> {
+    let window_size = event
+        .window_size
+        .ok_or_else(|| -> Box<dyn Error> { "resize event has no window size".into() })?;
+    let widget = obj.downcast_mut::<W>().unwrap(); // unwrap OK because only registered on a W
+    let old_rect = widget.rect();
+    let new_rect = anchor.rect(window_size);
+    let offset = Vector2 {
+        x: new_rect.x - old_rect.x,
+        y: new_rect.y - old_rect.y,
+    };
+    widget.translate(offset);
+    translate_nested(uictx, offset)?;
+    Ok(Handled::NotHandled)
+}
+
+/// Moves every widget in `uictx`'s view by `offset`.
+fn translate_nested(uictx: &mut UIContext, offset: Vector2<f32>) -> Result<(), Box<dyn Error>> {
+    for child_id in uictx.widget_view.children_ids() {
+        let (widget_ref, mut subuictx) = uictx.derive(&child_id)?;
+        widget_ref.translate(offset);
+        translate_nested(&mut subuictx, offset)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn anchor(corner: Corner) -> Anchor {
+        Anchor::new(corner, 30.0, 40.0, 300.0, 175.0)
+    }
+
+    #[test]
+    fn test_corners() {
+        let size = (1200.0, 800.0);
+        assert_eq!(anchor(Corner::TopLeft).rect(size), Rect::new(30.0, 40.0, 300.0, 175.0));
+        assert_eq!(
+            anchor(Corner::TopRight).rect(size),
+            Rect::new(870.0, 40.0, 300.0, 175.0)
+        );
+        assert_eq!(
+            anchor(Corner::BottomLeft).rect(size),
+            Rect::new(30.0, 585.0, 300.0, 175.0)
+        );
+        assert_eq!(
+            anchor(Corner::BottomRight).rect(size),
+            Rect::new(870.0, 585.0, 300.0, 175.0)
+        );
+    }
+
+    #[test]
+    fn test_follows_the_window_size() {
+        let bottom_left = anchor(Corner::BottomLeft);
+        assert_eq!(bottom_left.rect((640.0, 480.0)), Rect::new(30.0, 265.0, 300.0, 175.0));
+        assert_eq!(bottom_left.rect((1920.0, 1080.0)), Rect::new(30.0, 865.0, 300.0, 175.0));
+
+        let top_right = anchor(Corner::TopRight);
+        assert_eq!(top_right.rect((640.0, 480.0)), Rect::new(310.0, 40.0, 300.0, 175.0));
+        assert_eq!(top_right.rect((1920.0, 1080.0)), Rect::new(1590.0, 40.0, 300.0, 175.0));
+    }
+
+    #[test]
+    fn test_tiny_window_keeps_size_and_stays_on_screen() {
+        for &corner in [
+            Corner::TopLeft,
+            Corner::TopRight,
+            Corner::BottomLeft,
+            Corner::BottomRight,
+        ]
+        .iter()
+        {
+            for &size in [(100.0, 50.0), (0.0, 0.0)].iter() {
+                assert_eq!(anchor(corner).rect(size), Rect::new(30.0, 40.0, 300.0, 175.0));
+            }
+        }
+
+        // only too short, so it still sticks to the right
+        assert_eq!(
+            anchor(Corner::BottomRight).rect((1200.0, 100.0)),
+            Rect::new(870.0, 40.0, 300.0, 175.0)
+        );
+    }
+}
@@ -18,10 +18,8 @@
 use std::error::Error;
 use std::fmt;
 
-use chromatica::css;
-
 use ggez::event::MouseButton;
-use ggez::graphics::{self, Color, DrawMode, DrawParam, Rect};
+use ggez::graphics::{self, DrawMode, DrawParam, Rect};
 use ggez::input::keyboard::KeyCode;
 use ggez::mint::{Point2, Vector2};
 use ggez::{Context, GameResult};
@@ -29,19 +27,19 @@ use ggez::{Context, GameResult};
 use id_tree::NodeId;
 
 use super::{
-    common::{center, color_with_alpha, FontInfo},
+    common::{center, FontInfo},
     context,
     context::{EmitEvent, Event, EventType, Handled, KeyCodeOrChar, MoveCross, UIContext},
     label::Label,
     widget::Widget,
     UIError, UIResult,
 };
+use crate::theme::{self, ThemeColor};
 
 pub struct Button {
     id:               Option<NodeId>,
     z_index:          usize,
     pub label:        Label,
-    pub button_color: Color,
     pub draw_mode:    DrawMode,
     pub dimensions:   Rect,
     pub hover:        bool, // is mouse hovering over this?
@@ -94,13 +92,14 @@ impl Button {
     pub fn new(ctx: &mut Context, font_info: FontInfo, button_text: String) -> Self {
         // label positioning defined an offset to button origin after centering
         let label_position = Point2 { x: 0.0, y: 0.0 };
-        let label = Label::new(
+        let mut label = Label::new(
             ctx,
             font_info,
             button_text,
-            color_with_alpha(css::WHITE, 0.1),
+            theme::current().button_text,
             label_position,
         );
+        label.set_theme_color(ThemeColor::ButtonText);
         let label_dims = label.rect();
 
         let dimensions = Rect::new(
@@ -114,7 +113,6 @@ impl Button {
             id: None,
             z_index: std::usize::MAX,
             label,
-            button_color: color_with_alpha(css::DARKCYAN, 0.8),
             draw_mode: DrawMode::fill(),
             dimensions,
             hover: false,
@@ -139,6 +137,18 @@ impl Button {
         b
     }
 
+    /// Changes the button's text, keeping it centered. The button's size stays the same.
+    pub fn set_text(&mut self, ctx: &mut Context, text: String) {
+        self.label.set_text(ctx, text);
+        self.center_label_text();
+    }
+
+    /// Gets the button's text.
+    #[allow(unused)]
+    pub fn text(&self) -> &str {
+        self.label.text()
+    }
+
     /// Centers the label's text to the dimensions of the button
     fn center_label_text(&mut self) {
         let text_dims = self.label.rect();
@@ -224,7 +234,7 @@ impl Widget for Button {
             DrawMode::stroke(2.0)
         };
 
-        let button = graphics::Mesh::new_rectangle(ctx, draw_mode, self.dimensions, self.button_color)?;
+        let button = graphics::Mesh::new_rectangle(ctx, draw_mode, self.dimensions, theme::current().button)?;
         graphics::draw(ctx, &button, DrawParam::default())?;
 
         self.label.draw(ctx)?;
@@ -238,7 +248,7 @@ impl Widget for Button {
 
     fn set_rect(&mut self, new_dims: Rect) -> UIResult<()> {
         if new_dims.w == 0.0 || new_dims.h == 0.0 {
-            return Err(Box::new(UIError::InvalidDimensions {
+            return Err(Box::new(UIError::InvalidRect {
                 reason: format!("Cannot set the width or height of a Button {:?} to zero", self.id()),
             }));
         }
@@ -246,7 +256,7 @@ impl Widget for Button {
         if new_dims.w < self.label.dimensions.w + BUTTON_LABEL_PADDING_W
             || new_dims.h < self.label.dimensions.h + BUTTON_LABEL_PADDING_H
         {
-            return Err(Box::new(UIError::InvalidDimensions {
+            return Err(Box::new(UIError::InvalidRect {
                 reason: format!(
                     "Cannot set the Button's size {}x{} smaller than the space taken by the
                     button's text ({:?} size {}x{}): {:?}",
@@ -282,14 +292,14 @@ impl Widget for Button {
 
     fn set_size(&mut self, w: f32, h: f32) -> UIResult<()> {
         if w == 0.0 || h == 0.0 {
-            return Err(Box::new(UIError::InvalidDimensions {
+            return Err(Box::new(UIError::InvalidRect {
                 reason: format!("Cannot set the width or height of Button {:?} to zero", self.id()),
             }));
         }
 
         if w < self.label.dimensions.w + BUTTON_LABEL_PADDING_W || h < self.label.dimensions.h + BUTTON_LABEL_PADDING_H
         {
-            return Err(Box::new(UIError::InvalidDimensions {
+            return Err(Box::new(UIError::InvalidRect {
                 reason: format!(
                     "Cannot set the width or height of Button {:?} smaller than
                     the space taken by the button's text",
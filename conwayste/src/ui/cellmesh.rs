@@ -0,0 +1,297 @@
+/*  Copyright 2021 the Conwayste Developers.
+ *
+ *  This file is part of conwayste.
+ *
+ *  conwayste is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  conwayste is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with conwayste.  If not, see
+ *  <http://www.gnu.org/licenses/>. */
+
+//! Batches the non-dead cells on screen into a single mesh, so that the whole universe is drawn in
+//! one draw call. Building the mesh only needs the `Context` at the very end, so the batching
+//! itself can be tested and benchmarked without a window.
+
+use std::collections::{BTreeMap, HashSet};
+
+use ggez::graphics::{DrawMode, Mesh, MeshBuilder, Rect};
+use ggez::mint::Point2;
+use ggez::{Context, GameResult};
+
+use conway::universe::{CellState, Region, Universe};
+
+use super::gamearea::ColorSettings;
+use crate::viewport::{Cell, GridView};
+
+/// The on-screen rectangles of the visible non-dead cells, grouped by cell state so that cells of
+/// the same color end up next to each other in the mesh.
+#[derive(Debug, Default)]
+pub struct CellBatches {
+    groups: BTreeMap<CellState, Vec<Rect>>,
+}
+
+impl CellBatches {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Collects the non-dead cells of `uni` that are on screen in `view`, as seen by `visibility`.
+    /// Cells whose (col, row) is in `skip` are left out.
+    pub fn from_universe(
+        uni: &Universe,
+        view: &GridView,
+        visibility: Option<usize>,
+        skip: &HashSet<(usize, usize)>,
+    ) -> Self {
+        let mut batches = CellBatches::new();
+        let region = match visible_region(view).and_then(|r| r.intersection(uni.region())) {
+            Some(region) => region,
+            None => return batches,
+        };
+        uni.each_non_dead(region, visibility, &mut |col, row, state| {
+            if skip.contains(&(col, row)) {
+                return;
+            }
+            // Clipped to the view, and None if a cell on the border doesn't actually show
+            if let Some(rect) = view.window_coords_from_game(Cell::new(col, row)) {
+                batches.add(state, rect);
+            }
+        });
+        batches
+    }
+
+    pub fn add(&mut self, state: CellState, rect: Rect) {
+        self.groups.entry(state).or_insert_with(Vec::new).push(rect);
+    }
+
+    /// Total number of cells in all groups.
+    pub fn len(&self) -> usize {
+        self.groups.values().map(|rects| rects.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    /// Iterates over the groups in a stable order, each with every rectangle of that cell state.
+    pub fn groups(&self) -> impl Iterator<Item = (CellState, &[Rect])> {
+        self.groups.iter().map(|(&state, rects)| (state, rects.as_slice()))
+    }
+
+    /// Adds every cell to `builder` in the color for its state, one group after another.
+    pub fn add_to_builder(&self, builder: &mut MeshBuilder, color_settings: &ColorSettings) -> GameResult<()> {
+        for (state, rects) in self.groups() {
+            let color = color_settings.get_color(Some(state));
+            for &rect in rects {
+                builder.rectangle(DrawMode::fill(), rect, color)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Builds a single mesh of every cell, or `None` if there are none (ggez refuses to build an
+    /// empty mesh).
+    pub fn build_mesh(&self, ctx: &mut Context, color_settings: &ColorSettings) -> GameResult<Option<Mesh>> {
+        if self.is_empty() {
+            return Ok(None);
+        }
+        let mut builder = MeshBuilder::new();
+        self.add_to_builder(&mut builder, color_settings)?;
+        Ok(Some(builder.build(ctx)?))
+    }
+}
+
+/// The cells at least partly on screen in `view`, or `None` if the view has no area. Not clipped
+/// to the universe.
+fn visible_region(view: &GridView) -> Option<Region> {
+    let cells = view.visible_cells();
+    let left = cells.x.floor();
+    let top = cells.y.floor();
+    let right = (cells.x + cells.w).ceil();
+    let bottom = (cells.y + cells.h).ceil();
+    if right <= left || bottom <= top {
+        return None;
+    }
+    Some(Region::new(
+        left as isize,
+        top as isize,
+        (right - left) as usize,
+        (bottom - top) as usize,
+    ))
+}
+
+/// What the cached cell mesh was built from. If any of it changes, the mesh must be rebuilt.
+/// Changes to the universe that don't calculate a new generation aren't covered, so whoever makes
+/// them must also drop the cached mesh.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CellMeshKey {
+    pub generation: usize,
+    pub view_rect:  Rect,
+    pub origin:     Point2<f32>,
+    pub cell_size:  f32,
+    pub skipped:    HashSet<(usize, usize)>,
+}
+
+impl CellMeshKey {
+    pub fn new(uni: &Universe, view: &GridView, skipped: HashSet<(usize, usize)>) -> Self {
+        CellMeshKey {
+            generation: uni.latest_gen(),
+            view_rect: view.get_rect(),
+            origin: view.get_origin(),
+            cell_size: view.get_cell_size(),
+            skipped,
+        }
+    }
+}
+
+/// A cell mesh along with what it was built from.
+#[derive(Debug)]
+pub struct CachedCellMesh {
+    pub key:  CellMeshKey,
+    pub mesh: Option<Mesh>, // None if there were no cells to draw
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::two_player_universe;
+    use rand::{Rng, SeedableRng};
+    use std::time::Instant;
+
+    const WIDTH: usize = 256;
+    const HEIGHT: usize = 120;
+
+    /// A universe where about half the cells are alive, split between the two players.
+    fn dense_random_universe() -> Universe {
+        let mut uni = two_player_universe(WIDTH, HEIGHT);
+        let mut rng = rand::rngs::StdRng::seed_from_u64(1311);
+        for row in 0..HEIGHT {
+            for col in 0..WIDTH {
+                if rng.gen::<bool>() {
+                    let player_id = if col < WIDTH / 2 { 0 } else { 1 };
+                    uni.set(col, row, CellState::Alive(Some(player_id)), player_id);
+                }
+            }
+        }
+        uni
+    }
+
+    fn new_view(cell_size: f32) -> GridView {
+        let mut view = GridView::new(cell_size, WIDTH, HEIGHT);
+        view.set_rect(Rect::new(0.0, 0.0, 1024.0, 768.0));
+        view
+    }
+
+    #[test]
+    fn test_cells_grouped_by_player() {
+        let mut uni = two_player_universe(WIDTH, HEIGHT);
+        uni.set(1, 1, CellState::Alive(Some(0)), 0);
+        uni.set(2, 1, CellState::Alive(Some(0)), 0);
+        uni.set(WIDTH / 2 + 1, 1, CellState::Alive(Some(1)), 1);
+        let view = new_view(5.0);
+
+        let batches = CellBatches::from_universe(&uni, &view, None, &HashSet::new());
+        let groups: Vec<_> = batches.groups().map(|(state, rects)| (state, rects.len())).collect();
+        assert_eq!(
+            groups,
+            vec![(CellState::Alive(Some(0)), 2), (CellState::Alive(Some(1)), 1)]
+        );
+        assert_eq!(batches.len(), 3);
+    }
+
+    #[test]
+    fn test_offscreen_cells_are_culled() {
+        let mut uni = two_player_universe(WIDTH, HEIGHT);
+        uni.set(0, 0, CellState::Alive(Some(0)), 0);
+        uni.set(WIDTH - 1, HEIGHT - 1, CellState::Alive(Some(1)), 1);
+        let mut view = new_view(10.0);
+        view.set_origin(Point2 { x: 0.0, y: 0.0 });
+
+        // only the top-left corner of the universe fits on screen at this zoom
+        let batches = CellBatches::from_universe(&uni, &view, None, &HashSet::new());
+        let groups: Vec<_> = batches.groups().map(|(state, rects)| (state, rects.to_vec())).collect();
+        assert_eq!(
+            groups,
+            vec![(CellState::Alive(Some(0)), vec![Rect::new(0.0, 0.0, 9.0, 9.0)])]
+        );
+    }
+
+    #[test]
+    fn test_skipped_cells_are_left_out() {
+        let mut uni = two_player_universe(WIDTH, HEIGHT);
+        uni.set(1, 1, CellState::Alive(Some(0)), 0);
+        uni.set(2, 1, CellState::Alive(Some(0)), 0);
+        let view = new_view(5.0);
+
+        let skip = vec![(2, 1)].into_iter().collect();
+        let batches = CellBatches::from_universe(&uni, &view, None, &skip);
+        assert_eq!(batches.len(), 1);
+    }
+
+    #[test]
+    fn test_empty_universe() {
+        let uni = two_player_universe(WIDTH, HEIGHT);
+        let batches = CellBatches::from_universe(&uni, &new_view(5.0), None, &HashSet::new());
+        assert!(batches.is_empty());
+    }
+
+    /// Compares the old way of drawing cells, where every non-dead cell in the universe was looked
+    /// up and turned into a `DrawParam` for a sprite batch, with batching only the visible cells
+    /// into a mesh. Only the CPU side can be measured without a window, so this excludes the draw
+    /// calls themselves. Run with `cargo test --release -- --ignored bench_cell_batching --nocapture`.
+    #[test]
+    #[ignore]
+    fn bench_cell_batching() {
+        use ggez::graphics::{Color, DrawParam};
+        use ggez::mint::Vector2;
+
+        const FRAMES: u32 = 100;
+        let uni = dense_random_universe();
+        let color_settings = ColorSettings::from_theme(&crate::theme::Theme::dark());
+
+        for &cell_size in &[5.0, 10.0, 40.0] {
+            let view = new_view(cell_size);
+
+            let start = Instant::now();
+            let mut params = vec![];
+            for _ in 0..FRAMES {
+                params.clear();
+                uni.each_non_dead_full(None, &mut |col, row, state| {
+                    if let Some(rect) = view.window_coords_from_game(Cell::new(col, row)) {
+                        let color: Color = color_settings.get_color(Some(state));
+                        params.push(
+                            DrawParam::new()
+                                .dest(Point2 { x: rect.x, y: rect.y })
+                                .scale(Vector2 { x: rect.w, y: rect.h })
+                                .color(color),
+                        );
+                    }
+                });
+            }
+            let sprite_batch_time = start.elapsed() / FRAMES;
+
+            let start = Instant::now();
+            let mut cells = 0;
+            for _ in 0..FRAMES {
+                let batches = CellBatches::from_universe(&uni, &view, None, &HashSet::new());
+                let mut builder = MeshBuilder::new();
+                batches.add_to_builder(&mut builder, &color_settings).unwrap();
+                cells = batches.len();
+            }
+            let mesh_time = start.elapsed() / FRAMES;
+
+            println!(
+                "cell size {}: {} cells on screen; sprite batch {:?}/frame, mesh {:?}/frame",
+                cell_size, cells, sprite_batch_time, mesh_time
+            );
+        }
+    }
+}
@@ -19,36 +19,157 @@
 use std::collections::VecDeque;
 use std::error::Error;
 use std::fmt;
+use std::mem;
 use std::sync::mpsc::{channel, Receiver, Sender};
 
-use ggez::graphics::{self, Color, DrawMode, DrawParam, FilterMode, Rect, Text};
+use chrono::{DateTime, Local};
+use ggez::graphics::{self, Color, DrawMode, DrawParam, FilterMode, Rect, Text, TextFragment};
 use ggez::mint::{Point2, Vector2};
 use ggez::{Context, GameResult};
 
 use id_tree::NodeId;
+use unicode_segmentation::UnicodeSegmentation;
 
 use super::{
+    clipboard::Clipboard,
     common::FontInfo,
     context::{EmitEvent, Event, EventType, Handled, HandlerData, MoveCross, UIContext},
+    contextmenu::{ContextMenu, MenuItem},
     widget::Widget,
     UIError, UIResult,
 };
 
 use crate::constants::{self, colors::*};
+use crate::theme;
+
+/// How the time a message arrived is shown before it.
+const TIMESTAMP_FORMAT: &str = "%H:%M";
+
+/// The name the server uses for the notices it sends as chat messages.
+const SERVER_SENDER: &str = "Server";
+
+/// A message whose prefix (its time and sender) takes up more than this fraction of the chatbox's
+/// width has its prefix on a line of its own, so that a long name can't squeeze the message into a
+/// narrow column.
+const MAX_PREFIX_FRACTION: f32 = 0.5;
+
+/// How many spaces' worth to indent a message whose prefix is on a line of its own.
+const OWN_LINE_PREFIX_INDENT: f32 = 2.0;
+
+/// Whether a message was said by a player, or is a notice such as a player joining or leaving.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MessageKind {
+    Chat,
+    System,
+}
+
+/// One message in the chat.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChatEntry {
+    pub timestamp: DateTime<Local>,
+    pub sender:    Option<String>, // None for system messages
+    pub body:      String,
+    pub kind:      MessageKind,
+}
+
+impl ChatEntry {
+    /// A message said by the player named `sender`, arriving now.
+    pub fn chat(sender: String, body: String) -> Self {
+        ChatEntry {
+            timestamp: Local::now(),
+            sender: Some(sender),
+            body,
+            kind: MessageKind::Chat,
+        }
+    }
+
+    /// A notice from the server or the game, arriving now.
+    pub fn system(body: String) -> Self {
+        ChatEntry {
+            timestamp: Local::now(),
+            sender: None,
+            body,
+            kind: MessageKind::System,
+        }
+    }
+
+    /// The message as one line of text, such as "alice: hello", for copying.
+    fn plain_text(&self) -> String {
+        match self.sender {
+            Some(ref sender) => format!("{}: {}", sender, self.body),
+            None => self.body.clone(),
+        }
+    }
+
+    /// The pieces of text drawn before the body, each in its own color: the time the message
+    /// arrived if `show_timestamp` is set, then the sender's name.
+    fn prefix_parts(&self, show_timestamp: bool) -> Vec<(String, Color)> {
+        let mut parts = vec![];
+        if show_timestamp {
+            parts.push((
+                self.timestamp.format(TIMESTAMP_FORMAT).to_string(),
+                *CHAT_TIMESTAMP_COLOR,
+            ));
+        }
+        if let Some(ref sender) = self.sender {
+            parts.push((format!("{}:", sender), sender_color(sender)));
+        }
+        parts
+    }
+}
+
+/// For callers that have a message as a single string, such as "alice: hello". A string with no
+/// sender, or with the server as its sender, becomes a system message.
+impl From<String> for ChatEntry {
+    fn from(msg: String) -> Self {
+        match msg.find(": ") {
+            Some(i) if &msg[..i] == SERVER_SENDER => ChatEntry::system(msg[i + 2..].to_owned()),
+            Some(i) if i > 0 => ChatEntry::chat(msg[..i].to_owned(), msg[i + 2..].to_owned()),
+            _ => ChatEntry::system(msg),
+        }
+    }
+}
+
+/// The color the name `sender` is drawn in. A name always gets the same color, so players can be
+/// told apart at a glance.
+fn sender_color(sender: &str) -> Color {
+    CHAT_SENDER_COLORS[sender_color_index(sender)]
+}
+
+/// Which of `CHAT_SENDER_COLORS` belongs to `sender`. This is an FNV-1a hash of the name, since
+/// the standard library's hasher is randomly seeded.
+fn sender_color_index(sender: &str) -> usize {
+    let hash = sender.bytes().fold(0xcbf2_9ce4_8422_2325u64, |hash, b| {
+        (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+    });
+    (hash % CHAT_SENDER_COLORS.len() as u64) as usize
+}
+
+/// One line of a wrapped message, ready to be drawn.
+struct WrappedLine {
+    prefix:  Option<Text>, // the message's time and sender, on its first line
+    body:    Text,
+    indent:  f32, // how far right of the prefix's left edge `body` starts
+    kind:    MessageKind,
+    message: usize, // index of the message in `messages` that this line is part of
+}
 
 pub struct Chatbox {
-    id:            Option<NodeId>,
-    z_index:       usize,
-    history_lines: usize,
-    color:         Color,
-    messages:      VecDeque<String>,
-    wrapped:       VecDeque<(bool, Text)>,
-    dimensions:    Rect,
-    hover:         bool,
-    font_info:     FontInfo,
-    msg_sender:    Sender<String>,
-    msg_receiver:  Receiver<String>,
-    handler_data:  HandlerData,
+    id:              Option<NodeId>,
+    z_index:         usize,
+    history_lines:   usize,
+    messages:        VecDeque<ChatEntry>,
+    wrapped:         VecDeque<WrappedLine>, // lines of `messages`, wrapped to fit; rebuilt when drawn if stale
+    needs_reflow:    bool,
+    show_timestamps: bool,
+    dimensions:      Rect,
+    hover:           bool,
+    font_info:       FontInfo,
+    msg_sender:      Sender<ChatEntry>,
+    msg_receiver:    Receiver<ChatEntry>,
+    cleared_at:      Option<DateTime<Local>>, // messages from before this are not restored
+    clipboard:       Clipboard,
+    handler_data:    HandlerData,
 }
 
 impl fmt::Debug for Chatbox {
@@ -83,19 +204,22 @@ impl Chatbox {
     pub fn new(font_info: FontInfo, history_lines: usize) -> Self {
         // TODO: affix to bottom left corner once "anchoring"/"gravity" is implemented
         let rect = *constants::DEFAULT_CHATBOX_RECT;
-        let (msg_tx, msg_rx) = channel::<String>();
+        let (msg_tx, msg_rx) = channel::<ChatEntry>();
         let mut chatbox = Chatbox {
             id: None,
             z_index: std::usize::MAX,
             history_lines,
-            color: *CHATBOX_BORDER_COLOR,
             messages: VecDeque::with_capacity(history_lines),
             wrapped: VecDeque::new(),
+            needs_reflow: false,
+            show_timestamps: false,
             dimensions: rect,
             hover: false,
             font_info,
             msg_sender: msg_tx,
             msg_receiver: msg_rx,
+            cleared_at: None,
+            clipboard: Clipboard::new(),
             handler_data: HandlerData::new(),
         };
         chatbox
@@ -105,6 +229,9 @@ impl Chatbox {
             .on(EventType::MouseMove, Box::new(Chatbox::mouse_move_handler))
             .unwrap(); // unwrap OK b/c not being called within handler
         chatbox
+            .on(EventType::RightClick, Box::new(Chatbox::context_menu_handler))
+            .unwrap(); // unwrap OK b/c not being called within handler
+        chatbox
     }
 
     /// Returns a handle that enables you to asynchronously publish messages to this chatbox.
@@ -147,10 +274,61 @@ impl Chatbox {
         Ok(Handled::NotHandled)
     }
 
+    /// Opens a menu for copying the message that was right-clicked, or clearing every message.
+    fn context_menu_handler(
+        obj: &mut dyn EmitEvent,
+        uictx: &mut UIContext,
+        event: &Event,
+    ) -> Result<Handled, Box<dyn Error>> {
+        let chatbox = obj.downcast_mut::<Chatbox>().unwrap(); // unwrap OK because it's always a Chatbox
+        let id = match chatbox.id {
+            Some(ref id) => id.clone(),
+            None => return Ok(Handled::NotHandled),
+        };
+        let point = event.point.unwrap(); // unwrap OK because a mouse event always has a point
+        let mut items = vec![];
+        if let Some(index) = chatbox.message_at(point) {
+            let text = chatbox.messages[index].plain_text();
+            items.push(MenuItem::new(
+                "Copy message",
+                Box::new(move |obj: &mut dyn EmitEvent, _uictx: &mut UIContext, _event: &Event| {
+                    let chatbox = obj.downcast_mut::<Chatbox>().unwrap(); // unwrap OK; the menu is a Chatbox's
+                    chatbox.clipboard.set_contents(text.clone());
+                    Ok(Handled::Handled)
+                }),
+            ));
+        }
+        items.push(MenuItem::new(
+            "Clear history",
+            Box::new(Chatbox::clear_history_item_handler),
+        ));
+        uictx.open_context_menu(point, ContextMenu::new(id, chatbox.font_info, items));
+        Ok(Handled::Handled)
+    }
+
+    fn clear_history_item_handler(
+        obj: &mut dyn EmitEvent,
+        _uictx: &mut UIContext,
+        _event: &Event,
+    ) -> Result<Handled, Box<dyn Error>> {
+        let chatbox = obj.downcast_mut::<Chatbox>().unwrap(); // unwrap OK because it's always a Chatbox
+        chatbox.clear_history();
+        Ok(Handled::Handled)
+    }
+
+    /// Sets whether each message is shown with the time it arrived.
+    pub fn set_show_timestamps(&mut self, show_timestamps: bool) {
+        if self.show_timestamps != show_timestamps {
+            self.show_timestamps = show_timestamps;
+            self.needs_reflow = true;
+        }
+    }
+
     /// Adds a message to the chatbox
     ///
     /// # Arguments
-    /// * `msg` - New chat message
+    /// * `msg` - New chat message. A plain `String` is converted as described for `ChatEntry`'s
+    ///   `From<String>`.
     ///
     /// # Examples
     ///
@@ -161,8 +339,8 @@ impl Chatbox {
     /// let font = Font::Default;
     /// let font_info = common::FontInfo::new(ctx, font, Some(20.0));
     /// let mut chatbox = Chatbox::new(SOME_CHATBOX_WIDGET_ID, font_info, 200);
-    /// chatbox.add_message(String::new("Player 1: This is a new chat message");
-    /// chatbox.add_message(String::new("-- This is a Server broadcast message -- ");
+    /// chatbox.add_message(ChatEntry::chat("Player 1".to_owned(), "This is a new chat message".to_owned()));
+    /// chatbox.add_message(ChatEntry::system("This is a Server broadcast message".to_owned()));
     /// chatbox.set_rect(chatbox_rect);
     /// chatpane.add(Box::new(chatbox));
     ///
@@ -170,111 +348,200 @@ impl Chatbox {
     /// chatbox.draw(ctx);
     /// ```
     ///
-    pub fn add_message(&mut self, msg: String) {
-        let mut texts = Chatbox::reflow_message(&msg, self.dimensions.w, &self.font_info);
-        self.wrapped.append(&mut texts);
+    pub fn add_message<M: Into<ChatEntry>>(&mut self, msg: M) {
+        self.messages.push_back(msg.into());
 
-        self.messages.push_back(msg);
-
-        // Remove any message(s) that exceed the alloted history. Any wrapped texts created from the
-        // message(s) also need to be removed
+        // Remove any message(s) that exceed the alloted history
         while self.messages.len() > self.history_lines {
             self.messages.pop_front();
+        }
 
-            let mut count = 0;
-            for (has_more, _) in self.wrapped.iter() {
-                if *has_more {
-                    count += 1;
-                } else {
-                    break;
-                }
-            }
-            for _ in 0..count + 1 {
-                self.wrapped.remove(0);
+        // Wrapping needs the `Context` to measure the text, so it waits until the next draw
+        self.needs_reflow = true;
+    }
+
+    /// Replaces every message with those in `history`, oldest first. Messages published through a
+    /// `ChatboxPublishHandle` but not yet received are dropped, since they are expected to be in
+    /// `history` already.
+    pub fn restore_history(&mut self, history: &ChatHistory) {
+        while self.msg_receiver.try_recv().is_ok() {}
+        self.messages.clear();
+        self.wrapped.clear();
+        let cleared_at = self.cleared_at;
+        for msg in history.iter() {
+            if cleared_at.map_or(true, |cleared_at| msg.timestamp > cleared_at) {
+                self.add_message(msg.clone());
             }
         }
     }
 
-    fn reflow_messages(&mut self) {
+    /// Removes every message, including any published but not yet received. They are left out
+    /// when the history is restored, too.
+    pub fn clear_history(&mut self) {
+        while self.msg_receiver.try_recv().is_ok() {}
+        self.messages.clear();
         self.wrapped.clear();
-        for msg in self.messages.iter_mut() {
-            let mut texts = Chatbox::reflow_message(msg, self.dimensions.w, &self.font_info);
-            self.wrapped.append(&mut texts);
-        }
+        self.cleared_at = Some(Local::now());
     }
 
-    fn count_chars(msg: &str) -> usize {
-        let mut count = 0;
-        for _ in msg.chars() {
-            count += 1;
+    /// The index in `messages` of the message with a line drawn at `point`, if any.
+    fn message_at(&self, point: Point2<f32>) -> Option<usize> {
+        if self.needs_reflow || !self.dimensions.contains(point) {
+            return None; // the lines are out of date until the next draw
+        }
+        let line_height = self.font_info.char_dimensions.y;
+        let max_lines = (self.dimensions.h / (line_height + constants::CHATBOX_LINE_SPACING)) as usize;
+        // lines are drawn up from the bottom, as in `draw`
+        let lines_up = ((self.dimensions.bottom() - point.y) / line_height) as usize;
+        if lines_up >= max_lines || lines_up >= self.wrapped.len() {
+            return None;
         }
-        count
+        Some(self.wrapped[self.wrapped.len() - 1 - lines_up].message)
     }
 
-    /// Breaks the message up into segments that are at most `width` long for the provided `font_info`
-    fn reflow_message(msg: &str, width: f32, font_info: &FontInfo) -> VecDeque<(bool, Text)> {
-        let mut texts = VecDeque::new();
-        let max_chars_per_line = (width / font_info.char_dimensions.x) as usize;
-        let mut s = String::with_capacity(max_chars_per_line);
-
-        let mut chars_added = 0;
-        for word in msg.split_whitespace() {
-            let word_chars = Chatbox::count_chars(word);
-
-            // If the word can fit on the next line, but not the current line
-            if chars_added != 0 && chars_added + word_chars > max_chars_per_line && word_chars <= max_chars_per_line {
-                let mut text = Text::new(s.clone());
-                font_info.apply(&mut text);
-                texts.push_back((true, text));
-                s.clear();
-                chars_added = 0;
-            }
+    /// The width available for text, inside the border.
+    fn text_width(&self) -> f32 {
+        self.dimensions.w - 2.0 * (constants::CHATBOX_BORDER_PIXELS + 1.0)
+    }
 
-            if word_chars > max_chars_per_line {
-                // If word is too long to fit on a line, then break the word into multiple lines
-                for ch in word.chars() {
-                    if chars_added == max_chars_per_line {
-                        let mut text = Text::new(s.clone());
-                        font_info.apply(&mut text);
-                        texts.push_back((true, text));
-                        s.clear();
-                        chars_added = 0;
+    /// Wraps every message to fit the chatbox, measuring text with `measure`.
+    fn reflow_messages(&mut self, measure: &mut dyn FnMut(&str) -> f32) {
+        let width = self.text_width();
+        let space_width = self.font_info.char_dimensions.x;
+        self.wrapped.clear();
+        for (message, entry) in self.messages.iter().enumerate() {
+            let parts = entry.prefix_parts(self.show_timestamps);
+            let prefix = parts
+                .iter()
+                .map(|(part, _)| part.as_str())
+                .collect::<Vec<_>>()
+                .join(" ");
+            for line in layout_message(&prefix, &entry.body, width, space_width, measure) {
+                let prefix_text = if line.prefix {
+                    let mut text = Text::default();
+                    for (i, (part, color)) in parts.iter().enumerate() {
+                        if i > 0 {
+                            text.add(" ");
+                        }
+                        text.add(TextFragment::new(part.as_str()).color(*color));
                     }
-
-                    s.push(ch);
-                    chars_added += 1;
-                }
-                // add a space after the long word and continue forward
-                if !s.is_empty() {
-                    s.push(' ');
-                    chars_added += 1;
-                }
-                continue;
+                    self.font_info.apply(&mut text);
+                    Some(text)
+                } else {
+                    None
+                };
+                let mut body = Text::new(line.body);
+                self.font_info.apply(&mut body);
+                self.wrapped.push_back(WrappedLine {
+                    prefix: prefix_text,
+                    body,
+                    indent: line.indent,
+                    kind: entry.kind,
+                    message,
+                });
             }
+        }
+        self.needs_reflow = false;
+    }
+}
 
-            for ch in word.chars() {
-                s.push(ch);
-                chars_added += 1;
-            }
+/// Where one line of a message goes: whether it starts with the message's prefix, and its part of
+/// the body, which starts `indent` pixels right of the prefix's left edge.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct LineLayout {
+    pub prefix: bool,
+    pub indent: f32,
+    pub body:   String,
+}
 
-            if chars_added + 1 <= max_chars_per_line {
-                s.push(' ');
-                chars_added += 1;
-            }
+/// Lays out a message with the text `prefix` before its `body`, in lines at most `width` wide.
+/// Text is measured by `measure`, and a space is `space_width` wide. The body is wrapped into a
+/// column just right of the prefix, so that every line of it starts at the same place. If the
+/// prefix is too wide for that (see `MAX_PREFIX_FRACTION`), it gets a line of its own and the body
+/// is wrapped under it, slightly indented.
+pub(super) fn layout_message(
+    prefix: &str,
+    body: &str,
+    width: f32,
+    space_width: f32,
+    measure: &mut dyn FnMut(&str) -> f32,
+) -> Vec<LineLayout> {
+    let mut lines = vec![];
+    let mut indent = if prefix.is_empty() {
+        0.0
+    } else {
+        measure(prefix) + space_width
+    };
+    if indent > width * MAX_PREFIX_FRACTION {
+        lines.push(LineLayout {
+            prefix: true,
+            indent: 0.0,
+            body:   String::new(),
+        });
+        indent = OWN_LINE_PREFIX_INDENT * space_width;
+    }
+    for body_line in wrap_message(body, width - indent, measure) {
+        lines.push(LineLayout {
+            prefix: lines.is_empty() && !prefix.is_empty(),
+            indent,
+            body: body_line,
+        });
+    }
+    if lines.is_empty() {
+        // nothing but whitespace in the body
+        lines.push(LineLayout {
+            prefix: !prefix.is_empty(),
+            indent,
+            body: String::new(),
+        });
+    }
+    lines
+}
+
+/// Breaks `msg` into lines that are at most `width` wide, as measured by `measure`. Lines are broken
+/// between words where possible. A word too wide for a line of its own is broken between grapheme
+/// clusters, so that accented letters and emoji sequences are never split apart.
+pub(super) fn wrap_message(msg: &str, width: f32, measure: &mut dyn FnMut(&str) -> f32) -> Vec<String> {
+    let mut lines = vec![];
+    let mut line = String::new();
+
+    for word in msg.split_whitespace() {
+        let candidate = if line.is_empty() {
+            word.to_owned()
+        } else {
+            format!("{} {}", line, word)
+        };
+        if measure(&candidate) <= width {
+            line = candidate;
+            continue;
         }
 
-        if !s.is_empty() {
-            let mut text = Text::new(s.clone());
-            font_info.apply(&mut text);
-            texts.push_back((true, text));
+        // If the word can fit on the next line, but not the current line
+        if !line.is_empty() && measure(word) <= width {
+            lines.push(mem::replace(&mut line, word.to_owned()));
+            continue;
         }
 
-        if let Some((ref mut has_more_texts, _)) = texts.back_mut() {
-            *has_more_texts = false;
+        // The word is too long to fit on a line, so break it into multiple lines, starting on
+        // the current one
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        for grapheme in word.graphemes(true) {
+            let candidate = format!("{}{}", line, grapheme);
+            if measure(&candidate) > width && !line.trim_end().is_empty() {
+                lines.push(line.trim_end().to_owned());
+                line = grapheme.to_owned();
+            } else {
+                line = candidate;
+            }
         }
+    }
 
-        texts
+    if !line.is_empty() {
+        lines.push(line);
     }
+    lines
 }
 
 impl Widget for Chatbox {
@@ -300,7 +567,7 @@ impl Widget for Chatbox {
 
     fn set_rect(&mut self, new_dims: Rect) -> UIResult<()> {
         if new_dims.w == 0.0 || new_dims.h == 0.0 {
-            return Err(Box::new(UIError::InvalidDimensions {
+            return Err(Box::new(UIError::InvalidRect {
                 reason: format!(
                     "Cannot set the size to a width or height of a chatbox, {:?}, to zero",
                     self.id()
@@ -312,7 +579,7 @@ impl Widget for Chatbox {
         self.dimensions = new_dims;
         if old_dims.w != new_dims.w {
             // width changed
-            self.reflow_messages();
+            self.needs_reflow = true;
         }
         Ok(())
     }
@@ -332,7 +599,7 @@ impl Widget for Chatbox {
 
     fn set_size(&mut self, w: f32, h: f32) -> UIResult<()> {
         if w == 0.0 || h == 0.0 {
-            return Err(Box::new(UIError::InvalidDimensions {
+            return Err(Box::new(UIError::InvalidRect {
                 reason: format!("Cannot set the width or height of Chatbox {:?} to zero", self.id()),
             }));
         }
@@ -342,7 +609,7 @@ impl Widget for Chatbox {
         self.dimensions.h = h;
 
         if old_w != w {
-            self.reflow_messages();
+            self.needs_reflow = true;
         }
 
         Ok(())
@@ -354,8 +621,9 @@ impl Widget for Chatbox {
 
     fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
         // TODO: Add support to scroll through history
+        let theme = theme::current();
         if self.hover {
-            // Add in a teal border while hovered. Color checkbox differently to indicate hovered state.
+            // Add in a highlighted border while hovered.
             let border_rect = Rect::new(
                 self.dimensions.x - 1.0,
                 self.dimensions.y - 1.0,
@@ -363,7 +631,7 @@ impl Widget for Chatbox {
                 self.dimensions.h + constants::CHATBOX_BORDER_PIXELS / 2.0 + 2.0,
             );
             let hovered_border =
-                graphics::Mesh::new_rectangle(ctx, DrawMode::stroke(2.0), border_rect, *CHATBOX_BORDER_ON_HOVER_COLOR)?;
+                graphics::Mesh::new_rectangle(ctx, DrawMode::stroke(2.0), border_rect, theme.focus_highlight)?;
             graphics::draw(ctx, &hovered_border, DrawParam::default())?;
         }
 
@@ -377,7 +645,7 @@ impl Widget for Chatbox {
             ctx,
             DrawMode::stroke(constants::CHATBOX_BORDER_PIXELS),
             text_entry_rect,
-            self.color,
+            theme.border,
         )?;
         graphics::draw(ctx, &border, DrawParam::default())?;
 
@@ -391,7 +659,12 @@ impl Widget for Chatbox {
             y: self.dimensions.y + self.dimensions.h - self.font_info.char_dimensions.y,
         };
 
-        for (_, wrapped_text) in self.wrapped.iter().rev() {
+        if self.needs_reflow {
+            let font_info = self.font_info;
+            self.reflow_messages(&mut |text| font_info.measure_width(ctx, text));
+        }
+
+        for line in self.wrapped.iter().rev() {
             if max_lines == 0 {
                 break;
             }
@@ -399,7 +672,18 @@ impl Widget for Chatbox {
                 x: bottom_left_corner.x + constants::CHATBOX_BORDER_PIXELS + 1.0,
                 y: bottom_left_corner.y - (i as f32 * self.font_info.char_dimensions.y),
             };
-            graphics::queue_text(ctx, wrapped_text, point, Some(*CHATBOX_TEXT_COLOR));
+            if let Some(ref prefix) = line.prefix {
+                graphics::queue_text(ctx, prefix, point, Some(theme.chat_text));
+            }
+            let body_point = Point2 {
+                x: point.x + line.indent,
+                y: point.y,
+            };
+            let body_color = match line.kind {
+                MessageKind::Chat => theme.chat_text,
+                MessageKind::System => *CHAT_SYSTEM_TEXT_COLOR,
+            };
+            graphics::queue_text(ctx, &line.body, body_point, Some(body_color));
             max_lines -= 1;
             i += 1;
         }
@@ -418,24 +702,63 @@ widget_from_id!(Chatbox);
 impl_emit_event!(Chatbox, self.handler_data);
 
 pub struct ChatboxPublishHandle {
-    msg_sender: Sender<String>,
+    msg_sender: Sender<ChatEntry>,
 }
 
 impl ChatboxPublishHandle {
-    pub fn add_message(&mut self, msg: String) {
-        self.msg_sender.send(msg).unwrap_or_else(|_e| {
+    pub fn add_message<M: Into<ChatEntry>>(&mut self, msg: M) {
+        self.msg_sender.send(msg.into()).unwrap_or_else(|_e| {
             error!("Chatbox has been dropped!");
         });
     }
 
-    pub fn new(msg_sender: Sender<String>) -> Self {
+    pub fn new(msg_sender: Sender<ChatEntry>) -> Self {
         ChatboxPublishHandle { msg_sender }
     }
 }
 
+/// Chat messages kept outside of any `Chatbox`, so that they can be put back into one with
+/// `Chatbox::restore_history`. Only the newest `max_len` messages are kept.
+#[derive(Debug, Clone)]
+pub struct ChatHistory {
+    messages: VecDeque<ChatEntry>,
+    max_len:  usize,
+}
+
+impl ChatHistory {
+    pub fn new(max_len: usize) -> Self {
+        ChatHistory {
+            messages: VecDeque::with_capacity(max_len),
+            max_len,
+        }
+    }
+
+    /// Adds a message, forgetting the oldest one if there are too many.
+    pub fn push<M: Into<ChatEntry>>(&mut self, msg: M) {
+        self.messages.push_back(msg.into());
+        while self.messages.len() > self.max_len {
+            self.messages.pop_front();
+        }
+    }
+
+    /// Iterates over the messages, oldest first.
+    pub fn iter(&self) -> impl Iterator<Item = &ChatEntry> {
+        self.messages.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use chrono::TimeZone;
     use ggez::graphics::PxScale;
     use std::collections::vec_deque;
 
@@ -448,18 +771,51 @@ mod tests {
             char_dimensions: Vector2 { x: 5.0f32, y: 5.0f32 }, // any positive values will do
         };
         let height = 123.0; // doesn't matter
-                            // The following must be the reverse of the `max_chars_per_line` calculation in
-                            // `reflow_message`, plus 0.01 padding.
-        let width = font_info.char_dimensions.x * (max_chars_per_line as f32) + 0.01;
+
+        // The following must be the reverse of the `text_width` calculation, for text measured by
+        // `measure`, plus 0.01 padding.
+        let width = 5.0 * (max_chars_per_line as f32) + 2.0 * (constants::CHATBOX_BORDER_PIXELS + 1.0) + 0.01;
         let mut cb = Chatbox::new(font_info, history_lines);
         let _result = cb.set_rect(Rect::new(0.0, 0.0, width, height));
         cb
     }
 
+    // Stands in for measuring with a real font: each grapheme cluster is 5 pixels wide, except for
+    // CJK and emoji, which are twice as wide.
+    fn measure(text: &str) -> f32 {
+        text.graphemes(true)
+            .map(|g| {
+                if g.chars().next().unwrap() >= '\u{2E80}' {
+                    10.0
+                } else {
+                    5.0
+                }
+            })
+            .sum()
+    }
+
     // Read the next item from the iterator and compare it. Trailing whitespace is removed before
     // comparison.
-    fn compare_next(text_iter: &mut vec_deque::Iter<(bool, Text)>, expected: &str) {
-        assert_eq!(text_iter.next().unwrap().1.contents().trim_end(), expected.trim_end());
+    fn compare_next(text_iter: &mut vec_deque::Iter<WrappedLine>, expected: &str) {
+        assert_eq!(
+            text_iter.next().unwrap().body.contents().trim_end(),
+            expected.trim_end()
+        );
+    }
+
+    fn entry_at(hour: u32, min: u32, sender: &str, body: &str) -> ChatEntry {
+        ChatEntry {
+            timestamp: Local.ymd(2021, 6, 1).and_hms(hour, min, 0),
+            ..ChatEntry::chat(sender.to_owned(), body.to_owned())
+        }
+    }
+
+    fn line(prefix: bool, indent: f32, body: &str) -> LineLayout {
+        LineLayout {
+            prefix,
+            indent,
+            body: body.to_owned(),
+        }
     }
 
     // Tests
@@ -467,7 +823,7 @@ mod tests {
     fn chatbox_reflow_all_fit() {
         let mut cb = max_chars_chatbox(20);
         cb.add_message("what a great game".to_owned());
-        cb.reflow_messages();
+        cb.reflow_messages(&mut measure);
         let mut text_iter = cb.wrapped.iter();
         compare_next(&mut text_iter, "what a great game");
         assert!(text_iter.next().is_none());
@@ -476,9 +832,9 @@ mod tests {
     #[test]
     fn chatbox_reflow_just_barely_fit() {
         let s = "what a great game".to_owned();
-        let mut cb = max_chars_chatbox(s.len()); // note: won't work if any multi-byte characters
+        let mut cb = max_chars_chatbox(s.len()); // all ASCII, so one byte per character
         cb.add_message(s.clone());
-        cb.reflow_messages();
+        cb.reflow_messages(&mut measure);
         let mut text_iter = cb.wrapped.iter();
         compare_next(&mut text_iter, &s);
         assert!(text_iter.next().is_none());
@@ -488,7 +844,7 @@ mod tests {
     fn chatbox_reflow_short_words_no_fit_bound_at_end_of_word() {
         let mut cb = max_chars_chatbox(12);
         cb.add_message("what a great game".to_owned());
-        cb.reflow_messages();
+        cb.reflow_messages(&mut measure);
         let mut text_iter = cb.wrapped.iter();
         compare_next(&mut text_iter, "what a great");
         compare_next(&mut text_iter, "game");
@@ -499,7 +855,7 @@ mod tests {
     fn chatbox_reflow_short_words_no_fit_bound_in_middle_of_word() {
         let mut cb = max_chars_chatbox(15);
         cb.add_message("what a great game".to_owned());
-        cb.reflow_messages();
+        cb.reflow_messages(&mut measure);
         let mut text_iter = cb.wrapped.iter();
         compare_next(&mut text_iter, "what a great");
         compare_next(&mut text_iter, "game");
@@ -510,7 +866,7 @@ mod tests {
     fn chatbox_reflow_short_words_no_fit_bound_at_start_of_word() {
         let mut cb = max_chars_chatbox(13);
         cb.add_message("what a great game".to_owned());
-        cb.reflow_messages();
+        cb.reflow_messages(&mut measure);
         let mut text_iter = cb.wrapped.iter();
         compare_next(&mut text_iter, "what a great");
         compare_next(&mut text_iter, "game");
@@ -521,7 +877,7 @@ mod tests {
     fn chatbox_reflow_short_words_plus_long_word_on_same_line() {
         let mut cb = max_chars_chatbox(9);
         cb.add_message("what an entertaining game".to_owned());
-        cb.reflow_messages();
+        cb.reflow_messages(&mut measure);
         let mut text_iter = cb.wrapped.iter();
         compare_next(&mut text_iter, "what an e");
         compare_next(&mut text_iter, "ntertaini");
@@ -533,10 +889,276 @@ mod tests {
     fn chatbox_reflow_long_word_at_start() {
         let mut cb = max_chars_chatbox(10);
         cb.add_message("entertaining".to_owned());
-        cb.reflow_messages();
+        cb.reflow_messages(&mut measure);
         let mut text_iter = cb.wrapped.iter();
         compare_next(&mut text_iter, "entertaini");
         compare_next(&mut text_iter, "ng");
         assert!(text_iter.next().is_none());
     }
+
+    #[test]
+    fn chatbox_reflow_is_deferred_until_drawn() {
+        let mut cb = max_chars_chatbox(20);
+        cb.add_message("what a great game".to_owned());
+        assert!(cb.needs_reflow);
+        assert!(cb.wrapped.is_empty());
+        cb.reflow_messages(&mut measure);
+        assert!(!cb.needs_reflow);
+        assert_eq!(cb.wrapped.len(), 1);
+    }
+
+    #[test]
+    fn chatbox_reflow_wide_characters() {
+        // each CJK character is as wide as two ASCII ones
+        let mut cb = max_chars_chatbox(6);
+        cb.add_message("日本語のテキスト".to_owned());
+        cb.reflow_messages(&mut measure);
+        let mut text_iter = cb.wrapped.iter();
+        compare_next(&mut text_iter, "日本語");
+        compare_next(&mut text_iter, "のテキ");
+        compare_next(&mut text_iter, "スト");
+        assert!(text_iter.next().is_none());
+    }
+
+    #[test]
+    fn chatbox_reflow_ascii_and_emoji() {
+        let mut cb = max_chars_chatbox(6);
+        cb.add_message("gg 🎉🎉 wp".to_owned());
+        cb.reflow_messages(&mut measure);
+        let mut text_iter = cb.wrapped.iter();
+        compare_next(&mut text_iter, "gg");
+        compare_next(&mut text_iter, "🎉🎉");
+        compare_next(&mut text_iter, "wp");
+        assert!(text_iter.next().is_none());
+    }
+
+    #[test]
+    fn chatbox_reflow_keeps_combining_characters_together() {
+        let mut cb = max_chars_chatbox(3);
+        cb.add_message("cafe\u{301}s".to_owned()); // "cafés", with a combining acute accent
+        cb.reflow_messages(&mut measure);
+        let mut text_iter = cb.wrapped.iter();
+        compare_next(&mut text_iter, "caf");
+        compare_next(&mut text_iter, "e\u{301}s");
+        assert!(text_iter.next().is_none());
+    }
+
+    #[test]
+    fn chatbox_reflow_keeps_emoji_sequences_together() {
+        let family = "👨\u{200D}👩\u{200D}👧"; // one emoji made of three, joined by zero width joiners
+        let mut cb = max_chars_chatbox(4);
+        cb.add_message(family.repeat(3));
+        cb.reflow_messages(&mut measure);
+        let mut text_iter = cb.wrapped.iter();
+        compare_next(&mut text_iter, &family.repeat(2));
+        compare_next(&mut text_iter, family);
+        assert!(text_iter.next().is_none());
+    }
+
+    #[test]
+    fn chat_history_keeps_newest() {
+        let mut history = ChatHistory::new(3);
+        assert!(history.is_empty());
+        for i in 0..5 {
+            history.push(format!("message {}", i));
+        }
+        assert_eq!(history.len(), 3);
+        let messages: Vec<_> = history.iter().map(|entry| entry.body.as_str()).collect();
+        assert_eq!(messages, vec!["message 2", "message 3", "message 4"]);
+    }
+
+    #[test]
+    fn chatbox_restore_history() {
+        let mut cb = max_chars_chatbox(20);
+        cb.add_message("this goes away".to_owned());
+        let mut handle = cb.new_handle();
+        handle.add_message("you there?".to_owned()); // published, but not received yet
+
+        let mut history = ChatHistory::new(20);
+        history.push("hello".to_owned());
+        history.push("you there?".to_owned());
+        cb.restore_history(&history);
+
+        let messages: Vec<_> = cb.messages.iter().map(|entry| entry.body.as_str()).collect();
+        assert_eq!(messages, vec!["hello", "you there?"]);
+        cb.reflow_messages(&mut measure);
+        let mut text_iter = cb.wrapped.iter();
+        compare_next(&mut text_iter, "hello");
+        compare_next(&mut text_iter, "you there?");
+        assert!(text_iter.next().is_none());
+        assert!(cb.msg_receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn chat_entry_from_plain_string() {
+        let entry = ChatEntry::from("alice: hi: there".to_owned());
+        assert_eq!(entry.kind, MessageKind::Chat);
+        assert_eq!(entry.sender.as_deref(), Some("alice"));
+        assert_eq!(entry.body, "hi: there");
+
+        let entry = ChatEntry::from("Server: Player bob has left.".to_owned());
+        assert_eq!(entry.kind, MessageKind::System);
+        assert_eq!(entry.sender, None);
+        assert_eq!(entry.body, "Player bob has left.");
+
+        let entry = ChatEntry::from("-- no sender here --".to_owned());
+        assert_eq!(entry.kind, MessageKind::System);
+        assert_eq!(entry.body, "-- no sender here --");
+    }
+
+    #[test]
+    fn sender_colors_are_deterministic() {
+        for name in &["alice", "bob", "xXx_destroyer_xXx", "日本語"] {
+            let index = sender_color_index(name);
+            assert!(index < CHAT_SENDER_COLORS.len());
+            assert_eq!(sender_color_index(&name.to_string()), index);
+        }
+        // not every name gets the same color
+        let indexes: Vec<_> = (0..20).map(|i| sender_color_index(&format!("player{}", i))).collect();
+        assert!(indexes.iter().any(|&index| index != indexes[0]));
+    }
+
+    #[test]
+    fn layout_indents_long_body_past_the_name() {
+        // 20 characters wide; "bob:" and a space take up 5
+        let lines = layout_message(
+            "bob:",
+            "the quick brown fox jumps over the lazy dog",
+            100.0,
+            5.0,
+            &mut measure,
+        );
+        assert_eq!(
+            lines,
+            vec![
+                line(true, 25.0, "the quick brown"),
+                line(false, 25.0, "fox jumps over"),
+                line(false, 25.0, "the lazy dog"),
+            ]
+        );
+    }
+
+    #[test]
+    fn layout_puts_long_name_on_its_own_line() {
+        let lines = layout_message("a_very_long_player_name:", "gg wp", 100.0, 5.0, &mut measure);
+        assert_eq!(lines, vec![line(true, 0.0, ""), line(false, 10.0, "gg wp")]);
+    }
+
+    #[test]
+    fn layout_long_name_and_long_body() {
+        // the body gets 18 of the 20 characters, after the indent
+        let lines = layout_message(
+            "12:34 xXx_destroyer_xXx:",
+            "that was a close one, well played",
+            100.0,
+            5.0,
+            &mut measure,
+        );
+        assert_eq!(
+            lines,
+            vec![
+                line(true, 0.0, ""),
+                line(false, 10.0, "that was a close"),
+                line(false, 10.0, "one, well played"),
+            ]
+        );
+    }
+
+    #[test]
+    fn layout_without_prefix_uses_the_whole_width() {
+        let lines = layout_message("", "what a great game", 60.0, 5.0, &mut measure);
+        assert_eq!(lines, vec![line(false, 0.0, "what a great"), line(false, 0.0, "game")]);
+    }
+
+    #[test]
+    fn layout_empty_body_keeps_the_prefix() {
+        let lines = layout_message("bob:", "   ", 100.0, 5.0, &mut measure);
+        assert_eq!(lines, vec![line(true, 25.0, "")]);
+    }
+
+    #[test]
+    fn chatbox_reflow_with_timestamps() {
+        let mut cb = max_chars_chatbox(24);
+        cb.set_show_timestamps(true);
+        cb.add_message(entry_at(9, 5, "bob", "is anyone out there at all"));
+        cb.add_message(ChatEntry {
+            timestamp: Local.ymd(2021, 6, 1).and_hms(21, 30, 0),
+            ..ChatEntry::system("bob has left".to_owned())
+        });
+        cb.reflow_messages(&mut measure);
+
+        // "09:05 bob:" and a space take up 11 of the 24 characters, leaving 13 for the message
+        let lines: Vec<_> = cb.wrapped.iter().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0].prefix.as_ref().unwrap().contents(), "09:05 bob:");
+        assert_eq!(lines[0].kind, MessageKind::Chat);
+        let mut text_iter = cb.wrapped.iter();
+        compare_next(&mut text_iter, "is anyone out");
+        compare_next(&mut text_iter, "there at all");
+        for wrapped in &lines[0..2] {
+            assert_eq!(wrapped.indent, 55.0);
+        }
+        assert!(lines[1].prefix.is_none());
+
+        // system messages have no sender, only the time
+        assert_eq!(lines[2].prefix.as_ref().unwrap().contents(), "21:30");
+        assert_eq!(lines[2].kind, MessageKind::System);
+        assert_eq!(lines[2].indent, 30.0);
+        compare_next(&mut text_iter, "bob has left");
+        assert!(text_iter.next().is_none());
+    }
+
+    #[test]
+    fn chatbox_message_at() {
+        let mut cb = max_chars_chatbox(12);
+        cb.add_message("hello".to_owned());
+        cb.add_message(entry_at(9, 5, "bob", "what a great game"));
+        let bottom = cb.rect().bottom();
+        // not until the lines have been laid out
+        assert_eq!(
+            cb.message_at(Point2 {
+                x: 10.0,
+                y: bottom - 1.0,
+            }),
+            None
+        );
+
+        cb.reflow_messages(&mut measure);
+        let line_count = cb.wrapped.len();
+        assert!(line_count >= 3); // bob's message takes more than one line
+        assert_eq!(
+            cb.message_at(Point2 {
+                x: 10.0,
+                y: bottom - 1.0,
+            }),
+            Some(1)
+        );
+        let top_line_y = bottom - 5.0 * line_count as f32 + 1.0;
+        assert_eq!(cb.message_at(Point2 { x: 10.0, y: top_line_y }), Some(0));
+        assert_eq!(
+            cb.message_at(Point2 {
+                x: 10.0,
+                y: top_line_y - 5.0,
+            }),
+            None
+        );
+        assert_eq!(cb.messages[1].plain_text(), "bob: what a great game");
+        assert_eq!(cb.messages[0].plain_text(), "hello");
+    }
+
+    #[test]
+    fn chatbox_clear_history_is_not_restored() {
+        let mut cb = max_chars_chatbox(20);
+        let mut history = ChatHistory::new(10);
+        history.push(entry_at(9, 5, "bob", "hi"));
+        cb.restore_history(&history);
+        assert_eq!(cb.messages.len(), 1);
+
+        cb.clear_history();
+        assert!(cb.messages.is_empty());
+        history.push(ChatEntry::chat("alice".to_owned(), "back again".to_owned()));
+        cb.restore_history(&history);
+        let bodies: Vec<&str> = cb.messages.iter().map(|msg| msg.body.as_str()).collect();
+        assert_eq!(bodies, vec!["back again"]);
+    }
 }
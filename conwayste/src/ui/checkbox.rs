@@ -30,13 +30,35 @@ use id_tree::NodeId;
 use super::context::{EmitEvent, Event, EventType, Handled, HandlerData, KeyCodeOrChar, MoveCross, UIContext};
 use super::{common::FontInfo, label::Label, widget::Widget, UIError, UIResult};
 
-use crate::constants::colors::*;
+use crate::theme::{self, ThemeColor};
+
+/// Whether a checkbox is checked. `Indeterminate` is only used by tri-state checkboxes, for
+/// settings that can also be left to a default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckState {
+    Unchecked,
+    Checked,
+    Indeterminate,
+}
+
+impl CheckState {
+    /// The state a click moves to: unchecked, then checked, then indeterminate if `tri_state`, and
+    /// around again.
+    pub fn next(self, tri_state: bool) -> Self {
+        match self {
+            CheckState::Unchecked => CheckState::Checked,
+            CheckState::Checked if tri_state => CheckState::Indeterminate,
+            CheckState::Checked | CheckState::Indeterminate => CheckState::Unchecked,
+        }
+    }
+}
 
 pub struct Checkbox {
     id:               Option<NodeId>,
     z_index:          usize,
     pub label:        Label,
-    pub enabled:      bool,
+    pub state:        CheckState,
+    tri_state:        bool, // whether clicking goes through CheckState::Indeterminate
     pub dimensions:   Rect,
     pub focused:      bool,        // has keyboard focus?
     pub hover_box:    bool,        // hovering checkbox itself?
@@ -48,8 +70,8 @@ impl fmt::Debug for Checkbox {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "Checkbox {{ id: {:?}, z-index: {}, Dimensions: {:?}, Checked: {} }}",
-            self.id, self.z_index, self.dimensions, self.enabled
+            "Checkbox {{ id: {:?}, z-index: {}, Dimensions: {:?}, State: {:?} }}",
+            self.id, self.z_index, self.dimensions, self.state
         )
     }
 }
@@ -57,7 +79,8 @@ impl fmt::Debug for Checkbox {
 const LABEL_OFFSET_X: f32 = 30.0;
 const LABEL_OFFSET_Y: f32 = -5.0;
 
-/// A standard checkbox widget that can be toggled between enabled or disabled
+/// A standard checkbox widget that can be toggled between enabled or disabled, or in tri-state mode,
+/// also set to indeterminate
 impl Checkbox {
     /// Creates a Checkbox widget.
     ///
@@ -92,11 +115,19 @@ impl Checkbox {
             y: dimensions.y + dimensions.h + LABEL_OFFSET_Y,
         };
 
+        let mut label = Label::new(ctx, font_info, text, theme::current().label_text, label_origin);
+        label.set_theme_color(ThemeColor::LabelText);
+
         let mut cb = Checkbox {
             id: None,
             z_index: std::usize::MAX,
-            label: Label::new(ctx, font_info, text, *CHECKBOX_TEXT_COLOR, label_origin),
-            enabled,
+            label,
+            state: if enabled {
+                CheckState::Checked
+            } else {
+                CheckState::Unchecked
+            },
+            tri_state: false,
             dimensions,
             focused: false,
             hover_box: false,
@@ -122,6 +153,24 @@ impl Checkbox {
         cb
     }
 
+    /// Whether the checkbox is checked, as opposed to unchecked or indeterminate.
+    pub fn is_checked(&self) -> bool {
+        self.state == CheckState::Checked
+    }
+
+    pub fn tri_state(&self) -> bool {
+        self.tri_state
+    }
+
+    /// Turns tri-state mode on or off. An indeterminate checkbox becomes unchecked when it is
+    /// turned off.
+    pub fn set_tri_state(&mut self, tri_state: bool) {
+        self.tri_state = tri_state;
+        if !tri_state && self.state == CheckState::Indeterminate {
+            self.state = CheckState::Unchecked;
+        }
+    }
+
     fn focus_change_handler(
         obj: &mut dyn EmitEvent,
         _uictx: &mut UIContext,
@@ -154,8 +203,8 @@ impl Checkbox {
     fn click_handler(obj: &mut dyn EmitEvent, _uictx: &mut UIContext, _evt: &Event) -> Result<Handled, Box<dyn Error>> {
         let mut checkbox = obj.downcast_mut::<Checkbox>().unwrap();
 
-        // toggle
-        checkbox.enabled = !checkbox.enabled;
+        // toggle, or cycle through the three states
+        checkbox.state = checkbox.state.next(checkbox.tri_state);
 
         Ok(Handled::Handled)
     }
@@ -212,7 +261,7 @@ impl Widget for Checkbox {
 
     fn set_rect(&mut self, new_dims: Rect) -> UIResult<()> {
         if new_dims.w == 0.0 || new_dims.h == 0.0 {
-            return Err(Box::new(UIError::InvalidDimensions {
+            return Err(Box::new(UIError::InvalidRect {
                 reason: format!(
                     "Cannot set the size to a width or height of Checkbox {:?} to zero",
                     self.id()
@@ -247,7 +296,7 @@ impl Widget for Checkbox {
 
     fn set_size(&mut self, w: f32, h: f32) -> UIResult<()> {
         if w == 0.0 || h == 0.0 {
-            return Err(Box::new(UIError::InvalidDimensions {
+            return Err(Box::new(UIError::InvalidRect {
                 reason: format!("Cannot set the width or height of Checkbox {:?} to zero", self.id()),
             }));
         }
@@ -264,8 +313,9 @@ impl Widget for Checkbox {
     }
 
     fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
+        let theme = theme::current();
         if self.hover_box || self.hover_label || self.focused {
-            // Add in a violet border/fill while hovered. Color checkbox differently to indicate
+            // Add in a highlighted border/fill while hovered. Color checkbox differently to indicate
             // hovering and/or keyboard focus.
             let border_rect = Rect::new(
                 self.dimensions.x - 1.0,
@@ -274,31 +324,31 @@ impl Widget for Checkbox {
                 self.dimensions.h + 4.0,
             );
 
-            let hovered_border = graphics::Mesh::new_rectangle(
-                ctx,
-                DrawMode::stroke(2.0),
-                border_rect,
-                *CHECKBOX_BORDER_ON_HOVER_COLOR,
-            )?;
+            let hovered_border =
+                graphics::Mesh::new_rectangle(ctx, DrawMode::stroke(2.0), border_rect, theme.focus_highlight)?;
 
             graphics::draw(ctx, &hovered_border, DrawParam::default())?;
         }
 
-        let draw_mode = if self.enabled {
+        let draw_mode = if self.state == CheckState::Checked {
             DrawMode::fill()
         } else {
             DrawMode::stroke(2.0)
         };
 
-        let border = graphics::Mesh::new_rectangle(ctx, draw_mode, self.dimensions, *CHECKBOX_TOGGLED_FILL_COLOR)?;
+        let border = graphics::Mesh::new_rectangle(ctx, draw_mode, self.dimensions, theme.checkbox_fill)?;
         graphics::draw(ctx, &border, DrawParam::default())?;
 
-        let label_border = graphics::Mesh::new_rectangle(
-            ctx,
-            DrawMode::stroke(2.0),
-            self.dimensions,
-            *CHECKBOX_TOGGLED_FILL_COLOR,
-        )?;
+        if self.state == CheckState::Indeterminate {
+            // a bar across the middle
+            let Rect { x, y, w, h } = self.dimensions;
+            let bar_rect = Rect::new(x + w / 4.0, y + h * 3.0 / 8.0, w / 2.0, h / 4.0);
+            let bar = graphics::Mesh::new_rectangle(ctx, DrawMode::fill(), bar_rect, theme.checkbox_fill)?;
+            graphics::draw(ctx, &bar, DrawParam::default())?;
+        }
+
+        let label_border =
+            graphics::Mesh::new_rectangle(ctx, DrawMode::stroke(2.0), self.dimensions, theme.checkbox_fill)?;
         graphics::draw(ctx, &label_border, DrawParam::default())?;
 
         self.label.draw(ctx)?;
@@ -319,3 +369,32 @@ impl Widget for Checkbox {
 
 impl_emit_event!(Checkbox, self.handler_data);
 widget_from_id!(Checkbox);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_check_state_cycles() {
+        let mut state = CheckState::Unchecked;
+        let mut seen = vec![];
+        for _ in 0..4 {
+            state = state.next(true);
+            seen.push(state);
+        }
+        assert_eq!(
+            seen,
+            vec![
+                CheckState::Checked,
+                CheckState::Indeterminate,
+                CheckState::Unchecked,
+                CheckState::Checked
+            ]
+        );
+
+        assert_eq!(CheckState::Checked.next(false), CheckState::Unchecked);
+        assert_eq!(CheckState::Unchecked.next(false), CheckState::Checked);
+        // left over from tri-state mode
+        assert_eq!(CheckState::Indeterminate.next(false), CheckState::Unchecked);
+    }
+}
@@ -0,0 +1,78 @@
+/*  Copyright 2021 the Conwayste Developers.
+ *
+ *  This file is part of conwayste.
+ *
+ *  conwayste is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  conwayste is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with conwayste.  If not, see
+ *  <http://www.gnu.org/licenses/>. */
+
+//! The system clipboard, for widgets that copy or paste text. ggez doesn't expose it, so it is
+//! accessed directly.
+
+#[cfg(not(test))]
+use copypasta::{ClipboardContext, ClipboardProvider};
+
+/// A widget's handle on the system clipboard. The context behind it is created on first use and kept
+/// for as long as the handle, since on some platforms the copied text is only available while it
+/// exists. There is no clipboard in tests: nothing gets copied, and there is never anything to paste.
+#[derive(Default)]
+pub(crate) struct Clipboard {
+    #[cfg(not(test))]
+    context: Option<ClipboardContext>,
+}
+
+impl Clipboard {
+    pub fn new() -> Self {
+        Clipboard::default()
+    }
+
+    #[cfg(not(test))]
+    fn context(&mut self) -> Option<&mut ClipboardContext> {
+        if self.context.is_none() {
+            match ClipboardContext::new() {
+                Ok(context) => self.context = Some(context),
+                Err(e) => warn!("Could not access the clipboard: {}", e),
+            }
+        }
+        self.context.as_mut()
+    }
+
+    /// The text on the clipboard, if it can be had.
+    #[cfg(not(test))]
+    pub fn contents(&mut self) -> Option<String> {
+        let context = self.context()?;
+        context
+            .get_contents()
+            .map_err(|e| warn!("Could not paste from the clipboard: {}", e))
+            .ok()
+    }
+
+    /// Puts `text` on the clipboard, logging it if that fails.
+    #[cfg(not(test))]
+    pub fn set_contents(&mut self, text: String) {
+        if let Some(context) = self.context() {
+            context.set_contents(text).unwrap_or_else(|e| {
+                warn!("Could not copy to the clipboard: {}", e);
+            });
+        }
+    }
+
+    #[cfg(test)]
+    pub fn contents(&mut self) -> Option<String> {
+        None
+    }
+
+    #[cfg(test)]
+    pub fn set_contents(&mut self, _text: String) {
+    }
+}
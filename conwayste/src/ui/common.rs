@@ -30,40 +30,39 @@ macro_rules! widget_from_id {
         #[allow(unused)]
         impl $type {
             pub fn widget_from_id_mut<'a, 'b>(layer: &'b mut Layering, id: &'a NodeId) -> UIResult<&'b mut $type> {
-                let widget_result = layer.get_widget_mut(id);
-                match widget_result {
-                    Ok(widget) => match widget.downcast_mut::<$type>() {
-                        Some(downcasted_widget) => {
-                            return Ok(downcasted_widget);
-                        }
-                        None => {
-                            return Err(Box::new(UIError::WidgetNotFound {
-                                reason: format!("{:?} could not be downcasted to type $type", id),
-                            }));
-                        }
-                    },
-                    Err(e) => {
-                        return Err(e);
-                    }
-                }
+                // Check first, since the layering can't be borrowed again for the error message
+                $type::widget_from_id(layer, id)?;
+                // unwraps OK because of the check above
+                Ok(layer.get_widget_mut(id).unwrap().downcast_mut::<$type>().unwrap())
             }
 
             pub fn widget_from_id<'a, 'b>(layer: &'b Layering, id: &'a NodeId) -> UIResult<&'b $type> {
-                let widget_result = layer.get_widget(id);
-                match widget_result {
-                    Ok(widget) => match widget.downcast_ref::<$type>() {
-                        Some(downcasted_widget) => {
-                            return Ok(downcasted_widget);
-                        }
-                        None => {
-                            return Err(Box::new(UIError::WidgetNotFound {
-                                reason: format!("{:?} could not be downcasted to type $type", id),
-                            }));
-                        }
-                    },
-                    Err(e) => {
-                        return Err(e);
-                    }
+                let widget = layer.get_widget(id)?;
+                match widget.downcast_ref::<$type>() {
+                    Some(downcasted_widget) => Ok(downcasted_widget),
+                    None => Err(Box::new(UIError::WrongWidgetType {
+                        expected: stringify!($type),
+                        found:    widget.type_name(),
+                        path:     layer.widget_path(id),
+                    })),
+                }
+            }
+
+            pub fn widget_from_name_mut<'b>(layer: &'b mut Layering, name: &str) -> UIResult<&'b mut $type> {
+                match layer.widget_by_name(name) {
+                    Some(id) => $type::widget_from_id_mut(layer, &id),
+                    None => Err(Box::new(UIError::WidgetNameNotFound {
+                        name: name.to_owned(),
+                    })),
+                }
+            }
+
+            pub fn widget_from_name<'b>(layer: &'b Layering, name: &str) -> UIResult<&'b $type> {
+                match layer.widget_by_name(name) {
+                    Some(id) => $type::widget_from_id(layer, &id),
+                    None => Err(Box::new(UIError::WidgetNameNotFound {
+                        name: name.to_owned(),
+                    })),
                 }
             }
         }
@@ -187,6 +186,14 @@ impl FontInfo {
         #[cfg(test)]
         let _ = text;
     }
+
+    /// The width in pixels of `text` in this font, as laid out by ggez. Unlike multiplying by
+    /// `char_dimensions.x`, this is right for wide characters such as CJK and emoji.
+    pub fn measure_width(&self, ctx: &mut Context, text: &str) -> f32 {
+        let mut text = Text::new(text);
+        self.apply(&mut text);
+        text.width(ctx) as f32
+    }
 }
 
 /// Determines if two rectangles overlap, and if so,
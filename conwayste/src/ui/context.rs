@@ -25,14 +25,18 @@ use downcast_rs::Downcast;
 use enum_iterator::IntoEnumIterator;
 use ggez;
 use ggez::event::MouseButton;
-use ggez::graphics::Rect;
+use ggez::graphics::{self, Rect};
 use ggez::input::keyboard::KeyCode;
 use ggez::mint::Point2;
 use id_tree::NodeId;
 
+use super::contextmenu::ContextMenu;
+use super::toast::{Notifier, Severity};
 use super::treeview::TreeView;
+use super::ui_errors::short_type_name;
 use super::BoxedWidget;
-use crate::{config, uilayout::StaticNodeIds, viewport::GridView, Screen};
+use crate::input::ScrollEvent;
+use crate::{config, uilayout::StaticNodeIds, Screen};
 
 /// Stores references to many things a handler is likely to need:
 ///
@@ -40,6 +44,8 @@ use crate::{config, uilayout::StaticNodeIds, viewport::GridView, Screen};
 /// * `config` - Conwayste configuration settings.
 /// * `widget_view` - a `TreeView` on the handler's widget and all widgets beneath it in the widget tree.
 /// * `screen_stack` - the layers of `Screen`s in the UI. Handlers are able to push or pop this stack.
+/// * `notifier` - posts toasts; see `notify`.
+/// * `context_menu` - the layering's right-click menu, if open; see `open_context_menu`.
 pub struct UIContext<'a> {
     pub ggez_context:     &'a mut ggez::Context,
     pub config:           &'a mut config::Config,
@@ -47,7 +53,8 @@ pub struct UIContext<'a> {
     pub screen_stack:     &'a mut Vec<Screen>,
     pub game_in_progress: bool,
     pub static_node_ids:  &'a mut StaticNodeIds,
-    pub viewport:         &'a mut GridView,
+    pub notifier:         &'a Notifier,
+    pub context_menu:     &'a mut Option<ContextMenu>,
     child_events:         Vec<Event>,
 }
 
@@ -59,7 +66,8 @@ impl<'a> UIContext<'a> {
         screen_stack: &'a mut Vec<Screen>,
         game_in_progress: bool,
         static_node_ids: &'a mut StaticNodeIds,
-        viewport: &'a mut GridView,
+        notifier: &'a Notifier,
+        context_menu: &'a mut Option<ContextMenu>,
     ) -> Self {
         UIContext {
             ggez_context,
@@ -69,7 +77,8 @@ impl<'a> UIContext<'a> {
             screen_stack,
             game_in_progress,
             static_node_ids,
-            viewport,
+            notifier,
+            context_menu,
         }
     }
 
@@ -98,7 +107,8 @@ impl<'a> UIContext<'a> {
                 child_events:     vec![],
                 game_in_progress: self.game_in_progress,
                 static_node_ids:  self.static_node_ids,
-                viewport:         self.viewport,
+                notifier:         self.notifier,
+                context_menu:     self.context_menu,
             },
         ))
     }
@@ -155,6 +165,22 @@ impl<'a> UIContext<'a> {
         Ok(self.screen_stack.pop().unwrap())
     }
 
+    /// Shows `message` in a toast for a few seconds, whatever screen is shown. For example,
+    /// `uictx.notify(Severity::Warn, "Kicked from slot: AFK")`.
+    pub fn notify<S: Into<String>>(&self, severity: Severity, message: S) {
+        self.notifier.notify(severity, message);
+    }
+
+    /// Shows `menu` with its top left corner at `point`, or as close as it fits in the window,
+    /// replacing any menu already open. Usually called from a RightClick handler.
+    pub fn open_context_menu(&mut self, point: Point2<f32>, mut menu: ContextMenu) {
+        let window = graphics::screen_coordinates(self.ggez_context);
+        let font_info = menu.font_info();
+        let ggez_context = &mut *self.ggez_context;
+        menu.layout(point, window, &mut |text| font_info.measure_width(ggez_context, text));
+        *self.context_menu = Some(menu);
+    }
+
     /// Pushes a screen onto the screen stack.
     pub fn push_screen(&mut self, screen: Screen) {
         self.screen_stack.push(screen)
@@ -191,6 +217,7 @@ impl<'a> Drop for UIContext<'a> {
 pub enum EventType {
     None,
     Click,
+    RightClick,
     DoubleClick,
     KeyPress,
     MouseMove,
@@ -211,6 +238,7 @@ pub enum EventType {
     RequestFocus,
     Load,
     Save,
+    Scroll,
 }
 
 /// Describes a MouseMove event in relation to a Rect.
@@ -234,9 +262,12 @@ pub struct Event {
     pub button:        Option<MouseButton>, // Click
     pub key:           Option<KeyCodeOrChar>,
     pub shift_pressed: bool,
+    pub ctrl_pressed:  bool, // KeyPress
     pub key_repeating: bool,
     pub text:          Option<String>,
     pub node_id:       Option<NodeId>,
+    pub scroll:        Option<ScrollEvent>, // Scroll
+    pub window_size:   Option<(f32, f32)>,  // Resize
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -251,9 +282,11 @@ const KEY_EVENTS: &[EventType] = &[EventType::KeyPress];
 /// A slice containing all EventTypes related to the mouse.
 const MOUSE_EVENTS: &[EventType] = &[
     EventType::Click,
+    EventType::RightClick,
     EventType::MouseMove,
     EventType::Drag,
     EventType::MouseButtonHeld,
+    EventType::Scroll,
 ];
 
 /// A slice containing all EventTypes related to keyboard focus changes.
@@ -270,6 +303,7 @@ const BROADCASTED_EVENTS: &[EventType] = &[
     EventType::MouseMove,
     EventType::Load,
     EventType::Save,
+    EventType::Resize,
 ];
 
 impl EventType {
@@ -302,9 +336,12 @@ impl Default for Event {
             button:        None,
             key:           None,
             shift_pressed: false,
+            ctrl_pressed:  false,
             key_repeating: false,
             text:          None,
             node_id:       None,
+            scroll:        None,
+            window_size:   None,
         }
     }
 }
@@ -320,12 +357,19 @@ impl Event {
         }
     }
 
-    pub fn new_key_press(mouse_point: Point2<f32>, key_code: KeyCode, is_shift: bool, is_repeating: bool) -> Self {
+    pub fn new_key_press(
+        mouse_point: Point2<f32>,
+        key_code: KeyCode,
+        is_shift: bool,
+        is_ctrl: bool,
+        is_repeating: bool,
+    ) -> Self {
         Event {
             what: EventType::KeyPress,
             point: Some(mouse_point),
             key: Some(KeyCodeOrChar::KeyCode(key_code)),
             shift_pressed: is_shift,
+            ctrl_pressed: is_ctrl,
             key_repeating: is_repeating,
             ..Default::default()
         }
@@ -341,6 +385,16 @@ impl Event {
         }
     }
 
+    pub fn new_right_click(mouse_point: Point2<f32>, is_shift: bool) -> Self {
+        Event {
+            what: EventType::RightClick,
+            point: Some(mouse_point),
+            button: Some(MouseButton::Right),
+            shift_pressed: is_shift,
+            ..Default::default()
+        }
+    }
+
     pub fn new_mouse_move(
         prev_point: Point2<f32>,
         point: Point2<f32>,
@@ -357,6 +411,15 @@ impl Event {
         }
     }
 
+    pub fn new_scroll(mouse_point: Point2<f32>, scroll: ScrollEvent) -> Self {
+        Event {
+            what: EventType::Scroll,
+            point: Some(mouse_point),
+            scroll: Some(scroll),
+            ..Default::default()
+        }
+    }
+
     pub fn new_mouse_held(mouse_point: Point2<f32>, mouse_button: MouseButton, is_shift: bool) -> Self {
         Event {
             what: EventType::MouseButtonHeld,
@@ -460,6 +523,15 @@ impl Event {
         }
     }
 
+    /// The window was resized to `width` by `height` pixels.
+    pub fn new_resize(width: f32, height: f32) -> Self {
+        Event {
+            what: EventType::Resize,
+            window_size: Some((width, height)),
+            ..Default::default()
+        }
+    }
+
     /// Returns true if and only if this is a keyboard event.
     pub fn is_key_event(&self) -> bool {
         self.what.is_key_event()
@@ -563,6 +635,11 @@ pub trait EmitEvent: Downcast {
     /// * The first error to be returned by a handler will be returned here, and no other handlers
     ///   will run.
     fn emit(&mut self, event: &Event, uictx: &mut UIContext) -> Result<Handled, Box<dyn Error>>;
+
+    /// The name of the widget's type, like "Button", for error messages.
+    fn type_name(&self) -> &'static str {
+        short_type_name::<Self>()
+    }
 }
 
 impl_downcast!(EmitEvent);
@@ -0,0 +1,374 @@
+/*  Copyright 2021 the Conwayste Developers.
+ *
+ *  This file is part of conwayste.
+ *
+ *  conwayste is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  conwayste is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with conwayste.  If not, see
+ *  <http://www.gnu.org/licenses/>. */
+
+//! Right-click menus: a small list of labeled actions that pops up where the mouse was
+//! right-clicked. A widget opens one from its RightClick handler with
+//! `UIContext::open_context_menu`. The menu belongs to the `Layering` rather than the widget, so
+//! that it is drawn over every widget and sees mouse events before them. Choosing an item calls
+//! the item's handler on the widget that opened the menu, as if it were one of that widget's own
+//! handlers. Clicking anywhere else, or pressing a key, closes the menu.
+
+use std::error::Error;
+use std::fmt;
+
+use ggez::graphics::{self, DrawMode, DrawParam, FilterMode, Rect, Text};
+use ggez::input::keyboard::KeyCode;
+use ggez::mint::Point2;
+use ggez::{Context, GameResult};
+
+use id_tree::NodeId;
+
+use super::{
+    common::{color_with_alpha, FontInfo},
+    context::{Event, EventType, Handler, KeyCodeOrChar, UIContext},
+};
+use crate::constants::CONTEXT_MENU_PADDING;
+use crate::theme;
+
+/// One action in a context menu.
+pub struct MenuItem {
+    pub label: String,
+    handler:   Handler,
+}
+
+impl fmt::Debug for MenuItem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "MenuItem {{ label: {:?} }}", self.label)
+    }
+}
+
+impl MenuItem {
+    /// An item that calls `handler` on the widget that opened the menu when it is chosen. The
+    /// handler gets the click that chose it as its event.
+    pub fn new<S: Into<String>>(label: S, handler: Handler) -> Self {
+        MenuItem {
+            label: label.into(),
+            handler,
+        }
+    }
+}
+
+/// What an open context menu makes of an event.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MenuResponse {
+    Pass,          // not for the menu; the widgets get it as usual
+    Consumed,      // used by the menu, or swallowed so that it doesn't reach the widgets under it
+    Close,         // the menu closes, and the widgets don't get the event
+    CloseAndPass,  // the menu closes, and the widgets get the event
+    Chosen(usize), // the item at this index was clicked; the menu closes and the item's handler is called
+}
+
+#[derive(Debug)]
+pub struct ContextMenu {
+    owner:      NodeId, // the widget that opened the menu, which the items' handlers are called on
+    items:      Vec<MenuItem>,
+    font_info:  FontInfo,
+    dimensions: Rect,
+    row_height: f32,
+    hover:      Option<usize>, // the item under the mouse
+    dismissed:  bool,          // a mouse button was pressed outside the menu; it closes when released
+}
+
+impl ContextMenu {
+    /// A menu of `items` for the widget `owner`, in the font of `font_info`. It is laid out and
+    /// shown by `UIContext::open_context_menu`.
+    pub fn new(owner: NodeId, font_info: FontInfo, items: Vec<MenuItem>) -> Self {
+        ContextMenu {
+            owner,
+            items,
+            font_info,
+            dimensions: Rect::default(),
+            row_height: 0.0,
+            hover: None,
+            dismissed: false,
+        }
+    }
+
+    /// Puts the menu's top left corner at `point`, moved as little as needed to fit in `window`.
+    /// Labels are measured with `measure`.
+    pub fn layout(&mut self, point: Point2<f32>, window: Rect, measure: &mut dyn FnMut(&str) -> f32) {
+        let widest = self.items.iter().map(|item| measure(&item.label)).fold(0.0, f32::max);
+        self.row_height = self.font_info.char_dimensions.y + 2.0 * CONTEXT_MENU_PADDING;
+        let w = widest + 2.0 * CONTEXT_MENU_PADDING;
+        let h = self.row_height * self.items.len() as f32;
+        // A menu bigger than the window sticks out past its right or bottom edge
+        let x = point.x.min(window.right() - w).max(window.left());
+        let y = point.y.min(window.bottom() - h).max(window.top());
+        self.dimensions = Rect::new(x, y, w, h);
+        self.hover = self.item_at(point);
+    }
+
+    pub fn font_info(&self) -> FontInfo {
+        self.font_info
+    }
+
+    pub fn rect(&self) -> Rect {
+        self.dimensions
+    }
+
+    /// The index of the item at `point`, if any.
+    pub fn item_at(&self, point: Point2<f32>) -> Option<usize> {
+        if self.items.is_empty() || !self.dimensions.contains(point) {
+            return None;
+        }
+        let row = ((point.y - self.dimensions.y) / self.row_height) as usize;
+        Some(row.min(self.items.len() - 1))
+    }
+
+    /// Decides what happens to `event` while the menu is open.
+    pub fn respond(&mut self, event: &Event) -> MenuResponse {
+        let inside = event.point.map_or(false, |point| self.dimensions.contains(point));
+        if self.dismissed {
+            // Swallow the rest of the press that dismissed the menu
+            return match event.what {
+                EventType::MouseButtonHeld | EventType::Drag => MenuResponse::Consumed,
+                EventType::Click => MenuResponse::Close,
+                EventType::RightClick | EventType::Scroll | EventType::KeyPress => MenuResponse::CloseAndPass,
+                _ => MenuResponse::Pass,
+            };
+        }
+        match event.what {
+            EventType::MouseMove => {
+                self.hover = event.point.and_then(|point| self.item_at(point));
+                MenuResponse::Pass
+            }
+            EventType::MouseButtonHeld if !inside => {
+                self.dismissed = true;
+                MenuResponse::Consumed
+            }
+            EventType::MouseButtonHeld | EventType::Drag => MenuResponse::Consumed,
+            EventType::Click => match event.point.and_then(|point| self.item_at(point)) {
+                Some(index) => MenuResponse::Chosen(index),
+                None => MenuResponse::Close,
+            },
+            EventType::RightClick | EventType::Scroll if inside => MenuResponse::Consumed,
+            EventType::RightClick | EventType::Scroll => MenuResponse::CloseAndPass,
+            EventType::KeyPress if event.key == Some(KeyCodeOrChar::KeyCode(KeyCode::Escape)) => MenuResponse::Close,
+            EventType::KeyPress => MenuResponse::CloseAndPass,
+            _ => MenuResponse::Pass,
+        }
+    }
+
+    /// Calls the handler of the item at `index` on the widget that opened the menu, with `event`
+    /// (the click that chose it). `uictx` must have a view on that widget.
+    pub fn choose(self, index: usize, event: &Event, uictx: &mut UIContext) -> Result<(), Box<dyn Error>> {
+        let owner = self.owner;
+        let mut item = match self.items.into_iter().nth(index) {
+            Some(item) => item,
+            None => return Err(format!("context menu has no item {}", index).into()),
+        };
+        let (widget_ref, mut subuictx) = match uictx.derive(&owner) {
+            Ok(derived) => derived,
+            Err(e) => {
+                warn!(
+                    "Widget {:?} went away before {:?} was chosen from its menu: {}",
+                    owner, item.label, e
+                );
+                return Ok(());
+            }
+        };
+        let emittable = widget_ref
+            .as_emit_event()
+            .ok_or_else(|| -> Box<dyn Error> { format!("{:?} opened a menu but is not an EmitEvent", owner).into() })?;
+        (item.handler)(emittable, &mut subuictx, event)?;
+        let child_events = subuictx.collect_child_events();
+        if !child_events.is_empty() {
+            warn!("Ignoring child events from a context menu item: {:?}", child_events);
+        }
+        Ok(())
+    }
+
+    pub fn draw(&self, ctx: &mut Context) -> GameResult<()> {
+        if self.dismissed {
+            return Ok(());
+        }
+        let theme = theme::current();
+        let background = graphics::Mesh::new_rectangle(ctx, DrawMode::fill(), self.dimensions, theme.menu_pane_fill)?;
+        graphics::draw(ctx, &background, DrawParam::default())?;
+        if let Some(index) = self.hover {
+            let row = Rect::new(
+                self.dimensions.x,
+                self.dimensions.y + index as f32 * self.row_height,
+                self.dimensions.w,
+                self.row_height,
+            );
+            let highlight = color_with_alpha(theme.focus_highlight, 0.4);
+            let mesh = graphics::Mesh::new_rectangle(ctx, DrawMode::fill(), row, highlight)?;
+            graphics::draw(ctx, &mesh, DrawParam::default())?;
+        }
+        let border = graphics::Mesh::new_rectangle(ctx, DrawMode::stroke(1.0), self.dimensions, theme.border)?;
+        graphics::draw(ctx, &border, DrawParam::default())?;
+
+        for (i, item) in self.items.iter().enumerate() {
+            let mut text = Text::new(item.label.as_str());
+            self.font_info.apply(&mut text);
+            let point = Point2 {
+                x: self.dimensions.x + CONTEXT_MENU_PADDING,
+                y: self.dimensions.y + i as f32 * self.row_height + CONTEXT_MENU_PADDING,
+            };
+            graphics::queue_text(ctx, &text, point, Some(theme.button_text));
+        }
+        graphics::draw_queued_text(ctx, DrawParam::default(), None, FilterMode::Linear)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ggez::{event::MouseButton, graphics::PxScale, mint::Vector2};
+    use crate::ui::context::{EmitEvent, Handled};
+    use id_tree::{InsertBehavior, Node, TreeBuilder};
+
+    const WINDOW: Rect = Rect {
+        x: 0.0,
+        y: 0.0,
+        w: 400.0,
+        h: 300.0,
+    };
+
+    fn do_nothing(_obj: &mut dyn EmitEvent, _uictx: &mut UIContext, _evt: &Event) -> Result<Handled, Box<dyn Error>> {
+        Ok(Handled::Handled)
+    }
+
+    fn menu(labels: &[&str]) -> ContextMenu {
+        // any NodeId will do, since no items are chosen
+        let mut tree = TreeBuilder::new().build();
+        let owner = tree.insert(Node::new(()), InsertBehavior::AsRoot).unwrap();
+        let font_info = FontInfo {
+            font:            (),
+            scale:           PxScale::from(1.0),
+            char_dimensions: Vector2 { x: 10.0, y: 10.0 }, // rows are 10 + 2 * padding high
+        };
+        let items = labels
+            .iter()
+            .map(|label| MenuItem::new(*label, Box::new(do_nothing)))
+            .collect();
+        ContextMenu::new(owner, font_info, items)
+    }
+
+    fn measure(text: &str) -> f32 {
+        text.len() as f32 * 10.0
+    }
+
+    fn click(what: EventType, x: f32, y: f32) -> Event {
+        Event {
+            what,
+            point: Some(Point2 { x, y }),
+            button: Some(MouseButton::Left),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_layout_opens_at_the_point() {
+        let mut menu = menu(&["Copy", "Paste", "Clear"]);
+        menu.layout(Point2 { x: 50.0, y: 60.0 }, WINDOW, &mut measure);
+        let row_height = 10.0 + 2.0 * CONTEXT_MENU_PADDING;
+        assert_eq!(
+            menu.rect(),
+            Rect::new(50.0, 60.0, 50.0 + 2.0 * CONTEXT_MENU_PADDING, 3.0 * row_height)
+        );
+        assert_eq!(menu.item_at(Point2 { x: 55.0, y: 61.0 }), Some(0));
+        assert_eq!(
+            menu.item_at(Point2 {
+                x: 55.0,
+                y: 60.0 + row_height * 2.5,
+            }),
+            Some(2)
+        );
+        assert_eq!(menu.item_at(Point2 { x: 49.0, y: 61.0 }), None);
+    }
+
+    #[test]
+    fn test_layout_clamps_to_the_window() {
+        let mut menu = menu(&["Copy message", "Clear history"]);
+        menu.layout(Point2 { x: 390.0, y: 295.0 }, WINDOW, &mut measure);
+        let rect = menu.rect();
+        assert_eq!(rect.right(), WINDOW.right());
+        assert_eq!(rect.bottom(), WINDOW.bottom());
+
+        // too big for the window; it starts at the top left and sticks out
+        let mut tiny_window = WINDOW;
+        tiny_window.w = 20.0;
+        menu.layout(Point2 { x: 10.0, y: 10.0 }, tiny_window, &mut measure);
+        assert_eq!((menu.rect().x, menu.rect().y), (0.0, 10.0));
+    }
+
+    #[test]
+    fn test_respond() {
+        let mut menu = menu(&["Copy", "Paste"]);
+        menu.layout(Point2 { x: 50.0, y: 50.0 }, WINDOW, &mut measure);
+        let row_height = 10.0 + 2.0 * CONTEXT_MENU_PADDING;
+        let second_row_y = 50.0 + row_height * 1.5;
+
+        assert_eq!(
+            menu.respond(&click(EventType::MouseMove, 55.0, second_row_y)),
+            MenuResponse::Pass
+        );
+        assert_eq!(menu.hover, Some(1));
+        assert_eq!(menu.respond(&Event::new_update()), MenuResponse::Pass);
+        assert_eq!(
+            menu.respond(&click(EventType::MouseButtonHeld, 55.0, second_row_y)),
+            MenuResponse::Consumed
+        );
+        assert_eq!(
+            menu.respond(&click(EventType::Click, 55.0, second_row_y)),
+            MenuResponse::Chosen(1)
+        );
+        assert_eq!(
+            menu.respond(&click(EventType::RightClick, 55.0, 55.0)),
+            MenuResponse::Consumed
+        );
+        // right-clicking elsewhere opens a menu there instead
+        assert_eq!(
+            menu.respond(&click(EventType::RightClick, 5.0, 5.0)),
+            MenuResponse::CloseAndPass
+        );
+        assert_eq!(
+            menu.respond(&Event::new_key_press(
+                Point2 { x: 0.0, y: 0.0 },
+                KeyCode::Escape,
+                false,
+                false,
+                false
+            )),
+            MenuResponse::Close
+        );
+    }
+
+    #[test]
+    fn test_click_away_swallows_the_whole_press() {
+        let mut menu = menu(&["Clear"]);
+        menu.layout(Point2 { x: 50.0, y: 50.0 }, WINDOW, &mut measure);
+
+        assert_eq!(
+            menu.respond(&click(EventType::MouseButtonHeld, 5.0, 5.0)),
+            MenuResponse::Consumed
+        );
+        assert!(menu.dismissed);
+        assert_eq!(menu.respond(&click(EventType::Drag, 6.0, 6.0)), MenuResponse::Consumed);
+        assert_eq!(menu.respond(&click(EventType::MouseMove, 6.0, 6.0)), MenuResponse::Pass);
+        assert_eq!(menu.respond(&click(EventType::Click, 6.0, 6.0)), MenuResponse::Close);
+
+        // a click too quick for a MouseButtonHeld closes it all the same
+        let mut menu = self::menu(&["Clear"]);
+        menu.layout(Point2 { x: 50.0, y: 50.0 }, WINDOW, &mut measure);
+        assert_eq!(menu.respond(&click(EventType::Click, 5.0, 5.0)), MenuResponse::Close);
+    }
+}
@@ -17,25 +17,47 @@
  *  <http://www.gnu.org/licenses/>. */
 
 use super::{
+    cellmesh::{CachedCellMesh, CellBatches, CellMeshKey},
+    common::{draw_text, intersection, FontInfo},
     context::{EmitEvent, Event, EventType, Handled, HandlerData, KeyCodeOrChar, UIContext},
+    contextmenu::{ContextMenu, MenuItem},
     widget::Widget,
-    UIError, UIResult,
+    Severity, UIError, UIResult,
+};
+use crate::{
+    autosave::{Autosave, AutosaveSchedule, Autosaver, Camera},
+    config::Config,
+    constants::{colors::*, *},
+    export::{self, Pixels},
+    history::{self, Edit, EditHistory},
+    keybindings::{KeyAction, KeyBindings, KeyChord},
+    prediction::PendingActions,
+    replay::{self, Playback, Recorder, Replay, ReplayAction, ReplayHeader},
+    theme::{self, Theme},
+    viewport::{Cell, GridView, ZoomDirection},
 };
-use crate::{config::Config, constants::*, viewport::ZoomDirection};
 use conway::{
     error::ConwayError,
-    grids::{BitGrid, CharGrid, Rotation},
-    rle::Pattern,
-    universe::{BigBang, CellState, PlayerBuilder, Region, Universe},
+    grids::{BitGrid, BitOperation, CharGrid, Rotation},
+    rle::{Pattern, PatternFile},
+    universe::{BigBang, CellState, EdgeBehavior, GenStateDiff, PlayerBuilder, Region, Universe},
     ConwayResult,
 };
-use ggez::graphics::Rect;
-use ggez::input::keyboard::KeyCode;
+use ggez::graphics::{self, spritebatch::SpriteBatch, Color, DrawParam, Font, Rect};
+use ggez::input::{keyboard::KeyCode, mouse};
 use ggez::mint::{Point2, Vector2};
+use ggez::timer;
 use ggez::{Context, GameResult};
 use id_tree::NodeId;
+use rand::Rng;
+use std::collections::{BTreeMap, HashSet, VecDeque};
 use std::error::Error;
 use std::fmt;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 pub struct GameArea {
     id:                     Option<NodeId>,
@@ -44,7 +66,26 @@ pub struct GameArea {
     dimensions:             Rect,
     handler_data:           HandlerData,
     pub uni:                Universe,
+    pub view:               GridView, // which part of `uni` is shown within `dimensions`, and how big
+    color_settings:         ColorSettings,
+    theme:                  Arc<Theme>, // the theme `color_settings` came from
+    font:                   Font,
     game_state:             GameAreaState,
+    pub predict_actions:    bool, // track local cell changes until the server confirms them
+    pub paint_drag_lines:   bool, // fill in the cells skipped over between Drag events
+    pending_actions:        PendingActions,
+    recorder:               Option<Recorder<BufWriter<File>>>,
+    playback:               Option<Playback>, // if Some, input that would change `uni` is ignored
+    autosave:               Option<(Autosaver, AutosaveSchedule)>,
+    history:                EditHistory,
+    stroke:                 Option<Edit>, // the drag-paint stroke in progress, undone as a whole
+    keybindings:            KeyBindings,
+    cell_mesh:              Option<CachedCellMesh>, // reused until the generation or camera changes
+    edges:                  EdgeBehavior,           // of the single player universe
+    grid_line_width:        Option<f32>,            // if Some, lines this many pixels wide are drawn between cells
+    generation_rate:        RateMeter,              // shown in the stats overlay
+    // the cells copied last, with their width and height
+    clipboard:              Option<(BitGrid, usize, usize)>,
 }
 
 impl fmt::Debug for GameArea {
@@ -56,30 +97,60 @@ impl fmt::Debug for GameArea {
     }
 }
 
-/// For now, this is a dummy widget to represent the actual game area. It may not always be a dummy
-/// widget.
+/// Colors used to draw each kind of cell, plus the background behind the grid.
+pub struct ColorSettings {
+    cell_colors: BTreeMap<CellState, Color>,
+    background:  Color,
+}
+
+impl ColorSettings {
+    pub fn from_theme(theme: &Theme) -> Self {
+        let mut cell_colors = BTreeMap::new();
+        cell_colors.insert(CellState::Dead, theme.cell_dead);
+        if GRID_DRAW_STYLE == DrawStyle::Line {
+            // black background - for a "tetris-like" effect
+            cell_colors.insert(CellState::Alive(None), *CELL_STATE_BG_FILL_HOLLOW_COLOR);
+        } else {
+            // light background - default setting
+            cell_colors.insert(CellState::Alive(None), theme.cell_alive);
+        }
+        cell_colors.insert(CellState::Alive(Some(0)), theme.cell_player_0);
+        cell_colors.insert(CellState::Alive(Some(1)), theme.cell_player_1);
+        cell_colors.insert(CellState::Wall, theme.cell_wall);
+        cell_colors.insert(CellState::Fog, theme.cell_fog);
+
+        ColorSettings {
+            cell_colors,
+            background: theme.universe_background,
+        }
+    }
+
+    pub fn get_color(&self, cell_or_none: Option<CellState>) -> Color {
+        match cell_or_none {
+            Some(cell) => self.cell_colors[&cell],
+            None => self.background,
+        }
+    }
+
+    pub fn get_random_color(&self) -> Color {
+        let mut colors = vec![1.0, 2.0, 3.0];
+        let mut rng = rand::thread_rng();
+
+        for x in colors.iter_mut() {
+            *x = rng.gen_range(0.0..1.0);
+        }
+        let mut iter = colors.into_iter();
+        Color::new(iter.next().unwrap(), iter.next().unwrap(), iter.next().unwrap(), 1.0)
+    }
+}
+
+/// The game board of the Run screen. It owns the `Universe` and the `GridView` looking onto it, and
+/// draws the cells (including walls and fog) clipped to its own rectangle.
 impl GameArea {
-    pub fn new() -> Self {
-        let bigbang = {
-            // we're going to have to tear this all out when this becomes a real game
-            let player0_writable = Region::new(100, 70, 34, 16);
-            let player1_writable = Region::new(0, 0, 80, 80);
-
-            let player0 = PlayerBuilder::new(player0_writable);
-            let player1 = PlayerBuilder::new(player1_writable);
-            let players = vec![player0, player1];
-
-            BigBang::new()
-                .width(UNIVERSE_WIDTH_IN_CELLS)
-                .height(UNIVERSE_HEIGHT_IN_CELLS)
-                .server_mode(true) // TODO will change to false once we get server support up
-                // Currently 'client' is technically both client and server
-                .history(HISTORY_SIZE)
-                .fog_radius(FOG_RADIUS)
-                .add_players(players)
-                .birth()
-        };
-        let mut uni = bigbang.unwrap();
+    /// Creates a GameArea whose cells are initially `cell_size` pixels wide. The `font` is used for
+    /// the generation counter. Up to `undo_depth` cell edits can be undone.
+    pub fn new(font: Font, cell_size: f32, undo_depth: usize) -> Self {
+        let mut uni = new_universe().unwrap();
 
         init_patterns(&mut uni).unwrap();
 
@@ -90,7 +161,25 @@ impl GameArea {
             dimensions:         Rect::default(),
             handler_data:       HandlerData::new(),
             uni:                uni,
+            view:               GridView::new(cell_size, UNIVERSE_WIDTH_IN_CELLS, UNIVERSE_HEIGHT_IN_CELLS),
+            color_settings:     ColorSettings::from_theme(&theme::current()),
+            theme:              theme::current(),
+            font:               font,
             game_state:         GameAreaState::default(),
+            predict_actions:    false,
+            paint_drag_lines:   true,
+            pending_actions:    PendingActions::new(),
+            recorder:           None,
+            playback:           None,
+            autosave:           None,
+            history:            EditHistory::new(undo_depth),
+            stroke:             None,
+            keybindings:        KeyBindings::default(),
+            cell_mesh:          None,
+            edges:              EdgeBehavior::default(),
+            grid_line_width:    None,
+            generation_rate:    RateMeter::new(GENERATION_RATE_WINDOW),
+            clipboard:          None,
         };
 
         // Set handlers for toggling has_keyboard_focus.
@@ -105,6 +194,9 @@ impl GameArea {
         game_area
             .on(EventType::Update, Box::new(GameArea::update_handler))
             .unwrap();
+        game_area
+            .on(EventType::Resize, Box::new(GameArea::resize_handler))
+            .unwrap();
         game_area
             .on(EventType::KeyPress, Box::new(GameArea::keypress_handler))
             .unwrap();
@@ -117,11 +209,39 @@ impl GameArea {
         game_area
             .on(EventType::Drag, Box::new(GameArea::mouse_handler))
             .unwrap();
+        game_area
+            .on(EventType::RightClick, Box::new(GameArea::context_menu_handler))
+            .unwrap();
 
         game_area
     }
 }
 
+/// Creates the universe for a single player game, without any cells set.
+fn new_universe() -> ConwayResult<Universe> {
+    bigbang().birth()
+}
+
+/// The settings shared by every single player universe.
+fn bigbang() -> BigBang {
+    // we're going to have to tear this all out when this becomes a real game
+    let player0_writable = Region::new(100, 70, 34, 16);
+    let player1_writable = Region::new(0, 0, 80, 80);
+
+    let player0 = PlayerBuilder::new(player0_writable);
+    let player1 = PlayerBuilder::new(player1_writable);
+    let players = vec![player0, player1];
+
+    BigBang::new()
+        .width(UNIVERSE_WIDTH_IN_CELLS)
+        .height(UNIVERSE_HEIGHT_IN_CELLS)
+        .server_mode(true) // TODO will change to false once we get server support up
+        // Currently 'client' is technically both client and server
+        .history(HISTORY_SIZE)
+        .fog_radius(FOG_RADIUS)
+        .add_players(players)
+}
+
 fn init_patterns(uni: &mut Universe) -> ConwayResult<()> {
     let _pat = Pattern("10$10b16W$10bW14bW$10bW14bW$10bW14bW$10bW14bW$10bW14bW$10bW14bW$10bW14bW$10bW14bW$10bW$10bW$10bW$10b16W48$100b2A5b2A$100b2A5b2A2$104b2A$104b2A5$122b2Ab2A$121bA5bA$121bA6bA2b2A$121b3A3bA3b2A$126bA!".to_owned());
 
@@ -239,23 +359,141 @@ impl GameArea {
         Ok(Handled::NotHandled)
     }
 
-    fn update_handler(
-        obj: &mut dyn EmitEvent,
-        _uictx: &mut UIContext,
-        _evt: &Event,
-    ) -> Result<Handled, Box<dyn Error>> {
+    /// Fills the resized window, or the smallest size the UI is laid out for if the window is even
+    /// smaller.
+    fn resize_handler(obj: &mut dyn EmitEvent, _uictx: &mut UIContext, evt: &Event) -> Result<Handled, Box<dyn Error>> {
         // Unwrap OK because we are guaranteed a GameArea
         let game_area = obj.downcast_mut::<GameArea>().unwrap();
-        let game_state = &mut game_area.game_state;
+        let (w, h) = evt
+            .window_size
+            .ok_or_else(|| -> Box<dyn Error> { "resize event has no window size".into() })?;
+        game_area.set_size(f32::max(w, MIN_UI_WIDTH), f32::max(h, MIN_UI_HEIGHT))?;
+        Ok(Handled::NotHandled)
+    }
+
+    fn update_handler(obj: &mut dyn EmitEvent, uictx: &mut UIContext, _evt: &Event) -> Result<Handled, Box<dyn Error>> {
+        // Unwrap OK because we are guaranteed a GameArea
+        let game_area = obj.downcast_mut::<GameArea>().unwrap();
+
+        let video = &uictx.config.get().video;
+        game_area.grid_line_width = if video.grid_lines {
+            Some(video.grid_line_width)
+        } else {
+            None
+        };
+
+        if let Some(ref mut playback) = game_area.playback {
+            let generation = game_area.uni.latest_gen();
+            let player_id = playback.header().player_id;
+            let actions = playback.take_actions(generation);
+            for action in actions.iter() {
+                match action {
+                    ReplayAction::Pause => game_area.game_state.running = false,
+                    ReplayAction::Resume => game_area.game_state.running = true,
+                    ReplayAction::Step => {
+                        game_area.game_state.single_step = true;
+                        game_area.game_state.running = false;
+                    }
+                    _ => {
+                        if let Err(e) = replay::apply_action(&mut game_area.uni, player_id, action) {
+                            error!("Failed to replay {:?} at generation {}: {}", action, generation, e);
+                        }
+                        game_area.cell_mesh = None;
+                    }
+                }
+            }
+            if !actions.is_empty() && playback.is_finished() {
+                info!("Replay has no more recorded actions as of generation {}", generation);
+            }
+        }
 
+        let game_state = &mut game_area.game_state;
         if game_state.first_gen_was_drawn && (game_state.running || game_state.single_step) {
-            game_area.uni.next(); // next generation
             game_state.single_step = false;
+            let advanced = if game_area.playback.is_some() {
+                game_area.play_frame()
+            } else {
+                game_area.uni.next(); // next generation
+                true
+            };
+            if advanced {
+                game_area.generation_done();
+                // Undoing an edit from an earlier generation would make no sense, so forget them all
+                game_area.forget_edits();
+            }
         }
+        game_area
+            .generation_rate
+            .record(Instant::now(), game_area.uni.latest_gen());
+        game_area.autosave_if_due();
+
+        // Drop unconfirmed cell changes that the server never responded to
+        let now = Instant::now();
+        game_area
+            .pending_actions
+            .reconcile(None, now, PENDING_ACTION_EXPIRY, |_, _| CellState::Dead);
+        game_area
+            .pending_actions
+            .expire_rejected(now, REJECTED_ACTION_FLASH_DURATION);
 
         Ok(NotHandled)
     }
 
+    /// Opens a menu with the same things Ctrl-C, Ctrl-V and the Clear key do, for the mouse.
+    fn context_menu_handler(
+        obj: &mut dyn EmitEvent,
+        uictx: &mut UIContext,
+        evt: &Event,
+    ) -> Result<Handled, Box<dyn Error>> {
+        // Unwrap OK because we are guaranteed a GameArea
+        let game_area = obj.downcast_mut::<GameArea>().unwrap();
+        let id = match game_area.id {
+            Some(ref id) => id.clone(),
+            None => return Ok(NotHandled),
+        };
+        let items = vec![
+            MenuItem::new("Copy", Box::new(GameArea::copy_item_handler)),
+            MenuItem::new("Paste", Box::new(GameArea::paste_item_handler)),
+            MenuItem::new("Clear", Box::new(GameArea::clear_item_handler)),
+        ];
+        let font_info = FontInfo::new(uictx.ggez_context, game_area.font, None);
+        let point = evt.point.unwrap(); // unwrap safe b/c mouse clicks must have a point
+        uictx.open_context_menu(point, ContextMenu::new(id, font_info, items));
+        Ok(Handled)
+    }
+
+    fn copy_item_handler(
+        obj: &mut dyn EmitEvent,
+        uictx: &mut UIContext,
+        _evt: &Event,
+    ) -> Result<Handled, Box<dyn Error>> {
+        let game_area = obj.downcast_mut::<GameArea>().unwrap();
+        game_area.copy_selection(uictx);
+        Ok(Handled)
+    }
+
+    fn paste_item_handler(
+        obj: &mut dyn EmitEvent,
+        uictx: &mut UIContext,
+        _evt: &Event,
+    ) -> Result<Handled, Box<dyn Error>> {
+        let game_area = obj.downcast_mut::<GameArea>().unwrap();
+        game_area.paste(uictx);
+        Ok(Handled)
+    }
+
+    fn clear_item_handler(
+        obj: &mut dyn EmitEvent,
+        _uictx: &mut UIContext,
+        _evt: &Event,
+    ) -> Result<Handled, Box<dyn Error>> {
+        let game_area = obj.downcast_mut::<GameArea>().unwrap();
+        // stops inserting a pattern, and forgets the selected cells
+        game_area.game_state.insert_mode = None;
+        game_area.game_state.selection = None;
+        Ok(Handled)
+    }
+
     fn keypress_handler(
         obj: &mut dyn EmitEvent,
         uictx: &mut UIContext,
@@ -268,81 +506,184 @@ impl GameArea {
             return Ok(NotHandled);
         }
 
-        let game_area_state = &mut game_area.game_state;
+        // Keys rebound on the Options screen work right away
+        if !game_area.keybindings.is_from(&uictx.config.get().keybindings) {
+            game_area.keybindings = KeyBindings::from_settings(&uictx.config.get().keybindings);
+        }
 
         if let Some(KeyCodeOrChar::KeyCode(keycode)) = evt.key {
-            match keycode {
-                KeyCode::Key1 => {
-                    // pressing 1 clears selection
-                    game_area_state.insert_mode = None;
-                }
-                k if k >= KeyCode::Key2 && k <= KeyCode::Key0 => {
-                    // select a pattern
-                    let grid_info_result = bit_pattern_from_char(&mut uictx.config, keycode);
-                    let grid_info = handle_error! {grid_info_result -> (BitGrid, usize, usize),
-                        ConwayError => |e| {
-                            return Err(format!("Invalid pattern bound to keycode {:?}: {}", keycode, e).into())
-                        }
-                    }?;
-                    game_area_state.insert_mode = Some(grid_info);
+            // Ctrl-Z undoes; Ctrl-Y and Ctrl-Shift-Z redo. Edits are forgotten every generation, so
+            // this is only done while paused.
+            if evt.ctrl_pressed && (keycode == KeyCode::Z || keycode == KeyCode::Y) {
+                if game_area.game_state.running {
+                    uictx.notify(Severity::Info, "Pause the game to undo or redo edits");
+                } else if keycode == KeyCode::Z && !evt.shift_pressed {
+                    game_area.undo();
+                } else {
+                    game_area.redo();
                 }
-                KeyCode::Return => {
-                    let chatbox_pane_id = uictx.static_node_ids.chatbox_pane_id.clone();
-                    uictx.child_event(Event::new_request_focus(chatbox_pane_id));
+                return Ok(Handled);
+            }
+
+            // Ctrl-C copies the selected cells and picks them up to be stamped; Ctrl-V picks up
+            // whatever was copied last
+            if evt.ctrl_pressed && (keycode == KeyCode::C || keycode == KeyCode::V) {
+                if evt.key_repeating {
+                    // already done
+                } else if keycode == KeyCode::C {
+                    game_area.copy_selection(uictx);
+                } else {
+                    game_area.paste(uictx);
                 }
-                KeyCode::R => {
-                    if !evt.key_repeating {
-                        game_area_state.running = !game_area_state.running;
+                return Ok(Handled);
+            }
+        }
+
+        let game_area_state = &mut game_area.game_state;
+        let mut recorded = None;
+        let mut seek_by = 0;
+        let mut toggle_edges = false;
+        let mut take_screenshot = false;
+        let mut export_universe = false;
+
+        if let Some(KeyCodeOrChar::KeyCode(keycode)) = evt.key {
+            // While a pattern is selected, R rotates it and F flips it, taking precedence over
+            // whatever they are bound to
+            if let Some(ref mut insert_mode) = game_area_state.insert_mode {
+                match keycode {
+                    KeyCode::R if !evt.key_repeating => {
+                        rotate_pattern(insert_mode, Rotation::CW);
+                        return Ok(Handled);
                     }
+                    KeyCode::F if !evt.key_repeating => {
+                        let (ref mut grid, width, height) = *insert_mode;
+                        grid.flip_horizontal(width, height).unwrap_or_else(|e| {
+                            error!("Failed to flip pattern: {:?}", e);
+                        });
+                        return Ok(Handled);
+                    }
+                    _ => {}
                 }
-                KeyCode::Space => {
-                    game_area_state.single_step = true;
-                    game_area_state.running = false;
-                }
-                KeyCode::Up => {
-                    game_area_state.arrow_input = (0, -1);
-                }
-                KeyCode::Down => {
-                    game_area_state.arrow_input = (0, 1);
-                }
-                KeyCode::Left => {
-                    game_area_state.arrow_input = (-1, 0);
-                }
-                KeyCode::Right => {
-                    game_area_state.arrow_input = (1, 0);
-                }
-                KeyCode::Plus | KeyCode::Equals => {
-                    uictx.viewport.adjust_zoom_level(ZoomDirection::ZoomIn);
-                    let cell_size = uictx.viewport.get_cell_size();
-                    uictx.config.modify(|settings| {
-                        settings.gameplay.zoom = cell_size;
-                    });
-                }
-                KeyCode::Minus => {
-                    uictx.viewport.adjust_zoom_level(ZoomDirection::ZoomOut);
-                    let cell_size = uictx.viewport.get_cell_size();
-                    uictx.config.modify(|settings| {
-                        settings.gameplay.zoom = cell_size;
-                    });
-                }
-                KeyCode::D => {
-                    // TODO: do something with this debug code
-                    let visibility = None; // can also do Some(player_id)
-                    let pat = game_area.uni.to_pattern(visibility);
-                    println!("PATTERN DUMP:\n{}", pat.0);
-                }
-                KeyCode::Escape => {
-                    uictx.pop_screen()?;
+            }
+
+            let chord = KeyChord {
+                key:   keycode,
+                ctrl:  evt.ctrl_pressed,
+                shift: evt.shift_pressed,
+            };
+            if let Some(action) = game_area.keybindings.action(chord) {
+                match action {
+                    KeyAction::Clear => {
+                        // stops inserting a pattern, and forgets the selected cells
+                        game_area_state.insert_mode = None;
+                        game_area_state.selection = None;
+                    }
+                    KeyAction::Chat => {
+                        let chatbox_pane_id = uictx.static_node_ids.chatbox_pane_id.clone();
+                        uictx.child_event(Event::new_request_focus(chatbox_pane_id));
+                    }
+                    KeyAction::Pause => {
+                        if !evt.key_repeating {
+                            game_area_state.running = !game_area_state.running;
+                            recorded = Some(if game_area_state.running {
+                                ReplayAction::Resume
+                            } else {
+                                ReplayAction::Pause
+                            });
+                        }
+                    }
+                    KeyAction::Step => {
+                        game_area_state.single_step = true;
+                        game_area_state.running = false;
+                        recorded = Some(ReplayAction::Step);
+                    }
+                    KeyAction::ZoomIn => {
+                        game_area.view.adjust_zoom_level(ZoomDirection::ZoomIn);
+                        let cell_size = game_area.view.get_cell_size();
+                        uictx.config.modify(|settings| {
+                            settings.gameplay.zoom = cell_size;
+                        });
+                    }
+                    KeyAction::ZoomOut => {
+                        game_area.view.adjust_zoom_level(ZoomDirection::ZoomOut);
+                        let cell_size = game_area.view.get_cell_size();
+                        uictx.config.modify(|settings| {
+                            settings.gameplay.zoom = cell_size;
+                        });
+                    }
+                    KeyAction::PanLeft => game_area_state.arrow_input = (-1, 0),
+                    KeyAction::PanRight => game_area_state.arrow_input = (1, 0),
+                    KeyAction::PanUp => game_area_state.arrow_input = (0, -1),
+                    KeyAction::PanDown => game_area_state.arrow_input = (0, 1),
+                    KeyAction::Minimap => {
+                        if !evt.key_repeating {
+                            game_area_state.show_minimap = !game_area_state.show_minimap;
+                        }
+                    }
+                    KeyAction::Wall => {
+                        if !evt.key_repeating {
+                            game_area_state.wall_mode = !game_area_state.wall_mode;
+                            let message = if game_area_state.wall_mode {
+                                "Building walls; clicking a cell puts a wall there"
+                            } else {
+                                "Drawing cells"
+                            };
+                            uictx.notify(Severity::Info, message);
+                        }
+                    }
+                    KeyAction::SeekBack => seek_by = -(REPLAY_SEEK_GENERATIONS as isize),
+                    KeyAction::SeekForward => seek_by = REPLAY_SEEK_GENERATIONS as isize,
+                    KeyAction::Edges => toggle_edges = !evt.key_repeating,
+                    KeyAction::Screenshot => take_screenshot = !evt.key_repeating,
+                    KeyAction::ExportUniverse => export_universe = !evt.key_repeating,
+                    KeyAction::Stats => {
+                        if !evt.key_repeating {
+                            game_area_state.show_stats = !game_area_state.show_stats;
+                        }
+                    }
+                    KeyAction::GridLines => {
+                        if !evt.key_repeating {
+                            let mut shown = false;
+                            uictx.config.modify(|settings| {
+                                settings.video.grid_lines = !settings.video.grid_lines;
+                                shown = settings.video.grid_lines;
+                            });
+                            let width = uictx.config.get().video.grid_line_width;
+                            if shown && !grid_lines_fit(game_area.view.get_cell_size(), width) {
+                                uictx.notify(Severity::Info, "Grid lines are shown once zoomed in further");
+                            }
+                        }
+                    }
                 }
-                _ => {
-                    error!("Unrecognized keycode {:?} in GameArea keypress_handler", keycode);
-                    return Ok(NotHandled);
+            } else {
+                // keys that can't be rebound
+                match keycode {
+                    k if k >= KeyCode::Key2 && k <= KeyCode::Key0 => {
+                        // select a pattern
+                        let grid_info_result = bit_pattern_from_char(&mut uictx.config, keycode);
+                        let grid_info = handle_error! {grid_info_result -> (BitGrid, usize, usize),
+                            ConwayError => |e| {
+                                return Err(format!("Invalid pattern bound to keycode {:?}: {}", keycode, e).into())
+                            }
+                        }?;
+                        game_area_state.insert_mode = Some(grid_info);
+                    }
+                    KeyCode::D => {
+                        // TODO: do something with this debug code
+                        let visibility = None; // can also do Some(player_id)
+                        let pat = game_area.uni.to_pattern(visibility);
+                        println!("PATTERN DUMP:\n{}", pat.0);
+                    }
+                    _ => {
+                        error!("Unrecognized keycode {:?} in GameArea keypress_handler", keycode);
+                        return Ok(NotHandled);
+                    }
                 }
             }
         }
 
         if evt.shift_pressed && game_area_state.arrow_input != (0, 0) {
-            if let Some((ref mut grid, ref mut width, ref mut height)) = game_area_state.insert_mode {
+            if let Some(ref mut insert_mode) = game_area_state.insert_mode {
                 let rotation = match game_area_state.arrow_input {
                     (-1, 0) => Some(Rotation::CCW),
                     (1, 0) => Some(Rotation::CW),
@@ -350,85 +691,290 @@ impl GameArea {
                     _ => None, // do nothing in this case
                 };
                 if let Some(rotation) = rotation {
-                    grid.rotate(*width, *height, rotation).unwrap_or_else(|e| {
-                        error!("Failed to rotate pattern {:?}: {:?}", rotation, e);
-                    });
-                    // reverse the stored width and height
-                    let (new_width, new_height) = (*height, *width);
-                    *width = new_width;
-                    *height = new_height;
+                    rotate_pattern(insert_mode, rotation);
                 } else {
                     info!("Ignoring Shift-<Up/Down>");
                 }
             }
         }
+
+        if let Some(action) = recorded {
+            game_area.record(action);
+        }
+        if seek_by != 0 && game_area.playback.is_some() {
+            let generation = (game_area.uni.latest_gen() as isize + seek_by).max(0) as usize;
+            game_area.seek_playback(generation)?;
+        }
+        if toggle_edges {
+            if game_area.playback.is_some() || game_area.predict_actions {
+                // the replay or the server decides how the universe behaves
+                uictx.notify(Severity::Warn, "The edges can only be changed in a single player game");
+            } else {
+                let message = match game_area.toggle_edges()? {
+                    EdgeBehavior::Toroidal => "Starting over; the universe wraps around at the edges",
+                    EdgeBehavior::Bounded => "Starting over; nothing lives past the edges of the universe",
+                };
+                uictx.notify(Severity::Info, message);
+            }
+        }
+        // The PNG files are written in the background, which posts a toast once done
+        if take_screenshot {
+            match screenshot(uictx.ggez_context) {
+                Ok(pixels) => {
+                    export::save_png_in_background(export::picture_path("screenshot"), pixels, uictx.notifier.clone());
+                }
+                Err(e) => uictx.notify(Severity::Error, format!("Failed to take a screenshot: {}", e)),
+            }
+        }
+        if export_universe {
+            let pixels = export::rasterize_universe(
+                &game_area.uni,
+                Some(CURRENT_PLAYER_ID),
+                &game_area.color_settings,
+                EXPORT_CELL_PIXELS,
+            );
+            export::save_png_in_background(export::picture_path("universe"), pixels, uictx.notifier.clone());
+        }
         Ok(Handled)
     }
 
-    fn mouse_handler(obj: &mut dyn EmitEvent, uictx: &mut UIContext, evt: &Event) -> Result<Handled, Box<dyn Error>> {
+    fn mouse_handler(obj: &mut dyn EmitEvent, _uictx: &mut UIContext, evt: &Event) -> Result<Handled, Box<dyn Error>> {
         // Unwrap OK because we are guaranteed a GameArea
         let game_area = obj.downcast_mut::<GameArea>().unwrap();
-        let game_area_state = &mut game_area.game_state;
         use ggez::input::mouse::MouseButton;
 
+        if game_area.playback.is_some() {
+            // the universe may only be changed by the replay
+            return Ok(NotHandled);
+        }
+
+        if evt.button != Some(MouseButton::Left) {
+            return Ok(NotHandled);
+        }
+        let mouse_pos = evt.point.unwrap(); //unwrap safe b/c mouse clicks must have a point
+        let cell_under_mouse = game_area.cell_at(mouse_pos);
         let mut event_handled = NotHandled;
 
-        if let Some(MouseButton::Left) = evt.button {
-            let mouse_pos = evt.point.unwrap(); //unwrap safe b/c mouse clicks must have a point
+        let game_state = &mut game_area.game_state;
+        let can_select = game_state.insert_mode.is_none() && game_state.drag_draw.is_none();
+        if game_state.selecting || (evt.shift_pressed && can_select) {
+            // Shift-dragging selects a rectangle of cells to copy, instead of drawing
+            match (evt.what, cell_under_mouse) {
+                (EventType::MouseButtonHeld, Some(cell)) if !game_state.selecting => {
+                    game_state.selection = Some((cell, cell));
+                    game_state.selecting = true;
+                }
+                (EventType::Drag, Some(cell)) if game_state.selecting => {
+                    game_state.selection = game_state.selection.map(|(start, _)| (start, cell));
+                }
+                (EventType::Click, _) => {
+                    if !game_state.selecting {
+                        // Pressed and released before a MouseButtonHeld could be seen
+                        game_state.selection = cell_under_mouse.map(|cell| (cell, cell));
+                    }
+                    game_state.selecting = false;
+                }
+                _ => return Ok(NotHandled),
+            }
+            return Ok(Handled);
+        }
 
-            if let Some((ref grid, width, height)) = game_area_state.insert_mode {
-                // inserting a pattern
-                if evt.what == EventType::Click {
-                    if let Some(cell) = uictx.viewport.get_cell(mouse_pos) {
-                        let insert_col = cell.col as isize - (width / 2) as isize;
-                        let insert_row = cell.row as isize - (height / 2) as isize;
-                        let dst_region = Region::new(insert_col, insert_row, width, height);
+        if let Some((ref grid, width, height)) = game_area.game_state.insert_mode {
+            // inserting a pattern; only the part of it within the universe, since it never wraps
+            if evt.what == EventType::Click {
+                let stamp = cell_under_mouse
+                    .and_then(|cell| clip_stamp(grid, stamp_region(cell, width, height), &game_area.uni));
+                match stamp {
+                    Some((grid, dst_region)) => {
+                        let (insert_col, insert_row) = (dst_region.left(), dst_region.top());
+                        let before = game_area.cell_states_in(dst_region);
                         game_area
                             .uni
-                            .copy_from_bit_grid(grid, dst_region, Some(CURRENT_PLAYER_ID));
+                            .copy_from_bit_grid(&grid, dst_region, Some(CURRENT_PLAYER_ID));
+                        let mut edit = Edit::new();
+                        for (col, row, before_state) in before {
+                            edit.record(col, row, before_state, history::cell_state(&game_area.uni, col, row));
+                        }
+                        game_area.history.push(edit);
+                        let action = ReplayAction::Insert {
+                            left:    insert_col,
+                            top:     insert_row,
+                            width:   dst_region.width(),
+                            height:  dst_region.height(),
+                            pattern: grid.to_pattern(None).0,
+                        };
+                        game_area.record(action);
+                        game_area.mark_changed();
 
                         event_handled = Handled;
-                    } else {
-                        error!("Failed to get cell coordinates from mouse position during Click");
                     }
+                    None => debug!("Ignoring Click outside of the universe"),
                 }
-            } else {
-                // not inserting a pattern, just drawing single cells
-                match evt.what {
-                    EventType::Click => {
-                        // release
-                        game_area_state.drag_draw = None;
-                        event_handled = Handled;
+            }
+        } else {
+            // not inserting a pattern, just drawing single cells
+            match evt.what {
+                EventType::Click => {
+                    // release
+                    if game_area.game_state.drag_draw.is_none() {
+                        // Pressed and released before a MouseButtonHeld could be seen
+                        if let Some(cell) = cell_under_mouse {
+                            game_area.draw_cell(cell);
+                        }
                     }
-                    EventType::Drag => {
-                        // hold + motion
-                        if let Some(cell) = uictx.viewport.get_cell(mouse_pos) {
-                            // Only make dead cells alive
-                            if let Some(cell_state) = game_area_state.drag_draw {
-                                game_area.uni.set(cell.col, cell.row, cell_state, CURRENT_PLAYER_ID);
-                                event_handled = Handled;
+                    game_area.game_state.drag_draw = None;
+                    game_area.game_state.last_drag_cell = None;
+                    game_area.finish_stroke();
+                    event_handled = Handled;
+                }
+                EventType::Drag => {
+                    // hold + motion
+                    if let Some(cell) = cell_under_mouse {
+                        // Only make dead cells alive
+                        if let Some(cell_state) = game_area.game_state.drag_draw {
+                            let cells = match game_area.game_state.last_drag_cell {
+                                Some(last_cell) if game_area.paint_drag_lines => last_cell.line_to(cell),
+                                _ => vec![cell],
+                            };
+                            for cell in cells {
+                                game_area.set_cell(cell, cell_state);
                             }
+                            game_area.game_state.last_drag_cell = Some(cell);
+                            event_handled = Handled;
                         }
                     }
-                    EventType::MouseButtonHeld => {
-                        // depress, no move yet
-                        if let Some(cell) = uictx.viewport.get_cell(mouse_pos) {
-                            if game_area_state.drag_draw.is_none() {
-                                game_area_state.drag_draw =
-                                    game_area.uni.toggle(cell.col, cell.row, CURRENT_PLAYER_ID).ok();
-                                event_handled = Handled;
-                            }
-                        } else {
-                            error!("Failed to get cell coordinates from mouse position during MouseButtonHeld");
+                }
+                EventType::MouseButtonHeld => {
+                    // depress, no move yet
+                    if let Some(cell) = cell_under_mouse {
+                        if game_area.game_state.drag_draw.is_none() {
+                            game_area.stroke = Some(Edit::new());
+                            game_area.game_state.drag_draw = game_area.draw_cell(cell);
+                            game_area.game_state.last_drag_cell = Some(cell);
+                            event_handled = Handled;
                         }
+                    } else {
+                        debug!("Ignoring MouseButtonHeld outside of the universe");
                     }
-                    _ => {}
                 }
+                _ => {}
             }
         }
 
         Ok(event_handled)
     }
+
+    /// Returns the cell under `point`, or `None` if `point` is outside of this widget or the universe.
+    fn cell_at(&self, point: Point2<f32>) -> Option<Cell> {
+        if !self.dimensions.contains(point) {
+            return None;
+        }
+        self.view.get_cell(point)
+    }
+
+    /// Toggles `cell`, or in wall mode builds a wall on it, returning the new state if that could be
+    /// done. Dragging from there paints the cells passed over with that state.
+    fn draw_cell(&mut self, cell: Cell) -> Option<CellState> {
+        if self.game_state.wall_mode {
+            self.build_wall(cell)
+        } else {
+            self.toggle_cell(cell)
+        }
+    }
+
+    /// Builds a wall on `cell` on behalf of the current player, returning `CellState::Wall` if one
+    /// could be built there. There can't be a wall in fog or on another player's cell. Walls stay
+    /// put from generation to generation, and can only be undone.
+    fn build_wall(&mut self, cell: Cell) -> Option<CellState> {
+        let before = history::cell_state(&self.uni, cell.col, cell.row);
+        self.uni.set(cell.col, cell.row, CellState::Wall, CURRENT_PLAYER_ID);
+        let after = history::cell_state(&self.uni, cell.col, cell.row);
+        if after == before {
+            return None; // already a wall, or not ours to build on
+        }
+        self.track_edit(cell, before, after);
+        self.predict(cell, after);
+        self.record(ReplayAction::Set {
+            col:   cell.col,
+            row:   cell.row,
+            state: after.to_char(),
+        });
+        self.mark_changed();
+        Some(after)
+    }
+
+    /// Toggles `cell` on behalf of the current player, returning the new state if it could be toggled.
+    fn toggle_cell(&mut self, cell: Cell) -> Option<CellState> {
+        let before = history::cell_state(&self.uni, cell.col, cell.row);
+        let new_state = self.uni.toggle(cell.col, cell.row, CURRENT_PLAYER_ID).ok();
+        if let Some(cell_state) = new_state {
+            self.track_edit(cell, before, cell_state);
+            self.predict(cell, cell_state);
+            self.record(ReplayAction::Toggle {
+                col: cell.col,
+                row: cell.row,
+            });
+            self.mark_changed();
+        }
+        new_state
+    }
+
+    /// Sets `cell` to `cell_state` on behalf of the current player.
+    fn set_cell(&mut self, cell: Cell, cell_state: CellState) {
+        let before = history::cell_state(&self.uni, cell.col, cell.row);
+        self.uni.set(cell.col, cell.row, cell_state, CURRENT_PLAYER_ID);
+        let after = history::cell_state(&self.uni, cell.col, cell.row);
+        self.track_edit(cell, before, after);
+        self.predict(cell, cell_state);
+        self.record(ReplayAction::Set {
+            col:   cell.col,
+            row:   cell.row,
+            state: cell_state.to_char(),
+        });
+        self.mark_changed();
+    }
+
+    /// Adds a change to `cell` to the drag-paint stroke in progress, or else makes it an undoable
+    /// edit of its own.
+    fn track_edit(&mut self, cell: Cell, before: CellState, after: CellState) {
+        if let Some(stroke) = self.stroke.as_mut() {
+            stroke.record(cell.col, cell.row, before, after);
+        } else {
+            let mut edit = Edit::new();
+            edit.record(cell.col, cell.row, before, after);
+            self.history.push(edit);
+        }
+    }
+
+    /// Ends the drag-paint stroke in progress, if any, so it can be undone.
+    fn finish_stroke(&mut self) {
+        if let Some(stroke) = self.stroke.take() {
+            self.history.push(stroke);
+        }
+    }
+
+    /// Returns the state of every cell of `region` that is inside the universe.
+    fn cell_states_in(&self, region: Region) -> Vec<(usize, usize, CellState)> {
+        let mut states = vec![];
+        if let Some(region) = region.intersection(self.uni.region()) {
+            for row in region.top()..=region.bottom() {
+                for col in region.left()..=region.right() {
+                    let (col, row) = (col as usize, row as usize);
+                    states.push((col, row, history::cell_state(&self.uni, col, row)));
+                }
+            }
+        }
+        states
+    }
+
+    /// Tracks a local change to `cell` until the server confirms it, if predicting actions.
+    fn predict(&mut self, cell: Cell, cell_state: CellState) {
+        if self.predict_actions {
+            let generation = self.uni.latest_gen();
+            self.pending_actions
+                .insert(cell.col, cell.row, generation, cell_state, Instant::now());
+        }
+    }
 }
 
 /// This takes a keyboard code and returns a `Result` whose Ok value is a `(BitGrid, width,
@@ -457,6 +1003,139 @@ fn bit_pattern_from_char(config: &mut Config, keycode: KeyCode) -> Result<(BitGr
     Ok((grid, width, height))
 }
 
+/// Rotates a pattern to be inserted, given as a `(BitGrid, width, height)` tuple, by a quarter turn.
+fn rotate_pattern(insert_mode: &mut (BitGrid, usize, usize), rotation: Rotation) {
+    let (ref mut grid, width, height) = *insert_mode;
+    grid.rotate(width, height, rotation).unwrap_or_else(|e| {
+        error!("Failed to rotate pattern {:?}: {:?}", rotation, e);
+    });
+    // reverse the stored width and height
+    insert_mode.1 = height;
+    insert_mode.2 = width;
+}
+
+/// Captures what was last drawn to the window.
+fn screenshot(ctx: &mut Context) -> GameResult<Pixels> {
+    let image = graphics::screenshot(ctx)?;
+    Ok(Pixels {
+        width:  image.width() as usize,
+        height: image.height() as usize,
+        rgba:   image.to_rgba8(ctx)?,
+    })
+}
+
+/// Returns the region covered by a pattern of the given size when inserted with `cursor` at its
+/// center.
+fn stamp_region(cursor: Cell, width: usize, height: usize) -> Region {
+    Region::new(
+        cursor.col as isize - (width / 2) as isize,
+        cursor.row as isize - (height / 2) as isize,
+        width,
+        height,
+    )
+}
+
+/// Cuts a pattern to be inserted at `region` down to the part that lies within `uni`, since
+/// patterns are never wrapped around the edges. Returns that part of the pattern, along with the
+/// region it covers, or `None` if no part of it is within `uni`.
+fn clip_stamp(grid: &BitGrid, region: Region, uni: &Universe) -> Option<(BitGrid, Region)> {
+    let clipped = region.intersection(uni.region())?;
+    if clipped == region {
+        return Some((grid.clone(), region));
+    }
+    let skip_cols = (clipped.left() - region.left()) as usize;
+    let skip_rows = (clipped.top() - region.top()) as usize;
+    let mut visible = new_bit_grid(clipped.width(), clipped.height());
+    grid.each_set(|col, row| {
+        if col < skip_cols || row < skip_rows {
+            return;
+        }
+        let (col, row) = (col - skip_cols, row - skip_rows);
+        if col < clipped.width() && row < clipped.height() {
+            set_bit(&mut visible, col, row);
+        }
+    });
+    Some((visible, clipped))
+}
+
+/// Measures how fast a count, such as the generation, goes up over the last `window` of time.
+struct RateMeter {
+    window:  Duration,
+    samples: VecDeque<(Instant, usize)>, // oldest first
+}
+
+impl RateMeter {
+    fn new(window: Duration) -> Self {
+        RateMeter {
+            window,
+            samples: VecDeque::new(),
+        }
+    }
+
+    /// Takes note of the count at `now`. If the count went down, as it does when a replay seeks
+    /// backwards or the game starts over, the rate is measured from this point on.
+    fn record(&mut self, now: Instant, count: usize) {
+        if self.samples.back().map_or(false, |&(_, last)| count < last) {
+            self.samples.clear();
+        }
+        self.samples.push_back((now, count));
+        while self
+            .samples
+            .front()
+            .map_or(false, |&(then, _)| now.duration_since(then) > self.window)
+        {
+            self.samples.pop_front();
+        }
+    }
+
+    /// How much the count went up per second across the samples in the window, or 0 if they don't
+    /// span any time yet.
+    fn per_second(&self) -> f64 {
+        match (self.samples.front(), self.samples.back()) {
+            (Some(&(first_time, first)), Some(&(last_time, last))) if last_time > first_time => {
+                (last - first) as f64 / last_time.duration_since(first_time).as_secs_f64()
+            }
+            _ => 0.0,
+        }
+    }
+}
+
+/// Whether grid lines `width` pixels wide leave enough room for cells `cell_size` pixels wide.
+fn grid_lines_fit(cell_size: f32, width: f32) -> bool {
+    cell_size >= MIN_GRID_LINE_CELL_SIZE && width > 0.0 && width * 4.0 <= cell_size
+}
+
+/// Returns the rectangle of cells with `start` and `end` at opposite corners.
+fn selected_region(start: Cell, end: Cell) -> Region {
+    let (left, right) = (start.col.min(end.col), start.col.max(end.col));
+    let (top, bottom) = (start.row.min(end.row), start.row.max(end.row));
+    Region::new(left as isize, top as isize, right - left + 1, bottom - top + 1)
+}
+
+/// Copies the live cells of `region`, which must be within `uni`, as a `(BitGrid, width, height)`
+/// tuple that can be inserted like a pattern. Walls and fog are not copied, and live cells are
+/// copied no matter which player they belong to.
+fn copy_region(uni: &Universe, region: Region) -> (BitGrid, usize, usize) {
+    let (left, top) = (region.left() as usize, region.top() as usize);
+    let (width, height) = (region.width(), region.height());
+    let mut grid = new_bit_grid(width, height);
+    for (col, row, state) in uni.cells_in_rect(left, top, width, height) {
+        if let CellState::Alive(_) = state {
+            set_bit(&mut grid, col - left, row - top);
+        }
+    }
+    (grid, width, height)
+}
+
+/// Creates an empty BitGrid big enough for a pattern of the given size.
+fn new_bit_grid(width: usize, height: usize) -> BitGrid {
+    BitGrid::new((width + 63) / 64, height)
+}
+
+fn set_bit(grid: &mut BitGrid, col: usize, row: usize) {
+    grid.modify_bits_in_word(row, col / 64, 1 << (63 - col % 64), BitOperation::Set);
+}
+
 impl Widget for GameArea {
     fn id(&self) -> Option<&NodeId> {
         self.id.as_ref()
@@ -480,7 +1159,7 @@ impl Widget for GameArea {
 
     fn set_rect(&mut self, new_dims: Rect) -> UIResult<()> {
         if new_dims.w == 0.0 || new_dims.h == 0.0 {
-            return Err(Box::new(UIError::InvalidDimensions {
+            return Err(Box::new(UIError::InvalidRect {
                 reason: format!(
                     "Cannot set the size to a width or height of GameArea {:?} to zero",
                     self.id()
@@ -489,6 +1168,7 @@ impl Widget for GameArea {
         }
 
         self.dimensions = new_dims;
+        self.view.set_rect(self.dimensions);
         Ok(())
     }
 
@@ -499,6 +1179,7 @@ impl Widget for GameArea {
     fn set_position(&mut self, x: f32, y: f32) {
         self.dimensions.x = x;
         self.dimensions.y = y;
+        self.view.set_rect(self.dimensions);
     }
 
     fn size(&self) -> (f32, f32) {
@@ -507,23 +1188,197 @@ impl Widget for GameArea {
 
     fn set_size(&mut self, w: f32, h: f32) -> UIResult<()> {
         if w == 0.0 || h == 0.0 {
-            return Err(Box::new(UIError::InvalidDimensions {
+            return Err(Box::new(UIError::InvalidRect {
                 reason: format!("Cannot set the width or height of GameArea {:?} to zero", self.id()),
             }));
         }
 
         self.dimensions.w = w;
         self.dimensions.h = h;
+        self.view.set_rect(self.dimensions);
 
         Ok(())
     }
 
     fn translate(&mut self, dest: Vector2<f32>) {
         self.dimensions.translate(dest);
+        self.view.set_rect(self.dimensions);
     }
 
-    fn draw(&mut self, _ctx: &mut Context) -> GameResult<()> {
-        // no-op; dummy widget
+    fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
+        // Generations are only calculated once the first one has been drawn
+        self.game_state.first_gen_was_drawn = true;
+
+        let theme = theme::current();
+        if !Arc::ptr_eq(&theme, &self.theme) {
+            self.color_settings = ColorSettings::from_theme(&theme);
+            self.theme = theme;
+            self.cell_mesh = None; // built with the old colors
+        }
+
+        let view_rect = self.view.get_rect();
+
+        // grid background
+        let rectangle = graphics::Mesh::new_rectangle(
+            ctx,
+            GRID_DRAW_STYLE.to_draw_mode(),
+            view_rect,
+            self.color_settings.get_color(None),
+        )?;
+        graphics::draw(ctx, &rectangle, DrawParam::new())?;
+
+        let image = graphics::Image::solid(ctx, 1u16, WHITE)?; // 1x1 square
+        let mut overlay_spritebatch = SpriteBatch::new(image);
+
+        // Every cell rectangle returned by the view is already clipped to `view_rect`
+        let view = &self.view;
+        let cell_param = |rect: Rect, color: Color| {
+            DrawParam::new()
+                .dest(Point2 { x: rect.x, y: rect.y })
+                .scale(Vector2 { x: rect.w, y: rect.h })
+                .color(color)
+        };
+
+        let mut pending_cells = vec![];
+        self.pending_actions
+            .each_pending(&mut |col, row, state| pending_cells.push((col, row, state)));
+        let pending_set: HashSet<(usize, usize)> = pending_cells.iter().map(|&(col, row, _)| (col, row)).collect();
+
+        // grid non-dead cells (walls, players, fog, etc.), as a single mesh that is only rebuilt
+        // when something has changed since the last frame. Pending cells are drawn below as an
+        // overlay until the server confirms them.
+        let color_settings = &self.color_settings;
+        let key = CellMeshKey::new(&self.uni, view, pending_set);
+        if self.cell_mesh.as_ref().map_or(true, |cached| cached.key != key) {
+            let batches = CellBatches::from_universe(&self.uni, view, Some(CURRENT_PLAYER_ID), &key.skipped);
+            let mesh = batches.build_mesh(ctx, color_settings)?;
+            self.cell_mesh = Some(CachedCellMesh { key, mesh });
+        }
+
+        // Unconfirmed cell changes are drawn translucent; ones the server rejected flash briefly
+        let flash_on = timer::time_since_start(ctx).subsec_millis() % 250 < 125; // 50% duty cycle, 250ms period
+        for (col, row, state) in pending_cells {
+            if let Some(rect) = view.window_coords_from_game(Cell::new(col, row)) {
+                let mut color = color_settings.get_color(Some(state));
+                color.a = PENDING_CELL_ALPHA;
+                overlay_spritebatch.add(cell_param(rect, color));
+            }
+        }
+        if flash_on {
+            self.pending_actions
+                .each_rejected(Instant::now(), REJECTED_ACTION_FLASH_DURATION, &mut |col, row| {
+                    if let Some(rect) = view.window_coords_from_game(Cell::new(col, row)) {
+                        overlay_spritebatch.add(cell_param(rect, *REJECTED_CELL_FLASH_COLOR));
+                    }
+                });
+        }
+
+        // Ghost of the pattern to be inserted. Cells that can't be written flash red. Whatever is past
+        // the edge of the universe isn't drawn, since it is cut off when inserted.
+        if let Some((ref grid, width, height)) = self.game_state.insert_mode {
+            let player_color = color_settings.get_color(Some(CellState::Alive(Some(CURRENT_PLAYER_ID))));
+            if let Some(cursor_cell) = view.game_coords_from_window(mouse::position(ctx)) {
+                let region = stamp_region(cursor_cell, width, height);
+                let uni = &self.uni;
+                grid.each_set(|grid_col, grid_row| {
+                    let col = region.left() + grid_col as isize;
+                    let row = region.top() + grid_row as isize;
+                    if col < 0 || row < 0 {
+                        // out of range
+                        return;
+                    }
+                    let (col, row) = (col as usize, row as usize);
+                    if let Some(rect) = view.window_coords_from_game(Cell::new(col, row)) {
+                        let mut color = player_color;
+                        // only error is due to player_id out of range, so unwrap OK here
+                        if !uni.writable(col, row, CURRENT_PLAYER_ID).unwrap() {
+                            // not writable, so draw flashing red cells
+                            if flash_on {
+                                color = *INSERT_PATTERN_UNWRITABLE;
+                            } else {
+                                return;
+                            }
+                        }
+                        color.a = 0.5; // semi-transparent since this is an overlay
+                        overlay_spritebatch.add(cell_param(rect, color));
+                    }
+                });
+            }
+        }
+
+        // the selected cells, which Ctrl-C copies
+        if let Some((start, end)) = self.game_state.selection {
+            let region = selected_region(start, end);
+            for row in region.top()..=region.bottom() {
+                for col in region.left()..=region.right() {
+                    if let Some(rect) = view.window_coords_from_game(Cell::new(col as usize, row as usize)) {
+                        overlay_spritebatch.add(cell_param(rect, *SELECTION_COLOR));
+                    }
+                }
+            }
+        }
+
+        // lines between the cells, unless they would crowd out the cells themselves. Each is centered
+        // on the 1 pixel gap left between neighboring cells.
+        let cell_size = view.get_cell_size();
+        if let Some(width) = self.grid_line_width.filter(|&width| grid_lines_fit(cell_size, width)) {
+            if let Some(grid_rect) = intersection(view.get_rect_from_origin(), view_rect) {
+                let offset = (width + 1.0) / 2.0;
+                let (xs, ys) = view.grid_line_coords();
+                let mut lines = vec![];
+                for x in xs {
+                    lines.push(Rect::new(x - offset, grid_rect.y, width, grid_rect.h));
+                }
+                for y in ys {
+                    lines.push(Rect::new(grid_rect.x, y - offset, grid_rect.w, width));
+                }
+                for line in lines {
+                    if let Some(line) = intersection(line, grid_rect) {
+                        overlay_spritebatch.add(cell_param(line, self.theme.grid_line));
+                    }
+                }
+            }
+        }
+
+        // grid foreground (dead cells), clipped to this widget
+        if let Some(clipped_rect) = intersection(view.get_rect_from_origin(), view_rect) {
+            let origin = DrawParam::new().dest(Point2 { x: 0.0, y: 0.0 });
+            let rectangle = graphics::Mesh::new_rectangle(
+                ctx,
+                GRID_DRAW_STYLE.to_draw_mode(),
+                clipped_rect,
+                color_settings.get_color(Some(CellState::Dead)),
+            )?;
+
+            graphics::draw(ctx, &rectangle, origin)?;
+            if let Some(mesh) = self.cell_mesh.as_ref().and_then(|cached| cached.mesh.as_ref()) {
+                graphics::draw(ctx, mesh, origin)?;
+            }
+            graphics::draw(ctx, &overlay_spritebatch, origin)?;
+        }
+
+        // generation counter, along with how fast things are going if the stats overlay is on
+        let gen_counter = if self.game_state.show_stats {
+            format!(
+                "Gen: {}  Gens/s: {:.1}  FPS: {:.0}",
+                self.uni.latest_gen(),
+                self.generation_rate.per_second(),
+                timer::fps(ctx)
+            )
+        } else {
+            self.uni.latest_gen().to_string()
+        };
+        draw_text(
+            ctx,
+            self.font,
+            self.theme.gen_counter,
+            gen_counter,
+            &Point2 {
+                x: self.dimensions.x,
+                y: self.dimensions.y,
+            },
+        )?;
+
         Ok(())
     }
 
@@ -549,7 +1404,13 @@ impl GameArea {
             single_step:         self.game_state.single_step,
             arrow_input:         self.game_state.arrow_input,
             drag_draw:           self.game_state.drag_draw,
+            last_drag_cell:      self.game_state.last_drag_cell,
             insert_mode:         self.insert_mode(),
+            show_minimap:        self.game_state.show_minimap,
+            show_stats:          self.game_state.show_stats,
+            wall_mode:           self.game_state.wall_mode,
+            selection:           self.game_state.selection,
+            selecting:           self.game_state.selecting,
         }
     }
 
@@ -561,8 +1422,365 @@ impl GameArea {
         self.game_state.drag_draw = dd;
     }
 
-    pub fn first_gen_drawn(&mut self) {
-        self.game_state.first_gen_was_drawn = true;
+    /// Unconfirmed local cell changes, along with recently rejected ones.
+    pub fn pending_actions(&self) -> &PendingActions {
+        &self.pending_actions
+    }
+
+    /// Reconcile unconfirmed local cell changes after an authoritative update covering
+    /// `confirmed_gen` has been applied to `self.uni`. Changes the server disagreed with are
+    /// reverted to the server's state and flashed briefly.
+    pub fn reconcile_pending_actions(&mut self, confirmed_gen: usize) {
+        let uni = &mut self.uni;
+        let rejected = self.pending_actions.reconcile(
+            Some(confirmed_gen),
+            Instant::now(),
+            PENDING_ACTION_EXPIRY,
+            |col, row| uni.get_cell_state(col, row, Some(CURRENT_PLAYER_ID)),
+        );
+        for (col, row) in rejected {
+            info!("Server rejected cell change at col={}, row={}", col, row);
+        }
+    }
+
+    /// Applies a diff sent by the server to `self.uni`, then reconciles unconfirmed local cell
+    /// changes against it. Returns the new generation, or `None` if the diff is based on a
+    /// generation this universe doesn't have.
+    pub fn apply_server_diff(&mut self, diff: &GenStateDiff) -> ConwayResult<Option<usize>> {
+        let opt_gen = self.uni.apply(diff, Some(CURRENT_PLAYER_ID))?;
+        if let Some(gen) = opt_gen {
+            self.forget_edits();
+            self.mark_changed();
+            self.reconcile_pending_actions(gen);
+        }
+        Ok(opt_gen)
+    }
+
+    /// Checks the latest generation against the server's checksum of `generation`. Returns `None`
+    /// if the latest generation is a different one, since only the latest can be checked.
+    pub fn matches_server_checksum(&self, generation: usize, checksum: u64) -> Option<bool> {
+        if self.uni.latest_gen() != generation {
+            return None;
+        }
+        Some(self.uni.checksum(Some(CURRENT_PLAYER_ID)) == checksum)
+    }
+
+    /// Throws away a universe that no longer matches the server's, so that the full state the
+    /// server sends next can be applied to an empty one.
+    pub fn discard_universe(&mut self) -> ConwayResult<()> {
+        self.uni = new_universe()?;
+        self.pending_actions = PendingActions::new();
+        self.forget_edits();
+        self.mark_changed();
+        Ok(())
+    }
+
+    /// Starts appending this session to a new replay file at `path`, beginning with the current
+    /// state of the universe.
+    pub fn start_recording(&mut self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let header = ReplayHeader::from_universe(&self.uni, CURRENT_PLAYER_ID, REPLAY_CHECKPOINT_INTERVAL);
+        let writer = BufWriter::new(File::create(path)?);
+        self.recorder = Some(Recorder::new(writer, &header)?);
+        info!("Recording session to {:?}", path);
+        Ok(())
+    }
+
+    /// Replaces the universe with the starting point of `replay`, whose actions and generations are
+    /// then played back as recorded. From then on, input that would change the universe is ignored,
+    /// and the seek keys jump around in the replay.
+    pub fn start_playback(&mut self, replay: Replay) -> Result<(), Box<dyn Error>> {
+        let mut uni = new_universe()?;
+        replay.header.restore(&mut uni)?;
+        self.uni = uni;
+        self.cell_mesh = None;
+        self.recorder = None;
+        self.autosave = None;
+        self.pending_actions = PendingActions::new();
+        self.history.clear();
+        self.stroke = None;
+        self.game_state.insert_mode = None;
+        self.game_state.selection = None;
+        self.playback = Some(Playback::new(replay));
+        Ok(())
+    }
+
+    /// Ends the game in progress, including any recording or playback, and sets up a new one in
+    /// its place. The camera is left where it was.
+    pub fn reset(&mut self) -> ConwayResult<()> {
+        let mut uni = bigbang().edge_behavior(self.edges).birth()?;
+        init_patterns(&mut uni)?;
+        self.uni = uni;
+        self.cell_mesh = None;
+        if self.recorder.take().is_some() {
+            info!("Stopped recording since the game has ended");
+        }
+        self.playback = None;
+        self.pending_actions = PendingActions::new();
+        self.history.clear();
+        self.stroke = None;
+        self.game_state = GameAreaState {
+            show_minimap: self.game_state.show_minimap,
+            show_stats: self.game_state.show_stats,
+            ..GameAreaState::default()
+        };
+        if let Some((_, ref mut schedule)) = self.autosave {
+            schedule.mark_saved(self.uni.latest_gen(), Instant::now());
+        }
+        Ok(())
+    }
+
+    /// Switches the single player universe between wrapping around at the edges and being bounded
+    /// by them, and starts the game over so that the difference shows from the beginning. A running
+    /// game keeps running. Returns the new edge behavior.
+    fn toggle_edges(&mut self) -> ConwayResult<EdgeBehavior> {
+        self.edges = match self.edges {
+            EdgeBehavior::Toroidal => EdgeBehavior::Bounded,
+            EdgeBehavior::Bounded => EdgeBehavior::Toroidal,
+        };
+        let running = self.game_state.running;
+        self.reset()?;
+        self.game_state.running = running;
+        info!("Edge behavior is now {:?}", self.edges);
+        Ok(self.edges)
+    }
+
+    /// Picks up `pattern` as if it had been chosen with a number key, so that the next click
+    /// stamps it into the universe.
+    pub fn start_inserting(&mut self, pattern: &PatternFile) -> ConwayResult<()> {
+        let grid = pattern.to_new_bit_grid()?;
+        self.game_state.insert_mode = Some((grid, pattern.width(), pattern.height()));
+        Ok(())
+    }
+
+    /// Copies the live cells of the selection, and picks them up to be stamped like a pattern. The
+    /// selection is then forgotten.
+    fn copy_selection(&mut self, uictx: &UIContext) {
+        let region = match self.game_state.selection.take() {
+            Some((start, end)) => selected_region(start, end),
+            None => {
+                uictx.notify(Severity::Info, "Shift-drag over some cells to select them first");
+                return;
+            }
+        };
+        self.game_state.selecting = false;
+        let copied = copy_region(&self.uni, region);
+        let message = format!(
+            "Copied {}x{} cells; click to stamp them",
+            region.width(),
+            region.height()
+        );
+        uictx.notify(Severity::Info, message);
+        self.game_state.insert_mode = Some(copied.clone());
+        self.clipboard = Some(copied);
+    }
+
+    /// Picks up the cells copied last, so that the next click stamps them again.
+    fn paste(&mut self, uictx: &UIContext) {
+        match self.clipboard {
+            Some(ref copied) => self.game_state.insert_mode = Some(copied.clone()),
+            None => uictx.notify(Severity::Info, "Nothing has been copied yet"),
+        }
+    }
+
+    pub fn is_playing_back(&self) -> bool {
+        self.playback.is_some()
+    }
+
+    /// Starts handing snapshots of the universe and camera to `autosaver`, every `every_generations`
+    /// generations or after `every` has passed with unsaved changes. Either can be disabled by
+    /// setting it to zero.
+    pub fn start_autosaving(&mut self, autosaver: Autosaver, every_generations: usize, every: Duration) {
+        let schedule = AutosaveSchedule::new(every_generations, every, self.uni.latest_gen(), Instant::now());
+        info!("Autosaving to {:?}", autosaver.path());
+        self.autosave = Some((autosaver, schedule));
+    }
+
+    /// Replaces the universe and camera with those of `save`. Autosaving, if started, carries on from
+    /// the resumed generation.
+    pub fn resume_autosave(&mut self, save: &Autosave) -> Result<(), Box<dyn Error>> {
+        let mut uni = bigbang().server_mode(false).birth()?;
+        save.restore(&mut uni)?;
+        self.uni = uni;
+        self.cell_mesh = None;
+        self.view.set_cell_size(save.camera.cell_size);
+        self.view.set_origin(Point2 {
+            x: save.camera.origin_x,
+            y: save.camera.origin_y,
+        });
+        self.pending_actions = PendingActions::new();
+        self.history.clear();
+        self.stroke = None;
+        self.game_state.insert_mode = None;
+        self.game_state.selection = None;
+        if let Some((_, ref mut schedule)) = self.autosave {
+            schedule.mark_saved(self.uni.latest_gen(), Instant::now());
+        }
+        info!("Resumed autosaved game at generation {}", save.generation);
+        Ok(())
+    }
+
+    /// Replaces the keys used to control the game, until the key bindings in the config change.
+    pub fn set_keybindings(&mut self, keybindings: KeyBindings) {
+        self.keybindings = keybindings;
+    }
+
+    /// Undoes the most recent cell edit, if the universe hasn't moved on to a new generation since.
+    /// The keyboard shortcut only calls this while the game is paused.
+    pub fn undo(&mut self) {
+        if self.playback.is_some() {
+            return;
+        }
+        self.finish_stroke();
+        if let Some(edit) = self.history.undo(&mut self.uni) {
+            self.edit_applied(&edit);
+        }
+    }
+
+    /// Redoes the most recently undone cell edit.
+    pub fn redo(&mut self) {
+        if self.playback.is_some() {
+            return;
+        }
+        self.finish_stroke();
+        if let Some(edit) = self.history.redo(&mut self.uni) {
+            self.edit_applied(&edit);
+        }
+    }
+
+    /// Forgets every edit that could have been undone or redone. The stroke in progress, if any,
+    /// carries on as a new edit.
+    fn forget_edits(&mut self) {
+        self.history.clear();
+        if let Some(stroke) = self.stroke.as_mut() {
+            *stroke = Edit::new();
+        }
+    }
+
+    /// Called after an undo or redo has changed the universe.
+    fn edit_applied(&mut self, edit: &Edit) {
+        for change in edit.changes() {
+            self.predict(Cell::new(change.col, change.row), change.after);
+            self.record(ReplayAction::Set {
+                col:   change.col,
+                row:   change.row,
+                state: change.after.to_char(),
+            });
+        }
+        self.mark_changed();
+    }
+
+    /// Deletes the autosave file, if autosaving.
+    pub fn discard_autosave(&mut self) {
+        if let Some((ref autosaver, _)) = self.autosave {
+            autosaver.remove();
+        }
+    }
+
+    /// Notes that the universe was changed other than by calculating a generation.
+    fn mark_changed(&mut self) {
+        self.cell_mesh = None;
+        if let Some((_, ref mut schedule)) = self.autosave {
+            schedule.mark_changed();
+        }
+    }
+
+    /// Hands a snapshot to the autosave worker if one is due. Only the snapshot is taken here; it
+    /// is encoded and written on the worker thread.
+    fn autosave_if_due(&mut self) {
+        if let Some((ref autosaver, ref mut schedule)) = self.autosave {
+            let generation = self.uni.latest_gen();
+            let now = Instant::now();
+            if schedule.is_due(generation, now) {
+                let origin = self.view.get_origin();
+                let camera = Camera {
+                    cell_size: self.view.get_cell_size(),
+                    origin_x:  origin.x,
+                    origin_y:  origin.y,
+                };
+                autosaver.save(Autosave::from_universe(&self.uni, camera));
+                schedule.mark_saved(generation, now);
+            }
+        }
+    }
+
+    /// Appends `action` to the replay file, if recording.
+    fn record(&mut self, action: ReplayAction) {
+        let generation = self.uni.latest_gen();
+        if let Some(recorder) = self.recorder.as_mut() {
+            if let Err(e) = recorder.record(generation, action) {
+                error!("Stopped recording after failing to write to the replay file: {}", e);
+                self.recorder = None;
+            }
+        }
+    }
+
+    /// Moves a replay on to its next generation, as recorded rather than calculated. At the end of
+    /// the recording, the replay is paused instead, and false is returned.
+    fn play_frame(&mut self) -> bool {
+        let playback = match self.playback.as_ref() {
+            Some(playback) => playback,
+            None => return false,
+        };
+        match playback.play_frame(&mut self.uni) {
+            Ok(true) => return true,
+            Ok(false) => info!("Replay ended at generation {}", self.uni.latest_gen()),
+            Err(e) => error!("Failed to replay generation {}: {}", self.uni.latest_gen() + 1, e),
+        }
+        self.game_state.running = false;
+        false
+    }
+
+    /// Jumps a replay to `generation`, or as close to it as the recording goes. Going back starts
+    /// over from the beginning of the recording. Either way, nothing is calculated, so this is
+    /// quick even for long replays.
+    fn seek_playback(&mut self, generation: usize) -> Result<(), Box<dyn Error>> {
+        let playback = match self.playback.as_mut() {
+            Some(playback) => playback,
+            None => return Ok(()),
+        };
+        if generation < self.uni.latest_gen() {
+            let mut uni = new_universe()?;
+            playback.header().restore(&mut uni)?;
+            playback.rewind();
+            self.uni = uni;
+        }
+        let player_id = playback.header().player_id;
+        while self.uni.latest_gen() < generation {
+            // Pause, Resume, and Step are left to whoever is watching
+            for action in playback.take_actions(self.uni.latest_gen()) {
+                replay::apply_action(&mut self.uni, player_id, &action)?;
+            }
+            if !playback.play_frame(&mut self.uni)? {
+                break;
+            }
+            if let Some(divergence) = playback.verify(&self.uni) {
+                error!(
+                    "Replay diverged from the recording at generation {} (live cells hash {:#x}, expected {:#x})",
+                    divergence.generation, divergence.actual, divergence.expected
+                );
+            }
+        }
+        self.cell_mesh = None;
+        info!("Replay jumped to generation {}", self.uni.latest_gen());
+        Ok(())
+    }
+
+    /// Called after each new generation to write or check replay checkpoints.
+    fn generation_done(&mut self) {
+        if let Some(recorder) = self.recorder.as_mut() {
+            if let Err(e) = recorder.generation_done(&self.uni) {
+                error!("Stopped recording after failing to write to the replay file: {}", e);
+                self.recorder = None;
+            }
+        }
+        if let Some(playback) = self.playback.as_mut() {
+            if let Some(divergence) = playback.verify(&self.uni) {
+                error!(
+                    "Replay diverged from the recording at generation {} (live cells hash {:#x}, expected {:#x})",
+                    divergence.generation, divergence.actual, divergence.expected
+                );
+            }
+        }
     }
 
     pub fn insert_mode(&self) -> Option<(BitGrid, usize, usize)> {
@@ -581,7 +1799,14 @@ pub struct GameAreaState {
     pub single_step:         bool,
     pub arrow_input:         (isize, isize),
     pub drag_draw:           Option<CellState>,
+    pub last_drag_cell:      Option<Cell>, // where the previous Drag event was, to paint a line from
     pub insert_mode:         Option<(BitGrid, usize, usize)>, // pattern to be drawn on click along with width and height;
+    pub show_minimap:        bool,
+    pub show_stats:          bool, // show the generation rate and frame rate next to the generation counter
+    pub wall_mode:           bool, // clicking and dragging build walls instead of drawing cells
+    // opposite corners of the selected cells, the first being where the Shift-drag began
+    pub selection:           Option<(Cell, Cell)>,
+    pub selecting:           bool, // a Shift-drag is still moving the second corner of the selection
 }
 
 impl Default for GameAreaState {
@@ -592,7 +1817,111 @@ impl Default for GameAreaState {
             single_step:         false,
             arrow_input:         (0, 0),
             drag_draw:           None,
+            last_drag_cell:      None,
             insert_mode:         None,
+            show_minimap:        true,
+            show_stats:          false,
+            wall_mode:           false,
+            selection:           None,
+            selecting:           false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GLIDER: &str = "bo$2bo$3o!";
+
+    fn glider() -> BitGrid {
+        Pattern(GLIDER.to_owned()).to_new_bit_grid(3, 3).unwrap()
+    }
+
+    #[test]
+    fn test_selection_can_start_at_any_corner() {
+        let expected = Region::new(2, 3, 4, 5);
+        assert_eq!(selected_region(Cell::new(2, 3), Cell::new(5, 7)), expected);
+        assert_eq!(selected_region(Cell::new(5, 7), Cell::new(2, 3)), expected);
+        assert_eq!(selected_region(Cell::new(2, 7), Cell::new(5, 3)), expected);
+        assert_eq!(
+            selected_region(Cell::new(4, 4), Cell::new(4, 4)),
+            Region::new(4, 4, 1, 1)
+        );
+    }
+
+    #[test]
+    fn test_copy_region_copies_only_live_cells() {
+        let mut uni = new_universe().unwrap();
+        glider().each_set(|col, row| uni.set_unchecked(200 + col, 100 + row, CellState::Alive(None)));
+        uni.set_unchecked(200, 100, CellState::Wall);
+        uni.set_unchecked(203, 100, CellState::Alive(None)); // just outside the selection
+
+        let (grid, width, height) = copy_region(&uni, Region::new(200, 100, 3, 3));
+        assert_eq!((width, height), (3, 3));
+        assert_eq!(grid.to_pattern(None).0, GLIDER);
+    }
+
+    #[test]
+    fn test_stamps_are_clipped_at_the_edges() {
+        let uni = new_universe().unwrap();
+
+        // entirely within the universe
+        let region = Region::new(10, 10, 3, 3);
+        let (grid, clipped) = clip_stamp(&glider(), region, &uni).unwrap();
+        assert_eq!(clipped, region);
+        assert_eq!(grid.to_pattern(None).0, GLIDER);
+
+        // only the bottom row's last two cells are within the top left corner
+        let (grid, clipped) = clip_stamp(&glider(), Region::new(-1, -2, 3, 3), &uni).unwrap();
+        assert_eq!(clipped, Region::new(0, 0, 2, 1));
+        assert_eq!(grid.to_pattern(None).0, "2o!");
+
+        // only the top right cell of the pattern is within the bottom right corner, and it's dead
+        let corner = Region::new(
+            UNIVERSE_WIDTH_IN_CELLS as isize - 1,
+            UNIVERSE_HEIGHT_IN_CELLS as isize - 1,
+            3,
+            3,
+        );
+        let (grid, clipped) = clip_stamp(&glider(), corner, &uni).unwrap();
+        assert_eq!(clipped, Region::new(corner.left(), corner.top(), 1, 1));
+        assert_eq!(grid.to_pattern(None).0, "!");
+
+        assert!(clip_stamp(&glider(), Region::new(-5, -5, 3, 3), &uni).is_none());
+    }
+
+    #[test]
+    fn test_grid_lines_hide_when_zoomed_out() {
+        assert!(grid_lines_fit(MAX_CELL_SIZE, 1.0));
+        assert!(grid_lines_fit(MIN_GRID_LINE_CELL_SIZE, 1.0));
+        assert!(!grid_lines_fit(MIN_CELL_SIZE, 1.0));
+
+        // thick lines need bigger cells
+        assert!(!grid_lines_fit(MIN_GRID_LINE_CELL_SIZE, 3.0));
+        assert!(grid_lines_fit(MAX_CELL_SIZE, 3.0));
+        assert!(!grid_lines_fit(MAX_CELL_SIZE, 0.0));
+    }
+
+    #[test]
+    fn test_rate_meter() {
+        let start = Instant::now();
+        let mut meter = RateMeter::new(Duration::from_secs(1));
+        assert_eq!(meter.per_second(), 0.0);
+        meter.record(start, 10);
+        assert_eq!(meter.per_second(), 0.0); // a single sample spans no time
+
+        for i in 1..=20 {
+            meter.record(start + Duration::from_millis(100 * i), 10 + 2 * i as usize);
         }
+        // only the last second counts
+        assert_eq!(meter.samples.len(), 11);
+        assert!((meter.per_second() - 20.0).abs() < 1e-9);
+
+        // going backwards starts over
+        meter.record(start + Duration::from_millis(2100), 5);
+        assert_eq!(meter.per_second(), 0.0);
+        meter.record(start + Duration::from_millis(2600), 10);
+        assert!((meter.per_second() - 10.0).abs() < 1e-9);
     }
 }
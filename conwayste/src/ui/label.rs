@@ -20,7 +20,7 @@ use std::fmt;
 
 #[cfg(test)]
 use ggez::graphics::Font;
-use ggez::graphics::{self, Color, DrawParam, Drawable, Rect, Text, TextFragment};
+use ggez::graphics::{self, Align, Color, DrawParam, Drawable, Rect, Text, TextFragment};
 use ggez::mint::{Point2, Vector2};
 use ggez::{Context, GameResult};
 
@@ -32,12 +32,34 @@ use super::{
     widget::Widget,
     UIError, UIResult,
 };
+use crate::theme::{self, Theme, ThemeColor};
+
+/// How each line of a `Label`'s text lines up within the label's maximum width.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextAlignment {
+    Left,
+    Center,
+    Right,
+}
+
+impl From<TextAlignment> for Align {
+    fn from(alignment: TextAlignment) -> Align {
+        match alignment {
+            TextAlignment::Left => Align::Left,
+            TextAlignment::Center => Align::Center,
+            TextAlignment::Right => Align::Right,
+        }
+    }
+}
 
 pub struct Label {
     id:             Option<NodeId>,
     font_info:      FontInfo,
     color:          Color,
+    theme_color:    Option<ThemeColor>, // if Some, overrides `color`
     z_index:        usize,
+    alignment:      TextAlignment,
+    max_width:      Option<f32>, // if Some, text is wrapped to and aligned within this width
     pub textfrag:   TextFragment,
     pub dimensions: Rect,
     handler_data:   HandlerData,
@@ -109,7 +131,10 @@ impl Label {
             id: None,
             font_info,
             color,
+            theme_color: None,
             z_index: std::usize::MAX,
+            alignment: TextAlignment::Left,
+            max_width: None,
             textfrag: text_fragment,
             dimensions,
             handler_data: HandlerData::new(),
@@ -118,7 +143,6 @@ impl Label {
 
     /// Sets the text for this label. Note that the dimensions are changed by this.
     pub fn set_text(&mut self, ctx: &mut Context, text: String) {
-        let dest = self.dimensions.point();
         let text_fragment;
         #[cfg(not(test))]
         {
@@ -135,12 +159,8 @@ impl Label {
                 .font(Font::default());
         }
 
-        let text = Text::new(text_fragment.clone());
-        // unwrap safe b/c if this fails then the game is fundamentally broken and is not in a usable state
-        let mut dimensions = <Text as Drawable>::dimensions(&text, ctx).unwrap();
-        dimensions.move_to(dest);
-        self.dimensions = dimensions;
         self.textfrag = text_fragment;
+        self.refresh_dimensions(ctx);
     }
 
     /// Gets the text set for this label.
@@ -148,6 +168,81 @@ impl Label {
     pub fn text(&self) -> &str {
         &self.textfrag.text
     }
+
+    /// Draws the text in the current theme's color for `theme_color` rather than the color passed
+    /// to `new`, following the theme as it changes.
+    pub fn set_theme_color(&mut self, theme_color: ThemeColor) {
+        self.theme_color = Some(theme_color);
+    }
+
+    /// The color the text is drawn in under `theme`.
+    pub fn resolved_color(&self, theme: &Theme) -> Color {
+        self.theme_color.map_or(self.color, |which| theme.color(which))
+    }
+
+    /// Sets how each line of text lines up within the maximum width. This has no effect unless a
+    /// maximum width is set.
+    #[allow(unused)]
+    pub fn set_alignment(&mut self, ctx: &mut Context, alignment: TextAlignment) {
+        self.alignment = alignment;
+        self.refresh_dimensions(ctx);
+    }
+
+    #[allow(unused)]
+    pub fn alignment(&self) -> TextAlignment {
+        self.alignment
+    }
+
+    /// Sets the width at which text is word-wrapped, and within which it is aligned. If `None`,
+    /// the label is exactly as wide as its text, which is never wrapped. Note that the dimensions
+    /// are changed by this.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `max_width` is not positive.
+    #[allow(unused)]
+    pub fn set_max_width(&mut self, ctx: &mut Context, max_width: Option<f32>) -> UIResult<()> {
+        if let Some(w) = max_width {
+            if !(w > 0.0) {
+                return Err(Box::new(UIError::InvalidRect {
+                    reason: format!("Cannot set the maximum width of Label {:?} to {}", self.id(), w),
+                }));
+            }
+        }
+        self.max_width = max_width;
+        self.refresh_dimensions(ctx);
+        Ok(())
+    }
+
+    #[allow(unused)]
+    pub fn max_width(&self) -> Option<f32> {
+        self.max_width
+    }
+
+    /// Lays out the text fragment, applying the maximum width and alignment.
+    fn layout_text(&self) -> Text {
+        let mut text = Text::new(self.textfrag.clone());
+        if let Some(max_width) = self.max_width {
+            text.set_bounds(
+                Point2 {
+                    x: max_width,
+                    y: f32::INFINITY,
+                },
+                self.alignment.into(),
+            );
+        }
+        text
+    }
+
+    /// Recalculates the width and height from the laid out text. With a maximum width, the label
+    /// always takes up that full width so that aligned text has something to line up within.
+    fn refresh_dimensions(&mut self, ctx: &mut Context) {
+        let text = self.layout_text();
+        // unwrap safe b/c if this fails then the game is fundamentally broken and is not in a usable state
+        let recalculated = <Text as Drawable>::dimensions(&text, ctx).unwrap();
+        self.dimensions.w = self.max_width.unwrap_or(recalculated.w);
+        self.dimensions.h = recalculated.h;
+    }
 }
 
 impl Widget for Label {
@@ -175,7 +270,7 @@ impl Widget for Label {
 
     fn set_rect(&mut self, new_dims: Rect) -> UIResult<()> {
         if new_dims.w == 0.0 || new_dims.h == 0.0 {
-            return Err(Box::new(UIError::InvalidDimensions {
+            return Err(Box::new(UIError::InvalidRect {
                 reason: format!("Cannot set the width or height of Label {:?} to zero", self.id()),
             }));
         }
@@ -199,7 +294,7 @@ impl Widget for Label {
 
     fn set_size(&mut self, w: f32, h: f32) -> UIResult<()> {
         if w == 0.0 || h == 0.0 {
-            return Err(Box::new(UIError::InvalidDimensions {
+            return Err(Box::new(UIError::InvalidRect {
                 reason: format!("Cannot set the width or height of Label {:?} to zero", self.id()),
             }));
         }
@@ -216,15 +311,10 @@ impl Widget for Label {
     }
 
     fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
-        let text = Text::new(self.textfrag.clone());
-
+        self.textfrag.color = Some(self.resolved_color(&theme::current()));
         // If the text is updated, we need to refresh the dimensions of the virtual rectangle bounding it.
-        // unwrap safe b/c if this fails then the game is fundamentally broken and is not in a usable state
-        let recalculated = <Text as Drawable>::dimensions(&text, ctx).unwrap();
-        if recalculated.w != self.dimensions.w || recalculated.h != self.dimensions.h {
-            self.dimensions.w = recalculated.w;
-            self.dimensions.h = recalculated.h;
-        }
+        self.refresh_dimensions(ctx);
+        let text = self.layout_text();
 
         graphics::draw(ctx, &text, DrawParam::default().dest(self.dimensions.point()))?;
 
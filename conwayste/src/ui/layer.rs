@@ -18,7 +18,8 @@
 
 use std::error::Error;
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
 
 use ggez::graphics::{self, DrawMode, DrawParam, Rect};
 use ggez::input::keyboard::KeyCode;
@@ -30,16 +31,18 @@ use id_tree::{InsertBehavior, Node, NodeId, RemoveBehavior, Tree, TreeBuilder};
 use super::{
     common::within_widget,
     context::{Event, EventType, Handled, KeyCodeOrChar, UIContext},
+    contextmenu::{ContextMenu, MenuResponse},
     focus::{CycleType, FocusCycle},
+    toast::Notifier,
     treeview,
+    tween::{Easing, Tween},
     widget::Widget,
-    BoxedWidget, GameAreaState, Pane, UIError, UIResult,
+    BoxedWidget, GameAreaState, Pane, TextField, UIError, UIResult,
 };
 
 use crate::config;
 use crate::constants::{colors::*, LAYERING_NODE_CAPACITY, LAYERING_SWAP_CAPACITY};
-use crate::uilayout::StaticNodeIds;
-use crate::viewport::GridView;
+use crate::uilayout::{self, StaticNodeIds};
 use crate::Screen;
 
 /// Dummy Widget to serve as a root node in the tree. Serves no other purpose.
@@ -95,9 +98,13 @@ pub struct Layering {
     // (think Buttons) of the widget.
     removed_node_ids:      HashSet<NodeId>, // Set of all node-ids that have been removed from the Tree
     pub highest_z_order:   usize,           // Number of layers allocated in the system + 1
+    widget_names:          HashMap<String, NodeId>, // Optional names of widgets, unique in this layering
+    notifier:              Notifier,        // Given to the handlers of this layering's widgets, for toasts
     focus_cycles:          Vec<FocusCycle>, // For each layer, a "FocusCycle" keeping track of which widgets
-                                            // can be tabbed through to get focus, in which order, and which
-                                            // widget of these (if any) has focus.
+    // can be tabbed through to get focus, in which order, and which
+    // widget of these (if any) has focus.
+    tweens:                HashMap<NodeId, Tween<Rect>>, // Widgets being moved or resized over time
+    context_menu:          Option<ContextMenu>,          // Right-click menu, drawn over everything while open
 }
 
 /// A `Layering` is a container of one or more widgets or panes (hereby referred to as widgets),
@@ -137,9 +144,18 @@ impl Layering {
             highest_z_order:   0,
             with_transparency: false,
             focus_cycles:      vec![FocusCycle::new(CycleType::Circular)], // empty focus cycle for z_order 0
+            widget_names:      HashMap::new(),
+            notifier:          Notifier::default(),
+            tweens:            HashMap::new(),
+            context_menu:      None,
         }
     }
 
+    /// Sets where toasts posted by this layering's handlers go.
+    pub fn set_notifier(&mut self, notifier: Notifier) {
+        self.notifier = notifier;
+    }
+
     /// Returns true if an entry with the provided NodeId exists.
     fn widget_exists(&self, id: &NodeId) -> bool {
         self.widget_tree.get(id).is_ok()
@@ -176,9 +192,7 @@ impl Layering {
         if let Ok(node) = self.widget_tree.get_mut(id) {
             Ok(node.data_mut())
         } else {
-            Err(Box::new(UIError::WidgetNotFound {
-                reason: format!("{:?} not found in layering's widget list", id).to_owned(),
-            }))
+            Err(Box::new(UIError::WidgetNotFound { node_id: id.clone() }))
         }
     }
 
@@ -186,10 +200,73 @@ impl Layering {
         if let Ok(node) = self.widget_tree.get(id) {
             Ok(node.data())
         } else {
-            Err(Box::new(UIError::WidgetNotFound {
-                reason: format!("{:?} not found in layering's widget list", id).to_owned(),
-            }))
+            Err(Box::new(UIError::WidgetNotFound { node_id: id.clone() }))
+        }
+    }
+
+    /// Describes where a widget is in the widget tree for error messages, as the types of the
+    /// containers it is nested in followed by its own, like "Pane > Pane > Button".
+    pub fn widget_path(&self, id: &NodeId) -> String {
+        let node = match self.widget_tree.get(id) {
+            Ok(node) => node,
+            Err(_) => return format!("{:?} (not in the layering)", id),
+        };
+        // unwrap OK because the node exists; the root node is a dummy, so it has no type worth showing
+        let mut types: Vec<&str> = self
+            .widget_tree
+            .ancestors(id)
+            .unwrap()
+            .filter(|ancestor| ancestor.parent().is_some())
+            .map(|ancestor| ancestor.data().type_name())
+            .collect();
+        types.reverse();
+        types.push(node.data().type_name());
+        types.join(" > ")
+    }
+
+    /// Returns the NodeId of the widget with the provided name, if there is one. Widgets are named
+    /// with `set_widget_name` or `add_named_widget`.
+    pub fn widget_by_name(&self, name: &str) -> Option<NodeId> {
+        self.widget_names.get(name).cloned()
+    }
+
+    /// Gives a widget a human-readable name, so that it can be found with `widget_by_name` without
+    /// holding on to its NodeId. A widget can have more than one name.
+    ///
+    /// # Errors
+    ///
+    /// A `WidgetNotFound` error will be returned if the node id is not found.
+    /// A `NodeIDCollision` error will be returned if another widget already has this name. This is
+    /// a bug in the layout, so debug builds will panic instead.
+    pub fn set_widget_name(&mut self, id: &NodeId, name: &str) -> UIResult<()> {
+        if !self.widget_exists(id) {
+            return Err(Box::new(UIError::WidgetNotFound { node_id: id.clone() }));
+        }
+        self.check_name_unused(name, Some(id))?;
+        self.widget_names.insert(name.to_owned(), id.clone());
+        Ok(())
+    }
+
+    /// Adds a widget with `add_widget`, then names it with `set_widget_name`. Nothing is added if
+    /// the name is already used.
+    pub fn add_named_widget(&mut self, name: &str, widget: BoxedWidget, modifier: InsertLocation) -> UIResult<NodeId> {
+        self.check_name_unused(name, None)?;
+        let id = self.add_widget(widget, modifier)?;
+        self.set_widget_name(&id, name)?;
+        Ok(id)
+    }
+
+    /// Checks that no widget other than `id` has the provided name.
+    fn check_name_unused(&self, name: &str, id: Option<&NodeId>) -> UIResult<()> {
+        if let Some(other_id) = self.widget_names.get(name) {
+            debug_assert_eq!(Some(other_id), id, "widget name {:?} is already used", name);
+            if Some(other_id) != id {
+                return Err(Box::new(UIError::NodeIDCollision {
+                    reason: format!("Widget name {:?} is already used by {:?}", name, other_id),
+                }));
+            }
         }
+        Ok(())
     }
 
     /// Add a widget to the layering, where the z-order is specified by the insert modifier.
@@ -203,15 +280,13 @@ impl Layering {
     ///
     /// # Errors
     ///
-    /// A `NodeIDCollision` error can be returned if the node id exists in this layering.
+    /// A `DuplicateInsertion` error can be returned if the widget was already inserted somewhere.
     /// An `InvalidAction` error can be returned if the widget addition operation fails.
     /// A `WidgetNotFound` error can be returned if the nested container's node id does not exist.
     pub fn add_widget(&mut self, mut widget: BoxedWidget, modifier: InsertLocation) -> UIResult<NodeId> {
         // Check that we aren't inserting a widget into the tree that already exists
         if let Some(id) = widget.id() {
-            return Err(Box::new(UIError::NodeIDCollision {
-                reason: format!("Attempted to insert widget with previously assigned ID {:?}.", id),
-            }));
+            return Err(Box::new(UIError::DuplicateInsertion { node_id: id.clone() }));
         }
         let widget_accepts_keyboard_events = widget.accepts_keyboard_events();
 
@@ -243,10 +318,7 @@ impl Layering {
             InsertLocation::ToNestedContainer(parent_id) => {
                 if !self.widget_exists(parent_id) {
                     return Err(Box::new(UIError::WidgetNotFound {
-                        reason: format!(
-                            "Parent Container with NodeId {:?} not found in tree. Cannot nest {:?}.",
-                            parent_id, widget
-                        ),
+                        node_id: (*parent_id).clone(),
                     }));
                 }
 
@@ -316,9 +388,7 @@ impl Layering {
     #[allow(unused)]
     pub fn remove_widget(&mut self, id: NodeId) -> UIResult<()> {
         if !self.widget_exists(&id) {
-            return Err(Box::new(UIError::WidgetNotFound {
-                reason: format!("{:?} not found in layer during removal", id).to_owned(),
-            }));
+            return Err(Box::new(UIError::WidgetNotFound { node_id: id }));
         }
 
         // call remove_widget on its containing Pane, if any
@@ -358,6 +428,10 @@ impl Layering {
                 }));
             })?;
 
+        // Forget the names of the widget and any of its children
+        let widget_tree = &self.widget_tree;
+        self.widget_names.retain(|_, id| widget_tree.get(id).is_ok());
+
         // Determine if the highest z-order changes due to the widget removal by checking no other
         // widgets are present at that z_order
         while self.highest_z_order != 0 && self.collect_node_ids(self.highest_z_order).is_empty() {
@@ -368,12 +442,189 @@ impl Layering {
         Ok(())
     }
 
+    /// Moves a widget, along with everything nested in it, in front of the other widgets in its
+    /// layer. It is then drawn over them, and gets the first pick of mouse events.
+    ///
+    /// # Errors
+    ///
+    /// A `WidgetNotFound` error will be returned if the node id is not found.
+    /// An `InvalidAction` error will be returned if the widget is nested in a container, since it
+    /// is drawn along with its container.
+    pub fn move_to_front(&mut self, id: &NodeId) -> UIResult<()> {
+        self.check_top_level(id)?;
+        self.widget_tree.make_last_sibling(id).map_err(|e| {
+            Box::new(UIError::InvalidAction {
+                reason: format!("NodeIDError occurred while moving {:?} to the front: {:?}", id, e),
+            })
+        })?;
+        Ok(())
+    }
+
+    /// Moves a widget, along with everything nested in it, to the front of the layer `z_order`.
+    /// This can be any existing layer, or the one above the highest, which is then added. Layers
+    /// at the top that are left empty are removed, as with `remove_widget`.
+    ///
+    /// # Errors
+    ///
+    /// A `WidgetNotFound` error will be returned if the node id is not found.
+    /// An `InvalidAction` error will be returned if the widget is nested in a container, or if
+    /// `z_order` is more than one above the highest layer.
+    pub fn move_to_layer(&mut self, id: &NodeId, z_order: usize) -> UIResult<()> {
+        self.check_top_level(id)?;
+        if z_order > self.highest_z_order + 1 {
+            return Err(Box::new(UIError::InvalidAction {
+                reason: format!(
+                    "Cannot move {:?} to layer {}; the highest layer is {}",
+                    id, z_order, self.highest_z_order
+                ),
+            }));
+        }
+        let old_z_order = self.widget_tree.get(id).unwrap().data().z_index(); // unwrap OK (id is valid)
+        if z_order > self.highest_z_order {
+            self.highest_z_order = z_order;
+            self.focus_cycles.push(FocusCycle::new(CycleType::Circular));
+        }
+
+        // unwrap OK because id is valid
+        let moved_ids: Vec<NodeId> = self.widget_tree.traverse_pre_order_ids(id).unwrap().collect();
+        for node_id in moved_ids {
+            self.widget_tree
+                .get_mut(&node_id)
+                .unwrap()
+                .data_mut()
+                .set_z_index(z_order);
+        }
+        if self.focus_cycles[old_z_order].find(id).is_some() {
+            self.focus_cycles[old_z_order].remove(id);
+            self.focus_cycles[z_order].push(id.clone());
+        }
+        self.move_to_front(id)?;
+
+        while self.highest_z_order != 0 && self.collect_node_ids(self.highest_z_order).is_empty() {
+            self.highest_z_order -= 1;
+            self.focus_cycles.pop();
+        }
+        Ok(())
+    }
+
+    /// Moves and resizes a widget from where it is now to `target` over `duration`, a little more
+    /// each Update event. Any widgets nested in it are moved along with it. Animating a widget that
+    /// is already being animated starts over from wherever it has got to.
+    ///
+    /// # Errors
+    ///
+    /// A `WidgetNotFound` error will be returned if the node id is not found.
+    pub fn animate_to(&mut self, id: &NodeId, target: Rect, duration: Duration, easing: Easing) -> UIResult<()> {
+        if !self.widget_exists(id) {
+            return Err(Box::new(UIError::WidgetNotFound { node_id: id.clone() }));
+        }
+        let from = self.widget_tree.get(id).unwrap().data().rect(); // unwrap OK (id is valid)
+        self.tweens
+            .insert(id.clone(), Tween::new(from, target, duration, easing));
+        Ok(())
+    }
+
+    /// Moves and resizes a widget to `rect` at once, moving any widgets nested in it along with it.
+    /// This stops any animation of the widget.
+    ///
+    /// # Errors
+    ///
+    /// A `WidgetNotFound` error will be returned if the node id is not found.
+    /// The widget's `set_size` errors are passed along, like an `InvalidRect` for a Pane of no size.
+    pub fn move_widget(&mut self, id: &NodeId, rect: Rect) -> UIResult<()> {
+        self.tweens.remove(id);
+        self.place_widget(id, rect)
+    }
+
+    fn place_widget(&mut self, id: &NodeId, rect: Rect) -> UIResult<()> {
+        if !self.widget_exists(id) {
+            return Err(Box::new(UIError::WidgetNotFound { node_id: id.clone() }));
+        }
+        let old_rect = self.widget_tree.get(id).unwrap().data().rect(); // unwrap OK (id is valid)
+        let offset = Vector2 {
+            x: rect.x - old_rect.x,
+            y: rect.y - old_rect.y,
+        };
+        // unwrap OK because id is valid
+        let moved_ids: Vec<NodeId> = self.widget_tree.traverse_pre_order_ids(id).unwrap().collect();
+        for node_id in moved_ids {
+            self.widget_tree.get_mut(&node_id).unwrap().data_mut().translate(offset);
+        }
+        if (rect.w, rect.h) != (old_rect.w, old_rect.h) {
+            self.widget_tree
+                .get_mut(id)
+                .unwrap()
+                .data_mut()
+                .set_size(rect.w, rect.h)?;
+        }
+        Ok(())
+    }
+
+    /// Moves every animated widget `dt` further along. Animations that are done, or whose widget
+    /// has been removed, are dropped.
+    fn advance_tweens(&mut self, dt: Duration) -> UIResult<()> {
+        let animated_ids: Vec<NodeId> = self.tweens.keys().cloned().collect();
+        for id in animated_ids {
+            if !self.widget_exists(&id) {
+                self.tweens.remove(&id);
+                continue;
+            }
+            let tween = self.tweens.get_mut(&id).unwrap(); // unwrap OK (from the keys)
+            let rect = tween.advance(dt);
+            if tween.is_finished() {
+                self.tweens.remove(&id);
+            }
+            self.place_widget(&id, rect)?;
+        }
+        Ok(())
+    }
+
+    /// Checks that the widget exists and is not nested in a container.
+    fn check_top_level(&self, id: &NodeId) -> UIResult<()> {
+        let node = self
+            .widget_tree
+            .get(id)
+            .map_err(|_| Box::new(UIError::WidgetNotFound { node_id: id.clone() }))?;
+        if node.parent() != self.widget_tree.root_node_id() {
+            return Err(Box::new(UIError::InvalidAction {
+                reason: format!("{} {:?} is nested in a container", self.widget_path(id), id),
+            }));
+        }
+        Ok(())
+    }
+
+    /// The floating Pane that a click at `point` would land on, if it would land on one. Only the
+    /// highest layer is looked at, since the widgets there are drawn over the rest.
+    fn floating_pane_at(&self, point: &Point2<f32>) -> Option<NodeId> {
+        let root_id = self.widget_tree.root_node_id().unwrap(); // unwrap OK; there is always a root
+        let top_level_ids: Vec<&NodeId> = self.widget_tree.children_ids(root_id).unwrap().collect();
+        let topmost = top_level_ids.into_iter().rev().find(|node_id| {
+            let widget = self.widget_tree.get(node_id).unwrap().data(); // unwrap OK (from the tree)
+            widget.z_index() == self.highest_z_order && within_widget(point, &widget.rect())
+        })?;
+        let widget = self.widget_tree.get(topmost).unwrap().data();
+        match downcast_widget!(widget, Pane) {
+            Some(pane) if pane.floating => Some(topmost.clone()),
+            _ => None,
+        }
+    }
+
     /// Returns the NodeId of the widget currently in-focus
     #[allow(unused)]
     pub fn focused_widget_id(&self) -> Option<&NodeId> {
         self.focus_cycles[self.highest_z_order].focused_widget_id()
     }
 
+    /// Returns true if any text field in this layering has keyboard focus.
+    fn text_field_has_focus(&self) -> bool {
+        let root_id = self.widget_tree.root_node_id().unwrap();
+        self.widget_tree.traverse_pre_order(&root_id).unwrap().any(|node| {
+            node.data()
+                .downcast_ref::<TextField>()
+                .map_or(false, |tf| tf.is_focused())
+        })
+    }
+
     fn switch_focus(
         uictx: &mut UIContext,
         focus_cycle: &mut FocusCycle,
@@ -412,7 +663,7 @@ impl Layering {
     /// # Errors
     ///
     /// A WidgetNotFound error can be returned if a widget with the `widget_id` does not exist in
-    /// the internal list of widgets.
+    /// the internal list of widgets, and an InvalidAction error if it can't receive focus.
     pub fn enter_focus(
         &mut self,
         ggez_context: &mut ggez::Context,
@@ -420,16 +671,15 @@ impl Layering {
         screen_stack: &mut Vec<Screen>,
         game_area_state: &mut GameAreaState,
         static_node_ids: &mut StaticNodeIds,
-        viewport: &mut GridView,
         id: &NodeId,
     ) -> UIResult<()> {
         let mut focus_cycle = &mut self.focus_cycles[self.highest_z_order];
         if focus_cycle.find(id).is_none() {
-            return Err(Box::new(UIError::WidgetNotFound {
-                reason: format!(
-                    "{:?} either not found in layering's widget list or can't receive focus",
-                    id
-                ),
+            if !self.widget_exists(id) {
+                return Err(Box::new(UIError::WidgetNotFound { node_id: id.clone() }));
+            }
+            return Err(Box::new(UIError::InvalidAction {
+                reason: format!("{} can't receive focus", self.widget_path(id)),
             }));
         }
 
@@ -451,7 +701,8 @@ impl Layering {
             screen_stack,
             game_area_state.first_gen_was_drawn,
             static_node_ids,
-            viewport,
+            &self.notifier,
+            &mut self.context_menu,
         );
 
         Layering::switch_focus(&mut uictx, &mut focus_cycle, old_focused_widget.as_ref(), id)?;
@@ -499,6 +750,10 @@ impl Layering {
             widget.draw(ctx)?;
         }
 
+        if let Some(ref menu) = self.context_menu {
+            menu.draw(ctx)?;
+        }
+
         Ok(())
     }
 
@@ -513,8 +768,47 @@ impl Layering {
         screen_stack: &mut Vec<Screen>,
         game_area_state: &mut GameAreaState,
         static_node_ids: &mut StaticNodeIds,
-        viewport: &mut GridView,
     ) -> Result<(), Box<dyn Error>> {
+        // An open context menu gets the first look at the event, and is closed when leaving the screen
+        if event.what == EventType::Save {
+            self.context_menu = None;
+        }
+        let menu_response = self
+            .context_menu
+            .as_mut()
+            .map_or(MenuResponse::Pass, |menu| menu.respond(event));
+        let mut chosen_item = None;
+        match menu_response {
+            MenuResponse::Pass => {}
+            MenuResponse::Consumed => return Ok(()),
+            MenuResponse::Close => {
+                self.context_menu = None;
+                return Ok(());
+            }
+            MenuResponse::CloseAndPass => self.context_menu = None,
+            MenuResponse::Chosen(index) => {
+                chosen_item = self.context_menu.take().map(|menu| (menu, index));
+            }
+        }
+
+        // Escape goes back, unless it's needed to leave a text field or by a modal dialog
+        let escape_goes_back = event.what == EventType::KeyPress
+            && event.key == Some(KeyCodeOrChar::KeyCode(KeyCode::Escape))
+            && self.highest_z_order == 0
+            && !self.text_field_has_focus();
+
+        // A floating pane comes to the front when clicked, so that it stays over the panes it was
+        // dragged across
+        if event.what == EventType::Click && chosen_item.is_none() {
+            if let Some(pane_id) = event.point.as_ref().and_then(|point| self.floating_pane_at(point)) {
+                self.move_to_front(&pane_id)?;
+            }
+        }
+
+        if event.what == EventType::Update {
+            self.advance_tweens(ggez::timer::delta(ggez_context))?;
+        }
+
         let widget_view = treeview::TreeView::new(&mut self.widget_tree);
         let mut uictx = UIContext::new(
             ggez_context,
@@ -523,13 +817,23 @@ impl Layering {
             screen_stack,
             game_area_state.first_gen_was_drawn,
             static_node_ids,
-            viewport,
+            &self.notifier,
+            &mut self.context_menu,
         );
-        if event.is_broadcast_event() {
+        if let Some((menu, index)) = chosen_item {
+            menu.choose(index, event, &mut uictx)
+        } else if event.is_broadcast_event() {
             Layering::broadcast_event(event, &mut uictx)
         } else if event.is_mouse_event() {
             Layering::emit_mouse_event(event, &mut uictx, &mut self.focus_cycles[self.highest_z_order])
         } else if event.is_key_event() {
+            if escape_goes_back {
+                // Holding Escape down only goes back one screen
+                if !event.key_repeating {
+                    uilayout::go_back(uictx.screen_stack, uictx.game_in_progress);
+                }
+                return Ok(());
+            }
             Layering::handle_keyboard_event(event, &mut uictx, &mut self.focus_cycles[self.highest_z_order])
         } else {
             warn!("Don't know how to handle event type {:?}", event.what); // nothing to do if this is not a key or a mouse event
@@ -609,8 +913,6 @@ impl Layering {
                 let id = id.clone();
                 let pane_events = Layering::emit_keyboard_event(event, uictx, &id)?;
                 Layering::handle_keyboard_child_event(key, focus_cycle, uictx, &pane_events[..], false)?;
-            } else {
-                Layering::handle_no_focus_keyboard_event(event, uictx)?;
             }
             Ok(())
         }
@@ -720,7 +1022,8 @@ impl Layering {
 
         let mut child_events = vec![];
 
-        for child_id in uictx.widget_view.children_ids() {
+        // Widgets added later are drawn on top of earlier ones, so they get first pick of the event
+        for child_id in uictx.widget_view.children_ids().into_iter().rev() {
             // Get a mutable reference to a BoxedWidget, as well as a UIContext with a view on the
             // widgets in the tree under this widget.
             let (widget_ref, mut subuictx) = uictx.derive(&child_id).unwrap(); // unwrap OK b/c NodeId valid & in view
@@ -765,30 +1068,11 @@ impl Layering {
         }
         Ok(())
     }
-
-    // Layers that do not have a default focus (like Run/Gamearea)
-    fn handle_no_focus_keyboard_event(event: &Event, uictx: &mut UIContext) -> Result<(), Box<dyn Error>> {
-        if let Some(KeyCodeOrChar::KeyCode(key)) = event.key {
-            match key {
-                KeyCode::Escape => {
-                    // Handle Escape, only if screen was not changed above
-                    let screen = uictx.current_screen();
-                    if screen == Screen::Menu && uictx.game_in_progress {
-                        uictx.push_screen(Screen::Run);
-                    } else {
-                        uictx.pop_screen()?;
-                    }
-                }
-                _ => {}
-            }
-        }
-        Ok(())
-    }
 }
 
 #[cfg(test)]
 mod test {
-    use super::super::{common::FontInfo, Chatbox};
+    use super::super::{common::FontInfo, Button, Chatbox};
     use super::*;
     use crate::constants;
     use crate::ggez::{graphics::PxScale, mint::Vector2};
@@ -1098,4 +1382,249 @@ mod test {
 
         assert_eq!(all_ids.difference(&layer_info.removed_node_ids).count(), 0);
     }
+
+    #[test]
+    fn test_widget_by_name() {
+        let mut layer_info = Layering::new();
+        let font_info = create_dummy_font();
+
+        let pane = Pane::new(Rect::new(0.0, 0.0, 100.0, 100.0));
+        let pane_id = layer_info
+            .add_named_widget("chat pane", Box::new(pane), InsertLocation::AtCurrentLayer)
+            .unwrap();
+        let chatbox = Chatbox::new(font_info, 5);
+        let chatbox_id = layer_info
+            .add_widget(Box::new(chatbox), InsertLocation::ToNestedContainer(&pane_id))
+            .unwrap();
+        assert_eq!(layer_info.widget_by_name("chatbox"), None);
+        layer_info.set_widget_name(&chatbox_id, "chatbox").unwrap();
+
+        assert_eq!(layer_info.widget_by_name("chat pane"), Some(pane_id.clone()));
+        assert_eq!(layer_info.widget_by_name("chatbox"), Some(chatbox_id.clone()));
+        let chatbox = Chatbox::widget_from_name_mut(&mut layer_info, "chatbox").unwrap();
+        assert_eq!(chatbox.id(), Some(&chatbox_id));
+        assert!(Pane::widget_from_name(&layer_info, "chatbox").is_err());
+        assert!(Chatbox::widget_from_name(&layer_info, "no such widget").is_err());
+
+        // naming a widget again with the same name is fine
+        assert!(layer_info.set_widget_name(&chatbox_id, "chatbox").is_ok());
+
+        // removing the pane forgets its name and its children's names
+        layer_info.remove_widget(pane_id).unwrap();
+        assert_eq!(layer_info.widget_by_name("chat pane"), None);
+        assert_eq!(layer_info.widget_by_name("chatbox"), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "already used")]
+    fn test_widget_name_collision() {
+        let mut layer_info = Layering::new();
+        let font_info = create_dummy_font();
+
+        layer_info
+            .add_named_widget(
+                "chatbox",
+                Box::new(Chatbox::new(font_info, 5)),
+                InsertLocation::AtCurrentLayer,
+            )
+            .unwrap();
+        let _ = layer_info.add_named_widget(
+            "chatbox",
+            Box::new(Chatbox::new(font_info, 5)),
+            InsertLocation::AtCurrentLayer,
+        );
+    }
+
+    #[test]
+    fn test_wrong_widget_type_says_where_the_widget_is() {
+        let mut layer_info = Layering::new();
+        let font_info = create_dummy_font();
+        let outer_id = layer_info
+            .add_widget(
+                Box::new(Pane::new(Rect::new(0.0, 0.0, 200.0, 200.0))),
+                InsertLocation::AtCurrentLayer,
+            )
+            .unwrap();
+        let inner_id = layer_info
+            .add_widget(
+                Box::new(Pane::new(Rect::new(10.0, 10.0, 100.0, 100.0))),
+                InsertLocation::ToNestedContainer(&outer_id),
+            )
+            .unwrap();
+        let chatbox_id = layer_info
+            .add_widget(
+                Box::new(Chatbox::new(font_info, 5)),
+                InsertLocation::ToNestedContainer(&inner_id),
+            )
+            .unwrap();
+        assert_eq!(layer_info.widget_path(&chatbox_id), "Pane > Pane > Chatbox");
+
+        match *Button::widget_from_id_mut(&mut layer_info, &chatbox_id).unwrap_err() {
+            UIError::WrongWidgetType { expected, found, path } => {
+                assert_eq!((expected, found), ("Button", "Chatbox"));
+                assert_eq!(path, "Pane > Pane > Chatbox");
+            }
+            other => panic!("unexpected error: {}", other),
+        }
+        assert!(Chatbox::widget_from_id_mut(&mut layer_info, &chatbox_id).is_ok());
+
+        layer_info.remove_widget(inner_id).unwrap();
+        match *Chatbox::widget_from_id(&layer_info, &chatbox_id).unwrap_err() {
+            UIError::WidgetNotFound { node_id } => assert_eq!(node_id, chatbox_id),
+            other => panic!("unexpected error: {}", other),
+        }
+        match *Chatbox::widget_from_name(&layer_info, "chatbox").unwrap_err() {
+            UIError::WidgetNameNotFound { name } => assert_eq!(name, "chatbox"),
+            other => panic!("unexpected error: {}", other),
+        }
+    }
+
+    fn add_pane(layer_info: &mut Layering, rect: Rect, floating: bool, location: InsertLocation) -> NodeId {
+        let mut pane = Pane::new(rect);
+        pane.floating = floating;
+        layer_info.add_widget(Box::new(pane), location).unwrap()
+    }
+
+    #[test]
+    fn test_move_to_front() {
+        let mut layer_info = Layering::new();
+        let rect = Rect::new(0.0, 0.0, 100.0, 100.0);
+        let back_id = add_pane(&mut layer_info, rect, true, InsertLocation::AtCurrentLayer);
+        let front_id = add_pane(&mut layer_info, rect, true, InsertLocation::AtCurrentLayer);
+        let nested_id = add_pane(
+            &mut layer_info,
+            rect,
+            false,
+            InsertLocation::ToNestedContainer(&back_id),
+        );
+        assert_eq!(
+            layer_info.collect_node_ids(0),
+            vec![back_id.clone(), front_id.clone(), nested_id.clone()]
+        );
+        assert_eq!(
+            layer_info.floating_pane_at(&Point2 { x: 50.0, y: 50.0 }),
+            Some(front_id.clone())
+        );
+
+        layer_info.move_to_front(&back_id).unwrap();
+        // drawn last, and the first to be offered a click
+        assert_eq!(
+            layer_info.collect_node_ids(0),
+            vec![front_id.clone(), back_id.clone(), nested_id.clone()]
+        );
+        assert_eq!(
+            layer_info.floating_pane_at(&Point2 { x: 50.0, y: 50.0 }),
+            Some(back_id.clone())
+        );
+        assert_eq!(layer_info.floating_pane_at(&Point2 { x: 150.0, y: 50.0 }), None);
+
+        // a nested widget is drawn along with its container
+        match *layer_info.move_to_front(&nested_id).unwrap_err() {
+            UIError::InvalidAction { .. } => {}
+            other => panic!("unexpected error: {}", other),
+        }
+        layer_info.remove_widget(front_id.clone()).unwrap();
+        match *layer_info.move_to_front(&front_id).unwrap_err() {
+            UIError::WidgetNotFound { node_id } => assert_eq!(node_id, front_id),
+            other => panic!("unexpected error: {}", other),
+        }
+    }
+
+    #[test]
+    fn test_move_to_layer() {
+        let mut layer_info = Layering::new();
+        let rect = Rect::new(0.0, 0.0, 100.0, 100.0);
+        let pane_id = add_pane(&mut layer_info, rect, true, InsertLocation::AtCurrentLayer);
+        let nested_id = add_pane(
+            &mut layer_info,
+            rect,
+            false,
+            InsertLocation::ToNestedContainer(&pane_id),
+        );
+        let other_id = add_pane(&mut layer_info, rect, true, InsertLocation::AtCurrentLayer);
+
+        // only one layer above the highest can be made
+        match *layer_info.move_to_layer(&pane_id, 2).unwrap_err() {
+            UIError::InvalidAction { .. } => {}
+            other => panic!("unexpected error: {}", other),
+        }
+
+        layer_info.move_to_layer(&pane_id, 1).unwrap();
+        assert_eq!(layer_info.highest_z_order, 1);
+        assert_eq!(layer_info.focus_cycles.len(), 2);
+        assert_eq!(layer_info.collect_node_ids(0), vec![other_id.clone()]);
+        assert_eq!(layer_info.collect_node_ids(1), vec![pane_id.clone(), nested_id.clone()]);
+        assert_eq!(layer_info.get_widget_mut(&nested_id).unwrap().z_index(), 1);
+        // the floating pane in the highest layer is the one clicked, even though it is behind
+        assert_eq!(
+            layer_info.floating_pane_at(&Point2 { x: 50.0, y: 50.0 }),
+            Some(pane_id.clone())
+        );
+
+        // moving it back leaves the layer above empty, so that goes away
+        layer_info.move_to_layer(&pane_id, 0).unwrap();
+        assert_eq!(layer_info.highest_z_order, 0);
+        assert_eq!(layer_info.focus_cycles.len(), 1);
+        assert_eq!(layer_info.collect_node_ids(0), vec![other_id, pane_id, nested_id]);
+    }
+
+    #[test]
+    fn test_animate_to_moves_nested_widgets_along() {
+        let mut layer_info = Layering::new();
+        let pane_id = add_pane(
+            &mut layer_info,
+            Rect::new(-100.0, 20.0, 100.0, 50.0),
+            false,
+            InsertLocation::AtCurrentLayer,
+        );
+        let nested_id = add_pane(
+            &mut layer_info,
+            Rect::new(10.0, 10.0, 20.0, 20.0),
+            false,
+            InsertLocation::ToNestedContainer(&pane_id),
+        );
+        assert_eq!(
+            layer_info.get_widget_mut(&nested_id).unwrap().rect(),
+            Rect::new(-90.0, 30.0, 20.0, 20.0)
+        );
+
+        let target = Rect::new(100.0, 20.0, 200.0, 50.0);
+        layer_info
+            .animate_to(&pane_id, target, Duration::from_millis(100), Easing::Linear)
+            .unwrap();
+        layer_info.advance_tweens(Duration::from_millis(50)).unwrap();
+        let halfway = layer_info.get_widget_mut(&pane_id).unwrap().rect();
+        assert!(halfway.x.abs() < 0.001);
+        assert!((halfway.w - 150.0).abs() < 0.001);
+        let nested_rect = layer_info.get_widget_mut(&nested_id).unwrap().rect();
+        assert!((nested_rect.x - 10.0).abs() < 0.001);
+        assert_eq!((nested_rect.w, nested_rect.h), (20.0, 20.0));
+
+        layer_info.advance_tweens(Duration::from_millis(80)).unwrap();
+        assert_eq!(layer_info.get_widget_mut(&pane_id).unwrap().rect(), target);
+        let nested_rect = layer_info.get_widget_mut(&nested_id).unwrap().rect();
+        assert!((nested_rect.x - 110.0).abs() < 0.001);
+        assert!((nested_rect.y - 30.0).abs() < 0.001);
+        assert!(layer_info.tweens.is_empty());
+
+        // the animation of a removed widget goes away with it
+        layer_info
+            .animate_to(
+                &nested_id,
+                Rect::new(0.0, 0.0, 5.0, 5.0),
+                Duration::from_secs(1),
+                Easing::EaseIn,
+            )
+            .unwrap();
+        layer_info.remove_widget(nested_id.clone()).unwrap();
+        layer_info.advance_tweens(Duration::from_millis(10)).unwrap();
+        assert!(layer_info.tweens.is_empty());
+        match *layer_info
+            .animate_to(&nested_id, target, Duration::from_secs(1), Easing::EaseIn)
+            .unwrap_err()
+        {
+            UIError::WidgetNotFound { node_id } => assert_eq!(node_id, nested_id),
+            other => panic!("unexpected error: {}", other),
+        }
+    }
 }
@@ -0,0 +1,364 @@
+/*  Copyright 2021 the Conwayste Developers.
+ *
+ *  This file is part of conwayste.
+ *
+ *  conwayste is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  conwayste is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with conwayste.  If not, see
+ *  <http://www.gnu.org/licenses/>. */
+
+use std::error::Error;
+use std::fmt;
+
+use ggez::event::MouseButton;
+use ggez::graphics::{self, DrawMode, DrawParam, Mesh, MeshBuilder, Rect};
+use ggez::mint::{Point2, Vector2};
+use ggez::{Context, GameResult};
+
+use id_tree::NodeId;
+
+use super::context::{EmitEvent, Event, EventType, Handled, HandlerData, UIContext};
+use super::{widget::Widget, UIError, UIResult};
+
+use crate::constants::{colors::*, MINIMAP_CELLS_PER_BLOCK, MINIMAP_REFRESH_FRAMES};
+use crate::theme;
+
+/// A scaled-down view of the whole universe, drawn in a corner of the Run screen. Each block of
+/// the minimap is lit if any cell in the region of the universe it covers is alive. The part of
+/// the universe currently shown by the `GameArea` is outlined. Clicking or dragging on the minimap
+/// requests that the main view be recentered on that spot; see `take_recenter`.
+pub struct Minimap {
+    id:                   Option<NodeId>,
+    z_index:              usize,
+    dimensions:           Rect,
+    handler_data:         HandlerData,
+    pub visible:          bool,
+    columns:              usize,     // universe width in cells
+    rows:                 usize,     // universe height in cells
+    blocks:               Vec<bool>, // row-major; true if any cell in the block is alive
+    block_columns:        usize,
+    block_rows:           usize,
+    view:                 Rect,      // area of the universe on screen, in cells
+    frames_since_refresh: usize,
+    mesh:                 Option<Mesh>, // cached drawing of `blocks`; rebuilt after a refresh
+    recenter_on:          Option<(usize, usize)>, // (col, row) clicked on since the last take_recenter
+}
+
+impl fmt::Debug for Minimap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Minimap")
+            .field("id", &self.id)
+            .field("dimensions", &self.dimensions)
+            .field("visible", &self.visible)
+            .finish()
+    }
+}
+
+/// Aggregates the live cells of a `columns` x `rows` universe into blocks of
+/// `cells_per_block` x `cells_per_block` cells. A block is lit if any of its cells is alive.
+///
+/// # Return value
+///
+/// A `(blocks, block_columns, block_rows)` tuple, where `blocks` is in row-major order.
+pub fn aggregate_live_cells(
+    columns: usize,
+    rows: usize,
+    cells_per_block: usize,
+    live_cells: &[(usize, usize)],
+) -> (Vec<bool>, usize, usize) {
+    let cells_per_block = cells_per_block.max(1);
+    let block_columns = (columns + cells_per_block - 1) / cells_per_block;
+    let block_rows = (rows + cells_per_block - 1) / cells_per_block;
+    let mut blocks = vec![false; block_columns * block_rows];
+    for &(col, row) in live_cells {
+        if col >= columns || row >= rows {
+            continue;
+        }
+        blocks[(row / cells_per_block) * block_columns + col / cells_per_block] = true;
+    }
+    (blocks, block_columns, block_rows)
+}
+
+impl Minimap {
+    /// Creates a Minimap for a universe `columns` cells wide and `rows` cells high.
+    pub fn new(columns: usize, rows: usize) -> Self {
+        let (blocks, block_columns, block_rows) = aggregate_live_cells(columns, rows, MINIMAP_CELLS_PER_BLOCK, &[]);
+        let mut minimap = Minimap {
+            id: None,
+            z_index: 0,
+            dimensions: Rect::default(),
+            handler_data: HandlerData::new(),
+            visible: true,
+            columns,
+            rows,
+            blocks,
+            block_columns,
+            block_rows,
+            view: Rect::default(),
+            frames_since_refresh: MINIMAP_REFRESH_FRAMES, // refresh on first frame
+            mesh: None,
+            recenter_on: None,
+        };
+
+        // unwrap OK b/c not being called within handler
+        minimap.on(EventType::Click, Box::new(Minimap::mouse_handler)).unwrap();
+        minimap
+            .on(EventType::MouseButtonHeld, Box::new(Minimap::mouse_handler))
+            .unwrap();
+        minimap.on(EventType::Drag, Box::new(Minimap::mouse_handler)).unwrap();
+        minimap
+    }
+
+    /// Counts a frame. Returns true if enough frames have passed that the live cells should be
+    /// refreshed with `set_live_cells`.
+    pub fn tick(&mut self) -> bool {
+        self.frames_since_refresh += 1;
+        self.frames_since_refresh >= MINIMAP_REFRESH_FRAMES
+    }
+
+    /// Replaces the live cells shown on the minimap.
+    pub fn set_live_cells(&mut self, live_cells: &[(usize, usize)]) {
+        let (blocks, _, _) = aggregate_live_cells(self.columns, self.rows, MINIMAP_CELLS_PER_BLOCK, live_cells);
+        self.blocks = blocks;
+        self.frames_since_refresh = 0;
+        self.mesh = None;
+    }
+
+    /// Sets the area of the universe shown by the main view, in cells.
+    pub fn set_view(&mut self, view: Rect) {
+        self.view = view;
+    }
+
+    /// Returns the (col, row) the main view should be centered on, if the minimap was clicked since
+    /// the last call.
+    pub fn take_recenter(&mut self) -> Option<(usize, usize)> {
+        self.recenter_on.take()
+    }
+
+    /// Converts a point in window coordinates within the minimap to a (col, row) of the universe.
+    fn cell_at(&self, point: Point2<f32>) -> Option<(usize, usize)> {
+        if !self.dimensions.contains(point) {
+            return None;
+        }
+        let col = ((point.x - self.dimensions.x) / self.dimensions.w * self.columns as f32) as usize;
+        let row = ((point.y - self.dimensions.y) / self.dimensions.h * self.rows as f32) as usize;
+        Some((col.min(self.columns - 1), row.min(self.rows - 1)))
+    }
+
+    fn mouse_handler(obj: &mut dyn EmitEvent, _uictx: &mut UIContext, evt: &Event) -> Result<Handled, Box<dyn Error>> {
+        let minimap = obj.downcast_mut::<Minimap>().unwrap(); // unwrap OK because this will always be Minimap
+        if !minimap.visible || evt.button != Some(MouseButton::Left) {
+            return Ok(Handled::NotHandled);
+        }
+        let point = evt.point.unwrap(); // unwrap safe b/c mouse events must have a point
+        if let Some((col, row)) = minimap.cell_at(point) {
+            minimap.recenter_on = Some((col, row));
+        }
+        // Consume the click so that the GameArea underneath does not toggle a cell
+        Ok(Handled::Handled)
+    }
+
+    fn build_mesh(&self, ctx: &mut Context) -> GameResult<Option<Mesh>> {
+        let block_w = self.dimensions.w / self.block_columns as f32;
+        let block_h = self.dimensions.h / self.block_rows as f32;
+        let mut builder = MeshBuilder::new();
+        let mut any_lit = false;
+        for (i, lit) in self.blocks.iter().enumerate() {
+            if !lit {
+                continue;
+            }
+            any_lit = true;
+            let (block_col, block_row) = (i % self.block_columns, i / self.block_columns);
+            builder.rectangle(
+                DrawMode::fill(),
+                Rect::new(
+                    self.dimensions.x + block_col as f32 * block_w,
+                    self.dimensions.y + block_row as f32 * block_h,
+                    block_w,
+                    block_h,
+                ),
+                *MINIMAP_LIVE_COLOR,
+            )?;
+        }
+        if !any_lit {
+            return Ok(None);
+        }
+        Ok(Some(builder.build(ctx)?))
+    }
+}
+
+impl Widget for Minimap {
+    fn id(&self) -> Option<&NodeId> {
+        self.id.as_ref()
+    }
+
+    fn set_id(&mut self, new_id: NodeId) {
+        self.id = Some(new_id);
+    }
+
+    fn z_index(&self) -> usize {
+        self.z_index
+    }
+
+    fn set_z_index(&mut self, new_z_index: usize) {
+        self.z_index = new_z_index;
+    }
+
+    fn rect(&self) -> Rect {
+        if self.visible {
+            self.dimensions
+        } else {
+            // hidden, so don't let the layering send us mouse events
+            Rect::new(self.dimensions.x, self.dimensions.y, 0.0, 0.0)
+        }
+    }
+
+    fn set_rect(&mut self, new_dims: Rect) -> UIResult<()> {
+        if new_dims.w == 0.0 || new_dims.h == 0.0 {
+            return Err(Box::new(UIError::InvalidRect {
+                reason: format!(
+                    "Cannot set the size to a width or height of Minimap {:?} to zero",
+                    self.id()
+                ),
+            }));
+        }
+
+        self.dimensions = new_dims;
+        self.mesh = None;
+        Ok(())
+    }
+
+    fn position(&self) -> Point2<f32> {
+        self.dimensions.point().into()
+    }
+
+    fn set_position(&mut self, x: f32, y: f32) {
+        self.dimensions.x = x;
+        self.dimensions.y = y;
+        self.mesh = None;
+    }
+
+    fn size(&self) -> (f32, f32) {
+        (self.dimensions.w, self.dimensions.h)
+    }
+
+    fn set_size(&mut self, w: f32, h: f32) -> UIResult<()> {
+        if w == 0.0 || h == 0.0 {
+            return Err(Box::new(UIError::InvalidRect {
+                reason: format!("Cannot set the width or height of Minimap {:?} to zero", self.id()),
+            }));
+        }
+
+        self.dimensions.w = w;
+        self.dimensions.h = h;
+        self.mesh = None;
+
+        Ok(())
+    }
+
+    fn translate(&mut self, dest: Vector2<f32>) {
+        self.dimensions.translate(dest);
+        self.mesh = None;
+    }
+
+    fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
+        if !self.visible {
+            return Ok(());
+        }
+
+        let background = graphics::Mesh::new_rectangle(ctx, DrawMode::fill(), self.dimensions, *MINIMAP_BG_COLOR)?;
+        graphics::draw(ctx, &background, DrawParam::default())?;
+
+        if self.mesh.is_none() {
+            self.mesh = self.build_mesh(ctx)?;
+        }
+        if let Some(ref mesh) = self.mesh {
+            graphics::draw(ctx, mesh, DrawParam::default())?;
+        }
+
+        // outline the part of the universe currently on screen
+        let scale_x = self.dimensions.w / self.columns as f32;
+        let scale_y = self.dimensions.h / self.rows as f32;
+        let view_rect = Rect::new(
+            self.dimensions.x + self.view.x * scale_x,
+            self.dimensions.y + self.view.y * scale_y,
+            self.view.w * scale_x,
+            self.view.h * scale_y,
+        );
+        if let Some(view_rect) = super::intersection(view_rect, self.dimensions) {
+            let outline = graphics::Mesh::new_rectangle(ctx, DrawMode::stroke(1.0), view_rect, *MINIMAP_VIEW_COLOR)?;
+            graphics::draw(ctx, &outline, DrawParam::default())?;
+        }
+
+        let border_color = theme::current().border;
+        let border = graphics::Mesh::new_rectangle(ctx, DrawMode::stroke(1.0), self.dimensions, border_color)?;
+        graphics::draw(ctx, &border, DrawParam::default())?;
+
+        Ok(())
+    }
+
+    /// convert to EmitEvent
+    fn as_emit_event(&mut self) -> Option<&mut dyn EmitEvent> {
+        Some(self)
+    }
+}
+
+impl_emit_event!(Minimap, self.handler_data);
+widget_from_id!(Minimap);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_aggregate_empty_universe_is_dark() {
+        let (blocks, w, h) = aggregate_live_cells(8, 4, 2, &[]);
+        assert_eq!((w, h), (4, 2));
+        assert!(blocks.iter().all(|lit| !lit));
+    }
+
+    #[test]
+    fn test_aggregate_block_pattern_lights_one_block() {
+        // 2x2 still life aligned to a block
+        let block = [(2, 2), (3, 2), (2, 3), (3, 3)];
+        let (blocks, w, h) = aggregate_live_cells(8, 8, 2, &block);
+        assert_eq!((w, h), (4, 4));
+        let lit: Vec<usize> = (0..blocks.len()).filter(|&i| blocks[i]).collect();
+        assert_eq!(lit, vec![w + 1]);
+    }
+
+    #[test]
+    fn test_aggregate_blinker_straddling_blocks() {
+        // horizontal blinker across columns 1..=3 on row 0 spans two blocks
+        let blinker = [(1, 0), (2, 0), (3, 0)];
+        let (blocks, w, _) = aggregate_live_cells(8, 8, 2, &blinker);
+        assert!(blocks[0]);
+        assert!(blocks[1]);
+        assert!(!blocks[2]);
+        assert!(!blocks[w]);
+    }
+
+    #[test]
+    fn test_aggregate_partial_blocks_at_edges() {
+        // 5x3 universe with 2x2 blocks leaves partial blocks on the right and bottom
+        let (blocks, w, h) = aggregate_live_cells(5, 3, 2, &[(4, 2)]);
+        assert_eq!((w, h), (3, 2));
+        assert!(blocks[w + 2]);
+        assert_eq!(blocks.iter().filter(|lit| **lit).count(), 1);
+    }
+
+    #[test]
+    fn test_aggregate_ignores_out_of_range_cells() {
+        let (blocks, _, _) = aggregate_live_cells(4, 4, 2, &[(4, 0), (0, 4)]);
+        assert!(blocks.iter().all(|lit| !lit));
+    }
+}
@@ -24,30 +24,45 @@ extern crate ggez;
 pub(crate) mod common;
 #[macro_use]
 pub(crate) mod context;
+pub(crate) mod anchor;
 mod button;
+mod cellmesh;
 mod chatbox;
 mod checkbox;
+mod clipboard;
+mod contextmenu;
 mod focus;
 mod gamearea;
 mod label;
 mod layer;
+mod minimap;
 mod pane;
+mod serverlist;
+mod textedit;
 mod textfield;
+mod toast;
 mod treeview;
+mod tween;
 pub(crate) mod ui_errors;
 mod widget;
 
+pub use anchor::{Anchor, Corner};
 pub use button::Button;
-pub use chatbox::{Chatbox, ChatboxPublishHandle};
-pub use checkbox::Checkbox;
+pub use chatbox::{ChatEntry, ChatHistory, Chatbox, ChatboxPublishHandle};
+pub use checkbox::{CheckState, Checkbox};
 pub use common::{center, color_with_alpha, draw_text, intersection, point_offset, within_widget};
 pub use context::{EmitEvent, Event, EventType, UIContext};
-pub use gamearea::{GameArea, GameAreaState};
-pub use label::Label;
+pub use contextmenu::{ContextMenu, MenuItem};
+pub use gamearea::{ColorSettings, GameArea, GameAreaState};
+pub use label::{Label, TextAlignment};
 pub use layer::{InsertLocation, Layering};
+pub use minimap::Minimap;
 pub use pane::Pane;
+pub use serverlist::{ServerList, ServerListEntry};
 pub use textfield::TextField;
-pub use ui_errors::{UIError, UIResult};
+pub use toast::{Notifier, Severity, Toasts};
+pub use tween::{Easing, Lerp, Tween};
+pub use ui_errors::{short_type_name, UIError, UIResult};
 pub use widget::Widget;
 
 type BoxedWidget = Box<dyn Widget>;
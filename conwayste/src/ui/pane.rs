@@ -38,7 +38,7 @@ use ggez::input::keyboard::KeyCode;
 
 use context::{EmitEvent, Event, EventType, Handled, UIContext};
 
-use crate::constants::colors::*;
+use crate::theme::{self, Theme, ThemeColor};
 
 pub struct Pane {
     id:               Option<NodeId>,
@@ -47,7 +47,7 @@ pub struct Pane {
     pub floating:     bool, // can the window be dragged around?
     pub previous_pos: Option<Point2<f32>>,
     pub border:       f32,
-    pub bg_color:     Option<Color>,
+    pub bg_color:     Option<ThemeColor>,
     pub focus_cycle:  FocusCycle,
     pub handler_data: context::HandlerData, // required for impl_emit_event!
 
@@ -427,6 +427,11 @@ impl Pane {
     pub fn remove_widget(&mut self, widget_id: &NodeId) {
         self.focus_cycle.remove(widget_id);
     }
+
+    /// The color the background is filled with under `theme`, if it is filled at all.
+    pub fn resolved_bg_color(&self, theme: &Theme) -> Option<Color> {
+        self.bg_color.map(|which| theme.color(which))
+    }
 }
 
 impl Widget for Pane {
@@ -452,7 +457,7 @@ impl Widget for Pane {
 
     fn set_rect(&mut self, new_dims: Rect) -> UIResult<()> {
         if new_dims.w == 0.0 || new_dims.h == 0.0 {
-            return Err(Box::new(UIError::InvalidDimensions {
+            return Err(Box::new(UIError::InvalidRect {
                 reason: format!(
                     "Cannot set the size of a Pane {:?} to a width or height of zero",
                     self.id()
@@ -479,7 +484,7 @@ impl Widget for Pane {
 
     fn set_size(&mut self, w: f32, h: f32) -> UIResult<()> {
         if w == 0.0 || h == 0.0 {
-            return Err(Box::new(UIError::InvalidDimensions {
+            return Err(Box::new(UIError::InvalidRect {
                 reason: format!("Cannot set the width or height of Pane {:?} to zero", self.id()),
             }));
         }
@@ -544,13 +549,14 @@ impl Widget for Pane {
     */
 
     fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
-        if let Some(bg_color) = self.bg_color {
+        let theme = theme::current();
+        if let Some(bg_color) = self.resolved_bg_color(&theme) {
             let mesh = graphics::Mesh::new_rectangle(ctx, DrawMode::fill(), self.dimensions, bg_color)?;
             graphics::draw(ctx, &mesh, DrawParam::default())?;
         }
 
         if self.border > 0.0 {
-            let mesh = graphics::Mesh::new_rectangle(ctx, DrawMode::stroke(1.0), self.dimensions, *PANE_BORDER_COLOR)?;
+            let mesh = graphics::Mesh::new_rectangle(ctx, DrawMode::stroke(1.0), self.dimensions, theme.border)?;
             graphics::draw(ctx, &mesh, DrawParam::default())?;
         }
 
@@ -570,3 +576,24 @@ impl Widget for Pane {
 
 widget_from_id!(Pane);
 impl_emit_event!(Pane, self.handler_data);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_switching_themes_changes_bg_color() {
+        let mut pane = Pane::new(Rect::new(0.0, 0.0, 100.0, 100.0));
+        assert_eq!(pane.resolved_bg_color(&theme::current()), None);
+
+        pane.bg_color = Some(ThemeColor::ChatPaneFill);
+        theme::set_current(Theme::light());
+        let light_color = pane.resolved_bg_color(&theme::current());
+        theme::set_current(Theme::dark());
+        let dark_color = pane.resolved_bg_color(&theme::current());
+
+        assert_eq!(light_color, Some(Theme::light().chat_pane_fill));
+        assert_eq!(dark_color, Some(Theme::dark().chat_pane_fill));
+        assert_ne!(light_color, dark_color);
+    }
+}
@@ -0,0 +1,670 @@
+/*  Copyright 2021 the Conwayste Developers.
+ *
+ *  This file is part of conwayste.
+ *
+ *  conwayste is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  conwayste is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with conwayste.  If not, see
+ *  <http://www.gnu.org/licenses/>. */
+
+use std::error::Error;
+use std::fmt;
+use std::ops::Range;
+
+use ggez::event::MouseButton;
+use ggez::graphics::{self, Color, DrawMode, DrawParam, FilterMode, Rect, Text, TextFragment};
+use ggez::mint::{Point2, Vector2};
+use ggez::{Context, GameResult};
+
+use id_tree::NodeId;
+
+use super::{
+    common::FontInfo,
+    context::{EmitEvent, Event, EventType, Handled, HandlerData, UIContext},
+    widget::Widget,
+    UIError, UIResult,
+};
+
+use crate::constants::{
+    colors::*, SERVER_LIST_JOIN_BUTTON_WIDTH, SERVER_LIST_PADDING, SERVER_LIST_ROW_HEIGHT, SERVER_LIST_SCROLLBAR_WIDTH,
+};
+use crate::input::ScrollEvent;
+use crate::theme;
+
+/// One server shown in a `ServerList`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerListEntry {
+    pub name:         String,
+    pub player_count: usize,
+}
+
+impl ServerListEntry {
+    pub fn new(name: String, player_count: usize) -> Self {
+        ServerListEntry { name, player_count }
+    }
+}
+
+/// Keeps track of which rows of a vertical list are scrolled into view. The list is divided into
+/// slots of `row_height` pixels, and only as many whole rows as fit in `height` are shown, so
+/// nothing ever needs to be clipped. Slots are reused for whichever rows are in view, so the
+/// layout stays the same however many rows are added or removed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScrollLayout {
+    row_height: f32,
+    height:     f32,
+    row_count:  usize,
+    first_row:  usize, // the row shown in the topmost slot
+}
+
+impl ScrollLayout {
+    pub fn new(row_height: f32, height: f32) -> Self {
+        ScrollLayout {
+            row_height,
+            height,
+            row_count: 0,
+            first_row: 0,
+        }
+    }
+
+    /// The number of rows that fit in the list's height.
+    pub fn slots(&self) -> usize {
+        if self.row_height <= 0.0 || self.height <= 0.0 {
+            return 0;
+        }
+        (self.height / self.row_height) as usize
+    }
+
+    pub fn row_height(&self) -> f32 {
+        self.row_height
+    }
+
+    pub fn row_count(&self) -> usize {
+        self.row_count
+    }
+
+    pub fn first_row(&self) -> usize {
+        self.first_row
+    }
+
+    /// Changes the height of the list. The first row is adjusted if the list can now scroll less.
+    pub fn set_height(&mut self, height: f32) {
+        self.height = height;
+        self.clamp();
+    }
+
+    /// Changes the number of rows. The first row is kept, unless that would leave empty slots at
+    /// the bottom that could have been filled.
+    pub fn set_row_count(&mut self, row_count: usize) {
+        self.row_count = row_count;
+        self.clamp();
+    }
+
+    /// The highest first row that still leaves no slots empty at the bottom.
+    pub fn max_first_row(&self) -> usize {
+        self.row_count.saturating_sub(self.slots())
+    }
+
+    pub fn can_scroll_up(&self) -> bool {
+        self.first_row > 0
+    }
+
+    pub fn can_scroll_down(&self) -> bool {
+        self.first_row < self.max_first_row()
+    }
+
+    /// Scrolls by `rows` rows, down if positive and up if negative, stopping at either end.
+    /// Returns true if anything moved.
+    pub fn scroll_by(&mut self, rows: isize) -> bool {
+        let old_first_row = self.first_row;
+        self.first_row = if rows < 0 {
+            self.first_row.saturating_sub((-rows) as usize)
+        } else {
+            self.first_row.saturating_add(rows as usize)
+        };
+        self.clamp();
+        self.first_row != old_first_row
+    }
+
+    /// The rows currently in view, from top to bottom.
+    pub fn visible_rows(&self) -> Range<usize> {
+        let end = (self.first_row + self.slots()).min(self.row_count);
+        self.first_row..end
+    }
+
+    /// The slot that `row` is drawn in, or `None` if it is scrolled out of view.
+    pub fn slot_of(&self, row: usize) -> Option<usize> {
+        if self.visible_rows().contains(&row) {
+            Some(row - self.first_row)
+        } else {
+            None
+        }
+    }
+
+    /// The row at `y` pixels below the top of the list, if there is one there.
+    pub fn row_at(&self, y: f32) -> Option<usize> {
+        if y < 0.0 || self.row_height <= 0.0 {
+            return None;
+        }
+        let slot = (y / self.row_height) as usize;
+        if slot >= self.slots() {
+            return None;
+        }
+        let row = self.first_row + slot;
+        if row < self.row_count {
+            Some(row)
+        } else {
+            None
+        }
+    }
+
+    fn clamp(&mut self) {
+        self.first_row = self.first_row.min(self.max_first_row());
+    }
+}
+
+/// A scrollable list of servers for the ServerList screen. Each row shows a server's name and
+/// player count, along with a Join button. The mouse wheel scrolls the list by a row at a time.
+/// Clicking a Join button requests that the server be joined; see `take_join_request`.
+pub struct ServerList {
+    id:           Option<NodeId>,
+    z_index:      usize,
+    dimensions:   Rect,
+    handler_data: HandlerData,
+    font_info:    FontInfo,
+    entries:      Vec<ServerListEntry>,
+    layout:       ScrollLayout,
+    hover_join:   Option<usize>,  // row whose Join button is under the mouse
+    join_request: Option<String>, // server whose Join button was clicked since the last take_join_request
+}
+
+impl fmt::Debug for ServerList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ServerList")
+            .field("id", &self.id)
+            .field("dimensions", &self.dimensions)
+            .field("entries", &self.entries)
+            .field("first_row", &self.layout.first_row())
+            .finish()
+    }
+}
+
+impl ServerList {
+    pub fn new(font_info: FontInfo, dimensions: Rect) -> Self {
+        let mut server_list = ServerList {
+            id: None,
+            z_index: std::usize::MAX,
+            dimensions,
+            handler_data: HandlerData::new(),
+            font_info,
+            entries: vec![],
+            layout: ScrollLayout::new(SERVER_LIST_ROW_HEIGHT, dimensions.h),
+            hover_join: None,
+            join_request: None,
+        };
+
+        // unwrap OK b/c not being called within handler
+        server_list
+            .on(EventType::Click, Box::new(ServerList::click_handler))
+            .unwrap();
+        server_list
+            .on(EventType::Scroll, Box::new(ServerList::scroll_handler))
+            .unwrap();
+        server_list
+            .on(EventType::MouseMove, Box::new(ServerList::move_handler))
+            .unwrap();
+        server_list
+    }
+
+    pub fn entries(&self) -> &[ServerListEntry] {
+        &self.entries
+    }
+
+    /// Replaces every entry in the list, keeping the scroll position where possible.
+    pub fn set_entries(&mut self, entries: Vec<ServerListEntry>) {
+        self.entries = entries;
+        self.entries_changed();
+    }
+
+    /// Adds a server to the bottom of the list, or updates it if a server by that name is already
+    /// listed.
+    pub fn add_entry(&mut self, entry: ServerListEntry) {
+        if let Some(existing) = self.entries.iter_mut().find(|e| e.name == entry.name) {
+            *existing = entry;
+        } else {
+            self.entries.push(entry);
+        }
+        self.entries_changed();
+    }
+
+    /// Removes the server called `name`. Returns true if it was listed.
+    pub fn remove_entry(&mut self, name: &str) -> bool {
+        let old_len = self.entries.len();
+        self.entries.retain(|e| e.name != name);
+        self.entries_changed();
+        self.entries.len() != old_len
+    }
+
+    /// Returns the name of the server to join, if a Join button was clicked since the last call.
+    pub fn take_join_request(&mut self) -> Option<String> {
+        self.join_request.take()
+    }
+
+    /// Scrolls by `rows` rows, down if positive and up if negative.
+    pub fn scroll_by(&mut self, rows: isize) {
+        if self.layout.scroll_by(rows) {
+            self.hover_join = None;
+        }
+    }
+
+    pub fn first_visible_row(&self) -> usize {
+        self.layout.first_row()
+    }
+
+    fn entries_changed(&mut self) {
+        self.layout.set_row_count(self.entries.len());
+        self.hover_join = None;
+    }
+
+    /// The rectangle of a slot, in window coordinates. The scrollbar is to the right of it.
+    fn slot_rect(&self, slot: usize) -> Rect {
+        Rect::new(
+            self.dimensions.x,
+            self.dimensions.y + slot as f32 * self.layout.row_height(),
+            self.dimensions.w - SERVER_LIST_SCROLLBAR_WIDTH,
+            self.layout.row_height(),
+        )
+    }
+
+    /// The rectangle of the Join button within the row drawn in `slot_rect`.
+    fn join_button_rect(slot_rect: Rect) -> Rect {
+        Rect::new(
+            slot_rect.right() - SERVER_LIST_JOIN_BUTTON_WIDTH - SERVER_LIST_PADDING,
+            slot_rect.y + SERVER_LIST_PADDING / 2.0,
+            SERVER_LIST_JOIN_BUTTON_WIDTH,
+            slot_rect.h - SERVER_LIST_PADDING,
+        )
+    }
+
+    /// The row whose Join button is at `point`, in window coordinates.
+    fn join_button_at(&self, point: Point2<f32>) -> Option<usize> {
+        let row = self.layout.row_at(point.y - self.dimensions.y)?;
+        let slot = self.layout.slot_of(row)?;
+        if ServerList::join_button_rect(self.slot_rect(slot)).contains(point) {
+            Some(row)
+        } else {
+            None
+        }
+    }
+
+    /// The thumb of the scrollbar, or `None` if every row fits.
+    fn scrollbar_thumb(&self) -> Option<Rect> {
+        let row_count = self.layout.row_count();
+        let slots = self.layout.slots();
+        if row_count <= slots {
+            return None;
+        }
+        let h = self.dimensions.h * slots as f32 / row_count as f32;
+        let y = self.dimensions.y + self.dimensions.h * self.layout.first_row() as f32 / row_count as f32;
+        Some(Rect::new(
+            self.dimensions.right() - SERVER_LIST_SCROLLBAR_WIDTH,
+            y,
+            SERVER_LIST_SCROLLBAR_WIDTH,
+            h,
+        ))
+    }
+
+    fn click_handler(obj: &mut dyn EmitEvent, _uictx: &mut UIContext, evt: &Event) -> Result<Handled, Box<dyn Error>> {
+        let server_list = obj.downcast_mut::<ServerList>().unwrap(); // unwrap OK because this will always be ServerList
+        if evt.button != Some(MouseButton::Left) {
+            return Ok(Handled::NotHandled);
+        }
+        let point = evt.point.unwrap(); // unwrap safe b/c mouse events must have a point
+        if let Some(row) = server_list.join_button_at(point) {
+            server_list.join_request = Some(server_list.entries[row].name.clone());
+        }
+        Ok(Handled::Handled)
+    }
+
+    fn scroll_handler(obj: &mut dyn EmitEvent, _uictx: &mut UIContext, evt: &Event) -> Result<Handled, Box<dyn Error>> {
+        let server_list = obj.downcast_mut::<ServerList>().unwrap(); // unwrap OK because this will always be ServerList
+        match evt.scroll {
+            Some(ScrollEvent::ScrollUp) => server_list.scroll_by(-1),
+            Some(ScrollEvent::ScrollDown) => server_list.scroll_by(1),
+            None => return Ok(Handled::NotHandled),
+        }
+        Ok(Handled::Handled)
+    }
+
+    fn move_handler(obj: &mut dyn EmitEvent, _uictx: &mut UIContext, evt: &Event) -> Result<Handled, Box<dyn Error>> {
+        let server_list = obj.downcast_mut::<ServerList>().unwrap(); // unwrap OK because this will always be ServerList
+        let point = evt.point.unwrap(); // unwrap safe b/c mouse events must have a point
+        server_list.hover_join = server_list.join_button_at(point);
+        Ok(Handled::NotHandled)
+    }
+
+    fn queue_text(&self, ctx: &mut Context, text: String, point: Point2<f32>, color: Color) {
+        let mut text = Text::new(TextFragment::new(text).color(color));
+        self.font_info.apply(&mut text);
+        graphics::queue_text(ctx, &text, point, None);
+    }
+
+    /// Shortens `name` so that it fits in `width` pixels.
+    fn fit_text(&self, name: &str, width: f32) -> String {
+        let max_chars = (width / self.font_info.char_dimensions.x).max(0.0) as usize;
+        if name.chars().count() <= max_chars {
+            return name.to_owned();
+        }
+        let mut shortened: String = name.chars().take(max_chars.saturating_sub(3)).collect();
+        shortened.push_str("...");
+        shortened
+    }
+}
+
+impl Widget for ServerList {
+    fn id(&self) -> Option<&NodeId> {
+        self.id.as_ref()
+    }
+
+    fn set_id(&mut self, new_id: NodeId) {
+        self.id = Some(new_id);
+    }
+
+    fn z_index(&self) -> usize {
+        self.z_index
+    }
+
+    fn set_z_index(&mut self, new_z_index: usize) {
+        self.z_index = new_z_index;
+    }
+
+    fn rect(&self) -> Rect {
+        self.dimensions
+    }
+
+    fn set_rect(&mut self, new_dims: Rect) -> UIResult<()> {
+        if new_dims.w == 0.0 || new_dims.h == 0.0 {
+            return Err(Box::new(UIError::InvalidRect {
+                reason: format!(
+                    "Cannot set the size to a width or height of ServerList {:?} to zero",
+                    self.id()
+                ),
+            }));
+        }
+
+        self.dimensions = new_dims;
+        self.layout.set_height(new_dims.h);
+        self.hover_join = None;
+        Ok(())
+    }
+
+    fn position(&self) -> Point2<f32> {
+        self.dimensions.point().into()
+    }
+
+    fn set_position(&mut self, x: f32, y: f32) {
+        self.dimensions.x = x;
+        self.dimensions.y = y;
+    }
+
+    fn size(&self) -> (f32, f32) {
+        (self.dimensions.w, self.dimensions.h)
+    }
+
+    fn set_size(&mut self, w: f32, h: f32) -> UIResult<()> {
+        if w == 0.0 || h == 0.0 {
+            return Err(Box::new(UIError::InvalidRect {
+                reason: format!("Cannot set the width or height of ServerList {:?} to zero", self.id()),
+            }));
+        }
+
+        self.dimensions.w = w;
+        self.dimensions.h = h;
+        self.layout.set_height(h);
+        self.hover_join = None;
+        Ok(())
+    }
+
+    fn translate(&mut self, dest: Vector2<f32>) {
+        self.dimensions.translate(dest);
+    }
+
+    fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
+        let theme = theme::current();
+        let text_offset_y = (self.layout.row_height() - self.font_info.char_dimensions.y) / 2.0;
+
+        if self.entries.is_empty() {
+            let point = Point2 {
+                x: self.dimensions.x + SERVER_LIST_PADDING,
+                y: self.dimensions.y + text_offset_y,
+            };
+            self.queue_text(ctx, "No servers found".to_owned(), point, theme.menu_text);
+        }
+
+        for row in self.layout.visible_rows() {
+            let slot = self.layout.slot_of(row).unwrap(); // unwrap OK b/c the row is visible
+            let slot_rect = self.slot_rect(slot);
+            if row % 2 == 1 {
+                let stripe = graphics::Mesh::new_rectangle(ctx, DrawMode::fill(), slot_rect, *SERVER_LIST_ROW_COLOR)?;
+                graphics::draw(ctx, &stripe, DrawParam::default())?;
+            }
+
+            // name on the left, then the player count, then the Join button on the right
+            let entry = &self.entries[row];
+            let join_rect = ServerList::join_button_rect(slot_rect);
+            let count_x = slot_rect.x + (join_rect.x - slot_rect.x) * 0.65;
+            let name_x = slot_rect.x + SERVER_LIST_PADDING;
+            let name = self.fit_text(&entry.name, count_x - name_x - SERVER_LIST_PADDING);
+            let text_y = slot_rect.y + text_offset_y;
+            self.queue_text(ctx, name, Point2 { x: name_x, y: text_y }, theme.menu_text);
+            let player_count = if entry.player_count == 1 {
+                "1 player".to_owned()
+            } else {
+                format!("{} players", entry.player_count)
+            };
+            self.queue_text(ctx, player_count, Point2 { x: count_x, y: text_y }, theme.menu_text);
+
+            let join_color = if self.hover_join == Some(row) {
+                *SERVER_LIST_JOIN_HOVER_COLOR
+            } else {
+                *SERVER_LIST_JOIN_COLOR
+            };
+            let join_button = graphics::Mesh::new_rectangle(ctx, DrawMode::fill(), join_rect, join_color)?;
+            graphics::draw(ctx, &join_button, DrawParam::default())?;
+            let join_text_point = Point2 {
+                x: join_rect.x + (join_rect.w - 4.0 * self.font_info.char_dimensions.x) / 2.0,
+                y: text_y,
+            };
+            self.queue_text(ctx, "Join".to_owned(), join_text_point, theme.menu_text);
+        }
+
+        graphics::draw_queued_text(ctx, DrawParam::default(), None, FilterMode::Linear)?;
+
+        if let Some(thumb) = self.scrollbar_thumb() {
+            let thumb = graphics::Mesh::new_rectangle(ctx, DrawMode::fill(), thumb, *SERVER_LIST_SCROLLBAR_COLOR)?;
+            graphics::draw(ctx, &thumb, DrawParam::default())?;
+        }
+
+        let border = graphics::Mesh::new_rectangle(ctx, DrawMode::stroke(1.0), self.dimensions, theme.border)?;
+        graphics::draw(ctx, &border, DrawParam::default())?;
+
+        Ok(())
+    }
+
+    /// convert to EmitEvent
+    fn as_emit_event(&mut self) -> Option<&mut dyn EmitEvent> {
+        Some(self)
+    }
+}
+
+impl_emit_event!(ServerList, self.handler_data);
+widget_from_id!(ServerList);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ggez::graphics::PxScale;
+
+    fn new_server_list(height: f32) -> ServerList {
+        let font_info = FontInfo {
+            font:            (), // dummy font because we can't create a real Font without ggez
+            scale:           PxScale::from(1.0),
+            char_dimensions: Vector2 { x: 10.0, y: 20.0 },
+        };
+        ServerList::new(font_info, Rect::new(100.0, 50.0, 600.0, height))
+    }
+
+    fn entries(count: usize) -> Vec<ServerListEntry> {
+        (0..count)
+            .map(|i| ServerListEntry::new(format!("room {}", i), i))
+            .collect()
+    }
+
+    #[test]
+    fn test_layout_only_whole_rows_fit() {
+        let mut layout = ScrollLayout::new(40.0, 130.0);
+        assert_eq!(layout.slots(), 3);
+        assert_eq!(layout.visible_rows(), 0..0);
+
+        layout.set_row_count(2);
+        assert_eq!(layout.visible_rows(), 0..2);
+        assert!(!layout.can_scroll_down());
+
+        layout.set_row_count(10);
+        assert_eq!(layout.visible_rows(), 0..3);
+        assert_eq!(layout.max_first_row(), 7);
+    }
+
+    #[test]
+    fn test_layout_scrolling_stops_at_ends() {
+        let mut layout = ScrollLayout::new(40.0, 120.0);
+        layout.set_row_count(5);
+        assert!(!layout.scroll_by(-1));
+        assert!(layout.scroll_by(1));
+        assert_eq!(layout.visible_rows(), 1..4);
+        assert!(layout.scroll_by(100));
+        assert_eq!(layout.visible_rows(), 2..5);
+        assert!(!layout.can_scroll_down());
+        assert!(layout.can_scroll_up());
+        assert!(layout.scroll_by(-100));
+        assert_eq!(layout.first_row(), 0);
+    }
+
+    #[test]
+    fn test_layout_removing_rows_pulls_view_up() {
+        let mut layout = ScrollLayout::new(40.0, 120.0);
+        layout.set_row_count(10);
+        layout.scroll_by(6);
+        assert_eq!(layout.visible_rows(), 6..9);
+
+        // adding rows keeps the view where it was
+        layout.set_row_count(20);
+        assert_eq!(layout.visible_rows(), 6..9);
+
+        // but there are no empty slots left at the bottom after removing rows
+        layout.set_row_count(7);
+        assert_eq!(layout.visible_rows(), 4..7);
+        layout.set_row_count(1);
+        assert_eq!(layout.visible_rows(), 0..1);
+    }
+
+    #[test]
+    fn test_layout_slots_are_reused() {
+        let mut layout = ScrollLayout::new(40.0, 120.0);
+        layout.set_row_count(10);
+        layout.scroll_by(4);
+        assert_eq!(layout.slot_of(3), None);
+        assert_eq!(layout.slot_of(4), Some(0));
+        assert_eq!(layout.slot_of(6), Some(2));
+        assert_eq!(layout.slot_of(7), None);
+        assert_eq!(layout.row_at(0.0), Some(4));
+        assert_eq!(layout.row_at(85.0), Some(6));
+        assert_eq!(layout.row_at(125.0), None);
+        assert_eq!(layout.row_at(-1.0), None);
+    }
+
+    #[test]
+    fn test_layout_row_at_past_last_row() {
+        let mut layout = ScrollLayout::new(40.0, 120.0);
+        layout.set_row_count(2);
+        assert_eq!(layout.row_at(50.0), Some(1));
+        assert_eq!(layout.row_at(90.0), None);
+    }
+
+    #[test]
+    fn test_join_button_hit_testing() {
+        let mut server_list = new_server_list(SERVER_LIST_ROW_HEIGHT * 3.0);
+        server_list.set_entries(entries(5));
+        let slot_rect = server_list.slot_rect(1);
+        let join_rect = ServerList::join_button_rect(slot_rect);
+        let join_center = Point2 {
+            x: join_rect.x + join_rect.w / 2.0,
+            y: join_rect.y + join_rect.h / 2.0,
+        };
+        assert_eq!(server_list.join_button_at(join_center), Some(1));
+
+        // the name isn't part of the button
+        let name_point = Point2 {
+            x: slot_rect.x + 5.0,
+            y: join_center.y,
+        };
+        assert_eq!(server_list.join_button_at(name_point), None);
+
+        // the same spot on screen is the next row's button after scrolling
+        server_list.scroll_by(1);
+        assert_eq!(server_list.join_button_at(join_center), Some(2));
+    }
+
+    #[test]
+    fn test_add_and_remove_entries() {
+        let mut server_list = new_server_list(SERVER_LIST_ROW_HEIGHT * 3.0);
+        server_list.set_entries(entries(4));
+        server_list.scroll_by(1);
+        server_list.add_entry(ServerListEntry::new("room 2".to_owned(), 9));
+        assert_eq!(server_list.entries().len(), 4);
+        assert_eq!(server_list.entries()[2].player_count, 9);
+        server_list.add_entry(ServerListEntry::new("general".to_owned(), 0));
+        assert_eq!(server_list.entries().len(), 5);
+        assert_eq!(server_list.first_visible_row(), 1);
+
+        assert!(server_list.remove_entry("room 0"));
+        assert!(!server_list.remove_entry("room 0"));
+        assert!(server_list.remove_entry("general"));
+        assert_eq!(server_list.entries().len(), 3);
+        assert_eq!(server_list.first_visible_row(), 0);
+    }
+
+    #[test]
+    fn test_fit_text() {
+        let server_list = new_server_list(100.0);
+        assert_eq!(server_list.fit_text("general", 100.0), "general");
+        assert_eq!(server_list.fit_text("a very long room name", 100.0), "a very ...");
+        assert_eq!(
+            server_list.fit_text("日本語のルーム名です", 100.0),
+            "日本語のルーム名です"
+        );
+    }
+
+    #[test]
+    fn test_scrollbar_only_when_rows_are_hidden() {
+        let mut server_list = new_server_list(SERVER_LIST_ROW_HEIGHT * 4.0);
+        server_list.set_entries(entries(4));
+        assert_eq!(server_list.scrollbar_thumb(), None);
+
+        server_list.set_entries(entries(8));
+        let thumb = server_list.scrollbar_thumb().unwrap();
+        assert_eq!(thumb.h, server_list.rect().h / 2.0);
+        assert_eq!(thumb.y, server_list.rect().y);
+        server_list.scroll_by(4);
+        let thumb = server_list.scrollbar_thumb().unwrap();
+        assert_eq!(thumb.bottom(), server_list.rect().bottom());
+    }
+}
@@ -0,0 +1,605 @@
+/*  Copyright 2021 the Conwayste Developers.
+ *
+ *  This file is part of conwayste.
+ *
+ *  conwayste is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  conwayste is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with conwayste.  If not, see
+ *  <http://www.gnu.org/licenses/>. */
+
+//! The editing behind a `TextField`: a single line of text, a cursor, and an optional selection.
+//! Nothing in here depends on ggez, so it can be tested without a window.
+//!
+//! Positions are counted in characters, not bytes, so that multi-byte characters can be edited
+//! like any other. The cursor moves, and deletes, a whole grapheme cluster at a time, so that a
+//! letter and its combining accent, or an emoji sequence, act like the single character they look
+//! like. The text is short (a chat line or a player name), so a `Vec<char>` is simpler than a real
+//! gap buffer and plenty fast.
+
+use unicode_segmentation::UnicodeSegmentation;
+
+/// A single line of editable text.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TextEditor {
+    chars:   Vec<char>,
+    cursor:  usize,         // in 0..=chars.len(); the cursor is to the left of chars[cursor]
+    anchor:  Option<usize>, // the other end of the selection, if there is one
+    max_len: Option<usize>, // in characters
+}
+
+impl TextEditor {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn text(&self) -> String {
+        self.chars.iter().collect()
+    }
+
+    /// Number of characters in the text.
+    pub fn len(&self) -> usize {
+        self.chars.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chars.is_empty()
+    }
+
+    /// The cursor position, as the number of characters to its left.
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    /// Replaces the text, with the cursor at the start and nothing selected. Text past the maximum
+    /// length is dropped.
+    pub fn set_text(&mut self, text: &str) {
+        self.chars = truncate(&sanitize(text), self.max_len).chars().collect();
+        self.cursor = 0;
+        self.anchor = None;
+    }
+
+    /// Limits the text to `max_len` characters. Any text already past the limit is dropped.
+    pub fn set_max_len(&mut self, max_len: Option<usize>) {
+        self.max_len = max_len;
+        let text = self.text();
+        self.set_text(&text);
+    }
+
+    /// The positions between grapheme clusters, from 0 to the length of the text.
+    fn boundaries(&self) -> Vec<usize> {
+        let mut boundaries = vec![0];
+        let mut pos = 0;
+        for grapheme in self.text().graphemes(true) {
+            pos += grapheme.chars().count();
+            boundaries.push(pos);
+        }
+        boundaries
+    }
+
+    /// The start of the grapheme cluster to the left of `pos`.
+    fn prev_boundary(&self, pos: usize) -> usize {
+        self.boundaries().into_iter().rev().find(|&b| b < pos).unwrap_or(0)
+    }
+
+    /// The end of the grapheme cluster to the right of `pos`.
+    fn next_boundary(&self, pos: usize) -> usize {
+        self.boundaries()
+            .into_iter()
+            .find(|&b| b > pos)
+            .unwrap_or(self.chars.len())
+    }
+
+    /// How many more characters fit before the maximum length.
+    fn room_left(&self) -> usize {
+        match self.max_len {
+            Some(max_len) => max_len.saturating_sub(self.chars.len()),
+            None => usize::MAX,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.chars.clear();
+        self.cursor = 0;
+        self.anchor = None;
+    }
+
+    /// The selected range of characters as `(start, end)`, with `start < end`, or `None` if nothing
+    /// is selected.
+    pub fn selection(&self) -> Option<(usize, usize)> {
+        match self.anchor {
+            Some(anchor) if anchor < self.cursor => Some((anchor, self.cursor)),
+            Some(anchor) if anchor > self.cursor => Some((self.cursor, anchor)),
+            _ => None,
+        }
+    }
+
+    pub fn selected_text(&self) -> Option<String> {
+        self.selection()
+            .map(|(start, end)| self.chars[start..end].iter().collect())
+    }
+
+    pub fn select_all(&mut self) {
+        self.anchor = Some(0);
+        self.cursor = self.chars.len();
+    }
+
+    /// Removes the selected text, leaving the cursor where it was. Returns false if nothing was
+    /// selected.
+    fn delete_selection(&mut self) -> bool {
+        let selection = self.selection();
+        self.anchor = None;
+        match selection {
+            Some((start, end)) => {
+                self.chars.drain(start..end);
+                self.cursor = start;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Types a character, replacing the selection if there is one. Control characters are ignored,
+    /// as is anything typed once the text is at its maximum length.
+    pub fn insert_char(&mut self, ch: char) {
+        if ch.is_control() {
+            return;
+        }
+        self.delete_selection();
+        if self.room_left() == 0 {
+            return;
+        }
+        self.chars.insert(self.cursor, ch);
+        self.cursor += 1;
+    }
+
+    /// Inserts text at the cursor, replacing the selection if there is one. Line breaks and tabs
+    /// become spaces, and any other control characters are dropped, so the text stays on one line.
+    /// Only as much as fits in the maximum length is inserted.
+    pub fn insert_str(&mut self, text: &str) {
+        self.delete_selection();
+        let text = sanitize(text);
+        let text = truncate(&text, Some(self.room_left()));
+        let tail = self.chars.split_off(self.cursor);
+        self.chars.extend(text.chars());
+        self.cursor = self.chars.len();
+        self.chars.extend(tail);
+    }
+
+    /// Deletes the selection, or else the grapheme cluster to the left of the cursor.
+    pub fn backspace(&mut self) {
+        if !self.delete_selection() && self.cursor > 0 {
+            let start = self.prev_boundary(self.cursor);
+            self.chars.drain(start..self.cursor);
+            self.cursor = start;
+        }
+    }
+
+    /// Deletes the selection, or else the grapheme cluster to the right of the cursor.
+    pub fn delete(&mut self) {
+        if !self.delete_selection() && self.cursor < self.chars.len() {
+            let end = self.next_boundary(self.cursor);
+            self.chars.drain(self.cursor..end);
+        }
+    }
+
+    /// Removes the selected text and returns it, or returns `None` if nothing was selected.
+    pub fn cut(&mut self) -> Option<String> {
+        let text = self.selected_text();
+        self.delete_selection();
+        text
+    }
+
+    /// Moves the cursor to `new_cursor`. If `selecting`, the selection is extended (or started)
+    /// from where the cursor was; otherwise it is cleared.
+    fn move_to(&mut self, new_cursor: usize, selecting: bool) {
+        if selecting {
+            if self.anchor.is_none() {
+                self.anchor = Some(self.cursor);
+            }
+        } else {
+            self.anchor = None;
+        }
+        self.cursor = new_cursor;
+    }
+
+    /// Moves the cursor one grapheme cluster to the left. Without `selecting`, a selection is
+    /// collapsed to its start instead.
+    pub fn move_left(&mut self, selecting: bool) {
+        let new_cursor = match self.selection() {
+            Some((start, _)) if !selecting => start,
+            _ => self.prev_boundary(self.cursor),
+        };
+        self.move_to(new_cursor, selecting);
+    }
+
+    /// Moves the cursor one grapheme cluster to the right. Without `selecting`, a selection is
+    /// collapsed to its end instead.
+    pub fn move_right(&mut self, selecting: bool) {
+        let new_cursor = match self.selection() {
+            Some((_, end)) if !selecting => end,
+            _ => self.next_boundary(self.cursor),
+        };
+        self.move_to(new_cursor, selecting);
+    }
+
+    /// Moves the cursor to the start of the word it is in, or of the previous word if it is
+    /// already at the start of one.
+    pub fn move_word_left(&mut self, selecting: bool) {
+        let mut new_cursor = self.cursor;
+        while new_cursor > 0 && self.chars[new_cursor - 1].is_whitespace() {
+            new_cursor -= 1;
+        }
+        while new_cursor > 0 && !self.chars[new_cursor - 1].is_whitespace() {
+            new_cursor -= 1;
+        }
+        self.move_to(new_cursor, selecting);
+    }
+
+    /// Moves the cursor to the end of the word it is in, or of the next word if it is already at
+    /// the end of one.
+    pub fn move_word_right(&mut self, selecting: bool) {
+        let len = self.chars.len();
+        let mut new_cursor = self.cursor;
+        while new_cursor < len && self.chars[new_cursor].is_whitespace() {
+            new_cursor += 1;
+        }
+        while new_cursor < len && !self.chars[new_cursor].is_whitespace() {
+            new_cursor += 1;
+        }
+        self.move_to(new_cursor, selecting);
+    }
+
+    pub fn move_home(&mut self, selecting: bool) {
+        self.move_to(0, selecting);
+    }
+
+    pub fn move_end(&mut self, selecting: bool) {
+        self.move_to(self.chars.len(), selecting);
+    }
+
+    /// The characters in `start..end`, clamped to the text.
+    pub fn slice(&self, start: usize, end: usize) -> String {
+        let end = end.min(self.chars.len());
+        let start = start.min(end);
+        self.chars[start..end].iter().collect()
+    }
+}
+
+/// Makes `text` fit on one line: line breaks and tabs become spaces (a Windows line break becomes
+/// just one), and other control characters are dropped.
+pub fn sanitize(text: &str) -> String {
+    text.replace("\r\n", " ")
+        .chars()
+        .filter_map(|ch| match ch {
+            '\n' | '\r' | '\t' => Some(' '),
+            ch if ch.is_control() => None,
+            ch => Some(ch),
+        })
+        .collect()
+}
+
+/// The longest start of `text` that is at most `max_len` characters and doesn't end partway
+/// through a grapheme cluster.
+pub fn truncate(text: &str, max_len: Option<usize>) -> &str {
+    let max_len = match max_len {
+        Some(max_len) => max_len,
+        None => return text,
+    };
+    let mut len = 0;
+    let mut end = 0;
+    for (offset, grapheme) in text.grapheme_indices(true) {
+        len += grapheme.chars().count();
+        if len > max_len {
+            break;
+        }
+        end = offset + grapheme.len();
+    }
+    &text[..end]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// An editor holding `text`, with the cursor at the end.
+    fn editor_with(text: &str) -> TextEditor {
+        let mut editor = TextEditor::new();
+        editor.insert_str(text);
+        editor
+    }
+
+    #[test]
+    fn test_insert_at_cursor() {
+        let mut editor = editor_with("helo");
+        assert_eq!(editor.cursor(), 4);
+        editor.move_left(false);
+        editor.insert_char('l');
+        assert_eq!(editor.text(), "hello");
+        assert_eq!(editor.cursor(), 4);
+        editor.move_home(false);
+        editor.insert_str("oh, ");
+        assert_eq!(editor.text(), "oh, hello");
+        assert_eq!(editor.cursor(), 4);
+    }
+
+    #[test]
+    fn test_multibyte_characters() {
+        let mut editor = editor_with("naïve café");
+        assert_eq!(editor.len(), 10);
+        editor.move_word_left(false);
+        editor.backspace();
+        editor.backspace();
+        editor.insert_char('ü');
+        assert_eq!(editor.text(), "naïvücafé");
+        editor.move_end(true);
+        assert_eq!(editor.selected_text(), Some("café".to_owned()));
+    }
+
+    #[test]
+    fn test_movement_at_limits() {
+        let mut editor = TextEditor::new();
+        editor.move_left(false);
+        editor.move_right(false);
+        editor.move_word_left(false);
+        editor.move_word_right(false);
+        assert_eq!(editor.cursor(), 0);
+
+        let mut editor = editor_with("abc");
+        editor.move_right(false);
+        assert_eq!(editor.cursor(), 3);
+        editor.move_home(false);
+        editor.move_left(false);
+        assert_eq!(editor.cursor(), 0);
+        editor.move_end(false);
+        assert_eq!(editor.cursor(), 3);
+    }
+
+    #[test]
+    fn test_word_movement() {
+        let mut editor = editor_with("the  quick brown");
+        editor.move_word_left(false);
+        assert_eq!(editor.cursor(), 11); // start of "brown"
+        editor.move_word_left(false);
+        assert_eq!(editor.cursor(), 5); // start of "quick", skipping both spaces
+        editor.move_word_left(false);
+        assert_eq!(editor.cursor(), 0);
+        editor.move_word_left(false);
+        assert_eq!(editor.cursor(), 0);
+
+        editor.move_word_right(false);
+        assert_eq!(editor.cursor(), 3); // end of "the"
+        editor.move_word_right(false);
+        assert_eq!(editor.cursor(), 10); // end of "quick"
+        editor.move_right(false);
+        editor.move_right(false);
+        editor.move_word_right(false);
+        assert_eq!(editor.cursor(), 16); // from inside "brown" to its end
+    }
+
+    #[test]
+    fn test_shift_selection() {
+        let mut editor = editor_with("hello world");
+        assert_eq!(editor.selection(), None);
+        editor.move_word_left(true);
+        assert_eq!(editor.selection(), Some((6, 11)));
+        assert_eq!(editor.selected_text(), Some("world".to_owned()));
+
+        // moving back past the anchor flips the selection around
+        editor.move_word_left(true);
+        editor.move_word_left(true);
+        editor.move_end(true);
+        editor.move_home(true);
+        assert_eq!(editor.selection(), Some((0, 11)));
+        editor.move_right(true);
+        assert_eq!(editor.selected_text(), Some("ello world".to_owned()));
+
+        // shrinking the selection back to nothing
+        editor.move_end(true);
+        assert_eq!(editor.selection(), None);
+    }
+
+    #[test]
+    fn test_moving_without_shift_collapses_selection() {
+        let mut editor = editor_with("hello world");
+        editor.move_home(false);
+        editor.move_word_right(true);
+        editor.move_right(false);
+        assert_eq!(editor.selection(), None);
+        assert_eq!(editor.cursor(), 5);
+
+        editor.move_end(true);
+        editor.move_left(false);
+        assert_eq!(editor.selection(), None);
+        assert_eq!(editor.cursor(), 5);
+
+        editor.move_home(true);
+        editor.move_word_right(false);
+        assert_eq!(editor.selection(), None);
+        assert_eq!(editor.cursor(), 5);
+    }
+
+    #[test]
+    fn test_delete_and_backspace() {
+        let mut editor = editor_with("abcd");
+        editor.backspace();
+        assert_eq!(editor.text(), "abc");
+        editor.delete();
+        assert_eq!(editor.text(), "abc");
+        editor.move_home(false);
+        editor.backspace();
+        assert_eq!(editor.text(), "abc");
+        editor.delete();
+        assert_eq!(editor.text(), "bc");
+        assert_eq!(editor.cursor(), 0);
+    }
+
+    #[test]
+    fn test_delete_and_backspace_remove_selection() {
+        let mut editor = editor_with("hello brave world");
+        editor.move_word_left(false);
+        editor.move_left(false);
+        editor.move_word_left(true);
+        editor.backspace();
+        assert_eq!(editor.text(), "hello  world");
+        assert_eq!(editor.cursor(), 6);
+        assert_eq!(editor.selection(), None);
+
+        editor.move_home(false);
+        editor.move_word_right(true);
+        editor.delete();
+        assert_eq!(editor.text(), "  world");
+        assert_eq!(editor.cursor(), 0);
+    }
+
+    #[test]
+    fn test_typing_replaces_selection() {
+        let mut editor = editor_with("cat");
+        editor.select_all();
+        editor.insert_char('d');
+        assert_eq!(editor.text(), "d");
+        editor.insert_str("og");
+        editor.move_home(true);
+        editor.insert_str("frog");
+        assert_eq!(editor.text(), "frog");
+        assert_eq!(editor.cursor(), 4);
+    }
+
+    #[test]
+    fn test_cut() {
+        let mut editor = editor_with("copy and paste");
+        assert_eq!(editor.cut(), None);
+        assert_eq!(editor.text(), "copy and paste");
+
+        editor.move_word_left(true);
+        editor.move_word_left(true);
+        assert_eq!(editor.cut(), Some("and paste".to_owned()));
+        assert_eq!(editor.text(), "copy ");
+        assert_eq!(editor.cursor(), 5);
+    }
+
+    #[test]
+    fn test_paste_is_sanitized() {
+        let mut editor = editor_with("[]");
+        editor.move_left(false);
+        editor.insert_str("one\r\ntwo\nthree\tfour\u{7}\r");
+        assert_eq!(editor.text(), "[one two three four ]");
+        assert_eq!(editor.cursor(), 20);
+
+        editor.insert_char('\n');
+        assert_eq!(editor.len(), 21);
+    }
+
+    #[test]
+    fn test_set_text_and_clear() {
+        let mut editor = editor_with("old");
+        editor.move_home(true);
+        editor.set_text("new\nname");
+        assert_eq!(editor.text(), "new name");
+        assert_eq!(editor.cursor(), 0);
+        assert_eq!(editor.selection(), None);
+
+        editor.select_all();
+        editor.clear();
+        assert!(editor.is_empty());
+        assert_eq!(editor.selection(), None);
+    }
+
+    #[test]
+    fn test_cjk_characters() {
+        let mut editor = editor_with("日本語");
+        editor.backspace();
+        assert_eq!(editor.text(), "日本");
+        assert_eq!(editor.cursor(), 2);
+        editor.move_left(false);
+        editor.delete();
+        assert_eq!(editor.text(), "日");
+        editor.move_end(false);
+        editor.insert_str("本語 text");
+        editor.move_word_left(true);
+        editor.move_word_left(true);
+        assert_eq!(editor.selected_text(), Some("日本語 text".to_owned()));
+    }
+
+    #[test]
+    fn test_combining_characters_are_edited_together() {
+        let mut editor = editor_with("cafe\u{301}s"); // "cafés", with a combining acute accent
+        assert_eq!(editor.len(), 6);
+        editor.move_left(false);
+        editor.move_left(false);
+        assert_eq!(editor.cursor(), 3);
+        editor.move_right(true);
+        assert_eq!(editor.selected_text(), Some("e\u{301}".to_owned()));
+        editor.move_right(false); // collapses the selection to its end
+        editor.backspace();
+        assert_eq!(editor.text(), "cafs");
+    }
+
+    #[test]
+    fn test_emoji_sequences_are_edited_together() {
+        let thumbs_up = "👍\u{1F3FD}"; // with a skin tone modifier
+        let family = "👨\u{200D}👩\u{200D}👧"; // three emoji joined by zero width joiners
+        let flag = "\u{1F1EF}\u{1F1F5}"; // a pair of regional indicators
+        let mut editor = editor_with(&format!("ok {}{}{}", thumbs_up, family, flag));
+        editor.backspace();
+        assert_eq!(editor.text(), format!("ok {}{}", thumbs_up, family));
+        editor.backspace();
+        assert_eq!(editor.text(), format!("ok {}", thumbs_up));
+        editor.move_left(false);
+        assert_eq!(editor.cursor(), 3);
+        editor.delete();
+        assert_eq!(editor.text(), "ok ");
+    }
+
+    #[test]
+    fn test_max_len() {
+        let mut editor = TextEditor::new();
+        editor.set_max_len(Some(5));
+        editor.insert_str("hello world");
+        assert_eq!(editor.text(), "hello");
+        editor.insert_char('!');
+        assert_eq!(editor.text(), "hello");
+
+        // typing over a selection makes room
+        editor.move_left(true);
+        editor.insert_char('!');
+        assert_eq!(editor.text(), "hell!");
+
+        // a grapheme cluster that doesn't fit isn't split
+        editor.select_all();
+        editor.insert_str("abc");
+        editor.insert_str("de\u{301}");
+        assert_eq!(editor.text(), "abcd");
+
+        editor.set_max_len(Some(2));
+        assert_eq!(editor.text(), "ab");
+        editor.set_max_len(None);
+        editor.set_text("no limit");
+        assert_eq!(editor.len(), 8);
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("日本語", Some(2)), "日本");
+        assert_eq!(truncate("cafe\u{301}", Some(4)), "caf");
+        assert_eq!(truncate("cafe\u{301}", Some(5)), "cafe\u{301}");
+        assert_eq!(truncate("anything", None), "anything");
+        assert_eq!(truncate("anything", Some(0)), "");
+    }
+
+    #[test]
+    fn test_slice() {
+        let editor = editor_with("héllo");
+        assert_eq!(editor.slice(1, 3), "él");
+        assert_eq!(editor.slice(3, 100), "lo");
+        assert_eq!(editor.slice(9, 100), "");
+    }
+}
@@ -21,7 +21,7 @@ use std::fmt;
 use std::time::{Duration, Instant};
 
 use ggez::event::KeyCode;
-use ggez::graphics::{self, Color, DrawMode, DrawParam, Rect};
+use ggez::graphics::{self, DrawMode, DrawParam, Rect};
 use ggez::mint::{Point2, Vector2};
 use ggez::{Context, GameResult};
 
@@ -30,13 +30,16 @@ use id_tree::NodeId;
 #[cfg(not(test))]
 use super::common::draw_text;
 use super::{
+    clipboard::Clipboard,
     common::FontInfo,
     context::{EmitEvent, Event, EventType, Handled, HandlerData, KeyCodeOrChar, UIContext},
+    textedit::TextEditor,
     widget::Widget,
     UIError, UIResult,
 };
 
 use crate::constants::{colors::*, CHATBOX_BORDER_PIXELS};
+use crate::theme::{self, ThemeColor};
 
 pub const BLINK_RATE_MS: u64 = 500;
 
@@ -44,14 +47,14 @@ pub struct TextField {
     id:                     Option<NodeId>,
     z_index:                usize,
     focused:                bool,
-    text:                   String,
-    cursor_index:           usize, // Position of the cursor in the text fields' string
+    editor:                 TextEditor,      // the text, cursor, and selection
     cursor_blink_timestamp: Option<Instant>, // last time the cursor blinked on/off
     draw_cursor:            bool,
     dimensions:             Rect,
-    visible_start_index:    usize, // The index of the first character in `self.text` that is visible.
+    visible_start_index:    usize, // The index of the first character in the text that is visible.
     font_info:              FontInfo,
-    pub bg_color:           Option<Color>,
+    clipboard:              Clipboard,
+    pub bg_color:           Option<ThemeColor>,
     pub handler_data:       HandlerData, // required for impl_emit_event!
 }
 
@@ -93,13 +96,13 @@ impl TextField {
             id: None,
             z_index: std::usize::MAX,
             focused: false,
-            text: String::new(),
-            cursor_index: 0,
+            editor: TextEditor::new(),
             cursor_blink_timestamp: None,
             draw_cursor: false,
             dimensions,
             visible_start_index: 0,
             font_info,
+            clipboard: Clipboard::new(),
             bg_color: None,
             handler_data: HandlerData::new(),
         };
@@ -181,7 +184,8 @@ impl TextField {
 
     /// Returns the a string of the inputted text
     pub fn text(&self) -> Option<String> {
-        let trimmed_str = self.text.trim();
+        let text = self.editor.text();
+        let trimmed_str = text.trim();
         if !trimmed_str.is_empty() {
             return Some(String::from(trimmed_str));
         }
@@ -190,8 +194,18 @@ impl TextField {
 
     /// Sets the text field's string contents
     pub fn set_text(&mut self, text: String) {
-        self.text = text;
-        self.cursor_index = 0;
+        self.editor.set_text(&text);
+        self.visible_start_index = 0;
+    }
+
+    pub fn is_focused(&self) -> bool {
+        self.focused
+    }
+
+    /// Limits the text to `max_len` characters, or removes the limit if `None`.
+    pub fn set_max_len(&mut self, max_len: Option<usize>) {
+        self.editor.set_max_len(max_len);
+        self.visible_start_index = 0;
     }
 
     /// Handle a key.
@@ -200,6 +214,7 @@ impl TextField {
         if evt.key.is_none() {
             return Err("keyboard event does not have a key!".to_owned().into());
         }
+        let selecting = evt.shift_pressed;
         match evt.key.unwrap() {
             KeyCodeOrChar::KeyCode(keycode) => match keycode {
                 KeyCode::Return => {
@@ -218,10 +233,16 @@ impl TextField {
                 }
                 KeyCode::Back => tf.remove_left_of_cursor(),
                 KeyCode::Delete => tf.remove_right_of_cursor(),
-                KeyCode::Left => tf.move_cursor_left(),
-                KeyCode::Right => tf.move_cursor_right(),
-                KeyCode::Home => tf.cursor_home(),
-                KeyCode::End => tf.cursor_end(),
+                KeyCode::Left if evt.ctrl_pressed => tf.move_cursor_word_left(selecting),
+                KeyCode::Right if evt.ctrl_pressed => tf.move_cursor_word_right(selecting),
+                KeyCode::Left => tf.move_cursor_left(selecting),
+                KeyCode::Right => tf.move_cursor_right(selecting),
+                KeyCode::Home => tf.cursor_home(selecting),
+                KeyCode::End => tf.cursor_end(selecting),
+                KeyCode::A if evt.ctrl_pressed => tf.edit(|editor| editor.select_all()),
+                KeyCode::C if evt.ctrl_pressed => tf.copy_to_clipboard(),
+                KeyCode::X if evt.ctrl_pressed => tf.cut_to_clipboard(),
+                KeyCode::V if evt.ctrl_pressed => tf.paste_from_clipboard(),
                 KeyCode::Escape => tf.release_focus(uictx),
                 _ => return Ok(Handled::NotHandled),
             },
@@ -244,102 +265,95 @@ impl TextField {
         uictx.child_event(evt);
     }
 
-    /// Adds a character at the current cursor position
-    fn add_char_at_cursor(&mut self, character: char) {
+    /// Makes a change to the text or cursor, then shows the cursor and scrolls so it is visible.
+    fn edit<F: FnOnce(&mut TextEditor)>(&mut self, f: F) {
+        f(&mut self.editor);
+
         self.draw_cursor = true;
         self.cursor_blink_timestamp = Some(Instant::now());
 
-        if self.cursor_index == self.text.len() {
-            self.text.push(character);
-        } else {
-            self.text.insert(self.cursor_index, character);
-        }
-        self.cursor_index += 1;
-        if self.visible_start_index + self.max_visible_chars() < self.cursor_index {
-            self.visible_start_index = self.cursor_index - self.max_visible_chars();
+        let cursor = self.editor.cursor();
+        if self.visible_start_index > cursor {
+            self.visible_start_index = cursor;
+        } else if self.visible_start_index + self.max_visible_chars() < cursor {
+            self.visible_start_index = cursor - self.max_visible_chars();
         }
     }
 
-    /// Deletes a character to the left of the current cursor
-    fn remove_left_of_cursor(&mut self) {
-        self.draw_cursor = true;
-        self.cursor_blink_timestamp = Some(Instant::now());
+    /// Adds a character at the current cursor position, replacing the selection if any
+    fn add_char_at_cursor(&mut self, character: char) {
+        self.edit(|editor| editor.insert_char(character));
+    }
 
-        if self.cursor_index != 0 {
-            self.text.remove(self.cursor_index - 1);
-            self.cursor_index -= 1;
-            if self.visible_start_index > self.cursor_index {
-                self.visible_start_index = self.cursor_index;
-            }
-        }
+    /// Deletes the selection, or else the character to the left of the current cursor
+    fn remove_left_of_cursor(&mut self) {
+        self.edit(|editor| editor.backspace());
     }
 
-    /// Deletes a chracter to the right of the current cursor
+    /// Deletes the selection, or else the character to the right of the current cursor
     fn remove_right_of_cursor(&mut self) {
-        self.draw_cursor = true;
-        self.cursor_blink_timestamp = Some(Instant::now());
-
-        let text_len = self.text.len();
-
-        if text_len != 0 && self.cursor_index != text_len {
-            self.text.remove(self.cursor_index);
-        }
+        self.edit(|editor| editor.delete());
     }
 
     /// Clears the contents of the text field
     pub fn clear(&mut self) {
-        self.text.clear();
-        self.cursor_index = 0;
+        self.editor.clear();
         self.visible_start_index = 0;
         self.cursor_blink_timestamp = None;
         self.draw_cursor = false;
     }
 
-    /// Moves the cursor position to the right by one character
-    fn move_cursor_right(&mut self) {
-        self.draw_cursor = true;
-        self.cursor_blink_timestamp = Some(Instant::now());
-
-        if self.cursor_index < self.text.len() {
-            self.cursor_index += 1;
-
-            if self.visible_start_index + self.max_visible_chars() < self.cursor_index {
-                self.visible_start_index = self.cursor_index - self.max_visible_chars();
-            }
-        }
+    /// Moves the cursor position to the right by one character. If `selecting`, the selection is
+    /// extended along with it.
+    fn move_cursor_right(&mut self, selecting: bool) {
+        self.edit(|editor| editor.move_right(selecting));
     }
 
     /// Moves the cursor position to the left by one character
-    fn move_cursor_left(&mut self) {
-        self.draw_cursor = true;
-        self.cursor_blink_timestamp = Some(Instant::now());
+    fn move_cursor_left(&mut self, selecting: bool) {
+        self.edit(|editor| editor.move_left(selecting));
+    }
 
-        if self.cursor_index > 0 {
-            self.cursor_index -= 1;
+    /// Moves the cursor to the end of the word it is in, or of the next word
+    fn move_cursor_word_right(&mut self, selecting: bool) {
+        self.edit(|editor| editor.move_word_right(selecting));
+    }
 
-            if self.visible_start_index > self.cursor_index {
-                self.visible_start_index = self.cursor_index;
-            }
-        }
+    /// Moves the cursor to the start of the word it is in, or of the previous word
+    fn move_cursor_word_left(&mut self, selecting: bool) {
+        self.edit(|editor| editor.move_word_left(selecting));
     }
 
     /// Moves the cursor before to the first character in the field
-    fn cursor_home(&mut self) {
-        self.draw_cursor = true;
-        self.cursor_blink_timestamp = Some(Instant::now());
-
-        self.cursor_index = 0;
-        self.visible_start_index = 0;
+    fn cursor_home(&mut self, selecting: bool) {
+        self.edit(|editor| editor.move_home(selecting));
     }
 
     /// Moves the cursor after the last character in the field
-    fn cursor_end(&mut self) {
-        self.draw_cursor = true;
-        self.cursor_blink_timestamp = Some(Instant::now());
+    fn cursor_end(&mut self, selecting: bool) {
+        self.edit(|editor| editor.move_end(selecting));
+    }
+
+    /// Copies the selected text, if any, to the system clipboard.
+    fn copy_to_clipboard(&mut self) {
+        if let Some(text) = self.editor.selected_text() {
+            self.clipboard.set_contents(text);
+        }
+    }
+
+    /// Moves the selected text, if any, to the system clipboard.
+    fn cut_to_clipboard(&mut self) {
+        let mut cut_text = None;
+        self.edit(|editor| cut_text = editor.cut());
+        if let Some(text) = cut_text {
+            self.clipboard.set_contents(text);
+        }
+    }
 
-        self.cursor_index = self.text.len();
-        if self.text.len() - self.visible_start_index > self.max_visible_chars() {
-            self.visible_start_index = self.text.len() - self.max_visible_chars();
+    /// Inserts the contents of the system clipboard at the cursor, all on one line.
+    fn paste_from_clipboard(&mut self) {
+        if let Some(text) = self.clipboard.contents() {
+            self.edit(|editor| editor.insert_str(&text));
         }
     }
 }
@@ -362,18 +376,19 @@ impl Widget for TextField {
     }
 
     fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
-        if !self.focused && self.text.is_empty() {
+        if !self.focused && self.editor.is_empty() {
             // textfield is hidden
             return Ok(());
         }
 
+        let theme = theme::current();
         if let Some(bg_color) = self.bg_color {
-            let mesh = graphics::Mesh::new_rectangle(ctx, DrawMode::fill(), self.dimensions, bg_color)?;
+            let mesh = graphics::Mesh::new_rectangle(ctx, DrawMode::fill(), self.dimensions, theme.color(bg_color))?;
             graphics::draw(ctx, &mesh, DrawParam::default())?;
         }
 
         let colored_rect;
-        if !self.text.is_empty() && !self.focused {
+        if !self.editor.is_empty() && !self.focused {
             colored_rect = graphics::Mesh::new_rectangle(
                 ctx,
                 DrawMode::stroke(CHATBOX_BORDER_PIXELS),
@@ -385,7 +400,7 @@ impl Widget for TextField {
                 ctx,
                 DrawMode::stroke(CHATBOX_BORDER_PIXELS),
                 self.dimensions,
-                *CHATBOX_BORDER_COLOR,
+                theme.border,
             )?;
         }
 
@@ -397,15 +412,29 @@ impl Widget for TextField {
             y: self.dimensions.y + 3.0,
         };
 
-        let mut end = self.text.len();
-        if self.visible_start_index + self.max_visible_chars() < end {
-            end = self.visible_start_index + self.max_visible_chars();
+        let visible_end = self.visible_start_index + self.max_visible_chars();
+        let visible_text = self.editor.slice(self.visible_start_index, visible_end);
+
+        // Highlight the visible part of the selection, behind the text
+        if let Some((start, end)) = self.editor.selection() {
+            let start = start.max(self.visible_start_index);
+            let end = end.min(visible_end);
+            if start < end {
+                let char_width = self.font_info.char_dimensions.x;
+                let highlight = Rect::new(
+                    text_pos.x + (start - self.visible_start_index) as f32 * char_width,
+                    self.dimensions.y + CHATBOX_BORDER_PIXELS,
+                    (end - start) as f32 * char_width,
+                    self.dimensions.h - 2.0 * CHATBOX_BORDER_PIXELS,
+                );
+                let mesh = graphics::Mesh::new_rectangle(ctx, DrawMode::fill(), highlight, theme.input_selection)?;
+                graphics::draw(ctx, &mesh, DrawParam::default())?;
+            }
         }
-        let visible_text = self.text[self.visible_start_index..end].to_owned();
 
         #[cfg(not(test))]
         {
-            draw_text(ctx, self.font_info.font, *INPUT_TEXT_COLOR, visible_text, &text_pos)?;
+            draw_text(ctx, self.font_info.font, theme.input_text, visible_text, &text_pos)?;
         }
         #[cfg(test)]
         {
@@ -415,7 +444,7 @@ impl Widget for TextField {
         if self.draw_cursor {
             let mut cursor_pos = text_pos.clone();
 
-            cursor_pos.x += (self.cursor_index - self.visible_start_index) as f32 * self.font_info.char_dimensions.x;
+            cursor_pos.x += (self.editor.cursor() - self.visible_start_index) as f32 * self.font_info.char_dimensions.x;
 
             // Remove half the width of a character so the pipe character is at the beginning
             // of its area (like a cursor), not the center (like a character).
@@ -426,7 +455,7 @@ impl Widget for TextField {
                 draw_text(
                     ctx,
                     self.font_info.font,
-                    *INPUT_TEXT_COLOR,
+                    theme.input_text,
                     String::from("|"),
                     &cursor_pos,
                 )?;
@@ -442,7 +471,7 @@ impl Widget for TextField {
 
     fn set_rect(&mut self, new_dims: Rect) -> UIResult<()> {
         if new_dims.w == 0.0 || new_dims.h == 0.0 {
-            return Err(Box::new(UIError::InvalidDimensions {
+            return Err(Box::new(UIError::InvalidRect {
                 reason: format!(
                     "Cannot set the size of a TextField {:?} to a width or height of
                     zero",
@@ -470,7 +499,7 @@ impl Widget for TextField {
 
     fn set_size(&mut self, w: f32, h: f32) -> UIResult<()> {
         if w == 0.0 || h == 0.0 {
-            return Err(Box::new(UIError::InvalidDimensions {
+            return Err(Box::new(UIError::InvalidRect {
                 reason: format!("Cannot set the width or height of Label {:?} to zero", self.id()),
             }));
         }
@@ -517,19 +546,19 @@ mod test {
     fn test_add_char_at_cursor_beginning_middle_end() {
         let mut tf = create_dummy_textfield();
 
-        assert_eq!(tf.cursor_index, 0);
+        assert_eq!(tf.editor.cursor(), 0);
 
         tf.add_char_at_cursor('A');
-        assert_eq!(tf.cursor_index, 1);
+        assert_eq!(tf.editor.cursor(), 1);
 
         tf.add_char_at_cursor('B');
-        assert_eq!(tf.cursor_index, 2);
+        assert_eq!(tf.editor.cursor(), 2);
 
-        tf.move_cursor_left();
-        assert_eq!(tf.cursor_index, 1);
+        tf.move_cursor_left(false);
+        assert_eq!(tf.editor.cursor(), 1);
 
         tf.add_char_at_cursor('C');
-        assert_eq!(tf.cursor_index, 2);
+        assert_eq!(tf.editor.cursor(), 2);
     }
 
     #[test]
@@ -547,70 +576,70 @@ mod test {
     #[test]
     fn test_move_cursor_left_at_limits() {
         let mut tf = create_dummy_textfield();
-        assert_eq!(tf.cursor_index, 0);
-        tf.move_cursor_left();
-        assert_eq!(tf.cursor_index, 0);
+        assert_eq!(tf.editor.cursor(), 0);
+        tf.move_cursor_left(false);
+        assert_eq!(tf.editor.cursor(), 0);
 
         let test_string = "TestString";
         for ch in test_string.chars() {
             tf.add_char_at_cursor(ch);
         }
 
-        tf.move_cursor_left();
-        assert_eq!(tf.cursor_index, test_string.len() - 1);
-        tf.move_cursor_left();
-        assert_eq!(tf.cursor_index, test_string.len() - 2);
+        tf.move_cursor_left(false);
+        assert_eq!(tf.editor.cursor(), test_string.len() - 1);
+        tf.move_cursor_left(false);
+        assert_eq!(tf.editor.cursor(), test_string.len() - 2);
     }
 
     #[test]
     fn test_move_cursor_right_at_limits() {
         let mut tf = create_dummy_textfield();
-        assert_eq!(tf.cursor_index, 0);
-        tf.move_cursor_right();
-        assert_eq!(tf.cursor_index, 0);
+        assert_eq!(tf.editor.cursor(), 0);
+        tf.move_cursor_right(false);
+        assert_eq!(tf.editor.cursor(), 0);
 
         let test_string = "TestString";
         for ch in test_string.chars() {
             tf.add_char_at_cursor(ch);
         }
 
-        tf.move_cursor_right();
-        assert_eq!(tf.cursor_index, test_string.len());
-        tf.move_cursor_right();
-        assert_eq!(tf.cursor_index, test_string.len());
-        tf.move_cursor_left();
-        tf.move_cursor_right();
-        assert_eq!(tf.cursor_index, test_string.len());
+        tf.move_cursor_right(false);
+        assert_eq!(tf.editor.cursor(), test_string.len());
+        tf.move_cursor_right(false);
+        assert_eq!(tf.editor.cursor(), test_string.len());
+        tf.move_cursor_left(false);
+        tf.move_cursor_right(false);
+        assert_eq!(tf.editor.cursor(), test_string.len());
     }
 
     #[test]
     fn test_move_cursor_to_home() {
         let mut tf = create_dummy_textfield();
-        assert_eq!(tf.cursor_index, 0);
+        assert_eq!(tf.editor.cursor(), 0);
 
         let test_string = "TestString";
         for ch in test_string.chars() {
             tf.add_char_at_cursor(ch);
         }
-        assert_eq!(tf.cursor_index, test_string.len());
-        tf.cursor_home();
-        assert_eq!(tf.cursor_index, 0);
+        assert_eq!(tf.editor.cursor(), test_string.len());
+        tf.cursor_home(false);
+        assert_eq!(tf.editor.cursor(), 0);
     }
 
     #[test]
     fn test_move_cursor_to_end() {
         let mut tf = create_dummy_textfield();
-        assert_eq!(tf.cursor_index, 0);
+        assert_eq!(tf.editor.cursor(), 0);
 
         let test_string = "TestString";
         for ch in test_string.chars() {
             tf.add_char_at_cursor(ch);
         }
-        assert_eq!(tf.cursor_index, test_string.len());
-        tf.cursor_home();
-        assert_eq!(tf.cursor_index, 0);
-        tf.cursor_end();
-        assert_eq!(tf.cursor_index, test_string.len());
+        assert_eq!(tf.editor.cursor(), test_string.len());
+        tf.cursor_home(false);
+        assert_eq!(tf.editor.cursor(), 0);
+        tf.cursor_end(false);
+        assert_eq!(tf.editor.cursor(), test_string.len());
     }
 
     #[test]
@@ -624,10 +653,10 @@ mod test {
 
         for _ in 0..max_chars + 1 {
             assert_eq!(tf.visible_start_index, 2);
-            tf.move_cursor_left();
+            tf.move_cursor_left(false);
         }
         assert_eq!(tf.visible_start_index, 1);
-        tf.move_cursor_left();
+        tf.move_cursor_left(false);
         assert_eq!(tf.visible_start_index, 0);
     }
 
@@ -640,14 +669,14 @@ mod test {
             tf.add_char_at_cursor('A');
         }
 
-        tf.cursor_home();
+        tf.cursor_home(false);
 
         for _ in 0..max_chars + 1 {
             assert_eq!(tf.visible_start_index, 0);
-            tf.move_cursor_right();
+            tf.move_cursor_right(false);
         }
         assert_eq!(tf.visible_start_index, 1);
-        tf.move_cursor_right();
+        tf.move_cursor_right(false);
         assert_eq!(tf.visible_start_index, 2);
     }
 
@@ -655,19 +684,19 @@ mod test {
     fn test_remove_left_of_cursor_basic_case() {
         let mut tf = create_dummy_textfield();
 
-        assert_eq!(tf.text, "");
+        assert_eq!(tf.editor.text(), "");
         tf.remove_left_of_cursor();
-        assert_eq!(tf.text, "");
+        assert_eq!(tf.editor.text(), "");
 
         for _ in 0..10 {
             tf.add_char_at_cursor('A');
         }
-        assert_eq!(tf.text, "AAAAAAAAAA");
+        assert_eq!(tf.editor.text(), "AAAAAAAAAA");
 
         for _ in 0..10 {
             tf.remove_left_of_cursor();
         }
-        assert_eq!(tf.text, "");
+        assert_eq!(tf.editor.text(), "");
     }
 
     #[test]
@@ -678,7 +707,7 @@ mod test {
         for _ in 0..max_chars + 2 {
             tf.add_char_at_cursor('A');
         }
-        assert_eq!(tf.text, "AAAAAAAAAAAAAAAAAAAAAA");
+        assert_eq!(tf.editor.text(), "AAAAAAAAAAAAAAAAAAAAAA");
         assert_eq!(tf.visible_start_index, 2);
         tf.remove_left_of_cursor();
         assert_eq!(tf.visible_start_index, 2);
@@ -694,30 +723,30 @@ mod test {
         tf.remove_left_of_cursor();
         assert_eq!(tf.visible_start_index, 0);
 
-        assert_eq!(tf.text, "");
+        assert_eq!(tf.editor.text(), "");
     }
 
     #[test]
     fn test_remove_right_of_cursor_basic_case() {
         let mut tf = create_dummy_textfield();
 
-        assert_eq!(tf.text, "");
+        assert_eq!(tf.editor.text(), "");
         tf.remove_right_of_cursor();
-        assert_eq!(tf.text, "");
+        assert_eq!(tf.editor.text(), "");
 
         for _ in 0..10 {
             tf.add_char_at_cursor('A');
         }
-        assert_eq!(tf.text, "AAAAAAAAAA");
+        assert_eq!(tf.editor.text(), "AAAAAAAAAA");
         tf.remove_right_of_cursor();
-        assert_eq!(tf.text, "AAAAAAAAAA");
+        assert_eq!(tf.editor.text(), "AAAAAAAAAA");
 
-        tf.cursor_home();
+        tf.cursor_home(false);
 
         for _ in 0..10 {
             tf.remove_right_of_cursor();
         }
-        assert_eq!(tf.text, "");
+        assert_eq!(tf.editor.text(), "");
     }
 
     #[test]
@@ -728,16 +757,50 @@ mod test {
         for _ in 0..max_chars + 2 {
             tf.add_char_at_cursor('A');
         }
-        assert_eq!(tf.text, "AAAAAAAAAAAAAAAAAAAAAA");
-        tf.cursor_home();
+        assert_eq!(tf.editor.text(), "AAAAAAAAAAAAAAAAAAAAAA");
+        tf.cursor_home(false);
 
-        for _ in 0..tf.text.len() {
+        for _ in 0..tf.editor.len() {
             assert_eq!(tf.visible_start_index, 0);
             tf.remove_right_of_cursor();
         }
         tf.remove_right_of_cursor();
         assert_eq!(tf.visible_start_index, 0);
 
-        assert_eq!(tf.text, "");
+        assert_eq!(tf.editor.text(), "");
+    }
+
+    #[test]
+    fn test_word_movement_scrolls_to_cursor() {
+        let mut tf = create_dummy_textfield();
+        let max_chars = tf.max_visible_chars();
+
+        tf.set_text(format!("{} end", "A".repeat(max_chars)));
+        assert_eq!(tf.visible_start_index, 0);
+        tf.move_cursor_word_right(false);
+        assert_eq!(tf.editor.cursor(), max_chars);
+        assert_eq!(tf.visible_start_index, 0);
+        tf.move_cursor_word_right(false);
+        assert_eq!(tf.editor.cursor(), max_chars + 4);
+        assert_eq!(tf.visible_start_index, 4);
+        tf.move_cursor_word_left(false);
+        tf.move_cursor_word_left(false);
+        assert_eq!(tf.editor.cursor(), 0);
+        assert_eq!(tf.visible_start_index, 0);
+    }
+
+    #[test]
+    fn test_selection_replaced_by_typing() {
+        let mut tf = create_dummy_textfield();
+        tf.set_text("Player 1".to_owned());
+        tf.cursor_end(false);
+        tf.move_cursor_word_left(true);
+        assert_eq!(tf.editor.selected_text(), Some("1".to_owned()));
+        tf.add_char_at_cursor('2');
+        assert_eq!(tf.editor.text(), "Player 2");
+
+        tf.cursor_home(true);
+        tf.remove_right_of_cursor();
+        assert_eq!(tf.text(), None);
     }
 }
@@ -0,0 +1,370 @@
+/*  Copyright 2021 the Conwayste Developers.
+ *
+ *  This file is part of conwayste.
+ *
+ *  conwayste is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  conwayste is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with conwayste.  If not, see
+ *  <http://www.gnu.org/licenses/>. */
+
+//! Short messages ("toasts") that pop up in the top right corner of the window for a few seconds,
+//! on top of whatever screen is shown. Anything can post one through a `Notifier`, including
+//! handlers, with `UIContext::notify`. Clicking a toast dismisses it early.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::time::{Duration, Instant};
+
+use ggez::graphics::{self, Color, DrawMode, DrawParam, FilterMode, Rect, Text};
+use ggez::mint::Point2;
+use ggez::{Context, GameResult};
+
+use super::chatbox::wrap_message;
+use super::common::FontInfo;
+use crate::constants::{
+    self,
+    colors::{TOAST_ERROR_COLOR, TOAST_INFO_COLOR, TOAST_TEXT_COLOR, TOAST_WARN_COLOR},
+};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Severity {
+    Info,
+    Warn,
+    Error,
+}
+
+impl Severity {
+    fn color(self) -> Color {
+        match self {
+            Severity::Info => *TOAST_INFO_COLOR,
+            Severity::Warn => *TOAST_WARN_COLOR,
+            Severity::Error => *TOAST_ERROR_COLOR,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Toast {
+    pub id:       u64,
+    pub severity: Severity,
+    pub message:  String,
+    shown_at:     Option<Instant>, // None while waiting to be shown
+}
+
+/// Which toasts are shown and which are waiting for room, and when each one goes away. This knows
+/// nothing about drawing, and is told the time rather than reading the clock.
+#[derive(Debug)]
+pub struct ToastQueue {
+    shown:     VecDeque<Toast>, // oldest first
+    waiting:   VecDeque<Toast>, // oldest first
+    max_shown: usize,
+    lifetime:  Duration,
+    fade:      Duration, // toasts fade out over this last part of their lifetime
+    next_id:   u64,
+}
+
+impl ToastQueue {
+    pub fn new(max_shown: usize, lifetime: Duration, fade: Duration) -> Self {
+        ToastQueue {
+            shown: VecDeque::new(),
+            waiting: VecDeque::new(),
+            max_shown,
+            lifetime,
+            fade: fade.min(lifetime),
+            next_id: 0,
+        }
+    }
+
+    /// Adds a toast, which is shown at the next `update` if there is room for it. Returns its id.
+    pub fn push(&mut self, severity: Severity, message: String) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.waiting.push_back(Toast {
+            id,
+            severity,
+            message,
+            shown_at: None,
+        });
+        id
+    }
+
+    /// Removes the toasts whose time is up, then shows as many of the waiting ones as there is
+    /// room for, oldest first. Their time starts now.
+    pub fn update(&mut self, now: Instant) {
+        let lifetime = self.lifetime;
+        // unwrap OK because only shown toasts are in `shown`
+        self.shown.retain(|toast| now < toast.shown_at.unwrap() + lifetime);
+        while self.shown.len() < self.max_shown {
+            match self.waiting.pop_front() {
+                Some(mut toast) => {
+                    toast.shown_at = Some(now);
+                    self.shown.push_back(toast);
+                }
+                None => break,
+            }
+        }
+    }
+
+    /// Removes the toast with this id, whether it is shown or waiting. Returns false if there is no
+    /// such toast.
+    pub fn dismiss(&mut self, id: u64) -> bool {
+        let before = self.shown.len() + self.waiting.len();
+        self.shown.retain(|toast| toast.id != id);
+        self.waiting.retain(|toast| toast.id != id);
+        self.shown.len() + self.waiting.len() < before
+    }
+
+    /// The toasts being shown, oldest first.
+    pub fn shown(&self) -> impl Iterator<Item = &Toast> {
+        self.shown.iter()
+    }
+
+    #[cfg(test)]
+    pub fn waiting_len(&self) -> usize {
+        self.waiting.len()
+    }
+
+    /// How opaque `toast` should be drawn at `now`: 1.0 until it starts fading out, then down to 0.0
+    /// when its time is up.
+    pub fn alpha(&self, toast: &Toast, now: Instant) -> f32 {
+        let shown_at = match toast.shown_at {
+            Some(shown_at) => shown_at,
+            None => return 0.0,
+        };
+        let remaining = (shown_at + self.lifetime).saturating_duration_since(now);
+        if remaining >= self.fade {
+            1.0
+        } else {
+            remaining.as_secs_f32() / self.fade.as_secs_f32()
+        }
+    }
+}
+
+/// A handle for posting toasts from anywhere. Cheap to clone. Messages posted through a `Notifier`
+/// with no `Toasts` behind it are only logged.
+#[derive(Debug, Clone, Default)]
+pub struct Notifier {
+    sender: Option<Sender<(Severity, String)>>,
+}
+
+impl Notifier {
+    pub fn notify<S: Into<String>>(&self, severity: Severity, message: S) {
+        let message = message.into();
+        let sent = match self.sender {
+            Some(ref sender) => sender.send((severity, message.clone())).is_ok(),
+            None => false,
+        };
+        if !sent {
+            info!("Notification not shown ({:?}): {}", severity, message);
+        }
+    }
+}
+
+/// Draws the toasts of a `ToastQueue` over everything else, and dismisses them when clicked.
+pub struct Toasts {
+    queue:     ToastQueue,
+    font_info: FontInfo,
+    sender:    Sender<(Severity, String)>,
+    receiver:  Receiver<(Severity, String)>,
+    wrapped:   HashMap<u64, Vec<Text>>, // lines of each shown toast, wrapped to fit when first drawn
+    drawn:     Vec<(u64, Rect)>,        // where each toast was drawn last, for clicks
+}
+
+impl Toasts {
+    pub fn new(font_info: FontInfo) -> Self {
+        let (sender, receiver) = channel();
+        Toasts {
+            queue: ToastQueue::new(
+                constants::MAX_TOASTS_SHOWN,
+                constants::TOAST_LIFETIME,
+                constants::TOAST_FADE,
+            ),
+            font_info,
+            sender,
+            receiver,
+            wrapped: HashMap::new(),
+            drawn: vec![],
+        }
+    }
+
+    /// Returns a handle that posts toasts here.
+    pub fn notifier(&self) -> Notifier {
+        Notifier {
+            sender: Some(self.sender.clone()),
+        }
+    }
+
+    pub fn notify<S: Into<String>>(&mut self, severity: Severity, message: S) {
+        self.queue.push(severity, message.into());
+    }
+
+    /// Picks up toasts posted through a `Notifier`, and shows or removes toasts as their time comes.
+    pub fn update(&mut self, now: Instant) {
+        while let Ok((severity, message)) = self.receiver.try_recv() {
+            self.queue.push(severity, message);
+        }
+        self.queue.update(now);
+        let queue = &self.queue;
+        self.wrapped.retain(|id, _| queue.shown().any(|toast| toast.id == *id));
+    }
+
+    /// Dismisses the toast at `point`, if any. Returns whether there was one, in which case the
+    /// click shouldn't go to anything underneath.
+    pub fn click(&mut self, point: Point2<f32>) -> bool {
+        let clicked = self
+            .drawn
+            .iter()
+            .find(|(_, rect)| rect.contains(point))
+            .map(|(id, _)| *id);
+        match clicked {
+            Some(id) => {
+                self.queue.dismiss(id);
+                self.drawn.retain(|(drawn_id, _)| *drawn_id != id);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
+        let now = Instant::now();
+        let window = graphics::screen_coordinates(ctx);
+        let font_info = self.font_info;
+        let line_height = font_info.char_dimensions.y;
+        let text_width = constants::TOAST_MAX_WIDTH - 2.0 * constants::TOAST_PADDING;
+
+        self.drawn.clear();
+        let mut y = window.y + constants::TOAST_MARGIN;
+        for toast in self.queue.shown() {
+            let lines = self.wrapped.entry(toast.id).or_insert_with(|| {
+                wrap_message(&toast.message, text_width, &mut |text| {
+                    font_info.measure_width(ctx, text)
+                })
+                .into_iter()
+                .map(|line| {
+                    let mut text = Text::new(line);
+                    font_info.apply(&mut text);
+                    text
+                })
+                .collect()
+            });
+            let widest = lines.iter().map(|text| text.width(ctx) as f32).fold(0.0, f32::max);
+            let w = widest + 2.0 * constants::TOAST_PADDING;
+            let h = lines.len() as f32 * line_height + 2.0 * constants::TOAST_PADDING;
+            let rect = Rect::new(window.right() - constants::TOAST_MARGIN - w, y, w, h);
+
+            let alpha = self.queue.alpha(toast, now);
+            let mut fill = toast.severity.color();
+            fill.a *= alpha;
+            let background = graphics::Mesh::new_rectangle(ctx, DrawMode::fill(), rect, fill)?;
+            graphics::draw(ctx, &background, DrawParam::default())?;
+
+            let mut text_color = *TOAST_TEXT_COLOR;
+            text_color.a *= alpha;
+            for (i, line) in lines.iter().enumerate() {
+                let point = Point2 {
+                    x: rect.x + constants::TOAST_PADDING,
+                    y: rect.y + constants::TOAST_PADDING + i as f32 * line_height,
+                };
+                graphics::queue_text(ctx, line, point, Some(text_color));
+            }
+            graphics::draw_queued_text(ctx, DrawParam::default(), None, FilterMode::Linear)?;
+
+            self.drawn.push((toast.id, rect));
+            y += h + constants::TOAST_SPACING;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIFETIME: Duration = Duration::from_secs(4);
+    const FADE: Duration = Duration::from_secs(1);
+
+    fn shown_ids(queue: &ToastQueue) -> Vec<u64> {
+        queue.shown().map(|toast| toast.id).collect()
+    }
+
+    #[test]
+    fn test_toasts_are_shown_in_order() {
+        let mut queue = ToastQueue::new(4, LIFETIME, FADE);
+        let now = Instant::now();
+        let first = queue.push(Severity::Info, "first".to_owned());
+        let second = queue.push(Severity::Error, "second".to_owned());
+        let third = queue.push(Severity::Warn, "third".to_owned());
+        assert!(shown_ids(&queue).is_empty()); // not until the next update
+        queue.update(now);
+        assert_eq!(shown_ids(&queue), vec![first, second, third]);
+        let messages: Vec<&str> = queue.shown().map(|toast| toast.message.as_str()).collect();
+        assert_eq!(messages, vec!["first", "second", "third"]);
+    }
+
+    #[test]
+    fn test_overflow_waits_for_room() {
+        let mut queue = ToastQueue::new(2, LIFETIME, FADE);
+        let start = Instant::now();
+        let ids: Vec<u64> = (0..3).map(|i| queue.push(Severity::Info, format!("{}", i))).collect();
+        queue.update(start);
+        assert_eq!(shown_ids(&queue), vec![ids[0], ids[1]]);
+        assert_eq!(queue.waiting_len(), 1);
+
+        // the third gets its turn when the first two are gone, and its time starts then
+        let later = start + LIFETIME;
+        queue.update(later);
+        assert_eq!(shown_ids(&queue), vec![ids[2]]);
+        assert_eq!(queue.waiting_len(), 0);
+        queue.update(later + LIFETIME - Duration::from_millis(1));
+        assert_eq!(shown_ids(&queue), vec![ids[2]]);
+    }
+
+    #[test]
+    fn test_expiry_and_fade() {
+        let mut queue = ToastQueue::new(4, LIFETIME, FADE);
+        let start = Instant::now();
+        queue.push(Severity::Warn, "Kicked from slot: AFK".to_owned());
+        queue.update(start);
+        let toast = queue.shown().next().unwrap().clone();
+
+        assert_eq!(queue.alpha(&toast, start), 1.0);
+        assert_eq!(queue.alpha(&toast, start + LIFETIME - FADE), 1.0);
+        let halfway = queue.alpha(&toast, start + LIFETIME - FADE / 2);
+        assert!((halfway - 0.5).abs() < 0.001);
+        assert_eq!(queue.alpha(&toast, start + LIFETIME), 0.0);
+
+        queue.update(start + LIFETIME - Duration::from_millis(1));
+        assert_eq!(shown_ids(&queue), vec![toast.id]);
+        queue.update(start + LIFETIME);
+        assert!(shown_ids(&queue).is_empty());
+    }
+
+    #[test]
+    fn test_dismiss_makes_room() {
+        let mut queue = ToastQueue::new(1, LIFETIME, FADE);
+        let now = Instant::now();
+        let first = queue.push(Severity::Info, "first".to_owned());
+        let second = queue.push(Severity::Info, "second".to_owned());
+        queue.update(now);
+        assert!(queue.dismiss(first));
+        assert!(!queue.dismiss(first));
+        queue.update(now);
+        assert_eq!(shown_ids(&queue), vec![second]);
+    }
+
+    #[test]
+    fn test_notifier_without_toasts() {
+        // only logged, and doesn't panic
+        Notifier::default().notify(Severity::Error, "Connection lost");
+    }
+}
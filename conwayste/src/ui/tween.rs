@@ -0,0 +1,214 @@
+/*  Copyright 2021 the Conwayste Developers.
+ *
+ *  This file is part of conwayste.
+ *
+ *  conwayste is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  conwayste is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with conwayste.  If not, see
+ *  <http://www.gnu.org/licenses/>. */
+
+//! Values that change smoothly from one to another over time, for animating widgets. A `Tween` is
+//! advanced by the time since the last frame, usually from an Update handler, and says where the
+//! value has got to. See `Layering::animate_to` for moving widgets around this way.
+
+use std::time::Duration;
+
+use ggez::graphics::{Color, Rect};
+use ggez::mint::Point2;
+
+/// How a tween's progress over time maps to how far its value has gone from start to end.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Easing {
+    Linear,
+    EaseIn,    // starts slowly and speeds up
+    EaseOut,   // starts quickly and slows down at the end
+    EaseInOut, // slow at both ends
+}
+
+impl Easing {
+    /// Maps `t`, the fraction of the duration gone by, to the fraction of the way from start to end.
+    /// Both are 0.0 at the start and 1.0 at the end.
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.max(0.0).min(1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t).powi(3),
+            Easing::EaseInOut => {
+                if t < 0.5 {
+                    4.0 * t * t * t
+                } else {
+                    1.0 - (2.0 - 2.0 * t).powi(3) / 2.0
+                }
+            }
+        }
+    }
+}
+
+/// A value that can be part of the way between two others.
+pub trait Lerp {
+    /// The value `t` of the way from `self` to `to`, where 0.0 is `self` and 1.0 is `to`.
+    fn lerp(&self, to: &Self, t: f32) -> Self;
+}
+
+impl Lerp for f32 {
+    fn lerp(&self, to: &Self, t: f32) -> Self {
+        self + (to - self) * t
+    }
+}
+
+impl Lerp for Point2<f32> {
+    fn lerp(&self, to: &Self, t: f32) -> Self {
+        Point2 {
+            x: self.x.lerp(&to.x, t),
+            y: self.y.lerp(&to.y, t),
+        }
+    }
+}
+
+impl Lerp for Rect {
+    fn lerp(&self, to: &Self, t: f32) -> Self {
+        Rect::new(
+            self.x.lerp(&to.x, t),
+            self.y.lerp(&to.y, t),
+            self.w.lerp(&to.w, t),
+            self.h.lerp(&to.h, t),
+        )
+    }
+}
+
+impl Lerp for Color {
+    fn lerp(&self, to: &Self, t: f32) -> Self {
+        Color::new(
+            self.r.lerp(&to.r, t),
+            self.g.lerp(&to.g, t),
+            self.b.lerp(&to.b, t),
+            self.a.lerp(&to.a, t),
+        )
+    }
+}
+
+/// A value going from `from` to `to` over `duration`, eased by `easing`.
+#[derive(Debug, Clone)]
+pub struct Tween<T: Lerp> {
+    from:     T,
+    to:       T,
+    duration: Duration,
+    elapsed:  Duration,
+    easing:   Easing,
+}
+
+impl<T: Lerp> Tween<T> {
+    pub fn new(from: T, to: T, duration: Duration, easing: Easing) -> Self {
+        Tween {
+            from,
+            to,
+            duration,
+            elapsed: Duration::from_secs(0),
+            easing,
+        }
+    }
+
+    /// Moves the tween `dt` further along, and returns its value there. It stops at the end.
+    pub fn advance(&mut self, dt: Duration) -> T {
+        self.elapsed = (self.elapsed + dt).min(self.duration);
+        self.value()
+    }
+
+    /// The value as of how far the tween has been advanced.
+    pub fn value(&self) -> T {
+        if self.duration == Duration::from_secs(0) {
+            return self.from.lerp(&self.to, 1.0);
+        }
+        let t = self.elapsed.as_secs_f32() / self.duration.as_secs_f32();
+        self.from.lerp(&self.to, self.easing.apply(t))
+    }
+
+    /// The value the tween ends at.
+    pub fn target(&self) -> &T {
+        &self.to
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const ALL_EASINGS: [Easing; 4] = [Easing::Linear, Easing::EaseIn, Easing::EaseOut, Easing::EaseInOut];
+
+    #[test]
+    fn test_easing_endpoints_and_shape() {
+        for easing in ALL_EASINGS.iter() {
+            assert_eq!(easing.apply(0.0), 0.0, "{:?}", easing);
+            assert_eq!(easing.apply(1.0), 1.0, "{:?}", easing);
+            // out of range is clamped
+            assert_eq!(easing.apply(-1.0), 0.0, "{:?}", easing);
+            assert_eq!(easing.apply(2.0), 1.0, "{:?}", easing);
+            // never goes backwards
+            let mut last = 0.0;
+            for i in 1..=100 {
+                let eased = easing.apply(i as f32 / 100.0);
+                assert!(eased >= last, "{:?} went backwards at {}", easing, i);
+                last = eased;
+            }
+        }
+        assert_eq!(Easing::Linear.apply(0.25), 0.25);
+        assert!(Easing::EaseIn.apply(0.25) < 0.25);
+        assert!(Easing::EaseOut.apply(0.25) > 0.25);
+        assert_eq!(Easing::EaseInOut.apply(0.5), 0.5);
+    }
+
+    #[test]
+    fn test_tween_advances_and_stops_at_the_end() {
+        let mut tween = Tween::new(
+            Rect::new(-100.0, 20.0, 50.0, 50.0),
+            Rect::new(100.0, 20.0, 150.0, 50.0),
+            Duration::from_millis(200),
+            Easing::Linear,
+        );
+        assert_eq!(tween.value(), Rect::new(-100.0, 20.0, 50.0, 50.0));
+        assert!(!tween.is_finished());
+
+        let halfway = tween.advance(Duration::from_millis(100));
+        assert!((halfway.x - 0.0).abs() < 0.001);
+        assert!((halfway.w - 100.0).abs() < 0.001);
+        assert_eq!(halfway.y, 20.0);
+        assert!(!tween.is_finished());
+
+        assert_eq!(
+            tween.advance(Duration::from_millis(500)),
+            Rect::new(100.0, 20.0, 150.0, 50.0)
+        );
+        assert!(tween.is_finished());
+        assert_eq!(tween.value(), *tween.target());
+    }
+
+    #[test]
+    fn test_tween_alpha_and_zero_duration() {
+        let mut fade = Tween::new(
+            Color::new(0.0, 0.0, 0.0, 0.0),
+            Color::new(0.0, 0.0, 0.0, 0.6),
+            Duration::from_millis(100),
+            Easing::EaseOut,
+        );
+        let faded = fade.advance(Duration::from_millis(50));
+        assert!(faded.a > 0.3 && faded.a < 0.6);
+
+        let snap = Tween::new(1.0f32, 5.0, Duration::from_secs(0), Easing::EaseIn);
+        assert!(snap.is_finished());
+        assert_eq!(snap.value(), 5.0);
+    }
+}
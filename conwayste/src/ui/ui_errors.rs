@@ -16,14 +16,38 @@
  *  along with libconway.  If not, see <http://www.gnu.org/licenses/>. */
 
 use crate::ggez::GameError;
+use crate::Screen;
 
+use id_tree::NodeId;
+
+// Errors about a particular widget say where it is in its layering's widget tree, as the widget
+// types from the outermost container down to it (see `Layering::widget_path`).
 custom_error! {pub UIError
-    InvalidDimensions {reason: String} = "UIError::InvalidDimensions({reason})",
-    WidgetNotFound {reason: String} = "UIError::WidgetNotFound({reason})",
+    InvalidRect {reason: String} = "UIError::InvalidRect({reason})",
+    ScreenNotFound {screen: Screen} = @{
+        format!("UIError::ScreenNotFound({:?} has no layering in the UI layout)", screen)
+    },
+    WidgetNotFound {node_id: NodeId} = @{ format!("UIError::WidgetNotFound({:?} is not in the layering)", node_id) },
+    WidgetNameNotFound {name: String} = "UIError::WidgetNameNotFound(no widget is named {name:?})",
+    WrongWidgetType {expected: &'static str, found: &'static str, path: String} =
+        "UIError::WrongWidgetType(expected {expected}, found {found} at {path})",
+    DuplicateInsertion {node_id: NodeId} = @{
+        format!("UIError::DuplicateInsertion(widget was already inserted as {:?})", node_id)
+    },
     InvalidAction {reason: String} = "UIError::InvalidAction({reason})",
     ActionRestricted{reason: String} = "UIError::ActionRestricted({reason})",
     NodeIDCollision{reason: String} = "UIError::NodeIDCollision({reason})",
-    InvalidArgument{reason: String} = "UIError::InvalidArgument({reason})",
+}
+
+/// The name of type `T` without its module path, like "Button", for error messages.
+pub fn short_type_name<T: ?Sized>() -> &'static str {
+    let name = std::any::type_name::<T>();
+    // the module path ends at the last "::" before any generic parameters
+    let end = name.find('<').unwrap_or(name.len());
+    match name[..end].rfind("::") {
+        Some(i) => &name[i + 2..],
+        None => name,
+    }
 }
 
 // TODO: use Box<dyn Error> and make this a generic CwResult (project-wide)
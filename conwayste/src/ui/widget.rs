@@ -24,7 +24,7 @@ use downcast_rs::Downcast;
 
 use id_tree::NodeId;
 
-use super::{context, UIResult};
+use super::{context, ui_errors::short_type_name, UIResult};
 
 /// A user interface element trait that defines graphical, interactive behavior to be specified.
 /// Relies on the `downcast_rs` crate to be able to transform widgets into their specific
@@ -88,6 +88,11 @@ pub trait Widget: Downcast + std::fmt::Debug {
     fn accepts_keyboard_events(&self) -> bool {
         false
     }
+
+    /// The name of the widget's type, like "Button", for error messages.
+    fn type_name(&self) -> &'static str {
+        short_type_name::<Self>()
+    }
 }
 
 impl_downcast!(Widget);
@@ -18,18 +18,24 @@
 
 use std::collections::HashMap;
 use std::error::Error;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
 use ggez::graphics::{Font, Rect};
 use ggez::mint::Point2;
 use ggez::Context;
 
 use id_tree::NodeId;
+use netwayste::net::MAX_CHAT_MESSAGE_CHARS;
 
 use crate::config::Config;
 use crate::constants;
+use crate::keybindings::{KeyAction, KeyBindings, KeyChord};
+use crate::theme::{self, ThemeColor, DARK_THEME_NAME, LIGHT_THEME_NAME};
 use crate::ui::{
-    color_with_alpha, common, context, Button, Chatbox, Checkbox, GameArea, InsertLocation, Label, Layering, Pane,
-    TextField, UIResult, Widget,
+    anchor, color_with_alpha, common, context, short_type_name, Anchor, Button, Chatbox, Checkbox, Corner, Easing,
+    GameArea, InsertLocation, Label, Layering, Minimap, Notifier, Pane, ServerList, Severity, TextField, UIError,
+    UIResult, Widget,
 };
 use crate::Screen;
 
@@ -37,11 +43,12 @@ use chromatica::css;
 use context::{
     EmitEvent, // so we can call .on(...) on widgets that implement this
     EventType,
+    Handler,
 };
 
 // When adding support for a new widget, use this macro to define a routine which allows the
-// developer to search in a `UILayout`/`Screen` pair for a widget by its ID. In most cases this is
-// all you need to retrieve a widget for mutating, like on an "update".
+// developer to search in a `UILayout`/`Screen` pair for a widget by its ID, or by its name if it
+// was given one. In most cases this is all you need to retrieve a widget for mutating, like on an "update".
 macro_rules! add_widget_from_screen_id_mut {
     ($type:ident) => {
         #[allow(unused)]
@@ -54,9 +61,18 @@ macro_rules! add_widget_from_screen_id_mut {
                 if let Some(layer) = ui.get_screen_layering_mut(screen) {
                     return $type::widget_from_id_mut(layer, id);
                 }
-                Err(Box::new(crate::ui::UIError::InvalidArgument {
-                    reason: format!("{:?} not found in UI Layout", screen),
-                }))
+                Err(Box::new(crate::ui::UIError::ScreenNotFound { screen }))
+            }
+
+            pub fn widget_from_screen_and_name_mut(
+                ui: &'a mut UILayout,
+                screen: Screen,
+                name: &str,
+            ) -> crate::ui::UIResult<&'a mut $type> {
+                if let Some(layer) = ui.get_screen_layering_mut(screen) {
+                    return $type::widget_from_name_mut(layer, name);
+                }
+                Err(Box::new(crate::ui::UIError::ScreenNotFound { screen }))
             }
         }
     };
@@ -77,9 +93,18 @@ macro_rules! add_widget_from_screen_id {
                 if let Some(layer) = ui.get_screen_layering(screen) {
                     return $type::widget_from_id(layer, id);
                 }
-                Err(Box::new(crate::ui::UIError::InvalidArgument {
-                    reason: format!("{:?} not found in UI Layout", screen),
-                }))
+                Err(Box::new(crate::ui::UIError::ScreenNotFound { screen }))
+            }
+
+            pub fn widget_from_screen_and_name(
+                ui: &'a UILayout,
+                screen: Screen,
+                name: &str,
+            ) -> crate::ui::UIResult<&'a $type> {
+                if let Some(layer) = ui.get_screen_layering(screen) {
+                    return $type::widget_from_name(layer, name);
+                }
+                Err(Box::new(crate::ui::UIError::ScreenNotFound { screen }))
             }
         }
     };
@@ -98,6 +123,7 @@ pub struct StaticNodeIds {
     pub chatbox_pane_id: NodeId,
     pub chatbox_tf_id:   NodeId,
     pub game_area_id:    NodeId,
+    pub minimap_id:      NodeId,
 }
 
 /// `UILayout` is responsible for the definition and storage of UI elements.
@@ -111,6 +137,38 @@ impl UILayout {
         self.layers.get_mut(&screen)
     }
 
+    /// Sends the toasts posted by every screen's handlers to `notifier`.
+    pub fn set_notifier(&mut self, notifier: &Notifier) {
+        for layering in self.layers.values_mut() {
+            layering.set_notifier(notifier.clone());
+        }
+    }
+
+    /// Returns the minimap's rectangle for a window of the given resolution. The minimap sits in
+    /// the bottom right corner and keeps the universe's aspect ratio.
+    pub fn minimap_rect((res_x, res_y): (f32, f32)) -> Rect {
+        let w = res_x * constants::MINIMAP_WIDTH_FRACTION;
+        let h = w * constants::UNIVERSE_HEIGHT_IN_CELLS as f32 / constants::UNIVERSE_WIDTH_IN_CELLS as f32;
+        Rect::new(
+            res_x - w - constants::MINIMAP_MARGIN,
+            res_y - h - constants::MINIMAP_MARGIN,
+            w,
+            h,
+        )
+    }
+
+    /// The chat pane stays in the bottom left corner of the window.
+    pub fn chat_pane_anchor() -> Anchor {
+        let size = *constants::DEFAULT_CHATBOX_RECT;
+        Anchor::new(
+            Corner::BottomLeft,
+            constants::CHAT_PANE_MARGIN_X,
+            constants::CHAT_PANE_MARGIN_Y,
+            size.w,
+            size.h,
+        )
+    }
+
     fn build_options_menu(
         ctx: &mut Context,
         config: &Config,
@@ -125,18 +183,25 @@ impl UILayout {
             Rect::new(10.0, 210.0, 20.0, 20.0),
         ));
 
-        let name_color = color_with_alpha(css::WHITE, 1.0);
+        let mut theme_checkbox = Box::new(Checkbox::new(
+            ctx,
+            config.get().video.theme == LIGHT_THEME_NAME,
+            default_font_info,
+            "Light Theme".to_owned(),
+            Rect::new(10.0, 250.0, 20.0, 20.0),
+        ));
+
+        let name_color = theme::current().label_text;
         let value_color = color_with_alpha(css::AQUAMARINE, 1.0);
-        layer_options.add_widget(
-            Box::new(Label::new(
-                ctx,
-                default_font_info,
-                "Resolution".to_owned(),
-                name_color,
-                Point2 { x: 10.0, y: 300.0 },
-            )),
-            InsertLocation::AtCurrentLayer,
-        )?;
+        let mut resolution_label = Box::new(Label::new(
+            ctx,
+            default_font_info,
+            "Resolution".to_owned(),
+            name_color,
+            Point2 { x: 10.0, y: 300.0 },
+        ));
+        resolution_label.set_theme_color(ThemeColor::LabelText);
+        layer_options.add_widget(resolution_label, InsertLocation::AtCurrentLayer)?;
 
         let mut resolution_value_label = Box::new(Label::new(
             ctx,
@@ -145,24 +210,32 @@ impl UILayout {
             value_color,
             Point2 { x: 200.0, y: 300.0 },
         ));
-        resolution_value_label
-            .on(context::EventType::Update, Box::new(resolution_update_handler))
-            .unwrap();
-        layer_options.add_widget(resolution_value_label, InsertLocation::AtCurrentLayer)?;
-
-        // unwrap OK here because we are not calling .on from within a handler
-        fullscreen_checkbox
-            .on(EventType::Click, Box::new(fullscreen_toggle_handler))
-            .unwrap();
-        layer_options.add_widget(fullscreen_checkbox, InsertLocation::AtCurrentLayer)?;
-
-        let playername_label = Box::new(Label::new(
+        // wrap rather than run off the screen if the value is long
+        resolution_value_label.set_max_width(ctx, Some(300.0))?;
+        register(
+            &mut *resolution_value_label,
+            context::EventType::Update,
+            Box::new(resolution_update_handler),
+        )?;
+        layer_options.add_named_widget("resolution", resolution_value_label, InsertLocation::AtCurrentLayer)?;
+
+        register(
+            &mut *fullscreen_checkbox,
+            EventType::Click,
+            Box::new(fullscreen_toggle_handler),
+        )?;
+        layer_options.add_named_widget("fullscreen", fullscreen_checkbox, InsertLocation::AtCurrentLayer)?;
+        register(&mut *theme_checkbox, EventType::Click, Box::new(theme_toggle_handler))?;
+        layer_options.add_named_widget("light theme", theme_checkbox, InsertLocation::AtCurrentLayer)?;
+
+        let mut playername_label = Box::new(Label::new(
             ctx,
             default_font_info,
             "Player Name:".to_owned(),
             name_color,
             Point2 { x: 0.0, y: 0.0 },
         ));
+        playername_label.set_theme_color(ThemeColor::LabelText);
         let pnlabel_x = playername_label.position().x;
         let pnlabel_y = playername_label.position().y;
         let pnlabel_r_edge = playername_label.size().0 + pnlabel_x;
@@ -170,8 +243,8 @@ impl UILayout {
             default_font_info,
             Rect::new(pnlabel_r_edge + 20.0, pnlabel_y, 200.0, 30.0),
         ));
-        playername_tf.on(EventType::Load, Box::new(load_player_name)).unwrap();
-        playername_tf.on(EventType::Save, Box::new(save_player_name)).unwrap();
+        register(&mut *playername_tf, EventType::Load, Box::new(load_player_name))?;
+        register(&mut *playername_tf, EventType::Save, Box::new(save_player_name))?;
 
         let mut playername_pane = Box::new(Pane::new(Rect::new(10.0, 0.0, 0.0, 0.0)));
         playername_pane.set_rect(Rect::new(
@@ -184,21 +257,49 @@ impl UILayout {
 
         let playername_pane_id = layer_options.add_widget(playername_pane, InsertLocation::AtCurrentLayer)?;
         layer_options.add_widget(playername_label, InsertLocation::ToNestedContainer(&playername_pane_id))?;
-        layer_options.add_widget(playername_tf, InsertLocation::ToNestedContainer(&playername_pane_id))?;
+        layer_options.add_named_widget(
+            "player name",
+            playername_tf,
+            InsertLocation::ToNestedContainer(&playername_pane_id),
+        )?;
+
+        let mut keybindings_label = Box::new(Label::new(
+            ctx,
+            default_font_info,
+            "Key Bindings".to_owned(),
+            name_color,
+            Point2 { x: 450.0, y: 10.0 },
+        ));
+        keybindings_label.set_theme_color(ThemeColor::LabelText);
+        layer_options.add_widget(keybindings_label, InsertLocation::AtCurrentLayer)?;
+        for (i, &action) in KeyAction::ALL.iter().enumerate() {
+            let mut button = Box::new(Button::new(ctx, default_font_info, keybinding_text(config, action)));
+            button.set_rect(Rect::new(450.0, 50.0 + 36.0 * i as f32, 300.0, 30.0))?;
+            register_rebind_handlers(&mut *button, action)?;
+            layer_options.add_named_widget(action.name(), button, InsertLocation::AtCurrentLayer)?;
+        }
 
         Ok(layer_options)
     }
 
-    fn build_main_menu(ctx: &mut Context, default_font_info: common::FontInfo) -> UIResult<Layering> {
+    /// If `resume_autosave_handler` is Some, a button to resume the autosaved game is added below
+    /// the one for starting a single player game, and clicking it calls the handler.
+    fn build_main_menu(
+        ctx: &mut Context,
+        default_font_info: common::FontInfo,
+        resume_autosave_handler: Option<Handler>,
+    ) -> UIResult<Layering> {
         let mut layer_mainmenu = Layering::new();
 
         // Create a new pane, and add two test buttons to it.
         let pane = Box::new(Pane::new(Rect::new_i32(20, 20, 410, 450)));
         let mut serverlist_button = Box::new(Button::new(ctx, default_font_info, "Server List".to_owned()));
         serverlist_button.set_rect(Rect::new(10.0, 10.0, 180.0, 50.0))?;
-        serverlist_button
-            .on(EventType::Click, Box::new(server_list_click_handler))
-            .unwrap(); // unwrap OK
+        register(
+            &mut *serverlist_button,
+            EventType::Click,
+            Box::new(server_list_click_handler),
+        )?;
 
         let mut start_1p_game_button = Box::new(Button::new(
             ctx,
@@ -206,35 +307,160 @@ impl UILayout {
             "Start Single Player Game".to_owned(),
         ));
         start_1p_game_button.set_rect(Rect::new(10.0, 70.0, 350.0, 50.0))?;
-        start_1p_game_button
-            .on(EventType::Click, Box::new(start_or_resume_game_click_handler))
-            .unwrap(); // unwrap OK
+        register(
+            &mut *start_1p_game_button,
+            EventType::Click,
+            Box::new(start_game_click_handler),
+        )?;
+
+        let mut resume_autosave_button = None;
+        if let Some(handler) = resume_autosave_handler {
+            let mut button = Box::new(Button::new(ctx, default_font_info, "Resume Autosaved Game".to_owned()));
+            button.set_rect(Rect::new(10.0, 130.0, 350.0, 50.0))?;
+            register(&mut *button, EventType::Click, handler)?;
+            resume_autosave_button = Some(button);
+        }
+        // the buttons below move down to make room for the resume button
+        let y_offset = if resume_autosave_button.is_some() { 60.0 } else { 0.0 };
 
         let mut options_button = Box::new(Button::new(ctx, default_font_info, "Options".to_owned()));
-        options_button.set_rect(Rect::new(10.0, 130.0, 180.0, 50.0))?;
-        options_button
-            .on(EventType::Click, Box::new(options_click_handler))
-            .unwrap(); // unwrap OK
+        options_button.set_rect(Rect::new(10.0, 130.0 + y_offset, 180.0, 50.0))?;
+        register(&mut *options_button, EventType::Click, Box::new(options_click_handler))?;
 
         let mut quit_button = Box::new(Button::new(ctx, default_font_info, "Quit".to_owned()));
-        quit_button.set_rect(Rect::new(10.0, 190.0, 180.0, 50.0))?;
-        quit_button.on(EventType::Click, Box::new(quit_click_handler)).unwrap(); // unwrap OK
+        quit_button.set_rect(Rect::new(10.0, 190.0 + y_offset, 180.0, 50.0))?;
+        register(&mut *quit_button, EventType::Click, Box::new(quit_click_handler))?;
 
         let menupane_id = layer_mainmenu.add_widget(pane, InsertLocation::AtCurrentLayer)?;
         // Add widgets in the order you want keyboard focus
-        layer_mainmenu.add_widget(serverlist_button, InsertLocation::ToNestedContainer(&menupane_id))?;
-        layer_mainmenu.add_widget(start_1p_game_button, InsertLocation::ToNestedContainer(&menupane_id))?;
-        layer_mainmenu.add_widget(options_button, InsertLocation::ToNestedContainer(&menupane_id))?;
-        layer_mainmenu.add_widget(quit_button, InsertLocation::ToNestedContainer(&menupane_id))?;
+        layer_mainmenu.add_named_widget(
+            "server list",
+            serverlist_button,
+            InsertLocation::ToNestedContainer(&menupane_id),
+        )?;
+        layer_mainmenu.add_named_widget(
+            "start single player game",
+            start_1p_game_button,
+            InsertLocation::ToNestedContainer(&menupane_id),
+        )?;
+        if let Some(button) = resume_autosave_button {
+            layer_mainmenu.add_named_widget(
+                "resume autosaved game",
+                button,
+                InsertLocation::ToNestedContainer(&menupane_id),
+            )?;
+        }
+        layer_mainmenu.add_named_widget(
+            "options",
+            options_button,
+            InsertLocation::ToNestedContainer(&menupane_id),
+        )?;
+        layer_mainmenu.add_named_widget("quit", quit_button, InsertLocation::ToNestedContainer(&menupane_id))?;
         Ok(layer_mainmenu)
     }
 
-    pub fn new(ctx: &mut Context, config: &Config, font: Font) -> UIResult<(UILayout, StaticNodeIds)> {
+    /// The menu shown over the paused game when Escape is pressed on the Run screen.
+    fn build_ingame_menu(ctx: &mut Context, default_font_info: common::FontInfo) -> UIResult<Layering> {
+        let mut layer_ingame_menu = Layering::new();
+
+        let mut pane = Box::new(Pane::new(*constants::INGAME_MENU_RECT));
+        pane.bg_color = Some(ThemeColor::MenuPaneFill);
+
+        let mut resume_button = Box::new(Button::new(ctx, default_font_info, "Resume".to_owned()));
+        resume_button.set_rect(Rect::new(10.0, 10.0, 350.0, 50.0))?;
+        register(
+            &mut *resume_button,
+            EventType::Click,
+            Box::new(resume_game_click_handler),
+        )?;
+
+        let mut options_button = Box::new(Button::new(ctx, default_font_info, "Options".to_owned()));
+        options_button.set_rect(Rect::new(10.0, 70.0, 350.0, 50.0))?;
+        register(&mut *options_button, EventType::Click, Box::new(options_click_handler))?;
+
+        let mut quit_to_menu_button = Box::new(Button::new(ctx, default_font_info, "Quit to Menu".to_owned()));
+        quit_to_menu_button.set_rect(Rect::new(10.0, 130.0, 350.0, 50.0))?;
+        register(
+            &mut *quit_to_menu_button,
+            EventType::Click,
+            Box::new(quit_to_menu_click_handler),
+        )?;
+
+        let menupane_id = layer_ingame_menu.add_named_widget("menu pane", pane, InsertLocation::AtCurrentLayer)?;
+        // Add widgets in the order you want keyboard focus
+        layer_ingame_menu.add_named_widget("resume", resume_button, InsertLocation::ToNestedContainer(&menupane_id))?;
+        layer_ingame_menu.add_named_widget(
+            "options",
+            options_button,
+            InsertLocation::ToNestedContainer(&menupane_id),
+        )?;
+        layer_ingame_menu.add_named_widget(
+            "quit to menu",
+            quit_to_menu_button,
+            InsertLocation::ToNestedContainer(&menupane_id),
+        )?;
+        Ok(layer_ingame_menu)
+    }
+
+    /// Asks whether to really quit, over the main menu. Shown when going back from the main menu.
+    fn build_quit_confirm(ctx: &mut Context, default_font_info: common::FontInfo) -> UIResult<Layering> {
+        let mut layer_quit_confirm = Layering::new();
+
+        let mut pane = Box::new(Pane::new(Rect::new_i32(20, 20, 370, 150)));
+        pane.bg_color = Some(ThemeColor::MenuPaneFill);
+
+        let mut question = Box::new(Label::new(
+            ctx,
+            default_font_info,
+            "Quit Conwayste?".to_owned(),
+            theme::current().label_text,
+            Point2 { x: 10.0, y: 10.0 },
+        ));
+        question.set_theme_color(ThemeColor::LabelText);
+
+        let mut quit_button = Box::new(Button::new(ctx, default_font_info, "Quit".to_owned()));
+        quit_button.set_rect(Rect::new(10.0, 70.0, 170.0, 50.0))?;
+        register(&mut *quit_button, EventType::Click, Box::new(quit_click_handler))?;
+
+        let mut cancel_button = Box::new(Button::new(ctx, default_font_info, "Cancel".to_owned()));
+        cancel_button.set_rect(Rect::new(190.0, 70.0, 170.0, 50.0))?;
+        register(
+            &mut *cancel_button,
+            EventType::Click,
+            Box::new(cancel_quit_click_handler),
+        )?;
+
+        let pane_id = layer_quit_confirm.add_widget(pane, InsertLocation::AtCurrentLayer)?;
+        layer_quit_confirm.add_widget(question, InsertLocation::ToNestedContainer(&pane_id))?;
+        // Add widgets in the order you want keyboard focus
+        layer_quit_confirm.add_named_widget("quit", quit_button, InsertLocation::ToNestedContainer(&pane_id))?;
+        layer_quit_confirm.add_named_widget("cancel", cancel_button, InsertLocation::ToNestedContainer(&pane_id))?;
+        Ok(layer_quit_confirm)
+    }
+
+    /// The list of servers to join, below the screen's title.
+    fn build_server_list(default_font_info: common::FontInfo) -> UIResult<Layering> {
+        let mut layer_server_list = Layering::new();
+        let server_list = Box::new(ServerList::new(
+            default_font_info,
+            Rect::new(100.0, 140.0, 600.0, 400.0),
+        ));
+        layer_server_list.add_named_widget("server list", server_list, InsertLocation::AtCurrentLayer)?;
+        Ok(layer_server_list)
+    }
+
+    /// Builds every screen's widgets. See `build_main_menu` for `resume_autosave_handler`.
+    pub fn new(
+        ctx: &mut Context,
+        config: &Config,
+        font: Font,
+        resume_autosave_handler: Option<Handler>,
+    ) -> UIResult<(UILayout, StaticNodeIds)> {
         let mut ui_layers = HashMap::new();
 
         let default_font_info = common::FontInfo::new(ctx, font, None);
 
-        let layer_mainmenu = UILayout::build_main_menu(ctx, default_font_info)?;
+        let layer_mainmenu = UILayout::build_main_menu(ctx, default_font_info, resume_autosave_handler)?;
         debug!("MENU WIDGET TREE");
         layer_mainmenu.debug_display_widget_tree();
         ui_layers.insert(Screen::Menu, layer_mainmenu);
@@ -244,12 +470,42 @@ impl UILayout {
         layer_options.debug_display_widget_tree();
         ui_layers.insert(Screen::Options, layer_options);
 
+        let layer_ingame_menu = UILayout::build_ingame_menu(ctx, default_font_info)?;
+        debug!("IN-GAME MENU WIDGET TREE");
+        layer_ingame_menu.debug_display_widget_tree();
+        ui_layers.insert(Screen::InGameMenu, layer_ingame_menu);
+
+        let layer_quit_confirm = UILayout::build_quit_confirm(ctx, default_font_info)?;
+        debug!("QUIT CONFIRMATION WIDGET TREE");
+        layer_quit_confirm.debug_display_widget_tree();
+        ui_layers.insert(Screen::QuitConfirm, layer_quit_confirm);
+
+        let layer_server_list = UILayout::build_server_list(default_font_info)?;
+        debug!("SERVER LIST WIDGET TREE");
+        layer_server_list.debug_display_widget_tree();
+        ui_layers.insert(Screen::ServerList, layer_server_list);
+
         // ==== In-Game (Run screen) ====
         let mut layer_ingame = Layering::new();
-        let chat_pane_rect = *constants::DEFAULT_CHATBOX_RECT;
+        // Widgets are drawn in the order they are added, so the game area goes first to stay underneath
+        // the minimap and chat pane
+        let gameplay = &config.get().gameplay;
+        let mut game_area = Box::new(GameArea::new(font, gameplay.zoom, gameplay.undo_depth));
+        info!("Setting Game Area to {:?}", config.get_resolution());
+        let (x, y) = config.get_resolution();
+        game_area.set_rect(Rect::new(0.0, 0.0, x, y))?;
+        let game_area_id = layer_ingame.add_named_widget("game area", game_area, InsertLocation::AtCurrentLayer)?;
+
+        let chat_pane_anchor = UILayout::chat_pane_anchor();
+        let chat_pane_rect = chat_pane_anchor.rect(config.get_resolution());
         let mut chatpane = Box::new(Pane::new(chat_pane_rect));
-        chatpane.bg_color = Some(*constants::colors::CHAT_PANE_FILL_COLOR);
-        let chatpane_id = layer_ingame.add_widget(chatpane, InsertLocation::AtCurrentLayer)?;
+        chatpane.bg_color = Some(ThemeColor::ChatPaneFill);
+        register(
+            &mut *chatpane,
+            EventType::Resize,
+            anchor::resize_handler::<Pane>(chat_pane_anchor),
+        )?;
+        let chatpane_id = layer_ingame.add_named_widget("chat pane", chatpane, InsertLocation::AtCurrentLayer)?;
 
         let chatbox_rect = Rect::new(
             0.0,
@@ -270,15 +526,22 @@ impl UILayout {
             constants::CHAT_TEXTFIELD_HEIGHT,
         );
         let mut textfield = Box::new(TextField::new(default_font_info, textfield_rect));
-        textfield.bg_color = Some(*constants::colors::CHAT_PANE_FILL_COLOR);
-        let chatbox_id = layer_ingame.add_widget(chatbox, InsertLocation::ToNestedContainer(&chatpane_id))?;
-        let chatbox_tf_id = layer_ingame.add_widget(textfield, InsertLocation::ToNestedContainer(&chatpane_id))?;
+        textfield.bg_color = Some(ThemeColor::ChatPaneFill);
+        textfield.set_max_len(Some(MAX_CHAT_MESSAGE_CHARS));
+        let chatbox_id =
+            layer_ingame.add_named_widget("chatbox", chatbox, InsertLocation::ToNestedContainer(&chatpane_id))?;
+        let chatbox_tf_id = layer_ingame.add_named_widget(
+            "chat textfield",
+            textfield,
+            InsertLocation::ToNestedContainer(&chatpane_id),
+        )?;
 
-        let mut game_area = Box::new(GameArea::new());
-        info!("Setting Game Area to {:?}", config.get_resolution());
-        let (x, y) = config.get_resolution();
-        game_area.set_rect(Rect::new(0.0, 0.0, x, y))?;
-        let game_area_id = layer_ingame.add_widget(game_area, InsertLocation::AtCurrentLayer)?;
+        let mut minimap = Box::new(Minimap::new(
+            constants::UNIVERSE_WIDTH_IN_CELLS,
+            constants::UNIVERSE_HEIGHT_IN_CELLS,
+        ));
+        minimap.set_rect(UILayout::minimap_rect(config.get_resolution()))?;
+        let minimap_id = layer_ingame.add_named_widget("minimap", minimap, InsertLocation::AtCurrentLayer)?;
 
         debug!("RUN WIDGET TREE");
         layer_ingame.debug_display_widget_tree();
@@ -291,24 +554,117 @@ impl UILayout {
                 chatbox_pane_id: chatpane_id,
                 chatbox_tf_id,
                 game_area_id,
+                minimap_id,
             },
         ))
     }
 }
+
+/// Goes back from the screen on top of `screen_stack`, which is what Escape does when no text
+/// field has focus. Most screens are popped, returning to the one below. The Run screen is never
+/// popped this way; the game is paused under the in-game menu instead, so that resuming returns to
+/// the same universe. Going back from the bottom of the stack (the main menu) asks whether to
+/// quit, unless there is a game in progress to return to.
+pub fn go_back(screen_stack: &mut Vec<Screen>, game_in_progress: bool) {
+    match screen_stack.last() {
+        Some(Screen::Run) => screen_stack.push(Screen::InGameMenu),
+        Some(Screen::Menu) if game_in_progress => screen_stack.push(Screen::Run),
+        Some(Screen::Intro) | Some(Screen::Exit) | None => {}
+        Some(_) if screen_stack.len() > 1 => {
+            screen_stack.pop();
+        }
+        Some(_) => screen_stack.push(Screen::QuitConfirm),
+    }
+}
+
+/// Slides the in-game menu's pane in from the left edge of the window, rather than have it pop up
+/// over the game all at once.
+pub fn slide_in_ingame_menu(layering: &mut Layering) -> UIResult<()> {
+    let pane_id = layering.widget_by_name("menu pane").ok_or_else(|| {
+        Box::new(UIError::WidgetNameNotFound {
+            name: "menu pane".to_owned(),
+        })
+    })?;
+    let rect = *constants::INGAME_MENU_RECT;
+    layering.move_widget(&pane_id, Rect { x: -rect.w, ..rect })?;
+    layering.animate_to(&pane_id, rect, constants::INGAME_MENU_SLIDE_DURATION, Easing::EaseOut)
+}
+
+/// The events to emit, in order, on each screen's layering when the screen on top of the stack
+/// changes from `old_screen` to `new_screen`. The screen being left gets a Save event first, so
+/// that anything it writes to the config is there for the Load event on the screen being entered.
+/// This is the same whether the new screen was pushed or the old one popped.
+pub fn screen_change_events(old_screen: Screen, new_screen: Screen) -> Vec<(Screen, context::Event)> {
+    if old_screen == new_screen {
+        return vec![];
+    }
+    vec![
+        (old_screen, context::Event::new_save()),
+        (new_screen, context::Event::new_load()),
+    ]
+}
+
+/// Registers `handler` for `what` events on `widget`, while building a screen's widgets. This can
+/// only fail if called from within a handler.
+fn register(widget: &mut dyn EmitEvent, what: EventType, handler: Handler) -> UIResult<()> {
+    widget.on(what, handler).map_err(|e| {
+        Box::new(UIError::InvalidAction {
+            reason: format!(
+                "could not register a {:?} handler on a {}: {}",
+                what,
+                widget.type_name(),
+                e
+            ),
+        })
+    })
+}
+
+/// The widget a handler was called for `evt` on, as the `T` that the handler is registered on.
+fn handler_widget<'a, T: EmitEvent>(obj: &'a mut dyn EmitEvent, evt: &context::Event) -> UIResult<&'a mut T> {
+    if !obj.is::<T>() {
+        return Err(Box::new(UIError::WrongWidgetType {
+            expected: short_type_name::<T>(),
+            found:    obj.type_name(),
+            path:     format!("the widget of a {:?} handler", evt.what),
+        }));
+    }
+    Ok(obj.downcast_mut::<T>().unwrap()) // unwrap OK because of the check above
+}
+
 fn fullscreen_toggle_handler(
     obj: &mut dyn EmitEvent,
     uictx: &mut context::UIContext,
-    _evt: &context::Event,
+    evt: &context::Event,
 ) -> Result<context::Handled, Box<dyn Error>> {
     use context::Handled::*;
 
-    // NOTE: the checkbox installed its own handler to toggle the `enabled` field on click
+    // NOTE: the checkbox installed its own handler to change its `state` on click
     // We are running after it, since the handler registered first gets called first.
 
-    let checkbox = obj.downcast_ref::<Checkbox>().unwrap();
+    let checkbox = handler_widget::<Checkbox>(obj, evt)?;
+
+    uictx.config.modify(|settings| {
+        settings.video.fullscreen = checkbox.is_checked();
+    });
+    Ok(Handled)
+}
+
+fn theme_toggle_handler(
+    obj: &mut dyn EmitEvent,
+    uictx: &mut context::UIContext,
+    evt: &context::Event,
+) -> Result<context::Handled, Box<dyn Error>> {
+    use context::Handled::*;
+
+    // NOTE: as with fullscreen_toggle_handler, the checkbox has already changed its `state`
+    let checkbox = handler_widget::<Checkbox>(obj, evt)?;
 
     uictx.config.modify(|settings| {
-        settings.video.fullscreen = checkbox.enabled;
+        settings.video.theme = if checkbox.is_checked() {
+            LIGHT_THEME_NAME.to_owned()
+        } else {
+            DARK_THEME_NAME.to_owned()
+        };
     });
     Ok(Handled)
 }
@@ -331,17 +687,41 @@ fn options_click_handler(
     Ok(context::Handled::Handled)
 }
 
-fn start_or_resume_game_click_handler(
-    obj: &mut dyn EmitEvent,
+fn start_game_click_handler(
+    _obj: &mut dyn EmitEvent,
+    uictx: &mut context::UIContext,
+    _evt: &context::Event,
+) -> Result<context::Handled, Box<dyn Error>> {
+    uictx.push_screen(Screen::Run);
+    Ok(context::Handled::Handled)
+}
+
+fn resume_game_click_handler(
+    _obj: &mut dyn EmitEvent,
     uictx: &mut context::UIContext,
     _evt: &context::Event,
 ) -> Result<context::Handled, Box<dyn Error>> {
-    let btn = obj.downcast_mut::<Button>().unwrap(); // unwrap OK because this is only registered on a button
+    uictx.pop_screen()?; // back to Screen::Run
+    Ok(context::Handled::Handled)
+}
 
-    // TODO: don't do this anymore once we have an in-game menu that is above Screen::Run in screen_stack.
-    btn.label.set_text(uictx.ggez_context, "Resume Game".to_owned());
+fn quit_to_menu_click_handler(
+    _obj: &mut dyn EmitEvent,
+    uictx: &mut context::UIContext,
+    _evt: &context::Event,
+) -> Result<context::Handled, Box<dyn Error>> {
+    // The game is ended when the client sees the transition from Screen::InGameMenu to Screen::Menu
+    uictx.pop_screen()?; // Screen::InGameMenu
+    uictx.pop_screen()?; // Screen::Run
+    Ok(context::Handled::Handled)
+}
 
-    uictx.push_screen(Screen::Run);
+fn cancel_quit_click_handler(
+    _obj: &mut dyn EmitEvent,
+    uictx: &mut context::UIContext,
+    _evt: &context::Event,
+) -> Result<context::Handled, Box<dyn Error>> {
+    uictx.pop_screen()?; // back to Screen::Menu
     Ok(context::Handled::Handled)
 }
 
@@ -358,9 +738,9 @@ fn quit_click_handler(
 fn resolution_update_handler(
     obj: &mut dyn EmitEvent,
     uictx: &mut context::UIContext,
-    _evt: &context::Event,
+    evt: &context::Event,
 ) -> Result<context::Handled, Box<dyn Error>> {
-    let label = obj.downcast_mut::<Label>().unwrap(); // unwrap OK because it's always a Label
+    let label = handler_widget::<Label>(obj, evt)?;
     let (x, y) = (
         uictx.config.get().video.resolution_x,
         uictx.config.get().video.resolution_y,
@@ -376,9 +756,9 @@ fn resolution_update_handler(
 fn load_player_name(
     obj: &mut dyn EmitEvent,
     uictx: &mut context::UIContext,
-    _evt: &context::Event,
+    evt: &context::Event,
 ) -> Result<context::Handled, Box<dyn Error>> {
-    let textfield = obj.downcast_mut::<TextField>().unwrap(); // unwrap OK because it's always a textfield
+    let textfield = handler_widget::<TextField>(obj, evt)?;
     let ref player_name = uictx.config.get().user.name;
     textfield.set_text(player_name.clone());
     Ok(context::Handled::NotHandled)
@@ -387,9 +767,9 @@ fn load_player_name(
 fn save_player_name(
     obj: &mut dyn EmitEvent,
     uictx: &mut context::UIContext,
-    _evt: &context::Event,
+    evt: &context::Event,
 ) -> Result<context::Handled, Box<dyn Error>> {
-    let textfield = obj.downcast_mut::<TextField>().unwrap(); // unwrap OK because it's always a textfield
+    let textfield = handler_widget::<TextField>(obj, evt)?;
     if let Some(player_name) = textfield.text() {
         uictx.config.modify(|c| {
             c.user.name = player_name.clone();
@@ -398,6 +778,104 @@ fn save_player_name(
     Ok(context::Handled::NotHandled)
 }
 
+/// The text of the button for rebinding `action` on the Options screen.
+fn keybinding_text(config: &Config, action: KeyAction) -> String {
+    let bindings = KeyBindings::from_settings(&config.get().keybindings);
+    format!("{}: {}", action.description(), bindings.key(action))
+}
+
+/// Makes the button rebind `action` to the next key pressed after it's clicked. Clicking it again
+/// gives up, and so does Space, since Space presses the button; Space can still be bound in the
+/// config file.
+fn register_rebind_handlers(button: &mut Button, action: KeyAction) -> UIResult<()> {
+    use context::Handled::*;
+
+    let waiting = Arc::new(AtomicBool::new(false)); // for a key to be pressed?
+
+    let waiting_on_click = waiting.clone();
+    register(
+        button,
+        EventType::Click,
+        Box::new(
+            move |obj: &mut dyn EmitEvent,
+                  uictx: &mut context::UIContext,
+                  evt: &context::Event|
+                  -> Result<context::Handled, Box<dyn Error>> {
+                let button = handler_widget::<Button>(obj, evt)?;
+                let now_waiting = !waiting_on_click.load(Ordering::Relaxed);
+                waiting_on_click.store(now_waiting, Ordering::Relaxed);
+                if now_waiting {
+                    button.set_text(uictx.ggez_context, format!("{}: press a key", action.description()));
+                    // the button only sees key presses while it has the keyboard focus
+                    uictx.child_event(context::Event::new_child_request_focus());
+                } else {
+                    button.set_text(uictx.ggez_context, keybinding_text(uictx.config, action));
+                }
+                Ok(Handled)
+            },
+        ),
+    )?;
+
+    let waiting_on_key = waiting.clone();
+    register(
+        button,
+        EventType::KeyPress,
+        Box::new(
+            move |obj: &mut dyn EmitEvent,
+                  uictx: &mut context::UIContext,
+                  evt: &context::Event|
+                  -> Result<context::Handled, Box<dyn Error>> {
+                let key = match evt.key {
+                    Some(context::KeyCodeOrChar::KeyCode(key)) if waiting_on_key.load(Ordering::Relaxed) => key,
+                    _ => return Ok(NotHandled),
+                };
+                waiting_on_key.store(false, Ordering::Relaxed);
+                let button = handler_widget::<Button>(obj, evt)?;
+                let chord = KeyChord {
+                    key,
+                    ctrl: evt.ctrl_pressed,
+                    shift: evt.shift_pressed,
+                };
+                let mut bindings = KeyBindings::from_settings(&uictx.config.get().keybindings);
+                match bindings.rebind(action, chord) {
+                    // the game area picks the change up from the config
+                    Ok(()) => uictx.config.modify(|settings| {
+                        *action.configured_mut(&mut settings.keybindings) = chord.to_string();
+                    }),
+                    Err(e) => uictx.notify(
+                        Severity::Warn,
+                        format!("Can't bind {} to {}: {}", action.description(), chord, e),
+                    ),
+                }
+                button.set_text(uictx.ggez_context, keybinding_text(uictx.config, action));
+                Ok(Handled)
+            },
+        ),
+    )?;
+
+    // Give up on leaving the button or the screen
+    for &what in &[EventType::LoseFocus, EventType::Load] {
+        let waiting_elsewhere = waiting.clone();
+        register(
+            button,
+            what,
+            Box::new(
+                move |obj: &mut dyn EmitEvent,
+                      uictx: &mut context::UIContext,
+                      evt: &context::Event|
+                      -> Result<context::Handled, Box<dyn Error>> {
+                    if waiting_elsewhere.swap(false, Ordering::Relaxed) {
+                        let button = handler_widget::<Button>(obj, evt)?;
+                        button.set_text(uictx.ggez_context, keybinding_text(uictx.config, action));
+                    }
+                    Ok(NotHandled)
+                },
+            ),
+        )?;
+    }
+    Ok(())
+}
+
 add_widget_from_screen_id_mut!(Button);
 add_widget_from_screen_id_mut!(Checkbox);
 add_widget_from_screen_id_mut!(Label);
@@ -405,4 +883,153 @@ add_widget_from_screen_id_mut!(Pane);
 add_widget_from_screen_id_mut!(TextField);
 add_widget_from_screen_id_mut!(Chatbox);
 add_widget_from_screen_id_mut!(GameArea);
+add_widget_from_screen_id_mut!(Minimap);
+add_widget_from_screen_id_mut!(ServerList);
 add_widget_from_screen_id!(GameArea);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Goes back from the top of `screen_stack`, returning the screen changes as (screen, event
+    /// type) pairs.
+    fn back(screen_stack: &mut Vec<Screen>, game_in_progress: bool) -> Vec<(Screen, EventType)> {
+        let old_screen = *screen_stack.last().unwrap();
+        go_back(screen_stack, game_in_progress);
+        event_types(old_screen, *screen_stack.last().unwrap())
+    }
+
+    fn event_types(old_screen: Screen, new_screen: Screen) -> Vec<(Screen, EventType)> {
+        screen_change_events(old_screen, new_screen)
+            .into_iter()
+            .map(|(screen, event)| (screen, event.what))
+            .collect()
+    }
+
+    #[test]
+    fn test_push_then_back_fires_save_before_load() {
+        let mut screen_stack = vec![Screen::Menu];
+        screen_stack.push(Screen::Options);
+        assert_eq!(
+            event_types(Screen::Menu, Screen::Options),
+            vec![(Screen::Menu, EventType::Save), (Screen::Options, EventType::Load)]
+        );
+
+        assert_eq!(
+            back(&mut screen_stack, false),
+            vec![(Screen::Options, EventType::Save), (Screen::Menu, EventType::Load)]
+        );
+        assert_eq!(screen_stack, vec![Screen::Menu]);
+    }
+
+    #[test]
+    fn test_no_events_without_a_screen_change() {
+        assert!(screen_change_events(Screen::Run, Screen::Run).is_empty());
+    }
+
+    #[test]
+    fn test_back_pops_one_screen_at_a_time() {
+        let mut screen_stack = vec![Screen::Menu, Screen::Run, Screen::InGameMenu, Screen::Options];
+        back(&mut screen_stack, true);
+        assert_eq!(screen_stack, vec![Screen::Menu, Screen::Run, Screen::InGameMenu]);
+
+        let mut screen_stack = vec![Screen::Menu, Screen::ServerList];
+        back(&mut screen_stack, false);
+        assert_eq!(screen_stack, vec![Screen::Menu]);
+    }
+
+    #[test]
+    fn test_back_from_run_pauses_instead_of_popping() {
+        let mut screen_stack = vec![Screen::Menu, Screen::Run];
+        assert_eq!(
+            back(&mut screen_stack, true),
+            vec![(Screen::Run, EventType::Save), (Screen::InGameMenu, EventType::Load)]
+        );
+        assert_eq!(screen_stack, vec![Screen::Menu, Screen::Run, Screen::InGameMenu]);
+
+        // going back again resumes the same game
+        back(&mut screen_stack, true);
+        assert_eq!(screen_stack, vec![Screen::Menu, Screen::Run]);
+    }
+
+    #[test]
+    fn test_back_from_main_menu_asks_to_quit() {
+        let mut screen_stack = vec![Screen::Menu];
+        back(&mut screen_stack, false);
+        assert_eq!(screen_stack, vec![Screen::Menu, Screen::QuitConfirm]);
+
+        // and going back again cancels
+        assert_eq!(
+            back(&mut screen_stack, false),
+            vec![(Screen::QuitConfirm, EventType::Save), (Screen::Menu, EventType::Load)]
+        );
+        assert_eq!(screen_stack, vec![Screen::Menu]);
+    }
+
+    #[test]
+    fn test_back_from_main_menu_returns_to_game_in_progress() {
+        let mut screen_stack = vec![Screen::Menu];
+        back(&mut screen_stack, true);
+        assert_eq!(screen_stack, vec![Screen::Menu, Screen::Run]);
+    }
+
+    #[test]
+    fn test_back_does_nothing_during_intro() {
+        let mut screen_stack = vec![Screen::Intro];
+        assert!(back(&mut screen_stack, false).is_empty());
+        assert_eq!(screen_stack, vec![Screen::Intro]);
+    }
+
+    #[test]
+    fn test_chat_pane_stays_in_bottom_left_corner() {
+        let anchor = UILayout::chat_pane_anchor();
+        let size = *constants::DEFAULT_CHATBOX_RECT;
+        for &(w, h) in [(1200.0, 800.0), (800.0, 600.0), (1920.0, 1080.0)].iter() {
+            let rect = anchor.rect((w, h));
+            assert_eq!((rect.w, rect.h), (size.w, size.h));
+            assert_eq!(rect.x, constants::CHAT_PANE_MARGIN_X);
+            assert_eq!(rect.bottom(), h - constants::CHAT_PANE_MARGIN_Y);
+        }
+
+        // a window too short for it keeps it below the top margin rather than pushing it off screen
+        let rect = anchor.rect((constants::MIN_UI_WIDTH, 100.0));
+        assert_eq!(
+            (rect.x, rect.y),
+            (constants::CHAT_PANE_MARGIN_X, constants::CHAT_PANE_MARGIN_Y)
+        );
+        assert_eq!((rect.w, rect.h), (size.w, size.h));
+    }
+
+    #[test]
+    fn test_minimap_stays_in_bottom_right_corner() {
+        for &(w, h) in [(1200.0, 800.0), (800.0, 600.0), (1920.0, 1080.0)].iter() {
+            let rect = UILayout::minimap_rect((w, h));
+            assert!((rect.right() - (w - constants::MINIMAP_MARGIN)).abs() < 0.01);
+            assert!((rect.bottom() - (h - constants::MINIMAP_MARGIN)).abs() < 0.01);
+            assert!(rect.w > 0.0 && rect.h > 0.0);
+        }
+    }
+
+    #[test]
+    fn test_lookup_on_wrong_screen_or_with_wrong_type() {
+        let mut layering = Layering::new();
+        let pane = Box::new(Pane::new(Rect::new(0.0, 0.0, 100.0, 100.0)));
+        let pane_id = layering.add_widget(pane, InsertLocation::AtCurrentLayer).unwrap();
+        let mut layers = HashMap::new();
+        layers.insert(Screen::Menu, layering);
+        let mut ui = UILayout { layers };
+
+        assert!(Pane::widget_from_screen_and_id_mut(&mut ui, Screen::Menu, &pane_id).is_ok());
+        match *Pane::widget_from_screen_and_id_mut(&mut ui, Screen::Options, &pane_id).unwrap_err() {
+            UIError::ScreenNotFound { screen } => assert_eq!(screen, Screen::Options),
+            other => panic!("unexpected error: {}", other),
+        }
+        match *Button::widget_from_screen_and_id_mut(&mut ui, Screen::Menu, &pane_id).unwrap_err() {
+            UIError::WrongWidgetType { expected, found, path } => {
+                assert_eq!((expected, found), ("Button", "Pane"));
+                assert_eq!(path, "Pane");
+            }
+            other => panic!("unexpected error: {}", other),
+        }
+    }
+}
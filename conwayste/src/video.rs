@@ -55,7 +55,6 @@ impl VideoSettings {
     }
 
     /// Gets the current active resolution.
-    #[allow(unused)]
     pub fn get_resolution(&self) -> Resolution {
         self.resolution
     }
@@ -34,7 +34,7 @@ const PAN_DOWN: isize = 1;
 const ZOOM_IN: f32 = 1.0;
 const ZOOM_OUT: f32 = -1.0;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Cell {
     pub col: usize,
     pub row: usize,
@@ -44,6 +44,34 @@ impl Cell {
     pub fn new(col: usize, row: usize) -> Cell {
         Cell { col, row }
     }
+
+    /// Returns the cells on a straight line from `self` to `other`, excluding `self` but including
+    /// `other`. Consecutive cells are adjacent (possibly diagonally), so a fast mouse drag can be
+    /// drawn without gaps.
+    pub fn line_to(&self, other: Cell) -> Vec<Cell> {
+        // Bresenham's line algorithm
+        let (mut col, mut row) = (self.col as isize, self.row as isize);
+        let (end_col, end_row) = (other.col as isize, other.row as isize);
+        let d_col = (end_col - col).abs();
+        let d_row = -(end_row - row).abs();
+        let step_col = if col < end_col { 1 } else { -1 };
+        let step_row = if row < end_row { 1 } else { -1 };
+        let mut err = d_col + d_row;
+        let mut cells = vec![];
+        while (col, row) != (end_col, end_row) {
+            let err2 = 2 * err;
+            if err2 >= d_row {
+                err += d_row;
+                col += step_col;
+            }
+            if err2 <= d_col {
+                err += d_col;
+                row += step_row;
+            }
+            cells.push(Cell::new(col as usize, row as usize));
+        }
+        cells
+    }
 }
 
 #[derive(PartialEq)]
@@ -248,6 +276,11 @@ impl GridView {
         self.set_height(h);
     }
 
+    /// Set the area the grid takes up, in window coordinates (pixels).
+    pub fn set_rect(&mut self, rect: Rect) {
+        self.rect = rect;
+    }
+
     /// Given a point, find the nearest Cell (game coordinates) specified by a point in window
     /// coordinates.
     pub fn get_cell(&self, point: Point2<f32>) -> Option<Cell> {
@@ -259,6 +292,11 @@ impl GridView {
         self.cell_size
     }
 
+    /// Sets the cell size in pixels, limited to the supported zoom levels.
+    pub fn set_cell_size(&mut self, cell_size: f32) {
+        self.cell_size = cell_size.max(MIN_CELL_SIZE).min(MAX_CELL_SIZE);
+    }
+
     /// Gets a rectangle representing the grid in game coordinates.
     pub fn get_rect(&self) -> Rect {
         self.rect
@@ -340,6 +378,43 @@ impl GridView {
         return None;
     }
 
+    /// Moves the grid so that the cell at (`col`, `row`) is at the center of the GridView, as far
+    /// as the panning limits allow.
+    pub fn center_on_cell(&mut self, col: usize, row: usize) {
+        self.grid_origin = Point2 {
+            x: self.rect.w / 2.0 - (col as f32 + 0.5) * self.cell_size,
+            y: self.rect.h / 2.0 - (row as f32 + 0.5) * self.cell_size,
+        };
+        self.adjust_panning(true, NO_INPUT);
+    }
+
+    /// Returns the area of the universe currently on screen, in game coordinates (cells). It is
+    /// not clipped to the universe.
+    pub fn visible_cells(&self) -> Rect {
+        Rect::new(
+            (self.rect.x - self.grid_origin.x) / self.cell_size,
+            (self.rect.y - self.grid_origin.y) / self.cell_size,
+            self.rect.w / self.cell_size,
+            self.rect.h / self.cell_size,
+        )
+    }
+
+    /// Where to draw the lines between the cells on screen: the window x coordinates of the lines
+    /// between columns, and the window y coordinates of the lines between rows, in order. The
+    /// outer edges of the universe are included.
+    pub fn grid_line_coords(&self) -> (Vec<f32>, Vec<f32>) {
+        let cell_size = self.cell_size;
+        let coords = |origin: f32, start: f32, len: f32, count: usize| -> Vec<f32> {
+            let first = ((start - origin) / cell_size).ceil().max(0.0) as isize;
+            let last = (((start + len - origin) / cell_size).floor() as isize).min(count as isize);
+            (first..=last).map(|i| origin + i as f32 * cell_size).collect()
+        };
+        (
+            coords(self.grid_origin.x, self.rect.x, self.rect.w, self.columns),
+            coords(self.grid_origin.y, self.rect.y, self.rect.h, self.rows),
+        )
+    }
+
     /// Sets the width of the GridView in window coordinates (pixels).
     pub fn set_width(&mut self, width: f32) {
         self.rect.w = width;
@@ -362,6 +437,26 @@ mod test {
         GridView::new(cell_size, UNIVERSE_WIDTH_IN_CELLS, UNIVERSE_HEIGHT_IN_CELLS)
     }
 
+    #[test]
+    fn test_cell_line_to_same_cell_is_empty() {
+        assert_eq!(Cell::new(3, 4).line_to(Cell::new(3, 4)), vec![]);
+    }
+
+    #[test]
+    fn test_cell_line_to_is_gapless() {
+        let start = Cell::new(10, 2);
+        let end = Cell::new(3, 7);
+        let line = start.line_to(end);
+        assert_eq!(line.len(), 7); // one cell per column, since the line is wider than tall
+        assert_eq!(line.last(), Some(&end));
+        let mut prev = start;
+        for cell in line {
+            assert!((cell.col as isize - prev.col as isize).abs() <= 1);
+            assert!((cell.row as isize - prev.row as isize).abs() <= 1);
+            prev = cell;
+        }
+    }
+
     #[test]
     fn test_gridview_default_instantiation() {
         let gv = gen_default_gridview();
@@ -423,6 +518,16 @@ mod test {
         assert_eq!(gv.window_coords_from_game_unchecked(-1, -1), None);
     }
 
+    #[test]
+    fn test_gridview_center_on_cell() {
+        let mut gv = gen_default_gridview();
+        gv.center_on_cell(128, 64);
+        let visible = gv.visible_cells();
+
+        assert_eq!(visible.x + visible.w / 2.0, 128.5);
+        assert_eq!(visible.y + visible.h / 2.0, 64.5);
+    }
+
     #[test]
     fn test_gridview_window_coords_from_game_checked() {
         let gv = gen_default_gridview();
@@ -436,4 +541,26 @@ mod test {
         assert_eq!(gv.window_coords_from_game(outside1), None);
         assert_eq!(gv.window_coords_from_game(outside2), None);
     }
+
+    #[test]
+    fn test_gridview_grid_line_coords() {
+        let mut gv = gen_default_gridview();
+        let (xs, ys) = gv.grid_line_coords();
+        assert_eq!(xs.len(), 121); // every 10 pixels across 1200, both ends included
+        assert_eq!((xs[0], xs[120]), (0.0, 1200.0));
+        assert_eq!(ys.len(), 81);
+
+        // partway through a cell, the lines start at the next one
+        gv.set_origin(Point2 { x: -15.0, y: 0.0 });
+        let (xs, _) = gv.grid_line_coords();
+        assert_eq!(xs.first(), Some(&5.0));
+        assert_eq!(xs.last(), Some(&1195.0));
+
+        // lines stop at the edges of the universe, when all of it is on screen
+        gv.set_origin(Point2 { x: 0.0, y: 0.0 });
+        gv.set_cell_size(5.0);
+        let (_, ys) = gv.grid_line_coords();
+        assert_eq!(ys.len(), 129);
+        assert_eq!(ys.last(), Some(&640.0));
+    }
 }
@@ -417,7 +417,7 @@ impl ConwaysteTree {
 ///
 /// # Unsafe
 /// Usage of unsafe interacts with the tv buffer directly by peeking at bytes
-fn get_cwte_packet(tvb: *mut ws::tvbuff_t) -> Result<NetwaystePacket, std::io::Error> {
+fn get_cwte_packets(tvb: *mut ws::tvbuff_t) -> Result<Vec<NetwaystePacket>, std::io::Error> {
     let tvblen = tvb_reported_length(tvb) as usize;
 
     if tvblen > ETH_MTU_PAYLOAD_LIMIT {
@@ -436,9 +436,9 @@ fn get_cwte_packet(tvb: *mut ws::tvbuff_t) -> Result<NetwaystePacket, std::io::E
     let mut packet_bytes = BytesMut::from(packet_vec.as_slice());
 
     // set the info column
-    NetwaystePacketCodec.decode(&mut packet_bytes).and_then(|opt_packet| {
-        if let Some(packet) = opt_packet {
-            return Ok(packet);
+    NetwaystePacketCodec.decode(&mut packet_bytes).and_then(|opt_packets| {
+        if let Some(packets) = opt_packets {
+            return Ok(packets);
         } else {
             return Err(Error::new(ErrorKind::InvalidData, "CWTE Decode Error"));
         }
@@ -459,8 +459,16 @@ extern "C" fn dissect_conwayste(
     column_clear(pinfo, WSColumn::Info);
 
     /* decode packet into a Rust str */
-    if let Ok(packet) = get_cwte_packet(tvb) {
-        let info_str = CString::new(format!("{:?}", packet)).unwrap();
+    if let Ok(packets) = get_cwte_packets(tvb) {
+        // A datagram can hold a bundle of packets; list them all
+        let info_str = CString::new(
+            packets
+                .iter()
+                .map(|packet| format!("{:?}", packet))
+                .collect::<Vec<_>>()
+                .join("; "),
+        )
+        .unwrap();
         column_add_str(pinfo, WSColumn::Info, info_str);
     } else {
         column_set_str(pinfo, WSColumn::Info, &protocol_strings.invalid_packet);
@@ -278,6 +278,36 @@ impl BitGrid {
         self.0 = new.0;
         Ok(())
     }
+
+    /// Mirrors pattern with top-left corner at `(0,0)` in the grid and lower right corner at
+    /// `(width - 1, height - 1)` from left to right. The dimensions of the grid are unchanged.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if the width or height are out of range.
+    pub fn flip_horizontal(&mut self, width: usize, height: usize) -> Result<(), Box<dyn Error>> {
+        if width > self.width() || height > self.height() {
+            return Err(format!(
+                "Expected passed-in width={} and height={} to be less than grid width={} and height={}",
+                width,
+                height,
+                self.width(),
+                self.height()
+            )
+            .into());
+        }
+        let mut new = BitGrid::new(self.width_in_words(), self.height());
+        self.each_set(|col, row| {
+            if col < width && row < height {
+                let new_col = width - col - 1;
+                let new_shift = 63 - new_col % 64;
+                new.0[row][new_col / 64] |= 1 << new_shift;
+            }
+        });
+        // replace self with new
+        self.0 = new.0;
+        Ok(())
+    }
 }
 
 impl Index<usize> for BitGrid {
@@ -25,6 +25,7 @@ pub mod error;
 pub mod grids;
 pub mod rle;
 pub mod universe;
+pub mod visibility;
 
 pub use error::{ConwayError, ConwayResult};
 
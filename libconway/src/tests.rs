@@ -265,6 +265,232 @@ mod universe_tests {
         });
     }
 
+    #[test]
+    fn cell_at_reports_owner_wall_and_dead() {
+        let mut uni = generate_test_universe_with_default_params(UniType::Server);
+        let player1 = 1;
+        uni.toggle(16, 15, player1).unwrap();
+        uni.set_unchecked(10, 12, CellState::Wall);
+        uni.set_unchecked(40, 40, CellState::Alive(None));
+
+        assert_eq!(uni.cell_at(16, 15), CellState::Alive(Some(player1)));
+        assert_eq!(uni.cell_at(10, 12), CellState::Wall);
+        assert_eq!(uni.cell_at(40, 40), CellState::Alive(None));
+        assert_eq!(uni.cell_at(17, 15), CellState::Dead);
+
+        // outside the universe
+        assert_eq!(uni.cell_at(uni.width(), 0), CellState::Dead);
+        assert_eq!(uni.cell_at(0, usize::MAX), CellState::Dead);
+    }
+
+    #[test]
+    fn cells_in_rect_matches_each_non_dead() {
+        let mut uni = generate_test_universe_with_default_params(UniType::Server);
+        let player1 = 1;
+        uni.toggle(62, 15, player1).unwrap();
+        uni.toggle(63, 16, player1).unwrap();
+        uni.toggle(64, 16, player1).unwrap(); // crosses a word boundary
+        uni.set_unchecked(60, 17, CellState::Wall);
+
+        let cells = uni.cells_in_rect(60, 15, 6, 3);
+        assert_eq!(cells.len(), 6 * 3);
+        assert_eq!(cells[0], (60, 15, CellState::Dead));
+        assert_eq!(cells[5], (65, 15, CellState::Dead)); // row-major
+        assert_eq!(cells[6], (60, 16, CellState::Dead));
+
+        let non_dead: Vec<(usize, usize, CellState)> = cells
+            .into_iter()
+            .filter(|&(_, _, state)| state != CellState::Dead)
+            .collect();
+        let mut expected = vec![];
+        uni.each_non_dead(Region::new(60, 15, 6, 3), None, &mut |col, row, state| {
+            expected.push((col, row, state));
+        });
+        assert_eq!(non_dead, expected);
+    }
+
+    #[test]
+    fn cells_in_rect_is_clamped_to_the_universe() {
+        let uni = generate_test_universe_with_default_params(UniType::Server);
+        let (width, height) = (uni.width(), uni.height());
+
+        let cells = uni.cells_in_rect(width - 2, height - 1, 10, 10);
+        assert_eq!(
+            cells,
+            vec![
+                (width - 2, height - 1, CellState::Dead),
+                (width - 1, height - 1, CellState::Dead)
+            ]
+        );
+        assert!(uni.cells_in_rect(width, 0, 5, 5).is_empty());
+        assert!(uni.cells_in_rect(0, 0, 0, 5).is_empty());
+        assert_eq!(uni.cells_in_rect(0, 0, usize::MAX, usize::MAX).len(), width * height);
+    }
+
+    /// The state of the cell at (`col`, `row`) as `player_id` sees it.
+    fn seen_by(uni: &Universe, player_id: usize, col: usize, row: usize) -> CellState {
+        let mut seen = CellState::Dead;
+        uni.each_non_dead(
+            Region::new(col as isize, row as isize, 1, 1),
+            Some(player_id),
+            &mut |_, _, state| {
+                seen = state;
+            },
+        );
+        seen
+    }
+
+    #[test]
+    fn placing_a_cell_reveals_fog_around_it() {
+        let mut uni = generate_test_universe_with_default_params(UniType::Server);
+        let player1 = 1; // writable region ends at col 79; fog radius is 9
+        assert_eq!(seen_by(&uni, player1, 80, 40), CellState::Fog);
+
+        uni.toggle(79, 40, player1).unwrap();
+        assert_eq!(seen_by(&uni, player1, 80, 40), CellState::Dead);
+        assert_eq!(seen_by(&uni, player1, 87, 40), CellState::Dead);
+        assert_eq!(seen_by(&uni, player1, 88, 40), CellState::Fog); // just past the fog radius
+
+        uni.set(79, 60, CellState::Alive(Some(player1)), player1);
+        assert_eq!(seen_by(&uni, player1, 80, 60), CellState::Dead);
+
+        // the other player's fog is untouched
+        assert_eq!(seen_by(&uni, 0, 80, 40), CellState::Fog);
+    }
+
+    #[test]
+    fn walls_survive_generations() {
+        let mut uni = generate_test_universe_with_default_params(UniType::Server);
+        let player1 = 1;
+        uni.set(17, 15, CellState::Wall, player1);
+        // a blinker that would grow into the wall
+        for &row in &[14, 15, 16] {
+            uni.toggle(16, row, player1).unwrap();
+        }
+        for _ in 0..3 {
+            uni.next();
+            assert_eq!(uni.cell_at(17, 15), CellState::Wall);
+        }
+        uni.toggle(17, 15, player1).unwrap_err();
+        assert_eq!(uni.cell_at(17, 15), CellState::Wall);
+    }
+
+    #[test]
+    fn changed_cells_of_a_blinker() {
+        let mut uni = generate_test_universe_with_default_params(UniType::Server);
+        let player1 = 1;
+        for &col in &[62, 63, 64] {
+            uni.toggle(col, 15, player1).unwrap(); // crosses a word boundary
+        }
+        assert!(uni.changed_cells().is_empty());
+
+        uni.next();
+        let owned = CellState::Alive(Some(player1));
+        assert_eq!(
+            uni.changed_cells(),
+            &[
+                (63, 14, owned),
+                (62, 15, CellState::Dead),
+                (64, 15, CellState::Dead),
+                (63, 16, owned)
+            ][..]
+        );
+
+        uni.next();
+        assert_eq!(
+            uni.changed_cells(),
+            &[
+                (63, 14, CellState::Dead),
+                (62, 15, owned),
+                (64, 15, owned),
+                (63, 16, CellState::Dead)
+            ][..]
+        );
+    }
+
+    #[test]
+    fn changed_cells_is_empty_when_nothing_changes() {
+        let mut uni = generate_test_universe_with_default_params(UniType::Server);
+        let player1 = 1;
+        uni.next();
+        assert!(uni.changed_cells().is_empty());
+
+        // a block never changes
+        for &(col, row) in &[(16, 15), (17, 15), (16, 16), (17, 16)] {
+            uni.toggle(col, row, player1).unwrap();
+        }
+        uni.next();
+        assert!(uni.changed_cells().is_empty());
+        uni.next();
+        assert!(uni.changed_cells().is_empty());
+    }
+
+    /// A universe as big as a single word is wide, with a glider heading straight for the bottom
+    /// right corner.
+    fn glider_universe(edges: EdgeBehavior) -> Universe {
+        let player = PlayerBuilder::new(Region::new(0, 0, 64, 32));
+        let mut uni = BigBang::new()
+            .width(64)
+            .height(32)
+            .add_player(player)
+            .edge_behavior(edges)
+            .birth()
+            .unwrap();
+        for &(col, row) in &[(59, 26), (60, 27), (58, 28), (59, 28), (60, 28)] {
+            uni.toggle(col, row, 0).unwrap();
+        }
+        uni
+    }
+
+    fn live_cells(uni: &Universe) -> Vec<(usize, usize)> {
+        let mut cells = vec![];
+        uni.each_non_dead_full(None, &mut |col, row, _| cells.push((col, row)));
+        cells.sort();
+        cells
+    }
+
+    #[test]
+    fn glider_wraps_around_a_toroidal_universe() {
+        assert_eq!(BigBang::new().birth().unwrap().edge_behavior(), EdgeBehavior::Toroidal);
+        let mut uni = glider_universe(EdgeBehavior::Toroidal);
+        // a glider moves one cell diagonally every 4 generations
+        for _ in 0..40 {
+            uni.next();
+        }
+        assert_eq!(live_cells(&uni), vec![(4, 6), (5, 4), (5, 6), (6, 5), (6, 6)]);
+    }
+
+    #[test]
+    fn glider_stalls_in_a_bounded_universe() {
+        let mut uni = glider_universe(EdgeBehavior::Bounded);
+        for _ in 0..40 {
+            uni.next();
+        }
+        // it crashed into the corner and left a block behind
+        assert_eq!(live_cells(&uni), vec![(62, 30), (62, 31), (63, 30), (63, 31)]);
+        uni.next();
+        assert!(uni.changed_cells().is_empty());
+    }
+
+    #[test]
+    fn blinker_across_the_edge_needs_a_toroidal_universe() {
+        let mut uni = glider_universe(EdgeBehavior::Toroidal);
+        for &col in &[63, 0, 1] {
+            uni.toggle(col, 10, 0).unwrap();
+        }
+        uni.next();
+        assert_eq!(uni.cell_at(0, 9), CellState::Alive(Some(0)));
+        assert_eq!(uni.cell_at(0, 11), CellState::Alive(Some(0)));
+
+        // from the next generation on, the right edge no longer sees the left edge, so only the
+        // half of the blinker on the left comes back
+        uni.set_edge_behavior(EdgeBehavior::Bounded);
+        uni.next();
+        assert_eq!(uni.cell_at(63, 10), CellState::Dead);
+        assert_eq!(uni.cell_at(0, 10), CellState::Alive(Some(0)));
+        assert_eq!(uni.cell_at(1, 10), CellState::Alive(Some(0)));
+    }
+
     #[test]
     fn universe_apply_basic2() {
         // we do Server to Server so that the fog doesn't interfere with pattern comparison
@@ -1000,6 +1226,77 @@ mod grid_tests {
         assert_eq!(pat_r, Pattern("o$obo$2o!".to_owned()));
     }
 
+    #[test]
+    fn bit_grid_rotate_lwss_four_times_is_unchanged() {
+        let pat = Pattern("4bo$5bo$o4bo$b5o!".to_owned());
+        let mut grid = pat.to_new_bit_grid(6, 4).unwrap();
+        let (mut width, mut height) = (6, 4);
+        for _ in 0..4 {
+            grid.rotate(width, height, Rotation::CW).unwrap();
+            let (new_width, new_height) = (height, width);
+            width = new_width;
+            height = new_height;
+        }
+        assert_eq!(grid.to_pattern(None), pat);
+    }
+
+    #[test]
+    fn bit_grid_flip_glider() {
+        // SE glider becomes SW glider
+        let pat = Pattern("bo$2bo$3o!".to_owned());
+        let mut grid = pat.to_new_bit_grid(3, 3).unwrap();
+        grid.flip_horizontal(3, 3).unwrap();
+        let pat_f = grid.to_pattern(None);
+        assert_eq!(pat_f, Pattern("bo$o$3o!".to_owned()));
+    }
+
+    #[test]
+    fn bit_grid_flip_lwss() {
+        // E LWSS becomes W LWSS
+        let pat = Pattern("4bo$5bo$o4bo$b5o!".to_owned());
+        let mut grid = pat.to_new_bit_grid(6, 4).unwrap();
+        grid.flip_horizontal(6, 4).unwrap();
+        let pat_f = grid.to_pattern(None);
+        assert_eq!(pat_f, Pattern("bo$o$o4bo$5o!".to_owned()));
+    }
+
+    #[test]
+    fn bit_grid_flip_twice_is_unchanged() {
+        let pat = Pattern("b2o$2ob$bo!".to_owned());
+        let mut grid = pat.to_new_bit_grid(3, 3).unwrap();
+        grid.flip_horizontal(3, 3).unwrap();
+        assert_eq!(grid.to_pattern(None), Pattern("2o$b2o$bo!".to_owned()));
+        grid.flip_horizontal(3, 3).unwrap();
+        assert_eq!(grid, pat.to_new_bit_grid(3, 3).unwrap());
+    }
+
+    #[test]
+    fn bit_grid_flip_across_word_boundary() {
+        // 70 wide, so the pattern spans two words
+        let pat = Pattern("o68bo$2o!".to_owned());
+        let mut grid = pat.to_new_bit_grid(70, 2).unwrap();
+        grid.flip_horizontal(70, 2).unwrap();
+        let mut set = vec![];
+        grid.each_set(|c, r| set.push((c, r)));
+        assert_eq!(set, vec![(0, 0), (69, 0), (68, 1), (69, 1)]);
+    }
+
+    #[test]
+    fn bit_grid_flip_then_rotate_glider() {
+        let pat = Pattern("bo$2bo$3o!".to_owned());
+        let mut grid = pat.to_new_bit_grid(3, 3).unwrap();
+        grid.flip_horizontal(3, 3).unwrap();
+        grid.rotate(3, 3, Rotation::CW).unwrap();
+        assert_eq!(grid.to_pattern(None), Pattern("2o$obo$o!".to_owned()));
+    }
+
+    #[test]
+    fn bit_grid_flip_out_of_range() {
+        let pat = Pattern("bo$2bo$3o!".to_owned());
+        let mut grid = pat.to_new_bit_grid(3, 3).unwrap();
+        assert!(grid.flip_horizontal(3, 4).is_err());
+    }
+
     #[test]
     fn bit_grid_each_set1() {
         let pat = Pattern("bo$2bo$3o!".to_owned());
@@ -1271,3 +1568,198 @@ mod rle_tests {
         );
     }
 }
+
+mod visibility_tests {
+    use crate::universe::test_helpers::*;
+    use crate::universe::*;
+    use crate::visibility::Visibility;
+
+    const WIDTH: usize = 256;
+    const HEIGHT: usize = 128;
+
+    /// The state of a cell as `player_id` sees it, fog included.
+    fn seen_by(uni: &Universe, col: usize, row: usize, player_id: usize) -> CellState {
+        let mut seen = CellState::Dead;
+        uni.each_non_dead(
+            Region::new(col as isize, row as isize, 1, 1),
+            Some(player_id),
+            &mut |_, _, state| seen = state,
+        );
+        seen
+    }
+
+    #[test]
+    fn cells_reveal_a_circle_around_them() {
+        let mut sight = Visibility::new(WIDTH, HEIGHT, Region::new(0, 0, 10, 10), 4);
+        assert!(sight.is_visible(9, 9));
+        assert!(!sight.is_visible(100, 50));
+
+        let revealed = sight.update(vec![(100, 50, true)]);
+        assert_eq!(revealed.len(), 45); // a 7x7 square without its corners
+        assert!(revealed.iter().all(|&(_, _, visible)| visible));
+        assert!(sight.is_visible(100, 50));
+        assert!(sight.is_visible(103, 50));
+        assert!(sight.is_visible(102, 53));
+        assert!(!sight.is_visible(103, 53));
+        assert!(!sight.is_visible(104, 50));
+
+        // nothing changes if the player still owns the cell
+        assert!(sight.update(vec![(100, 50, true)]).is_empty());
+    }
+
+    #[test]
+    fn fog_comes_back_where_no_cells_are_left() {
+        let mut sight = Visibility::new(WIDTH, HEIGHT, Region::new(0, 0, 10, 10), 4);
+        sight.update(vec![(100, 50, true)]);
+        let revealed = sight.update(vec![(101, 50, true)]);
+        assert_eq!(revealed.len(), 7); // along the right side of the circle
+        assert!(revealed.iter().all(|&(_, _, visible)| visible));
+        assert!(revealed.contains(&(104, 50, true)));
+        assert!(revealed.contains(&(103, 47, true)));
+
+        let hidden = sight.update(vec![(100, 50, false)]);
+        assert_eq!(hidden.len(), 7); // along the left side
+        assert!(hidden.contains(&(97, 50, false)));
+        assert!(hidden.contains(&(98, 47, false)));
+        assert!(sight.is_visible(100, 50));
+
+        // one cell dies as its neighbor is born
+        let changed = sight.update(vec![(101, 50, false), (102, 50, true)]);
+        assert_eq!(changed.len(), 14);
+        assert_eq!(changed.iter().filter(|&&(_, _, visible)| visible).count(), 7);
+        assert!(!sight.is_visible(98, 50));
+        assert!(sight.is_visible(105, 50));
+    }
+
+    #[test]
+    fn writable_region_is_always_visible() {
+        let writable = Region::new(0, 0, 10, 10);
+        let mut sight = Visibility::new(WIDTH, HEIGHT, writable, 4);
+        let revealed = sight.update(vec![(9, 5, true)]);
+        assert!(!revealed.is_empty());
+        assert!(revealed.iter().all(|&(col, _, _)| col >= 10));
+        let hidden = sight.update(vec![(9, 5, false)]);
+        assert_eq!(hidden.len(), revealed.len());
+        assert!(sight.is_visible(6, 5));
+    }
+
+    #[test]
+    fn visibility_wraps_around_the_edges() {
+        let mut sight = Visibility::new(WIDTH, HEIGHT, Region::new(100, 50, 10, 10), 2);
+        sight.update(vec![(0, 0, true)]);
+        assert!(sight.is_visible(WIDTH - 1, 0));
+        assert!(sight.is_visible(0, HEIGHT - 1));
+        assert!(sight.is_visible(WIDTH - 1, HEIGHT - 1));
+        assert!(!sight.is_visible(WIDTH - 2, HEIGHT - 1)); // a radius of 2 doesn't reach this far
+    }
+
+    #[test]
+    fn incremental_updates_touch_far_fewer_cells_than_rebuilding() {
+        const PLAYERS: usize = 8;
+        const HEIGHT: usize = 120;
+        const FOG_RADIUS: usize = 6;
+        let strip = WIDTH / PLAYERS;
+        let regions: Vec<Region> = (0..PLAYERS)
+            .map(|player| Region::new((player * strip) as isize, 0, strip, HEIGHT))
+            .collect();
+        let mut uni = BigBang::new()
+            .width(WIDTH)
+            .height(HEIGHT)
+            .server_mode(true)
+            .fog_radius(FOG_RADIUS)
+            .add_players(regions.iter().map(|&region| PlayerBuilder::new(region)).collect())
+            .birth()
+            .unwrap();
+        let mut incremental: Vec<Visibility> = regions
+            .iter()
+            .map(|&region| Visibility::new(WIDTH, HEIGHT, region, FOG_RADIUS))
+            .collect();
+        let mut rebuilt = incremental.clone();
+
+        // three gliders for each player, heading down and to the right
+        let glider = [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)];
+        for player in 0..PLAYERS {
+            for &top in &[10, 50, 90] {
+                for &(col, row) in glider.iter() {
+                    let (col, row) = (player * strip + 10 + col, top + row);
+                    uni.toggle(col, row, player).unwrap();
+                    incremental[player].update(vec![(col, row, true)]);
+                }
+            }
+        }
+        let touched_at_start: Vec<usize> = incremental.iter().map(Visibility::cells_touched).collect();
+
+        for _ in 0..50 {
+            uni.next();
+            let changes = uni.changed_cells().to_vec();
+            let mut owned = vec![vec![]; PLAYERS];
+            uni.each_non_dead_full(None, &mut |col, row, state| {
+                if let CellState::Alive(Some(player)) = state {
+                    owned[player].push((col, row));
+                }
+            });
+            for player in 0..PLAYERS {
+                let player_changes = changes
+                    .iter()
+                    .map(|&(col, row, state)| (col, row, state == CellState::Alive(Some(player))));
+                incremental[player].update(player_changes);
+                rebuilt[player].rebuild(owned[player].drain(..));
+            }
+        }
+
+        for player in 0..PLAYERS {
+            for row in 0..HEIGHT {
+                for col in 0..WIDTH {
+                    assert_eq!(
+                        incremental[player].is_visible(col, row),
+                        rebuilt[player].is_visible(col, row),
+                        "player {} at ({}, {})",
+                        player,
+                        col,
+                        row
+                    );
+                }
+            }
+            let incremental_work = incremental[player].cells_touched() - touched_at_start[player];
+            assert!(
+                incremental_work * 10 < rebuilt[player].cells_touched(),
+                "updating touched {} cells, and rebuilding {}",
+                incremental_work,
+                rebuilt[player].cells_touched()
+            );
+        }
+    }
+
+    #[test]
+    fn client_sees_fog_where_cells_have_died() {
+        let mut s_uni = generate_test_universe_with_default_params(UniType::Server);
+        let mut c_uni = generate_test_universe_with_default_params(UniType::Client);
+        let player_id = 0;
+        let writable = Region::new(100, 70, 34, 16); // player 0's, in the test universe
+        let mut sight = Visibility::new(s_uni.width(), s_uni.height(), writable, 9);
+
+        // a lone cell at the edge of the writable region, which reveals some cells past it and dies
+        s_uni.next();
+        s_uni.toggle(133, 75, player_id).unwrap();
+        sight.update(vec![(133, 75, true)]);
+        let diff = s_uni.diff(0, 2, Some(player_id)).unwrap();
+        assert_eq!(c_uni.apply(&diff, Some(player_id)), Ok(Some(2)));
+        assert_eq!(seen_by(&c_uni, 137, 75, player_id), CellState::Dead);
+
+        s_uni.next();
+        let changes: Vec<_> = s_uni
+            .changed_cells()
+            .iter()
+            .map(|&(col, row, state)| (col, row, state == CellState::Alive(Some(player_id))))
+            .collect();
+        for (col, row, visible) in sight.update(changes) {
+            s_uni.set_fog(player_id, Region::new(col as isize, row as isize, 1, 1), !visible);
+        }
+
+        let diff = s_uni.diff(2, 3, Some(player_id)).unwrap();
+        assert_eq!(c_uni.apply(&diff, Some(player_id)), Ok(Some(3)));
+        assert_eq!(seen_by(&c_uni, 137, 75, player_id), CellState::Fog);
+        assert_eq!(seen_by(&c_uni, 133, 75, player_id), CellState::Dead); // still in the writable region
+        assert_eq!(seen_by(&s_uni, 137, 75, player_id), CellState::Fog);
+    }
+}
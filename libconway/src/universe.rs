@@ -17,6 +17,8 @@
 
 use std::{char, cmp, fmt};
 
+use serde::{Deserialize, Serialize};
+
 use crate::error::{ConwayError, ConwayResult};
 use crate::grids::{BitGrid, BitOperation, CharGrid};
 use crate::rle::{Pattern, NO_OP_CHAR};
@@ -30,6 +32,7 @@ pub struct BigBang {
     num_players:     usize,
     player_writable: Vec<Region>,
     fog_radius:      usize,
+    edges:           EdgeBehavior,
 }
 
 /// Player builder
@@ -69,6 +72,7 @@ impl BigBang {
             num_players:     0,
             player_writable: vec![],
             fog_radius:      6,
+            edges:           EdgeBehavior::Toroidal,
         }
     }
 
@@ -134,6 +138,12 @@ impl BigBang {
         self
     }
 
+    /// Chooses what cells on the edges of the universe see past them. Toroidal by default.
+    pub fn edge_behavior(mut self, edges: EdgeBehavior) -> BigBang {
+        self.edges = edges;
+        self
+    }
+
     /// "Gives life to the universe and the first moment of time."
     /// Creates a Universe which can then CGoL process generations.
     ///
@@ -152,11 +162,31 @@ impl BigBang {
             self.player_writable.clone(), // writable region (indexed by player_id)
             self.fog_radius,              // fog radius provides visiblity outside of writable regions
         );
-        universe
+        universe.map(|mut uni| {
+            uni.edges = self.edges;
+            uni
+        })
+    }
+}
+
+/// What a cell on the edge of the universe sees past the edge.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum EdgeBehavior {
+    /// The universe wraps around, so cells on opposite edges are neighbors. A glider that leaves on
+    /// one side comes back on the other.
+    Toroidal,
+    /// Nothing is past the edge, so cells there have dead neighbors off the board.
+    Bounded,
+}
+
+impl Default for EdgeBehavior {
+    fn default() -> Self {
+        EdgeBehavior::Toroidal
     }
 }
 
-/// Represents a wrapping universe in Conway's game of life.
+/// Represents a universe in Conway's game of life, which wraps around at the edges unless it is
+/// bounded.
 pub struct Universe {
     width:           usize,
     height:          usize,
@@ -168,6 +198,9 @@ pub struct Universe {
     player_writable: Vec<Region>,   // writable region (indexed by player_id)
     fog_radius:      usize,
     fog_circle:      BitGrid,
+    edges:           EdgeBehavior,
+    // (col, row, new state) of the cells changed by the last call to next()
+    changed_cells:   Vec<(usize, usize, CellState)>,
 }
 
 // Describes the state of the universe for a particular generation
@@ -195,7 +228,7 @@ struct PlayerGenState {
     fog:   BitGrid, // cells that are currently invisible to the player
 }
 
-#[derive(Eq, PartialEq, Ord, PartialOrd, Copy, Clone, Debug)]
+#[derive(Eq, PartialEq, Ord, PartialOrd, Copy, Clone, Debug, Serialize, Deserialize)]
 pub enum CellState {
     Dead,
     Alive(Option<usize>), // Some(player_number) or alive but not belonging to any player
@@ -726,7 +759,8 @@ impl Universe {
     /// * fog
     /// * if current cell is alive, player_id matches player_id argument
     ///
-    /// If any of the above checks fail, do nothing.
+    /// If any of the above checks fail, do nothing. Setting a cell alive for the player clears
+    /// their fog around it, as far as the fog radius.
     ///
     /// # Panics
     ///
@@ -768,12 +802,15 @@ impl Universe {
             }
         }
 
-        self.set_unchecked(col, row, new_state)
+        self.set_unchecked(col, row, new_state);
+        if let CellState::Alive(Some(_)) = new_state {
+            self.reveal_fog(col, row, player_id);
+        }
     }
 
     /// Switches any non-dead state to CellState::Dead.
     /// Switches CellState::Dead to CellState::Alive(opt_player_id) and clears fog for that player,
-    /// if any.
+    /// if any, as far as the fog radius around the cell.
     ///
     /// This operation works in three steps:
     ///  1. Toggle alive/dead cell in the current generation state cell grid
@@ -807,6 +844,7 @@ impl Universe {
                 let ref mut player = self.gen_states[self.state_index].player_states[player_id];
                 player.cells.modify_bits_in_word(row, word_col, mask, BitOperation::Set);
                 player.fog.modify_bits_in_word(row, word_col, mask, BitOperation::Clear);
+                self.reveal_fog(col, row, player_id);
             }
 
             CellState::Alive(opt_player_id)
@@ -849,6 +887,23 @@ impl Universe {
         Ok(self.toggle_unchecked(col, row, Some(player_id)))
     }
 
+    /// Clears `player_id`'s fog around (`col`, `row`) in the latest generation, the same as when
+    /// one of their cells is born during `next`.
+    fn reveal_fog(&mut self, col: usize, row: usize, player_id: usize) {
+        let shift = 63 - (col & (64 - 1));
+        let gen_state = &mut self.gen_states[self.state_index];
+        Universe::clear_fog(
+            &mut gen_state.player_states[player_id].fog,
+            &self.fog_circle,
+            self.fog_radius,
+            self.width,
+            self.height,
+            row,
+            col / 64,
+            1 << shift,
+        );
+    }
+
     /// Returns Ok(true) if col and row are in writable area for specified player.
     ///
     /// # Errors
@@ -873,6 +928,20 @@ impl Universe {
         Ok(!on_wall_cell)
     }
 
+    /// Covers every cell of `region` with `player_id`'s fog in the latest generation, or clears
+    /// their fog from it. Fog only clears by itself, as the player's cells are born; this is how it
+    /// comes back once the player can no longer see somewhere (see `visibility::Visibility`). The
+    /// player's own live cells must not be covered.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `player_id` is out of range or `region` is not entirely within the universe.
+    pub fn set_fog(&mut self, player_id: usize, region: Region, fogged: bool) {
+        let op = if fogged { BitOperation::Set } else { BitOperation::Clear };
+        let gen_state = &mut self.gen_states[self.state_index];
+        gen_state.player_states[player_id].fog.modify_region(region, op);
+    }
+
     /// Instantiate a new blank universe with the given width and height, in cells.
     /// The universe is at generation 1.
     ///
@@ -972,6 +1041,8 @@ impl Universe {
             // TODO: it's not very rusty to have uninitialized stuff (use Option<FogInfo> instead)
             fog_radius:      fog_radius,      // uninitialized
             fog_circle:      BitGrid(vec![]), // uninitialized
+            edges:           EdgeBehavior::Toroidal,
+            changed_cells:   vec![],
         };
         uni.generate_fog_circle_bitmap();
         Ok(uni)
@@ -1022,6 +1093,15 @@ impl Universe {
         }
     }
 
+    pub fn edge_behavior(&self) -> EdgeBehavior {
+        self.edges
+    }
+
+    /// Changes what cells on the edges see past them. Takes effect from the next generation on.
+    pub fn set_edge_behavior(&mut self, edges: EdgeBehavior) {
+        self.edges = edges;
+    }
+
     /// Get the latest generation number (1-based).
     pub fn latest_gen(&self) -> usize {
         assert!(self.generation != 0);
@@ -1097,7 +1177,8 @@ impl Universe {
         a | b | c | d | center | e | f | g | h
     }
 
-    /// Compute the next generation. Returns the new latest generation number.
+    /// Compute the next generation. Returns the new latest generation number. The cells that
+    /// changed are available from `changed_cells` until the next call.
     pub fn next(&mut self) -> usize {
         // get the buffers and buffers_next
         assert!(self.gen_states[self.state_index].gen_or_none.unwrap() == self.generation);
@@ -1113,6 +1194,8 @@ impl Universe {
             (&p1[history - 2], &mut p0[0])
         };
 
+        // (row_idx, col_idx, bits that changed) for every word with a changed cell
+        let mut changed_words = vec![];
         {
             let cells = &gen_state.cells;
             let wall = &gen_state.wall_cells;
@@ -1129,38 +1212,63 @@ impl Universe {
                 }
             }
 
+            // Past the edges of a bounded universe, every cell is dead and known
+            let bounded = self.edges == EdgeBehavior::Bounded;
+            let dead_row = vec![0u64; self.width_in_words];
+            let known_row = vec![u64::max_value(); self.width_in_words];
+            let last_col_idx = self.width_in_words - 1;
+
             for row_idx in 0..self.height {
                 let n_row_idx = (row_idx + self.height - 1) % self.height;
                 let s_row_idx = (row_idx + 1) % self.height;
-                let cells_row_n = &cells[n_row_idx];
+                let off_n = bounded && row_idx == 0;
+                let off_s = bounded && row_idx == self.height - 1;
+                let cells_row_n = if off_n { &dead_row } else { &cells[n_row_idx] };
                 let cells_row_c = &cells[row_idx];
-                let cells_row_s = &cells[s_row_idx];
+                let cells_row_s = if off_s { &dead_row } else { &cells[s_row_idx] };
                 let wall_row_c = &wall[row_idx];
-                let known_row_n = &known[n_row_idx];
+                let known_row_n = if off_n { &known_row } else { &known[n_row_idx] };
                 let known_row_c = &known[row_idx];
-                let known_row_s = &known[s_row_idx];
+                let known_row_s = if off_s { &known_row } else { &known[s_row_idx] };
 
                 // These will be shifted over at the beginning of the loop
                 let mut cells_nw;
                 let mut cells_w;
                 let mut cells_sw;
-                let mut cells_n = cells_row_n[self.width_in_words - 1];
-                let mut cells_cen = cells_row_c[self.width_in_words - 1];
-                let mut cells_s = cells_row_s[self.width_in_words - 1];
+                let mut cells_n = if bounded { 0 } else { cells_row_n[last_col_idx] };
+                let mut cells_cen = if bounded { 0 } else { cells_row_c[last_col_idx] };
+                let mut cells_s = if bounded { 0 } else { cells_row_s[last_col_idx] };
                 let mut cells_ne = cells_row_n[0];
                 let mut cells_e = cells_row_c[0];
                 let mut cells_se = cells_row_s[0];
                 let mut known_nw;
                 let mut known_w;
                 let mut known_sw;
-                let mut known_n = known_row_n[self.width_in_words - 1];
-                let mut known_cen = known_row_c[self.width_in_words - 1];
-                let mut known_s = known_row_s[self.width_in_words - 1];
+                let mut known_n = if bounded {
+                    u64::max_value()
+                } else {
+                    known_row_n[last_col_idx]
+                };
+                let mut known_cen = if bounded {
+                    u64::max_value()
+                } else {
+                    known_row_c[last_col_idx]
+                };
+                let mut known_s = if bounded {
+                    u64::max_value()
+                } else {
+                    known_row_s[last_col_idx]
+                };
                 let mut known_ne = known_row_n[0];
                 let mut known_e = known_row_c[0];
                 let mut known_se = known_row_s[0];
 
                 for col_idx in 0..self.width_in_words {
+                    let w_col_idx = (col_idx + self.width_in_words - 1) % self.width_in_words;
+                    let e_col_idx = (col_idx + 1) % self.width_in_words;
+                    let off_w = bounded && col_idx == 0;
+                    let off_e = bounded && col_idx == last_col_idx;
+
                     // shift over
                     cells_nw = cells_n;
                     cells_n = cells_ne;
@@ -1168,18 +1276,30 @@ impl Universe {
                     cells_cen = cells_e;
                     cells_sw = cells_s;
                     cells_s = cells_se;
-                    cells_ne = cells_row_n[(col_idx + 1) % self.width_in_words];
-                    cells_e = cells_row_c[(col_idx + 1) % self.width_in_words];
-                    cells_se = cells_row_s[(col_idx + 1) % self.width_in_words];
+                    cells_ne = if off_e { 0 } else { cells_row_n[e_col_idx] };
+                    cells_e = if off_e { 0 } else { cells_row_c[e_col_idx] };
+                    cells_se = if off_e { 0 } else { cells_row_s[e_col_idx] };
                     known_nw = known_n;
                     known_n = known_ne;
                     known_w = known_cen;
                     known_cen = known_e;
                     known_sw = known_s;
                     known_s = known_se;
-                    known_ne = known_row_n[(col_idx + 1) % self.width_in_words];
-                    known_e = known_row_c[(col_idx + 1) % self.width_in_words];
-                    known_se = known_row_s[(col_idx + 1) % self.width_in_words];
+                    known_ne = if off_e {
+                        u64::max_value()
+                    } else {
+                        known_row_n[e_col_idx]
+                    };
+                    known_e = if off_e {
+                        u64::max_value()
+                    } else {
+                        known_row_c[e_col_idx]
+                    };
+                    known_se = if off_e {
+                        u64::max_value()
+                    } else {
+                        known_row_s[e_col_idx]
+                    };
 
                     // apply BitGrid changes
                     let mut cells_cen_next = Universe::next_single_gen(
@@ -1198,6 +1318,7 @@ impl Universe {
                     // assign to the u64 element in the next generation
                     cells_next[row_idx][col_idx] = cells_cen_next;
 
+                    let mut changed = (cells_cen_next ^ cells_cen) | (known_next[row_idx][col_idx] ^ known_cen);
                     let mut in_multiple: u64 = 0;
                     let mut seen_before: u64 = 0;
                     for player_id in 0..self.num_players {
@@ -1211,19 +1332,24 @@ impl Universe {
                         // Any unowned cells are influenced by their neighbors, and if players,
                         // can be acquired by the player, just as long as no two players are
                         // fighting over those cells
+                        let player_cells = &gen_state.player_states[player_id].cells;
+                        let word = |row_idx: usize, col_idx: usize, off_board: bool| {
+                            if off_board {
+                                0
+                            } else {
+                                player_cells[row_idx][col_idx]
+                            }
+                        };
                         let player_cell_next = Universe::contagious_one(
-                            gen_state.player_states[player_id].cells[n_row_idx]
-                                [(col_idx + self.width_in_words - 1) % self.width_in_words],
-                            gen_state.player_states[player_id].cells[n_row_idx][col_idx],
-                            gen_state.player_states[player_id].cells[n_row_idx][(col_idx + 1) % self.width_in_words],
-                            gen_state.player_states[player_id].cells[row_idx]
-                                [(col_idx + self.width_in_words - 1) % self.width_in_words],
-                            gen_state.player_states[player_id].cells[row_idx][col_idx],
-                            gen_state.player_states[player_id].cells[row_idx][(col_idx + 1) % self.width_in_words],
-                            gen_state.player_states[player_id].cells[s_row_idx]
-                                [(col_idx + self.width_in_words - 1) % self.width_in_words],
-                            gen_state.player_states[player_id].cells[s_row_idx][col_idx],
-                            gen_state.player_states[player_id].cells[s_row_idx][(col_idx + 1) % self.width_in_words],
+                            word(n_row_idx, w_col_idx, off_n || off_w),
+                            word(n_row_idx, col_idx, off_n),
+                            word(n_row_idx, e_col_idx, off_n || off_e),
+                            word(row_idx, w_col_idx, off_w),
+                            word(row_idx, col_idx, false),
+                            word(row_idx, e_col_idx, off_e),
+                            word(s_row_idx, w_col_idx, off_s || off_w),
+                            word(s_row_idx, col_idx, off_s),
+                            word(s_row_idx, e_col_idx, off_s || off_e),
                         ) & cells_cen_next;
                         in_multiple |= player_cell_next & seen_before;
                         seen_before |= player_cell_next;
@@ -1234,6 +1360,7 @@ impl Universe {
                         let mut cell_next = gen_state_next.player_states[player_id].cells[row_idx][col_idx];
                         cell_next &= !in_multiple; // if a cell would have belonged to multiple players, it belongs to none
                         gen_state_next.player_states[player_id].cells[row_idx][col_idx] = cell_next;
+                        changed |= cell_next ^ cell_cur; // a cell can change owners without dying
 
                         // clear fog for all cells that turned on in this generation
                         Universe::clear_fog(
@@ -1247,6 +1374,9 @@ impl Universe {
                             cell_next & !cell_cur,
                         );
                     }
+                    if changed != 0 {
+                        changed_words.push((row_idx, col_idx, changed));
+                    }
                 }
 
                 // copy wall to wall_next
@@ -1258,9 +1388,28 @@ impl Universe {
         self.generation += 1;
         self.state_index = next_state_index;
         gen_state_next.gen_or_none = Some(self.generation);
+
+        self.changed_cells.clear();
+        for (row, col_idx, changed) in changed_words {
+            for shift in (0..64).rev() {
+                if (changed >> shift) & 1 == 1 {
+                    let col = col_idx * 64 + (63 - shift);
+                    let state = self.cell_at(col, row);
+                    self.changed_cells.push((col, row, state));
+                }
+            }
+        }
         self.generation
     }
 
+    /// The cells that the last call to `next` changed, as (col, row, state in the latest
+    /// generation), in row-major order. A cell changes if it is born, dies, changes owner, or
+    /// becomes unknown. Empty if nothing changed or `next` hasn't been called yet. Cells changed
+    /// any other way, like with `set`, are not included.
+    pub fn changed_cells(&self) -> &[(usize, usize, CellState)] {
+        &self.changed_cells
+    }
+
     /// Clears the fog for the specified bits in the 64-bit word at `center_row_idx` and
     /// `center_col_idx` using the fog circle (see `generate_fog_circle_bitmap` documentation for
     /// more on this).
@@ -1513,6 +1662,75 @@ impl Universe {
         self.each_non_dead(self.region(), visibility, callback);
     }
 
+    /// Gets the state of the cell at (`col`, `row`) in the current generation, with no fog applied.
+    /// A live cell's state includes the player who owns it, if any. Cells outside the universe are
+    /// `CellState::Dead`.
+    pub fn cell_at(&self, col: usize, row: usize) -> CellState {
+        if col >= self.width || row >= self.height {
+            return CellState::Dead;
+        }
+        let gen_state = &self.gen_states[self.state_index];
+        let word_col = col / 64;
+        let shift = 63 - (col & (64 - 1)); // translate literal col (ex: 134) to bit index in word_col
+        let bit = |grid: &BitGrid| (grid[row][word_col] >> shift) & 1 == 1;
+
+        if bit(&gen_state.wall_cells) {
+            CellState::Wall
+        } else if bit(&gen_state.cells) {
+            let owner = gen_state
+                .player_states
+                .iter()
+                .position(|player_state| bit(&player_state.cells));
+            CellState::Alive(owner)
+        } else if !bit(&gen_state.known) {
+            CellState::Fog
+        } else {
+            CellState::Dead
+        }
+    }
+
+    /// Gets the state of every cell, dead ones included, in the rectangle `w` cells wide and `h`
+    /// cells high with its top left corner at (`col`, `row`), in the current generation. The
+    /// rectangle is clipped to the universe, so it may be smaller than asked for, or empty. Cells
+    /// are in row-major order, as (col, row, state).
+    pub fn cells_in_rect(&self, col: usize, row: usize, w: usize, h: usize) -> Vec<(usize, usize, CellState)> {
+        let right = col.saturating_add(w).min(self.width);
+        let bottom = row.saturating_add(h).min(self.height);
+        let mut cells = Vec::with_capacity(right.saturating_sub(col) * bottom.saturating_sub(row));
+        for r in row..bottom {
+            for c in col..right {
+                cells.push((c, r, self.cell_at(c, r)));
+            }
+        }
+        cells
+    }
+
+    /// Hashes the live cells (including which player owns each) and walls of the current
+    /// generation, as seen by the optional `visibility` player. Fog is left out, so a client that
+    /// has applied every diff for a generation gets the same checksum as the server does for that
+    /// player. Uses FNV-1a, since the result must not change between builds.
+    pub fn checksum(&self, visibility: Option<usize>) -> u64 {
+        const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+        const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+        let mut hash = FNV_OFFSET_BASIS;
+        let mut feed = |bytes: &[u8]| {
+            for byte in bytes {
+                hash ^= *byte as u64;
+                hash = hash.wrapping_mul(FNV_PRIME);
+            }
+        };
+        self.each_non_dead_full(visibility, &mut |col, row, state| match state {
+            CellState::Alive(_) | CellState::Wall => {
+                feed(&(col as u64).to_le_bytes());
+                feed(&(row as u64).to_le_bytes());
+                feed(&[state.to_char() as u8]);
+            }
+            CellState::Dead | CellState::Fog => {}
+        });
+        hash
+    }
+
     /// Get a Region of the same size as the universe.
     pub fn region(&self) -> Region {
         Region::new(0, 0, self.width, self.height)
@@ -2357,6 +2575,49 @@ mod universe_tests {
             c_uni.gen_states[c_idx].player_states[0].cells
         );
     }
+
+    #[test]
+    fn universe_checksum_matches_after_apply() {
+        let mut s_uni = generate_test_universe_with_default_params(UniType::Server);
+        let mut c_uni = generate_test_universe_with_default_params(UniType::Client);
+        let player1 = 1;
+        // glider
+        s_uni.toggle(16, 15, player1).unwrap();
+        s_uni.toggle(17, 16, player1).unwrap();
+        s_uni.toggle(15, 17, player1).unwrap();
+        s_uni.toggle(16, 17, player1).unwrap();
+        s_uni.toggle(17, 17, player1).unwrap();
+        // r-pentomino
+        s_uni.toggle(89 + 16, 68 + 15, 0).unwrap();
+        s_uni.toggle(89 + 17, 68 + 15, 0).unwrap();
+        s_uni.toggle(89 + 15, 68 + 16, 0).unwrap();
+        s_uni.toggle(89 + 16, 68 + 16, 0).unwrap();
+        s_uni.toggle(89 + 16, 68 + 17, 0).unwrap();
+        for _ in 0..4 {
+            s_uni.next();
+        }
+        let player_id = 0;
+        let diff = s_uni.diff(0, 5, Some(player_id)).unwrap();
+        assert_eq!(c_uni.apply(&diff, Some(player_id)), Ok(Some(5)));
+        assert_eq!(c_uni.checksum(Some(player_id)), s_uni.checksum(Some(player_id)));
+        // player 0 doesn't know about player 1's cells
+        assert!(s_uni.checksum(None) != s_uni.checksum(Some(player_id)));
+    }
+
+    #[test]
+    fn universe_checksum_changes_with_cells() {
+        let mut uni = generate_test_universe_with_default_params(UniType::Server);
+        let empty_checksum = uni.checksum(None);
+        uni.toggle(16, 15, 1).unwrap();
+        let one_cell_checksum = uni.checksum(None);
+        assert!(one_cell_checksum != empty_checksum);
+
+        // same position, different owner
+        uni.toggle(16, 15, 1).unwrap();
+        assert_eq!(uni.checksum(None), empty_checksum);
+        uni.set_unchecked(16, 15, CellState::Alive(None));
+        assert!(uni.checksum(None) != one_cell_checksum);
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,152 @@
+/*  Copyright 2021 the Conwayste Developers.
+ *
+ *  This file is part of libconway.
+ *
+ *  libconway is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  libconway is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with libconway.  If not, see <http://www.gnu.org/licenses/>. */
+
+//! Which cells a player can see, for fog of war that follows the player's cells around.
+//!
+//! A player always sees their writable region, along with every cell within the fog radius of
+//! one of their live cells. The fog in a `Universe` only ever clears as the player's cells are
+//! born, so once their cells have died somewhere, a `Visibility` says where the fog should come
+//! back (see `Universe::set_fog`). It only looks at the cells around each change, so that keeping
+//! track of every player is cheap enough to do every generation.
+
+use std::collections::BTreeMap;
+
+use crate::universe::Region;
+
+/// Keeps track of which cells one player can see.
+#[derive(Debug, Clone)]
+pub struct Visibility {
+    width:    usize,
+    height:   usize,
+    writable: Region,              // always visible
+    reach:    Vec<(isize, isize)>, // offsets of the cells a live cell reveals, itself included
+    owned:    Vec<bool>,           // whether the player owns each cell; indexed by row * width + col
+    coverage: Vec<u32>,            // how many of the player's cells each cell is within reach of
+    touched:  usize,               // cells whose coverage has been updated, to measure the work done
+}
+
+impl Visibility {
+    /// Starts keeping track for a player with no live cells, in a universe `width` by `height`
+    /// cells. Each of the player's cells reveals the same circle around it that `Universe` clears
+    /// fog in when one is born, for the same `fog_radius`, wrapping around the edges.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `fog_radius` is zero.
+    pub fn new(width: usize, height: usize, writable: Region, fog_radius: usize) -> Self {
+        assert!(fog_radius > 0, "fog radius must be positive");
+        let max_offset = fog_radius as isize - 1;
+        let mut reach = vec![];
+        for row_offset in -max_offset..=max_offset {
+            for col_offset in -max_offset..=max_offset {
+                if ((row_offset * row_offset + col_offset * col_offset) as usize) < fog_radius * fog_radius {
+                    reach.push((col_offset, row_offset));
+                }
+            }
+        }
+        Visibility {
+            width,
+            height,
+            writable,
+            reach,
+            owned: vec![false; width * height],
+            coverage: vec![0; width * height],
+            touched: 0,
+        }
+    }
+
+    /// Whether the player can see the cell at (`col`, `row`).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `col` or `row` are out of range.
+    pub fn is_visible(&self, col: usize, row: usize) -> bool {
+        self.writable.contains(col as isize, row as isize) || self.coverage[row * self.width + col] > 0
+    }
+
+    /// Takes note of the cells that just changed, as (col, row, whether the player now owns it),
+    /// and updates the cells around them. Cells whose owner didn't change as far as the player is
+    /// concerned may be included, and are skipped. Returns the cells that the player can now see or
+    /// can no longer see, as (col, row, visible), in row-major order.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a cell is out of range.
+    pub fn update<I: IntoIterator<Item = (usize, usize, bool)>>(&mut self, changes: I) -> Vec<(usize, usize, bool)> {
+        let mut crossed = BTreeMap::new(); // (row, col) -> whether it was visible beforehand
+        for (col, row, owned) in changes {
+            let index = row * self.width + col;
+            if self.owned[index] == owned {
+                continue;
+            }
+            self.owned[index] = owned;
+            for &(col_offset, row_offset) in self.reach.iter() {
+                let near_col = wrap(col, col_offset, self.width);
+                let near_row = wrap(row, row_offset, self.height);
+                let near_index = near_row * self.width + near_col;
+                let was_covered = self.coverage[near_index] > 0;
+                if owned {
+                    self.coverage[near_index] += 1;
+                } else {
+                    self.coverage[near_index] -= 1;
+                }
+                self.touched += 1;
+                if was_covered != (self.coverage[near_index] > 0) {
+                    let was_visible = was_covered || self.writable.contains(near_col as isize, near_row as isize);
+                    crossed.entry((near_row, near_col)).or_insert(was_visible);
+                }
+            }
+        }
+        crossed
+            .into_iter()
+            .filter_map(|((row, col), was_visible)| {
+                let visible = self.is_visible(col, row);
+                if visible != was_visible {
+                    Some((col, row, visible))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Works out from scratch which cells the player can see, given every cell they own. This goes
+    /// over the whole universe, so `update` is much quicker when only some cells have changed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a cell is out of range.
+    pub fn rebuild<I: IntoIterator<Item = (usize, usize)>>(&mut self, owned: I) {
+        for index in 0..self.width * self.height {
+            self.owned[index] = false;
+            self.coverage[index] = 0;
+        }
+        self.touched += self.width * self.height;
+        self.update(owned.into_iter().map(|(col, row)| (col, row, true)));
+    }
+
+    /// How many times a cell has been looked at so far, across every `update` and `rebuild`. This
+    /// is a measure of how much work has been done.
+    pub fn cells_touched(&self) -> usize {
+        self.touched
+    }
+}
+
+/// Adds `offset` to `coord`, wrapping around within `0..len`.
+fn wrap(coord: usize, offset: isize, len: usize) -> usize {
+    (coord as isize + offset).rem_euclid(len as isize) as usize
+}
@@ -26,6 +26,7 @@ extern crate futures;
 extern crate netwayste;
 extern crate tokio;
 
+use std::env;
 use std::io::{self, Read, Write};
 use std::str::FromStr;
 use std::thread;
@@ -95,16 +96,23 @@ fn read_stdin(channel_to_netwayste: mpsc::UnboundedSender<NetwaysteEvent>) {
 
 fn print_help() {
     info!("");
-    info!("/help                  - print this text");
-    info!("/status                - get the server's status");
-    info!("/connect <player_name> - connect to server");
-    info!("/disconnect            - disconnect from server");
-    info!("/list                  - list rooms when in lobby, or players when in game");
-    info!("/new <room_name>       - create a new room (when not in game)");
-    info!("/join <room_name>      - join a room (when not in game)");
-    info!("/leave                 - leave a room (when in game)");
-    info!("/part                  - alias of leave");
-    info!("/quit                  - exit the program");
+    info!("/help                        - print this text");
+    info!("/status                      - get the server's status");
+    info!("/connect <player_name>       - connect to server");
+    info!("/disconnect                  - disconnect from server");
+    info!("/list                        - list rooms when in lobby, or players when in game");
+    info!("/new <room_name> [password]  - create a new room (when not in game)");
+    info!("/join <room_name> [password] - join a room (when not in game)");
+    info!("/leave                       - leave a room (when in game)");
+    info!("/part                        - alias of leave");
+    info!("/invite <player_name>        - invite a player to the room you are in");
+    info!("/accept <token>              - join the room you were invited to");
+    info!("/decline <token>             - turn down an invitation");
+    info!("/team <number>               - join a team, numbered from 0, before the game starts");
+    info!("/ready                       - say you are ready for the next game in your room");
+    info!("/unready                     - say you are not ready after all");
+    info!("/start                       - start the game in the room you created, once all are ready");
+    info!("/quit                        - exit the program");
     info!("...or just type text to chat!");
 }
 
@@ -141,17 +149,17 @@ fn build_command_request_action(cmd: String, args: Vec<String>) -> NetwaysteEven
             }
         }
         "new" | "n" => {
-            if args.len() == 1 {
-                new_event = NetwaysteEvent::NewRoom(args[0].clone());
+            if args.len() == 1 || args.len() == 2 {
+                new_event = NetwaysteEvent::NewRoom(args[0].clone(), args.get(1).cloned(), None, None);
             } else {
-                debug!("Command failed: Expected name of room (no spaces allowed)");
+                debug!("Command failed: Expected name of room and optional password (no spaces allowed)");
             }
         }
         "join" | "j" => {
-            if args.len() == 1 {
-                new_event = NetwaysteEvent::JoinRoom(args[0].clone());
+            if args.len() == 1 || args.len() == 2 {
+                new_event = NetwaysteEvent::JoinRoom(args[0].clone(), args.get(1).cloned());
             } else {
-                debug!("Command failed: Expected room name only (no spaces allowed)");
+                debug!("Command failed: Expected room name and optional password (no spaces allowed)");
             }
         }
         "part" | "leave" => {
@@ -161,6 +169,45 @@ fn build_command_request_action(cmd: String, args: Vec<String>) -> NetwaysteEven
                 debug!("Command failed: Expected no arguments to leave");
             }
         }
+        "invite" => {
+            if args.len() == 1 {
+                new_event = NetwaysteEvent::InvitePlayer(args[0].clone());
+            } else {
+                debug!("Command failed: Expected player name only (no spaces allowed)");
+            }
+        }
+        "accept" => {
+            if args.len() == 1 {
+                new_event = NetwaysteEvent::AcceptInvite(args[0].clone());
+            } else {
+                debug!("Command failed: Expected the token from the invitation");
+            }
+        }
+        "decline" => {
+            if args.len() == 1 {
+                new_event = NetwaysteEvent::DeclineInvite(args[0].clone());
+            } else {
+                debug!("Command failed: Expected the token from the invitation");
+            }
+        }
+        "team" => match args.get(0).map(|arg| arg.parse::<u8>()) {
+            Some(Ok(team)) if args.len() == 1 => new_event = NetwaysteEvent::SetTeam(team),
+            _ => debug!("Command failed: Expected a team number only"),
+        },
+        "ready" | "unready" => {
+            if args.len() == 0 {
+                new_event = NetwaysteEvent::SetReady(cmd == "ready");
+            } else {
+                debug!("Command failed: Expected no arguments to {}", cmd);
+            }
+        }
+        "start" => {
+            if args.len() == 0 {
+                new_event = NetwaysteEvent::StartGame;
+            } else {
+                debug!("Command failed: Expected no arguments to start");
+            }
+        }
         "quit" | "q" | "exit" => {
             trace!("Peace out!");
             new_event = NetwaysteEvent::Disconnect;
@@ -194,7 +241,6 @@ async fn main() {
         })
         .filter(None, LevelFilter::Trace)
         .filter(Some("futures"), LevelFilter::Off)
-        .filter(Some("tokio_core"), LevelFilter::Off)
         .filter(Some("tokio_reactor"), LevelFilter::Off)
         .filter(Some("conway"), LevelFilter::Off)
         .filter(Some("ggez"), LevelFilter::Off)
@@ -205,8 +251,9 @@ async fn main() {
     let (ggez_client_request, nw_client_request) = mpsc::unbounded::<NetwaysteEvent>();
     let (nw_server_response, mut ggez_server_response) = mpsc::channel::<NetwaysteEvent>(5);
 
+    let server_str = env::args().nth(1).unwrap_or("localhost".to_owned());
     tokio::spawn(async {
-        match ClientNetState::start_network(nw_server_response, nw_client_request).await {
+        match ClientNetState::start_network(server_str, nw_server_response, nw_client_request).await {
             Ok(()) => {}
             Err(e) => error!("Error during ClientNetState: {}", e),
         }
@@ -0,0 +1,254 @@
+/*
+ * Copyright (C) 2021 The Conwayste Developers
+ *
+ * This program is free software: you can redistribute it and/or modify it
+ * under the terms of the GNU General Public License as published by the Free
+ * Software Foundation, either version 3 of the License, or (at your option)
+ * any later version.
+ *
+ * This program is distributed in the hope that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of  MERCHANTABILITY or
+ * FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for
+ * more details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Addresses and player names the server refuses to deal with. Bans can be kept in a file, one
+//! per line: an IP address, or otherwise a player name. Lines starting with `#` are ignored.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum BanTarget {
+    Addr(IpAddr),
+    Name(String),
+}
+
+impl BanTarget {
+    /// Parses `target` as an IP address, or otherwise as a player name. `None` if it is blank.
+    pub fn parse(target: &str) -> Option<BanTarget> {
+        let target = target.trim();
+        if target.is_empty() {
+            return None;
+        }
+        Some(match target.parse::<IpAddr>() {
+            Ok(ip) => BanTarget::Addr(canonical_ip(ip)),
+            Err(_) => BanTarget::Name(target.to_owned()),
+        })
+    }
+
+    /// Whether this target covers a player with this address and name.
+    pub fn matches(&self, ip: IpAddr, name: &str) -> bool {
+        match self {
+            BanTarget::Addr(banned_ip) => *banned_ip == canonical_ip(ip),
+            BanTarget::Name(banned_name) => banned_name == name,
+        }
+    }
+}
+
+impl fmt::Display for BanTarget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BanTarget::Addr(ip) => write!(f, "{}", ip),
+            BanTarget::Name(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+/// IPv4 peers of a dual-stack socket show up as IPv4-mapped IPv6 addresses; this turns those back
+/// into plain IPv4 addresses, so that a ban applies no matter how the peer reached us.
+fn canonical_ip(ip: IpAddr) -> IpAddr {
+    if let IpAddr::V6(v6) = ip {
+        if let [0, 0, 0, 0, 0, 0xffff, hi, lo] = v6.segments() {
+            return IpAddr::V4(Ipv4Addr::new((hi >> 8) as u8, hi as u8, (lo >> 8) as u8, lo as u8));
+        }
+    }
+    ip
+}
+
+/// What a banned address gets back when it sends something.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BanResponse {
+    Silent,     // nothing, ever
+    NotifyOnce, // one Unauthorized response, then nothing
+}
+
+pub struct BanList {
+    addrs:        HashSet<IpAddr>,
+    names:        HashSet<String>,
+    notified:     HashSet<IpAddr>, // banned addresses that have already had their one response
+    pub response: BanResponse,
+    path:         Option<PathBuf>, // where bans are saved, if anywhere
+}
+
+impl BanList {
+    pub fn new() -> Self {
+        BanList {
+            addrs:    HashSet::new(),
+            names:    HashSet::new(),
+            notified: HashSet::new(),
+            response: BanResponse::NotifyOnce,
+            path:     None,
+        }
+    }
+
+    /// Loads the bans in `path`, which is also where they get saved from now on. A missing file
+    /// is the same as an empty one.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let mut bans = BanList::new();
+        bans.path = Some(path.to_owned());
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(bans),
+            Err(e) => return Err(e),
+        };
+        for line in contents.lines() {
+            if line.trim_start().starts_with('#') {
+                continue;
+            }
+            if let Some(target) = BanTarget::parse(line) {
+                bans.ban(target);
+            }
+        }
+        Ok(bans)
+    }
+
+    /// Writes the bans to the file they were loaded from, if any.
+    pub fn save(&self) -> io::Result<()> {
+        let path = match self.path {
+            Some(ref path) => path,
+            None => return Ok(()),
+        };
+        let mut contents = "# Banned addresses and player names, one per line\n".to_owned();
+        for target in self.addrs().into_iter().chain(self.names()) {
+            contents.push_str(&target);
+            contents.push('\n');
+        }
+        fs::write(path, contents)
+    }
+
+    pub fn is_banned_addr(&self, ip: IpAddr) -> bool {
+        self.addrs.contains(&canonical_ip(ip))
+    }
+
+    pub fn is_banned_name(&self, name: &str) -> bool {
+        self.names.contains(name)
+    }
+
+    /// Returns false if `target` was already banned.
+    pub fn ban(&mut self, target: BanTarget) -> bool {
+        match target {
+            BanTarget::Addr(ip) => self.addrs.insert(canonical_ip(ip)),
+            BanTarget::Name(name) => self.names.insert(name),
+        }
+    }
+
+    /// Returns false if `target` was not banned.
+    pub fn unban(&mut self, target: &BanTarget) -> bool {
+        match target {
+            BanTarget::Addr(ip) => {
+                let ip = canonical_ip(*ip);
+                self.notified.remove(&ip);
+                self.addrs.remove(&ip)
+            }
+            BanTarget::Name(name) => self.names.remove(name),
+        }
+    }
+
+    /// Whether to send `ip` a response about being banned. With `NotifyOnce`, only the first time
+    /// this is asked about each address is the answer yes.
+    pub fn should_notify(&mut self, ip: IpAddr) -> bool {
+        match self.response {
+            BanResponse::Silent => false,
+            BanResponse::NotifyOnce => self.notified.insert(canonical_ip(ip)),
+        }
+    }
+
+    /// The banned addresses, sorted.
+    pub fn addrs(&self) -> Vec<String> {
+        let mut addrs: Vec<IpAddr> = self.addrs.iter().cloned().collect();
+        addrs.sort();
+        addrs.iter().map(|ip| ip.to_string()).collect()
+    }
+
+    /// The banned player names, sorted.
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.names.iter().cloned().collect();
+        names.sort();
+        names
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_target() {
+        assert_eq!(
+            BanTarget::parse(" 1.2.3.4 "),
+            Some(BanTarget::Addr("1.2.3.4".parse().unwrap()))
+        );
+        assert_eq!(
+            BanTarget::parse("2001:db8::7"),
+            Some(BanTarget::Addr("2001:db8::7".parse().unwrap()))
+        );
+        assert_eq!(
+            BanTarget::parse("::ffff:1.2.3.4"),
+            Some(BanTarget::Addr("1.2.3.4".parse().unwrap()))
+        );
+        assert_eq!(BanTarget::parse("Gosper"), Some(BanTarget::Name("Gosper".to_owned())));
+        assert_eq!(BanTarget::parse("  "), None);
+    }
+
+    #[test]
+    fn test_mapped_addresses_are_banned_too() {
+        let mut bans = BanList::new();
+        bans.ban(BanTarget::parse("1.2.3.4").unwrap());
+        assert!(bans.is_banned_addr("::ffff:1.2.3.4".parse().unwrap()));
+        assert!(!bans.is_banned_addr("::1.2.3.4".parse().unwrap())); // IPv4-compatible, not mapped
+    }
+
+    #[test]
+    fn test_notify_once() {
+        let ip: IpAddr = "1.2.3.4".parse().unwrap();
+        let mut bans = BanList::new();
+        bans.ban(BanTarget::Addr(ip));
+        assert!(bans.should_notify(ip));
+        assert!(!bans.should_notify(ip));
+
+        // banned again after an unban, it gets told again
+        bans.unban(&BanTarget::Addr(ip));
+        bans.ban(BanTarget::Addr(ip));
+        assert!(bans.should_notify(ip));
+
+        bans.response = BanResponse::Silent;
+        assert!(!bans.should_notify("5.6.7.8".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let path = std::env::temp_dir().join(format!("conwayste-bans-{}.txt", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let mut bans = BanList::load(&path).unwrap(); // missing file
+        assert!(bans.addrs().is_empty() && bans.names().is_empty());
+        for target in &["Gosper", "10.0.0.2", "2001:db8::7", "10.0.0.1"] {
+            assert!(bans.ban(BanTarget::parse(target).unwrap()));
+        }
+        assert!(!bans.ban(BanTarget::parse("Gosper").unwrap()));
+        bans.save().unwrap();
+
+        let loaded = BanList::load(&path).unwrap();
+        assert_eq!(loaded.addrs(), vec!["10.0.0.1", "10.0.0.2", "2001:db8::7"]);
+        assert_eq!(loaded.names(), vec!["Gosper"]);
+        fs::remove_file(&path).unwrap();
+    }
+}
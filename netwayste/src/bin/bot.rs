@@ -0,0 +1,143 @@
+/*
+ * Copyright (C) 2021 The Conwayste Developers
+ *
+ * This program is free software: you can redistribute it and/or modify it
+ * under the terms of the GNU General Public License as published by the Free
+ * Software Foundation, either version 3 of the License, or (at your option)
+ * any later version.
+ *
+ * This program is distributed in the hope that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of  MERCHANTABILITY or
+ * FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for
+ * more details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Puts load on a server with simulated players, and prints what they saw once they are done.
+
+#[macro_use]
+extern crate log;
+
+use std::io::Write;
+use std::process::exit;
+use std::time::Duration;
+
+use chrono::Local;
+use clap::{App, Arg, ArgMatches};
+use log::LevelFilter;
+
+use netwayste::bot::{run_bots, BotConfig};
+
+/// Parses the value of the option `name` as a positive number, if it was given.
+fn positive_arg(matches: &ArgMatches, name: &str) -> Option<u64> {
+    matches.value_of(name).map(|value| match value.parse::<u64>() {
+        Ok(number) if number > 0 => number,
+        _ => {
+            error!("Invalid {} {:?}; expected a positive number", name, value);
+            exit(1);
+        }
+    })
+}
+
+#[tokio::main]
+async fn main() {
+    env_logger::Builder::new()
+        .format(|buf, record| {
+            writeln!(
+                buf,
+                "{} [{:5}] - {}",
+                Local::now().format("%Y-%m-%dT%H:%M:%S%.6fZ"),
+                record.level(),
+                record.args(),
+            )
+        })
+        .filter(None, LevelFilter::Warn)
+        .filter(Some("futures"), LevelFilter::Off)
+        .filter(Some("tokio_reactor"), LevelFilter::Off)
+        .parse_env("RUST_LOG")
+        .init();
+
+    let defaults = BotConfig::default();
+    let matches = App::new("bot")
+        .about("simulated players for load testing a Conwayste server")
+        .arg(
+            Arg::with_name("server")
+                .help(&format!(
+                    "host name or address of the server, optionally followed by a port [default {}]",
+                    defaults.server
+                ))
+                .index(1),
+        )
+        .arg(
+            Arg::with_name("clients")
+                .short("n")
+                .long("clients")
+                .help(&format!("number of bots to run at once [default {}]", defaults.bots))
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("duration")
+                .short("d")
+                .long("duration")
+                .help(&format!(
+                    "seconds each bot stays connected [default {}]",
+                    defaults.duration.as_secs()
+                ))
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("chat-ms")
+                .long("chat-ms")
+                .help(&format!(
+                    "milliseconds between each bot's chat messages [default {}]",
+                    defaults.chat_interval.as_millis()
+                ))
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("list-ms")
+                .long("list-ms")
+                .help(&format!(
+                    "milliseconds between each bot's player list requests [default {}]",
+                    defaults.list_interval.as_millis()
+                ))
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("room-size")
+                .long("room-size")
+                .help(&format!("bots per room [default {}]", defaults.room_size))
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("timeout-ms")
+                .long("timeout-ms")
+                .help(&format!(
+                    "milliseconds a request can go unanswered before it counts as timed out [default {}]",
+                    defaults.timeout.as_millis()
+                ))
+                .takes_value(true),
+        )
+        .get_matches();
+
+    let config = BotConfig {
+        server:        matches.value_of("server").unwrap_or(&defaults.server).to_owned(),
+        bots:          positive_arg(&matches, "clients").map_or(defaults.bots, |n| n as usize),
+        duration:      positive_arg(&matches, "duration").map_or(defaults.duration, Duration::from_secs),
+        chat_interval: positive_arg(&matches, "chat-ms").map_or(defaults.chat_interval, Duration::from_millis),
+        list_interval: positive_arg(&matches, "list-ms").map_or(defaults.list_interval, Duration::from_millis),
+        room_size:     positive_arg(&matches, "room-size").map_or(defaults.room_size, |n| n as usize),
+        timeout:       positive_arg(&matches, "timeout-ms").map_or(defaults.timeout, Duration::from_millis),
+    };
+    println!(
+        "Running {} bots against {} for {:?}",
+        config.bots, config.server, config.duration
+    );
+    let report = run_bots(config).await;
+    println!("{}", report);
+    if report.connected < report.bots {
+        exit(1);
+    }
+}
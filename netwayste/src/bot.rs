@@ -0,0 +1,361 @@
+/*
+ * Copyright (C) 2021 The Conwayste Developers
+ *
+ * This program is free software: you can redistribute it and/or modify it
+ * under the terms of the GNU General Public License as published by the Free
+ * Software Foundation, either version 3 of the License, or (at your option)
+ * any later version.
+ *
+ * This program is distributed in the hope that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of  MERCHANTABILITY or
+ * FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for
+ * more details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Simulated players, for putting load on a server. Each bot runs a whole client network layer
+//! (`ClientNetState::start_network`) over real UDP and drives it through its channels, just like
+//! conwayste does, so keep alives and update replies are the library's own. A bot logs in, gets
+//! into a room with a few other bots, and then chats and lists the room's players until time is
+//! up. Every request that gets a response is timed, and the times are collected into a
+//! `LoadReport`.
+
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures as Fut;
+use tokio::time::{self as TokioTime, Instant};
+use Fut::channel::mpsc;
+use Fut::prelude::*;
+
+use crate::client::ClientNetState;
+use crate::net::{ConnectionStatus, NetwaysteEvent, VERSION};
+
+/// How long a bot waits before trying again to join a room that didn't let it in, most likely
+/// because the bot that creates the room hasn't done so yet.
+pub const JOIN_RETRY_DELAY: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Clone)]
+pub struct BotConfig {
+    pub server:        String,   // host name or address, optionally followed by a port
+    pub bots:          usize,    // how many bots to run at once
+    pub duration:      Duration, // how long each bot stays connected
+    pub chat_interval: Duration, // time between each bot's chat messages
+    pub list_interval: Duration, // time between each bot's player list requests
+    pub room_size:     usize,    // bots per room; the first bot of each group creates it
+    pub timeout:       Duration, // how long a request can go unanswered before it counts as timed out
+}
+
+impl Default for BotConfig {
+    fn default() -> Self {
+        BotConfig {
+            server:        "localhost".to_owned(),
+            bots:          5,
+            duration:      Duration::from_secs(30),
+            chat_interval: Duration::from_secs(2),
+            list_interval: Duration::from_secs(1),
+            room_size:     4,
+            timeout:       Duration::from_secs(5),
+        }
+    }
+}
+
+/// What one bot saw.
+#[derive(Debug, Clone, Default)]
+pub struct BotStats {
+    pub connected:  bool,
+    pub rtts:       Vec<Duration>, // how long each answered request took to be answered
+    pub timeouts:   u64,           // requests that went unanswered for longer than the timeout
+    pub errors:     u64,           // requests the server refused
+    pub chats_sent: u64,
+    pub lost:       u64, // times the network layer considered the connection lost
+}
+
+/// The requests a bot waits on an answer to. Only one is outstanding at a time.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Pending {
+    Connect,
+    Join,
+    List,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Stage {
+    LoggingIn,
+    Joining,
+    InRoom,
+}
+
+/// Runs `config.bots` bots against `config.server` at the same time, and returns what they saw
+/// once they are all done.
+pub async fn run_bots(config: BotConfig) -> LoadReport {
+    let config = Arc::new(config);
+    // Keeps the names of this run's bots and rooms apart from any other run's on the same server
+    let run_id: u16 = rand::random();
+    let bots: Vec<_> = (0..config.bots)
+        .map(|index| tokio::spawn(run_bot(config.clone(), run_id, index)))
+        .collect();
+    let mut stats = vec![];
+    for bot in bots {
+        match bot.await {
+            Ok(bot_stats) => stats.push(bot_stats),
+            Err(e) => error!("A bot failed: {}", e),
+        }
+    }
+    LoadReport::new(config.bots, &stats)
+}
+
+async fn run_bot(config: Arc<BotConfig>, run_id: u16, index: usize) -> BotStats {
+    let mut stats = BotStats::default();
+    let name = format!("bot{}-{}", run_id, index);
+    let room_size = config.room_size.max(1);
+    let room_name = format!("bots{}-{}", run_id, index / room_size);
+    let creates_room = index % room_size == 0;
+
+    let (to_network, requests) = mpsc::unbounded::<NetwaysteEvent>();
+    let (responses, mut from_network) = mpsc::channel::<NetwaysteEvent>(5);
+    let server = config.server.clone();
+    let network = tokio::spawn(async move {
+        if let Err(e) = ClientNetState::start_network(server, responses, requests).await {
+            error!("Network layer stopped: {}", e);
+        }
+    });
+
+    let start = Instant::now();
+    let time_up = TokioTime::sleep_until(start + config.duration);
+    tokio::pin!(time_up);
+    let mut chat_interval = TokioTime::interval_at(start + config.chat_interval, config.chat_interval);
+    let mut list_interval = TokioTime::interval_at(start + config.list_interval, config.list_interval);
+    let mut check_interval = TokioTime::interval(Duration::from_millis(100));
+    let mut stage = Stage::LoggingIn;
+    let mut pending = Some((Pending::Connect, Instant::now()));
+    let mut retry_join_at: Option<Instant> = None;
+    let send = |event: NetwaysteEvent| {
+        if let Err(e) = to_network.unbounded_send(event) {
+            error!("{}: could not send a request to the network layer: {}", name, e);
+        }
+    };
+
+    send(NetwaysteEvent::Connect(name.clone(), VERSION.to_owned()));
+    loop {
+        tokio::select! {
+            opt_event = from_network.next() => {
+                let event = match opt_event {
+                    Some(event) => event,
+                    None => break, // the network layer has stopped
+                };
+                let now = Instant::now();
+                match event {
+                    NetwaysteEvent::LoggedIn(_) => {
+                        answer(&mut stats, &mut pending, Pending::Connect, now);
+                        stats.connected = true;
+                        stage = Stage::Joining;
+                        if creates_room {
+                            send(NetwaysteEvent::NewRoom(room_name.clone(), None, None, None));
+                        }
+                        send(NetwaysteEvent::JoinRoom(room_name.clone()));
+                        pending = Some((Pending::Join, now));
+                    }
+                    NetwaysteEvent::JoinedRoom(_) => {
+                        answer(&mut stats, &mut pending, Pending::Join, now);
+                        stage = Stage::InRoom;
+                    }
+                    NetwaysteEvent::PlayerList(..) | NetwaysteEvent::RoomList(_) => {
+                        answer(&mut stats, &mut pending, Pending::List, now);
+                    }
+                    NetwaysteEvent::BadRequest(_) | NetwaysteEvent::RoomFull(..) => {
+                        debug!("{}: request refused: {:?}", name, event);
+                        stats.errors += 1;
+                        if stage == Stage::Joining {
+                            retry_join_at = Some(now + JOIN_RETRY_DELAY);
+                            pending = None;
+                        }
+                    }
+                    NetwaysteEvent::ConnectionStatus(ConnectionStatus::Disconnected) => stats.lost += 1,
+                    _ => {}
+                }
+            }
+            _ = chat_interval.tick(), if stage == Stage::InRoom => {
+                stats.chats_sent += 1;
+                send(NetwaysteEvent::ChatMessage(format!("message {} from {}", stats.chats_sent, name)));
+            }
+            _ = list_interval.tick(), if stage == Stage::InRoom && pending.is_none() => {
+                send(NetwaysteEvent::List);
+                pending = Some((Pending::List, Instant::now()));
+            }
+            _ = check_interval.tick() => {
+                let now = Instant::now();
+                if let Some((request, sent)) = pending {
+                    if now >= sent + config.timeout {
+                        debug!("{}: {:?} request timed out", name, request);
+                        stats.timeouts += 1;
+                        pending = None;
+                    }
+                }
+                if retry_join_at.map_or(false, |retry| now >= retry) {
+                    retry_join_at = None;
+                    if stage == Stage::Joining {
+                        send(NetwaysteEvent::JoinRoom(room_name.clone()));
+                        pending = Some((Pending::Join, now));
+                    }
+                }
+            }
+            _ = &mut time_up => break,
+        }
+    }
+
+    send(NetwaysteEvent::Disconnect);
+    // Closing the channels stops the network layer, once it has sent the disconnect
+    drop(to_network);
+    drop(from_network);
+    if let Err(e) = network.await {
+        error!("{}: network layer failed: {}", name, e);
+    }
+    stats
+}
+
+/// Records how long `request` took to be answered, if it is the one being waited on.
+fn answer(stats: &mut BotStats, pending: &mut Option<(Pending, Instant)>, request: Pending, now: Instant) {
+    match *pending {
+        Some((waiting_on, sent)) if waiting_on == request => {
+            stats.rtts.push(now - sent);
+            *pending = None;
+        }
+        _ => {}
+    }
+}
+
+/// What all the bots of a run saw, put together.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoadReport {
+    pub bots:       usize,
+    pub connected:  usize,
+    pub rtts:       Vec<Duration>, // sorted, shortest first
+    pub timeouts:   u64,
+    pub errors:     u64,
+    pub chats_sent: u64,
+    pub lost:       u64,
+}
+
+impl LoadReport {
+    /// `bots` is how many bots were started; any missing from `stats` count as not connected.
+    pub fn new(bots: usize, stats: &[BotStats]) -> Self {
+        let mut rtts: Vec<Duration> = stats.iter().flat_map(|bot| bot.rtts.iter().cloned()).collect();
+        rtts.sort();
+        LoadReport {
+            bots,
+            connected: stats.iter().filter(|bot| bot.connected).count(),
+            rtts,
+            timeouts: stats.iter().map(|bot| bot.timeouts).sum(),
+            errors: stats.iter().map(|bot| bot.errors).sum(),
+            chats_sent: stats.iter().map(|bot| bot.chats_sent).sum(),
+            lost: stats.iter().map(|bot| bot.lost).sum(),
+        }
+    }
+
+    /// The fraction of bots that logged in, from 0 to 1.
+    pub fn connect_rate(&self) -> f64 {
+        if self.bots == 0 {
+            return 0.0;
+        }
+        self.connected as f64 / self.bots as f64
+    }
+
+    /// The round trip time that `percent` percent of answered requests took no longer than, by
+    /// the nearest-rank method. `None` if no request was answered.
+    pub fn rtt_percentile(&self, percent: u32) -> Option<Duration> {
+        if self.rtts.is_empty() {
+            return None;
+        }
+        let rank = (percent.min(100) as usize * self.rtts.len() + 99) / 100;
+        Some(self.rtts[rank.max(1) - 1])
+    }
+}
+
+impl fmt::Display for LoadReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "connected: {}/{} ({:.1}%)",
+            self.connected,
+            self.bots,
+            self.connect_rate() * 100.0
+        )?;
+        write!(f, "round trips: {}", self.rtts.len())?;
+        for &percent in &[50, 90, 99] {
+            if let Some(rtt) = self.rtt_percentile(percent) {
+                write!(f, ", p{} {:.1} ms", percent, rtt.as_secs_f64() * 1000.0)?;
+            }
+        }
+        writeln!(f)?;
+        writeln!(f, "timeouts: {}", self.timeouts)?;
+        writeln!(f, "refused requests: {}", self.errors)?;
+        writeln!(f, "connections lost: {}", self.lost)?;
+        write!(f, "chat messages sent: {}", self.chats_sent)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn test_rtt_percentiles() {
+        let stats = vec![
+            BotStats {
+                rtts: (1..=50).rev().map(ms).collect(),
+                ..BotStats::default()
+            },
+            BotStats {
+                rtts: (51..=100).map(ms).collect(),
+                ..BotStats::default()
+            },
+        ];
+        let report = LoadReport::new(2, &stats);
+        assert_eq!(report.rtt_percentile(0), Some(ms(1)));
+        assert_eq!(report.rtt_percentile(50), Some(ms(50)));
+        assert_eq!(report.rtt_percentile(90), Some(ms(90)));
+        assert_eq!(report.rtt_percentile(99), Some(ms(99)));
+        assert_eq!(report.rtt_percentile(100), Some(ms(100)));
+
+        let report = LoadReport::new(1, &[BotStats::default()]);
+        assert_eq!(report.rtt_percentile(50), None);
+    }
+
+    #[test]
+    fn test_report_totals() {
+        let connected = BotStats {
+            connected:  true,
+            rtts:       vec![ms(3)],
+            timeouts:   1,
+            errors:     2,
+            chats_sent: 4,
+            lost:       1,
+        };
+        // the third bot panicked, so it has no stats
+        let report = LoadReport::new(
+            3,
+            &[
+                connected.clone(),
+                BotStats {
+                    timeouts: 2,
+                    ..BotStats::default()
+                },
+            ],
+        );
+        assert_eq!(report.connected, 1);
+        assert!((report.connect_rate() - 1.0 / 3.0).abs() < 1e-9);
+        assert_eq!(
+            (report.timeouts, report.errors, report.chats_sent, report.lost),
+            (3, 2, 4, 1)
+        );
+        assert_eq!(LoadReport::new(0, &[]).connect_rate(), 0.0);
+    }
+}
@@ -17,12 +17,11 @@
  * this program.  If not, see <http://www.gnu.org/licenses/>.
  */
 
-use std::env;
 use std::error::Error;
-use std::net::SocketAddr;
-use std::process::exit;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::time::Duration;
 use std::time::Instant;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use futures as Fut;
 use regex::Regex;
@@ -32,18 +31,29 @@ use tokio_util::udp::UdpFramed;
 use Fut::prelude::*;
 use Fut::select;
 
+use crate::health::{ConnectionHealth, HealthEvent};
 use crate::net::{
-    bind, has_connection_timed_out, BroadcastChatMessage, NetwaysteEvent, NetwaystePacketCodec, NetworkManager,
-    NetworkQueue, Packet, RequestAction, ResponseCode, RoomList, DEFAULT_PORT, VERSION,
+    bind_addr, BroadcastChatMessage, ConnectionStatus, GenChecksum, GenStateDiffPart, NetwaysteEvent,
+    NetwaystePacketCodec, NetworkManager, NetworkQueue, Packet, PartialGenStateDiff, RequestAction, ResponseCode,
+    RoomList, UniUpdate, DEFAULT_PORT, VERSION,
 };
 
 use crate::utils::{LatencyFilter, PingPong};
 
 const TICK_INTERVAL_IN_MS: u64 = 1000;
 const NETWORK_INTERVAL_IN_MS: u64 = 1000;
+const KEEP_ALIVE_GRACE_IN_MS: u64 = 500; // the server's keep alives may arrive a little late
 
 pub const CLIENT_VERSION: &str = "0.0.1";
 
+/// Milliseconds since the Unix epoch, for stamping pings. Only this client reads them back.
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_millis() as u64)
+        .unwrap_or(0)
+}
+
 pub struct ClientNetState {
     pub sequence:             u64, // Sequence number of requests
     pub response_sequence:    u64, // Value of the next expected sequence number from the server,
@@ -52,6 +62,8 @@ pub struct ClientNetState {
     pub room:                 Option<String>,
     pub cookie:               Option<String>,
     pub chat_msg_seq_num:     u64,
+    pub last_full_gen:        Option<u64>, // Generation of the last whole diff sent to conwayste
+    pub partial_diff:         Option<PartialGenStateDiff>, // Diff whose parts are still arriving
     pub tick:                 usize,
     pub network:              NetworkManager,
     pub last_received:        Option<Instant>,
@@ -59,6 +71,7 @@ pub struct ClientNetState {
     pub server_address:       Option<SocketAddr>,
     pub channel_to_conwayste: Fut::channel::mpsc::Sender<NetwaysteEvent>,
     latency_filter:           LatencyFilter,
+    health:                   ConnectionHealth,
 }
 
 impl ClientNetState {
@@ -70,6 +83,8 @@ impl ClientNetState {
             room:                 None,
             cookie:               None,
             chat_msg_seq_num:     0,
+            last_full_gen:        None,
+            partial_diff:         None,
             tick:                 0,
             network:              NetworkManager::new().with_message_buffering(),
             last_received:        None,
@@ -77,6 +92,7 @@ impl ClientNetState {
             server_address:       None,
             channel_to_conwayste: channel_to_conwayste,
             latency_filter:       LatencyFilter::new(),
+            health:               ConnectionHealth::new(),
         }
     }
 
@@ -92,6 +108,8 @@ impl ClientNetState {
             ref mut room,
             ref mut cookie,
             ref mut chat_msg_seq_num,
+            ref mut last_full_gen,
+            ref mut partial_diff,
             ref mut tick,
             ref mut network,
             ref mut last_received,
@@ -99,12 +117,15 @@ impl ClientNetState {
             ref mut server_address,
             channel_to_conwayste: ref _channel_to_conwayste, // Don't clear the channel to conwayste
             ref mut latency_filter,
+            health: ref _health, // Reconnecting starts with a reset, so don't forget that we're disconnected
         } = *self;
         *sequence = 0;
         *response_sequence = 0;
         *room = None;
         *cookie = None;
         *chat_msg_seq_num = 0;
+        *last_full_gen = None;
+        *partial_diff = None;
         *tick = 0;
         *last_received = None;
         *disconnect_initiated = false;
@@ -119,6 +140,23 @@ impl ClientNetState {
         self.room.is_some()
     }
 
+    pub fn connection_status(&self) -> ConnectionStatus {
+        self.health.status()
+    }
+
+    /// Feeds `event` to the connection health, and tells conwayste if the status changed.
+    async fn update_health(&mut self, event: HealthEvent, now: Instant) {
+        if let Some(status) = self.health.handle(event, now) {
+            info!("Connection status: {:?}", status);
+            self.channel_to_conwayste
+                .send(NetwaysteEvent::ConnectionStatus(status))
+                .await
+                .unwrap_or_else(|e| {
+                    error!("Could not send a netwayste response via channel_to_conwayste: {:?}", e);
+                });
+        }
+    }
+
     fn check_for_upgrade(&self, server_version: &String) {
         let client_version = &VERSION.to_owned();
         if client_version < server_version {
@@ -171,6 +209,10 @@ impl ClientNetState {
                 ref server_version,
             } => {
                 self.handle_logged_in(cookie.to_string(), server_version.to_string());
+                if self.health.status() == ConnectionStatus::Disconnected {
+                    self.update_health(HealthEvent::ReconnectSucceeded, Instant::now())
+                        .await;
+                }
             }
             ResponseCode::LeaveRoom => {
                 self.handle_left_room();
@@ -178,17 +220,90 @@ impl ClientNetState {
             ResponseCode::JoinedRoom { ref room_name } => {
                 self.handle_joined_room(room_name);
             }
-            ResponseCode::PlayerList { ref players } => {
-                self.handle_player_list(players.to_vec());
+            ResponseCode::PlayerList {
+                ref players,
+                ref muted,
+                ref teams,
+            } => {
+                self.handle_player_list(players.to_vec(), muted, teams);
             }
             ResponseCode::RoomList { ref rooms } => {
                 self.handle_room_list(rooms.to_vec());
             }
             ResponseCode::KeepAlive => {}
+            ResponseCode::CellsPlaced { .. } => {}
             // errors
             ResponseCode::Unauthorized { error_msg: opt_error } => {
                 info!("Unauthorized action attempted by client: {:?}", opt_error);
             }
+            ResponseCode::ChatThrottled { ref error_msg, .. } => {
+                info!("Chat message refused: {}", error_msg);
+            }
+            ResponseCode::BanList { ref addrs, ref names } => {
+                info!("Banned addresses: {:?}; banned names: {:?}", addrs, names);
+            }
+            ResponseCode::Invited {
+                ref inviter,
+                ref room_name,
+                ref token,
+            } => {
+                info!("{} invited us to room {}; invite token: {}", inviter, room_name, token);
+            }
+            ResponseCode::InviteFailed { reason } => {
+                info!("Invitation failed: {:?}", reason);
+            }
+            ResponseCode::InviteAnswered { ref name, answer } => {
+                info!("Invitation to {}: {:?}", name, answer);
+            }
+            ResponseCode::SetTeamFailed { reason } => {
+                info!("Could not change teams: {:?}", reason);
+            }
+            ResponseCode::GameStarted {
+                ref room_name,
+                ref teams,
+            } => {
+                info!("Game started in room {}; teams: {:?}", room_name, teams);
+            }
+            ResponseCode::GameEnded {
+                ref room_name,
+                generations,
+                ref results,
+            } => {
+                info!(
+                    "Game ended in room {} after {} generations; results: {:?}",
+                    room_name, generations, results
+                );
+            }
+            ResponseCode::LobbyChat {
+                ref player_name,
+                ref message,
+            } => {
+                info!("{}: {}", player_name, message);
+            }
+            ResponseCode::RoomNameTaken { ref room_name } => {
+                info!("There is already a room named {}", room_name);
+            }
+            ResponseCode::RoomFull {
+                ref room_name,
+                max_players,
+            } => {
+                info!("Room {} is full; it takes {} players", room_name, max_players);
+            }
+            ResponseCode::GameStarting {
+                ref room_name,
+                seconds_left,
+            } => {
+                info!("The game in room {} starts in {}...", room_name, seconds_left);
+            }
+            ResponseCode::GameStartCancelled { ref room_name } => {
+                info!("The game in room {} is not starting after all", room_name);
+            }
+            ResponseCode::OwnerChanged {
+                ref room_name,
+                ref owner,
+            } => {
+                info!("{} is now the owner of room {}", owner, room_name);
+            }
             _ => {
                 error!("unknown response from server: {:?}", code);
             }
@@ -205,12 +320,28 @@ impl ClientNetState {
 
     pub async fn handle_incoming_event(&mut self, packet: Packet, addr: SocketAddr) -> Vec<(Packet, SocketAddr)> {
         match packet.clone() {
+            // Not sequenced, so it skips the RX queue
+            Packet::Response {
+                code: ResponseCode::Pong { timestamp },
+                ..
+            } => {
+                let rtt_ms = unix_millis().saturating_sub(timestamp);
+                self.channel_to_conwayste
+                    .send(NetwaysteEvent::Pong(rtt_ms))
+                    .await
+                    .unwrap_or_else(|e| {
+                        error!("Could not send a netwayste response via channel_to_conwayste: {:?}", e);
+                    });
+                return vec![];
+            }
             Packet::Response {
                 sequence,
                 request_ack: _,
                 code,
             } => {
-                self.last_received = Some(Instant::now());
+                let now = Instant::now();
+                self.last_received = Some(now);
+                self.update_health(HealthEvent::ResponseReceived, now).await;
                 let code = code.clone();
                 if code != ResponseCode::KeepAlive {
                     // When a packet is acked, we can remove it from the TX buffer and buffer the response for
@@ -225,7 +356,7 @@ impl ClientNetState {
                         // println!("TX packets: {:?}", self.network.tx_packets);
                         // None means the packet was not found so we've probably already removed it.
                         if let Some(_) = self.network.tx_packets.remove(&packet) {
-                            self.network.rx_packets.buffer_item(packet);
+                            self.network.rx_packets.buffer_item(packet, Instant::now());
                         }
 
                         self.process_queued_server_responses().await;
@@ -233,25 +364,34 @@ impl ClientNetState {
                 }
                 return vec![];
             }
-            // TODO game_updates, game_update_seq, universe_update,
+            // TODO game_updates, game_update_seq
             Packet::Update {
                 chats,
                 game_updates: _,
                 game_update_seq: _,
-                universe_update: _,
+                universe_update,
+                gen_checksum,
                 ping,
             } => {
                 if chats.len() != 0 {
                     self.handle_incoming_chats(chats).await;
                 }
 
+                if let UniUpdate::Diff { diff } = universe_update {
+                    self.handle_universe_diff_part(diff).await;
+                }
+
+                if let Some(gen_checksum) = gen_checksum {
+                    self.handle_universe_checksum(gen_checksum).await;
+                }
+
                 // Reply to the update
                 let update_reply_packet = Packet::UpdateReply {
                     cookie:               self.cookie.clone().unwrap(),
                     last_chat_seq:        Some(self.chat_msg_seq_num),
                     last_game_update_seq: None,
-                    last_full_gen:        None,
-                    partial_gen:          None,
+                    last_full_gen:        self.last_full_gen,
+                    partial_gen:          self.partial_diff.as_ref().map(|partial| partial.info()),
                     pong:                 PingPong::pong(ping.nonce),
                 };
 
@@ -275,56 +415,110 @@ impl ClientNetState {
         }
     }
 
-    pub async fn collect_expired_tx_packets(&mut self) -> Vec<(Packet, SocketAddr)> {
+    pub async fn collect_expired_tx_packets(&mut self, now: Instant) -> Vec<(Packet, SocketAddr)> {
         if self.cookie.is_some() {
             // Determine what can be processed
             // Determine what needs to be resent
             // Resend anything remaining in TX queue if it has also expired.
             self.process_queued_server_responses().await;
 
-            let indices = self.network.tx_packets.get_retransmit_indices();
+            let indices = self.network.tx_packets.get_retransmit_indices(now);
 
             return self.network.get_expired_tx_packets(
                 self.server_address.unwrap().clone(),
                 Some(self.response_sequence),
                 &indices,
+                now,
             );
         }
         return vec![];
     }
 
-    fn handle_tick_event(&mut self) -> Option<Packet> {
-        // Every 100ms, after we've connected
-        if self.cookie.is_some() {
-            let timed_out = has_connection_timed_out(self.last_received.unwrap());
+    async fn handle_tick_event(&mut self) -> Option<Packet> {
+        // Every second, after we've connected
+        let now = Instant::now();
+        if self.health.status() == ConnectionStatus::Disconnected {
+            return self.try_reconnect(now).await;
+        }
 
-            if timed_out || self.disconnect_initiated {
-                if timed_out {
-                    info!("Server is non-responsive, disconnecting.");
-                }
-                if self.disconnect_initiated {
-                    info!("Disconnected from the server.")
-                }
+        if self.cookie.is_some() {
+            if self.disconnect_initiated {
+                info!("Disconnected from the server.");
                 self.reset();
                 return None;
-            } else {
-                // Send a keep alive if the connection is live
-                let keep_alive = Packet::Request {
-                    cookie:       self.cookie.clone(),
-                    sequence:     self.sequence,
-                    response_ack: None,
-                    action:       RequestAction::KeepAlive {
-                        latest_response_ack: self.response_sequence,
-                    },
-                };
-                return Some(keep_alive);
             }
+
+            // unwrap OK because the cookie came in a response
+            let silence = now.saturating_duration_since(self.last_received.unwrap());
+            if silence > Duration::from_millis(TICK_INTERVAL_IN_MS + KEEP_ALIVE_GRACE_IN_MS) {
+                self.update_health(HealthEvent::KeepAliveMissed, now).await;
+                if self.health.status() == ConnectionStatus::Disconnected {
+                    info!("Server is non-responsive; reconnecting.");
+                    return self.try_reconnect(now).await;
+                }
+            }
+
+            // Send a keep alive; while the connection is degraded, this is how we find out it's back
+            let keep_alive = Packet::Request {
+                cookie:       self.cookie.clone(),
+                sequence:     self.sequence,
+                response_ack: None,
+                action:       RequestAction::KeepAlive {
+                    latest_response_ack: self.response_sequence,
+                },
+            };
+            return Some(keep_alive);
         }
 
         self.tick = 1usize.wrapping_add(self.tick);
         None
     }
 
+    /// While disconnected, logs in again whenever the connection health says it is time to. The
+    /// server can't resume a session, so this starts a new one under the same name. It is refused
+    /// until the server has timed out the old one, and any room has to be joined again.
+    async fn try_reconnect(&mut self, now: Instant) -> Option<Packet> {
+        if self.health.reconnect_timed_out(now) {
+            info!("Reconnect attempt got no answer.");
+            self.update_health(HealthEvent::ReconnectFailed, now).await;
+        }
+        if !self.health.reconnect_due(now) {
+            return None;
+        }
+
+        // unwrap OK because we can only get disconnected after logging in, and the name is kept
+        let name = self.name.clone().unwrap();
+        let server_address = self.server_address;
+        self.reset();
+        self.server_address = server_address;
+        self.update_health(HealthEvent::ReconnectStarted, now).await;
+        info!("Reconnecting as {:?}", name);
+        Some(self.action_to_packet(RequestAction::Connect {
+            name,
+            client_version: VERSION.to_owned(),
+        }))
+    }
+
+    /// Called for each request from conwayste while disconnected. A disconnect stops the reconnect
+    /// attempts; anything else is refused without being sent, and conwayste is told why.
+    async fn refuse_while_disconnected(&mut self, request: NetwaysteEvent) {
+        if request == NetwaysteEvent::Disconnect {
+            info!("Stopped reconnecting.");
+            self.reset();
+            self.health = ConnectionHealth::new();
+            return;
+        }
+        debug!("Refused while disconnected: {:?}", request);
+        self.channel_to_conwayste
+            .send(NetwaysteEvent::RequestNotSent(
+                "Not connected to the server; trying to reconnect".to_owned(),
+            ))
+            .await
+            .unwrap_or_else(|e| {
+                error!("Could not send a netwayste response via channel_to_conwayste: {:?}", e);
+            });
+    }
+
     pub fn handle_response_ok(&mut self) -> Result<(), Box<dyn Error>> {
         info!("OK :)");
         return Ok(());
@@ -352,12 +546,19 @@ impl ClientNetState {
         }
         self.room = None;
         self.chat_msg_seq_num = 0;
+        self.last_full_gen = None;
+        self.partial_diff = None;
     }
 
-    pub fn handle_player_list(&mut self, player_names: Vec<String>) {
+    pub fn handle_player_list(&mut self, player_names: Vec<String>, muted: &[String], teams: &[Option<u8>]) {
         info!("---BEGIN PLAYER LIST---");
         for (i, player_name) in player_names.iter().enumerate() {
-            info!("{}\tname: {}", i, player_name);
+            let team = match teams.get(i) {
+                Some(Some(team)) => format!(" (team {})", team),
+                _ => String::new(),
+            };
+            let muted = if muted.contains(player_name) { " (muted)" } else { "" };
+            info!("{}\tname: {}{}{}", i, player_name, team, muted);
         }
         info!("---END PLAYER LIST---");
     }
@@ -366,8 +567,8 @@ impl ClientNetState {
         info!("---BEGIN GAME ROOM LIST---");
         for room in rooms {
             info!(
-                "#name: {},\trunning? {:?},\tplayers: {:?}",
-                room.room_name, room.in_progress, room.player_count
+                "#name: {},\trunning? {:?},\tplayers: {}/{},\tpassword? {:?}",
+                room.room_name, room.in_progress, room.player_count, room.max_players, room.has_password
             );
         }
         info!("---END GAME ROOM LIST---");
@@ -387,7 +588,7 @@ impl ClientNetState {
             self.chat_msg_seq_num = std::cmp::max(chat_seq, self.chat_msg_seq_num);
 
             let queue = self.network.rx_chat_messages.as_mut().unwrap();
-            queue.buffer_item(chat_message.clone());
+            queue.buffer_item(chat_message.clone(), Instant::now());
 
             if let Some(client_name) = self.name.as_ref() {
                 if client_name != &chat_message.player_name {
@@ -406,6 +607,69 @@ impl ClientNetState {
         }
     }
 
+    /// Collects a part of a universe diff. Once all parts of a diff newer than the last one have
+    /// arrived, sends the whole diff to conwayste.
+    pub async fn handle_universe_diff_part(&mut self, part: GenStateDiffPart) {
+        if self.last_full_gen >= Some(part.gen1 as u64) {
+            return; // already have this generation
+        }
+
+        let mut partial_diff = match self.partial_diff.take() {
+            Some(partial_diff) if partial_diff.is_same_diff(&part) => partial_diff,
+            // A part of a newer diff replaces the older diff's parts; the server won't finish it
+            Some(partial_diff) if partial_diff.info().gen1 > part.gen1 => {
+                self.partial_diff = Some(partial_diff);
+                return;
+            }
+            _ => PartialGenStateDiff::new(&part),
+        };
+
+        if let Some(diff) = partial_diff.add(part) {
+            self.last_full_gen = Some(diff.gen1 as u64);
+            let nw_response = NetwaysteEvent::UniverseUpdate(diff);
+            match self.channel_to_conwayste.send(nw_response).await {
+                Ok(_) => (),
+                Err(e) => error!("Could not send a netwayste response via channel_to_conwayste: {:?}", e),
+            }
+        } else {
+            self.partial_diff = Some(partial_diff);
+        }
+    }
+
+    /// Passes the server's checksum of a generation on to conwayste, if conwayste has been sent the
+    /// whole diff for that generation and so can check its universe against it.
+    pub async fn handle_universe_checksum(&mut self, gen_checksum: GenChecksum) {
+        if self.last_full_gen != Some(gen_checksum.generation) {
+            return;
+        }
+
+        let nw_response = NetwaysteEvent::UniverseChecksum(gen_checksum);
+        match self.channel_to_conwayste.send(nw_response).await {
+            Ok(_) => (),
+            Err(e) => error!("Could not send a netwayste response via channel_to_conwayste: {:?}", e),
+        }
+    }
+
+    /// Forgets the universe diffs received so far, since the server will follow a request for the
+    /// full state with a diff from the beginning of time.
+    pub fn handle_request_full_state(&mut self) {
+        self.last_full_gen = None;
+        self.partial_diff = None;
+    }
+
+    /// A Ping stamped with the time now. Unlike other requests, it isn't sequenced or kept for
+    /// retransmission, and it can be sent before we have a cookie.
+    fn ping_packet(&self) -> Packet {
+        Packet::Request {
+            sequence:     0,
+            response_ack: None,
+            cookie:       self.cookie.clone(),
+            action:       RequestAction::Ping {
+                timestamp: unix_millis(),
+            },
+        }
+    }
+
     /// Prepare a request action to the connected server
     fn action_to_packet(&mut self, action: RequestAction) -> Packet {
         // Sequence number can increment once we're talking to a server
@@ -428,66 +692,65 @@ impl ClientNetState {
 
         trace!("{:?}", packet);
 
-        self.network.tx_packets.buffer_item(packet.clone());
+        self.network.tx_packets.buffer_item(packet.clone(), Instant::now());
 
         packet
     }
 
     async fn maintain_network_state(&mut self) -> Vec<(Packet, SocketAddr)> {
-        self.collect_expired_tx_packets().await
+        self.collect_expired_tx_packets(Instant::now()).await
     }
 
     /// Main executor for the client-side network layer for conwayste and should be run from a thread.
-    /// Its two arguments are halves of a channel used for communication to send and receive Netwayste events.
+    /// `server_str` is the host name or address of the server, optionally followed by a port. The
+    /// other two arguments are halves of a channel used for communication to send and receive
+    /// Netwayste events. Runs until `channel_from_conwayste` is closed, or until an error.
     pub async fn start_network(
+        mut server_str: String,
         channel_to_conwayste: Fut::channel::mpsc::Sender<NetwaysteEvent>,
         mut channel_from_conwayste: Fut::channel::mpsc::UnboundedReceiver<NetwaysteEvent>,
     ) -> Result<(), Box<dyn std::error::Error + 'static>> {
         let has_port_re = Regex::new(r":\d{1,5}$").unwrap(); // match a colon followed by number up to 5 digits (16-bit port)
-        let mut server_str = env::args().nth(1).unwrap_or("localhost".to_owned());
 
-        // if no port, add the default port
-        if !has_port_re.is_match(&server_str) {
+        // A bare IP address can't have a port, and an IPv6 one would look like it ended in one
+        if let Ok(ip) = server_str.parse::<IpAddr>() {
+            server_str = SocketAddr::new(ip, DEFAULT_PORT).to_string();
+        } else if !has_port_re.is_match(&server_str) {
+            // if no port, add the default port
             debug!("Appending default port to {:?}", server_str);
             server_str = format!("{}:{}", server_str, DEFAULT_PORT);
         }
 
-        let addr_iter = tokio::net::lookup_host(server_str).await?;
+        let addr_iter = tokio::net::lookup_host(&server_str).await?;
         let addr_vec: Vec<SocketAddr> = addr_iter.collect();
 
         let addresses_resolved = addr_vec.len();
         if addresses_resolved == 0 {
-            error!("DNS resolution found 0 addresses");
-            exit(1);
+            return Err(format!("DNS resolution of {:?} found 0 addresses", server_str).into());
         }
 
-        // TODO: support IPv6
-        // filter out IPv6
-        let v4_addr_vec: Vec<_> = addr_vec.into_iter().filter(|addr| addr.is_ipv4()).collect();
-        if v4_addr_vec.len() < addresses_resolved {
-            warn!(
-                "Filtered out {} IPv6 addresses -- IPv6 is not implemented.",
-                addresses_resolved - v4_addr_vec.len()
-            );
-        }
-        if v4_addr_vec.len() > 1 {
+        if addresses_resolved > 1 {
             // This is probably not the best option -- could pick based on latency time, random choice,
             // and could also try other ones on connection failure.
             warn!(
                 "Multiple ({:?}) addresses returned; arbitrarily picking the first one.",
-                v4_addr_vec.len()
+                addresses_resolved
             );
         }
-
-        let addr = v4_addr_vec[0];
+        let addr = addr_vec[0];
 
         trace!("Connecting to {:?}", addr);
 
-        // Unwrap ok because bind will abort if unsuccessful
-        let udp = bind(Some("0.0.0.0"), Some(0)).await.unwrap_or_else(|e| {
-            error!("Error while trying to bind UDP socket: {:?}", e);
-            exit(1)
-        });
+        // Bind to an address of the same family as the server's, on any port
+        let local_ip: IpAddr = if addr.is_ipv4() {
+            Ipv4Addr::UNSPECIFIED.into()
+        } else {
+            Ipv6Addr::UNSPECIFIED.into()
+        };
+        // This runs inside the game, so return errors rather than exiting
+        let udp = bind_addr(SocketAddr::new(local_ip, 0))
+            .await
+            .map_err(|e| format!("Error while trying to bind UDP socket: {}", e))?;
 
         let local_addr = udp.local_addr()?;
 
@@ -511,7 +774,7 @@ impl ClientNetState {
         loop {
             select! {
                 _ = tick_interval_stream.select_next_some() => {
-                    if let Some(keep_alive_pkt) = client_state.handle_tick_event() {
+                    if let Some(keep_alive_pkt) = client_state.handle_tick_event().await {
                         // Unwrap safe b/c the connection to server is active
                         udp_sink.send((keep_alive_pkt, client_state.server_address.unwrap())).await?;
                     }
@@ -523,20 +786,40 @@ impl ClientNetState {
                     }
                 },
                 addr_packet_result = udp_stream.select_next_some() => {
-                    if let Ok((packet, addr)) = addr_packet_result {
-                        let responses = client_state.handle_incoming_event(packet, addr).await;
-                        for response in responses {
-                            udp_sink.send(response).await?;
+                    match addr_packet_result {
+                        Ok((packets, addr)) => {
+                            for packet in packets {
+                                let responses = client_state.handle_incoming_event(packet, addr).await;
+                                for response in responses {
+                                    udp_sink.send(response).await?;
+                                }
+                            }
                         }
+                        // e.g. the server speaks a different protocol version
+                        Err(e) => warn!("Dropped a datagram that could not be decoded: {}", e),
                     }
                 },
-                netwayste_request = channel_from_conwayste.select_next_some() => {
+                opt_request = channel_from_conwayste.next() => {
+                    let netwayste_request = match opt_request {
+                        Some(request) => request,
+                        None => {
+                            // Whoever started us has hung up, so there is no one left to talk for
+                            info!("Request channel closed; stopping the network layer");
+                            return Ok(());
+                        }
+                    };
                     if let NetwaysteEvent::GetStatus(ping) = netwayste_request {
                         let server_address = client_state.server_address.unwrap().clone();
 
                         client_state.latency_filter.start();
 
                         udp_sink.send((Packet::GetStatus { ping },server_address)).await?;
+                    } else if let NetwaysteEvent::Ping = netwayste_request {
+                        let server_address = client_state.server_address.unwrap().clone();
+
+                        udp_sink.send((client_state.ping_packet(), server_address)).await?;
+                    } else if client_state.connection_status() == ConnectionStatus::Disconnected {
+                        client_state.refuse_while_disconnected(netwayste_request).await;
                     } else {
                         let action: RequestAction = NetwaysteEvent::build_request_action_from_netwayste_event(
                             netwayste_request,
@@ -549,6 +832,9 @@ impl ClientNetState {
                                     // TODO: Have the conwayste client provide this
                                     client_state.name = Some(name.to_owned());
                                 },
+                                RequestAction::RequestFullState => {
+                                    client_state.handle_request_full_state();
+                                },
                                 _ => {}
                             }
 
@@ -564,6 +850,78 @@ impl ClientNetState {
     }
 }
 
+/// A server that answered a discovery broadcast.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiscoveredServer {
+    pub addr:         SocketAddr,
+    pub name:         String,
+    pub version:      String,
+    pub player_count: u64,
+    pub room_count:   u64,
+}
+
+/// Broadcasts a Discover request to `port` on the local network and collects the servers that
+/// answer within `wait`, in the order they answered. Only servers started with --discoverable
+/// answer. This needs no connection, so it can run before the player has picked a server.
+pub async fn discover_servers(
+    port: u16,
+    wait: Duration,
+) -> Result<Vec<DiscoveredServer>, Box<dyn std::error::Error + 'static>> {
+    let udp = bind_addr(SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0)).await?;
+    udp.set_broadcast(true)?;
+    let (mut udp_sink, mut udp_stream) = UdpFramed::new(udp, NetwaystePacketCodec).split();
+
+    let discover = Packet::Request {
+        sequence:     0,
+        response_ack: None,
+        cookie:       None,
+        action:       RequestAction::Discover,
+    };
+    udp_sink
+        .send((discover, SocketAddr::new(Ipv4Addr::BROADCAST.into(), port)))
+        .await?;
+
+    let mut servers: Vec<DiscoveredServer> = vec![];
+    let deadline = TokioTime::Instant::now() + wait;
+    while let Ok(Some(received)) = TokioTime::timeout_at(deadline, udp_stream.next()).await {
+        let (packets, addr) = match received {
+            Ok(received) => received,
+            Err(e) => {
+                // e.g. a server speaking another protocol version
+                debug!("Dropped a discovery answer that could not be decoded: {}", e);
+                continue;
+            }
+        };
+        for packet in packets {
+            if let Packet::Response {
+                code:
+                    ResponseCode::ServerInfo {
+                        server_name,
+                        server_version,
+                        player_count,
+                        room_count,
+                    },
+                ..
+            } = packet
+            {
+                // A server with more than one address on the LAN may answer more than once
+                if servers.iter().any(|server| server.addr == addr) {
+                    continue;
+                }
+                debug!("[{}] Discovered server {:?}", addr, server_name);
+                servers.push(DiscoveredServer {
+                    addr,
+                    name: server_name,
+                    version: server_version,
+                    player_count,
+                    room_count,
+                });
+            }
+        }
+    }
+    Ok(servers)
+}
+
 /*
 (conwayste_event) = conwayste_stream.select_next_some() => {
     if let NetwaysteEvent::GetStatus(ping) = netwayste_request {
@@ -0,0 +1,225 @@
+/*
+ * Copyright (C) 2021 The Conwayste Developers
+ *
+ * This program is free software: you can redistribute it and/or modify it
+ * under the terms of the GNU General Public License as published by the Free
+ * Software Foundation, either version 3 of the License, or (at your option)
+ * any later version.
+ *
+ * This program is distributed in the hope that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of  MERCHANTABILITY or
+ * FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for
+ * more details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! The server's config file, given with `--config`. It is TOML, with the same keys as the
+//! command-line options, for example:
+//!
+//! ```toml
+//! listen = "::"
+//! port = 2016
+//! tick-ms = 10
+//! max-players = 500
+//! discoverable = true
+//! ```
+//!
+//! An option given on the command line (or through its environment variable) wins over the file.
+//! Values are checked the same way wherever they came from.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use clap::ArgMatches;
+use toml::Value;
+
+/// Whether a key takes a value, or is a switch that is either on or off.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Value,
+    Switch,
+}
+
+/// Every key a config file can have, with the name of the command-line option it stands for. The
+/// file says `listen` like the option does, where the option's clap name is `address`.
+const KEYS: &[(&str, &str, Kind)] = &[
+    ("listen", "address", Kind::Value),
+    ("port", "port", Kind::Value),
+    ("name", "name", Kind::Value),
+    ("public-address", "public-address", Kind::Value),
+    ("max-chat-chars", "max-chat-chars", Kind::Value),
+    ("tick-ms", "tick-ms", Kind::Value),
+    ("events-file", "events-file", Kind::Value),
+    ("admin-token", "admin-token", Kind::Value),
+    ("ban-file", "ban-file", Kind::Value),
+    ("ban-response", "ban-response", Kind::Value),
+    ("discoverable", "discoverable", Kind::Switch),
+    ("full-teams", "full-teams", Kind::Switch),
+    ("fog-radius", "fog-radius", Kind::Value),
+    ("session-mins", "session-mins", Kind::Value),
+    ("timeout-secs", "timeout-secs", Kind::Value),
+    ("lobby-chat-rate", "lobby-chat-rate", Kind::Value),
+    ("max-players", "max-players", Kind::Value),
+    ("max-rooms", "max-rooms", Kind::Value),
+    ("registrar-url", "registrar-url", Kind::Value),
+];
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("could not read it: {0}")]
+    Io(#[from] io::Error),
+    #[error("it is not valid TOML: {0}")]
+    Toml(#[from] toml::de::Error),
+    #[error("unknown key {key:?}")]
+    UnknownKey { key: String },
+    #[error("{key:?} should be {expected}")]
+    WrongType { key: String, expected: &'static str },
+}
+
+/// The settings from a config file, keyed by the clap name of the option each one stands for.
+#[derive(Debug, Default)]
+pub struct ServerConfig {
+    values:   HashMap<&'static str, String>,
+    switches: HashMap<&'static str, bool>,
+}
+
+impl ServerConfig {
+    /// Reads the config file at `path`. Unlike a ban file, it has to be there, since someone asked
+    /// for it.
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        ServerConfig::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Parses the contents of a config file. Values are kept as text, to be checked by whatever
+    /// checks the command-line option; only whether each is a value or a switch is checked here.
+    pub fn parse(contents: &str) -> Result<Self, ConfigError> {
+        let table: toml::value::Table = toml::from_str(contents)?;
+        let mut config = ServerConfig::default();
+        for (key, value) in table {
+            let (_, option, kind) = match KEYS.iter().find(|(file_key, _, _)| *file_key == key) {
+                Some(entry) => *entry,
+                None => return Err(ConfigError::UnknownKey { key }),
+            };
+            match (kind, value) {
+                (Kind::Value, Value::String(s)) => {
+                    config.values.insert(option, s);
+                }
+                (Kind::Value, Value::Integer(i)) => {
+                    config.values.insert(option, i.to_string());
+                }
+                (Kind::Value, _) => {
+                    return Err(ConfigError::WrongType {
+                        key,
+                        expected: "a string or a number",
+                    })
+                }
+                (Kind::Switch, Value::Boolean(b)) => {
+                    config.switches.insert(option, b);
+                }
+                (Kind::Switch, _) => {
+                    return Err(ConfigError::WrongType {
+                        key,
+                        expected: "true or false",
+                    })
+                }
+            }
+        }
+        Ok(config)
+    }
+
+    /// The file's value for the option with this clap name, if it has one.
+    pub fn value_of(&self, option: &str) -> Option<&str> {
+        self.values.get(option).map(|s| s.as_str())
+    }
+
+    /// Whether the file turns on the switch with this clap name.
+    pub fn is_present(&self, option: &str) -> bool {
+        self.switches.get(option).copied().unwrap_or(false)
+    }
+}
+
+/// The server's settings: the command line, and then the config file for anything it leaves out.
+pub struct Settings<'a> {
+    matches: ArgMatches<'a>,
+    file:    ServerConfig,
+}
+
+impl<'a> Settings<'a> {
+    pub fn new(matches: ArgMatches<'a>, file: ServerConfig) -> Self {
+        Settings { matches, file }
+    }
+
+    pub fn value_of(&self, option: &str) -> Option<&str> {
+        self.matches.value_of(option).or_else(|| self.file.value_of(option))
+    }
+
+    pub fn is_present(&self, option: &str) -> bool {
+        self.matches.is_present(option) || self.file.is_present(option)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let config = ServerConfig::parse(
+            r#"
+            listen = "127.0.0.1"
+            port = 2016
+            max-players = 50
+            discoverable = true
+            full-teams = false
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.value_of("address"), Some("127.0.0.1"));
+        assert_eq!(config.value_of("port"), Some("2016"));
+        assert_eq!(config.value_of("max-players"), Some("50"));
+        assert_eq!(config.value_of("max-rooms"), None);
+        assert!(config.is_present("discoverable"));
+        assert!(!config.is_present("full-teams"));
+        assert!(!config.is_present("nonexistent"));
+    }
+
+    #[test]
+    fn test_empty_file() {
+        let config = ServerConfig::parse("").unwrap();
+        assert_eq!(config.value_of("address"), None);
+        assert!(!config.is_present("discoverable"));
+    }
+
+    #[test]
+    fn test_bad_files() {
+        assert!(matches!(ServerConfig::parse("port = "), Err(ConfigError::Toml(_))));
+        assert!(matches!(
+            ServerConfig::parse("max-slots = 3"),
+            Err(ConfigError::UnknownKey { key }) if key == "max-slots"
+        ));
+        // clap names are not keys; the file says what the command line says
+        assert!(matches!(
+            ServerConfig::parse("address = \"::\""),
+            Err(ConfigError::UnknownKey { .. })
+        ));
+        assert!(matches!(
+            ServerConfig::parse("discoverable = \"yes\""),
+            Err(ConfigError::WrongType { key, .. }) if key == "discoverable"
+        ));
+        assert!(matches!(
+            ServerConfig::parse("tick-ms = 1.5"),
+            Err(ConfigError::WrongType { key, .. }) if key == "tick-ms"
+        ));
+    }
+
+    #[test]
+    fn test_missing_file() {
+        let path = std::env::temp_dir().join(format!("conwayste-server-{}.toml", std::process::id()));
+        let _ = fs::remove_file(&path);
+        assert!(matches!(ServerConfig::load(&path), Err(ConfigError::Io(_))));
+    }
+}
@@ -0,0 +1,315 @@
+/*
+ * Copyright (C) 2021 The Conwayste Developers
+ *
+ * This program is free software: you can redistribute it and/or modify it
+ * under the terms of the GNU General Public License as published by the Free
+ * Software Foundation, either version 3 of the License, or (at your option)
+ * any later version.
+ *
+ * This program is distributed in the hope that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of  MERCHANTABILITY or
+ * FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for
+ * more details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Room lifecycle events for outside observers, like a status page. Each event is written as one
+//! line of JSON to an `EventSink`. The server only ever queues events; a separate thread does the
+//! writing, and if it falls behind, events are dropped rather than holding up the server. Every
+//! event gets the next id whether or not it is dropped, so a consumer can tell when it missed some.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::thread;
+
+use chrono::{SecondsFormat, Utc};
+use futures::channel::mpsc::{self, Receiver, Sender};
+use futures::executor::block_on_stream;
+use serde::Serialize;
+
+/// How many events can wait to be written before more are dropped.
+pub const EVENT_BUFFER: usize = 1024;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerEvent {
+    /// The first event after the server starts, listing the rooms it starts with.
+    ServerStarted {
+        server_name: String,
+        rooms:       Vec<String>,
+    },
+    RoomCreated {
+        room: String,
+    },
+    /// The room's owner closed it, or everyone left it. Anyone still in it is back in the lobby.
+    RoomClosed {
+        room: String,
+    },
+    /// The room's owner disconnected, and `owner`, who had been in the room the longest, took over.
+    OwnerChanged {
+        room:  String,
+        owner: String,
+    },
+    PlayerJoined {
+        room:   String,
+        player: String,
+    },
+    PlayerLeft {
+        room:   String,
+        player: String,
+    },
+    GameStarted {
+        room:    String,
+        players: usize,
+    },
+    GameEnded {
+        room: String,
+    },
+    /// How many chat messages were sent in the room over the last `period_secs`. Sent for every
+    /// room once per period, even if no one chatted.
+    ChatVolume {
+        room:        String,
+        messages:    u64,
+        period_secs: u64,
+    },
+}
+
+/// An event as it is written out.
+#[derive(Debug, Clone, Serialize)]
+pub struct EventRecord {
+    pub id:        u64,
+    pub timestamp: String, // RFC 3339, in UTC
+    #[serde(flatten)]
+    pub event:     ServerEvent,
+}
+
+/// Somewhere to write events to. Only an appended file for now.
+pub trait EventSink: Send {
+    /// Writes `line`, which has no line ending, as one line.
+    fn write_line(&mut self, line: &str) -> io::Result<()>;
+}
+
+pub struct FileSink {
+    file: File,
+}
+
+impl FileSink {
+    /// Opens `path` for appending, creating it if needed.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(FileSink { file })
+    }
+}
+
+impl EventSink for FileSink {
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+        // One write per line, so that a reader tailing the file never sees half of one
+        self.file.write_all(format!("{}\n", line).as_bytes())
+    }
+}
+
+/// The server's end of the event stream. A disabled emitter ignores every event.
+pub struct EventEmitter {
+    sender:  Option<Sender<EventRecord>>,
+    next_id: u64,
+    dropped: u64, // events dropped because the writer was too far behind
+}
+
+impl EventEmitter {
+    pub fn disabled() -> Self {
+        EventEmitter {
+            sender:  None,
+            next_id: 0,
+            dropped: 0,
+        }
+    }
+
+    /// An emitter and the receiving end of its queue, which holds `buffer` + 1 events.
+    pub fn channel(buffer: usize) -> (Self, Receiver<EventRecord>) {
+        let (sender, receiver) = mpsc::channel(buffer);
+        let emitter = EventEmitter {
+            sender:  Some(sender),
+            next_id: 0,
+            dropped: 0,
+        };
+        (emitter, receiver)
+    }
+
+    /// Starts a thread that writes events to `sink`, and returns the emitter feeding it.
+    pub fn spawn_writer(mut sink: Box<dyn EventSink>) -> Self {
+        let (emitter, receiver) = EventEmitter::channel(EVENT_BUFFER);
+        thread::spawn(move || {
+            for record in block_on_stream(receiver) {
+                let result = serde_json::to_string(&record)
+                    .map_err(io::Error::from)
+                    .and_then(|line| sink.write_line(&line));
+                if let Err(e) = result {
+                    error!("Could not write event {}: {}", record.id, e);
+                }
+            }
+        });
+        emitter
+    }
+
+    #[cfg(test)]
+    pub fn is_enabled(&self) -> bool {
+        self.sender.is_some()
+    }
+
+    /// The number of events dropped so far because the writer couldn't keep up.
+    #[cfg(test)]
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Queues `event` to be written, or drops it if the queue is full. Never blocks.
+    pub fn emit(&mut self, event: ServerEvent) {
+        let sender = match self.sender {
+            Some(ref mut sender) => sender,
+            None => return,
+        };
+        let record = EventRecord {
+            id: self.next_id,
+            timestamp: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            event,
+        };
+        self.next_id += 1;
+        if let Err(e) = sender.try_send(record) {
+            if e.is_full() {
+                self.dropped += 1;
+                // Logged less and less often, so that the log doesn't get flooded too
+                if self.dropped.is_power_of_two() {
+                    warn!(
+                        "Dropped {} events so far because they aren't being written fast enough",
+                        self.dropped
+                    );
+                }
+            } else {
+                error!("Event writer has stopped; no more events will be written");
+                self.sender = None;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn record_json(event: ServerEvent) -> serde_json::Value {
+        let record = EventRecord {
+            id: 7,
+            timestamp: "2021-06-01T12:00:00.000Z".to_owned(),
+            event,
+        };
+        serde_json::to_value(&record).unwrap()
+    }
+
+    #[test]
+    fn test_event_json() {
+        let room = || "general".to_owned();
+        let player = || "Gosper".to_owned();
+        let cases = vec![
+            (
+                ServerEvent::ServerStarted {
+                    server_name: "Leto II".to_owned(),
+                    rooms:       vec![room()],
+                },
+                json!({"type": "server_started", "server_name": "Leto II", "rooms": ["general"]}),
+            ),
+            (
+                ServerEvent::RoomCreated { room: room() },
+                json!({"type": "room_created", "room": "general"}),
+            ),
+            (
+                ServerEvent::RoomClosed { room: room() },
+                json!({"type": "room_closed", "room": "general"}),
+            ),
+            (
+                ServerEvent::OwnerChanged {
+                    room:  room(),
+                    owner: player(),
+                },
+                json!({"type": "owner_changed", "room": "general", "owner": "Gosper"}),
+            ),
+            (
+                ServerEvent::PlayerJoined {
+                    room:   room(),
+                    player: player(),
+                },
+                json!({"type": "player_joined", "room": "general", "player": "Gosper"}),
+            ),
+            (
+                ServerEvent::PlayerLeft {
+                    room:   room(),
+                    player: player(),
+                },
+                json!({"type": "player_left", "room": "general", "player": "Gosper"}),
+            ),
+            (
+                ServerEvent::GameStarted {
+                    room:    room(),
+                    players: 2,
+                },
+                json!({"type": "game_started", "room": "general", "players": 2}),
+            ),
+            (
+                ServerEvent::GameEnded { room: room() },
+                json!({"type": "game_ended", "room": "general"}),
+            ),
+            (
+                ServerEvent::ChatVolume {
+                    room:        room(),
+                    messages:    12,
+                    period_secs: 60,
+                },
+                json!({"type": "chat_volume", "room": "general", "messages": 12, "period_secs": 60}),
+            ),
+        ];
+        for (event, mut expected) in cases {
+            let fields = expected.as_object_mut().unwrap();
+            fields.insert("id".to_owned(), json!(7));
+            fields.insert("timestamp".to_owned(), json!("2021-06-01T12:00:00.000Z"));
+            assert_eq!(record_json(event), expected);
+        }
+    }
+
+    #[test]
+    fn test_emit_drops_when_full() {
+        // one slot for the buffer, plus one for the sender
+        let (mut emitter, mut receiver) = EventEmitter::channel(1);
+        for _ in 0..5 {
+            emitter.emit(ServerEvent::RoomCreated { room: "a".to_owned() });
+        }
+        assert_eq!(emitter.dropped(), 3);
+        assert_eq!(receiver.try_next().unwrap().unwrap().id, 0);
+        assert_eq!(receiver.try_next().unwrap().unwrap().id, 1);
+        assert!(receiver.try_next().is_err()); // empty, but still open
+
+        // the dropped events used up ids 2 to 4, so the consumer can see the gap
+        emitter.emit(ServerEvent::RoomCreated { room: "b".to_owned() });
+        assert_eq!(receiver.try_next().unwrap().unwrap().id, 5);
+        assert_eq!(emitter.dropped(), 3);
+    }
+
+    #[test]
+    fn test_emit_after_writer_stops() {
+        let (mut emitter, receiver) = EventEmitter::channel(1);
+        drop(receiver);
+        emitter.emit(ServerEvent::RoomCreated { room: "a".to_owned() });
+        assert!(!emitter.is_enabled());
+        assert_eq!(emitter.dropped(), 0);
+    }
+
+    #[test]
+    fn test_disabled_emitter() {
+        let mut emitter = EventEmitter::disabled();
+        emitter.emit(ServerEvent::RoomCreated { room: "a".to_owned() });
+        assert!(!emitter.is_enabled());
+        assert_eq!(emitter.dropped(), 0);
+    }
+}
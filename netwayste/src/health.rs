@@ -0,0 +1,251 @@
+/*
+ * Copyright (C) 2021 The Conwayste Developers
+ *
+ * This program is free software: you can redistribute it and/or modify it
+ * under the terms of the GNU General Public License as published by the Free
+ * Software Foundation, either version 3 of the License, or (at your option)
+ * any later version.
+ *
+ * This program is distributed in the hope that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of  MERCHANTABILITY or
+ * FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for
+ * more details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! How well the client's connection to the server is doing. The server sends a keep alive every
+//! second, so a client that hears nothing for a while knows something is wrong: first the
+//! connection is degraded, then it is lost, and then the client tries to log in again, waiting
+//! longer and longer between attempts. `ConnectionHealth` only decides what state the connection
+//! is in; it is fed events and told the time, and doesn't send anything itself.
+
+use std::time::{Duration, Instant};
+
+use crate::net::ConnectionStatus;
+
+/// Missed keep alives in a row before the connection is degraded.
+pub const DEGRADED_AFTER_MISSED: u32 = 2;
+/// Missed keep alives in a row before the connection is considered lost.
+pub const DISCONNECTED_AFTER_MISSED: u32 = 5;
+/// How long to wait after the first failed reconnect attempt. Doubles after each further failure.
+pub const RECONNECT_BACKOFF_BASE: Duration = Duration::from_secs(1);
+/// The longest wait between reconnect attempts.
+pub const RECONNECT_BACKOFF_MAX: Duration = Duration::from_secs(30);
+/// How long a reconnect attempt has to get an answer before it counts as failed.
+pub const RECONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum HealthEvent {
+    ResponseReceived,
+    KeepAliveMissed,
+    ReconnectStarted,
+    ReconnectSucceeded,
+    ReconnectFailed,
+}
+
+#[derive(Debug)]
+pub struct ConnectionHealth {
+    status:       ConnectionStatus,
+    missed:       u32,             // keep alives missed in a row
+    failures:     u32,             // failed reconnect attempts since the connection was lost
+    next_attempt: Option<Instant>, // when to try reconnecting next, while disconnected
+    attempt:      Option<Instant>, // when the reconnect attempt in progress started, if any
+}
+
+impl ConnectionHealth {
+    pub fn new() -> Self {
+        ConnectionHealth {
+            status:       ConnectionStatus::Connected,
+            missed:       0,
+            failures:     0,
+            next_attempt: None,
+            attempt:      None,
+        }
+    }
+
+    pub fn status(&self) -> ConnectionStatus {
+        self.status
+    }
+
+    /// Updates the state for `event`, which happened at `now`. Returns the new status if it
+    /// changed.
+    pub fn handle(&mut self, event: HealthEvent, now: Instant) -> Option<ConnectionStatus> {
+        let before = self.status;
+        match (event, self.status) {
+            // Only logging in again gets us out of Disconnected; the server may have forgotten us
+            (HealthEvent::ResponseReceived, ConnectionStatus::Disconnected) => {}
+            (HealthEvent::ResponseReceived, _) => {
+                self.missed = 0;
+                self.status = ConnectionStatus::Connected;
+            }
+            (HealthEvent::KeepAliveMissed, ConnectionStatus::Disconnected) => {}
+            (HealthEvent::KeepAliveMissed, _) => {
+                self.missed += 1;
+                if self.missed >= DISCONNECTED_AFTER_MISSED {
+                    self.status = ConnectionStatus::Disconnected;
+                    self.failures = 0;
+                    self.next_attempt = Some(now);
+                    self.attempt = None;
+                } else if self.missed >= DEGRADED_AFTER_MISSED {
+                    self.status = ConnectionStatus::Degraded;
+                }
+            }
+            (HealthEvent::ReconnectStarted, ConnectionStatus::Disconnected) => {
+                self.attempt = Some(now);
+                self.next_attempt = None;
+            }
+            (HealthEvent::ReconnectSucceeded, _) => {
+                self.status = ConnectionStatus::Connected;
+                self.missed = 0;
+                self.failures = 0;
+                self.next_attempt = None;
+                self.attempt = None;
+            }
+            (HealthEvent::ReconnectFailed, ConnectionStatus::Disconnected) => {
+                self.attempt = None;
+                self.next_attempt = Some(now + self.backoff());
+                self.failures += 1;
+            }
+            (HealthEvent::ReconnectStarted, _) | (HealthEvent::ReconnectFailed, _) => {
+                warn!("Ignoring {:?} while {:?}", event, self.status);
+            }
+        }
+        if self.status != before {
+            Some(self.status)
+        } else {
+            None
+        }
+    }
+
+    /// Whether it is time for another reconnect attempt.
+    pub fn reconnect_due(&self, now: Instant) -> bool {
+        match self.next_attempt {
+            Some(next_attempt) => self.attempt.is_none() && now >= next_attempt,
+            None => false,
+        }
+    }
+
+    /// Whether the reconnect attempt in progress has gone unanswered for too long.
+    pub fn reconnect_timed_out(&self, now: Instant) -> bool {
+        match self.attempt {
+            Some(started) => now >= started + RECONNECT_TIMEOUT,
+            None => false,
+        }
+    }
+
+    /// How long to wait before the next attempt, after the one in progress fails.
+    fn backoff(&self) -> Duration {
+        // Past 2^16 seconds, the cap has long been reached
+        let factor = 1u32 << self.failures.min(16);
+        (RECONNECT_BACKOFF_BASE * factor).min(RECONNECT_BACKOFF_MAX)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(s: u64) -> Duration {
+        Duration::from_secs(s)
+    }
+
+    #[test]
+    fn test_flap() {
+        let start = Instant::now();
+        let mut health = ConnectionHealth::new();
+        assert_eq!(health.status(), ConnectionStatus::Connected);
+        assert_eq!(health.handle(HealthEvent::ResponseReceived, start), None);
+
+        // healthy -> degraded
+        assert_eq!(health.handle(HealthEvent::KeepAliveMissed, start + secs(1)), None);
+        assert_eq!(
+            health.handle(HealthEvent::KeepAliveMissed, start + secs(2)),
+            Some(ConnectionStatus::Degraded)
+        );
+        assert!(!health.reconnect_due(start + secs(2)));
+
+        // degraded -> disconnected, and a reconnect attempt is due right away
+        for s in 3..5 {
+            assert_eq!(health.handle(HealthEvent::KeepAliveMissed, start + secs(s)), None);
+        }
+        let lost = start + secs(5);
+        assert_eq!(
+            health.handle(HealthEvent::KeepAliveMissed, lost),
+            Some(ConnectionStatus::Disconnected)
+        );
+        assert!(health.reconnect_due(lost));
+
+        // a late response doesn't mean we are logged in again
+        assert_eq!(health.handle(HealthEvent::ResponseReceived, lost), None);
+        assert_eq!(health.handle(HealthEvent::KeepAliveMissed, lost), None);
+
+        // the first attempt goes unanswered
+        health.handle(HealthEvent::ReconnectStarted, lost);
+        assert!(!health.reconnect_due(lost));
+        assert!(!health.reconnect_timed_out(lost + RECONNECT_TIMEOUT - Duration::from_millis(1)));
+        let failed = lost + RECONNECT_TIMEOUT;
+        assert!(health.reconnect_timed_out(failed));
+        assert_eq!(health.handle(HealthEvent::ReconnectFailed, failed), None);
+        assert!(!health.reconnect_timed_out(failed));
+        assert!(!health.reconnect_due(failed + RECONNECT_BACKOFF_BASE - Duration::from_millis(1)));
+        let retry = failed + RECONNECT_BACKOFF_BASE;
+        assert!(health.reconnect_due(retry));
+
+        // disconnected -> reconnected
+        health.handle(HealthEvent::ReconnectStarted, retry);
+        assert_eq!(
+            health.handle(HealthEvent::ReconnectSucceeded, retry + secs(1)),
+            Some(ConnectionStatus::Connected)
+        );
+        assert!(!health.reconnect_due(retry + secs(100)));
+
+        // and the count of missed keep alives starts over
+        assert_eq!(health.handle(HealthEvent::KeepAliveMissed, retry + secs(2)), None);
+        assert_eq!(health.status(), ConnectionStatus::Connected);
+    }
+
+    #[test]
+    fn test_response_recovers_from_degraded() {
+        let now = Instant::now();
+        let mut health = ConnectionHealth::new();
+        for _ in 0..DEGRADED_AFTER_MISSED {
+            health.handle(HealthEvent::KeepAliveMissed, now);
+        }
+        assert_eq!(health.status(), ConnectionStatus::Degraded);
+        assert_eq!(
+            health.handle(HealthEvent::ResponseReceived, now),
+            Some(ConnectionStatus::Connected)
+        );
+
+        // one response resets the count, so it takes just as many misses to degrade again
+        for _ in 1..DEGRADED_AFTER_MISSED {
+            health.handle(HealthEvent::KeepAliveMissed, now);
+        }
+        assert_eq!(health.status(), ConnectionStatus::Connected);
+    }
+
+    #[test]
+    fn test_backoff_doubles_up_to_the_cap() {
+        let mut now = Instant::now();
+        let mut health = ConnectionHealth::new();
+        for _ in 0..DISCONNECTED_AFTER_MISSED {
+            health.handle(HealthEvent::KeepAliveMissed, now);
+        }
+        let mut waits = vec![];
+        for _ in 0..8 {
+            assert!(health.reconnect_due(now));
+            health.handle(HealthEvent::ReconnectStarted, now);
+            now += RECONNECT_TIMEOUT;
+            health.handle(HealthEvent::ReconnectFailed, now);
+            let mut wait = secs(0);
+            while !health.reconnect_due(now + wait) {
+                wait += Duration::from_millis(500);
+            }
+            waits.push(wait.as_secs());
+            now += wait;
+        }
+        assert_eq!(waits, vec![1, 2, 4, 8, 16, 30, 30, 30]);
+    }
+}
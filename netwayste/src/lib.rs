@@ -21,6 +21,8 @@ extern crate bincode;
 extern crate chrono;
 extern crate env_logger;
 extern crate futures;
+// Log lines about one peer's traffic start with its address in brackets, like
+// "[1.2.3.4:2016] Retransmitting ...", so that one player's can be picked out with grep.
 #[macro_use]
 extern crate log;
 extern crate clap;
@@ -30,7 +32,9 @@ extern crate serde;
 
 #[macro_use]
 pub mod net;
+pub mod bot;
 pub mod client;
+pub mod health;
 pub mod utils;
 
 #[cfg(test)]
@@ -17,10 +17,10 @@
  * this program.  If not, see <http://www.gnu.org/licenses/>.
  */
 
-use std::cmp::{Ordering, PartialEq, PartialOrd};
+use std::cmp::{self, Ordering, PartialEq, PartialOrd};
 use std::collections::VecDeque;
 use std::fmt::Debug;
-use std::net::{self, SocketAddr};
+use std::net::{self, IpAddr, SocketAddr};
 use std::{
     fmt, io, result, str,
     time::{Duration, Instant},
@@ -28,20 +28,30 @@ use std::{
 
 use crate::utils::PingPong;
 
-use bincode::{deserialize, serialize};
+use conway::rle::Pattern;
+use conway::universe::{CellState, GenStateDiff};
+
+use bincode::{serialize, Options};
 use bytes::{Buf, BytesMut};
 use semver::{SemVerError, Version};
 use serde::{Deserialize, Serialize};
+use socket2::{Domain, Protocol, Socket, Type};
 use tokio::net::UdpSocket;
 use tokio_util::codec::{Decoder, Encoder};
 
+mod v1;
+
 pub const VERSION: &'static str = env!("CARGO_PKG_VERSION");
 pub const DEFAULT_HOST: &str = "0.0.0.0";
 pub const DEFAULT_PORT: u16 = 2016;
 pub const TIMEOUT_IN_SECONDS: u64 = 5;
+pub const MAX_CHAT_MESSAGE_CHARS: usize = 256; // counted in chars, not bytes
+pub const UDP_MTU_SIZE: usize = 1440; // bytes; leaves room for IP and UDP headers within a typical MTU of 1500
+pub const MAX_DIFF_PART_CHARS: usize = 1024; // per GenStateDiffPart, so that an Update packet fits in UDP_MTU_SIZE
+pub const MAX_DIFF_PARTS: usize = 32; // a GenPartInfo bitmask has one bit per part
 pub const NETWORK_QUEUE_LENGTH: usize = 600; // spot testing with poor network (~675 cmds) showed a max of ~512 length
                                              // keep this for now until the performance issues are resolved
-const RETRANSMISSION_THRESHOLD_IN_MS: Duration = Duration::from_millis(400);
+pub const RETRANSMISSION_THRESHOLD_IN_MS: Duration = Duration::from_millis(400);
 const RETRY_THRESHOLD: usize = 2; //
 const RETRY_AGGRESSIVE_THRESHOLD: usize = 5;
 const RETRANSMISSION_COUNT: usize = 32; // Testing some ideas out:. Resend length 16x2, 16=libconway::history_size)
@@ -75,6 +85,22 @@ macro_rules! netwayste_send {
 pub enum NetError {
     AddrParseError(net::AddrParseError),
     IoError(io::Error),
+    InvalidBindAddress(String, net::AddrParseError), // attempted host, error
+    BindError(SocketAddr, io::Error),                // attempted address, error
+}
+
+impl fmt::Display for NetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NetError::AddrParseError(e) => write!(f, "{}", e),
+            NetError::IoError(e) => write!(f, "{}", e),
+            NetError::InvalidBindAddress(host, e) => write!(f, "cannot bind to {:?}: {}", host, e),
+            NetError::BindError(addr, e) => write!(f, "cannot bind to {}: {}", addr, e),
+        }
+    }
+}
+
+impl std::error::Error for NetError {
 }
 
 impl From<net::AddrParseError> for NetError {
@@ -110,11 +136,17 @@ pub enum RequestAction {
         message: String,
     },
     ListRooms,
+    // With a password, only players who give the same password can join. Without a team count,
+    // the room has two teams, and without a player limit, it takes as many as the server allows.
     NewRoom {
-        room_name: String,
+        room_name:   String,
+        password:    Option<String>,
+        team_count:  Option<u8>,
+        max_players: Option<u8>,
     },
     JoinRoom {
         room_name: String,
+        password:  Option<String>,
     },
     LeaveRoom,
     // TODO: add support ("auto_match" bool key, see issue #101)
@@ -138,6 +170,61 @@ pub enum RequestAction {
         w: u32,
         h: u32,
     },
+    // Set cells in the running game's universe, as (col, row, new state). Every cell must be in the
+    // player's writable region, and the new state must be Dead or Alive with the player's own
+    // player number. Either all of the cells are set, or none are.
+    PlaceCells {
+        cells: Vec<(usize, usize, CellState)>,
+    },
+    // Start over with the whole universe of the running game, because ours doesn't match the
+    // server's. The next universe update will be a diff from the beginning of time.
+    RequestFullState,
+    // Stop or start getting the chat messages of the player with this name.
+    MutePlayer {
+        name: String,
+    },
+    UnmutePlayer {
+        name: String,
+    },
+    // Server operator commands, only carried out if `token` matches the server's admin token.
+    Admin {
+        token:   String,
+        command: AdminCommand,
+    },
+    // Close a room we created, sending everyone in it back to the lobby.
+    CloseRoom {
+        room_name: String,
+    },
+    // Invite the player with this name to the room we are in. They are sent an Invited response
+    // with a token, which they pass to AcceptInvite or DeclineInvite.
+    InvitePlayer {
+        name: String,
+    },
+    AcceptInvite {
+        token: String,
+    },
+    DeclineInvite {
+        token: String,
+    },
+    // Join this team (numbered from 0) in the room we are in, before its game starts. Players who
+    // never pick a team are put on the smallest ones when the game starts.
+    SetTeam {
+        team: u8,
+    },
+    // Like Connect, this needs no log-in. It is answered with a Pong that echoes `timestamp`, so
+    // that a client can measure its round trip time to a server before joining it.
+    Ping {
+        timestamp: u64,
+    },
+    // Also needs no log-in. Usually broadcast on the LAN to find servers; only servers started
+    // with --discoverable answer, with a ServerInfo.
+    Discover,
+    // Say whether we are ready for the next game in our room. The room's creator starts it with
+    // StartGame once everyone else in the room is ready, and after a countdown, it begins.
+    SetReady {
+        ready: bool,
+    },
+    StartGame,
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
@@ -155,6 +242,44 @@ pub enum ClientOptionValue {
     List { value: Vec<ClientOptionValue> },
 }
 
+/// What an admin can ask the server to do. A ban target is an IP address, or otherwise a player
+/// name.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub enum AdminCommand {
+    Ban { target: String },
+    Unban { target: String },
+    ListBans,
+}
+
+/// Why an invitation could not be sent or accepted.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Copy)]
+pub enum InviteFailure {
+    NoSuchPlayer,     // nobody with that name is connected
+    AlreadyInRoom,    // the player is already in the inviter's room
+    InviterNotInRoom, // only players in a room can invite
+    UnknownInvite,    // no invitation with that token for us; it may have been used or expired
+    Expired,          // too late to accept
+    RoomClosed,       // the room was closed before the invitation was accepted
+    RoomFull,         // the room filled up before the invitation was accepted
+    InviterLeft,      // the inviter left the room before the invitation was accepted
+}
+
+/// Why a SetTeam request was refused.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Copy)]
+pub enum TeamFailure {
+    NotInRoom,                     // only players in a room can pick a team
+    GameRunning,                   // teams are fixed once the game has started
+    NoSuchTeam { team_count: u8 }, // the room's teams are 0 to team_count - 1
+}
+
+/// What became of an invitation, as told to the inviter.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Copy)]
+pub enum InviteAnswer {
+    Accepted,
+    Declined,
+    Expired,
+}
+
 // server response codes -- mostly inspired by https://en.wikipedia.org/wiki/List_of_HTTP_status_codes
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
 pub enum ResponseCode {
@@ -171,10 +296,15 @@ pub enum ResponseCode {
     LeaveRoom, // player has left the room
     PlayerList {
         players: Vec<String>,
+        muted:   Vec<String>,     // the players in `players` whose chat messages are hidden from us
+        teams:   Vec<Option<u8>>, // the team of each of `players`, in the same order; None if not picked yet
     }, // list of players in room or lobby
     RoomList {
         rooms: Vec<RoomList>,
     }, // list of rooms and their statuses
+    CellsPlaced {
+        generation: usize,
+    }, // the cells from a PlaceCells request were set in this generation of the universe
 
     // errors
     BadRequest {
@@ -186,6 +316,10 @@ pub enum ResponseCode {
     TooManyRequests {
         error_msg: String,
     }, // 429
+    ChatThrottled {
+        error_msg:  String,
+        muted_secs: u64, // chat messages are refused for this many more seconds; 0 if not muted
+    }, // a chat message was refused for being too long or one of too many
     ServerError {
         error_msg: String,
     }, // 500
@@ -195,6 +329,81 @@ pub enum ResponseCode {
 
     // Misc.
     KeepAlive, // Server's heart is beating
+
+    // admin
+    BanList {
+        addrs: Vec<String>,
+        names: Vec<String>,
+    }, // in reply to AdminCommand::ListBans
+
+    // invitations; Invited and InviteAnswered are sent without a request
+    Invited {
+        inviter:   String,
+        room_name: String,
+        token:     String,
+    }, // someone wants us in their room
+    InviteFailed {
+        reason: InviteFailure,
+    },
+    InviteAnswered {
+        name:   String,
+        answer: InviteAnswer,
+    }, // what the player we invited did with the invitation
+
+    // teams
+    SetTeamFailed {
+        reason: TeamFailure,
+    },
+    GameStarted {
+        room_name: String,
+        teams:     Vec<(String, u8)>, // (player name, team) for everyone in the game
+    }, // sent without a request to everyone in a room when its game starts
+    GameEnded {
+        room_name:   String,
+        generations: u64,               // how many generations the game ran for
+        results:     Vec<PlayerResult>, // everyone who was in the game when it started
+    }, // sent without a request to everyone in a room when its game ends
+
+    // latency
+    Pong {
+        timestamp: u64,
+    }, // in reply to a Ping, with its timestamp unchanged
+
+    // discovery
+    ServerInfo {
+        server_name:    String,
+        server_version: String,
+        player_count:   u64,
+        room_count:     u64,
+    }, // in reply to a Discover
+
+    // lobby chat
+    LobbyChat {
+        player_name: String,
+        message:     String,
+    }, // sent without a request to everyone else in the lobby when a player there chats
+
+    // rooms
+    RoomNameTaken {
+        room_name: String,
+    }, // in reply to NewRoom, when there is already a room by that name
+    RoomFull {
+        room_name:   String,
+        max_players: u8,
+    }, // in reply to JoinRoom, when the room already has as many players as it takes
+
+    // starting games
+    GameStarting {
+        room_name:    String,
+        seconds_left: u64,
+    }, // sent without a request to everyone in a room each second of the countdown to its game
+    GameStartCancelled {
+        room_name: String,
+    }, // sent without a request when a countdown stops because someone joined, left or isn't ready
+    OwnerChanged {
+        room_name: String,
+        owner:     String,
+    }, // sent without a request to everyone in a room when its owner disconnects and someone takes over
 }
 
 // chat messages sent from server to all clients other than originating client
@@ -280,6 +489,31 @@ pub struct NetRegion {
     height: u32,
 }
 
+impl GameOptions {
+    #[allow(unused)]
+    pub fn new(width: u32, height: u32, history: u16, player_writable: Vec<NetRegion>, fog_radius: u32) -> Self {
+        GameOptions {
+            width,
+            height,
+            history,
+            player_writable,
+            fog_radius,
+        }
+    }
+}
+
+impl NetRegion {
+    #[allow(unused)]
+    pub fn new(left: i32, top: i32, width: u32, height: u32) -> Self {
+        NetRegion {
+            left,
+            top,
+            width,
+            height,
+        }
+    }
+}
+
 // TODO: add support
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
 pub struct PlayerInfo {
@@ -289,6 +523,13 @@ pub struct PlayerInfo {
     index: Option<u64>,
 }
 
+impl PlayerInfo {
+    #[allow(unused)]
+    pub fn new(name: String, index: Option<u64>) -> Self {
+        PlayerInfo { name, index }
+    }
+}
+
 // TODO: add support
 // The server doesn't have to send all GameUpdates to all clients because that would entail keeping
 // them all for the lifetime of the room, and sending that arbitrarily large list to clients upon
@@ -331,15 +572,25 @@ pub enum GameUpdate {
     },
 }
 
-// TODO: add support
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
 pub enum UniUpdate {
     Diff { diff: GenStateDiffPart },
     NoChange,
 }
 
-// TODO: add support
-/// One or more of these can be recombined into a GenStateDiff from the conway crate.
+/// The latest generation of a running game, and the checksum of the universe in that generation as
+/// seen by the player the Update is sent to (see `Universe::checksum`). A client that has applied
+/// this generation but gets a different checksum is out of sync with the server.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct GenChecksum {
+    pub generation: u64,
+    pub checksum:   u64,
+}
+
+/// One or more of these can be recombined into a GenStateDiff from the conway crate. The pattern is
+/// the RLE of the cells that changed between `gen0` and `gen1`, with unchanged cells written as
+/// no-ops, split into parts of at most `MAX_DIFF_PART_CHARS` characters. Each part is sent in its
+/// own Update packet.
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
 pub struct GenStateDiffPart {
     pub part_number:  u8,     // zero-based but less than 32
@@ -349,7 +600,6 @@ pub struct GenStateDiffPart {
     pub pattern_part: String, // concatenated together to form a Pattern
 }
 
-// TODO: add support
 /// GenPartInfo is sent in the UpdateReply to indicate which GenStateDiffParts are needed.
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
 pub struct GenPartInfo {
@@ -358,12 +608,114 @@ pub struct GenPartInfo {
     pub have_bitmask: u32, // bitmask indicating which parts for the specified diff are present; must be less than 1<<total_parts
 }
 
+impl GenStateDiffPart {
+    /// Splits `diff` into parts small enough to each fit in an Update packet. Returns `None` if it
+    /// would take more than `MAX_DIFF_PARTS`.
+    pub fn split(diff: &GenStateDiff) -> Option<Vec<GenStateDiffPart>> {
+        // RLE patterns are ASCII, so splitting by bytes never splits a character
+        let chunks: Vec<&[u8]> = diff.pattern.0.as_bytes().chunks(MAX_DIFF_PART_CHARS).collect();
+        let total_parts = cmp::max(chunks.len(), 1); // an empty pattern still takes a part
+        if total_parts > MAX_DIFF_PARTS {
+            return None;
+        }
+        let mut parts = vec![];
+        for part_number in 0..total_parts {
+            let pattern_part = chunks
+                .get(part_number)
+                .map(|chunk| String::from_utf8_lossy(chunk).into_owned())
+                .unwrap_or_default();
+            parts.push(GenStateDiffPart {
+                part_number: part_number as u8,
+                total_parts: total_parts as u8,
+                gen0: diff.gen0 as u32,
+                gen1: diff.gen1 as u32,
+                pattern_part,
+            });
+        }
+        Some(parts)
+    }
+}
+
+/// Collects the parts of a diff as they arrive, in any order, until the whole diff is there.
+#[derive(PartialEq, Debug, Clone)]
+pub struct PartialGenStateDiff {
+    gen0:  u32,
+    gen1:  u32,
+    parts: Vec<Option<String>>, // indexed by part number
+}
+
+impl PartialGenStateDiff {
+    pub fn new(part: &GenStateDiffPart) -> Self {
+        PartialGenStateDiff {
+            gen0:  part.gen0,
+            gen1:  part.gen1,
+            parts: vec![None; part.total_parts as usize],
+        }
+    }
+
+    /// Whether `part` belongs to the same diff as the parts collected so far.
+    pub fn is_same_diff(&self, part: &GenStateDiffPart) -> bool {
+        self.gen0 == part.gen0 && self.gen1 == part.gen1 && self.parts.len() == part.total_parts as usize
+    }
+
+    /// Adds `part`, which must be part of the same diff. Returns the whole diff once every part has
+    /// been added.
+    pub fn add(&mut self, part: GenStateDiffPart) -> Option<GenStateDiff> {
+        if !self.is_same_diff(&part) {
+            warn!(
+                "Ignoring a part of diff {}->{} while collecting diff {}->{}",
+                part.gen0, part.gen1, self.gen0, self.gen1
+            );
+            return None;
+        }
+        if let Some(slot) = self.parts.get_mut(part.part_number as usize) {
+            *slot = Some(part.pattern_part);
+        }
+        if self.parts.iter().any(|p| p.is_none()) {
+            return None;
+        }
+        let pattern: String = self.parts.iter().map(|p| p.as_ref().unwrap().as_str()).collect(); // unwrap OK; all present
+        Some(GenStateDiff {
+            gen0:    self.gen0 as usize,
+            gen1:    self.gen1 as usize,
+            pattern: Pattern(pattern),
+        })
+    }
+
+    /// Which parts have arrived, for the UpdateReply.
+    pub fn info(&self) -> GenPartInfo {
+        let mut have_bitmask = 0;
+        for (i, part) in self.parts.iter().enumerate() {
+            if part.is_some() {
+                have_bitmask |= 1 << i;
+            }
+        }
+        GenPartInfo {
+            gen0: self.gen0,
+            gen1: self.gen1,
+            have_bitmask,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
 pub struct RoomList {
     pub room_name:    String,
     pub player_count: u8,
+    pub max_players:  u8, // the room is full once player_count reaches this
     // TODO: add support
     pub in_progress:  bool,
+    pub has_password: bool, // whether joining takes a password
+}
+
+/// How one player did in a game that has ended.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct PlayerResult {
+    pub name:         String,
+    pub team:         u8,
+    pub generations:  u64,  // the last generation in which the player had live cells; 0 if never
+    pub cells_at_end: u64,  // live cells the player had in the last generation
+    pub disconnected: bool, // the player left or lost their connection before the game ended
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -391,7 +743,8 @@ pub enum Packet {
         chats:           Vec<BroadcastChatMessage>, // All non-acknowledged chats are sent each update
         game_update_seq: Option<u64>,
         game_updates:    Vec<GameUpdate>, // Information pertaining to a game tick update.
-        universe_update: UniUpdate,       // TODO: add support
+        universe_update: UniUpdate,       // at most one part of a diff; see GenStateDiffPart
+        gen_checksum:    Option<GenChecksum>, // present if the room's game is running
         ping:            PingPong,        // Used for server-to-client latency measurement (no room needed)
     },
     UpdateReply {
@@ -438,6 +791,7 @@ impl Packet {
             game_updates: _,
             game_update_seq: _,
             universe_update,
+            gen_checksum: _,
             ping: _,
         } = self
         {
@@ -520,11 +874,12 @@ impl fmt::Debug for Packet {
                 game_updates,
                 game_update_seq,
                 universe_update,
+                gen_checksum,
                 ping: _,
             } => write!(
                 f,
-                "[Update] game_updates: {:?} universe_update: {:?}, game_update_seq: {:?}",
-                game_updates, universe_update, game_update_seq
+                "[Update] game_updates: {:?} universe_update: {:?}, gen_checksum: {:?}, game_update_seq: {:?}",
+                game_updates, universe_update, gen_checksum, game_update_seq
             ),
             Packet::UpdateReply {
                 cookie,
@@ -581,29 +936,325 @@ impl Ord for Packet {
 }
 
 //////////////// Packet (de)serialization ////////////////
+
+// Every datagram starts with PROTOCOL_MAGIC and then a protocol version byte. Since version 2, that
+// is followed by a bundle of packets: a count byte, then each bincode-serialized Packet with its
+// length before it, as two little-endian bytes. Packets that go to the same place at about the same
+// time can then share a datagram. In version 1, the header was followed by a single Packet.
+//
+// Versioning rule: a release decodes MIN_PROTOCOL_VERSION through PROTOCOL_VERSION, which should
+// span no more than the previous version and the current one, and can encode any of those. Clients
+// always send PROTOCOL_VERSION. The server answers each peer in the version its datagrams last came
+// in, so that a client one version behind can still play; anyone it hasn't heard from in an older
+// version gets PROTOCOL_VERSION. That gives clients one release cycle to upgrade. To change the
+// serialized form of Packet:
+//   1. Copy the types as they were into a `v<old version>` module, with conversions to and from
+//      the new Packet, and add match arms for the old version to `decode_packet` and
+//      `serialize_packet`.
+//   2. Bump PROTOCOL_VERSION, raise MIN_PROTOCOL_VERSION to the old version, and remove the module
+//      for the version before that.
+//   3. Keep the old version's fixtures in the tests module; they must still decode, and encode to
+//      the same bytes.
+//
+// The exception is GetStatus and Status, which are exchanged with the registrar. Those are always
+// sent without a header, and are decoded with or without one. So is the server's reply to a datagram
+// with an unsupported protocol version or no header; see `unsupported_version_reply`.
+pub const PROTOCOL_MAGIC: [u8; 2] = *b"CW";
+pub const PROTOCOL_VERSION: u8 = 2;
+pub const MIN_PROTOCOL_VERSION: u8 = 1;
+pub const PROTOCOL_HEADER_LEN: usize = 3; // magic, then version
+pub const BUNDLE_PROTOCOL_VERSION: u8 = 2; // the first version that bundles packets
+pub const MAX_BUNDLE_PACKETS: usize = u8::MAX as usize;
+const BUNDLE_COUNT_LEN: usize = 1;
+const BUNDLE_LENGTH_LEN: usize = 2; // before each packet in a bundle
+
+/// Why a datagram could not be decoded into a Packet.
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum WireError {
+    #[error("packet has no protocol header; it may be from a client too old to have one")]
+    MissingHeader,
+    #[error(
+        "protocol version {version} is not supported; supported versions are {min} to {max}",
+        min = MIN_PROTOCOL_VERSION,
+        max = PROTOCOL_VERSION
+    )]
+    UnsupportedVersion { version: u8 },
+    #[error("malformed version {version} packet: {reason}")]
+    Malformed { version: u8, reason: String },
+    #[error("datagram is {len} bytes; the most allowed is {max}", max = UDP_MTU_SIZE)]
+    TooLarge { len: usize },
+}
+
+impl From<WireError> for io::Error {
+    fn from(e: WireError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, e)
+    }
+}
+
+/// True for the packets exchanged with the registrar, which never have a protocol header.
+fn is_registrar_packet(packet: &Packet) -> bool {
+    match packet {
+        Packet::GetStatus { .. } | Packet::Status { .. } => true,
+        _ => false,
+    }
+}
+
+/// What the server sends back, without a header, to a datagram whose protocol version it doesn't
+/// support, or that has no header at all. The peer can't decode this version, so it has to be
+/// readable without one. Response and BadRequest are in the same places in every version so far;
+/// keep them there, and keep this reply as it is, so that other versions can read it too.
+pub fn unsupported_version_reply() -> Packet {
+    Packet::Response {
+        sequence:    0,
+        request_ack: None,
+        code:        ResponseCode::BadRequest {
+            error_msg: format!(
+                "protocol version not supported; this server supports versions {} to {}",
+                MIN_PROTOCOL_VERSION, PROTOCOL_VERSION
+            ),
+        },
+    }
+}
+
+/// True for the unsupported-version reply; see `unsupported_version_reply`.
+fn is_unsupported_version_reply(packet: &Packet) -> bool {
+    match packet {
+        Packet::Response {
+            sequence: 0,
+            request_ack: None,
+            code: ResponseCode::BadRequest { .. },
+        } => true,
+        _ => false,
+    }
+}
+
+/// Deserializes like `bincode::deserialize`, except that it gives up on anything claiming to be
+/// larger than a datagram can be, rather than trying to allocate room for it.
+fn deserialize<'a, T: Deserialize<'a>>(payload: &'a [u8]) -> bincode::Result<T> {
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .allow_trailing_bytes()
+        .with_limit(UDP_MTU_SIZE as u64)
+        .deserialize(payload)
+}
+
+/// Decodes one serialized Packet, for any supported protocol version. Returns the packet and the
+/// number of bytes it took up.
+fn decode_packet(version: u8, payload: &[u8]) -> Result<(Packet, usize), WireError> {
+    let malformed = |e: bincode::Error| WireError::Malformed {
+        version,
+        reason: e.to_string(),
+    };
+    match version {
+        1 => {
+            let packet: v1::Packet = deserialize(payload).map_err(malformed)?;
+            let len = bincode::serialized_size(&packet).map_err(malformed)?;
+            Ok((packet.into(), len as usize))
+        }
+        2 => {
+            let packet: Packet = deserialize(payload).map_err(malformed)?;
+            let len = bincode::serialized_size(&packet).map_err(malformed)?;
+            Ok((packet, len as usize))
+        }
+        _ => Err(WireError::UnsupportedVersion { version }),
+    }
+}
+
+/// Decodes the part of a datagram after the header, for any supported protocol version. Returns
+/// the packets, in the order they were bundled, and the number of bytes they took up.
+fn decode_payload(version: u8, payload: &[u8]) -> Result<(Vec<Packet>, usize), WireError> {
+    if version < BUNDLE_PROTOCOL_VERSION {
+        let (packet, len) = decode_packet(version, payload)?;
+        return Ok((vec![packet], len));
+    }
+
+    let malformed = |reason: &str| WireError::Malformed {
+        version,
+        reason: reason.to_owned(),
+    };
+    let count = match payload.first() {
+        Some(0) => return Err(malformed("empty bundle")),
+        Some(&count) => count as usize,
+        None => return Err(malformed("no packet count")),
+    };
+    let mut packets = Vec::with_capacity(count);
+    let mut offset = BUNDLE_COUNT_LEN;
+    for _ in 0..count {
+        let len_bytes = payload
+            .get(offset..offset + BUNDLE_LENGTH_LEN)
+            .ok_or_else(|| malformed("bundle ends before a packet's length"))?;
+        let len = u16::from_le_bytes([len_bytes[0], len_bytes[1]]) as usize;
+        offset += BUNDLE_LENGTH_LEN;
+        let entry = payload
+            .get(offset..offset + len)
+            .ok_or_else(|| malformed("bundle ends in the middle of a packet"))?;
+        let (packet, packet_len) = decode_packet(version, entry)?;
+        if packet_len != len {
+            return Err(malformed("packet is shorter than its length"));
+        }
+        packets.push(packet);
+        offset += len;
+    }
+    Ok((packets, offset))
+}
+
+/// Decodes a whole datagram, header included. Returns the packets in it, in the order they were
+/// bundled, and the number of bytes they took up.
+pub fn decode_datagram(datagram: &[u8]) -> Result<(Vec<Packet>, usize), WireError> {
+    if datagram.len() > UDP_MTU_SIZE {
+        return Err(WireError::TooLarge { len: datagram.len() });
+    }
+    if !datagram.starts_with(&PROTOCOL_MAGIC) {
+        // Only the registrar's packets and the unsupported-version reply may leave out the header;
+        // see above
+        return match decode_packet(PROTOCOL_VERSION, datagram) {
+            Ok((packet, len)) if is_registrar_packet(&packet) || is_unsupported_version_reply(&packet) => {
+                Ok((vec![packet], len))
+            }
+            _ => Err(WireError::MissingHeader),
+        };
+    }
+    let version = *datagram.get(PROTOCOL_MAGIC.len()).ok_or_else(|| WireError::Malformed {
+        version: 0,
+        reason:  "no protocol version".to_owned(),
+    })?;
+    if version < MIN_PROTOCOL_VERSION || version > PROTOCOL_VERSION {
+        return Err(WireError::UnsupportedVersion { version });
+    }
+    let (packets, len) = decode_payload(version, &datagram[PROTOCOL_HEADER_LEN..])?;
+    Ok((packets, PROTOCOL_HEADER_LEN + len))
+}
+
+/// The protocol version in `datagram`'s header, if it has one.
+pub fn datagram_version(datagram: &[u8]) -> Option<u8> {
+    if !datagram.starts_with(&PROTOCOL_MAGIC) {
+        return None;
+    }
+    datagram.get(PROTOCOL_MAGIC.len()).copied()
+}
+
+/// Serializes one Packet as it is laid out in `version`, which must be supported.
+fn serialize_packet(version: u8, packet: &Packet) -> Result<Vec<u8>, bincode::Error> {
+    match version {
+        1 => serialize(&v1::Packet::from(packet.clone())),
+        2 => serialize(packet),
+        _ => Err(Box::new(bincode::ErrorKind::Custom(format!(
+            "can't encode for protocol version {}",
+            version
+        )))),
+    }
+}
+
+/// Serializes `packet` as a whole datagram in protocol `version`: a bundle of just that packet, or
+/// for the registrar, the packet alone without a header.
+pub fn encode_datagram(version: u8, packet: &Packet) -> Result<Vec<u8>, bincode::Error> {
+    if is_registrar_packet(packet) {
+        return serialize(packet);
+    }
+    encode_bundle(version, std::slice::from_ref(packet))
+}
+
+/// Serializes the unsupported-version reply as a whole datagram, which has no header.
+pub fn encode_unsupported_version_reply() -> Result<Vec<u8>, bincode::Error> {
+    serialize(&unsupported_version_reply())
+}
+
+/// Serializes `packets` as one datagram in protocol `version`, header included, to be decoded in the
+/// same order. See `bundle_len` for how many fit; before BUNDLE_PROTOCOL_VERSION, that is one. The
+/// registrar's packets can't be bundled.
+pub fn encode_bundle(version: u8, packets: &[Packet]) -> Result<Vec<u8>, bincode::Error> {
+    let invalid = |reason: String| Box::new(bincode::ErrorKind::Custom(reason));
+    let max_packets = if version < BUNDLE_PROTOCOL_VERSION {
+        1
+    } else {
+        MAX_BUNDLE_PACKETS
+    };
+    if packets.is_empty() || packets.len() > max_packets {
+        return Err(invalid(format!(
+            "can't bundle {} packets in protocol version {}",
+            packets.len(),
+            version
+        )));
+    }
+    let mut datagram = Vec::with_capacity(UDP_MTU_SIZE);
+    datagram.extend_from_slice(&PROTOCOL_MAGIC);
+    datagram.push(version);
+    if version < BUNDLE_PROTOCOL_VERSION {
+        datagram.extend_from_slice(&serialize_packet(version, &packets[0])?);
+        return Ok(datagram);
+    }
+    datagram.push(packets.len() as u8);
+    for packet in packets {
+        if is_registrar_packet(packet) {
+            return Err(invalid(format!(
+                "can't bundle a packet for the registrar: {:?}",
+                packet
+            )));
+        }
+        let payload = serialize_packet(version, packet)?;
+        if payload.len() > u16::MAX as usize {
+            return Err(invalid(format!(
+                "packet is too large to bundle: {} bytes",
+                payload.len()
+            )));
+        }
+        datagram.extend_from_slice(&(payload.len() as u16).to_le_bytes());
+        datagram.extend_from_slice(&payload);
+    }
+    Ok(datagram)
+}
+
+/// How many packets from the start of `packets` fit together in one datagram of at most
+/// `UDP_MTU_SIZE` bytes in protocol `version`. This is always at least one if there are any packets,
+/// so that one too large to fit anywhere goes out alone rather than holding up the rest. A packet
+/// for the registrar always goes alone, and so does every packet before BUNDLE_PROTOCOL_VERSION.
+pub fn bundle_len<'a, I>(version: u8, packets: I) -> usize
+where
+    I: IntoIterator<Item = &'a Packet>,
+{
+    if version < BUNDLE_PROTOCOL_VERSION {
+        return packets.into_iter().take(1).count();
+    }
+    let mut size = PROTOCOL_HEADER_LEN + BUNDLE_COUNT_LEN;
+    let mut count = 0;
+    for packet in packets {
+        if is_registrar_packet(packet) {
+            return if count == 0 { 1 } else { count };
+        }
+        // An error here will come up again when the packet is encoded, so it's left to that
+        let packet_size = bincode::serialized_size(packet).unwrap_or(0) as usize;
+        size += BUNDLE_LENGTH_LEN + packet_size;
+        if count > 0 && (size > UDP_MTU_SIZE || count == MAX_BUNDLE_PACKETS) {
+            break;
+        }
+        count += 1;
+    }
+    count
+}
+
 #[allow(dead_code)]
 pub struct NetwaystePacketCodec;
 
 impl Decoder for NetwaystePacketCodec {
-    type Item = Packet;
+    // Every packet in one datagram; from protocol version 2 on, there can be more than one
+    type Item = Vec<Packet>;
     type Error = io::Error;
 
     fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
-        match deserialize(src) {
-            Ok(decoded) => {
-                let pkt: Packet = decoded;
-                match bincode::serialized_size(&pkt) {
-                    Ok(length) => src.advance(length as usize),
-                    Err(err) => {
-                        // Something went horribly wrong if we were unable to serialize something we just deserialized.
-                        // Clear the buffer and restart the decoder by returning an error.
-                        src.clear();
-                        return Err(io::Error::new(io::ErrorKind::InvalidData, err));
-                    }
-                }
-                Ok(Some(pkt))
+        if src.is_empty() {
+            return Ok(None);
+        }
+        match decode_datagram(src) {
+            Ok((packets, len)) => {
+                src.advance(len);
+                Ok(Some(packets))
+            }
+            Err(e) => {
+                // Each datagram is decoded whole, so there is nothing more to wait for. Drop the
+                // rest of it so that the next datagram starts afresh.
+                src.clear();
+                Err(e.into())
             }
-            Err(_) => Ok(None),
         }
     }
 }
@@ -612,20 +1263,52 @@ impl Encoder<Packet> for NetwaystePacketCodec {
     type Error = io::Error;
 
     fn encode(&mut self, packet: Packet, dst: &mut BytesMut) -> Result<(), Self::Error> {
-        let encoded: Vec<u8> = serialize(&packet).unwrap();
+        let encoded: Vec<u8> =
+            encode_datagram(PROTOCOL_VERSION, &packet).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
         dst.extend_from_slice(&encoded[..]);
         Ok(())
     }
 }
 
 //////////////// Network interface ////////////////
+/// Binds a UDP socket to `opt_host` (an IPv4 or IPv6 address) and `opt_port`, or the defaults for
+/// whichever is `None`. See `bind_addr`.
 #[allow(dead_code)]
 pub async fn bind(opt_host: Option<&str>, opt_port: Option<u16>) -> Result<UdpSocket, NetError> {
     let host = if let Some(host) = opt_host { host } else { DEFAULT_HOST };
     let port = if let Some(port) = opt_port { port } else { DEFAULT_PORT };
-    let addr: SocketAddr = format!("{}:{}", host, port).parse()?;
+    // Parse the IP on its own so that IPv6 addresses don't need brackets
+    let ip: IpAddr = host
+        .parse()
+        .map_err(|e| NetError::InvalidBindAddress(host.to_owned(), e))?;
+    bind_addr(SocketAddr::new(ip, port)).await
+}
+
+/// Binds a UDP socket to `addr`. Binding to the unspecified IPv6 address (`::`) also accepts IPv4
+/// where the platform allows it, in which case IPv4 peers show up as IPv4-mapped IPv6 addresses
+/// (`::ffff:a.b.c.d`). Replies must go to those addresses as they are.
+#[allow(dead_code)]
+pub async fn bind_addr(addr: SocketAddr) -> Result<UdpSocket, NetError> {
     info!("Attempting to bind to {}", addr);
-    let sock = UdpSocket::bind(&addr).await?;
+    let bind_error = |e| NetError::BindError(addr, e);
+    let sock = match addr {
+        SocketAddr::V6(v6_addr) if v6_addr.ip().is_unspecified() => {
+            // The standard library leaves IPV6_V6ONLY at the platform default, which differs
+            // between platforms, so set it here
+            let socket = Socket::new(Domain::ipv6(), Type::dgram(), Some(Protocol::udp())).map_err(bind_error)?;
+            if let Err(e) = socket.set_only_v6(false) {
+                warn!(
+                    "Listening for IPv6 only, since IPv4 can't be accepted on {}: {}",
+                    addr, e
+                );
+            }
+            socket.bind(&addr.into()).map_err(bind_error)?;
+            let std_socket = socket.into_udp_socket();
+            std_socket.set_nonblocking(true).map_err(bind_error)?;
+            UdpSocket::from_std(std_socket).map_err(bind_error)?
+        }
+        _ => UdpSocket::bind(&addr).await.map_err(bind_error)?,
+    };
     Ok(sock)
 }
 
@@ -634,11 +1317,6 @@ pub fn get_version() -> result::Result<Version, SemVerError> {
     Version::parse(VERSION)
 }
 
-#[allow(dead_code)]
-pub fn has_connection_timed_out(last_received: Instant) -> bool {
-    (Instant::now() - last_received) > Duration::from_secs(TIMEOUT_IN_SECONDS)
-}
-
 pub struct NetworkStatistics {
     pub tx_packets_failed:  u64, // From the perspective of the Network OSI layer
     pub tx_packets_success: u64, // From the perspective of the Network OSI layer
@@ -762,7 +1440,7 @@ pub trait NetworkQueue<T: Ord + Sequenced + Debug + Clone> {
     fn remove(&mut self, pkt: &T) -> Option<T>;
 
     fn discard_older_items(&mut self);
-    fn buffer_item(&mut self, item: T) -> bool;
+    fn buffer_item(&mut self, item: T, now: Instant) -> bool;
     fn as_queue_type(&self) -> &ItemQueue<T>;
     fn as_queue_type_mut(&mut self) -> &mut ItemQueue<T>;
 }
@@ -773,18 +1451,19 @@ pub struct NetAttempt {
 }
 
 impl NetAttempt {
+    /// An item first sent or received at `now`.
     #[allow(unused)]
-    pub fn new() -> Self {
+    pub fn new(now: Instant) -> Self {
         Self {
-            time:    Instant::now(),
+            time:    now,
             retries: 0,
         }
     }
 
     #[allow(unused)]
-    pub fn increment_retries(&mut self) {
+    pub fn increment_retries(&mut self, now: Instant) {
         self.retries += 1;
-        self.time = Instant::now();
+        self.time = now;
     }
 }
 
@@ -797,15 +1476,14 @@ pub struct NetQueue<T> {
 }
 
 impl NetQueue<Packet> {
+    /// Indices of the packets that are due to be resent as of `now`: those that have gone
+    /// unacknowledged for `RETRANSMISSION_THRESHOLD_IN_MS` since they were last sent. Packets that
+    /// have been retried a lot are sent more times over, not sooner; see `get_expired_tx_packets`.
     #[allow(unused)]
-    pub fn get_retransmit_indices(&self) -> Vec<usize> {
+    pub fn get_retransmit_indices(&self, now: Instant) -> Vec<usize> {
         let iter = self.attempts.iter();
         iter.enumerate()
-            .filter(|(_, ts)| {
-                ((Instant::now() - ts.time) >= RETRANSMISSION_THRESHOLD_IN_MS)
-                    || (ts.retries >= RETRY_THRESHOLD)
-                    || (ts.retries >= RETRY_AGGRESSIVE_THRESHOLD)
-            })
+            .filter(|(_, ts)| now.saturating_duration_since(ts.time) >= RETRANSMISSION_THRESHOLD_IN_MS)
             .map(|(i, _)| i)
             .take(RETRANSMISSION_COUNT)
             .collect::<Vec<usize>>()
@@ -898,8 +1576,10 @@ where
     /// against this. Primarily, they cover the cases where out-of-order insertion would transition the queue into a
     /// wrapped state from a non-wrapped state.
     ///
+    /// `now` is when the item was sent or received; it is due for retransmission counting from then.
+    ///
     /// boolean return value states whether or not the packet we are buffering is already present within the queue.
-    fn buffer_item(&mut self, item: T) -> bool {
+    fn buffer_item(&mut self, item: T, now: Instant) -> bool {
         let mut packet_exists: bool = false;
         let sequence = item.sequence_number();
 
@@ -907,7 +1587,7 @@ where
         let opt_head_seq_num: Option<u64> = self.newest_seq_num();
         if opt_head_seq_num.is_none() {
             self.push_back(item);
-            self.attempts.push_back(NetAttempt::new());
+            self.attempts.push_back(NetAttempt::new(now));
             return packet_exists;
         }
         let opt_tail_seq_num: Option<u64> = self.oldest_seq_num();
@@ -919,11 +1599,11 @@ where
             if newest_seq_num == u64::max_value() {
                 if self.will_seq_cause_a_wrap(self.buffer_wrap_index, sequence, oldest_seq_num, newest_seq_num) {
                     self.push_back(item);
-                    self.attempts.push_back(NetAttempt::new());
+                    self.attempts.push_back(NetAttempt::new(now));
                     self.buffer_wrap_index = Some(self.len() - 1);
                 } else {
                     self.push_front(item);
-                    self.attempts.push_back(NetAttempt::new());
+                    self.attempts.push_back(NetAttempt::new(now));
                 }
             } else if sequence > newest_seq_num && self.buffer_wrap_index.is_some() {
                 // When wrapped, either this is the newest sequence number so far, or
@@ -932,11 +1612,11 @@ where
                     if let Some(buffer_wrap_index) = self.buffer_wrap_index {
                         let insertion_index = self.find_rx_insertion_index_in_subset(0, buffer_wrap_index, &item);
                         self.buffer_wrap_index = Some(buffer_wrap_index + 1);
-                        packet_exists = self.insert_into_rx_queue(insertion_index, item);
+                        packet_exists = self.insert_into_rx_queue(insertion_index, item, now);
                     }
                 } else {
                     self.push_back(item);
-                    self.attempts.push_back(NetAttempt::new());
+                    self.attempts.push_back(NetAttempt::new(now));
                 }
             } else if sequence < newest_seq_num {
                 // The new seq num appears to be older than everything,
@@ -951,11 +1631,11 @@ where
                     insertion_index = self.find_rx_insertion_index(&item);
                 }
 
-                packet_exists = self.insert_into_rx_queue(insertion_index, item);
+                packet_exists = self.insert_into_rx_queue(insertion_index, item, now);
             } else {
                 // Smallest sequence number (in value) that we have seen thus far.
                 self.push_front(item);
-                self.attempts.push_back(NetAttempt::new());
+                self.attempts.push_back(NetAttempt::new(now));
 
                 if self.buffer_wrap_index.is_some() {
                     self.buffer_wrap_index = Some(self.buffer_wrap_index.unwrap() + 1);
@@ -986,7 +1666,7 @@ where
                     }
                 }
             }
-            packet_exists = self.insert_into_rx_queue(insertion_index, item);
+            packet_exists = self.insert_into_rx_queue(insertion_index, item, now);
         }
         packet_exists
     }
@@ -1047,18 +1727,18 @@ where
     }
 
     // Checked insertion against the sentinel used during unit testing
-    fn insert_into_rx_queue(&mut self, index: Option<usize>, item: T) -> bool {
+    fn insert_into_rx_queue(&mut self, index: Option<usize>, item: T, now: Instant) -> bool {
         let mut exists: bool = false;
         if let Some(insertion_index) = index {
             if insertion_index != MATCH_FOUND_SENTINEL {
                 if cfg!(test) {
                     self.as_queue_type_mut().insert(insertion_index, item.clone());
-                    self.attempts.push_back(NetAttempt::new());
+                    self.attempts.push_back(NetAttempt::new(now));
                 }
             }
             if !(cfg!(test)) {
                 self.as_queue_type_mut().insert(insertion_index, item);
-                self.attempts.push_back(NetAttempt::new());
+                self.attempts.push_back(NetAttempt::new(now));
             }
         } else {
             exists = true;
@@ -1143,6 +1823,7 @@ impl NetworkManager {
         addr: SocketAddr,
         confirmed_ack: Option<u64>,
         indices: &Vec<usize>,
+        now: Instant,
     ) -> Vec<(Packet, SocketAddr)> {
         let mut error_occurred = false;
         let mut failed_index = 0;
@@ -1153,7 +1834,7 @@ impl NetworkManager {
             let mut send_counter = 1;
 
             if let Some(ts) = self.tx_packets.attempts.get_mut(index) {
-                ts.increment_retries();
+                ts.increment_retries(now);
                 if ts.retries >= RETRY_AGGRESSIVE_THRESHOLD {
                     // If the packet is truly late, send it twice
                     send_counter += 2;
@@ -1165,7 +1846,7 @@ impl NetworkManager {
             if let Some(pkt) = self.tx_packets.queue.get_mut(index) {
                 // `response_sequence` may have advanced since this was last queued
                 pkt.set_response_sequence(confirmed_ack);
-                trace!("[Retransmitting (Times={})] {:?}", send_counter, pkt);
+                debug!("[{}] Retransmitting (times={}): {:?}", addr, send_counter, pkt);
                 for _ in 0..send_counter {
                     expired_packets.push(((*pkt).clone(), addr));
                 }
@@ -1178,8 +1859,9 @@ impl NetworkManager {
 
         if error_occurred {
             error!(
-                "Index ({}) in attempt queue out-of-bounds in tx packets queue,
+                "[{}] Index ({}) in attempt queue out-of-bounds in tx packets queue,
                             or perhaps `None`?:\n\t {:?}\n{:?}\n{:?}",
+                addr,
                 failed_index,
                 indices,
                 self.tx_packets.queue.len(),
@@ -1204,6 +1886,15 @@ impl NetworkManager {
     }
 }
 
+/// How the client's connection to the server is doing. See `health::ConnectionHealth`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum ConnectionStatus {
+    Connected,
+    Degraded,     // keep alives are going missing, but requests are still sent
+    Disconnected, // requests are refused until reconnected
+}
+
 #[derive(PartialEq, Debug, Clone)]
 #[allow(dead_code)]
 pub enum NetwaysteEvent {
@@ -1213,27 +1904,60 @@ pub enum NetwaysteEvent {
     Connect(String, String), // Player name, version
     Disconnect,
     List,
-    ChatMessage(String), // chat message
-    NewRoom(String),     // room name
-    JoinRoom(String),    // room name
+    ChatMessage(String),                                     // chat message
+    NewRoom(String, Option<String>, Option<u8>, Option<u8>), // room name, password, team count, max players
+    JoinRoom(String, Option<String>),                        // room name, password
     LeaveRoom,
+    PlaceCells(Vec<(usize, usize, CellState)>), // (col, row, new state)
+    RequestFullState,
+    MutePlayer(String),          // player name
+    UnmutePlayer(String),        // player name
+    Admin(String, AdminCommand), // admin token, command
+    CloseRoom(String),           // room name
+    InvitePlayer(String),        // player name
+    AcceptInvite(String),        // invite token
+    DeclineInvite(String),       // invite token
+    SetTeam(u8),                 // team
+    SetReady(bool),              // whether we are ready for the next game
+    StartGame,
 
     // Responses
-    LoggedIn(String),        // player is logged in -- (version)
-    JoinedRoom(String),      // player has joined the room
-    PlayerList(Vec<String>), // list of players in room or lobby with ping (ms)
-    RoomList(Vec<RoomList>), // (room name, # players, game has started?)
+    LoggedIn(String),                                      // player is logged in -- (version)
+    JoinedRoom(String),                                    // player has joined the room
+    PlayerList(Vec<String>, Vec<String>, Vec<Option<u8>>), // players in room or lobby, which we muted, their teams
+    RoomList(Vec<RoomList>),                               // (room name, # players, max players, game has started?)
     LeftRoom,
+    CellsPlaced(usize), // generation in which the cells were set
     BadRequest(String),
     ServerError(String),
+    ChatThrottled(String, u64),        // error message, seconds left muted
+    BanList(Vec<String>, Vec<String>), // banned addresses, banned names
+    Invited(String, String, String),   // inviter, room name, invite token
+    InviteFailed(InviteFailure),
+    InviteAnswered(String, InviteAnswer), // invited player's name, what they did
+    SetTeamFailed(TeamFailure),
+    GameStarted(String, Vec<(String, u8)>), // room name, (player name, team) for everyone in the game
+    GameEnded(String, u64, Vec<PlayerResult>), // room name, generations run, how everyone in the game did
+    RoomNameTaken(String),                  // room name
+    RoomFull(String, u8),                   // room name, how many players it takes
+    GameStarting(String, u64),              // room name, seconds until the game starts
+    GameStartCancelled(String),             // room name
+    OwnerChanged(String, String),           // room name, new owner's name
 
     // Updates
     ChatMessages(Vec<(String, String)>), // (player name, message)
-    UniverseUpdate,                      // TODO add libconway stuff for current universe gen
+    UniverseUpdate(GenStateDiff),        // a whole diff, ready to apply to the universe
+    UniverseChecksum(GenChecksum),       // compare with our universe, if we have the generation
 
     // Server Status
     GetStatus(PingPong),
     Status(Packet, Option<u64>), // `Packet::Status` variant only; u64 is latency. None if not yet calculated.
+    Ping,                        // answered with a Pong, whether or not we are connected
+    Pong(u64),                   // round trip time to the server, in milliseconds
+
+    // Connection
+    ConnectionStatus(ConnectionStatus), // the connection's status changed
+    RequestNotSent(String),             // a request was refused without sending it; why
 }
 
 impl NetwaysteEvent {
@@ -1256,17 +1980,25 @@ impl NetwaysteEvent {
                 }
             }
             NetwaysteEvent::ChatMessage(msg) => RequestAction::ChatMessage { message: msg },
-            NetwaysteEvent::NewRoom(name) => {
+            NetwaysteEvent::NewRoom(name, password, team_count, max_players) => {
                 if !is_in_game {
-                    RequestAction::NewRoom { room_name: name }
+                    RequestAction::NewRoom {
+                        room_name: name,
+                        password,
+                        team_count,
+                        max_players,
+                    }
                 } else {
                     debug!("Command failed: You are in a game");
                     RequestAction::None
                 }
             }
-            NetwaysteEvent::JoinRoom(name) => {
+            NetwaysteEvent::JoinRoom(name, password) => {
                 if !is_in_game {
-                    RequestAction::JoinRoom { room_name: name }
+                    RequestAction::JoinRoom {
+                        room_name: name,
+                        password,
+                    }
                 } else {
                     debug!("Command failed: You are already in a game");
                     RequestAction::None
@@ -1280,6 +2012,53 @@ impl NetwaysteEvent {
                     RequestAction::None
                 }
             }
+            NetwaysteEvent::PlaceCells(cells) => {
+                if is_in_game {
+                    RequestAction::PlaceCells { cells }
+                } else {
+                    debug!("Command failed: You are not in a game");
+                    RequestAction::None
+                }
+            }
+            NetwaysteEvent::RequestFullState => {
+                if is_in_game {
+                    RequestAction::RequestFullState
+                } else {
+                    debug!("Command failed: You are not in a game");
+                    RequestAction::None
+                }
+            }
+            NetwaysteEvent::MutePlayer(name) => RequestAction::MutePlayer { name },
+            NetwaysteEvent::UnmutePlayer(name) => RequestAction::UnmutePlayer { name },
+            NetwaysteEvent::Admin(token, command) => RequestAction::Admin { token, command },
+            NetwaysteEvent::CloseRoom(room_name) => RequestAction::CloseRoom { room_name },
+            NetwaysteEvent::InvitePlayer(name) => RequestAction::InvitePlayer { name },
+            NetwaysteEvent::AcceptInvite(token) => RequestAction::AcceptInvite { token },
+            NetwaysteEvent::DeclineInvite(token) => RequestAction::DeclineInvite { token },
+            NetwaysteEvent::SetTeam(team) => {
+                if is_in_game {
+                    RequestAction::SetTeam { team }
+                } else {
+                    debug!("Command failed: You are not in a game");
+                    RequestAction::None
+                }
+            }
+            NetwaysteEvent::SetReady(ready) => {
+                if is_in_game {
+                    RequestAction::SetReady { ready }
+                } else {
+                    debug!("Command failed: You are not in a game");
+                    RequestAction::None
+                }
+            }
+            NetwaysteEvent::StartGame => {
+                if is_in_game {
+                    RequestAction::StartGame
+                } else {
+                    debug!("Command failed: You are not in a game");
+                    RequestAction::None
+                }
+            }
             _ => {
                 panic!(
                     "Unexpected netwayste event during request action construction! {:?}",
@@ -1297,12 +2076,42 @@ impl NetwaysteEvent {
                 server_version,
             } => NetwaysteEvent::LoggedIn(server_version),
             ResponseCode::JoinedRoom { room_name } => NetwaysteEvent::JoinedRoom(room_name),
-            ResponseCode::PlayerList { players } => NetwaysteEvent::PlayerList(players),
+            ResponseCode::PlayerList { players, muted, teams } => NetwaysteEvent::PlayerList(players, muted, teams),
             ResponseCode::RoomList { rooms } => NetwaysteEvent::RoomList(rooms),
             ResponseCode::LeaveRoom => NetwaysteEvent::LeftRoom,
+            ResponseCode::CellsPlaced { generation } => NetwaysteEvent::CellsPlaced(generation),
             ResponseCode::BadRequest { error_msg } => NetwaysteEvent::BadRequest(error_msg),
             ResponseCode::ServerError { error_msg } => NetwaysteEvent::ServerError(error_msg),
             ResponseCode::Unauthorized { error_msg } => NetwaysteEvent::BadRequest(error_msg),
+            ResponseCode::ChatThrottled { error_msg, muted_secs } => {
+                NetwaysteEvent::ChatThrottled(error_msg, muted_secs)
+            }
+            ResponseCode::BanList { addrs, names } => NetwaysteEvent::BanList(addrs, names),
+            ResponseCode::Invited {
+                inviter,
+                room_name,
+                token,
+            } => NetwaysteEvent::Invited(inviter, room_name, token),
+            ResponseCode::InviteFailed { reason } => NetwaysteEvent::InviteFailed(reason),
+            ResponseCode::InviteAnswered { name, answer } => NetwaysteEvent::InviteAnswered(name, answer),
+            ResponseCode::SetTeamFailed { reason } => NetwaysteEvent::SetTeamFailed(reason),
+            ResponseCode::GameStarted { room_name, teams } => NetwaysteEvent::GameStarted(room_name, teams),
+            ResponseCode::GameEnded {
+                room_name,
+                generations,
+                results,
+            } => NetwaysteEvent::GameEnded(room_name, generations, results),
+            ResponseCode::LobbyChat { player_name, message } => {
+                NetwaysteEvent::ChatMessages(vec![(player_name, message)])
+            }
+            ResponseCode::RoomNameTaken { room_name } => NetwaysteEvent::RoomNameTaken(room_name),
+            ResponseCode::RoomFull { room_name, max_players } => NetwaysteEvent::RoomFull(room_name, max_players),
+            ResponseCode::GameStarting {
+                room_name,
+                seconds_left,
+            } => NetwaysteEvent::GameStarting(room_name, seconds_left),
+            ResponseCode::GameStartCancelled { room_name } => NetwaysteEvent::GameStartCancelled(room_name),
+            ResponseCode::OwnerChanged { room_name, owner } => NetwaysteEvent::OwnerChanged(room_name, owner),
             _ => {
                 panic!(
                     "Unexpected response code during netwayste event construction: {:?}",
@@ -0,0 +1,501 @@
+/*
+ * Herein lies a networking library for the multiplayer game, Conwayste.
+ *
+ * Copyright (C) 2021 The Conwayste Developers
+ *
+ * This program is free software: you can redistribute it and/or modify it
+ * under the terms of the GNU General Public License as published by the Free
+ * Software Foundation, either version 3 of the License, or (at your option)
+ * any later version.
+ *
+ * This program is distributed in the hope that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of  MERCHANTABILITY or
+ * FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for
+ * more details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Protocol version 1, as it was before packets were bundled, and before rooms had passwords,
+//! teams and player limits. Only the types whose serialized form has changed since, or that contain
+//! one, are copied here; everything else is shared with the current version. Remove this module once
+//! MIN_PROTOCOL_VERSION is past 1.
+
+use serde::{Deserialize, Serialize};
+
+use super::{AdminCommand, BroadcastChatMessage, ClientOptionValue, GameUpdate, GenChecksum, GenPartInfo, UniUpdate};
+use crate::utils::PingPong;
+use conway::universe::CellState;
+
+// Every room took this many players in version 1
+const MAX_ROOM_PLAYERS: u8 = 8;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum RequestAction {
+    None,
+    Connect {
+        name:           String,
+        client_version: String,
+    },
+    Disconnect,
+    KeepAlive {
+        latest_response_ack: u64,
+    },
+    ListPlayers,
+    ChatMessage {
+        message: String,
+    },
+    ListRooms,
+    NewRoom {
+        room_name: String,
+    },
+    JoinRoom {
+        room_name: String,
+    },
+    LeaveRoom,
+    SetClientOptions {
+        key:   String,
+        value: Option<ClientOptionValue>,
+    },
+    DropPattern {
+        x:       i32,
+        y:       i32,
+        pattern: String,
+    },
+    ClearArea {
+        x: i32,
+        y: i32,
+        w: u32,
+        h: u32,
+    },
+    PlaceCells {
+        cells: Vec<(usize, usize, CellState)>,
+    },
+    RequestFullState,
+    MutePlayer {
+        name: String,
+    },
+    UnmutePlayer {
+        name: String,
+    },
+    Admin {
+        token:   String,
+        command: AdminCommand,
+    },
+    CloseRoom {
+        room_name: String,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum ResponseCode {
+    OK,
+    LoggedIn {
+        cookie:         String,
+        server_version: String,
+    },
+    JoinedRoom {
+        room_name: String,
+    },
+    LeaveRoom,
+    PlayerList {
+        players: Vec<String>,
+        muted:   Vec<String>,
+    },
+    RoomList {
+        rooms: Vec<RoomList>,
+    },
+    CellsPlaced {
+        generation: usize,
+    },
+    BadRequest {
+        error_msg: String,
+    },
+    Unauthorized {
+        error_msg: String,
+    },
+    TooManyRequests {
+        error_msg: String,
+    },
+    ChatThrottled {
+        error_msg:  String,
+        muted_secs: u64,
+    },
+    ServerError {
+        error_msg: String,
+    },
+    NotConnected {
+        error_msg: String,
+    },
+    KeepAlive,
+    BanList {
+        addrs: Vec<String>,
+        names: Vec<String>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RoomList {
+    pub room_name:    String,
+    pub player_count: u8,
+    pub in_progress:  bool,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub enum Packet {
+    Request {
+        sequence:     u64,
+        response_ack: Option<u64>,
+        cookie:       Option<String>,
+        action:       RequestAction,
+    },
+    Response {
+        sequence:    u64,
+        request_ack: Option<u64>,
+        code:        ResponseCode,
+    },
+    Update {
+        chats:           Vec<BroadcastChatMessage>,
+        game_update_seq: Option<u64>,
+        game_updates:    Vec<GameUpdate>,
+        universe_update: UniUpdate,
+        gen_checksum:    Option<GenChecksum>,
+        ping:            PingPong,
+    },
+    UpdateReply {
+        cookie:               String,
+        last_chat_seq:        Option<u64>,
+        last_game_update_seq: Option<u64>,
+        last_full_gen:        Option<u64>,
+        partial_gen:          Option<GenPartInfo>,
+        pong:                 PingPong,
+    },
+    GetStatus {
+        ping: PingPong,
+    },
+    Status {
+        pong:           PingPong,
+        server_version: String,
+        player_count:   u64,
+        room_count:     u64,
+        server_name:    String,
+    },
+}
+
+impl From<RequestAction> for super::RequestAction {
+    fn from(action: RequestAction) -> Self {
+        use super::RequestAction as Current;
+        match action {
+            RequestAction::None => Current::None,
+            RequestAction::Connect { name, client_version } => Current::Connect { name, client_version },
+            RequestAction::Disconnect => Current::Disconnect,
+            RequestAction::KeepAlive { latest_response_ack } => Current::KeepAlive { latest_response_ack },
+            RequestAction::ListPlayers => Current::ListPlayers,
+            RequestAction::ChatMessage { message } => Current::ChatMessage { message },
+            RequestAction::ListRooms => Current::ListRooms,
+            RequestAction::NewRoom { room_name } => Current::NewRoom {
+                room_name,
+                password: None,
+                team_count: None,
+                max_players: None,
+            },
+            RequestAction::JoinRoom { room_name } => Current::JoinRoom {
+                room_name,
+                password: None,
+            },
+            RequestAction::LeaveRoom => Current::LeaveRoom,
+            RequestAction::SetClientOptions { key, value } => Current::SetClientOptions { key, value },
+            RequestAction::DropPattern { x, y, pattern } => Current::DropPattern { x, y, pattern },
+            RequestAction::ClearArea { x, y, w, h } => Current::ClearArea { x, y, w, h },
+            RequestAction::PlaceCells { cells } => Current::PlaceCells { cells },
+            RequestAction::RequestFullState => Current::RequestFullState,
+            RequestAction::MutePlayer { name } => Current::MutePlayer { name },
+            RequestAction::UnmutePlayer { name } => Current::UnmutePlayer { name },
+            RequestAction::Admin { token, command } => Current::Admin { token, command },
+            RequestAction::CloseRoom { room_name } => Current::CloseRoom { room_name },
+        }
+    }
+}
+
+impl From<ResponseCode> for super::ResponseCode {
+    fn from(code: ResponseCode) -> Self {
+        use super::ResponseCode as Current;
+        match code {
+            ResponseCode::OK => Current::OK,
+            ResponseCode::LoggedIn { cookie, server_version } => Current::LoggedIn { cookie, server_version },
+            ResponseCode::JoinedRoom { room_name } => Current::JoinedRoom { room_name },
+            ResponseCode::LeaveRoom => Current::LeaveRoom,
+            ResponseCode::PlayerList { players, muted } => Current::PlayerList {
+                teams: vec![None; players.len()],
+                players,
+                muted,
+            },
+            ResponseCode::RoomList { rooms } => Current::RoomList {
+                rooms: rooms.into_iter().map(Into::into).collect(),
+            },
+            ResponseCode::CellsPlaced { generation } => Current::CellsPlaced { generation },
+            ResponseCode::BadRequest { error_msg } => Current::BadRequest { error_msg },
+            ResponseCode::Unauthorized { error_msg } => Current::Unauthorized { error_msg },
+            ResponseCode::TooManyRequests { error_msg } => Current::TooManyRequests { error_msg },
+            ResponseCode::ChatThrottled { error_msg, muted_secs } => Current::ChatThrottled { error_msg, muted_secs },
+            ResponseCode::ServerError { error_msg } => Current::ServerError { error_msg },
+            ResponseCode::NotConnected { error_msg } => Current::NotConnected { error_msg },
+            ResponseCode::KeepAlive => Current::KeepAlive,
+            ResponseCode::BanList { addrs, names } => Current::BanList { addrs, names },
+        }
+    }
+}
+
+impl From<RoomList> for super::RoomList {
+    fn from(room: RoomList) -> Self {
+        super::RoomList {
+            room_name:    room.room_name,
+            player_count: room.player_count,
+            max_players:  MAX_ROOM_PLAYERS,
+            in_progress:  room.in_progress,
+            has_password: false, // version 1 servers had no passwords
+        }
+    }
+}
+
+impl From<Packet> for super::Packet {
+    fn from(packet: Packet) -> Self {
+        use super::Packet as Current;
+        match packet {
+            Packet::Request {
+                sequence,
+                response_ack,
+                cookie,
+                action,
+            } => Current::Request {
+                sequence,
+                response_ack,
+                cookie,
+                action: action.into(),
+            },
+            Packet::Response {
+                sequence,
+                request_ack,
+                code,
+            } => Current::Response {
+                sequence,
+                request_ack,
+                code: code.into(),
+            },
+            Packet::Update {
+                chats,
+                game_update_seq,
+                game_updates,
+                universe_update,
+                gen_checksum,
+                ping,
+            } => Current::Update {
+                chats,
+                game_update_seq,
+                game_updates,
+                universe_update,
+                gen_checksum,
+                ping,
+            },
+            Packet::UpdateReply {
+                cookie,
+                last_chat_seq,
+                last_game_update_seq,
+                last_full_gen,
+                partial_gen,
+                pong,
+            } => Current::UpdateReply {
+                cookie,
+                last_chat_seq,
+                last_game_update_seq,
+                last_full_gen,
+                partial_gen,
+                pong,
+            },
+            Packet::GetStatus { ping } => Current::GetStatus { ping },
+            Packet::Status {
+                pong,
+                server_version,
+                player_count,
+                room_count,
+                server_name,
+            } => Current::Status {
+                pong,
+                server_version,
+                player_count,
+                room_count,
+                server_name,
+            },
+        }
+    }
+}
+
+// The other way, for encoding packets to a version 1 peer. Only servers do that, and they send no
+// requests, so actions that version 1 didn't have become None.
+impl From<super::RequestAction> for RequestAction {
+    fn from(action: super::RequestAction) -> Self {
+        use super::RequestAction as Current;
+        match action {
+            Current::None => RequestAction::None,
+            Current::Connect { name, client_version } => RequestAction::Connect { name, client_version },
+            Current::Disconnect => RequestAction::Disconnect,
+            Current::KeepAlive { latest_response_ack } => RequestAction::KeepAlive { latest_response_ack },
+            Current::ListPlayers => RequestAction::ListPlayers,
+            Current::ChatMessage { message } => RequestAction::ChatMessage { message },
+            Current::ListRooms => RequestAction::ListRooms,
+            Current::NewRoom { room_name, .. } => RequestAction::NewRoom { room_name },
+            Current::JoinRoom { room_name, .. } => RequestAction::JoinRoom { room_name },
+            Current::LeaveRoom => RequestAction::LeaveRoom,
+            Current::SetClientOptions { key, value } => RequestAction::SetClientOptions { key, value },
+            Current::DropPattern { x, y, pattern } => RequestAction::DropPattern { x, y, pattern },
+            Current::ClearArea { x, y, w, h } => RequestAction::ClearArea { x, y, w, h },
+            Current::PlaceCells { cells } => RequestAction::PlaceCells { cells },
+            Current::RequestFullState => RequestAction::RequestFullState,
+            Current::MutePlayer { name } => RequestAction::MutePlayer { name },
+            Current::UnmutePlayer { name } => RequestAction::UnmutePlayer { name },
+            Current::Admin { token, command } => RequestAction::Admin { token, command },
+            Current::CloseRoom { room_name } => RequestAction::CloseRoom { room_name },
+            Current::InvitePlayer { .. }
+            | Current::AcceptInvite { .. }
+            | Current::DeclineInvite { .. }
+            | Current::SetTeam { .. }
+            | Current::Ping { .. }
+            | Current::Discover
+            | Current::SetReady { .. }
+            | Current::StartGame => RequestAction::None,
+        }
+    }
+}
+
+// Refusals that version 1 had no code for become BadRequest, with the reason in the message. The
+// rest are sequenced like any other response, so they still have to go out, but as OK, since a
+// version 1 client has no use for them.
+impl From<super::ResponseCode> for ResponseCode {
+    fn from(code: super::ResponseCode) -> Self {
+        use super::ResponseCode as Current;
+        match code {
+            Current::OK => ResponseCode::OK,
+            Current::LoggedIn { cookie, server_version } => ResponseCode::LoggedIn { cookie, server_version },
+            Current::JoinedRoom { room_name } => ResponseCode::JoinedRoom { room_name },
+            Current::LeaveRoom => ResponseCode::LeaveRoom,
+            Current::PlayerList { players, muted, .. } => ResponseCode::PlayerList { players, muted },
+            Current::RoomList { rooms } => ResponseCode::RoomList {
+                rooms: rooms.into_iter().map(Into::into).collect(),
+            },
+            Current::CellsPlaced { generation } => ResponseCode::CellsPlaced { generation },
+            Current::BadRequest { error_msg } => ResponseCode::BadRequest { error_msg },
+            Current::Unauthorized { error_msg } => ResponseCode::Unauthorized { error_msg },
+            Current::TooManyRequests { error_msg } => ResponseCode::TooManyRequests { error_msg },
+            Current::ChatThrottled { error_msg, muted_secs } => ResponseCode::ChatThrottled { error_msg, muted_secs },
+            Current::ServerError { error_msg } => ResponseCode::ServerError { error_msg },
+            Current::NotConnected { error_msg } => ResponseCode::NotConnected { error_msg },
+            Current::KeepAlive => ResponseCode::KeepAlive,
+            Current::BanList { addrs, names } => ResponseCode::BanList { addrs, names },
+            Current::InviteFailed { reason } => ResponseCode::BadRequest {
+                error_msg: format!("invitation failed: {:?}", reason),
+            },
+            Current::SetTeamFailed { reason } => ResponseCode::BadRequest {
+                error_msg: format!("could not set the team: {:?}", reason),
+            },
+            Current::RoomNameTaken { room_name } => ResponseCode::BadRequest {
+                error_msg: format!("there is already a room named {:?}", room_name),
+            },
+            Current::RoomFull { room_name, max_players } => ResponseCode::BadRequest {
+                error_msg: format!("room {:?} is full; it takes {} players", room_name, max_players),
+            },
+            Current::Invited { .. }
+            | Current::InviteAnswered { .. }
+            | Current::GameStarted { .. }
+            | Current::GameEnded { .. }
+            | Current::Pong { .. }
+            | Current::ServerInfo { .. }
+            | Current::LobbyChat { .. }
+            | Current::GameStarting { .. }
+            | Current::GameStartCancelled { .. }
+            | Current::OwnerChanged { .. } => ResponseCode::OK,
+        }
+    }
+}
+
+impl From<super::RoomList> for RoomList {
+    fn from(room: super::RoomList) -> Self {
+        RoomList {
+            room_name:    room.room_name,
+            player_count: room.player_count,
+            in_progress:  room.in_progress,
+        }
+    }
+}
+
+impl From<super::Packet> for Packet {
+    fn from(packet: super::Packet) -> Self {
+        use super::Packet as Current;
+        match packet {
+            Current::Request {
+                sequence,
+                response_ack,
+                cookie,
+                action,
+            } => Packet::Request {
+                sequence,
+                response_ack,
+                cookie,
+                action: action.into(),
+            },
+            Current::Response {
+                sequence,
+                request_ack,
+                code,
+            } => Packet::Response {
+                sequence,
+                request_ack,
+                code: code.into(),
+            },
+            Current::Update {
+                chats,
+                game_update_seq,
+                game_updates,
+                universe_update,
+                gen_checksum,
+                ping,
+            } => Packet::Update {
+                chats,
+                game_update_seq,
+                game_updates,
+                universe_update,
+                gen_checksum,
+                ping,
+            },
+            Current::UpdateReply {
+                cookie,
+                last_chat_seq,
+                last_game_update_seq,
+                last_full_gen,
+                partial_gen,
+                pong,
+            } => Packet::UpdateReply {
+                cookie,
+                last_chat_seq,
+                last_game_update_seq,
+                last_full_gen,
+                partial_gen,
+                pong,
+            },
+            Current::GetStatus { ping } => Packet::GetStatus { ping },
+            Current::Status {
+                pong,
+                server_version,
+                player_count,
+                room_count,
+                server_name,
+            } => Packet::Status {
+                pong,
+                server_version,
+                player_count,
+                room_count,
+                server_name,
+            },
+        }
+    }
+}
@@ -0,0 +1,259 @@
+/*
+ * Copyright (C) 2021 The Conwayste Developers
+ *
+ * This program is free software: you can redistribute it and/or modify it
+ * under the terms of the GNU General Public License as published by the Free
+ * Software Foundation, either version 3 of the License, or (at your option)
+ * any later version.
+ *
+ * This program is distributed in the hope that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of  MERCHANTABILITY or
+ * FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for
+ * more details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Outgoing packets waiting to be sent. Every destination has its own queue, and flushing takes
+//! a batch of packets from each destination in turn, so no destination has to wait behind another
+//! one's backlog. A batch is as many packets as can share one datagram. Sending never waits:
+//! whatever the socket can't take right now stays queued for the next flush, and a failed send only
+//! costs that one batch.
+
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::net::SocketAddr;
+
+/// How many packets can wait for one destination before its oldest ones are dropped.
+pub const MAX_QUEUED_PER_DEST: usize = 256;
+
+pub struct Outbox<T> {
+    queues:  HashMap<SocketAddr, VecDeque<T>>,
+    turns:   VecDeque<SocketAddr>, // destinations with something queued, in the order they get to send
+    dropped: u64,                  // packets dropped because their destination's queue was full
+}
+
+impl<T> Outbox<T> {
+    pub fn new() -> Self {
+        Outbox {
+            queues:  HashMap::new(),
+            turns:   VecDeque::new(),
+            dropped: 0,
+        }
+    }
+
+    /// Queues `item` to be sent to `addr`. If `addr` already has `MAX_QUEUED_PER_DEST` items
+    /// waiting, the oldest one is dropped; anything that important gets retransmitted anyway.
+    pub fn push(&mut self, addr: SocketAddr, item: T) {
+        let turns = &mut self.turns;
+        let queue = self.queues.entry(addr).or_insert_with(|| {
+            turns.push_back(addr);
+            VecDeque::new()
+        });
+        if queue.len() >= MAX_QUEUED_PER_DEST {
+            queue.pop_front();
+            self.dropped += 1;
+            // Logged less and less often, so that the log doesn't get flooded too
+            if self.dropped.is_power_of_two() {
+                warn!(
+                    "Dropped {} outgoing packets so far because their destination's queue was full",
+                    self.dropped
+                );
+            }
+        }
+        queue.push_back(item);
+    }
+
+    /// The number of items waiting to be sent, to all destinations.
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.queues.values().map(|queue| queue.len()).sum()
+    }
+
+    #[cfg(test)]
+    pub fn is_empty(&self) -> bool {
+        self.queues.is_empty()
+    }
+
+    /// The number of items waiting to be sent to `addr`.
+    #[cfg(test)]
+    pub fn queued_for(&self, addr: SocketAddr) -> usize {
+        self.queues.get(&addr).map_or(0, |queue| queue.len())
+    }
+
+    /// Sends queued items with `send`, one destination at a time in turn, until they are all sent
+    /// or `send` fails with `WouldBlock`. Each turn sends a batch of the destination's oldest items
+    /// together; `batch_len` is given the destination and its queue, and says how many (at least
+    /// one). If `send` fails with `WouldBlock`, the batch stays at the front of its queue for the
+    /// next flush. Any other error is logged and the batch is dropped, and the other items, including
+    /// the rest for that destination, are still sent. Returns how many items were sent.
+    pub fn flush<B, F>(&mut self, mut batch_len: B, mut send: F) -> usize
+    where
+        B: FnMut(SocketAddr, &VecDeque<T>) -> usize,
+        F: FnMut(SocketAddr, &[T]) -> io::Result<()>,
+    {
+        let mut sent = 0;
+        while let Some(addr) = self.turns.pop_front() {
+            // unwraps ok because only destinations with a non-empty queue get turns
+            let queue = self.queues.get_mut(&addr).unwrap();
+            let len = batch_len(addr, queue).max(1).min(queue.len());
+            match send(addr, &queue.make_contiguous()[..len]) {
+                Ok(()) => sent += len,
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    // the socket is full, whatever the destination; it keeps its turn
+                    self.turns.push_front(addr);
+                    break;
+                }
+                Err(e) => warn!("[{}] Dropped {} packet(s) that could not be sent: {}", addr, len, e),
+            }
+            queue.drain(..len);
+            if queue.is_empty() {
+                self.queues.remove(&addr);
+            } else {
+                self.turns.push_back(addr);
+            }
+        }
+        sent
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{IpAddr, Ipv4Addr};
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::new(IpAddr::V4(Ipv4Addr::new(1, 2, 3, 4)), port)
+    }
+
+    /// Flushes `outbox` one item at a time, returning what was sent and to where.
+    fn flush_all(outbox: &mut Outbox<u32>) -> Vec<(u16, u32)> {
+        let mut sent = vec![];
+        outbox.flush(
+            |_, _| 1,
+            |addr, items| {
+                sent.push((addr.port(), items[0]));
+                Ok(())
+            },
+        );
+        sent
+    }
+
+    #[test]
+    fn test_destinations_take_turns() {
+        let mut outbox = Outbox::new();
+        for item in 0..3 {
+            outbox.push(addr(1), item);
+        }
+        outbox.push(addr(2), 10);
+        outbox.push(addr(3), 20);
+        outbox.push(addr(2), 11);
+        assert_eq!(outbox.len(), 6);
+        assert_eq!(
+            flush_all(&mut outbox),
+            vec![(1, 0), (2, 10), (3, 20), (1, 1), (2, 11), (1, 2)]
+        );
+        assert!(outbox.is_empty());
+    }
+
+    #[test]
+    fn test_would_block_keeps_items_for_next_flush() {
+        let mut outbox = Outbox::new();
+        for item in 0..3 {
+            outbox.push(addr(1), item);
+            outbox.push(addr(2), item + 10);
+        }
+        let mut room_for = 3;
+        let sent = outbox.flush(
+            |_, _| 1,
+            |_, _| {
+                if room_for == 0 {
+                    return Err(io::Error::from(io::ErrorKind::WouldBlock));
+                }
+                room_for -= 1;
+                Ok(())
+            },
+        );
+        assert_eq!(sent, 3);
+        assert_eq!((outbox.queued_for(addr(1)), outbox.queued_for(addr(2))), (1, 2));
+
+        // the destination that was cut off goes first next time
+        assert_eq!(flush_all(&mut outbox), vec![(2, 11), (1, 2), (2, 12)]);
+    }
+
+    #[test]
+    fn test_send_errors_only_cost_that_packet() {
+        let mut outbox = Outbox::new();
+        for item in 0..3 {
+            outbox.push(addr(1), item);
+            outbox.push(addr(2), item + 10);
+        }
+        let mut sent = vec![];
+        outbox.flush(
+            |_, _| 1,
+            |addr, items| {
+                if addr.port() == 1 && items[0] == 0 {
+                    return Err(io::Error::from(io::ErrorKind::PermissionDenied));
+                }
+                sent.push(items[0]);
+                Ok(())
+            },
+        );
+        assert_eq!(sent, vec![10, 1, 11, 2, 12]);
+        assert!(outbox.is_empty());
+    }
+
+    #[test]
+    fn test_full_queue_drops_oldest() {
+        let mut outbox = Outbox::new();
+        for item in 0..MAX_QUEUED_PER_DEST as u32 + 2 {
+            outbox.push(addr(1), item);
+        }
+        outbox.push(addr(2), 1000);
+        assert_eq!(outbox.queued_for(addr(1)), MAX_QUEUED_PER_DEST);
+        let sent = flush_all(&mut outbox);
+        assert_eq!(sent[0], (1, 2));
+        assert_eq!(sent[1], (2, 1000));
+        assert_eq!(sent.len(), MAX_QUEUED_PER_DEST + 1);
+    }
+
+    #[test]
+    fn test_batches_take_turns() {
+        let mut outbox = Outbox::new();
+        for item in 0..5 {
+            outbox.push(addr(1), item);
+        }
+        outbox.push(addr(2), 10);
+        let mut batches = vec![];
+        let sent = outbox.flush(
+            |_, queue| queue.len().min(2),
+            |addr, items| {
+                batches.push((addr.port(), items.to_vec()));
+                Ok(())
+            },
+        );
+        assert_eq!(sent, 6);
+        assert_eq!(
+            batches,
+            vec![(1, vec![0, 1]), (2, vec![10]), (1, vec![2, 3]), (1, vec![4])]
+        );
+        assert!(outbox.is_empty());
+    }
+
+    #[test]
+    fn test_would_block_keeps_whole_batch() {
+        let mut outbox = Outbox::new();
+        for item in 0..3 {
+            outbox.push(addr(1), item);
+        }
+        let sent = outbox.flush(|_, _| 2, |_, _| Err(io::Error::from(io::ErrorKind::WouldBlock)));
+        assert_eq!(sent, 0);
+        assert_eq!(outbox.queued_for(addr(1)), 3);
+
+        // a batch that fails otherwise is dropped whole
+        let sent = outbox.flush(|_, _| 2, |_, _| Err(io::Error::from(io::ErrorKind::PermissionDenied)));
+        assert_eq!(sent, 0);
+        assert_eq!(flush_all(&mut outbox), vec![]);
+    }
+}
@@ -20,8 +20,12 @@
 #[macro_use]
 extern crate log;
 
+mod bans;
+mod config;
+mod events;
 #[macro_use]
 mod net;
+mod outbox;
 mod utils;
 
 #[cfg(test)]
@@ -29,16 +33,30 @@ mod utils;
 extern crate proptest;
 
 use netwayste::net::{
-    bind, get_version, has_connection_timed_out, BroadcastChatMessage, NetwaystePacketCodec, NetworkManager,
-    NetworkQueue, Packet, RequestAction, ResponseCode, RoomList, UniUpdate, DEFAULT_HOST, DEFAULT_PORT, VERSION,
+    bind, bundle_len, datagram_version, decode_datagram, encode_bundle, encode_datagram,
+    encode_unsupported_version_reply, get_version, AdminCommand, BroadcastChatMessage, GenChecksum, GenStateDiffPart,
+    InviteAnswer, InviteFailure, NetworkManager, NetworkQueue, Packet, PlayerResult, RequestAction, ResponseCode,
+    RoomList, TeamFailure, UniUpdate, WireError, DEFAULT_HOST, DEFAULT_PORT, MAX_CHAT_MESSAGE_CHARS, PROTOCOL_VERSION,
+    TIMEOUT_IN_SECONDS, VERSION,
 };
 use netwayste::utils::{LatencyFilter, PingPong};
 
-use std::collections::{HashMap, VecDeque};
+use bans::{BanList, BanResponse, BanTarget};
+use config::{ServerConfig, Settings};
+use events::{EventEmitter, FileSink, ServerEvent};
+use outbox::Outbox;
+use utils::{FaultConfig, FaultInjector};
+
+use conway::error::{ConwayError, ConwayResult};
+use conway::universe::{BigBang, CellState, PlayerBuilder, Region, Universe};
+use conway::visibility::Visibility;
+
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::error::Error;
 use std::fmt;
-use std::io::{self, ErrorKind, Write};
-use std::net::SocketAddr;
+use std::io::{self, Write};
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
 use std::process::exit;
 use std::time::{self, Duration, Instant};
 
@@ -46,17 +64,20 @@ use chrono::Local;
 use clap::{App, Arg};
 use futures as Fut;
 use log::LevelFilter;
-use rand::RngCore;
+use rand::{rngs::OsRng, RngCore};
 use reqwest;
 use semver::Version;
 use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tokio::io::{stdin, AsyncBufReadExt, BufReader};
+use tokio::net::UdpSocket;
 use tokio::time as TokioTime;
-use tokio_stream::wrappers::IntervalStream;
-use tokio_util::udp::UdpFramed;
+use tokio_stream::wrappers::{IntervalStream, LinesStream};
 use Fut::prelude::*;
 use Fut::select;
 
-pub const TICK_INTERVAL_IN_MS: u64 = 10;
+pub const TICK_INTERVAL_IN_MS: u64 = 10; // default; see --tick-ms
+pub const CHAT_INTERVAL_IN_MS: u64 = 10; // chat is delivered this often, however long a tick is
 pub const NETWORK_INTERVAL_IN_MS: u64 = 100; // Arbitrarily chosen
 pub const HEARTBEAT_INTERVAL_IN_MS: u64 = 1000; // Arbitrarily chosen
 pub const REGISTER_INTERVAL_IN_MS: u64 = 10_000_000;
@@ -64,10 +85,35 @@ pub const REGISTER_RETRIES: usize = 3;
 pub const REGISTER_RETRY_SLEEP: Duration = Duration::from_millis(5000);
 pub const REGISTRY_DEFAULT_URL: &str = "https://registry.conwayste.rs/addServer";
 pub const MAX_ROOM_NAME: usize = 16;
+pub const RESERVED_ROOM_NAMES: &[&str] = &["general", "lobby", "server"]; // in any case; "general" is made at startup
+pub const MAX_PLAYERS: usize = 1000; // default; see --max-players
+pub const MAX_ROOMS: usize = 100; // default; see --max-rooms
+pub const MAX_ROOM_PLAYERS: usize = 8;
+pub const DEFAULT_TEAM_COUNT: u8 = 2;
+pub const START_COUNTDOWN_SECS: u64 = 3; // a game starts this long after its room's creator asks
 pub const MAX_NUM_CHAT_MESSAGES: usize = 128;
 pub const MAX_AGE_CHAT_MESSAGES: usize = 60 * 5; // seconds
 pub const SERVER_ID: PlayerID = PlayerID(u64::max_value()); // 0xFFFF....FFFF
 pub const DEFAULT_NAME: &str = "Leto II";
+pub const GAME_UNIVERSE_WIDTH: usize = 256;
+pub const GAME_UNIVERSE_HEIGHT: usize = 128;
+pub const TICKS_PER_GENERATION: usize = 10; // running games advance one generation every this many ticks
+pub const GAME_GENERATION_LIMIT: usize = 3000; // games end when they reach this generation
+pub const DEFAULT_FOG_RADIUS: usize = 6; // cells; see --fog-radius
+pub const CHAT_WINDOW: Duration = Duration::from_secs(10);
+pub const MAX_CHAT_MESSAGES_PER_WINDOW: usize = 10;
+pub const LOBBY_CHAT_MESSAGES_PER_WINDOW: usize = 3; // default; see --lobby-chat-rate
+pub const CHAT_VIOLATIONS_BEFORE_MUTE: u32 = 3;
+pub const CHAT_FIRST_MUTE: Duration = Duration::from_secs(30); // doubles with each violation after that
+pub const CHAT_MAX_MUTE: Duration = Duration::from_secs(60 * 60);
+pub const CHAT_VIOLATION_EXPIRY: Duration = Duration::from_secs(60 * 5); // counted from the last violation or mute
+pub const CHAT_VOLUME_PERIOD: Duration = Duration::from_secs(60); // how often ChatVolume events are emitted
+pub const INVITE_LIFETIME: Duration = Duration::from_secs(60);
+pub const SESSION_LIFETIME_IN_MINS: u64 = 24 * 60; // default; see --session-mins
+pub const EXPIRED_COOKIE_MEMORY: Duration = Duration::from_secs(60 * 60); // expired cookies are told so for this long
+pub const PING_WINDOW: Duration = Duration::from_secs(1);
+pub const MAX_PINGS_PER_WINDOW: usize = 5; // from each address; any more are ignored
+pub const COOKIE_BYTES: usize = 32; // random bytes in each cookie, too many to guess another player's
 
 #[derive(PartialEq, Debug, Clone, Copy, Eq, Hash)]
 pub struct PlayerID(pub u64);
@@ -87,10 +133,61 @@ impl fmt::Display for RoomID {
     }
 }
 
+/// Why the server could not handle a packet or request from a client. None of these stop the
+/// server; the main loop logs them and moves on to the next packet.
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum ServerError {
+    #[error("invalid packet: {reason}")]
+    InvalidPacket { reason: String },
+    #[error("cookie required for non-connect actions")]
+    MissingCookie,
+    #[error("invalid cookie")]
+    InvalidCookie,
+    #[error("session expired")]
+    SessionExpired,
+    #[error("player {player_id} not found")]
+    PlayerNotFound { player_id: PlayerID },
+    #[error("client version {client_version:?} is out of date -- please upgrade")]
+    ClientOutOfDate { client_version: String },
+    #[error("no request from player {player_id} has been acknowledged yet")]
+    RequestAckNotSet { player_id: PlayerID },
+    #[error("unexpected request action: {action:?}")]
+    UnexpectedAction { action: RequestAction },
+    #[error("request action not implemented yet: {action:?}")]
+    NotImplemented { action: RequestAction },
+    #[error("banned from this server")]
+    Banned { notify: bool }, // notify is whether to tell the sender so
+}
+
+impl ServerError {
+    /// The code to send back in a `Response` to whoever sent the packet that caused this error, or
+    /// `None` if the error should only be logged.
+    pub fn response_code(&self) -> Option<ResponseCode> {
+        let error_msg = self.to_string();
+        match self {
+            ServerError::MissingCookie
+            | ServerError::InvalidCookie
+            | ServerError::SessionExpired
+            | ServerError::ClientOutOfDate { .. } => Some(ResponseCode::Unauthorized { error_msg }),
+            ServerError::UnexpectedAction { .. } | ServerError::NotImplemented { .. } => {
+                Some(ResponseCode::BadRequest { error_msg })
+            }
+            // Only servers send these packets, so the sender is not a client that could act on a
+            // response. Answering could also set two servers bouncing errors off each other.
+            ServerError::InvalidPacket { .. } => None,
+            // The cookie was valid, so the fault lies with the server's state and not the request
+            ServerError::PlayerNotFound { .. } | ServerError::RequestAckNotSet { .. } => None,
+            ServerError::Banned { notify: true } => Some(ResponseCode::Unauthorized { error_msg }),
+            ServerError::Banned { notify: false } => None,
+        }
+    }
+}
+
 #[derive(PartialEq, Debug, Clone)]
 pub struct Player {
     pub player_id:      PlayerID,
     pub cookie:         String,
+    pub cookie_issued:  time::Instant, // the cookie is good for ServerState::cookie_life after this
     pub addr:           SocketAddr,
     pub name:           String,
     pub request_ack:    Option<u64>, // The next number we expect is request_ack + 1
@@ -98,6 +195,9 @@ pub struct Player {
     pub game_info:      Option<PlayerInGameInfo>, // none means in lobby
     pub last_received:  time::Instant, // Time of last message received from player
     pub latency_filter: LatencyFilter, // Latency information
+    pub chat_throttle:  ChatThrottle,
+    pub muted_players:  HashSet<PlayerID>, // players whose chat messages are not sent to this player
+    pub answered:       VecDeque<(u64, u64)>, // (request, response) sequence numbers of responses not acked yet
 }
 
 // info for a player as it relates to a game/room
@@ -105,7 +205,7 @@ pub struct Player {
 pub struct PlayerInGameInfo {
     room_id:          RoomID,
     chat_msg_seq_num: Option<u64>, // Server has confirmed the client has received messages up to this value.
-                                   // TODO: add support
+    last_full_gen:    Option<u64>, // Server has confirmed the client has applied the universe up to this generation.
 }
 
 impl Player {
@@ -129,6 +229,27 @@ impl Player {
         }
     }
 
+    pub fn update_full_gen(&mut self, opt_full_gen: Option<u64>) {
+        if let Some(ref mut game_info) = self.game_info {
+            if game_info.last_full_gen < opt_full_gen {
+                game_info.last_full_gen = opt_full_gen;
+            }
+        }
+    }
+
+    /// Forgets the generation the player's client last applied, so that the next universe update
+    /// it gets is a diff from the beginning of time.
+    pub fn forget_full_gen(&mut self) {
+        if let Some(ref mut game_info) = self.game_info {
+            game_info.last_full_gen = None;
+        }
+    }
+
+    /// Returns the latest generation the player's client has confirmed it has applied, if any.
+    pub fn get_confirmed_full_gen(&self) -> Option<u64> {
+        self.game_info.as_ref().and_then(|game_info| game_info.last_full_gen)
+    }
+
     // If the player has chatted, we'll return Some(N),
     // where N is the last chat message the player has
     // notified the Server it got.
@@ -158,6 +279,87 @@ impl Player {
     }
 }
 
+/// Keeps a player from flooding their room's chat, or the lobby's. Sending a message that is too
+/// long, or one message too many within `CHAT_WINDOW`, is a violation. After `CHAT_VIOLATIONS_BEFORE_MUTE`
+/// violations the player is muted, for twice as long with each violation after that.
+#[derive(PartialEq, Debug, Clone)]
+pub struct ChatThrottle {
+    window_start:         Instant,
+    window_count:         usize, // messages sent since window_start
+    violations:           u32,
+    muted_until:          Option<Instant>,
+    forget_violations_at: Option<Instant>,
+}
+
+/// Why a chat message was refused.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum ChatViolation {
+    TooLong,
+    TooFast,
+    Muted,
+}
+
+impl ChatThrottle {
+    pub fn new(now: Instant) -> Self {
+        ChatThrottle {
+            window_start:         now,
+            window_count:         0,
+            violations:           0,
+            muted_until:          None,
+            forget_violations_at: None,
+        }
+    }
+
+    /// How much longer the player is muted for, as of `now`. Zero if not muted.
+    pub fn muted_for(&self, now: Instant) -> Duration {
+        self.muted_until
+            .map(|muted_until| muted_until.saturating_duration_since(now))
+            .unwrap_or_default()
+    }
+
+    /// Counts a message sent at `now` against the limits. `too_long` is whether it was over the
+    /// server's length limit. Messages sent while muted are refused without counting as another
+    /// violation.
+    pub fn check(&mut self, now: Instant, too_long: bool) -> Result<(), ChatViolation> {
+        self.check_with_limit(now, too_long, MAX_CHAT_MESSAGES_PER_WINDOW)
+    }
+
+    /// Like `check`, but with at most `max_messages` messages allowed within `CHAT_WINDOW`.
+    pub fn check_with_limit(&mut self, now: Instant, too_long: bool, max_messages: usize) -> Result<(), ChatViolation> {
+        if self.muted_for(now) > Duration::from_secs(0) {
+            return Err(ChatViolation::Muted);
+        }
+        if self.forget_violations_at.map_or(false, |at| now >= at) {
+            self.violations = 0;
+            self.forget_violations_at = None;
+        }
+        if now.saturating_duration_since(self.window_start) >= CHAT_WINDOW {
+            self.window_start = now;
+            self.window_count = 0;
+        }
+
+        let violation = if too_long {
+            ChatViolation::TooLong
+        } else if self.window_count >= max_messages {
+            ChatViolation::TooFast
+        } else {
+            self.window_count += 1;
+            return Ok(());
+        };
+
+        self.violations += 1;
+        if self.violations >= CHAT_VIOLATIONS_BEFORE_MUTE {
+            let doublings = (self.violations - CHAT_VIOLATIONS_BEFORE_MUTE).min(16);
+            let mute = (CHAT_FIRST_MUTE * 2u32.pow(doublings)).min(CHAT_MAX_MUTE);
+            self.muted_until = Some(now + mute);
+            self.forget_violations_at = Some(now + mute + CHAT_VIOLATION_EXPIRY);
+        } else {
+            self.forget_violations_at = Some(now + CHAT_VIOLATION_EXPIRY);
+        }
+        Err(violation)
+    }
+}
+
 #[derive(PartialEq, Debug, Clone)]
 pub struct ServerChatMessage {
     pub seq_num:     u64, // sequence number
@@ -167,15 +369,66 @@ pub struct ServerChatMessage {
     pub timestamp:   Instant,
 }
 
-#[derive(Clone, PartialEq)]
 pub struct Room {
     pub room_id:        RoomID,
     pub name:           String,
     pub player_ids:     Vec<PlayerID>,
     pub game_running:   bool,
-    pub universe:       u64, // Temp until we integrate
+    pub universe:       Option<Universe>, // Some while a game is running
+    pub game_players:   Vec<PlayerID>,    // index is the player number in the universe; set when the game starts
     pub latest_seq_num: u64,
     pub messages:       VecDeque<ServerChatMessage>, // Front == Oldest, Back == Newest
+    pub chat_volume:    u64,                         // chat messages since the last ChatVolume event
+    pub game_announced: bool, // whether a GameStarted event has been emitted for the running game
+    pub owner:          Option<PlayerID>, // the player who created the room; None if the server did
+    pub password:       Option<RoomPassword>, // players other than the owner must give this to join
+    pub team_count:     u8,   // teams are numbered from 0 to team_count - 1
+    pub max_players:    usize, // joins are refused once the room has this many players
+    pub teams:          HashMap<PlayerID, u8>, // everyone in the game has a team; before that, only those who picked
+    pub ready:          HashSet<PlayerID>, // players who are ready for the next game
+    pub countdown:      Option<(Instant, u64)>, // when the next game starts, and the seconds left last announced
+    pub gen_limit:      usize, // the game ends when it reaches this generation
+    pub game_stats:     Vec<PlayerStats>, // same order as game_players; set when the game starts
+    // how far around their cells players can see past their writable regions; None if they see everything
+    pub fog_radius:     Option<usize>,
+    pub sight:          Vec<Visibility>, // same order as game_players; empty unless the game has fog
+}
+
+/// How a player in a room's game has done so far, for the results when the game ends.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlayerStats {
+    pub name:        String,
+    pub team:        u8,
+    pub generations: usize, // the last generation in which the player had live cells
+    pub cells:       usize, // live cells the player has in the latest generation
+}
+
+/// A room's password. Only a salted hash of it is kept.
+pub struct RoomPassword {
+    salt: [u8; 16],
+    hash: Vec<u8>,
+}
+
+impl RoomPassword {
+    pub fn new(password: &str) -> Self {
+        let mut salt = [0u8; 16];
+        rand::thread_rng().fill_bytes(&mut salt);
+        let hash = RoomPassword::hash(&salt, password);
+        RoomPassword { salt, hash }
+    }
+
+    fn hash(salt: &[u8], password: &str) -> Vec<u8> {
+        let mut hasher = Sha256::new();
+        hasher.update(salt);
+        hasher.update(password.as_bytes());
+        hasher.finalize().to_vec()
+    }
+
+    pub fn matches(&self, password: &str) -> bool {
+        let hash = RoomPassword::hash(&self.salt, password);
+        // Look at every byte, so that how long this takes says nothing about how close it was
+        hash.iter().zip(&self.hash).fold(0u8, |diff, (a, b)| diff | (a ^ b)) == 0
+    }
 }
 
 pub struct ServerState {
@@ -187,6 +440,32 @@ pub struct ServerState {
     pub rooms:       HashMap<RoomID, Room>,
     pub room_map:    HashMap<String, RoomID>, // map room name to room ID
     pub network_map: HashMap<PlayerID, NetworkManager>, // map Player ID to Player's network data
+    pub events:      EventEmitter,
+    pub chat_limit:  usize,   // max characters in a chat message
+    pub chat_period: Instant, // when the current ChatVolume period started
+    pub bans:        BanList,
+    pub admin_token: Option<String>, // admin requests are refused if this is None
+    pub invites:     HashMap<String, Invite>, // map invite token to invitation
+    pub full_teams:  bool,           // whether a game can only start with someone on every team
+    pub fog_radius:  Option<usize>,  // for new rooms; see Room::fog_radius
+    pub cookie_life: Option<Duration>, // players have to connect again after this; None if they never do
+    pub old_cookies: HashMap<String, Instant>, // map expired cookie to when it expired
+    pub max_players: usize,          // connections are refused once this many players are connected
+    pub max_rooms:   usize,          // no more rooms can be created once there are this many
+    pub pings:       HashMap<IpAddr, (Instant, usize)>, // when each address's ping window started, and its pings in it
+    pub discovery:   bool,           // whether to answer Discover requests, which may come from anyone on the LAN
+    pub timeout:     Duration,       // players who send nothing for this long are disconnected
+    pub lobby_rate:  usize,          // lobby chat messages each player can send per CHAT_WINDOW; 0 turns it off
+    pub old_peers:   HashMap<SocketAddr, (u8, Instant)>, // peers on an older protocol version: which, and when last heard from
+}
+
+/// An invitation to a room that has not been accepted or declined yet.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Invite {
+    pub inviter_id: PlayerID,
+    pub invitee_id: PlayerID,
+    pub room_id:    RoomID,
+    pub expires_at: Instant,
 }
 
 #[derive(Debug, Clone)]
@@ -209,9 +488,11 @@ impl RegistryParams {
 
 //////////////// Utilities ///////////////////////
 
+/// Returns a random cookie, drawn from the operating system's secure random number generator. See
+/// `ServerState::new_unique_cookie` for one that no player has.
 pub fn new_cookie() -> String {
-    let mut buf = [0u8; 12];
-    rand::thread_rng().fill_bytes(&mut buf);
+    let mut buf = [0u8; COOKIE_BYTES];
+    OsRng.fill_bytes(&mut buf);
     let config = base64::Config::new(base64::CharacterSet::UrlSafe, false);
     base64::encode_config(&buf, config)
 }
@@ -240,6 +521,33 @@ pub fn new_uuid() -> u64 {
     hash
 }
 
+/// Drops the control characters from a chat message, since they would garble the chatbox. That
+/// includes newlines; each chat message is shown on one line.
+pub fn strip_control_chars(msg: &str) -> String {
+    msg.chars().filter(|c| !c.is_control()).collect()
+}
+
+/// Checks a name a player wants to give a new room. It has to be printable, without spaces at either
+/// end that would make it look like another room's name, and not one the server keeps for itself.
+pub fn validate_room_name(room_name: &str) -> Result<(), String> {
+    if room_name.trim().is_empty() {
+        return Err("room name is empty".to_owned());
+    }
+    if room_name.chars().any(|c| c.is_control()) {
+        return Err("room name can only have printable characters".to_owned());
+    }
+    if room_name.trim() != room_name {
+        return Err("room name cannot start or end with a space".to_owned());
+    }
+    if RESERVED_ROOM_NAMES
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(room_name))
+    {
+        return Err(format!("room name {:?} is reserved", room_name));
+    }
+    Ok(())
+}
+
 pub fn validate_client_version(client_version: String) -> bool {
     let server_version = get_version();
 
@@ -268,9 +576,23 @@ impl Room {
             name:           name,
             player_ids:     player_ids,
             game_running:   false,
-            universe:       0,
+            universe:       None,
+            game_players:   vec![],
             messages:       VecDeque::<ServerChatMessage>::with_capacity(MAX_NUM_CHAT_MESSAGES),
             latest_seq_num: 0,
+            chat_volume:    0,
+            game_announced: false,
+            owner:          None,
+            password:       None,
+            team_count:     DEFAULT_TEAM_COUNT,
+            max_players:    MAX_ROOM_PLAYERS,
+            teams:          HashMap::new(),
+            ready:          HashSet::new(),
+            countdown:      None,
+            gen_limit:      GAME_GENERATION_LIMIT,
+            game_stats:     vec![],
+            fog_radius:     Some(DEFAULT_FOG_RADIUS),
+            sight:          vec![],
         }
     }
 
@@ -289,6 +611,186 @@ impl Room {
         !self.player_ids.is_empty()
     }
 
+    /// The team of everyone in the room: the one they picked, or otherwise, in the order they
+    /// joined, whichever team has the fewest players so far.
+    pub fn balanced_teams(&self) -> HashMap<PlayerID, u8> {
+        let mut teams: HashMap<PlayerID, u8> = self
+            .player_ids
+            .iter()
+            .filter_map(|player_id| self.teams.get(player_id).map(|&team| (*player_id, team)))
+            .collect();
+        let mut team_sizes = vec![0usize; self.team_count as usize];
+        for &team in teams.values() {
+            team_sizes[team as usize] += 1;
+        }
+        for &player_id in &self.player_ids {
+            if teams.contains_key(&player_id) {
+                continue;
+            }
+            // unwrap ok because a room has at least one team
+            let team = (0..team_sizes.len()).min_by_key(|&team| team_sizes[team]).unwrap();
+            team_sizes[team] += 1;
+            teams.insert(player_id, team as u8);
+        }
+        teams
+    }
+
+    /// The first team that nobody would be on if the game started now.
+    pub fn empty_team(&self) -> Option<u8> {
+        let teams = self.balanced_teams();
+        (0..self.team_count).find(|team| !teams.values().any(|t| t == team))
+    }
+
+    /// Starts a game with everyone currently in the room. Each player gets an equal-width strip of
+    /// the universe as their writable region, in the order they joined. Players without a team are
+    /// put on one. Unless the room's `fog_radius` is `None`, each player only sees their own strip
+    /// and what is around their cells.
+    pub fn start_game(&mut self) -> ConwayResult<()> {
+        let num_players = self.player_ids.len();
+        if num_players == 0 {
+            return Err(ConwayError::InvalidData {
+                reason: "cannot start a game without players".to_owned(),
+            });
+        }
+        let strip_width = GAME_UNIVERSE_WIDTH / num_players;
+        let regions: Vec<Region> = (0..num_players)
+            .map(|i| Region::new((i * strip_width) as isize, 0, strip_width, GAME_UNIVERSE_HEIGHT))
+            .collect();
+        let mut bigbang = BigBang::new()
+            .width(GAME_UNIVERSE_WIDTH)
+            .height(GAME_UNIVERSE_HEIGHT)
+            .server_mode(true)
+            .add_players(regions.iter().map(|&region| PlayerBuilder::new(region)).collect());
+        if let Some(fog_radius) = self.fog_radius {
+            bigbang = bigbang.fog_radius(fog_radius);
+        }
+        let mut uni = bigbang.birth()?;
+        match self.fog_radius {
+            Some(fog_radius) => {
+                self.sight = regions
+                    .iter()
+                    .map(|&region| Visibility::new(GAME_UNIVERSE_WIDTH, GAME_UNIVERSE_HEIGHT, region, fog_radius))
+                    .collect();
+            }
+            None => {
+                let whole = uni.region();
+                for player_num in 0..num_players {
+                    uni.set_fog(player_num, whole, false);
+                }
+                self.sight.clear();
+            }
+        }
+        self.universe = Some(uni);
+        self.teams = self.balanced_teams();
+        self.game_players = self.player_ids.clone();
+        let teams = &self.teams;
+        self.game_stats = self
+            .game_players
+            .iter()
+            .map(|player_id| PlayerStats {
+                name:        String::new(),
+                team:        teams.get(player_id).cloned().unwrap_or(0),
+                generations: 0,
+                cells:       0,
+            })
+            .collect();
+        self.ready.clear();
+        self.countdown = None;
+        self.game_running = true;
+        Ok(())
+    }
+
+    /// Calculates the next generation of the room's game, if it is running, and updates everyone's
+    /// stats. Returns true if the game has now reached its generation limit.
+    pub fn advance_game(&mut self) -> bool {
+        if !self.game_running {
+            return false;
+        }
+        let uni = match self.universe {
+            Some(ref mut uni) => uni,
+            None => return false,
+        };
+        let generation = uni.next();
+        let changes = uni.changed_cells().to_vec();
+        let mut cells = vec![0usize; self.game_stats.len()];
+        uni.each_non_dead_full(None, &mut |_, _, state| {
+            if let CellState::Alive(Some(player_num)) = state {
+                if let Some(count) = cells.get_mut(player_num) {
+                    *count += 1;
+                }
+            }
+        });
+        for (stats, count) in self.game_stats.iter_mut().zip(cells) {
+            stats.cells = count;
+            if count > 0 {
+                stats.generations = generation;
+            }
+        }
+        self.update_fog(&changes);
+        generation >= self.gen_limit
+    }
+
+    /// Brings back the fog where players can no longer see, and clears it where they now can,
+    /// given the cells of the latest generation that just changed, as (col, row, new state). Only
+    /// the cells around the changes are looked at.
+    pub fn update_fog(&mut self, changes: &[(usize, usize, CellState)]) {
+        let uni = match self.universe {
+            Some(ref mut uni) => uni,
+            None => return,
+        };
+        for (player_num, sight) in self.sight.iter_mut().enumerate() {
+            let owned = changes
+                .iter()
+                .map(|&(col, row, state)| (col, row, state == CellState::Alive(Some(player_num))));
+            for (col, row, visible) in sight.update(owned) {
+                uni.set_fog(player_num, Region::new(col as isize, row as isize, 1, 1), !visible);
+            }
+        }
+    }
+
+    /// Ends the room's game and returns how everyone in it did, leaving the room ready to start
+    /// another game with whoever is in it then. Players from the game who are no longer in the room
+    /// are marked as disconnected. The results are just the stats for now; scoring goes here.
+    pub fn end_game(&mut self) -> Vec<PlayerResult> {
+        let player_ids = &self.player_ids;
+        let results = self
+            .game_players
+            .iter()
+            .zip(self.game_stats.drain(..))
+            .map(|(player_id, stats)| PlayerResult {
+                name:         stats.name,
+                team:         stats.team,
+                generations:  stats.generations as u64,
+                cells_at_end: stats.cells as u64,
+                disconnected: !player_ids.contains(player_id),
+            })
+            .collect();
+        self.game_players.clear();
+        self.sight.clear();
+        self.universe = None;
+        self.game_running = false;
+        results
+    }
+
+    /// The player number that `player_id` sees the universe as, or `None` if they joined after the
+    /// game started, in which case they can see everything.
+    pub fn visibility(&self, player_id: PlayerID) -> Option<usize> {
+        self.game_players.iter().position(|&id| id == player_id)
+    }
+
+    /// The latest generation of the room's game and the checksum of it as seen by `player_id`, if
+    /// the game is running.
+    pub fn gen_checksum(&self, player_id: PlayerID) -> Option<GenChecksum> {
+        if !self.game_running {
+            return None;
+        }
+        let uni = self.universe.as_ref()?;
+        Some(GenChecksum {
+            generation: uni.latest_gen() as u64,
+            checksum:   uni.checksum(self.visibility(player_id)),
+        })
+    }
+
     /// Increments the room's latest sequence number
     pub fn increment_seq_num(&mut self) -> u64 {
         self.latest_seq_num += 1;
@@ -348,33 +850,21 @@ impl Room {
 }
 
 impl ServerState {
-    pub fn get_player(&self, player_id: PlayerID) -> &Player {
-        let opt_player = self.players.get(&player_id);
-
-        if opt_player.is_none() {
-            panic!("player_id: {} could not be found!", player_id);
-        }
-
-        opt_player.unwrap()
+    pub fn get_player(&self, player_id: PlayerID) -> Result<&Player, ServerError> {
+        self.players
+            .get(&player_id)
+            .ok_or(ServerError::PlayerNotFound { player_id })
     }
 
-    pub fn get_player_mut(&mut self, player_id: PlayerID) -> &mut Player {
-        let opt_player = self.players.get_mut(&player_id);
-
-        if opt_player.is_none() {
-            panic!("player_id: {} could not be found!", player_id);
-        }
-
-        opt_player.unwrap()
+    pub fn get_player_mut(&mut self, player_id: PlayerID) -> Result<&mut Player, ServerError> {
+        self.players
+            .get_mut(&player_id)
+            .ok_or(ServerError::PlayerNotFound { player_id })
     }
 
     pub fn get_room_id(&self, player_id: PlayerID) -> Option<RoomID> {
-        let player = self.get_player(player_id);
-        if player.game_info == None {
-            return None;
-        };
-
-        Some(player.game_info.as_ref().unwrap().room_id) // unwrap ok because of test above
+        let player = self.players.get(&player_id)?;
+        player.game_info.as_ref().map(|game_info| game_info.room_id)
     }
 
     pub fn get_room_mut(&mut self, player_id: PlayerID) -> Option<&mut Room> {
@@ -405,28 +895,47 @@ impl ServerState {
         let room = opt_room.unwrap();
 
         let mut players = vec![];
+        let mut teams = vec![];
         self.players.values().for_each(|p| {
             if room.player_ids.contains(&p.player_id) {
                 players.push(p.name.clone());
+                teams.push(room.teams.get(&p.player_id).cloned());
             }
         });
 
-        return ResponseCode::PlayerList { players };
+        let mut muted = vec![];
+        if let Some(player) = self.players.get(&player_id) {
+            for muted_id in &player.muted_players {
+                if let Some(muted_player) = self.players.get(muted_id) {
+                    if players.contains(&muted_player.name) {
+                        muted.push(muted_player.name.clone());
+                    }
+                }
+            }
+        }
+
+        return ResponseCode::PlayerList { players, muted, teams };
     }
 
+    /// Adds a chat message from `player_id` to their room, or if they are in the lobby, sends it to
+    /// everyone else in the lobby.
     pub fn handle_chat_message(&mut self, player_id: PlayerID, msg: String) -> ResponseCode {
         let player_in_game = self.is_player_in_game(player_id);
 
         if !player_in_game {
+            return self.handle_lobby_chat(player_id, msg);
+        }
+
+        let msg = strip_control_chars(&msg);
+        if msg.trim().is_empty() {
             return ResponseCode::BadRequest {
-                error_msg: format!("Player {} has not joined a game.", player_id),
+                error_msg: "chat message is empty".to_owned(),
             };
         }
 
-        // We're borrowing self mutably below, so let's grab this now
-        let player_name = {
-            let player = self.players.get(&player_id);
-            player.unwrap().name.clone()
+        let player_name = match self.check_chat(player_id, &msg, MAX_CHAT_MESSAGES_PER_WINDOW) {
+            Ok(player_name) => player_name,
+            Err(code) => return code,
         };
 
         // User is in game, Server needs to broadcast this to Room
@@ -443,17 +952,151 @@ impl ServerState {
 
         room.discard_older_messages();
         room.add_message(ServerChatMessage::new(player_id, player_name, msg, seq_num));
+        room.chat_volume += 1;
 
         return ResponseCode::OK;
     }
 
+    /// Starts (`mute` is true) or stops hiding the chat messages of the player named `name` from
+    /// `player_id`.
+    pub fn handle_mute_player(&mut self, player_id: PlayerID, name: &str, mute: bool) -> ResponseCode {
+        let muted_id = match self.players.values().find(|p| p.name == name) {
+            Some(p) => p.player_id,
+            None => {
+                return ResponseCode::BadRequest {
+                    error_msg: format!("no player named {:?}", name),
+                };
+            }
+        };
+        if muted_id == player_id {
+            return ResponseCode::BadRequest {
+                error_msg: "cannot mute yourself".to_owned(),
+            };
+        }
+
+        let player = match self.players.get_mut(&player_id) {
+            Some(player) => player,
+            None => {
+                return ResponseCode::BadRequest {
+                    error_msg: format!("Player {} not found", player_id),
+                };
+            }
+        };
+        if mute {
+            player.muted_players.insert(muted_id);
+        } else {
+            player.muted_players.remove(&muted_id);
+        }
+        ResponseCode::OK
+    }
+
+    /// Sets cells in the universe of the game the player is in. Nothing is set unless every cell is
+    /// in bounds, writable by the player, and being set to Dead or to Alive as the player.
+    pub fn handle_place_cells(&mut self, player_id: PlayerID, cells: Vec<(usize, usize, CellState)>) -> ResponseCode {
+        if !self.is_player_in_game(player_id) {
+            return ResponseCode::BadRequest {
+                error_msg: format!("Player {} has not joined a game.", player_id),
+            };
+        }
+
+        let room = match self.get_room_mut(player_id) {
+            Some(room) => room,
+            None => {
+                return ResponseCode::BadRequest {
+                    error_msg: format!("Player \"{}\" should be in a room! None found.", player_id),
+                };
+            }
+        };
+
+        if !room.game_running {
+            return ResponseCode::BadRequest {
+                error_msg: "game is not running".to_owned(),
+            };
+        }
+        let player_num = match room.visibility(player_id) {
+            Some(player_num) => player_num,
+            None => {
+                return ResponseCode::BadRequest {
+                    error_msg: "player joined after the game started".to_owned(),
+                };
+            }
+        };
+        let uni = match room.universe.as_mut() {
+            Some(uni) => uni,
+            None => {
+                return ResponseCode::BadRequest {
+                    error_msg: "game is not running".to_owned(),
+                };
+            }
+        };
+
+        let region = uni.region();
+        for &(col, row, state) in cells.iter() {
+            if !region.contains(col as isize, row as isize) {
+                return ResponseCode::BadRequest {
+                    error_msg: format!("cell ({}, {}) is out of bounds", col, row),
+                };
+            }
+            match state {
+                CellState::Dead => {}
+                CellState::Alive(Some(num)) if num == player_num => {}
+                _ => {
+                    return ResponseCode::BadRequest {
+                        error_msg: format!("cannot set cell ({}, {}) to {:?}", col, row, state),
+                    };
+                }
+            }
+            if uni.writable(col, row, player_num) != Ok(true) {
+                return ResponseCode::BadRequest {
+                    error_msg: format!("cell ({}, {}) is not writable", col, row),
+                };
+            }
+        }
+
+        for &(col, row, state) in cells.iter() {
+            uni.set(col, row, state, player_num);
+        }
+        let generation = uni.latest_gen();
+        let changes: Vec<_> = cells
+            .iter()
+            .map(|&(col, row, _)| (col, row, uni.cell_at(col, row)))
+            .collect();
+        room.update_fog(&changes);
+        ResponseCode::CellsPlaced { generation }
+    }
+
+    /// Makes the next universe update for the player a diff from the beginning of time, for when
+    /// their universe no longer matches the server's.
+    pub fn handle_request_full_state(&mut self, player_id: PlayerID) -> ResponseCode {
+        let game_running = match self.get_room(player_id) {
+            Some(room) => room.game_running,
+            None => {
+                return ResponseCode::BadRequest {
+                    error_msg: format!("Player {} has not joined a game.", player_id),
+                };
+            }
+        };
+        if !game_running {
+            return ResponseCode::BadRequest {
+                error_msg: "game is not running".to_owned(),
+            };
+        }
+
+        if let Ok(player) = self.get_player_mut(player_id) {
+            player.forget_full_gen();
+        }
+        ResponseCode::OK
+    }
+
     pub fn list_rooms(&mut self) -> ResponseCode {
         let mut rooms = vec![];
         self.rooms.values().for_each(|gs| {
             let room_details = RoomList {
                 room_name:    gs.name.clone(),
                 player_count: gs.player_ids.len() as u8,
+                max_players:  gs.max_players as u8,
                 in_progress:  gs.game_running,
+                has_password: gs.password.is_some(),
             };
             rooms.push(room_details);
         });
@@ -462,7 +1105,8 @@ impl ServerState {
 
     /// Creates a new room. Does _not_ check whether it already exists!
     pub fn new_room(&mut self, name: String) -> RoomID {
-        let room = Room::new(name.clone(), vec![]);
+        let mut room = Room::new(name.clone(), vec![]);
+        room.fog_radius = self.fog_radius;
         let id = room.room_id;
 
         self.room_map.insert(name, room.room_id);
@@ -472,11 +1116,14 @@ impl ServerState {
 
     pub fn create_new_room(&mut self, opt_player_id: Option<PlayerID>, room_name: String) -> ResponseCode {
         // validate length
-        if room_name.len() > MAX_ROOM_NAME {
+        if room_name.chars().count() > MAX_ROOM_NAME {
             return ResponseCode::BadRequest {
                 error_msg: format!("room name too long; max {} characters", MAX_ROOM_NAME),
             };
         }
+        if let Err(error_msg) = validate_room_name(&room_name) {
+            return ResponseCode::BadRequest { error_msg };
+        }
 
         if let Some(player_id) = opt_player_id {
             if self.is_player_in_game(player_id) {
@@ -486,16 +1133,85 @@ impl ServerState {
             }
         }
 
+        if self.rooms.len() >= self.max_rooms {
+            return ResponseCode::BadRequest {
+                error_msg: format!("too many rooms; max {}", self.max_rooms),
+            };
+        }
+
         // Create room if the room name is not already taken
         if !self.room_map.get(&room_name).is_some() {
-            self.new_room(room_name);
+            let room_id = self.new_room(room_name.clone());
+            if let Some(room) = self.rooms.get_mut(&room_id) {
+                room.owner = opt_player_id;
+            }
+            self.events.emit(ServerEvent::RoomCreated { room: room_name });
 
             return ResponseCode::OK;
         } else {
+            return ResponseCode::RoomNameTaken { room_name };
+        }
+    }
+
+    /// Creates a room for `player_id`, who becomes its owner. An empty password is the same as
+    /// none, no team count is the same as `DEFAULT_TEAM_COUNT`, and no player limit is the same as
+    /// `MAX_ROOM_PLAYERS`.
+    pub fn handle_new_room(
+        &mut self,
+        player_id: PlayerID,
+        room_name: String,
+        password: Option<String>,
+        team_count: Option<u8>,
+        max_players: Option<u8>,
+    ) -> ResponseCode {
+        let team_count = team_count.unwrap_or(DEFAULT_TEAM_COUNT);
+        if team_count == 0 || team_count as usize > MAX_ROOM_PLAYERS {
+            return ResponseCode::BadRequest {
+                error_msg: format!("a room can have 1 to {} teams", MAX_ROOM_PLAYERS),
+            };
+        }
+        let max_players = max_players.map_or(MAX_ROOM_PLAYERS, |max_players| max_players as usize);
+        if max_players == 0 || max_players > MAX_ROOM_PLAYERS {
             return ResponseCode::BadRequest {
-                error_msg: format!("room name already in use"),
+                error_msg: format!("a room can take 1 to {} players", MAX_ROOM_PLAYERS),
             };
         }
+        if max_players < team_count as usize {
+            // some teams could never have anyone on them
+            return ResponseCode::BadRequest {
+                error_msg: format!("a room for {} players can't have {} teams", max_players, team_count),
+            };
+        }
+        let code = self.create_new_room(Some(player_id), room_name.clone());
+        if code != ResponseCode::OK {
+            return code;
+        }
+        let room_id = self.room_map[&room_name]; // ok because the room was just created
+        if let Some(room) = self.rooms.get_mut(&room_id) {
+            room.team_count = team_count;
+            room.max_players = max_players;
+            if let Some(password) = password.filter(|password| !password.is_empty()) {
+                room.password = Some(RoomPassword::new(&password));
+            }
+        }
+        code
+    }
+
+    /// Joins `player_id` to the room named `room_name`, as long as they have its password or are
+    /// its owner.
+    pub fn handle_join_room(&mut self, player_id: PlayerID, room_name: &str, password: Option<String>) -> ResponseCode {
+        let room = self.room_map.get(room_name).and_then(|room_id| self.rooms.get(room_id));
+        if let Some(room) = room {
+            if let Some(ref room_password) = room.password {
+                let is_owner = room.owner == Some(player_id);
+                if !is_owner && !password.map_or(false, |password| room_password.matches(&password)) {
+                    return ResponseCode::Unauthorized {
+                        error_msg: format!("wrong password for room {:?}", room_name),
+                    };
+                }
+            }
+        }
+        self.join_room(player_id, room_name)
     }
 
     pub fn join_room(&mut self, player_id: PlayerID, room_name: &str) -> ResponseCode {
@@ -506,16 +1222,37 @@ impl ServerState {
             };
         }
 
-        let player: &mut Player = self.players.get_mut(&player_id).unwrap();
+        let player: &mut Player = match self.players.get_mut(&player_id) {
+            Some(player) => player,
+            None => {
+                return ResponseCode::BadRequest {
+                    error_msg: format!("Player {} not found", player_id),
+                };
+            }
+        };
 
         // TODO replace loop with `get_key_value` once it reaches stable. Same thing with `leave_room` algorithm
         for ref mut gs in self.rooms.values_mut() {
             if gs.name == room_name {
+                if gs.player_ids.len() >= gs.max_players {
+                    return ResponseCode::RoomFull {
+                        room_name:   room_name.to_owned(),
+                        max_players: gs.max_players as u8,
+                    };
+                }
                 gs.player_ids.push(player_id);
+                let room_id = gs.room_id;
                 player.game_info = Some(PlayerInGameInfo {
                     room_id:          gs.room_id.clone(),
                     chat_msg_seq_num: None,
+                    last_full_gen:    None,
+                });
+                self.events.emit(ServerEvent::PlayerJoined {
+                    room:   room_name.to_owned(),
+                    player: player.name.clone(),
                 });
+                // The new player isn't ready yet
+                self.cancel_countdown(room_id);
                 return ResponseCode::JoinedRoom {
                     room_name: room_name.to_owned(),
                 };
@@ -526,6 +1263,8 @@ impl ServerState {
         }
     }
 
+    /// Takes `player_id` out of their room and tells the players still in it. A room a player
+    /// created is closed once the last player has left it; the server's own rooms stay open.
     pub fn leave_room(&mut self, player_id: PlayerID) -> ResponseCode {
         let already_playing = self.is_player_in_game(player_id);
         if !already_playing {
@@ -534,89 +1273,723 @@ impl ServerState {
             };
         }
 
-        let player: &mut Player = self.players.get_mut(&player_id).unwrap();
-        {
-            let room_id = &player.game_info.as_ref().unwrap().room_id; // unwrap ok because of test above
-            for ref mut gs in self.rooms.values_mut() {
-                if gs.room_id == *room_id {
-                    // remove player_id from room's player_ids
-                    gs.player_ids.retain(|&p_id| p_id != player.player_id);
-                    break;
-                }
+        let player: &mut Player = self.players.get_mut(&player_id).unwrap(); // unwrap ok because in game
+        let room_id = player.game_info.take().unwrap().room_id; // unwrap ok because of test above
+        let player_name = player.name.clone();
+
+        let now_empty = match self.rooms.get_mut(&room_id) {
+            Some(room) => {
+                room.player_ids.retain(|&p_id| p_id != player_id);
+                room.teams.remove(&player_id);
+                room.ready.remove(&player_id);
+                room.broadcast(format!("Player {} has left.", player_name));
+                self.events.emit(ServerEvent::PlayerLeft {
+                    room:   room.name.clone(),
+                    player: player_name,
+                });
+                room.player_ids.is_empty() && room.owner.is_some()
             }
+            None => false,
+        };
+        if now_empty {
+            let room = self.rooms.remove(&room_id).unwrap(); // unwrap ok because it was just found
+            self.room_map.remove(&room.name);
+            self.events.emit(ServerEvent::RoomClosed { room: room.name });
+        } else {
+            self.cancel_countdown(room_id);
         }
-        player.game_info = None;
 
         return ResponseCode::LeaveRoom;
     }
 
-    pub fn remove_player(&mut self, player_id: PlayerID, player_cookie: &str) {
-        if self.is_player_in_game(player_id) {
-            let player = self.get_player(player_id);
-            let broadcast_msg = format!("Player {} has left.", player.name);
-            let room: &mut Room = self.get_room_mut(player_id).unwrap(); // safe because in game check verifies room's existence
-            room.broadcast(broadcast_msg);
-            let _left = self.leave_room(player_id); // Ignore return since we don't care
+    /// Closes the room named `room_name` on behalf of `player_id`, who must have created it.
+    /// Everyone in the room goes back to the lobby.
+    pub fn close_room(&mut self, player_id: PlayerID, room_name: &str) -> ResponseCode {
+        let room_id = match self.room_map.get(room_name) {
+            Some(room_id) => *room_id,
+            None => {
+                return ResponseCode::BadRequest {
+                    error_msg: format!("no room named {:?}", room_name),
+                };
+            }
+        };
+        let owner = self.rooms.get(&room_id).and_then(|room| room.owner);
+        if owner != Some(player_id) {
+            return ResponseCode::Unauthorized {
+                error_msg: "only the player who created a room can close it".to_owned(),
+            };
+        }
+
+        let room = self.rooms.remove(&room_id).unwrap(); // unwrap ok because it had an owner
+        self.room_map.remove(room_name);
+        for member_id in &room.player_ids {
+            if let Some(member) = self.players.get_mut(member_id) {
+                member.game_info = None;
+            }
+        }
+        self.events.emit(ServerEvent::RoomClosed { room: room.name });
+
+        // The owner's client only knows it is back in the lobby if told so
+        if room.player_ids.contains(&player_id) {
+            ResponseCode::LeaveRoom
+        } else {
+            ResponseCode::OK
         }
-        self.player_map.remove(player_cookie);
-        self.players.remove(&player_id);
     }
 
-    pub fn handle_disconnect(&mut self, player_id: PlayerID) -> ResponseCode {
-        let player = self.get_player(player_id);
-        let player_cookie = player.cookie.clone();
-        self.remove_player(player_id, &player_cookie);
+    /// Puts `player_id` on `team` in the room they are in, as long as its game hasn't started.
+    pub fn handle_set_team(&mut self, player_id: PlayerID, team: u8) -> ResponseCode {
+        let failed = |reason| ResponseCode::SetTeamFailed { reason };
+        let room = match self.get_room_mut(player_id) {
+            Some(room) => room,
+            None => return failed(TeamFailure::NotInRoom),
+        };
+        if room.game_running {
+            return failed(TeamFailure::GameRunning);
+        }
+        if team >= room.team_count {
+            return failed(TeamFailure::NoSuchTeam {
+                team_count: room.team_count,
+            });
+        }
+        room.teams.insert(player_id, team);
+        ResponseCode::OK
+    }
+
+    /// Starts the game in the room with everyone in it; see `Room::start_game`. With `full_teams`,
+    /// every team must have at least one player once the players without a team are put on one.
+    pub fn start_game(&mut self, room_id: RoomID) -> ConwayResult<()> {
+        let room = self.rooms.get_mut(&room_id).ok_or_else(|| ConwayError::InvalidData {
+            reason: format!("no room with ID {}", room_id),
+        })?;
+        if self.full_teams {
+            if let Some(team) = room.empty_team() {
+                return Err(ConwayError::InvalidData {
+                    reason: format!("team {} has no players", team),
+                });
+            }
+        }
+        room.start_game()?;
+        // Names are kept now, so that players who disconnect mid-game still show up in the results
+        for (stats, player_id) in room.game_stats.iter_mut().zip(&room.game_players) {
+            if let Some(player) = self.players.get(player_id) {
+                stats.name = player.name.clone();
+            }
+        }
+        Ok(())
+    }
+
+    /// Says whether `player_id` is ready for the next game in their room. Someone who is no longer
+    /// ready stops the countdown to it, if there is one.
+    pub fn handle_set_ready(&mut self, player_id: PlayerID, ready: bool) -> ResponseCode {
+        let room = match self.get_room_mut(player_id) {
+            Some(room) => room,
+            None => {
+                return ResponseCode::BadRequest {
+                    error_msg: "cannot get ready because in lobby".to_owned(),
+                };
+            }
+        };
+        if room.game_running {
+            return ResponseCode::BadRequest {
+                error_msg: "the game has already started".to_owned(),
+            };
+        }
+        let room_id = room.room_id;
+        if ready {
+            room.ready.insert(player_id);
+        } else if room.ready.remove(&player_id) {
+            self.cancel_countdown(room_id);
+        }
+        ResponseCode::OK
+    }
+
+    /// Starts the countdown to the next game in the room `player_id` created, as long as everyone
+    /// else in it is ready; asking counts as being ready. Everyone in the room is told, and the
+    /// game starts `START_COUNTDOWN_SECS` later, in `run_countdowns`.
+    pub fn handle_start_game(&mut self, player_id: PlayerID, now: Instant) -> ResponseCode {
+        let full_teams = self.full_teams;
+        let room = match self.get_room_mut(player_id) {
+            Some(room) => room,
+            None => {
+                return ResponseCode::BadRequest {
+                    error_msg: "cannot start a game because in lobby".to_owned(),
+                };
+            }
+        };
+        if room.owner != Some(player_id) {
+            return ResponseCode::Unauthorized {
+                error_msg: "only the player who created a room can start its game".to_owned(),
+            };
+        }
+        if room.game_running || room.countdown.is_some() {
+            return ResponseCode::BadRequest {
+                error_msg: "the game has already started".to_owned(),
+            };
+        }
+        room.ready.insert(player_id);
+        if full_teams {
+            if let Some(team) = room.empty_team() {
+                return ResponseCode::BadRequest {
+                    error_msg: format!("team {} has no players", team),
+                };
+            }
+        }
+        let room_id = room.room_id;
+        let waiting: Vec<PlayerID> = room
+            .player_ids
+            .iter()
+            .filter(|id| !room.ready.contains(id))
+            .cloned()
+            .collect();
+        if !waiting.is_empty() {
+            let names: Vec<String> = waiting
+                .iter()
+                .filter_map(|id| self.players.get(id).map(|player| player.name.clone()))
+                .collect();
+            return ResponseCode::BadRequest {
+                error_msg: format!("waiting for {} to be ready", names.join(", ")),
+            };
+        }
+
+        let room = self.rooms.get_mut(&room_id).unwrap(); // unwrap ok because the player is in it
+        room.countdown = Some((now + Duration::from_secs(START_COUNTDOWN_SECS), START_COUNTDOWN_SECS));
+        let (room_name, player_ids) = (room.name.clone(), room.player_ids.clone());
+        for member_id in player_ids {
+            self.notify(
+                member_id,
+                ResponseCode::GameStarting {
+                    room_name:    room_name.clone(),
+                    seconds_left: START_COUNTDOWN_SECS,
+                },
+            );
+        }
+        ResponseCode::OK
+    }
+
+    /// Stops the countdown to the next game in `room_id`, if there is one, and tells everyone in
+    /// the room.
+    pub fn cancel_countdown(&mut self, room_id: RoomID) {
+        let room = match self.rooms.get_mut(&room_id) {
+            Some(room) if room.countdown.is_some() => room,
+            _ => return,
+        };
+        room.countdown = None;
+        let (room_name, player_ids) = (room.name.clone(), room.player_ids.clone());
+        for player_id in player_ids {
+            self.notify(
+                player_id,
+                ResponseCode::GameStartCancelled {
+                    room_name: room_name.clone(),
+                },
+            );
+        }
+    }
+
+    /// Tells everyone in a room with a countdown how many seconds are left each time that goes down
+    /// by one, and starts the games whose countdown is over.
+    pub fn run_countdowns(&mut self, now: Instant) {
+        let mut announced = vec![]; // (room name, seconds left, who to tell)
+        let mut starting = vec![];
+        for room in self.rooms.values_mut() {
+            let (starts_at, last_announced) = match room.countdown {
+                Some(countdown) => countdown,
+                None => continue,
+            };
+            if now >= starts_at {
+                starting.push(room.room_id);
+                continue;
+            }
+            // rounded up, so that the last one announced is one second
+            let left = starts_at - now;
+            let seconds_left = left.as_secs() + if left.subsec_nanos() > 0 { 1 } else { 0 };
+            if seconds_left < last_announced {
+                room.countdown = Some((starts_at, seconds_left));
+                announced.push((room.name.clone(), seconds_left, room.player_ids.clone()));
+            }
+        }
+        for (room_name, seconds_left, player_ids) in announced {
+            for player_id in player_ids {
+                self.notify(
+                    player_id,
+                    ResponseCode::GameStarting {
+                        room_name: room_name.clone(),
+                        seconds_left,
+                    },
+                );
+            }
+        }
+        for room_id in starting {
+            if let Err(e) = self.start_game(room_id) {
+                warn!("Could not start the game in room {}: {:?}", room_id, e);
+                self.cancel_countdown(room_id);
+            }
+        }
+    }
+
+    /// Sends a chat message from `player_id`, who is in the lobby, to every other player in the
+    /// lobby who hasn't muted them. Lobby chat reaches more players than a room's, so it has its own
+    /// limit, `lobby_rate` messages within `CHAT_WINDOW`; zero turns it off.
+    pub fn handle_lobby_chat(&mut self, player_id: PlayerID, msg: String) -> ResponseCode {
+        if self.lobby_rate == 0 {
+            return ResponseCode::BadRequest {
+                error_msg: "lobby chat is turned off on this server".to_owned(),
+            };
+        }
+
+        let msg = strip_control_chars(&msg);
+        if msg.trim().is_empty() {
+            return ResponseCode::BadRequest {
+                error_msg: "chat message is empty".to_owned(),
+            };
+        }
+
+        let player_name = match self.check_chat(player_id, &msg, self.lobby_rate) {
+            Ok(player_name) => player_name,
+            Err(code) => return code,
+        };
+
+        let listeners: Vec<PlayerID> = self
+            .players
+            .values()
+            .filter(|p| p.player_id != player_id && p.game_info.is_none() && !p.muted_players.contains(&player_id))
+            .map(|p| p.player_id)
+            .collect();
+        for listener_id in listeners {
+            self.notify(
+                listener_id,
+                ResponseCode::LobbyChat {
+                    player_name: player_name.clone(),
+                    message:     msg.clone(),
+                },
+            );
+        }
+        ResponseCode::OK
+    }
+
+    /// Counts `msg` against `player_id`'s chat throttle, allowing `max_messages` within
+    /// `CHAT_WINDOW`. Returns the player's name if it can be sent, or the response refusing it.
+    fn check_chat(&mut self, player_id: PlayerID, msg: &str, max_messages: usize) -> Result<String, ResponseCode> {
+        let chat_limit = self.chat_limit;
+        let player = match self.players.get_mut(&player_id) {
+            Some(player) => player,
+            None => {
+                return Err(ResponseCode::BadRequest {
+                    error_msg: format!("Player {} has not connected.", player_id),
+                });
+            }
+        };
+        let now = Instant::now();
+        // Counted in chars so that a message in a language with multi-byte characters isn't cut short
+        let too_long = msg.chars().count() > chat_limit;
+        if let Err(violation) = player.chat_throttle.check_with_limit(now, too_long, max_messages) {
+            let muted_for = player.chat_throttle.muted_for(now);
+            if violation != ChatViolation::Muted && muted_for > Duration::from_secs(0) {
+                info!(
+                    "[{}] Muting player {} in chat for {:?}",
+                    player.addr, player_id, muted_for
+                );
+            }
+            let error_msg = match violation {
+                // Still counts toward a mute, but it's the message itself that's bad
+                ChatViolation::TooLong => {
+                    return Err(ResponseCode::BadRequest {
+                        error_msg: format!("chat message too long; max {} characters", chat_limit),
+                    });
+                }
+                ChatViolation::TooFast => format!(
+                    "too many chat messages; max {} every {} seconds",
+                    max_messages,
+                    CHAT_WINDOW.as_secs()
+                ),
+                ChatViolation::Muted => "muted for sending too many chat messages".to_owned(),
+            };
+            return Err(ResponseCode::ChatThrottled {
+                error_msg,
+                // rounded up, so that a client waiting this long is no longer muted
+                muted_secs: (muted_for + Duration::from_millis(999)).as_secs(),
+            });
+        }
+        Ok(player.name.clone())
+    }
+
+    /// Queues `code` to be sent to `player_id` without a request to answer. It goes out with the
+    /// player's other unacknowledged responses, and is resent until acknowledged like them.
+    pub fn notify(&mut self, player_id: PlayerID, code: ResponseCode) {
+        let player = match self.players.get_mut(&player_id) {
+            Some(player) => player,
+            None => return,
+        };
+        let response = Packet::Response {
+            sequence: player.increment_response_seq_num(),
+            request_ack: player.request_ack,
+            code,
+        };
+        if let Some(player_net) = self.network_map.get_mut(&player_id) {
+            player_net.tx_packets.buffer_item(response, Instant::now());
+        }
+    }
+
+    /// Invites the player named `name` to the room `player_id` is in, wherever they are now.
+    pub fn handle_invite_player(&mut self, player_id: PlayerID, name: &str, now: Instant) -> ResponseCode {
+        let failed = |reason| ResponseCode::InviteFailed { reason };
+        let (inviter, room_id) = match self.players.get(&player_id) {
+            Some(Player {
+                name: inviter,
+                game_info: Some(game_info),
+                ..
+            }) => (inviter.clone(), game_info.room_id),
+            _ => return failed(InviteFailure::InviterNotInRoom),
+        };
+        let invitee_id = match self.players.values().find(|player| player.name == name) {
+            Some(invitee) => invitee.player_id,
+            None => return failed(InviteFailure::NoSuchPlayer),
+        };
+        let room_name = match self.rooms.get(&room_id) {
+            Some(room) if room.player_ids.contains(&invitee_id) => return failed(InviteFailure::AlreadyInRoom),
+            Some(room) => room.name.clone(),
+            None => return failed(InviteFailure::InviterNotInRoom),
+        };
 
+        let token = new_cookie();
+        self.invites.insert(
+            token.clone(),
+            Invite {
+                inviter_id: player_id,
+                invitee_id,
+                room_id,
+                expires_at: now + INVITE_LIFETIME,
+            },
+        );
+        self.notify(
+            invitee_id,
+            ResponseCode::Invited {
+                inviter,
+                room_name,
+                token,
+            },
+        );
         ResponseCode::OK
     }
 
+    /// Takes the invitation for `player_id` with this token out of `invites`.
+    fn take_invite(&mut self, player_id: PlayerID, token: &str) -> Option<Invite> {
+        match self.invites.get(token) {
+            Some(invite) if invite.invitee_id == player_id => self.invites.remove(token),
+            _ => None,
+        }
+    }
+
+    /// Tells the inviter what became of their invitation, if they are still around.
+    fn answer_invite(&mut self, invite: &Invite, answer: InviteAnswer) {
+        let name = match self.players.get(&invite.invitee_id) {
+            Some(invitee) => invitee.name.clone(),
+            None => return,
+        };
+        self.notify(invite.inviter_id, ResponseCode::InviteAnswered { name, answer });
+    }
+
+    /// Moves `player_id` into the room they were invited to, whether or not it has a password.
+    pub fn handle_accept_invite(&mut self, player_id: PlayerID, token: &str, now: Instant) -> ResponseCode {
+        let failed = |reason| ResponseCode::InviteFailed { reason };
+        let invite = match self.take_invite(player_id, token) {
+            Some(invite) => invite,
+            None => return failed(InviteFailure::UnknownInvite),
+        };
+        if now >= invite.expires_at {
+            self.answer_invite(&invite, InviteAnswer::Expired);
+            return failed(InviteFailure::Expired);
+        }
+        let room_name = match self.rooms.get(&invite.room_id) {
+            Some(room) if !room.player_ids.contains(&invite.inviter_id) => return failed(InviteFailure::InviterLeft),
+            Some(room) if room.player_ids.contains(&player_id) => return failed(InviteFailure::AlreadyInRoom),
+            Some(room) if room.player_ids.len() >= room.max_players => return failed(InviteFailure::RoomFull),
+            Some(room) => room.name.clone(),
+            None => return failed(InviteFailure::RoomClosed),
+        };
+
+        if self.is_player_in_game(player_id) {
+            self.leave_room(player_id);
+        }
+        let code = self.join_room(player_id, &room_name);
+        self.answer_invite(&invite, InviteAnswer::Accepted);
+        code
+    }
+
+    pub fn handle_decline_invite(&mut self, player_id: PlayerID, token: &str) -> ResponseCode {
+        match self.take_invite(player_id, token) {
+            Some(invite) => {
+                self.answer_invite(&invite, InviteAnswer::Declined);
+                ResponseCode::OK
+            }
+            None => ResponseCode::InviteFailed {
+                reason: InviteFailure::UnknownInvite,
+            },
+        }
+    }
+
+    /// Drops the invitations that have run out as of `now`, and tells their inviters.
+    pub fn expire_invites(&mut self, now: Instant) {
+        let expired: Vec<String> = self
+            .invites
+            .iter()
+            .filter(|(_, invite)| now >= invite.expires_at)
+            .map(|(token, _)| token.clone())
+            .collect();
+        for token in expired {
+            if let Some(invite) = self.invites.remove(&token) {
+                self.answer_invite(&invite, InviteAnswer::Expired);
+            }
+        }
+    }
+
+    pub fn remove_player(&mut self, player_id: PlayerID, player_cookie: &str) {
+        if self.is_player_in_game(player_id) {
+            let _left = self.leave_room(player_id); // Ignore return since we don't care
+        }
+        self.hand_over_rooms(player_id);
+        self.player_map.remove(player_cookie);
+        self.players.remove(&player_id);
+        self.network_map.remove(&player_id); // along with anything still queued for them
+    }
+
+    /// Hands each room that `player_id` owns over to whoever has been in it the longest, and tells
+    /// everyone in it, so that someone can still start its games and close it. A room with nobody
+    /// in it is closed instead. Called once the player is on their way out and has left their room.
+    fn hand_over_rooms(&mut self, player_id: PlayerID) {
+        let owned: Vec<RoomID> = self
+            .rooms
+            .values()
+            .filter(|room| room.owner == Some(player_id))
+            .map(|room| room.room_id)
+            .collect();
+        for room_id in owned {
+            let room = self.rooms.get_mut(&room_id).unwrap(); // unwrap ok because it was just found
+            let owner_id = match room.player_ids.first() {
+                Some(&owner_id) => owner_id,
+                None => {
+                    let room = self.rooms.remove(&room_id).unwrap(); // unwrap ok, as above
+                    self.room_map.remove(&room.name);
+                    self.events.emit(ServerEvent::RoomClosed { room: room.name });
+                    continue;
+                }
+            };
+            room.owner = Some(owner_id);
+            let (room_name, player_ids) = (room.name.clone(), room.player_ids.clone());
+            let owner = self
+                .players
+                .get(&owner_id)
+                .map(|player| player.name.clone())
+                .unwrap_or_default();
+            info!("Room {:?} now belongs to {}", room_name, owner);
+            self.events.emit(ServerEvent::OwnerChanged {
+                room:  room_name.clone(),
+                owner: owner.clone(),
+            });
+            for member_id in player_ids {
+                self.notify(
+                    member_id,
+                    ResponseCode::OwnerChanged {
+                        room_name: room_name.clone(),
+                        owner:     owner.clone(),
+                    },
+                );
+            }
+        }
+    }
+
+    pub fn handle_disconnect(&mut self, player_id: PlayerID) -> Result<ResponseCode, ServerError> {
+        let player_cookie = self.get_player(player_id)?.cookie.clone();
+        self.remove_player(player_id, &player_cookie);
+
+        Ok(ResponseCode::OK)
+    }
+
     // not used for connect
-    pub fn process_request_action(&mut self, player_id: PlayerID, action: RequestAction) -> ResponseCode {
+    pub fn process_request_action(
+        &mut self,
+        player_id: PlayerID,
+        action: RequestAction,
+    ) -> Result<ResponseCode, ServerError> {
+        // Every handler below can count on the player being there
+        self.get_player(player_id)?;
+
         match action {
             RequestAction::Disconnect => {
                 return self.handle_disconnect(player_id);
             }
             RequestAction::KeepAlive { latest_response_ack: _ } => {
-                return ResponseCode::OK;
+                return Ok(ResponseCode::OK);
             }
             RequestAction::ListPlayers => {
-                return self.list_players(player_id);
+                return Ok(self.list_players(player_id));
             }
             RequestAction::ChatMessage { message } => {
-                return self.handle_chat_message(player_id, message);
+                return Ok(self.handle_chat_message(player_id, message));
             }
             RequestAction::ListRooms => {
-                return self.list_rooms();
+                return Ok(self.list_rooms());
             }
-            RequestAction::NewRoom { room_name } => {
-                return self.create_new_room(Some(player_id), room_name);
+            RequestAction::NewRoom {
+                room_name,
+                password,
+                team_count,
+                max_players,
+            } => {
+                return Ok(self.handle_new_room(player_id, room_name, password, team_count, max_players));
             }
-            RequestAction::JoinRoom { room_name } => {
-                return self.join_room(player_id, &room_name);
+            RequestAction::JoinRoom { room_name, password } => {
+                return Ok(self.handle_join_room(player_id, &room_name, password));
             }
             RequestAction::LeaveRoom => {
-                return self.leave_room(player_id);
+                return Ok(self.leave_room(player_id));
             }
             RequestAction::Connect { .. } => {
-                return ResponseCode::BadRequest {
+                return Ok(ResponseCode::BadRequest {
                     error_msg: "Already connected".to_owned(),
-                };
+                });
+            }
+            // Normally answered in decode_packet, before the cookie is checked
+            RequestAction::Ping { timestamp } => {
+                return Ok(ResponseCode::Pong { timestamp });
+            }
+            RequestAction::Discover => {
+                return Ok(self.server_info());
             }
             RequestAction::SetClientOptions { .. } => {
-                unimplemented!(); // TODO: add support ("auto_match" bool key, see issue #101)
+                // TODO: add support ("auto_match" bool key, see issue #101)
+                return Err(ServerError::NotImplemented { action });
             }
             RequestAction::DropPattern { .. } => {
-                unimplemented!(); // TODO: add support
+                // TODO: add support
+                return Err(ServerError::NotImplemented { action });
             }
             RequestAction::ClearArea { .. } => {
-                unimplemented!(); // TODO: add support
+                // TODO: add support
+                return Err(ServerError::NotImplemented { action });
+            }
+            RequestAction::PlaceCells { cells } => {
+                return Ok(self.handle_place_cells(player_id, cells));
+            }
+            RequestAction::RequestFullState => {
+                return Ok(self.handle_request_full_state(player_id));
+            }
+            RequestAction::MutePlayer { name } => {
+                return Ok(self.handle_mute_player(player_id, &name, true));
+            }
+            RequestAction::UnmutePlayer { name } => {
+                return Ok(self.handle_mute_player(player_id, &name, false));
+            }
+            RequestAction::Admin { token, command } => {
+                return Ok(self.handle_admin(player_id, &token, command));
+            }
+            RequestAction::CloseRoom { room_name } => {
+                return Ok(self.close_room(player_id, &room_name));
+            }
+            RequestAction::InvitePlayer { name } => {
+                return Ok(self.handle_invite_player(player_id, &name, Instant::now()));
+            }
+            RequestAction::AcceptInvite { token } => {
+                return Ok(self.handle_accept_invite(player_id, &token, Instant::now()));
+            }
+            RequestAction::DeclineInvite { token } => {
+                return Ok(self.handle_decline_invite(player_id, &token));
+            }
+            RequestAction::SetTeam { team } => {
+                return Ok(self.handle_set_team(player_id, team));
+            }
+            RequestAction::SetReady { ready } => {
+                return Ok(self.handle_set_ready(player_id, ready));
+            }
+            RequestAction::StartGame => {
+                return Ok(self.handle_start_game(player_id, Instant::now()));
             }
             RequestAction::None => {
-                return ResponseCode::BadRequest {
+                return Ok(ResponseCode::BadRequest {
                     error_msg: format!("Invalid request: {:?}", action),
+                });
+            }
+        }
+    }
+
+    /// Carries out an admin command, if `token` is the server's admin token.
+    pub fn handle_admin(&mut self, player_id: PlayerID, token: &str, command: AdminCommand) -> ResponseCode {
+        match self.admin_token {
+            Some(ref admin_token) if admin_token == token => {}
+            _ => {
+                return ResponseCode::Unauthorized {
+                    error_msg: "invalid admin token".to_owned(),
                 };
             }
         }
+
+        let parse_target = |target: &str| {
+            BanTarget::parse(target).ok_or_else(|| ResponseCode::BadRequest {
+                error_msg: "no address or name given".to_owned(),
+            })
+        };
+        match command {
+            AdminCommand::Ban { target } => {
+                let target = match parse_target(&target) {
+                    Ok(target) => target,
+                    Err(code) => return code,
+                };
+                if let Ok(admin) = self.get_player(player_id) {
+                    if target.matches(admin.addr.ip(), &admin.name) {
+                        return ResponseCode::BadRequest {
+                            error_msg: format!("{} would ban you too", target),
+                        };
+                    }
+                }
+                info!("Banning {}", target);
+                self.evict_banned(&target);
+                if self.bans.ban(target) {
+                    self.save_bans();
+                }
+                ResponseCode::OK
+            }
+            AdminCommand::Unban { target } => {
+                let target = match parse_target(&target) {
+                    Ok(target) => target,
+                    Err(code) => return code,
+                };
+                if !self.bans.unban(&target) {
+                    return ResponseCode::BadRequest {
+                        error_msg: format!("{} is not banned", target),
+                    };
+                }
+                info!("Unbanned {}", target);
+                self.save_bans();
+                ResponseCode::OK
+            }
+            AdminCommand::ListBans => ResponseCode::BanList {
+                addrs: self.bans.addrs(),
+                names: self.bans.names(),
+            },
+        }
+    }
+
+    /// Disconnects every player covered by `target`, the same way as if they had timed out.
+    fn evict_banned(&mut self, target: &BanTarget) {
+        let banned_players: Vec<PlayerID> = self
+            .players
+            .values()
+            .filter(|player| target.matches(player.addr.ip(), &player.name))
+            .map(|player| player.player_id)
+            .collect();
+        for player_id in banned_players {
+            info!("Disconnecting banned player {}", player_id);
+            if let Err(e) = self.handle_disconnect(player_id) {
+                error!("Disconnecting banned player {} failed: {}", player_id, e);
+            }
+        }
+    }
+
+    fn save_bans(&self) {
+        // The bans still apply until the server stops, so carry on
+        if let Err(e) = self.bans.save() {
+            error!("Could not save bans: {}", e);
+        }
     }
 
     pub fn is_player_in_game(&self, player_id: PlayerID) -> bool {
@@ -637,8 +2010,8 @@ impl ServerState {
     // than the last processed, it must be rejected.
     // FIXME Does not handle wrapped sequence number case yet.
     pub fn is_previously_processed_packet(&mut self, player_id: PlayerID, sequence: u64) -> bool {
-        let player: &Player = self.get_player(player_id);
-        if let Some(request_ack) = player.request_ack {
+        let opt_request_ack = self.players.get(&player_id).and_then(|player| player.request_ack);
+        if let Some(request_ack) = opt_request_ack {
             if sequence <= request_ack {
                 return true;
             }
@@ -646,6 +2019,27 @@ impl ServerState {
         false
     }
 
+    /// The response to `player_id`'s request numbered `sequence`, if it is still waiting for the
+    /// player to acknowledge it. None if it has been acknowledged, or the request had no response.
+    pub fn cached_response(&self, player_id: PlayerID, sequence: u64) -> Option<Packet> {
+        let player = self.players.get(&player_id)?;
+        let &(_, response_seq) = player
+            .answered
+            .iter()
+            .find(|&&(request_seq, _)| request_seq == sequence)?;
+        let mut response = self
+            .network_map
+            .get(&player_id)?
+            .tx_packets
+            .as_queue_type()
+            .iter()
+            .find(|pkt| pkt.sequence_number() == response_seq)?
+            .clone();
+        // `request_ack` may have advanced since this was sent
+        response.set_response_sequence(player.request_ack);
+        Some(response)
+    }
+
     pub fn get_player_id_by_cookie(&self, cookie: &str) -> Option<PlayerID> {
         match self.player_map.get(cookie) {
             Some(player_id) => Some(*player_id),
@@ -653,25 +2047,37 @@ impl ServerState {
         }
     }
 
+    /// Looks up the player a packet's cookie belongs to, telling apart cookies that expired not long
+    /// ago from ones that were never valid.
+    fn authenticate(&self, cookie: &str) -> Result<PlayerID, ServerError> {
+        match self.get_player_id_by_cookie(cookie) {
+            Some(player_id) => Ok(player_id),
+            None if self.old_cookies.contains_key(cookie) => Err(ServerError::SessionExpired),
+            None => Err(ServerError::InvalidCookie),
+        }
+    }
+
     /// Returns true if the packet already exists in the queue, otherwise it will return false, and
     /// will be added in sequence_number order.
-    pub fn add_packet_to_queue(&mut self, player_id: PlayerID, packet: Packet) -> bool {
-        // Unwrap should be safe since a player ID was already found.
-        let network: &mut NetworkManager = self.network_map.get_mut(&player_id).unwrap();
-        let already_exists = network.rx_packets.buffer_item(packet);
-        already_exists
+    pub fn add_packet_to_queue(&mut self, player_id: PlayerID, packet: Packet) -> Result<bool, ServerError> {
+        let network: &mut NetworkManager = self
+            .network_map
+            .get_mut(&player_id)
+            .ok_or(ServerError::PlayerNotFound { player_id })?;
+        let already_exists = network.rx_packets.buffer_item(packet, Instant::now());
+        Ok(already_exists)
     }
 
     /// Checks to see if the incoming packet is immediately processable
-    pub fn can_process_packet(&mut self, player_id: PlayerID, sequence_number: u64) -> bool {
-        let player: &mut Player = self.get_player_mut(player_id);
+    pub fn can_process_packet(&mut self, player_id: PlayerID, sequence_number: u64) -> Result<bool, ServerError> {
+        let player: &mut Player = self.get_player_mut(player_id)?;
         if let Some(ack) = player.request_ack {
-            trace!("[CAN PROCESS?] Ack: {} Sqn: {}", ack, sequence_number);
-            ack + 1 == sequence_number
+            trace!("[{}] [CAN PROCESS?] Ack: {} Sqn: {}", player.addr, ack, sequence_number);
+            Ok(ack + 1 == sequence_number)
         } else {
             // request_ack has not been set yet, likely first packet
             player.request_ack = Some(0);
-            true
+            Ok(true)
         }
     }
 
@@ -681,16 +2087,26 @@ impl ServerState {
         &mut self,
         player_id: PlayerID,
         action: RequestAction,
-    ) -> Result<Option<Packet>, Box<dyn Error>> {
+    ) -> Result<Option<Packet>, ServerError> {
         match action {
-            RequestAction::Connect { .. } => unreachable!(),
+            // Handled when decoded, since there is no player yet
+            RequestAction::Connect { .. } => Err(ServerError::UnexpectedAction { action }),
             _ => {
-                if let Some(response) = self.prepare_response(player_id, action.clone()) {
+                if let Some(response) = self.prepare_response(player_id, action.clone())? {
+                    let addr = match self.get_player(player_id) {
+                        Ok(player) => player.addr,
+                        // Disconnected; the response is sent once, since nobody is left to resend it to
+                        Err(_) => return Ok(Some(response)),
+                    };
                     // Buffer all responses to the client for [re-]transmission
                     let network: Option<&mut NetworkManager> = self.network_map.get_mut(&player_id);
                     if let Some(player_net) = network {
-                        trace!("[A Response to Client Request added to TX Buffer]{:?}", response);
-                        player_net.tx_packets.buffer_item(response.clone());
+                        trace!(
+                            "[{}] [A Response to Client Request added to TX Buffer]{:?}",
+                            addr,
+                            response
+                        );
+                        player_net.tx_packets.buffer_item(response.clone(), Instant::now());
                     }
                     Ok(Some(response))
                 } else {
@@ -706,7 +2122,10 @@ impl ServerState {
         let mut dequeue_count = 0;
 
         // Get the last packet we've sent to this player
-        let player_processed_seq_num = self.get_player(player_id).request_ack;
+        let (addr, player_processed_seq_num) = match self.get_player(player_id) {
+            Ok(player) => (player.addr, player.request_ack),
+            Err(_) => return,
+        };
         let mut latest_processed_seq_num;
 
         if let Some(seq_num) = player_processed_seq_num {
@@ -741,7 +2160,7 @@ impl ServerState {
         }
 
         for packet in processable_packets {
-            trace!("[Processing Client Request from RX Buffer]: {:?}", packet);
+            trace!("[{}] [Processing Client Request from RX Buffer]: {:?}", addr, packet);
             match packet {
                 Packet::Request {
                     sequence,
@@ -751,7 +2170,12 @@ impl ServerState {
                 } => {
                     latest_processed_seq_num += 1;
                     assert!(sequence == latest_processed_seq_num);
-                    let _response_packet = self.process_player_request_action(player_id, action);
+                    if let Err(e) = self.process_player_request_action(player_id, action) {
+                        error!(
+                            "[{}] Processing a buffered request from player {} failed: {}",
+                            addr, player_id, e
+                        );
+                    }
                 }
                 _ => panic!("Development bug: Non-response packet found in client buffered RX queue"),
             }
@@ -784,7 +2208,7 @@ impl ServerState {
         }
     }
 
-    pub fn collect_expired_tx_packets(&mut self) -> Vec<(Packet, SocketAddr)> {
+    pub fn collect_expired_tx_packets(&mut self, now: Instant) -> Vec<(Packet, SocketAddr)> {
         if self.players.len() == 0 {
             return vec![];
         }
@@ -799,8 +2223,10 @@ impl ServerState {
         if players_to_update.len() != 0 {
             for player_id in players_to_update {
                 // If any processed packets result in responses, prepare them below for transmission
-                let player_addr: SocketAddr = self.get_player_mut(player_id).addr;
-                let ack = self.get_player_mut(player_id).request_ack;
+                let (player_addr, ack) = match self.get_player(player_id) {
+                    Ok(player) => (player.addr, player.request_ack),
+                    Err(_) => continue,
+                };
 
                 let player_network: Option<&mut NetworkManager> = self.network_map.get_mut(&player_id);
                 if let Some(player_net) = player_network {
@@ -808,16 +2234,20 @@ impl ServerState {
                         continue;
                     }
 
-                    let indices = player_net.tx_packets.get_retransmit_indices();
+                    let indices = player_net.tx_packets.get_retransmit_indices(now);
                     trace!(
-                        "[Sending expired responses to client from TX Buffer]: {:?} Len: {}",
+                        "[{}] [Sending expired responses to client from TX Buffer]: {:?} Len: {}",
+                        player_addr,
                         player_id,
                         indices.len()
                     );
-                    let retransmissions = player_net.get_expired_tx_packets(player_addr, ack, &indices);
+                    let retransmissions = player_net.get_expired_tx_packets(player_addr, ack, &indices, now);
                     expired_responses.extend_from_slice(retransmissions.as_slice());
                 } else {
-                    error!("I haven't found a NetworkManager for Player: {}", player_id);
+                    error!(
+                        "[{}] I haven't found a NetworkManager for Player: {}",
+                        player_addr, player_id
+                    );
                     continue;
                 }
             }
@@ -878,6 +2308,11 @@ impl ServerState {
                     player_network.tx_pop_front_with_count(removal_count);
                 }
             }
+            if let Some(player) = self.players.get_mut(&player_id) {
+                player
+                    .answered
+                    .retain(|&(_, response_seq)| response_ack == 0 || response_seq > response_ack - 1);
+            }
         }
     }
 
@@ -889,10 +2324,17 @@ impl ServerState {
     ///  3. Client should notified if version requires updating
     ///  4. Ignore if already received or processed
     /// Always returns either Ok(Some(Packet::Response{...})), Ok(None), or error.
-    pub fn decode_packet(&mut self, addr: SocketAddr, packet: Packet) -> Result<Option<Packet>, Box<dyn Error>> {
+    pub fn decode_packet(&mut self, addr: SocketAddr, packet: Packet) -> Result<Option<Packet>, ServerError> {
+        if self.bans.is_banned_addr(addr.ip()) {
+            return Err(ServerError::Banned {
+                notify: self.bans.should_notify(addr.ip()),
+            });
+        }
         match packet.clone() {
             Packet::Response { .. } | Packet::Update { .. } | Packet::Status { .. } => {
-                return Err(Box::new(io::Error::new(ErrorKind::InvalidData, "invalid packet type")));
+                return Err(ServerError::InvalidPacket {
+                    reason: "packet type is only sent by servers".to_owned(),
+                });
             }
             Packet::Request {
                 sequence,
@@ -900,15 +2342,22 @@ impl ServerState {
                 cookie,
                 action,
             } => {
+                // Answered for anyone, logged in or not, without taking part in sequencing
+                match action {
+                    RequestAction::Ping { timestamp } => return Ok(self.handle_ping(addr, timestamp, Instant::now())),
+                    RequestAction::Discover => return Ok(self.handle_discover(addr, Instant::now())),
+                    _ => {}
+                }
                 match action {
                     RequestAction::Connect { .. } => (),
                     RequestAction::KeepAlive { latest_response_ack: _ } => (),
                     _ => {
                         if cookie == None {
-                            return Err(Box::new(io::Error::new(ErrorKind::InvalidData, "no cookie")));
+                            return Err(ServerError::MissingCookie);
                         } else {
                             trace!(
-                                "[Request] cookie: {:?} sequence: {} resp_ack: {:?} event: {:?}",
+                                "[{}] [Request] cookie: {:?} sequence: {} resp_ack: {:?} event: {:?}",
+                                addr,
                                 cookie,
                                 sequence,
                                 response_ack,
@@ -919,34 +2368,23 @@ impl ServerState {
                 }
                 // handle connect (create user, and save cookie)
                 if let RequestAction::Connect { name, client_version } = action {
-                    if validate_client_version(client_version) {
+                    if self.bans.is_banned_name(&name) {
+                        return Err(ServerError::Banned {
+                            notify: self.bans.should_notify(addr.ip()),
+                        });
+                    }
+                    if validate_client_version(client_version.clone()) {
                         let response = self.handle_new_connection(name, addr);
                         return Ok(Some(response));
                     } else {
-                        return Err(Box::new(io::Error::new(
-                            ErrorKind::Other,
-                            "client out of date -- please upgrade",
-                        )));
+                        return Err(ServerError::ClientOutOfDate { client_version });
                     };
                 } else {
                     // look up player by cookie
-                    let cookie = match cookie {
-                        Some(cookie) => cookie,
-                        None => {
-                            return Err(Box::new(io::Error::new(
-                                ErrorKind::InvalidData,
-                                "cookie required for non-connect actions",
-                            )));
-                        }
-                    };
-                    let player_id = match self.get_player_id_by_cookie(cookie.as_str()) {
-                        Some(player_id) => player_id,
-                        None => {
-                            return Err(Box::new(io::Error::new(ErrorKind::PermissionDenied, "invalid cookie")));
-                        }
-                    };
+                    let cookie = cookie.ok_or(ServerError::MissingCookie)?;
+                    let player_id = self.authenticate(cookie.as_str())?;
 
-                    let mut player: &mut Player = self.get_player_mut(player_id);
+                    let mut player: &mut Player = self.get_player_mut(player_id)?;
                     player.last_received = time::Instant::now(); // reset time of last received packet from player
                     match action.clone() {
                         RequestAction::KeepAlive { latest_response_ack } => {
@@ -964,25 +2402,27 @@ impl ServerState {
 
                     // Check to see if it can be processed right away, otherwise buffer it for later consumption.
                     // Not sure if I like this name but it'll do for now.
-                    if self.can_process_packet(player_id, sequence) {
-                        trace!("[PROCESS IMMEDIATE]");
+                    if self.can_process_packet(player_id, sequence)? {
+                        trace!("[{}] [PROCESS IMMEDIATE]", addr);
                         return self.process_player_request_action(player_id, action);
                     }
 
                     // Packet may be resent by client but has since been processed.
                     if self.is_previously_processed_packet(player_id, sequence) {
-                        trace!("\t [ALREADY PROCESSED]");
-                        return Ok(None);
+                        trace!("[{}] [ALREADY PROCESSED]", addr);
+                        // The response may have been lost, so send it again rather than doing the
+                        // request twice
+                        return Ok(self.cached_response(player_id, sequence));
                     }
 
                     // Returns true if the packet already exists in the queue
-                    if self.add_packet_to_queue(player_id, packet) {
-                        trace!("\t [ALREADY QUEUED]");
+                    if self.add_packet_to_queue(player_id, packet)? {
+                        trace!("[{}] [ALREADY QUEUED]", addr);
                         return Ok(None);
                     }
 
                     // In the event we buffered it, we do not send a response
-                    trace!("\t [BUFFERED]");
+                    trace!("[{}] [BUFFERED]", addr);
                     Ok(None)
                 }
             }
@@ -990,27 +2430,16 @@ impl ServerState {
                 cookie,
                 last_chat_seq,
                 last_game_update_seq: _,
-                last_full_gen: _,
+                last_full_gen,
                 partial_gen: _,
                 pong: _,
             } => {
-                let opt_player_id = self.get_player_id_by_cookie(cookie.as_str());
-
-                if opt_player_id.is_none() {
-                    return Err(Box::new(io::Error::new(ErrorKind::PermissionDenied, "invalid cookie")));
-                }
-
-                let player_id = opt_player_id.unwrap();
-                let opt_player = self.players.get_mut(&player_id);
-
-                if opt_player.is_none() {
-                    return Err(Box::new(io::Error::new(ErrorKind::NotFound, "player not found")));
-                }
-
-                let player: &mut Player = opt_player.unwrap();
+                let player_id = self.authenticate(cookie.as_str())?;
+                let player: &mut Player = self.get_player_mut(player_id)?;
 
                 if player.game_info.is_some() {
                     player.update_chat_seq_num(last_chat_seq);
+                    player.update_full_gen(last_full_gen);
                 }
 
                 player.latency_filter.update();
@@ -1031,47 +2460,78 @@ impl ServerState {
         }
     }
 
-    pub fn prepare_response(&mut self, player_id: PlayerID, action: RequestAction) -> Option<Packet> {
-        let response_code = self.process_request_action(player_id, action.clone());
+    /// Takes the sequence number for the next response to `player_id`, and acknowledges the request
+    /// it answers. Returns None if the player is gone.
+    fn next_response_numbers(&mut self, player_id: PlayerID) -> Result<Option<(u64, Option<u64>)>, ServerError> {
+        let player: &mut Player = match self.players.get_mut(&player_id) {
+            Some(player) => player,
+            None => return Ok(None),
+        };
+        // Should have been set after the first packet
+        let ack = player.request_ack.ok_or(ServerError::RequestAckNotSet { player_id })?;
+        let sequence = player.increment_response_seq_num();
+        player.request_ack = Some(ack + 1);
+        player.answered.push_back((ack + 1, sequence));
+        Ok(Some((sequence, player.request_ack)))
+    }
 
-        let (sequence, request_ack);
+    pub fn prepare_response(
+        &mut self,
+        player_id: PlayerID,
+        action: RequestAction,
+    ) -> Result<Option<Packet>, ServerError> {
+        // Filtered away at the decoding packet layer
+        if let RequestAction::KeepAlive { .. } = action {
+            return Err(ServerError::UnexpectedAction { action });
+        }
 
-        match action {
-            // Filtered away at the decoding packet layer
-            RequestAction::KeepAlive { latest_response_ack: _ } => unreachable!(),
-            // Prepare a response for all other requests
-            _ => {
-                let opt_player: Option<&mut Player> = self.players.get_mut(&player_id);
-
-                match opt_player {
-                    Some(player) => {
-                        sequence = player.increment_response_seq_num();
-                        if let Some(ack) = player.request_ack {
-                            player.request_ack = Some(ack + 1);
-                            request_ack = player.request_ack;
-                        } else {
-                            panic!("Player's request ack has never been set. It should have been set after the first packet!");
-                        }
-                    }
-                    None => {
-                        // This happens with Disconnect packets -- player was deleted at top of this
-                        // function.
-                        return None;
-                    }
-                }
-            }
-        }
+        // A disconnecting player is gone once their request is processed, so the response that
+        // acknowledges it is numbered beforehand
+        let numbered_early = match action {
+            RequestAction::Disconnect => self.next_response_numbers(player_id)?,
+            _ => None,
+        };
 
-        Some(Packet::Response {
+        let response_code = match self.process_request_action(player_id, action) {
+            Ok(response_code) => response_code,
+            // The client still needs a response, or it will keep resending the request
+            Err(e @ ServerError::NotImplemented { .. }) => ResponseCode::BadRequest {
+                error_msg: e.to_string(),
+            },
+            Err(e) => return Err(e),
+        };
+
+        // Prepare a response for all other requests
+        let numbers = match numbered_early {
+            Some(_) => numbered_early,
+            None => self.next_response_numbers(player_id)?,
+        };
+        let (sequence, request_ack) = match numbers {
+            Some(numbers) => numbers,
+            None => return Ok(None),
+        };
+
+        Ok(Some(Packet::Response {
             sequence:    sequence,
             request_ack: request_ack,
             code:        response_code,
-        })
+        }))
     }
 
     pub fn handle_new_connection(&mut self, name: String, addr: SocketAddr) -> Packet {
+        if self.players.len() >= self.max_players {
+            warn!("[{}] Refused connection: the server is full", addr);
+            return Packet::Response {
+                sequence:    0,
+                request_ack: None,
+                code:        ResponseCode::Unauthorized {
+                    error_msg: "server full".to_owned(),
+                },
+            };
+        }
         if self.is_unique_player_name(&name) {
             let player = self.add_new_player(name, addr.clone());
+            info!("[{}] Player {:?} {} connected", addr, player.name, player.player_id);
             let cookie = player.cookie.clone();
 
             // Sequence is assumed to start at 0 for all new connections
@@ -1086,6 +2546,7 @@ impl ServerState {
             return response;
         } else {
             // not a unique name
+            debug!("[{}] Refused connection: name {:?} is taken", addr, name);
             let response = Packet::Response {
                 sequence:    0,
                 request_ack: None,
@@ -1131,13 +2592,14 @@ impl ServerState {
                 let messages_available = unsent_messages.len() != 0;
                 // TODO: add support
                 let game_updates_available = false;
-                let universe_updates_available = false;
+                let universe_updates_available = false; // sent separately by construct_universe_updates
 
                 let update_packet = Packet::Update {
                     chats:           unsent_messages,
                     game_updates:    vec![],
                     game_update_seq: None,
                     universe_update: UniUpdate::NoChange,
+                    gen_checksum:    room.gen_checksum(player_id),
                     ping:            PingPong::ping(),
                 };
 
@@ -1150,6 +2612,159 @@ impl ServerState {
         return client_updates;
     }
 
+    /// Advances every running game by one generation. A game that reaches its generation limit
+    /// ends, and everyone in its room is sent the results.
+    pub fn advance_games(&mut self) {
+        let mut ended = vec![]; // (room name, generations, results, who to tell)
+        for room in self.rooms.values_mut() {
+            if room.advance_game() {
+                let generations = room.universe.as_ref().map_or(0, |uni| uni.latest_gen() as u64);
+                let results = room.end_game();
+                info!("Game in room {:?} ended after {} generations", room.name, generations);
+                ended.push((room.name.clone(), generations, results, room.player_ids.clone()));
+            }
+        }
+        for (room_name, generations, results, player_ids) in ended {
+            for player_id in player_ids {
+                // The next game's universe starts over at the first generation
+                if let Some(player) = self.players.get_mut(&player_id) {
+                    player.forget_full_gen();
+                }
+                self.notify(
+                    player_id,
+                    ResponseCode::GameEnded {
+                        room_name: room_name.clone(),
+                        generations,
+                        results: results.clone(),
+                    },
+                );
+            }
+        }
+    }
+
+    /// Emits the room events that aren't tied to a request: games starting and ending, and the chat
+    /// volume of every room once per `CHAT_VOLUME_PERIOD`. Everyone in a game that just started is
+    /// also told who is on which team.
+    pub fn emit_room_events(&mut self, now: Instant) {
+        let report_chat_volume = now.duration_since(self.chat_period) >= CHAT_VOLUME_PERIOD;
+        if report_chat_volume {
+            self.chat_period = now;
+        }
+        let mut started = vec![]; // (room name, players in the game with their teams)
+        for room in self.rooms.values_mut() {
+            if room.game_running != room.game_announced {
+                room.game_announced = room.game_running;
+                let event = if room.game_running {
+                    let teams: Vec<(PlayerID, u8)> = room
+                        .game_players
+                        .iter()
+                        .map(|player_id| (*player_id, room.teams.get(player_id).cloned().unwrap_or(0)))
+                        .collect();
+                    started.push((room.name.clone(), teams));
+                    ServerEvent::GameStarted {
+                        room:    room.name.clone(),
+                        players: room.game_players.len(),
+                    }
+                } else {
+                    ServerEvent::GameEnded {
+                        room: room.name.clone(),
+                    }
+                };
+                self.events.emit(event);
+            }
+            if report_chat_volume {
+                self.events.emit(ServerEvent::ChatVolume {
+                    room:        room.name.clone(),
+                    messages:    room.chat_volume,
+                    period_secs: CHAT_VOLUME_PERIOD.as_secs(),
+                });
+                room.chat_volume = 0;
+            }
+        }
+        for (room_name, teams) in started {
+            let named_teams: Vec<(String, u8)> = teams
+                .iter()
+                .filter_map(|(player_id, team)| self.players.get(player_id).map(|player| (player.name.clone(), *team)))
+                .collect();
+            for (player_id, _) in teams {
+                self.notify(
+                    player_id,
+                    ResponseCode::GameStarted {
+                        room_name: room_name.clone(),
+                        teams:     named_teams.clone(),
+                    },
+                );
+            }
+        }
+    }
+
+    /// For each player in a running game, creates one Update packet per part of the diff from the
+    /// latest generation the player has applied to the room's latest generation. If the player has
+    /// not applied any generation yet, or the server no longer has the one they applied, the diff
+    /// is from the beginning of time, so that it can be applied to an empty universe.
+    /// Every packet also carries the checksum of the latest generation, as the player sees it.
+    pub fn construct_universe_updates(&self) -> Vec<(SocketAddr, Packet)> {
+        let mut universe_updates: Vec<(SocketAddr, Packet)> = vec![];
+
+        for room in self.rooms.values() {
+            if !room.game_running {
+                continue;
+            }
+            let uni = match room.universe {
+                Some(ref uni) => uni,
+                None => continue,
+            };
+            let latest_gen = uni.latest_gen();
+
+            for &player_id in &room.player_ids {
+                let player = match self.players.get(&player_id) {
+                    Some(player) => player,
+                    None => continue,
+                };
+                let full_gen = player.get_confirmed_full_gen().unwrap_or(0) as usize;
+                if full_gen >= latest_gen {
+                    continue; // caught up
+                }
+
+                let visibility = room.visibility(player_id);
+                let opt_diff = uni
+                    .diff(full_gen, latest_gen, visibility)
+                    .or_else(|| uni.diff(0, latest_gen, visibility));
+                let diff = match opt_diff {
+                    Some(diff) => diff,
+                    None => continue,
+                };
+                let gen_checksum = GenChecksum {
+                    generation: latest_gen as u64,
+                    checksum:   uni.checksum(visibility),
+                };
+                let parts = match GenStateDiffPart::split(&diff) {
+                    Some(parts) => parts,
+                    None => {
+                        warn!(
+                            "[{}] Diff {}->{} for player {} is too large to send",
+                            player.addr, diff.gen0, diff.gen1, player_id
+                        );
+                        continue;
+                    }
+                };
+                for part in parts {
+                    let update_packet = Packet::Update {
+                        chats:           vec![],
+                        game_updates:    vec![],
+                        game_update_seq: None,
+                        universe_update: UniUpdate::Diff { diff: part },
+                        gen_checksum:    Some(gen_checksum.clone()),
+                        ping:            PingPong::ping(),
+                    };
+                    universe_updates.push((player.addr.clone(), update_packet));
+                }
+            }
+        }
+
+        universe_updates
+    }
+
     /// Creates a vector of messages that the provided Player has not yet acknowledged.
     /// Exits early if the player is already caught up.
     pub fn collect_unacknowledged_messages(&self, room: &Room, player: &Player) -> Option<Vec<BroadcastChatMessage>> {
@@ -1192,9 +2807,14 @@ impl ServerState {
 
         let unsent_messages: Vec<BroadcastChatMessage> = raw_unsent_messages
             .iter()
+            .filter(|msg| !player.muted_players.contains(&msg.player_id))
             .map(|msg| BroadcastChatMessage::new(msg.seq_num, msg.player_name.clone(), msg.message.clone()))
             .collect();
 
+        if unsent_messages.is_empty() {
+            return None;
+        }
+
         return Some(unsent_messages);
     }
 
@@ -1210,12 +2830,29 @@ impl ServerState {
         }
     }
 
+    /// Returns a new cookie that isn't any current player's, nor one that expired recently.
+    pub fn new_unique_cookie(&self) -> String {
+        self.unused_cookie(new_cookie)
+    }
+
+    /// Calls `generate` until it returns a cookie that isn't in use.
+    fn unused_cookie<F: FnMut() -> String>(&self, mut generate: F) -> String {
+        loop {
+            let cookie = generate();
+            if !self.player_map.contains_key(&cookie) && !self.old_cookies.contains_key(&cookie) {
+                return cookie;
+            }
+            warn!("Generated a cookie that is already in use; generating another");
+        }
+    }
+
     pub fn add_new_player(&mut self, name: String, addr: SocketAddr) -> &mut Player {
-        let cookie = new_cookie();
+        let cookie = self.new_unique_cookie();
         let player_id = PlayerID(new_uuid());
         let player = Player {
             player_id:      player_id.clone(),
             cookie:         cookie.clone(),
+            cookie_issued:  Instant::now(),
             addr:           addr,
             name:           name,
             request_ack:    None,
@@ -1223,35 +2860,166 @@ impl ServerState {
             game_info:      None,
             last_received:  Instant::now(),
             latency_filter: LatencyFilter::new(),
+            chat_throttle:  ChatThrottle::new(Instant::now()),
+            answered:       VecDeque::new(),
+            muted_players:  HashSet::new(),
         };
 
         // save player into players hash map, and save player ID into hash map using cookie
         self.player_map.insert(cookie, player_id);
-        self.players.insert(player_id, player);
         self.network_map.insert(player_id, NetworkManager::new());
-
-        let player = self.get_player_mut(player_id);
+        let player = self.players.entry(player_id).or_insert(player);
 
         // We expect that the Server proceed with `1` after the connection has been established
         player.increment_response_seq_num();
         player
     }
 
-    pub fn remove_timed_out_clients(&mut self) {
+    /// Disconnects the players who haven't sent anything, not even a KeepAlive, within `timeout`
+    /// as of `now`.
+    pub fn remove_timed_out_clients(&mut self, now: Instant) {
         let mut timed_out_players: Vec<PlayerID> = vec![];
 
         for (p_id, p) in self.players.iter() {
-            if has_connection_timed_out(p.last_received) {
-                info!("Player(cookie={:?}) has timed out", p.cookie);
+            if now.saturating_duration_since(p.last_received) > self.timeout {
+                info!("[{}] Player {} has timed out", p.addr, p_id);
                 timed_out_players.push(*p_id);
             }
         }
 
         for player_id in timed_out_players {
-            self.handle_disconnect(player_id);
+            if let Err(e) = self.handle_disconnect(player_id) {
+                error!("Disconnecting timed out player {} failed: {}", player_id, e);
+            }
+        }
+    }
+
+    /// Disconnects the players whose cookies have outlived `cookie_life` as of `now`, so that they
+    /// have to connect again, and forgets the cookies that expired more than
+    /// `EXPIRED_COOKIE_MEMORY` ago.
+    pub fn expire_sessions(&mut self, now: Instant) {
+        if let Some(cookie_life) = self.cookie_life {
+            let expired: Vec<(PlayerID, SocketAddr, String)> = self
+                .players
+                .values()
+                .filter(|player| now.saturating_duration_since(player.cookie_issued) >= cookie_life)
+                .map(|player| (player.player_id, player.addr, player.cookie.clone()))
+                .collect();
+            for (player_id, addr, cookie) in expired {
+                info!("[{}] Player {} has had their session expire", addr, player_id);
+                self.remove_player(player_id, &cookie);
+                self.old_cookies.insert(cookie, now);
+            }
+        }
+        self.old_cookies
+            .retain(|_, expired_at| now.saturating_duration_since(*expired_at) < EXPIRED_COOKIE_MEMORY);
+    }
+
+    /// Answers a Ping from `addr` with a Pong echoing `timestamp`, unless that address has already
+    /// pinged `MAX_PINGS_PER_WINDOW` times within the last `PING_WINDOW`. The Pong is not sequenced
+    /// or retransmitted; a client that loses one just pings again.
+    pub fn handle_ping(&mut self, addr: SocketAddr, timestamp: u64, now: Instant) -> Option<Packet> {
+        if !self.count_ping(addr, now) {
+            debug!("[{}] Ignoring a ping; too many lately", addr);
+            return None;
+        }
+        Some(Packet::Response {
+            sequence:    0,
+            request_ack: None,
+            code:        ResponseCode::Pong { timestamp },
+        })
+    }
+
+    /// Answers a Discover from `addr` with a ServerInfo, if this server is discoverable. These count
+    /// against the same limit as pings, since a ServerInfo is bigger than the request for it.
+    pub fn handle_discover(&mut self, addr: SocketAddr, now: Instant) -> Option<Packet> {
+        if !self.discovery {
+            trace!("[{}] Ignoring a discovery request; not discoverable", addr);
+            return None;
+        }
+        if !self.count_ping(addr, now) {
+            debug!("[{}] Ignoring a discovery request; too many lately", addr);
+            return None;
+        }
+        Some(Packet::Response {
+            sequence:    0,
+            request_ack: None,
+            code:        self.server_info(),
+        })
+    }
+
+    /// Whether to send the unsupported-version reply to `addr`, for a datagram from it that could not
+    /// be decoded because of `error`. Only a missing header or an unsupported protocol version get
+    /// one. These count against the same limit as pings, so that a spoofed source address can't get
+    /// more than a few replies sent to someone else.
+    pub fn should_reply_unsupported(&mut self, addr: SocketAddr, error: &WireError, now: Instant) -> bool {
+        match error {
+            WireError::MissingHeader | WireError::UnsupportedVersion { .. } => {}
+            _ => return false,
+        }
+        if !self.count_ping(addr, now) {
+            debug!("[{}] Not replying to an undecodable datagram; too many lately", addr);
+            return false;
+        }
+        true
+    }
+
+    /// Counts a ping or discovery request from `addr` at `now`. Returns false if it is over the
+    /// address's limit and should be ignored.
+    fn count_ping(&mut self, addr: SocketAddr, now: Instant) -> bool {
+        let (window_start, count) = self.pings.entry(addr.ip()).or_insert((now, 0));
+        if now.saturating_duration_since(*window_start) >= PING_WINDOW {
+            *window_start = now;
+            *count = 0;
+        }
+        if *count >= MAX_PINGS_PER_WINDOW {
+            return false;
+        }
+        *count += 1;
+        true
+    }
+
+    fn server_info(&self) -> ResponseCode {
+        ResponseCode::ServerInfo {
+            server_name:    self.name.clone(),
+            server_version: VERSION.to_owned(),
+            player_count:   self.player_map.len() as u64,
+            room_count:     self.room_map.len() as u64,
+        }
+    }
+
+    /// Forgets the addresses whose last ping window ended before `now`.
+    pub fn expire_pings(&mut self, now: Instant) {
+        self.pings
+            .retain(|_, (window_start, _)| now.saturating_duration_since(*window_start) < PING_WINDOW);
+    }
+
+    /// Notes that a datagram from `addr` came in protocol `version` at `now`, so that what we send it
+    /// goes out in that version too. Only peers on an older version than ours are remembered.
+    pub fn note_version(&mut self, addr: SocketAddr, version: u8, now: Instant) {
+        if version == PROTOCOL_VERSION {
+            self.old_peers.remove(&addr);
+        } else {
+            self.old_peers.insert(addr, (version, now));
         }
     }
 
+    /// The protocol version to send to `addr` in: the one its datagrams last came in, or ours if we
+    /// haven't heard from it lately. A peer that isn't logged in yet is answered in its version too.
+    pub fn peer_version(&self, addr: SocketAddr) -> u8 {
+        self.old_peers
+            .get(&addr)
+            .map_or(PROTOCOL_VERSION, |&(version, _)| version)
+    }
+
+    /// Forgets the protocol version of the peers that have sent nothing for `timeout` as of `now`,
+    /// like the players who are disconnected for it.
+    pub fn expire_old_peers(&mut self, now: Instant) {
+        let timeout = self.timeout;
+        self.old_peers
+            .retain(|_, (_, last_heard)| now.saturating_duration_since(*last_heard) < timeout);
+    }
+
     /// Creates a new struct representing the global state of this server. Initially, there is one
     /// room -- "general".
     pub fn new() -> Self {
@@ -1264,6 +3032,23 @@ impl ServerState {
             player_map:  HashMap::<String, PlayerID>::new(),
             room_map:    HashMap::<String, RoomID>::new(),
             network_map: HashMap::<PlayerID, NetworkManager>::new(),
+            events:      EventEmitter::disabled(),
+            chat_limit:  MAX_CHAT_MESSAGE_CHARS,
+            chat_period: Instant::now(),
+            bans:        BanList::new(),
+            admin_token: None,
+            invites:     HashMap::new(),
+            full_teams:  false,
+            fog_radius:  Some(DEFAULT_FOG_RADIUS),
+            cookie_life: Some(Duration::from_secs(SESSION_LIFETIME_IN_MINS * 60)),
+            old_cookies: HashMap::new(),
+            max_players: MAX_PLAYERS,
+            max_rooms:   MAX_ROOMS,
+            pings:       HashMap::new(),
+            discovery:   false,
+            timeout:     Duration::from_secs(TIMEOUT_IN_SECONDS),
+            lobby_rate:  LOBBY_CHAT_MESSAGES_PER_WINDOW,
+            old_peers:   HashMap::new(),
         };
         server_state.new_room("general".to_owned());
         server_state
@@ -1272,18 +3057,32 @@ impl ServerState {
     fn process_packet(&mut self, packet_tuple: (Packet, SocketAddr)) -> Vec<(Packet, SocketAddr)> {
         let (packet, addr) = packet_tuple;
 
-        debug!("{:?}", packet);
+        trace!("[{}] Received: {:?}", addr, packet);
 
         // Decode incoming and send a Response to the Requester
-        let decode_result = self.decode_packet(addr, packet.clone());
-        if let Ok(opt_response_packet) = decode_result {
-            if let Some(response_packet) = opt_response_packet {
+        match self.decode_packet(addr, packet.clone()) {
+            Ok(Some(response_packet)) => {
                 let response = (response_packet, addr.clone());
                 return vec![response];
             }
-        } else {
-            let err = decode_result.unwrap_err();
-            error!("Decoding packet failed, from {:?}: {:?}", addr, err);
+            Ok(None) => {}
+            Err(err) => {
+                if let ServerError::Banned { .. } = err {
+                    // Expected, and could be flooded with
+                    debug!("[{}] Ignoring packet from banned address", addr);
+                } else {
+                    error!("[{}] Decoding packet failed: {}", addr, err);
+                }
+                // Let the client know, rather than leaving it to time out
+                if let Some(code) = err.response_code() {
+                    let response = Packet::Response {
+                        sequence: 0,
+                        request_ack: None,
+                        code,
+                    };
+                    return vec![(response, addr)];
+                }
+            }
         }
 
         vec![]
@@ -1309,14 +3108,30 @@ impl ServerState {
         // Process players in lobby
         self.process_buffered_packets_in_lobby();
 
-        self.collect_expired_tx_packets()
+        self.collect_expired_tx_packets(Instant::now())
     }
 
-    fn garbage_collection(&mut self) -> Vec<(SocketAddr, Packet)> {
+    /// Sends everyone the chat messages they haven't acknowledged yet. This runs on its own timer
+    /// rather than with each tick, so that a slow tick doesn't hold up chat.
+    fn deliver_chat(&mut self) -> Vec<(SocketAddr, Packet)> {
         self.expire_old_messages_in_all_rooms(time::Instant::now());
-        let update_packets_vec = self.construct_client_updates();
+        self.construct_client_updates()
+    }
+
+    fn garbage_collection(&mut self) -> Vec<(SocketAddr, Packet)> {
+        let mut update_packets_vec = vec![];
+        self.run_countdowns(Instant::now());
+        if self.tick % TICKS_PER_GENERATION == 0 {
+            self.advance_games();
+            update_packets_vec.extend(self.construct_universe_updates());
+        }
+        self.emit_room_events(Instant::now());
+        self.expire_invites(Instant::now());
+        self.expire_sessions(Instant::now());
+        self.expire_pings(Instant::now());
+        self.expire_old_peers(Instant::now());
 
-        self.remove_timed_out_clients();
+        self.remove_timed_out_clients(Instant::now());
         self.tick = 1usize.wrapping_add(self.tick);
         return update_packets_vec;
     }
@@ -1362,6 +3177,103 @@ async fn try_register(reg_params: RegistryParams) {
     }
 }
 
+/// Simulates a bad network for testing clients: packets in both directions may be dropped,
+/// duplicated, or delayed. Only available with the `netsim` feature.
+struct NetSim {
+    inbound:  FaultInjector<Packet>,
+    outbound: FaultInjector<Packet>,
+    config:   FaultConfig, // the same for both directions and every address
+}
+
+impl NetSim {
+    fn new(seed: u64) -> Self {
+        NetSim {
+            inbound:  FaultInjector::new(seed),
+            outbound: FaultInjector::new(seed.wrapping_add(1)),
+            config:   FaultConfig::default(),
+        }
+    }
+
+    fn set_config(&mut self, config: FaultConfig) {
+        self.inbound.set_default_config(config.clone());
+        self.outbound.set_default_config(config.clone());
+        self.config = config;
+    }
+}
+
+/// Queues `packet` to be sent to `addr`, by way of the network simulation if there is one.
+fn queue_outgoing(outbox: &mut Outbox<Packet>, netsim: &mut Option<NetSim>, addr: SocketAddr, packet: Packet) {
+    match netsim {
+        Some(netsim) => netsim.outbound.push(addr, packet, Instant::now()),
+        None => outbox.push(addr, packet),
+    }
+}
+
+/// Changes `config` as the operator's `netsim` console command says: `drop <rate>`,
+/// `dup <rate>`, `delay <ms> [<jitter ms>]`, or `off`. With no arguments, nothing changes.
+fn apply_netsim_command(config: &mut FaultConfig, args: &[&str]) -> Result<(), String> {
+    let rate = |arg: &str| match arg.parse::<f64>() {
+        Ok(rate) if rate >= 0.0 && rate <= 1.0 => Ok(rate),
+        _ => Err(format!("expected a rate from 0 to 1, not {:?}", arg)),
+    };
+    let millis = |arg: &str| {
+        arg.parse::<u64>()
+            .map(Duration::from_millis)
+            .map_err(|_| format!("expected a number of milliseconds, not {:?}", arg))
+    };
+    match args {
+        [] => {}
+        ["off"] => *config = FaultConfig::default(),
+        ["drop", arg] => config.drop_rate = rate(arg)?,
+        ["dup", arg] => config.duplicate_rate = rate(arg)?,
+        ["delay", delay] => {
+            config.min_delay = millis(delay)?;
+            config.max_delay = config.min_delay;
+        }
+        ["delay", delay, jitter] => {
+            config.min_delay = millis(delay)?;
+            config.max_delay = config.min_delay + millis(jitter)?;
+        }
+        _ => return Err("usage: netsim [drop <rate> | dup <rate> | delay <ms> [<jitter ms>] | off]".to_owned()),
+    }
+    Ok(())
+}
+
+/// Carries out a command typed into the server's console.
+fn handle_console_command(line: &str, netsim: &mut Option<NetSim>) {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    match words.split_first() {
+        None => {}
+        Some((&"netsim", args)) => {
+            let netsim = match netsim {
+                Some(netsim) => netsim,
+                None => {
+                    warn!("Network simulation is not enabled; start the server with --netsim to use it");
+                    return;
+                }
+            };
+            let mut config = netsim.config.clone();
+            if let Err(e) = apply_netsim_command(&mut config, args) {
+                warn!("{}", e);
+                return;
+            }
+            info!(
+                "Network simulation: drop {}, duplicate {}, delay {:?} to {:?}",
+                config.drop_rate, config.duplicate_rate, config.min_delay, config.max_delay
+            );
+            netsim.set_config(config);
+        }
+        Some((&"help", _)) => {
+            info!("netsim                      - show the network simulation settings");
+            info!("netsim drop <rate>          - drop this fraction of packets, from 0 to 1");
+            info!("netsim dup <rate>           - send this fraction of packets twice, from 0 to 1");
+            info!("netsim delay <ms> [<jitter>] - hold packets this long, plus up to <jitter> ms more");
+            info!("netsim off                  - stop dropping, duplicating and delaying packets");
+        }
+        Some((command, _)) => warn!("Unknown command {:?}; try help", command),
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error + 'static>> {
     env_logger::Builder::new()
@@ -1374,22 +3286,31 @@ async fn main() -> Result<(), Box<dyn std::error::Error + 'static>> {
                 record.args(),
             )
         })
-        .filter(None, LevelFilter::Trace)
+        .filter(None, LevelFilter::Info)
         .filter(Some("futures"), LevelFilter::Off)
-        .filter(Some("tokio_core"), LevelFilter::Off)
         .filter(Some("tokio_reactor"), LevelFilter::Off)
+        .parse_env("RUST_LOG") // e.g. RUST_LOG=trace to see every packet
         .init();
 
     let matches = App::new("server")
         .about("game server for Conwayste")
+        .arg(
+            Arg::with_name("config")
+                .short("c")
+                .long("config")
+                .help("TOML file of settings, with the same names as these options; options given here win")
+                .env("CONWAYSTE_CONFIG")
+                .takes_value(true),
+        )
         .arg(
             Arg::with_name("address")
                 .short("l")
                 .long("listen")
                 .help(&format!(
-                    "address to listen for connections on [default {}]",
+                    "IP address to listen for connections on; :: listens on IPv6 and IPv4 [default {}]",
                     DEFAULT_HOST
                 ))
+                .env("CONWAYSTE_LISTEN")
                 .takes_value(true),
         )
         .arg(
@@ -1397,6 +3318,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error + 'static>> {
                 .short("p")
                 .long("port")
                 .help(&format!("port to listen for connections on [default {}]", DEFAULT_PORT))
+                .env("CONWAYSTE_PORT")
                 .takes_value(true),
         )
         .arg(
@@ -1411,6 +3333,126 @@ async fn main() -> Result<(), Box<dyn std::error::Error + 'static>> {
                 .help("public-facing address for clients to connect to; this gets sent to registrar")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("max-chat-chars")
+                .long("max-chat-chars")
+                .help(&format!(
+                    "longest chat message allowed, in characters [default {}]",
+                    MAX_CHAT_MESSAGE_CHARS
+                ))
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("tick-ms")
+                .long("tick-ms")
+                .help(&format!(
+                    "milliseconds between game ticks; games advance one generation every {} ticks [default {}]",
+                    TICKS_PER_GENERATION, TICK_INTERVAL_IN_MS
+                ))
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("events-file")
+                .long("events-file")
+                .help("file to append room events to as JSON lines, for a status page")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("admin-token")
+                .long("admin-token")
+                .help("secret that clients must give to use admin commands, like banning; without it, they are refused")
+                .env("CONWAYSTE_ADMIN_TOKEN")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("ban-file")
+                .long("ban-file")
+                .help("file of banned addresses and player names, one per line; loaded at startup and saved on changes")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("ban-response")
+                .long("ban-response")
+                .help("what banned addresses get back: nothing, or one Unauthorized response [default notify]")
+                .possible_values(&["silent", "notify"])
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("netsim")
+                .long("netsim")
+                .help(
+                    "simulate a bad network, with this random seed; change its settings by typing netsim commands \
+                     (see help) [needs the netsim feature]",
+                )
+                .env("CONWAYSTE_NETSIM")
+                .value_name("SEED")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("discoverable")
+                .long("discoverable")
+                .help("answer discovery broadcasts on the LAN with the server's name, version and player count"),
+        )
+        .arg(
+            Arg::with_name("full-teams")
+                .long("full-teams")
+                .help("only start a game once every team in the room has at least one player"),
+        )
+        .arg(
+            Arg::with_name("fog-radius")
+                .long("fog-radius")
+                .help(&format!(
+                    "how many cells around their own cells players can see, or 0 to show everyone everything \
+                     [default {}]",
+                    DEFAULT_FOG_RADIUS
+                ))
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("session-mins")
+                .long("session-mins")
+                .help(&format!(
+                    "minutes a player's session lasts before they have to connect again, or 0 for no limit \
+                     [default {}]",
+                    SESSION_LIFETIME_IN_MINS
+                ))
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("timeout-secs")
+                .long("timeout-secs")
+                .help(&format!(
+                    "seconds a player can go without sending anything before they are disconnected [default {}]",
+                    TIMEOUT_IN_SECONDS
+                ))
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("lobby-chat-rate")
+                .long("lobby-chat-rate")
+                .help(&format!(
+                    "chat messages each player in the lobby can send every {} seconds, or 0 to turn lobby chat \
+                     off [default {}]",
+                    CHAT_WINDOW.as_secs(),
+                    LOBBY_CHAT_MESSAGES_PER_WINDOW
+                ))
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("max-players")
+                .long("max-players")
+                .help(&format!(
+                    "how many players can be connected at once [default {}]",
+                    MAX_PLAYERS
+                ))
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("max-rooms")
+                .long("max-rooms")
+                .help(&format!("how many rooms there can be at once [default {}]", MAX_ROOMS))
+                .takes_value(true),
+        )
         .arg(
             Arg::with_name("registrar-url")
                 .long("registrar-url")
@@ -1422,8 +3464,17 @@ async fn main() -> Result<(), Box<dyn std::error::Error + 'static>> {
         )
         .get_matches();
 
-    let opt_host = matches.value_of("address");
-    let opt_port = matches.value_of("port").map(|port_str| {
+    let file = match matches.value_of("config") {
+        Some(config_file) => ServerConfig::load(Path::new(config_file)).unwrap_or_else(|e| {
+            error!("Error in config file {:?}: {}", config_file, e);
+            exit(1);
+        }),
+        None => ServerConfig::default(),
+    };
+    let settings = Settings::new(matches, file);
+
+    let opt_host = settings.value_of("address");
+    let opt_port = settings.value_of("port").map(|port_str| {
         port_str.parse::<u16>().unwrap_or_else(|e| {
             error!("Error while attempting to parse {:?} as port number: {:?}", port_str, e);
             exit(1);
@@ -1431,59 +3482,237 @@ async fn main() -> Result<(), Box<dyn std::error::Error + 'static>> {
     });
 
     let udp = bind(opt_host, opt_port).await.unwrap_or_else(|e| {
-        error!("Error while trying to bind UDP socket: {:?}", e);
+        error!("Error while trying to bind UDP socket: {}", e);
         exit(1);
     });
 
-    trace!("Listening for connections on {:?}...", udp.local_addr()?);
-
-    let (mut udp_sink, udp_stream) = UdpFramed::new(udp, NetwaystePacketCodec).split();
-    let mut udp_stream = udp_stream.fuse();
+    info!("Listening for connections on {:?}...", udp.local_addr()?);
 
     let mut server_state = ServerState::new();
 
-    if let Some(name) = matches.value_of("name") {
+    if let Some(name) = settings.value_of("name") {
         server_state.name = name.to_owned();
     }
 
-    if let Some(public_addr) = matches.value_of("public-address") {
+    if let Some(public_addr) = settings.value_of("public-address") {
         let mut reg_params = RegistryParams::new(public_addr.to_owned());
-        if let Some(registrar_url) = matches.value_of("registrar-url") {
+        if let Some(registrar_url) = settings.value_of("registrar-url") {
             reg_params.registry_url = registrar_url.to_owned();
         }
         server_state.reg_params = Some(reg_params);
     }
 
-    let tick_interval = TokioTime::interval(Duration::from_millis(TICK_INTERVAL_IN_MS));
-    let mut tick_interval_stream = IntervalStream::new(tick_interval).fuse();
+    if let Some(chat_limit_str) = settings.value_of("max-chat-chars") {
+        server_state.chat_limit = match chat_limit_str.parse::<usize>() {
+            Ok(chat_limit) if chat_limit > 0 => chat_limit,
+            _ => {
+                error!(
+                    "Invalid max-chat-chars {:?}; expected a positive number",
+                    chat_limit_str
+                );
+                exit(1);
+            }
+        };
+    }
 
-    let network_interval = TokioTime::interval(Duration::from_millis(NETWORK_INTERVAL_IN_MS));
-    let mut network_interval_stream = IntervalStream::new(network_interval).fuse();
+    let tick_ms = match settings.value_of("tick-ms") {
+        Some(tick_ms_str) => match tick_ms_str.parse::<u64>() {
+            Ok(tick_ms) if tick_ms > 0 => tick_ms,
+            _ => {
+                error!("Invalid tick-ms {:?}; expected a positive number", tick_ms_str);
+                exit(1);
+            }
+        },
+        None => TICK_INTERVAL_IN_MS,
+    };
 
-    let heartbeat_interval = TokioTime::interval(Duration::from_millis(HEARTBEAT_INTERVAL_IN_MS));
-    let mut heartbeat_interval_stream = IntervalStream::new(heartbeat_interval).fuse();
+    if let Some(events_file) = settings.value_of("events-file") {
+        let sink = FileSink::open(Path::new(events_file)).unwrap_or_else(|e| {
+            error!("Error while trying to open events file {:?}: {}", events_file, e);
+            exit(1);
+        });
+        server_state.events = EventEmitter::spawn_writer(Box::new(sink));
+        let rooms = server_state.rooms.values().map(|room| room.name.clone()).collect();
+        server_state.events.emit(ServerEvent::ServerStarted {
+            server_name: server_state.name.clone(),
+            rooms,
+        });
+    }
 
-    let register_interval = TokioTime::interval(Duration::from_millis(REGISTER_INTERVAL_IN_MS));
-    let mut register_interval_stream = IntervalStream::new(register_interval).fuse();
+    if let Some(ban_file) = settings.value_of("ban-file") {
+        server_state.bans = BanList::load(Path::new(ban_file)).unwrap_or_else(|e| {
+            error!("Error while trying to load bans from {:?}: {}", ban_file, e);
+            exit(1);
+        });
+    }
+    // clap checks this, but not when it comes from the config file
+    match settings.value_of("ban-response") {
+        Some("silent") => server_state.bans.response = BanResponse::Silent,
+        Some("notify") | None => {}
+        Some(ban_response) => {
+            error!("Invalid ban-response {:?}; expected silent or notify", ban_response);
+            exit(1);
+        }
+    }
 
-    loop {
-        select! {
-            _ = tick_interval_stream.select_next_some() => {
-                let update_packets = server_state.garbage_collection();
-                for (addr, packet) in update_packets {
-                    udp_sink.send((packet, addr)).await?;
-                }
-            },
+    // An empty token would let anyone in
+    server_state.admin_token = settings
+        .value_of("admin-token")
+        .filter(|token| !token.is_empty())
+        .map(|token| token.to_owned());
+
+    server_state.full_teams = settings.is_present("full-teams");
+    server_state.discovery = settings.is_present("discoverable");
+
+    if let Some(fog_radius_str) = settings.value_of("fog-radius") {
+        server_state.fog_radius = match fog_radius_str.parse::<usize>() {
+            Ok(0) => None,
+            Ok(fog_radius) => Some(fog_radius),
+            Err(_) => {
+                error!("Invalid fog-radius {:?}; expected a number", fog_radius_str);
+                exit(1);
+            }
+        };
+        // the general room was made before we knew
+        for room in server_state.rooms.values_mut() {
+            room.fog_radius = server_state.fog_radius;
+        }
+    }
+
+    if let Some(session_mins_str) = settings.value_of("session-mins") {
+        server_state.cookie_life = match session_mins_str.parse::<u64>() {
+            Ok(0) => None,
+            Ok(session_mins) => Some(Duration::from_secs(session_mins * 60)),
+            Err(_) => {
+                error!("Invalid session-mins {:?}; expected a number", session_mins_str);
+                exit(1);
+            }
+        };
+    }
+
+    if let Some(timeout_str) = settings.value_of("timeout-secs") {
+        server_state.timeout = match timeout_str.parse::<u64>() {
+            Ok(timeout_secs) if timeout_secs > 0 => Duration::from_secs(timeout_secs),
+            _ => {
+                error!("Invalid timeout-secs {:?}; expected a positive number", timeout_str);
+                exit(1);
+            }
+        };
+    }
+
+    if let Some(lobby_rate_str) = settings.value_of("lobby-chat-rate") {
+        server_state.lobby_rate = match lobby_rate_str.parse::<usize>() {
+            Ok(lobby_rate) => lobby_rate,
+            Err(_) => {
+                error!("Invalid lobby-chat-rate {:?}; expected a number", lobby_rate_str);
+                exit(1);
+            }
+        };
+    }
+
+    if let Some(max_players_str) = settings.value_of("max-players") {
+        server_state.max_players = match max_players_str.parse::<usize>() {
+            Ok(max_players) if max_players > 0 => max_players,
+            _ => {
+                error!("Invalid max-players {:?}; expected a positive number", max_players_str);
+                exit(1);
+            }
+        };
+    }
+
+    if let Some(max_rooms_str) = settings.value_of("max-rooms") {
+        server_state.max_rooms = match max_rooms_str.parse::<usize>() {
+            Ok(max_rooms) if max_rooms > 0 => max_rooms,
+            _ => {
+                error!("Invalid max-rooms {:?}; expected a positive number", max_rooms_str);
+                exit(1);
+            }
+        };
+    }
+
+    // Off unless asked for, in which case it starts out passing everything through unharmed
+    let netsim = settings.value_of("netsim").map(|seed_str| {
+        if !cfg!(feature = "netsim") {
+            error!("Network simulation needs a server built with the netsim feature");
+            exit(1);
+        }
+        let seed = seed_str.parse::<u64>().unwrap_or_else(|e| {
+            error!("Invalid netsim seed {:?}: {}", seed_str, e);
+            exit(1);
+        });
+        warn!("Network simulation is enabled; type help for its commands");
+        NetSim::new(seed)
+    });
+    let console = LinesStream::new(BufReader::new(stdin()).lines());
+
+    serve(server_state, udp, tick_ms, netsim, console).await;
+    Ok(())
+}
+
+/// Runs the server on `udp` until the process exits, with a tick every `tick_ms` milliseconds.
+/// Lines from `console` are taken as console commands.
+async fn serve<S>(mut server_state: ServerState, udp: UdpSocket, tick_ms: u64, mut netsim: Option<NetSim>, console: S)
+where
+    S: Stream<Item = io::Result<String>> + Unpin,
+{
+    // Datagrams are read and decoded here rather than through UdpFramed, so that the sender of one
+    // that can't be decoded is known and can be told why. The buffer fits any UDP datagram, so that
+    // one too large for us is reported as such rather than cut short.
+    let mut recv_buf = vec![0u8; u16::MAX as usize];
+    // Sending never waits, so that no one client can hold up the others; see `Outbox`.
+    let mut outbox = Outbox::new();
+    let mut console = console.fuse();
+
+    let tick_interval = TokioTime::interval(Duration::from_millis(tick_ms));
+    let mut tick_interval_stream = IntervalStream::new(tick_interval).fuse();
+
+    let chat_interval = TokioTime::interval(Duration::from_millis(CHAT_INTERVAL_IN_MS));
+    let mut chat_interval_stream = IntervalStream::new(chat_interval).fuse();
+
+    let network_interval = TokioTime::interval(Duration::from_millis(NETWORK_INTERVAL_IN_MS));
+    let mut network_interval_stream = IntervalStream::new(network_interval).fuse();
+
+    let heartbeat_interval = TokioTime::interval(Duration::from_millis(HEARTBEAT_INTERVAL_IN_MS));
+    let mut heartbeat_interval_stream = IntervalStream::new(heartbeat_interval).fuse();
+
+    let register_interval = TokioTime::interval(Duration::from_millis(REGISTER_INTERVAL_IN_MS));
+    let mut register_interval_stream = IntervalStream::new(register_interval).fuse();
+
+    loop {
+        select! {
+            _ = tick_interval_stream.select_next_some() => {
+                let update_packets = server_state.garbage_collection();
+                for (addr, packet) in update_packets {
+                    queue_outgoing(&mut outbox, &mut netsim, addr, packet);
+                }
+                if let Some(ref mut netsim) = netsim {
+                    let now = Instant::now();
+                    for (addr, packet) in netsim.inbound.drain_ready(now) {
+                        for (response, addr) in server_state.process_packet((packet, addr)) {
+                            netsim.outbound.push(addr, response, now);
+                        }
+                    }
+                    for (addr, packet) in netsim.outbound.drain_ready(now) {
+                        outbox.push(addr, packet);
+                    }
+                }
+            },
+            _ = chat_interval_stream.select_next_some() => {
+                let chat_packets = server_state.deliver_chat();
+                for (addr, packet) in chat_packets {
+                    queue_outgoing(&mut outbox, &mut netsim, addr, packet);
+                }
+            },
             _ = network_interval_stream.select_next_some() => {
                 let retransmissions = server_state.maintain_network_state();
-                for packet_addr_tuple in retransmissions {
-                    udp_sink.send(packet_addr_tuple).await?;
+                for (packet, addr) in retransmissions {
+                    queue_outgoing(&mut outbox, &mut netsim, addr, packet);
                 }
             },
             _ = heartbeat_interval_stream.select_next_some() => {
                 let heartbeats = server_state.send_heartbeats();
-                for packet_addr_tuple in heartbeats {
-                    udp_sink.send(packet_addr_tuple).await?;
+                for (packet, addr) in heartbeats {
+                    queue_outgoing(&mut outbox, &mut netsim, addr, packet);
                 }
             },
             _ = register_interval_stream.select_next_some() => {
@@ -1491,23 +3720,105 @@ async fn main() -> Result<(), Box<dyn std::error::Error + 'static>> {
                     tokio::spawn(try_register(reg_params.clone()));
                 }
             },
-            addr_packet_result = udp_stream.select_next_some() => {
-                if let Ok(addr_packet_tuple) = addr_packet_result {
-                    let responses = server_state.process_packet(addr_packet_tuple);
-                    for response in responses {
-                        udp_sink.send(response).await?;
+            readable_result = udp.readable().fuse() => {
+                if let Err(e) = readable_result {
+                    warn!("Could not wait for the socket to be readable: {}", e);
+                    continue;
+                }
+                // Everything that has arrived, until the socket would block
+                loop {
+                    let (len, addr) = match udp.try_recv_from(&mut recv_buf) {
+                        Ok(len_addr) => len_addr,
+                        Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                        Err(e) => {
+                            warn!("Could not receive from the socket: {}", e);
+                            break;
+                        }
+                    };
+                    let datagram = &recv_buf[..len];
+                    let packets = match decode_datagram(datagram) {
+                        Ok((packets, _)) => packets,
+                        Err(e) => {
+                            warn!("[{}] Dropped a datagram that could not be decoded: {}", addr, e);
+                            if server_state.should_reply_unsupported(addr, &e, Instant::now()) {
+                                if let Err(e) = try_send_unsupported_version_reply(&udp, addr) {
+                                    debug!("[{}] Could not send the unsupported-version reply: {}", addr, e);
+                                }
+                            }
+                            continue;
+                        }
+                    };
+                    // Replies go out in the version this came in
+                    if let Some(version) = datagram_version(datagram) {
+                        server_state.note_version(addr, version, Instant::now());
                     }
+                    for packet in packets {
+                        if let Some(ref mut netsim) = netsim {
+                            netsim.inbound.push(addr, packet, Instant::now());
+                        } else {
+                            let responses = server_state.process_packet((packet, addr));
+                            for (packet, addr) in responses {
+                                outbox.push(addr, packet);
+                            }
+                        }
+                    }
+                }
+            }
+            line_result = console.select_next_some() => {
+                match line_result {
+                    Ok(line) => handle_console_command(&line, &mut netsim),
+                    Err(e) => warn!("Could not read from the console: {}", e),
                 }
             }
         }
+
+        // Whatever doesn't fit in the socket's buffer now goes out on a later pass; the chat timer
+        // fires often enough for that. Packets for the same player share datagrams where they fit,
+        // in the protocol version that player sends in.
+        outbox.flush(
+            |addr, queue| bundle_len(server_state.peer_version(addr), queue.iter()),
+            |addr, packets| try_send_packets(&udp, server_state.peer_version(addr), addr, packets),
+        );
+    }
+}
+
+/// Encodes `packets` as one datagram in protocol `version`.
+fn encode_packets(version: u8, packets: &[Packet]) -> io::Result<Vec<u8>> {
+    match packets {
+        [packet] => encode_datagram(version, packet),
+        _ => encode_bundle(version, packets),
     }
+    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Sends `packets` to `addr` in one datagram in protocol `version` if the socket can take it right
+/// away, or fails with `WouldBlock`.
+fn try_send_packets(udp: &UdpSocket, version: u8, addr: SocketAddr, packets: &[Packet]) -> io::Result<()> {
+    let datagram = encode_packets(version, packets)?;
+    udp.try_send_to(&datagram, addr)?;
+    Ok(())
+}
+
+/// Sends the unsupported-version reply to `addr`, without a header, if the socket can take it right
+/// away. It is neither queued nor retransmitted; a peer that misses it gets another the next time it
+/// tries.
+fn try_send_unsupported_version_reply(udp: &UdpSocket, addr: SocketAddr) -> io::Result<()> {
+    let datagram = encode_unsupported_version_reply().map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    udp.try_send_to(&datagram, addr)?;
+    Ok(())
 }
 
 #[cfg(test)]
 mod netwayste_server_tests {
     use super::*;
+    use ::proptest::option;
     use ::proptest::strategy::*;
-    use netwayste::net::NetAttempt;
+    use netwayste::bot::{run_bots, BotConfig};
+    use netwayste::net::{
+        NetAttempt, NetwaystePacketCodec, MIN_PROTOCOL_VERSION, PROTOCOL_MAGIC, PROTOCOL_VERSION,
+        RETRANSMISSION_THRESHOLD_IN_MS, UDP_MTU_SIZE,
+    };
+    use tokio_util::udp::UdpFramed;
 
     fn fake_socket_addr() -> SocketAddr {
         use std::net::{IpAddr, Ipv4Addr};
@@ -1532,7 +3843,7 @@ mod netwayste_server_tests {
         }
         let resp_code: ResponseCode = server.list_players(player_id);
         match resp_code {
-            ResponseCode::PlayerList { players } => {
+            ResponseCode::PlayerList { players, .. } => {
                 assert_eq!(players.len(), 1);
                 assert_eq!(*players.first().unwrap(), player_name);
             }
@@ -1554,7 +3865,7 @@ mod netwayste_server_tests {
         {
             server.join_room(player_id, room_name);
         }
-        let player = server.get_player(player_id);
+        let player = server.get_player(player_id).unwrap();
         assert_eq!(player.has_chatted(), false);
     }
 
@@ -1591,7 +3902,7 @@ mod netwayste_server_tests {
             .unwrap();
 
         {
-            let player = server.get_player(player_id);
+            let player = server.get_player(player_id).unwrap();
             assert_eq!(player.get_confirmed_chat_seq_num(), Some(1));
         }
 
@@ -1611,7 +3922,7 @@ mod netwayste_server_tests {
             .unwrap();
 
         {
-            let player = server.get_player(player_id);
+            let player = server.get_player(player_id).unwrap();
             assert_eq!(player.get_confirmed_chat_seq_num(), Some(1));
         }
 
@@ -1631,7 +3942,7 @@ mod netwayste_server_tests {
             .unwrap();
 
         {
-            let player = server.get_player(player_id);
+            let player = server.get_player(player_id).unwrap();
             assert_eq!(player.get_confirmed_chat_seq_num(), Some(1));
         }
     }
@@ -1671,7 +3982,7 @@ mod netwayste_server_tests {
 
         {
             //let player = server.get_player_mut(player_id);
-            let player = server.get_player_mut(player_id);
+            let player = server.get_player_mut(player_id).unwrap();
             // player has not acknowledged any yet
             #[should_panic]
             assert_eq!(player.get_confirmed_chat_seq_num(), None);
@@ -1679,7 +3990,7 @@ mod netwayste_server_tests {
 
         // player acknowledged four of the six
         let acked_message_count = {
-            let player = server.get_player_mut(player_id);
+            let player = server.get_player_mut(player_id).unwrap();
             player.update_chat_seq_num(Some(4));
 
             player.get_confirmed_chat_seq_num().unwrap()
@@ -1691,7 +4002,7 @@ mod netwayste_server_tests {
 
         // player acknowledged all six
         let acked_message_count = {
-            let player = server.get_player_mut(player_id);
+            let player = server.get_player_mut(player_id).unwrap();
             player.update_chat_seq_num(Some(6));
 
             player.get_confirmed_chat_seq_num().unwrap()
@@ -1744,7 +4055,7 @@ mod netwayste_server_tests {
 
         let acked_message_count = {
             // Ack up until 0xFFFFFFFFFFFFFFFD
-            let player = server.get_player_mut(player_id);
+            let player = server.get_player_mut(player_id).unwrap();
             player.update_chat_seq_num(Some(start_seq_num + 4));
 
             player.get_confirmed_chat_seq_num().unwrap()
@@ -1778,7 +4089,7 @@ mod netwayste_server_tests {
         {
             // Room has no messages, None to send to player
             let room = server.get_room(player_id).unwrap();
-            let player = server.get_player(player_id);
+            let player = server.get_player(player_id).unwrap();
             let messages = server.collect_unacknowledged_messages(room, player);
             assert_eq!(messages, None);
         }
@@ -1795,20 +4106,20 @@ mod netwayste_server_tests {
         {
             // Room has a message, player has yet to ack it
             let room = server.get_room(player_id).unwrap();
-            let player = server.get_player(player_id);
+            let player = server.get_player(player_id).unwrap();
             let messages = server.collect_unacknowledged_messages(room, player);
             assert_eq!(messages.is_some(), true);
             assert_eq!(messages.unwrap().len(), 1);
         }
 
         {
-            let player = server.get_player_mut(player_id);
+            let player = server.get_player_mut(player_id).unwrap();
             player.update_chat_seq_num(Some(1));
         }
         {
             // Room has a message, player acked, None
             let room = server.get_room(player_id).unwrap();
-            let player = server.get_player(player_id);
+            let player = server.get_player(player_id).unwrap();
             let messages = server.collect_unacknowledged_messages(room, player);
             assert_eq!(messages, None);
         }
@@ -1851,13 +4162,13 @@ mod netwayste_server_tests {
         {
             // Sanity check to ensure player gets the chat message if left unacknowledged
             let room = server.get_room(player_id).unwrap();
-            let player = server.get_player(player_id);
+            let player = server.get_player(player_id).unwrap();
             let messages = server.collect_unacknowledged_messages(room, player);
             assert_eq!(messages.is_some(), true);
             assert_eq!(messages.unwrap().len(), 1);
         }
         {
-            let player = server.get_player_mut(player_id);
+            let player = server.get_player_mut(player_id).unwrap();
             player.update_chat_seq_num(Some(1));
         }
 
@@ -1868,32 +4179,55 @@ mod netwayste_server_tests {
         {
             // A room that has no messages, but has player(s) who have acknowledged past messages
             let room = server.get_room(player_id).unwrap();
-            let player = server.get_player(player_id);
+            let player = server.get_player(player_id).unwrap();
             let messages = server.collect_unacknowledged_messages(room, player);
             assert_eq!(messages, None);
         }
     }
 
     #[test]
-    fn handle_chat_message_player_not_in_game() {
+    fn handle_chat_message_in_lobby_goes_to_the_rest_of_the_lobby() {
         let mut server = ServerState::new();
-        let room_name = "some name";
-
-        server.create_new_room(None, room_name.to_owned());
-
-        let player_id = {
-            let p: &mut Player = server.add_new_player("some name".to_owned(), fake_socket_addr());
-
-            p.player_id
-        };
-
-        let response = server.handle_chat_message(player_id, "test msg".to_owned());
+        let mut add_player = |name: &str| server.add_new_player(name.to_owned(), fake_socket_addr()).player_id;
+        let talker_id = add_player("talker");
+        let listener_id = add_player("listener");
+        let muter_id = add_player("muter");
+        let in_room_id = add_player("in room");
+        server.join_room(in_room_id, "general");
+        assert_eq!(server.handle_mute_player(muter_id, "talker", true), ResponseCode::OK);
+
+        let response = server.handle_chat_message(talker_id, "anyone here?".to_owned());
+        assert_eq!(response, ResponseCode::OK);
         assert_eq!(
-            response,
-            ResponseCode::BadRequest {
-                error_msg: format!("Player {} has not joined a game.", player_id),
-            }
+            notifications(&server, listener_id),
+            vec![ResponseCode::LobbyChat {
+                player_name: "talker".to_owned(),
+                message:     "anyone here?".to_owned(),
+            }]
         );
+        for player_id in &[talker_id, muter_id, in_room_id] {
+            assert_eq!(notifications(&server, *player_id), vec![]);
+        }
+        assert!(server.rooms.values().all(|room| room.messages.is_empty()));
+
+        // the lobby has a tighter limit than rooms
+        for _ in 1..LOBBY_CHAT_MESSAGES_PER_WINDOW {
+            assert_eq!(
+                server.handle_chat_message(talker_id, "hello?".to_owned()),
+                ResponseCode::OK
+            );
+        }
+        match server.handle_chat_message(talker_id, "hello?".to_owned()) {
+            ResponseCode::ChatThrottled { .. } => {}
+            code => panic!("Unexpected response to flooding the lobby: {:?}", code),
+        }
+
+        // and it can be turned off
+        server.lobby_rate = 0;
+        match server.handle_chat_message(listener_id, "hi".to_owned()) {
+            ResponseCode::BadRequest { .. } => {}
+            code => panic!("Unexpected response with lobby chat off: {:?}", code),
+        }
     }
 
     #[test]
@@ -1942,6 +4276,245 @@ mod netwayste_server_tests {
         assert_eq!(room.latest_seq_num, 2);
     }
 
+    #[test]
+    fn handle_chat_message_length_is_counted_in_chars() {
+        let mut server = ServerState::new();
+        let room_name = "some name";
+
+        server.create_new_room(None, room_name.to_owned());
+
+        let player_id = {
+            let p: &mut Player = server.add_new_player("some player".to_owned(), fake_socket_addr());
+
+            p.player_id
+        };
+        server.join_room(player_id, room_name);
+
+        // Mixes ASCII, a combining accent, CJK, and emoji, so there are far more bytes than chars
+        let chunk = "gg e\u{301} 日本語 🎉";
+        let chunk_chars = chunk.chars().count();
+        let msg = chunk.repeat(MAX_CHAT_MESSAGE_CHARS / chunk_chars);
+        assert!(msg.len() > MAX_CHAT_MESSAGE_CHARS);
+        let response = server.handle_chat_message(player_id, msg);
+        assert_eq!(response, ResponseCode::OK);
+
+        let msg = "日".repeat(MAX_CHAT_MESSAGE_CHARS);
+        let response = server.handle_chat_message(player_id, msg);
+        assert_eq!(response, ResponseCode::OK);
+    }
+
+    #[test]
+    fn handle_chat_message_too_long() {
+        let mut server = ServerState::new();
+        let room_name = "some name";
+
+        server.create_new_room(None, room_name.to_owned());
+
+        let player_id = {
+            let p: &mut Player = server.add_new_player("some player".to_owned(), fake_socket_addr());
+
+            p.player_id
+        };
+        server.join_room(player_id, room_name);
+
+        let msg = "🎉".repeat(MAX_CHAT_MESSAGE_CHARS + 1);
+        let response = server.handle_chat_message(player_id, msg);
+        assert_eq!(
+            response,
+            ResponseCode::BadRequest {
+                error_msg: format!("chat message too long; max {} characters", MAX_CHAT_MESSAGE_CHARS),
+            }
+        );
+        let room: &Room = server.get_room(player_id).unwrap();
+        assert_eq!(room.messages.len(), 0);
+
+        // The limit is up to the server operator
+        server.chat_limit = 4;
+        let response = server.handle_chat_message(player_id, "hello".to_owned());
+        assert!(matches!(response, ResponseCode::BadRequest { .. }));
+        let response = server.handle_chat_message(player_id, "hell".to_owned());
+        assert_eq!(response, ResponseCode::OK);
+    }
+
+    #[test]
+    fn handle_chat_message_empty() {
+        let mut server = ServerState::new();
+        let room_name = "some name";
+
+        server.create_new_room(None, room_name.to_owned());
+
+        let player_id = server
+            .add_new_player("some player".to_owned(), fake_socket_addr())
+            .player_id;
+        server.join_room(player_id, room_name);
+
+        for msg in &["", "   ", "\n\t", "\u{7}\u{1b}"] {
+            let response = server.handle_chat_message(player_id, msg.to_string());
+            assert_eq!(
+                response,
+                ResponseCode::BadRequest {
+                    error_msg: "chat message is empty".to_owned(),
+                }
+            );
+        }
+        let room: &Room = server.get_room(player_id).unwrap();
+        assert_eq!(room.messages.len(), 0);
+    }
+
+    #[test]
+    fn handle_chat_message_strips_control_chars() {
+        let mut server = ServerState::new();
+        let room_name = "some name";
+
+        server.create_new_room(None, room_name.to_owned());
+
+        let player_id = server
+            .add_new_player("some player".to_owned(), fake_socket_addr())
+            .player_id;
+        server.join_room(player_id, room_name);
+
+        let response = server.handle_chat_message(player_id, "\u{1b}[31mred\u{1b}[0m\r\nnext\u{85}".to_owned());
+        assert_eq!(response, ResponseCode::OK);
+        let room: &Room = server.get_room(player_id).unwrap();
+        assert_eq!(room.get_newest_msg().unwrap().message, "[31mred[0mnext");
+    }
+
+    #[test]
+    fn handle_chat_message_flood_is_throttled() {
+        let mut server = ServerState::new();
+        let room_name = "some name";
+
+        server.create_new_room(None, room_name.to_owned());
+
+        let player_id = {
+            let p: &mut Player = server.add_new_player("some player".to_owned(), fake_socket_addr());
+
+            p.player_id
+        };
+        server.join_room(player_id, room_name);
+
+        for _ in 0..MAX_CHAT_MESSAGES_PER_WINDOW {
+            let response = server.handle_chat_message(player_id, "spam".to_owned());
+            assert_eq!(response, ResponseCode::OK);
+        }
+        for violation in 1..=CHAT_VIOLATIONS_BEFORE_MUTE {
+            let response = server.handle_chat_message(player_id, "spam".to_owned());
+            match response {
+                ResponseCode::ChatThrottled { muted_secs, .. } => {
+                    if violation < CHAT_VIOLATIONS_BEFORE_MUTE {
+                        assert_eq!(muted_secs, 0);
+                    } else {
+                        assert_eq!(muted_secs, CHAT_FIRST_MUTE.as_secs());
+                    }
+                }
+                _ => panic!("Unexpected response to violation {}: {:?}", violation, response),
+            }
+        }
+
+        let room: &Room = server.get_room(player_id).unwrap();
+        assert_eq!(room.messages.len(), MAX_CHAT_MESSAGES_PER_WINDOW);
+    }
+
+    #[test]
+    fn chat_throttle_mutes_escalate_and_expire() {
+        let start = Instant::now();
+        let mut throttle = ChatThrottle::new(start);
+        for _ in 1..CHAT_VIOLATIONS_BEFORE_MUTE {
+            assert_eq!(throttle.check(start, true), Err(ChatViolation::TooLong));
+            assert_eq!(throttle.muted_for(start), Duration::from_secs(0));
+        }
+        assert_eq!(throttle.check(start, true), Err(ChatViolation::TooLong));
+        assert_eq!(throttle.muted_for(start), CHAT_FIRST_MUTE);
+
+        // Messages sent while muted are refused, but don't make the mute any longer
+        let during = start + CHAT_FIRST_MUTE / 2;
+        assert_eq!(throttle.check(during, false), Err(ChatViolation::Muted));
+        assert_eq!(throttle.muted_for(during), CHAT_FIRST_MUTE / 2);
+
+        // The next violation after the mute doubles it
+        let after = start + CHAT_FIRST_MUTE;
+        assert_eq!(throttle.check(after, false), Ok(()));
+        assert_eq!(throttle.check(after, true), Err(ChatViolation::TooLong));
+        assert_eq!(throttle.muted_for(after), CHAT_FIRST_MUTE * 2);
+
+        // Once enough time passes without a violation, they're forgotten
+        let much_later = after + CHAT_FIRST_MUTE * 2 + CHAT_VIOLATION_EXPIRY;
+        assert_eq!(throttle.check(much_later, false), Ok(()));
+        assert_eq!(throttle.check(much_later, true), Err(ChatViolation::TooLong));
+        assert_eq!(throttle.muted_for(much_later), Duration::from_secs(0));
+    }
+
+    #[test]
+    fn chat_throttle_window_resets() {
+        let start = Instant::now();
+        let mut throttle = ChatThrottle::new(start);
+
+        for _ in 0..MAX_CHAT_MESSAGES_PER_WINDOW {
+            assert_eq!(throttle.check(start, false), Ok(()));
+        }
+        assert_eq!(throttle.check(start, false), Err(ChatViolation::TooFast));
+        assert_eq!(throttle.check(start + CHAT_WINDOW, false), Ok(()));
+    }
+
+    #[test]
+    fn muted_sender_messages_go_to_everyone_but_the_muter() {
+        let mut server = ServerState::new();
+        let room_name = "some name";
+        server.create_new_room(None, room_name.to_owned());
+
+        let mut player_ids = vec![];
+        for name in &["muter", "sender", "bystander"] {
+            let player_id = server.add_new_player(name.to_string(), fake_socket_addr()).player_id;
+            server.join_room(player_id, room_name);
+            player_ids.push(player_id);
+        }
+        let (muter_id, sender_id, bystander_id) = (player_ids[0], player_ids[1], player_ids[2]);
+
+        assert_eq!(server.handle_mute_player(muter_id, "sender", true), ResponseCode::OK);
+        match server.list_players(muter_id) {
+            ResponseCode::PlayerList { muted, .. } => assert_eq!(muted, vec!["sender".to_owned()]),
+            resp_code @ _ => panic!("Unexpected response code: {:?}", resp_code),
+        }
+        match server.list_players(bystander_id) {
+            ResponseCode::PlayerList { muted, .. } => assert!(muted.is_empty()),
+            resp_code @ _ => panic!("Unexpected response code: {:?}", resp_code),
+        }
+
+        assert_eq!(
+            server.handle_chat_message(sender_id, "hello".to_owned()),
+            ResponseCode::OK
+        );
+        let room = server.get_room(sender_id).unwrap();
+        let muter = server.get_player(muter_id).unwrap();
+        assert_eq!(server.collect_unacknowledged_messages(room, muter), None);
+        let bystander = server.get_player(bystander_id).unwrap();
+        let messages = server.collect_unacknowledged_messages(room, bystander).unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].player_name, "sender");
+
+        assert_eq!(server.handle_mute_player(muter_id, "sender", false), ResponseCode::OK);
+        let room = server.get_room(sender_id).unwrap();
+        let muter = server.get_player(muter_id).unwrap();
+        assert_eq!(server.collect_unacknowledged_messages(room, muter).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn mute_player_bad_names() {
+        let mut server = ServerState::new();
+        let player_id = server
+            .add_new_player("some player".to_owned(), fake_socket_addr())
+            .player_id;
+
+        match server.handle_mute_player(player_id, "nobody", true) {
+            ResponseCode::BadRequest { .. } => {}
+            resp_code @ _ => panic!("Unexpected response code: {:?}", resp_code),
+        }
+        match server.handle_mute_player(player_id, "some player", true) {
+            ResponseCode::BadRequest { .. } => {}
+            resp_code @ _ => panic!("Unexpected response code: {:?}", resp_code),
+        }
+    }
+
     #[test]
     fn create_new_room_good_case() {
         {
@@ -1955,6 +4528,13 @@ mod netwayste_server_tests {
             let mut server = ServerState::new();
             let room_name = "0123456789ABCDEF".to_owned();
 
+            assert_eq!(server.create_new_room(None, room_name), ResponseCode::OK);
+        }
+        // Multi-byte characters count as one each
+        {
+            let mut server = ServerState::new();
+            let room_name = "ルーム名はここまでの長さでいいです".chars().take(16).collect();
+
             assert_eq!(server.create_new_room(None, room_name), ResponseCode::OK);
         }
     }
@@ -1978,11 +4558,43 @@ mod netwayste_server_tests {
         let room_name = "some room".to_owned();
         assert_eq!(server.create_new_room(None, room_name.clone()), ResponseCode::OK);
         assert_eq!(
-            server.create_new_room(None, room_name),
-            ResponseCode::BadRequest {
-                error_msg: "room name already in use".to_owned(),
-            }
+            server.create_new_room(None, room_name.clone()),
+            ResponseCode::RoomNameTaken { room_name }
+        );
+    }
+
+    #[test]
+    fn create_new_room_name_is_validated() {
+        let mut server = ServerState::new();
+        let refused = |error_msg: &str| ResponseCode::BadRequest {
+            error_msg: error_msg.to_owned(),
+        };
+        assert_eq!(
+            server.create_new_room(None, "".to_owned()),
+            refused("room name is empty")
+        );
+        assert_eq!(
+            server.create_new_room(None, "   ".to_owned()),
+            refused("room name is empty")
+        );
+        assert_eq!(
+            server.create_new_room(None, "bell\u{7}".to_owned()),
+            refused("room name can only have printable characters")
         );
+        assert_eq!(
+            server.create_new_room(None, "some room ".to_owned()),
+            refused("room name cannot start or end with a space")
+        );
+        for reserved in &["general", "Lobby", "SERVER"] {
+            assert_eq!(
+                server.create_new_room(None, reserved.to_string()),
+                refused(&format!("room name {:?} is reserved", reserved))
+            );
+        }
+        assert_eq!(server.rooms.len(), 1);
+
+        // spaces inside the name are fine
+        assert_eq!(server.create_new_room(None, "general 2".to_owned()), ResponseCode::OK);
     }
 
     #[test]
@@ -2121,455 +4733,2202 @@ mod netwayste_server_tests {
     }
 
     #[test]
-    fn add_new_player_player_added_with_initial_sequence_number() {
+    fn close_room_by_owner_returns_members_to_lobby() {
         let mut server = ServerState::new();
-        let name = "some player".to_owned();
+        let owner_id = server.add_new_player("owner".to_owned(), fake_socket_addr()).player_id;
+        let member_id = server.add_new_player("member".to_owned(), fake_socket_addr()).player_id;
+        assert_eq!(
+            server.create_new_room(Some(owner_id), "some room".to_owned()),
+            ResponseCode::OK
+        );
+        server.join_room(owner_id, "some room");
+        server.join_room(member_id, "some room");
 
-        let p: &mut Player = server.add_new_player(name.clone(), fake_socket_addr());
-        assert_eq!(p.name, name);
+        assert_eq!(server.close_room(owner_id, "some room"), ResponseCode::LeaveRoom);
+        assert!(!server.is_player_in_game(owner_id));
+        assert!(!server.is_player_in_game(member_id));
+        assert_eq!(server.room_map.get("some room"), None);
+        assert_eq!(server.rooms.len(), 1); // only "general" is left
+
+        // the name can be used again
+        assert_eq!(
+            server.create_new_room(Some(member_id), "some room".to_owned()),
+            ResponseCode::OK
+        );
+        // and closing from the lobby is just OK
+        assert_eq!(server.close_room(member_id, "some room"), ResponseCode::OK);
     }
 
     #[test]
-    fn is_unique_player_name_yes_and_no_case() {
+    fn leave_room_tells_the_others_and_closes_the_room_when_empty() {
         let mut server = ServerState::new();
-        let name = "some player".to_owned();
-        assert_eq!(server.is_unique_player_name("some player"), true);
+        let (emitter, mut receiver) = EventEmitter::channel(64);
+        server.events = emitter;
+        let owner_id = server.add_new_player("owner".to_owned(), fake_socket_addr()).player_id;
+        let member_id = server.add_new_player("member".to_owned(), fake_socket_addr()).player_id;
+        server.create_new_room(Some(owner_id), "some room".to_owned());
+        server.join_room(owner_id, "some room");
+        server.join_room(member_id, "some room");
+        let room_id = server.room_map["some room"];
+
+        assert_eq!(server.leave_room(member_id), ResponseCode::LeaveRoom);
+        assert!(!server.is_player_in_game(member_id));
+        let room = &server.rooms[&room_id];
+        assert_eq!(room.player_ids, vec![owner_id]);
+        assert_eq!(room.messages.back().unwrap().message, "Player member has left.");
+
+        // the last one out closes it
+        assert_eq!(server.leave_room(owner_id), ResponseCode::LeaveRoom);
+        assert_eq!(server.room_map.get("some room"), None);
+        assert!(!server.rooms.contains_key(&room_id));
+        let events: Vec<ServerEvent> =
+            std::iter::from_fn(|| receiver.try_next().ok().flatten().map(|record| record.event)).collect();
+        assert_eq!(
+            events.last(),
+            Some(&ServerEvent::RoomClosed {
+                room: "some room".to_owned(),
+            })
+        );
 
-        {
-            server.add_new_player(name.clone(), fake_socket_addr());
-        }
-        assert_eq!(server.is_unique_player_name("some player"), false);
+        // the rooms the server made stay open when empty
+        server.join_room(owner_id, "general");
+        server.leave_room(owner_id);
+        assert!(server.room_map.contains_key("general"));
     }
 
     #[test]
-    fn expire_old_messages_in_all_rooms_room_is_empty() {
+    fn close_room_by_non_owner_is_unauthorized() {
         let mut server = ServerState::new();
-        let room_name = "some room";
-
-        server.create_new_room(None, room_name.to_owned().clone());
-        server.expire_old_messages_in_all_rooms(time::Instant::now());
+        let owner_id = server.add_new_player("owner".to_owned(), fake_socket_addr()).player_id;
+        let other_id = server.add_new_player("other".to_owned(), fake_socket_addr()).player_id;
+        server.create_new_room(Some(owner_id), "some room".to_owned());
+        server.join_room(other_id, "some room");
+
+        // nobody owns the rooms the server creates
+        for room_name in &["some room", "general"] {
+            match server.close_room(other_id, room_name) {
+                ResponseCode::Unauthorized { .. } => {}
+                code => panic!("Unexpected response to closing {:?}: {:?}", room_name, code),
+            }
+            assert!(server.room_map.contains_key(*room_name));
+        }
+        assert!(server.is_player_in_game(other_id));
 
-        for room in server.rooms.values() {
-            assert_eq!(room.messages.len(), 0);
+        match server.close_room(owner_id, "no such room") {
+            ResponseCode::BadRequest { .. } => {}
+            code => panic!("Unexpected response to closing a missing room: {:?}", code),
         }
     }
 
     #[test]
-    fn expire_old_messages_in_all_rooms_one_room_good_case() {
+    fn join_room_with_password() {
         let mut server = ServerState::new();
+        let owner_id = server.add_new_player("owner".to_owned(), fake_socket_addr()).player_id;
+        let other_id = server.add_new_player("other".to_owned(), fake_socket_addr()).player_id;
+        assert_eq!(
+            server.handle_new_room(owner_id, "some room".to_owned(), Some("sesame".to_owned()), None, None),
+            ResponseCode::OK
+        );
 
-        let player_id: PlayerID = {
-            let player: &mut Player = server.add_new_player("some player".to_owned(), fake_socket_addr());
-            player.player_id
-        };
-
-        server.join_room(player_id, "general");
+        for password in &[None, Some("open sesame".to_owned()), Some("".to_owned())] {
+            match server.handle_join_room(other_id, "some room", password.clone()) {
+                ResponseCode::Unauthorized { .. } => {}
+                code => panic!("Unexpected response to joining with {:?}: {:?}", password, code),
+            }
+            assert!(!server.is_player_in_game(other_id));
+        }
+        assert_eq!(
+            server.handle_join_room(other_id, "some room", Some("sesame".to_owned())),
+            ResponseCode::JoinedRoom {
+                room_name: "some room".to_owned(),
+            }
+        );
+        // the owner doesn't need it
+        assert_eq!(
+            server.handle_join_room(owner_id, "some room", None),
+            ResponseCode::JoinedRoom {
+                room_name: "some room".to_owned(),
+            }
+        );
+    }
 
-        server.handle_chat_message(player_id, "Conwayste is such a fun game".to_owned());
-        server.handle_chat_message(player_id, "There are not loot boxes".to_owned());
-        server.handle_chat_message(player_id, "It is free!".to_owned());
-        server.handle_chat_message(player_id, "What's not to love?".to_owned());
+    #[test]
+    fn room_password_is_hashed() {
+        let mut server = ServerState::new();
+        let player_id = server
+            .add_new_player("some player".to_owned(), fake_socket_addr())
+            .player_id;
+        server.handle_new_room(player_id, "room one".to_owned(), Some("sesame".to_owned()), None, None);
+        server.handle_new_room(player_id, "room two".to_owned(), Some("sesame".to_owned()), None, None);
+        // an empty password is no password
+        server.handle_new_room(player_id, "room three".to_owned(), Some("".to_owned()), None, None);
+
+        let password_of = |name: &str| server.rooms[&server.room_map[name]].password.as_ref();
+        let (one, two) = (password_of("room one").unwrap(), password_of("room two").unwrap());
+        assert!(one.matches("sesame") && two.matches("sesame"));
+        assert!(!one.matches("Sesame"));
+        assert_ne!(one.hash, b"sesame".to_vec());
+        assert_ne!(one.hash, two.hash); // salted
+        assert!(password_of("room three").is_none());
+
+        match server.list_rooms() {
+            ResponseCode::RoomList { rooms } => {
+                for room in rooms {
+                    let expected = room.room_name == "room one" || room.room_name == "room two";
+                    assert_eq!(room.has_password, expected, "{:?}", room);
+                }
+            }
+            code => panic!("Unexpected response code: {:?}", code),
+        }
+    }
 
-        let message_count = {
-            let room: &Room = server.get_room(player_id).unwrap();
-            room.messages.len()
-        };
-        assert_eq!(message_count, 4);
+    /// The responses queued for `player_id` that did not answer a request, oldest first.
+    fn notifications(server: &ServerState, player_id: PlayerID) -> Vec<ResponseCode> {
+        server.network_map[&player_id]
+            .tx_packets
+            .as_queue_type()
+            .iter()
+            .filter_map(|packet| match packet {
+                Packet::Response { code, .. } => Some(code.clone()),
+                _ => None,
+            })
+            .collect()
+    }
 
-        // Messages are not old enough to be expired
-        server.expire_old_messages_in_all_rooms(time::Instant::now());
+    /// Returns a server with a player "host" in a room of their own, "some room", and a player
+    /// "guest" in the lobby, and their IDs.
+    fn server_with_host_and_guest() -> (ServerState, PlayerID, PlayerID) {
+        let mut server = ServerState::new();
+        let host_id = server.add_new_player("host".to_owned(), fake_socket_addr()).player_id;
+        let guest_id = server.add_new_player("guest".to_owned(), fake_socket_addr()).player_id;
+        server.handle_new_room(host_id, "some room".to_owned(), Some("sesame".to_owned()), None, None);
+        server.join_room(host_id, "some room");
+        (server, host_id, guest_id)
+    }
 
-        for room in server.rooms.values() {
-            assert_eq!(room.messages.len(), 4);
+    /// Invites "guest" and returns the token they were sent.
+    fn invite_guest(server: &mut ServerState, host_id: PlayerID, guest_id: PlayerID, now: Instant) -> String {
+        assert_eq!(server.handle_invite_player(host_id, "guest", now), ResponseCode::OK);
+        match notifications(server, guest_id).pop() {
+            Some(ResponseCode::Invited {
+                inviter,
+                room_name,
+                token,
+            }) => {
+                assert_eq!((inviter.as_str(), room_name.as_str()), ("host", "some room"));
+                token
+            }
+            code => panic!("Unexpected notification: {:?}", code),
         }
     }
 
     #[test]
-    fn expire_old_messages_in_all_rooms_several_rooms_good_case() {
-        let mut server = ServerState::new();
-        let room_name = "some room";
-        let room_name2 = "some room2";
-
-        let room_id = server.new_room(room_name.to_owned());
-        let room_id2 = server.new_room(room_name2.to_owned());
-        let player_id: PlayerID = {
-            let player: &mut Player = server.add_new_player("some player".to_owned(), fake_socket_addr());
-            player.player_id
-        };
-        let player_id2: PlayerID = {
-            let player: &mut Player = server.add_new_player("some player".to_owned(), fake_socket_addr());
-            player.player_id
-        };
+    fn invite_accepted() {
+        let (mut server, host_id, guest_id) = server_with_host_and_guest();
+        server.join_room(guest_id, "general");
+        let now = Instant::now();
+        let token = invite_guest(&mut server, host_id, guest_id, now);
 
-        server.join_room(player_id, room_name);
-        server.join_room(player_id2, room_name2);
+        // no password needed, and the guest leaves the room they were in
+        assert_eq!(
+            server.handle_accept_invite(guest_id, &token, now),
+            ResponseCode::JoinedRoom {
+                room_name: "some room".to_owned(),
+            }
+        );
+        let room_id = server.room_map["some room"];
+        assert_eq!(server.rooms[&room_id].player_ids, vec![host_id, guest_id]);
+        let general_id = server.room_map["general"];
+        assert!(server.rooms[&general_id].player_ids.is_empty());
+        assert_eq!(
+            notifications(&server, host_id),
+            vec![ResponseCode::InviteAnswered {
+                name:   "guest".to_owned(),
+                answer: InviteAnswer::Accepted,
+            }]
+        );
 
-        server.handle_chat_message(player_id, "Conwayste is such a fun game".to_owned());
-        server.handle_chat_message(player_id, "There are not loot boxes".to_owned());
-        server.handle_chat_message(player_id2, "It is free!".to_owned());
-        server.handle_chat_message(player_id2, "What's not to love?".to_owned());
+        // an invitation can only be used once
+        assert_eq!(
+            server.handle_accept_invite(guest_id, &token, now),
+            ResponseCode::InviteFailed {
+                reason: InviteFailure::UnknownInvite,
+            }
+        );
+    }
 
-        let message_count = {
-            let room: &Room = server.get_room(player_id).unwrap();
-            room.messages.len()
-        };
-        assert_eq!(message_count, 2);
-        let message_count2 = {
-            let room: &Room = server.get_room(player_id2).unwrap();
-            room.messages.len()
+    #[test]
+    fn invite_expires_or_is_declined() {
+        let (mut server, host_id, guest_id) = server_with_host_and_guest();
+        let now = Instant::now();
+        let expired = || ResponseCode::InviteAnswered {
+            name:   "guest".to_owned(),
+            answer: InviteAnswer::Expired,
         };
-        assert_eq!(message_count2, 2);
 
-        // Messages are not old enough to be expired
-        server.expire_old_messages_in_all_rooms(time::Instant::now());
+        // accepted too late
+        let token = invite_guest(&mut server, host_id, guest_id, now);
+        assert_eq!(
+            server.handle_accept_invite(guest_id, &token, now + INVITE_LIFETIME),
+            ResponseCode::InviteFailed {
+                reason: InviteFailure::Expired,
+            }
+        );
+        assert_eq!(notifications(&server, host_id), vec![expired()]);
+
+        // never answered
+        let token = invite_guest(&mut server, host_id, guest_id, now);
+        server.expire_invites(now + INVITE_LIFETIME - Duration::from_secs(1));
+        assert_eq!(server.invites.len(), 1);
+        server.expire_invites(now + INVITE_LIFETIME);
+        assert!(server.invites.is_empty());
+        assert_eq!(notifications(&server, host_id), vec![expired(), expired()]);
+        assert_eq!(
+            server.handle_accept_invite(guest_id, &token, now),
+            ResponseCode::InviteFailed {
+                reason: InviteFailure::UnknownInvite,
+            }
+        );
 
-        assert_eq!(server.rooms[&room_id].messages.len(), 2);
-        assert_eq!(server.rooms[&room_id2].messages.len(), 2);
+        // declined
+        let token = invite_guest(&mut server, host_id, guest_id, now);
+        assert_eq!(server.handle_decline_invite(guest_id, &token), ResponseCode::OK);
+        assert_eq!(
+            notifications(&server, host_id).pop(),
+            Some(ResponseCode::InviteAnswered {
+                name:   "guest".to_owned(),
+                answer: InviteAnswer::Declined,
+            })
+        );
+        assert!(!server.is_player_in_game(guest_id));
     }
 
     #[test]
-    fn expire_old_messages_in_all_rooms_one_room_old_messages_are_wiped() {
-        let mut server = ServerState::new();
-        let room_name = "some room";
-
-        server.create_new_room(None, room_name.to_owned().clone());
-        let player_id: PlayerID = {
-            let player: &mut Player = server.add_new_player("some player".to_owned(), fake_socket_addr());
-            player.player_id
-        };
+    fn invite_failures() {
+        let failed = |reason| ResponseCode::InviteFailed { reason };
+        let now = Instant::now();
 
-        server.join_room(player_id, room_name);
+        let (mut server, host_id, guest_id) = server_with_host_and_guest();
+        assert_eq!(
+            server.handle_invite_player(guest_id, "host", now),
+            failed(InviteFailure::InviterNotInRoom)
+        );
+        assert_eq!(
+            server.handle_invite_player(host_id, "nobody", now),
+            failed(InviteFailure::NoSuchPlayer)
+        );
+        assert_eq!(
+            server.handle_invite_player(host_id, "host", now),
+            failed(InviteFailure::AlreadyInRoom)
+        );
 
-        server.handle_chat_message(player_id, "Conwayste is such a fun game".to_owned());
-        server.handle_chat_message(player_id, "There are not loot boxes".to_owned());
-        server.handle_chat_message(player_id, "It is free!".to_owned());
-        server.handle_chat_message(player_id, "What's not to love?".to_owned());
+        // only the invited player can use the token
+        let token = invite_guest(&mut server, host_id, guest_id, now);
+        let other_id = server.add_new_player("other".to_owned(), fake_socket_addr()).player_id;
+        assert_eq!(
+            server.handle_accept_invite(other_id, &token, now),
+            failed(InviteFailure::UnknownInvite)
+        );
 
-        let current_timestamp = Instant::now();
-        let travel_to_the_past = current_timestamp.checked_sub(Duration::from_secs((MAX_AGE_CHAT_MESSAGES + 1) as u64));
-        if travel_to_the_past.is_none() {
-            warn!("skipping rest of test; cannot travel to the past :(");
-            return;
+        // the room fills up first
+        for i in 1..MAX_ROOM_PLAYERS {
+            let player_id = server
+                .add_new_player(format!("player {}", i), fake_socket_addr())
+                .player_id;
+            server.handle_join_room(player_id, "some room", Some("sesame".to_owned()));
         }
-        let travel_to_the_past = travel_to_the_past.unwrap();
-        for ref mut room in server.rooms.values_mut() {
-            println!("Room: {:?}", room.name);
-            for m in room.messages.iter_mut() {
-                println!(
-                    "{:?}, {:?},       {:?}",
-                    m.timestamp,
-                    travel_to_the_past,
-                    m.timestamp - travel_to_the_past
-                );
-                m.timestamp = travel_to_the_past;
-            }
+        assert_eq!(
+            server.handle_accept_invite(guest_id, &token, now),
+            failed(InviteFailure::RoomFull)
+        );
+
+        // the inviter leaves first, while someone else keeps the room open
+        let (mut server, host_id, guest_id) = server_with_host_and_guest();
+        let token = invite_guest(&mut server, host_id, guest_id, now);
+        let other_id = server.add_new_player("other".to_owned(), fake_socket_addr()).player_id;
+        server.handle_join_room(other_id, "some room", Some("sesame".to_owned()));
+        server.leave_room(host_id);
+        assert_eq!(
+            server.handle_accept_invite(guest_id, &token, now),
+            failed(InviteFailure::InviterLeft)
+        );
+
+        // the room is closed first
+        let (mut server, host_id, guest_id) = server_with_host_and_guest();
+        let token = invite_guest(&mut server, host_id, guest_id, now);
+        server.close_room(host_id, "some room");
+        assert_eq!(
+            server.handle_accept_invite(guest_id, &token, now),
+            failed(InviteFailure::RoomClosed)
+        );
+        assert!(!server.is_player_in_game(guest_id));
+    }
+
+    /// The team of each player in `player_id`'s room by name, as a player list shows them.
+    fn teams_by_name(server: &ServerState, player_id: PlayerID) -> HashMap<String, Option<u8>> {
+        match server.list_players(player_id) {
+            ResponseCode::PlayerList { players, teams, .. } => players.into_iter().zip(teams).collect(),
+            code => panic!("Unexpected response code: {:?}", code),
         }
+    }
 
-        // Messages are not old enough to be expired
-        server.expire_old_messages_in_all_rooms(time::Instant::now());
+    #[test]
+    fn set_team_explicit_assignment() {
+        let (mut server, host_id, guest_id) = server_with_host_and_guest();
+        server.join_room(guest_id, "some room");
+        assert_eq!(server.handle_set_team(host_id, 1), ResponseCode::OK);
+        assert_eq!(server.handle_set_team(guest_id, 0), ResponseCode::OK);
+        let teams = teams_by_name(&server, host_id);
+        assert_eq!((teams["host"], teams["guest"]), (Some(1), Some(0)));
+
+        // leaving the room clears the team
+        server.leave_room(guest_id);
+        server.join_room(guest_id, "some room");
+        assert_eq!(teams_by_name(&server, host_id)["guest"], None);
+
+        // Picked teams are kept when the game starts, even if that leaves a team empty, unless the
+        // server requires full teams
+        assert_eq!(server.handle_set_team(guest_id, 1), ResponseCode::OK);
+        let room_id = server.room_map["some room"];
+        server.full_teams = true;
+        assert!(server.start_game(room_id).is_err());
+        assert!(!server.rooms[&room_id].game_running);
+        server.full_teams = false;
+        server.start_game(room_id).unwrap();
+        let teams = teams_by_name(&server, host_id);
+        assert_eq!((teams["host"], teams["guest"]), (Some(1), Some(1)));
+    }
 
-        for room in server.rooms.values() {
-            assert_eq!(room.messages.len(), 0);
+    #[test]
+    fn set_team_out_of_range_or_too_late() {
+        let failed = |reason| ResponseCode::SetTeamFailed { reason };
+        let (mut server, host_id, guest_id) = server_with_host_and_guest();
+        assert_eq!(server.handle_set_team(guest_id, 0), failed(TeamFailure::NotInRoom));
+        assert_eq!(
+            server.handle_set_team(host_id, DEFAULT_TEAM_COUNT),
+            failed(TeamFailure::NoSuchTeam {
+                team_count: DEFAULT_TEAM_COUNT,
+            })
+        );
+        assert_eq!(teams_by_name(&server, host_id)["host"], None);
+
+        let room_id = server.room_map["some room"];
+        server.start_game(room_id).unwrap();
+        assert_eq!(server.handle_set_team(host_id, 0), failed(TeamFailure::GameRunning));
+    }
+
+    #[test]
+    fn new_room_team_count() {
+        let mut server = ServerState::new();
+        let player_id = server.add_new_player("host".to_owned(), fake_socket_addr()).player_id;
+        for &team_count in &[0, MAX_ROOM_PLAYERS as u8 + 1] {
+            match server.handle_new_room(player_id, "some room".to_owned(), None, Some(team_count), None) {
+                ResponseCode::BadRequest { .. } => {}
+                code => panic!("Unexpected response code: {:?}", code),
+            }
         }
+        assert!(!server.room_map.contains_key("some room"));
+
+        assert_eq!(
+            server.handle_new_room(player_id, "some room".to_owned(), None, Some(3), None),
+            ResponseCode::OK
+        );
+        server.join_room(player_id, "some room");
+        assert_eq!(server.handle_set_team(player_id, 2), ResponseCode::OK);
+        assert_eq!(
+            server.handle_set_team(player_id, 3),
+            ResponseCode::SetTeamFailed {
+                reason: TeamFailure::NoSuchTeam { team_count: 3 },
+            }
+        );
     }
 
     #[test]
-    fn expire_old_messages_in_all_rooms_several_rooms_old_messages_are_wiped() {
+    fn new_room_max_players() {
         let mut server = ServerState::new();
-        let room_name = "some room";
-        let room_name2 = "some room 2";
+        let host_id = server.add_new_player("host".to_owned(), fake_socket_addr()).player_id;
+        for &(team_count, max_players) in &[(None, 0), (None, MAX_ROOM_PLAYERS as u8 + 1), (Some(4), 3)] {
+            match server.handle_new_room(host_id, "some room".to_owned(), None, team_count, Some(max_players)) {
+                ResponseCode::BadRequest { .. } => {}
+                code => panic!("Unexpected response code: {:?}", code),
+            }
+        }
+        assert!(!server.room_map.contains_key("some room"));
 
-        server.create_new_room(None, room_name.to_owned().clone());
-        let player_id: PlayerID = {
-            let player: &mut Player = server.add_new_player("some player".to_owned(), fake_socket_addr());
-            player.player_id
-        };
-        server.create_new_room(None, room_name2.to_owned().clone());
-        let player_id2: PlayerID = {
-            let player: &mut Player = server.add_new_player("some player".to_owned(), fake_socket_addr());
-            player.player_id
-        };
+        assert_eq!(
+            server.handle_new_room(host_id, "some room".to_owned(), None, None, Some(2)),
+            ResponseCode::OK
+        );
+        match server.list_rooms() {
+            ResponseCode::RoomList { rooms } => {
+                let room = rooms.iter().find(|room| room.room_name == "some room").unwrap();
+                assert_eq!((room.player_count, room.max_players), (0, 2));
+            }
+            code => panic!("Unexpected response code: {:?}", code),
+        }
 
-        server.join_room(player_id, room_name);
-        server.join_room(player_id2, room_name);
+        let guest_id = server.add_new_player("guest".to_owned(), fake_socket_addr()).player_id;
+        let late_id = server.add_new_player("late".to_owned(), fake_socket_addr()).player_id;
+        server.join_room(host_id, "some room");
+        server.join_room(guest_id, "some room");
+        assert_eq!(
+            server.join_room(late_id, "some room"),
+            ResponseCode::RoomFull {
+                room_name:   "some room".to_owned(),
+                max_players: 2,
+            }
+        );
+        assert!(!server.is_player_in_game(late_id));
 
-        server.handle_chat_message(player_id, "Conwayste is such a fun game".to_owned());
-        server.handle_chat_message(player_id, "There are not loot boxes".to_owned());
-        server.handle_chat_message(player_id2, "It is free!".to_owned());
-        server.handle_chat_message(player_id2, "What's not to love?".to_owned());
+        // there is room again once someone leaves
+        server.leave_room(guest_id);
+        match server.join_room(late_id, "some room") {
+            ResponseCode::JoinedRoom { .. } => {}
+            code => panic!("Unexpected response code: {:?}", code),
+        }
+    }
 
-        let current_timestamp = Instant::now();
-        let travel_to_the_past = current_timestamp.checked_sub(Duration::from_secs((MAX_AGE_CHAT_MESSAGES + 1) as u64));
-        if travel_to_the_past.is_none() {
-            warn!("skipping rest of test; cannot travel to the past :(");
-            return;
+    #[test]
+    fn start_game_waits_for_everyone_to_be_ready_then_counts_down() {
+        let (mut server, host_id, guest_id) = server_with_host_and_guest();
+        server.join_room(guest_id, "some room");
+        let room_id = server.room_map["some room"];
+        let now = Instant::now();
+
+        match server.handle_start_game(guest_id, now) {
+            ResponseCode::Unauthorized { .. } => {}
+            code => panic!("Unexpected response code: {:?}", code),
         }
-        let travel_to_the_past = travel_to_the_past.unwrap();
-        for ref mut room in server.rooms.values_mut() {
-            println!("Room: {:?}", room.name);
-            for m in room.messages.iter_mut() {
-                println!(
-                    "{:?}, {:?},       {:?}",
-                    m.timestamp,
-                    travel_to_the_past,
-                    m.timestamp - travel_to_the_past
-                );
-                m.timestamp = travel_to_the_past;
-            }
+        match server.handle_start_game(host_id, now) {
+            ResponseCode::BadRequest { error_msg } => assert_eq!(error_msg, "waiting for guest to be ready"),
+            code => panic!("Unexpected response code: {:?}", code),
         }
+        assert!(server.rooms[&room_id].countdown.is_none());
 
-        // Messages are not old enough to be expired
-        server.expire_old_messages_in_all_rooms(time::Instant::now());
+        assert_eq!(server.handle_set_ready(guest_id, true), ResponseCode::OK);
+        assert_eq!(server.handle_start_game(host_id, now), ResponseCode::OK);
+        let starting = |seconds_left| ResponseCode::GameStarting {
+            room_name: "some room".to_owned(),
+            seconds_left,
+        };
+        assert_eq!(notifications(&server, guest_id), vec![starting(START_COUNTDOWN_SECS)]);
 
-        for room in server.rooms.values() {
-            assert_eq!(room.messages.len(), 0);
+        // one announcement per second, then the game starts
+        for &millis in &[500, 1000, 1500, 2000, 2500] {
+            server.run_countdowns(now + Duration::from_millis(millis));
+        }
+        assert!(!server.rooms[&room_id].game_running);
+        assert_eq!(
+            notifications(&server, guest_id),
+            vec![
+                starting(START_COUNTDOWN_SECS),
+                starting(START_COUNTDOWN_SECS - 1),
+                starting(START_COUNTDOWN_SECS - 2),
+            ]
+        );
+        server.run_countdowns(now + Duration::from_secs(START_COUNTDOWN_SECS));
+        let room = &server.rooms[&room_id];
+        assert!(room.game_running);
+        assert_eq!(room.game_players, vec![host_id, guest_id]);
+        assert!(room.countdown.is_none() && room.ready.is_empty());
+
+        match server.handle_set_ready(guest_id, true) {
+            ResponseCode::BadRequest { .. } => {}
+            code => panic!("Unexpected response code: {:?}", code),
         }
     }
 
     #[test]
-    fn handle_new_connection_good_case() {
-        let mut server = ServerState::new();
-        let player_name = "some name".to_owned();
-        let pkt = server.handle_new_connection(player_name, fake_socket_addr());
-        match pkt {
-            Packet::Response {
-                sequence: _,
-                request_ack: _,
-                code,
-            } => match code {
-                ResponseCode::LoggedIn {
-                    cookie: _,
-                    server_version: _,
-                } => {}
-                _ => panic!("Unexpected ResponseCode: {:?}", code),
-            },
-            _ => panic!("Unexpected Packet Type: {:?}", pkt),
+    fn countdown_stops_when_someone_joins_leaves_or_is_not_ready() {
+        let cancelled = ResponseCode::GameStartCancelled {
+            room_name: "some room".to_owned(),
+        };
+        let now = Instant::now();
+        let start_countdown = |server: &mut ServerState, host_id: PlayerID, guest_id: PlayerID| {
+            assert_eq!(server.handle_set_ready(guest_id, true), ResponseCode::OK);
+            assert_eq!(server.handle_start_game(host_id, now), ResponseCode::OK);
+        };
+
+        let (mut server, host_id, guest_id) = server_with_host_and_guest();
+        server.join_room(guest_id, "some room");
+        start_countdown(&mut server, host_id, guest_id);
+        server.handle_set_ready(guest_id, false);
+        assert_eq!(notifications(&server, host_id).pop(), Some(cancelled.clone()));
+
+        start_countdown(&mut server, host_id, guest_id);
+        let late_id = server.add_new_player("late".to_owned(), fake_socket_addr()).player_id;
+        server.handle_join_room(late_id, "some room", Some("sesame".to_owned()));
+        assert_eq!(notifications(&server, guest_id).pop(), Some(cancelled.clone()));
+
+        server.handle_set_ready(late_id, true);
+        start_countdown(&mut server, host_id, guest_id);
+        server.leave_room(late_id);
+        assert_eq!(notifications(&server, guest_id).pop(), Some(cancelled.clone()));
+
+        // nothing starts once the countdown has stopped
+        let room_id = server.room_map["some room"];
+        server.run_countdowns(now + Duration::from_secs(START_COUNTDOWN_SECS));
+        assert!(!server.rooms[&room_id].game_running);
+    }
+
+    #[test]
+    fn owner_disconnecting_hands_the_room_over() {
+        let (mut server, host_id, guest_id) = server_with_host_and_guest();
+        server.join_room(guest_id, "some room");
+        let other_id = server.add_new_player("other".to_owned(), fake_socket_addr()).player_id;
+        server.handle_join_room(other_id, "some room", Some("sesame".to_owned()));
+        // the host also has a room nobody is in
+        server.create_new_room(Some(host_id), "empty room".to_owned());
+
+        server.handle_disconnect(host_id).unwrap();
+        assert!(!server.room_map.contains_key("empty room"));
+        let room_id = server.room_map["some room"];
+        assert_eq!(server.rooms[&room_id].owner, Some(guest_id)); // in the room the longest
+        let owner_changed = ResponseCode::OwnerChanged {
+            room_name: "some room".to_owned(),
+            owner:     "guest".to_owned(),
+        };
+        for &player_id in &[guest_id, other_id] {
+            assert_eq!(notifications(&server, player_id).pop(), Some(owner_changed.clone()));
+        }
+
+        // and the new owner can start the game
+        server.handle_set_ready(other_id, true);
+        assert_eq!(server.handle_start_game(guest_id, Instant::now()), ResponseCode::OK);
+        match server.handle_start_game(other_id, Instant::now()) {
+            ResponseCode::Unauthorized { .. } => {}
+            code => panic!("Unexpected response code: {:?}", code),
         }
     }
 
     #[test]
-    fn handle_new_connection_player_name_taken() {
+    fn start_game_balances_unassigned_players() {
         let mut server = ServerState::new();
-        let player_name = "some name".to_owned();
-
-        let pkt = server.handle_new_connection(player_name.clone(), fake_socket_addr());
-        match pkt {
-            Packet::Response {
-                sequence: _,
-                request_ack: _,
-                code,
-            } => match code {
-                ResponseCode::LoggedIn {
-                    cookie: _,
-                    server_version,
-                } => assert_eq!(server_version, VERSION.to_owned()),
-                _ => panic!("Unexpected ResponseCode: {:?}", code),
-            },
-            _ => panic!("Unexpected Packet Type: {:?}", pkt),
+        let (room_id, player_ids) = start_game_in_new_room(&mut server, &["one", "two", "three"]);
+        let room = &server.rooms[&room_id];
+        let teams: Vec<u8> = player_ids.iter().map(|player_id| room.teams[player_id]).collect();
+        assert_eq!(teams, vec![0, 1, 0]);
+
+        // everyone in the game is told who is on which team
+        server.emit_room_events(Instant::now());
+        let expected = ResponseCode::GameStarted {
+            room_name: "some room".to_owned(),
+            teams:     vec![("one".to_owned(), 0), ("two".to_owned(), 1), ("three".to_owned(), 0)],
+        };
+        for player_id in &player_ids {
+            assert_eq!(notifications(&server, *player_id), vec![expected.clone()]);
         }
+    }
 
-        let pkt = server.handle_new_connection(player_name, fake_socket_addr());
-        match pkt {
-            Packet::Response {
-                sequence: _,
-                request_ack: _,
-                code,
-            } => match code {
-                ResponseCode::Unauthorized { error_msg } => {
-                    assert_eq!(error_msg, "not a unique name".to_owned());
-                }
-                _ => panic!("Unexpected ResponseCode: {:?}", code),
-            },
-            _ => panic!("Unexpected Packet Type: {:?}", pkt),
+    /// Starts a game in a new room on `server` between players with these names, in order, and
+    /// returns the room's ID and the players' IDs.
+    fn start_game_in_new_room(server: &mut ServerState, names: &[&str]) -> (RoomID, Vec<PlayerID>) {
+        let room_name = "some room";
+        server.create_new_room(None, room_name.to_owned());
+        let mut player_ids = vec![];
+        for name in names {
+            let player_id = server.add_new_player(name.to_string(), fake_socket_addr()).player_id;
+            server.join_room(player_id, room_name);
+            player_ids.push(player_id);
         }
+        let room_id = server.room_map[room_name];
+        server.start_game(room_id).unwrap();
+        (room_id, player_ids)
     }
 
-    fn a_request_action_strat() -> BoxedStrategy<RequestAction> {
-        prop_oneof![
-            //Just(RequestAction::Disconnect), // not yet implemented
-            //Just(RequestAction::KeepAlive),  // same
-            Just(RequestAction::LeaveRoom),
-            Just(RequestAction::ListPlayers),
-            Just(RequestAction::ListRooms),
-            Just(RequestAction::None),
-        ]
-        .boxed()
+    /// Returns a server with two players in a room whose game has started, the room's ID, and the
+    /// players' IDs.
+    fn server_with_running_game() -> (ServerState, RoomID, PlayerID, PlayerID) {
+        let mut server = ServerState::new();
+        let (room_id, player_ids) = start_game_in_new_room(&mut server, &["player one", "player two"]);
+        (server, room_id, player_ids[0], player_ids[1])
     }
 
-    fn a_request_action_complex_strat() -> BoxedStrategy<RequestAction> {
-        prop_oneof![
-            ("([A-Z]{1,4} [0-9]{1,2}){3}").prop_map(|a| RequestAction::ChatMessage { message: a }),
-            ("([A-Z]{1,4} [0-9]{1,2}){3}").prop_map(|a| RequestAction::NewRoom { room_name: a }),
-            ("([A-Z]{1,4} [0-9]{1,2}){3}").prop_map(|a| RequestAction::JoinRoom { room_name: a }),
-            ("([A-Z]{1,4} [0-9]{1,2}){3}", "[0-9].[0-9].[0-9]").prop_map(|(a, b)| {
-                RequestAction::Connect {
-                    name:           a,
-                    client_version: b,
+    fn cell_state(server: &mut ServerState, player_id: PlayerID, col: usize, row: usize) -> CellState {
+        let room = server.get_room_mut(player_id).unwrap();
+        room.universe.as_mut().unwrap().get_cell_state(col, row, None)
+    }
+
+    #[test]
+    fn place_cells_good_case() {
+        let (mut server, _, player_one, player_two) = server_with_running_game();
+        let cells = vec![(3, 4, CellState::Alive(Some(0))), (4, 4, CellState::Alive(Some(0)))];
+        assert_eq!(
+            server
+                .process_request_action(player_one, RequestAction::PlaceCells { cells })
+                .unwrap(),
+            ResponseCode::CellsPlaced { generation: 1 }
+        );
+        assert_eq!(cell_state(&mut server, player_one, 3, 4), CellState::Alive(None));
+        assert_eq!(cell_state(&mut server, player_one, 4, 4), CellState::Alive(None));
+
+        // the second player writes to the right half of the universe
+        let col = GAME_UNIVERSE_WIDTH - 1;
+        let cells = vec![(col, 0, CellState::Alive(Some(1)))];
+        assert_eq!(
+            server.handle_place_cells(player_two, cells),
+            ResponseCode::CellsPlaced { generation: 1 }
+        );
+        assert_eq!(cell_state(&mut server, player_two, col, 0), CellState::Alive(None));
+
+        let cells = vec![(3, 4, CellState::Dead)];
+        assert_eq!(
+            server.handle_place_cells(player_one, cells),
+            ResponseCode::CellsPlaced { generation: 1 }
+        );
+        assert_eq!(cell_state(&mut server, player_one, 3, 4), CellState::Dead);
+    }
+
+    #[test]
+    fn place_cells_player_not_in_room() {
+        let mut server = ServerState::new();
+        let player_id = server
+            .add_new_player("some player".to_owned(), fake_socket_addr())
+            .player_id;
+        assert_eq!(
+            server.handle_place_cells(player_id, vec![(0, 0, CellState::Dead)]),
+            ResponseCode::BadRequest {
+                error_msg: format!("Player {} has not joined a game.", player_id),
+            }
+        );
+    }
+
+    #[test]
+    fn place_cells_game_not_running() {
+        let mut server = ServerState::new();
+        let room_name = "some room";
+        server.create_new_room(None, room_name.to_owned());
+        let player_id = server
+            .add_new_player("some player".to_owned(), fake_socket_addr())
+            .player_id;
+        server.join_room(player_id, room_name);
+        assert_eq!(
+            server.handle_place_cells(player_id, vec![(0, 0, CellState::Alive(Some(0)))]),
+            ResponseCode::BadRequest {
+                error_msg: "game is not running".to_owned(),
+            }
+        );
+    }
+
+    #[test]
+    fn place_cells_outside_writable_region_sets_nothing() {
+        let (mut server, _, player_one, _) = server_with_running_game();
+        let col = GAME_UNIVERSE_WIDTH / 2; // first column of the second player's region
+        let cells = vec![(0, 0, CellState::Alive(Some(0))), (col, 0, CellState::Alive(Some(0)))];
+        assert_eq!(
+            server.handle_place_cells(player_one, cells),
+            ResponseCode::BadRequest {
+                error_msg: format!("cell ({}, 0) is not writable", col),
+            }
+        );
+        assert_eq!(cell_state(&mut server, player_one, 0, 0), CellState::Dead);
+    }
+
+    #[test]
+    fn place_cells_as_another_player() {
+        let (mut server, _, player_one, _) = server_with_running_game();
+        for &state in [CellState::Alive(Some(1)), CellState::Alive(None), CellState::Wall].iter() {
+            assert_eq!(
+                server.handle_place_cells(player_one, vec![(0, 0, state)]),
+                ResponseCode::BadRequest {
+                    error_msg: format!("cannot set cell (0, 0) to {:?}", state),
                 }
-            })
-        ]
-        .boxed()
+            );
+        }
+        assert_eq!(cell_state(&mut server, player_one, 0, 0), CellState::Dead);
     }
 
-    // These tests are checking that we do not panic on each RequestAction
-    proptest! {
-        #[test]
-        fn process_request_action_simple(ref request in a_request_action_strat()) {
-            let mut server = ServerState::new();
-            server.create_new_room(None, "some room".to_owned().clone());
-            let player_id: PlayerID = {
-                let player: &mut Player = server.add_new_player("some player".to_owned(), fake_socket_addr());
-                player.player_id
-            };
-            server.process_request_action(player_id, request.to_owned());
+    #[test]
+    fn place_cells_out_of_bounds() {
+        let (mut server, _, player_one, _) = server_with_running_game();
+        let cells = vec![
+            (0, 0, CellState::Alive(Some(0))),
+            (0, GAME_UNIVERSE_HEIGHT, CellState::Alive(Some(0))),
+        ];
+        assert_eq!(
+            server.handle_place_cells(player_one, cells),
+            ResponseCode::BadRequest {
+                error_msg: format!("cell (0, {}) is out of bounds", GAME_UNIVERSE_HEIGHT),
+            }
+        );
+        assert_eq!(cell_state(&mut server, player_one, 0, 0), CellState::Dead);
+    }
+
+    #[test]
+    fn game_ends_at_generation_limit_and_room_can_rematch() {
+        let (mut server, room_id, player_one, player_two) = server_with_running_game();
+        let room_name = "some room";
+        server.rooms.get_mut(&room_id).unwrap().gen_limit = 5;
+
+        // a block, which stays put; the second player places nothing, then disconnects
+        let cells = vec![(3, 3), (4, 3), (3, 4), (4, 4)]
+            .into_iter()
+            .map(|(col, row)| (col, row, CellState::Alive(Some(0))))
+            .collect();
+        assert_eq!(
+            server.handle_place_cells(player_one, cells),
+            ResponseCode::CellsPlaced { generation: 1 }
+        );
+        server.handle_disconnect(player_two).unwrap();
+        let spectator = server
+            .add_new_player("spectator".to_owned(), fake_socket_addr())
+            .player_id;
+        server.join_room(spectator, room_name);
+
+        for _ in 0..3 {
+            server.advance_games();
+            assert!(server.rooms[&room_id].game_running);
+        }
+        server.get_player_mut(player_one).unwrap().update_full_gen(Some(4));
+        server.advance_games();
+
+        let expected = ResponseCode::GameEnded {
+            room_name:   room_name.to_owned(),
+            generations: 5,
+            results:     vec![
+                PlayerResult {
+                    name:         "player one".to_owned(),
+                    team:         0,
+                    generations:  5,
+                    cells_at_end: 4,
+                    disconnected: false,
+                },
+                PlayerResult {
+                    name:         "player two".to_owned(),
+                    team:         1,
+                    generations:  0,
+                    cells_at_end: 0,
+                    disconnected: true,
+                },
+            ],
+        };
+        for &player_id in &[player_one, spectator] {
+            let ended: Vec<ResponseCode> = notifications(&server, player_id)
+                .into_iter()
+                .filter(|code| matches!(code, ResponseCode::GameEnded { .. }))
+                .collect();
+            assert_eq!(ended, vec![expected.clone()]);
         }
 
-        #[test]
-        fn process_request_action_complex(ref request in a_request_action_complex_strat()) {
-            let mut server = ServerState::new();
-            server.create_new_room(None, "some room".to_owned().clone());
-            let player_id: PlayerID = {
-                let player: &mut Player = server.add_new_player("some player".to_owned(), fake_socket_addr());
-                player.player_id
-            };
-            server.process_request_action(player_id, request.to_owned());
+        let room = &server.rooms[&room_id];
+        assert!(!room.game_running);
+        assert!(room.universe.is_none() && room.game_players.is_empty());
+        assert_eq!(server.get_player(player_one).unwrap().get_confirmed_full_gen(), None);
+
+        // the same room hosts the next game, starting over from the first generation
+        server.start_game(room_id).unwrap();
+        let room = &server.rooms[&room_id];
+        assert!(room.game_running);
+        assert_eq!(room.game_players, vec![player_one, spectator]);
+        assert_eq!(room.universe.as_ref().unwrap().latest_gen(), 1);
+        server.advance_games();
+        assert!(server.rooms[&room_id].game_running);
+    }
+
+    /// The state of a cell in the game `room_id` is running, as player number `player_num` sees it.
+    fn cell_seen_by(server: &ServerState, room_id: RoomID, player_num: usize, col: usize, row: usize) -> CellState {
+        let uni = server.rooms[&room_id].universe.as_ref().unwrap();
+        let mut seen = CellState::Dead;
+        let region = Region::new(col as isize, row as isize, 1, 1);
+        uni.each_non_dead(region, Some(player_num), &mut |_, _, state| seen = state);
+        seen
+    }
+
+    #[test]
+    fn fog_follows_the_players_cells() {
+        let (mut server, room_id, player_one, _) = server_with_running_game();
+        let strip_width = GAME_UNIVERSE_WIDTH / 2;
+        assert_eq!(cell_seen_by(&server, room_id, 0, strip_width + 2, 60), CellState::Fog);
+
+        // a lone cell at the edge of the first player's strip, which dies in the next generation
+        let cells = vec![(strip_width - 1, 60, CellState::Alive(Some(0)))];
+        assert_eq!(
+            server.handle_place_cells(player_one, cells),
+            ResponseCode::CellsPlaced { generation: 1 }
+        );
+        assert_eq!(cell_seen_by(&server, room_id, 0, strip_width + 2, 60), CellState::Dead);
+        assert_eq!(cell_seen_by(&server, room_id, 1, 2, 60), CellState::Fog);
+
+        server.advance_games();
+        assert_eq!(cell_seen_by(&server, room_id, 0, strip_width + 2, 60), CellState::Fog);
+        assert_eq!(cell_seen_by(&server, room_id, 0, strip_width - 1, 60), CellState::Dead);
+        assert_eq!(cell_seen_by(&server, room_id, 1, strip_width + 2, 60), CellState::Dead);
+    }
+
+    #[test]
+    fn no_fog_when_fog_radius_is_off() {
+        let mut server = ServerState::new();
+        server.fog_radius = None;
+        let (room_id, player_ids) = start_game_in_new_room(&mut server, &["player one", "player two"]);
+        assert!(server.rooms[&room_id].sight.is_empty());
+        let strip_width = GAME_UNIVERSE_WIDTH / 2;
+        assert_eq!(cell_seen_by(&server, room_id, 0, strip_width + 2, 60), CellState::Dead);
+
+        let cells = vec![(strip_width - 1, 60, CellState::Alive(Some(0)))];
+        server.handle_place_cells(player_ids[0], cells);
+        server.advance_games();
+        for &(player_num, col) in &[(0, GAME_UNIVERSE_WIDTH - 1), (1, 0), (0, strip_width + 2)] {
+            assert_eq!(cell_seen_by(&server, room_id, player_num, col, 60), CellState::Dead);
         }
     }
 
+    fn universe_diffs(updates: Vec<(SocketAddr, Packet)>) -> Vec<GenStateDiffPart> {
+        updates
+            .into_iter()
+            .map(|(_, pkt)| match pkt {
+                Packet::Update {
+                    universe_update: UniUpdate::Diff { diff },
+                    ..
+                } => diff,
+                _ => panic!("Unexpected packet in universe update construction!"),
+            })
+            .collect()
+    }
+
     #[test]
-    fn process_request_action_connect_while_connected() {
+    fn construct_universe_updates_game_not_running() {
         let mut server = ServerState::new();
-        let player_name = "some player".to_owned();
-        server.create_new_room(None, "some room".to_owned().clone());
-        let player_id: PlayerID = {
-            let player: &mut Player = server.add_new_player(player_name.clone(), fake_socket_addr());
-            player.player_id
-        };
-        let result = server.process_request_action(
-            player_id,
-            RequestAction::Connect {
-                name:           player_name,
-                client_version: "0.1.0".to_owned(),
+        let room_name = "some room";
+        server.create_new_room(None, room_name.to_owned());
+        let player_id = server
+            .add_new_player("some player".to_owned(), fake_socket_addr())
+            .player_id;
+        server.join_room(player_id, room_name);
+        server.advance_games();
+        assert!(server.construct_universe_updates().is_empty());
+    }
+
+    #[test]
+    fn construct_universe_updates_sends_full_diff_to_every_player() {
+        let (mut server, _, player_one, _) = server_with_running_game();
+        let cells = vec![(3, 4, CellState::Alive(Some(0)))];
+        server
+            .process_request_action(player_one, RequestAction::PlaceCells { cells })
+            .unwrap();
+        server.advance_games();
+
+        let diffs = universe_diffs(server.construct_universe_updates());
+        assert_eq!(diffs.len(), 2);
+        for diff in diffs {
+            assert_eq!(diff.gen0, 0);
+            assert_eq!(diff.gen1, 2);
+            assert_eq!(diff.part_number, 0);
+            assert_eq!(diff.total_parts, 1);
+        }
+    }
+
+    #[test]
+    fn construct_universe_updates_after_client_acked() {
+        let (mut server, _, player_one, player_two) = server_with_running_game();
+        server.advance_games();
+        server.advance_games();
+
+        // The first player has applied generation 2, and the second player is caught up
+        server.get_player_mut(player_one).unwrap().update_full_gen(Some(2));
+        server.get_player_mut(player_two).unwrap().update_full_gen(Some(3));
+
+        let diffs = universe_diffs(server.construct_universe_updates());
+        assert_eq!(diffs.len(), 1);
+        assert_eq!((diffs[0].gen0, diffs[0].gen1), (2, 3));
+    }
+
+    #[test]
+    fn construct_universe_updates_carry_checksum_of_latest_gen() {
+        let (mut server, _, player_one, player_two) = server_with_running_game();
+        let cells = vec![(3, 4, CellState::Alive(Some(0)))];
+        server
+            .process_request_action(player_one, RequestAction::PlaceCells { cells })
+            .unwrap();
+        server.advance_games();
+
+        let room = server.get_room(player_one).unwrap();
+        let uni = room.universe.as_ref().unwrap();
+        let mut expected = vec![
+            GenChecksum {
+                generation: 2,
+                checksum:   uni.checksum(Some(0)),
             },
-        );
-        assert_eq!(
-            result,
-            ResponseCode::BadRequest {
-                error_msg: "Already connected".to_owned(),
+            GenChecksum {
+                generation: 2,
+                checksum:   uni.checksum(Some(1)),
+            },
+        ];
+        assert_eq!(room.gen_checksum(player_one), Some(expected[0].clone()));
+        assert_eq!(room.gen_checksum(player_two), Some(expected[1].clone()));
+
+        let mut checksums = vec![];
+        for (_, pkt) in server.construct_universe_updates() {
+            match pkt {
+                Packet::Update {
+                    gen_checksum: Some(gen_checksum),
+                    ..
+                } => checksums.push(gen_checksum),
+                _ => panic!("Unexpected packet in universe update construction!"),
             }
+        }
+        checksums.sort_by_key(|gen_checksum| gen_checksum.checksum);
+        expected.sort_by_key(|gen_checksum| gen_checksum.checksum);
+        assert_eq!(checksums, expected);
+    }
+
+    #[test]
+    fn request_full_state_resends_from_the_beginning() {
+        let (mut server, _, player_one, player_two) = server_with_running_game();
+        server.advance_games();
+        server.get_player_mut(player_one).unwrap().update_full_gen(Some(2));
+        server.get_player_mut(player_two).unwrap().update_full_gen(Some(2));
+
+        assert_eq!(
+            server
+                .process_request_action(player_one, RequestAction::RequestFullState)
+                .unwrap(),
+            ResponseCode::OK
         );
+        assert_eq!(server.get_player(player_one).unwrap().get_confirmed_full_gen(), None);
+        assert_eq!(server.get_player(player_two).unwrap().get_confirmed_full_gen(), Some(2));
+
+        server.advance_games();
+        let diffs = universe_diffs(server.construct_universe_updates());
+        assert_eq!(diffs.len(), 2);
+        let mut gens: Vec<_> = diffs.iter().map(|diff| (diff.gen0, diff.gen1)).collect();
+        gens.sort();
+        assert_eq!(gens, vec![(0, 3), (2, 3)]);
     }
 
     #[test]
-    fn process_request_action_none_is_invalid() {
+    fn request_full_state_game_not_running() {
         let mut server = ServerState::new();
-        server.create_new_room(None, "some room".to_owned().clone());
-        let player_id: PlayerID = {
-            let player: &mut Player = server.add_new_player("some player".to_owned(), fake_socket_addr());
-            player.player_id
-        };
-        let result = server.process_request_action(player_id, RequestAction::None);
+        let room_name = "some room";
+        server.create_new_room(None, room_name.to_owned());
+        let player_id = server
+            .add_new_player("some player".to_owned(), fake_socket_addr())
+            .player_id;
         assert_eq!(
-            result,
+            server
+                .process_request_action(player_id, RequestAction::RequestFullState)
+                .unwrap(),
             ResponseCode::BadRequest {
-                error_msg: "Invalid request: None".to_owned(),
+                error_msg: format!("Player {} has not joined a game.", player_id),
+            }
+        );
+
+        server.join_room(player_id, room_name);
+        assert_eq!(
+            server
+                .process_request_action(player_id, RequestAction::RequestFullState)
+                .unwrap(),
+            ResponseCode::BadRequest {
+                error_msg: "game is not running".to_owned(),
             }
         );
     }
 
     #[test]
-    fn prepare_response_spot_check_response_packet() {
+    fn update_full_gen_never_goes_backwards() {
+        let (mut server, _, player_one, _) = server_with_running_game();
+        let player = server.get_player_mut(player_one).unwrap();
+        player.update_full_gen(Some(5));
+        player.update_full_gen(Some(3));
+        player.update_full_gen(None);
+        assert_eq!(player.get_confirmed_full_gen(), Some(5));
+    }
+
+    #[test]
+    fn add_new_player_player_added_with_initial_sequence_number() {
+        let mut server = ServerState::new();
+        let name = "some player".to_owned();
+
+        let p: &mut Player = server.add_new_player(name.clone(), fake_socket_addr());
+        assert_eq!(p.name, name);
+    }
+
+    #[test]
+    fn is_unique_player_name_yes_and_no_case() {
+        let mut server = ServerState::new();
+        let name = "some player".to_owned();
+        assert_eq!(server.is_unique_player_name("some player"), true);
+
+        {
+            server.add_new_player(name.clone(), fake_socket_addr());
+        }
+        assert_eq!(server.is_unique_player_name("some player"), false);
+    }
+
+    #[test]
+    fn expire_old_messages_in_all_rooms_room_is_empty() {
+        let mut server = ServerState::new();
+        let room_name = "some room";
+
+        server.create_new_room(None, room_name.to_owned().clone());
+        server.expire_old_messages_in_all_rooms(time::Instant::now());
+
+        for room in server.rooms.values() {
+            assert_eq!(room.messages.len(), 0);
+        }
+    }
+
+    #[test]
+    fn expire_old_messages_in_all_rooms_one_room_good_case() {
+        let mut server = ServerState::new();
+
+        let player_id: PlayerID = {
+            let player: &mut Player = server.add_new_player("some player".to_owned(), fake_socket_addr());
+            player.player_id
+        };
+
+        server.join_room(player_id, "general");
+
+        server.handle_chat_message(player_id, "Conwayste is such a fun game".to_owned());
+        server.handle_chat_message(player_id, "There are not loot boxes".to_owned());
+        server.handle_chat_message(player_id, "It is free!".to_owned());
+        server.handle_chat_message(player_id, "What's not to love?".to_owned());
+
+        let message_count = {
+            let room: &Room = server.get_room(player_id).unwrap();
+            room.messages.len()
+        };
+        assert_eq!(message_count, 4);
+
+        // Messages are not old enough to be expired
+        server.expire_old_messages_in_all_rooms(time::Instant::now());
+
+        for room in server.rooms.values() {
+            assert_eq!(room.messages.len(), 4);
+        }
+    }
+
+    #[test]
+    fn expire_old_messages_in_all_rooms_several_rooms_good_case() {
+        let mut server = ServerState::new();
+        let room_name = "some room";
+        let room_name2 = "some room2";
+
+        let room_id = server.new_room(room_name.to_owned());
+        let room_id2 = server.new_room(room_name2.to_owned());
+        let player_id: PlayerID = {
+            let player: &mut Player = server.add_new_player("some player".to_owned(), fake_socket_addr());
+            player.player_id
+        };
+        let player_id2: PlayerID = {
+            let player: &mut Player = server.add_new_player("some player".to_owned(), fake_socket_addr());
+            player.player_id
+        };
+
+        server.join_room(player_id, room_name);
+        server.join_room(player_id2, room_name2);
+
+        server.handle_chat_message(player_id, "Conwayste is such a fun game".to_owned());
+        server.handle_chat_message(player_id, "There are not loot boxes".to_owned());
+        server.handle_chat_message(player_id2, "It is free!".to_owned());
+        server.handle_chat_message(player_id2, "What's not to love?".to_owned());
+
+        let message_count = {
+            let room: &Room = server.get_room(player_id).unwrap();
+            room.messages.len()
+        };
+        assert_eq!(message_count, 2);
+        let message_count2 = {
+            let room: &Room = server.get_room(player_id2).unwrap();
+            room.messages.len()
+        };
+        assert_eq!(message_count2, 2);
+
+        // Messages are not old enough to be expired
+        server.expire_old_messages_in_all_rooms(time::Instant::now());
+
+        assert_eq!(server.rooms[&room_id].messages.len(), 2);
+        assert_eq!(server.rooms[&room_id2].messages.len(), 2);
+    }
+
+    #[test]
+    fn expire_old_messages_in_all_rooms_one_room_old_messages_are_wiped() {
+        let mut server = ServerState::new();
+        let room_name = "some room";
+
+        server.create_new_room(None, room_name.to_owned().clone());
+        let player_id: PlayerID = {
+            let player: &mut Player = server.add_new_player("some player".to_owned(), fake_socket_addr());
+            player.player_id
+        };
+
+        server.join_room(player_id, room_name);
+
+        server.handle_chat_message(player_id, "Conwayste is such a fun game".to_owned());
+        server.handle_chat_message(player_id, "There are not loot boxes".to_owned());
+        server.handle_chat_message(player_id, "It is free!".to_owned());
+        server.handle_chat_message(player_id, "What's not to love?".to_owned());
+
+        let current_timestamp = Instant::now();
+        let travel_to_the_past = current_timestamp.checked_sub(Duration::from_secs((MAX_AGE_CHAT_MESSAGES + 1) as u64));
+        if travel_to_the_past.is_none() {
+            warn!("skipping rest of test; cannot travel to the past :(");
+            return;
+        }
+        let travel_to_the_past = travel_to_the_past.unwrap();
+        for ref mut room in server.rooms.values_mut() {
+            println!("Room: {:?}", room.name);
+            for m in room.messages.iter_mut() {
+                println!(
+                    "{:?}, {:?},       {:?}",
+                    m.timestamp,
+                    travel_to_the_past,
+                    m.timestamp - travel_to_the_past
+                );
+                m.timestamp = travel_to_the_past;
+            }
+        }
+
+        // Messages are not old enough to be expired
+        server.expire_old_messages_in_all_rooms(time::Instant::now());
+
+        for room in server.rooms.values() {
+            assert_eq!(room.messages.len(), 0);
+        }
+    }
+
+    #[test]
+    fn expire_old_messages_in_all_rooms_several_rooms_old_messages_are_wiped() {
+        let mut server = ServerState::new();
+        let room_name = "some room";
+        let room_name2 = "some room 2";
+
+        server.create_new_room(None, room_name.to_owned().clone());
+        let player_id: PlayerID = {
+            let player: &mut Player = server.add_new_player("some player".to_owned(), fake_socket_addr());
+            player.player_id
+        };
+        server.create_new_room(None, room_name2.to_owned().clone());
+        let player_id2: PlayerID = {
+            let player: &mut Player = server.add_new_player("some player".to_owned(), fake_socket_addr());
+            player.player_id
+        };
+
+        server.join_room(player_id, room_name);
+        server.join_room(player_id2, room_name);
+
+        server.handle_chat_message(player_id, "Conwayste is such a fun game".to_owned());
+        server.handle_chat_message(player_id, "There are not loot boxes".to_owned());
+        server.handle_chat_message(player_id2, "It is free!".to_owned());
+        server.handle_chat_message(player_id2, "What's not to love?".to_owned());
+
+        let current_timestamp = Instant::now();
+        let travel_to_the_past = current_timestamp.checked_sub(Duration::from_secs((MAX_AGE_CHAT_MESSAGES + 1) as u64));
+        if travel_to_the_past.is_none() {
+            warn!("skipping rest of test; cannot travel to the past :(");
+            return;
+        }
+        let travel_to_the_past = travel_to_the_past.unwrap();
+        for ref mut room in server.rooms.values_mut() {
+            println!("Room: {:?}", room.name);
+            for m in room.messages.iter_mut() {
+                println!(
+                    "{:?}, {:?},       {:?}",
+                    m.timestamp,
+                    travel_to_the_past,
+                    m.timestamp - travel_to_the_past
+                );
+                m.timestamp = travel_to_the_past;
+            }
+        }
+
+        // Messages are not old enough to be expired
+        server.expire_old_messages_in_all_rooms(time::Instant::now());
+
+        for room in server.rooms.values() {
+            assert_eq!(room.messages.len(), 0);
+        }
+    }
+
+    #[test]
+    fn handle_new_connection_good_case() {
+        let mut server = ServerState::new();
+        let player_name = "some name".to_owned();
+        let pkt = server.handle_new_connection(player_name, fake_socket_addr());
+        match pkt {
+            Packet::Response {
+                sequence: _,
+                request_ack: _,
+                code,
+            } => match code {
+                ResponseCode::LoggedIn {
+                    cookie: _,
+                    server_version: _,
+                } => {}
+                _ => panic!("Unexpected ResponseCode: {:?}", code),
+            },
+            _ => panic!("Unexpected Packet Type: {:?}", pkt),
+        }
+    }
+
+    #[test]
+    fn handle_new_connection_player_name_taken() {
+        let mut server = ServerState::new();
+        let player_name = "some name".to_owned();
+
+        let pkt = server.handle_new_connection(player_name.clone(), fake_socket_addr());
+        match pkt {
+            Packet::Response {
+                sequence: _,
+                request_ack: _,
+                code,
+            } => match code {
+                ResponseCode::LoggedIn {
+                    cookie: _,
+                    server_version,
+                } => assert_eq!(server_version, VERSION.to_owned()),
+                _ => panic!("Unexpected ResponseCode: {:?}", code),
+            },
+            _ => panic!("Unexpected Packet Type: {:?}", pkt),
+        }
+
+        let pkt = server.handle_new_connection(player_name, fake_socket_addr());
+        match pkt {
+            Packet::Response {
+                sequence: _,
+                request_ack: _,
+                code,
+            } => match code {
+                ResponseCode::Unauthorized { error_msg } => {
+                    assert_eq!(error_msg, "not a unique name".to_owned());
+                }
+                _ => panic!("Unexpected ResponseCode: {:?}", code),
+            },
+            _ => panic!("Unexpected Packet Type: {:?}", pkt),
+        }
+    }
+
+    #[test]
+    fn handle_new_connection_server_full() {
+        let mut server = ServerState::new();
+        server.max_players = 2;
+        for name in &["first", "second"] {
+            server.handle_new_connection(name.to_string(), fake_socket_addr());
+        }
+        assert_eq!(server.players.len(), 2);
+
+        match server.handle_new_connection("third".to_owned(), fake_socket_addr()) {
+            Packet::Response {
+                code: ResponseCode::Unauthorized { error_msg },
+                ..
+            } => assert_eq!(error_msg, "server full"),
+            pkt => panic!("Unexpected packet: {:?}", pkt),
+        }
+        assert_eq!(server.players.len(), 2);
+
+        // there is space again once someone leaves
+        let player_id = server.players.values().find(|p| p.name == "first").unwrap().player_id;
+        server.handle_disconnect(player_id).unwrap();
+        match server.handle_new_connection("third".to_owned(), fake_socket_addr()) {
+            Packet::Response {
+                code: ResponseCode::LoggedIn { .. },
+                ..
+            } => {}
+            pkt => panic!("Unexpected packet: {:?}", pkt),
+        }
+    }
+
+    #[test]
+    fn create_new_room_too_many_rooms() {
+        let mut server = ServerState::new();
+        server.max_rooms = 2; // one more than the general room
+        assert_eq!(server.create_new_room(None, "second".to_owned()), ResponseCode::OK);
+        assert_eq!(
+            server.create_new_room(None, "third".to_owned()),
+            ResponseCode::BadRequest {
+                error_msg: "too many rooms; max 2".to_owned(),
+            }
+        );
+        assert_eq!(server.rooms.len(), 2);
+    }
+
+    fn a_request_action_strat() -> BoxedStrategy<RequestAction> {
+        prop_oneof![
+            Just(RequestAction::Disconnect),
+            Just(RequestAction::KeepAlive { latest_response_ack: 0 }),
+            Just(RequestAction::LeaveRoom),
+            Just(RequestAction::ListPlayers),
+            Just(RequestAction::ListRooms),
+            Just(RequestAction::None),
+            Just(RequestAction::RequestFullState),
+            Just(RequestAction::ClearArea { x: 0, y: 0, w: 1, h: 1 }),
+            Just(RequestAction::MutePlayer {
+                name: "some player".to_owned(),
+            }),
+            Just(RequestAction::UnmutePlayer {
+                name: "nobody".to_owned(),
+            }),
+            Just(RequestAction::Admin {
+                token:   "".to_owned(),
+                command: AdminCommand::ListBans,
+            }),
+            Just(RequestAction::CloseRoom {
+                room_name: "some room".to_owned(),
+            }),
+            Just(RequestAction::SetTeam { team: 1 }),
+        ]
+        .boxed()
+    }
+
+    fn a_request_action_complex_strat() -> BoxedStrategy<RequestAction> {
+        prop_oneof![
+            ("([A-Z]{1,4} [0-9]{1,2}){3}").prop_map(|a| RequestAction::ChatMessage { message: a }),
+            (
+                "([A-Z]{1,4} [0-9]{1,2}){3}",
+                option::of("[a-z]{0,8}"),
+                option::of(any::<u8>()),
+                option::of(any::<u8>())
+            )
+                .prop_map(|(a, b, c, d)| RequestAction::NewRoom {
+                    room_name:   a,
+                    password:    b,
+                    team_count:  c,
+                    max_players: d,
+                }),
+            ("([A-Z]{1,4} [0-9]{1,2}){3}", option::of("[a-z]{0,8}")).prop_map(|(a, b)| RequestAction::JoinRoom {
+                room_name: a,
+                password:  b,
+            }),
+            ("([A-Z]{1,4} [0-9]{1,2}){3}", "[0-9].[0-9].[0-9]").prop_map(|(a, b)| {
+                RequestAction::Connect {
+                    name:           a,
+                    client_version: b,
+                }
+            })
+        ]
+        .boxed()
+    }
+
+    // These tests are checking that we do not panic on each RequestAction
+    proptest! {
+        #[test]
+        fn process_request_action_simple(ref request in a_request_action_strat()) {
+            let mut server = ServerState::new();
+            server.create_new_room(None, "some room".to_owned().clone());
+            let player_id: PlayerID = {
+                let player: &mut Player = server.add_new_player("some player".to_owned(), fake_socket_addr());
+                player.player_id
+            };
+            let _result = server.process_request_action(player_id, request.to_owned());
+        }
+
+        #[test]
+        fn process_request_action_complex(ref request in a_request_action_complex_strat()) {
+            let mut server = ServerState::new();
+            server.create_new_room(None, "some room".to_owned().clone());
+            let player_id: PlayerID = {
+                let player: &mut Player = server.add_new_player("some player".to_owned(), fake_socket_addr());
+                player.player_id
+            };
+            let _result = server.process_request_action(player_id, request.to_owned());
+        }
+    }
+
+    #[test]
+    fn process_request_action_connect_while_connected() {
+        let mut server = ServerState::new();
+        let player_name = "some player".to_owned();
+        server.create_new_room(None, "some room".to_owned().clone());
+        let player_id: PlayerID = {
+            let player: &mut Player = server.add_new_player(player_name.clone(), fake_socket_addr());
+            player.player_id
+        };
+        let result = server
+            .process_request_action(
+                player_id,
+                RequestAction::Connect {
+                    name:           player_name,
+                    client_version: "0.1.0".to_owned(),
+                },
+            )
+            .unwrap();
+        assert_eq!(
+            result,
+            ResponseCode::BadRequest {
+                error_msg: "Already connected".to_owned(),
+            }
+        );
+    }
+
+    #[test]
+    fn process_request_action_none_is_invalid() {
+        let mut server = ServerState::new();
+        server.create_new_room(None, "some room".to_owned().clone());
+        let player_id: PlayerID = {
+            let player: &mut Player = server.add_new_player("some player".to_owned(), fake_socket_addr());
+            player.player_id
+        };
+        let result = server.process_request_action(player_id, RequestAction::None).unwrap();
+        assert_eq!(
+            result,
+            ResponseCode::BadRequest {
+                error_msg: "Invalid request: None".to_owned(),
+            }
+        );
+    }
+
+    #[test]
+    fn process_request_action_not_implemented() {
+        let mut server = ServerState::new();
+        let player_id: PlayerID = {
+            let player: &mut Player = server.add_new_player("some player".to_owned(), fake_socket_addr());
+            player.request_ack = Some(1);
+            player.player_id
+        };
+        let action = RequestAction::DropPattern {
+            x:       0,
+            y:       0,
+            pattern: "o!".to_owned(),
+        };
+        assert_eq!(
+            server.process_request_action(player_id, action.clone()),
+            Err(ServerError::NotImplemented { action: action.clone() })
+        );
+
+        // The client still gets a response, so that it stops resending the request
+        match server.prepare_response(player_id, action).unwrap() {
+            Some(Packet::Response {
+                code: ResponseCode::BadRequest { .. },
+                ..
+            }) => {}
+            pkt => panic!("Unexpected response to an unimplemented request: {:?}", pkt),
+        }
+    }
+
+    #[test]
+    fn process_request_action_unknown_player() {
+        let mut server = ServerState::new();
+        let player_id = PlayerID(1234);
+        assert_eq!(
+            server.process_request_action(player_id, RequestAction::ListRooms),
+            Err(ServerError::PlayerNotFound { player_id })
+        );
+        assert_eq!(
+            server.process_request_action(player_id, RequestAction::Disconnect),
+            Err(ServerError::PlayerNotFound { player_id })
+        );
+    }
+
+    #[test]
+    fn prepare_response_spot_check_response_packet() {
+        let mut server = ServerState::new();
+        let player_id: PlayerID = {
+            let player: &mut Player = server.add_new_player("some player".to_owned(), fake_socket_addr());
+            player.request_ack = Some(1);
+            player.player_id
+        };
+        let pkt: Packet = server
+            .prepare_response(player_id, RequestAction::ListRooms)
+            .unwrap()
+            .unwrap();
+        match pkt {
+            Packet::Response {
+                code,
+                sequence,
+                request_ack,
+            } => {
+                if let ResponseCode::RoomList { rooms } = code {
+                    assert_eq!(rooms.len(), 1); // 1 room - general
+                } else {
+                    panic!("`code` is not a RoomList! code is {:?}", code);
+                }
+                assert_eq!(sequence, 1);
+                assert_eq!(request_ack, Some(2));
+            }
+            _ => panic!("Unexpected Packet type on Response path: {:?}", pkt),
+        }
+        let player: &Player = server.get_player(player_id).unwrap();
+        assert_eq!(player.next_resp_seq, 2);
+    }
+
+    #[test]
+    fn validate_client_version_client_is_up_to_date() {
+        assert_eq!(validate_client_version(env!("CARGO_PKG_VERSION").to_owned()), true);
+    }
+
+    #[test]
+    fn validate_client_version_client_is_very_old() {
+        assert_eq!(validate_client_version("0.0.1".to_owned()), true);
+    }
+
+    #[test]
+    fn validate_client_version_client_is_from_the_future() {
+        assert_eq!(
+            validate_client_version(
+                format!("{}.{}.{}", <i32>::max_value(), <i32>::max_value(), <i32>::max_value()).to_owned()
+            ),
+            false
+        );
+    }
+
+    #[test]
+    fn decode_packet_update_reply_good_case() {
+        let mut server = ServerState::new();
+        let cookie: String = {
+            let player: &mut Player = server.add_new_player("some player".to_owned(), fake_socket_addr());
+            player.cookie.clone()
+        };
+
+        // TODO: Move this into a private helper
+        let update_reply_packet = Packet::UpdateReply {
+            cookie:               cookie,
+            last_chat_seq:        Some(0),
+            last_game_update_seq: None,
+            last_full_gen:        None,
+            partial_gen:          None,
+            pong:                 PingPong::pong(0),
+        };
+
+        let result = server.decode_packet(fake_socket_addr(), update_reply_packet);
+        assert!(result.is_ok());
+        assert!(result.unwrap().is_none());
+    }
+
+    #[test]
+    fn decode_packet_update_reply_invalid_cookie() {
+        let mut server = ServerState::new();
+        {
+            let player: &mut Player = server.add_new_player("some player".to_owned(), fake_socket_addr());
+            player.cookie.clone()
+        };
+
+        let cookie = "CookieMonster".to_owned();
+
+        let update_reply_packet = Packet::UpdateReply {
+            cookie:               cookie,
+            last_chat_seq:        Some(0),
+            last_game_update_seq: None,
+            last_full_gen:        None,
+            partial_gen:          None,
+            pong:                 PingPong::pong(0),
+        };
+
+        let result = server.decode_packet(fake_socket_addr(), update_reply_packet);
+        assert_eq!(result, Err(ServerError::InvalidCookie));
+    }
+
+    #[test]
+    fn decode_packet_request_without_cookie() {
+        let mut server = ServerState::new();
+        let request_packet = Packet::Request {
+            sequence:     1,
+            response_ack: None,
+            cookie:       None,
+            action:       RequestAction::ListRooms,
+        };
+
+        let result = server.decode_packet(fake_socket_addr(), request_packet);
+        assert_eq!(result, Err(ServerError::MissingCookie));
+    }
+
+    #[test]
+    fn decode_packet_request_invalid_cookie() {
+        let mut server = ServerState::new();
+        let request_packet = Packet::Request {
+            sequence:     1,
+            response_ack: None,
+            cookie:       Some("CookieMonster".to_owned()),
+            action:       RequestAction::ListRooms,
+        };
+
+        let result = server.decode_packet(fake_socket_addr(), request_packet);
+        assert_eq!(result, Err(ServerError::InvalidCookie));
+    }
+
+    #[test]
+    fn decode_packet_ping_without_cookie_is_answered() {
+        let mut server = ServerState::new();
+        let ping_packet = Packet::Request {
+            sequence:     0,
+            response_ack: None,
+            cookie:       None,
+            action:       RequestAction::Ping { timestamp: 12345 },
+        };
+
+        let result = server.decode_packet(fake_socket_addr(), ping_packet);
+        match result {
+            Ok(Some(Packet::Response { code, .. })) => assert_eq!(code, ResponseCode::Pong { timestamp: 12345 }),
+            _ => panic!("Unexpected result: {:?}", result),
+        }
+        // nobody was logged in by it
+        assert!(server.players.is_empty());
+    }
+
+    #[test]
+    fn pings_are_rate_limited_per_address() {
+        let mut server = ServerState::new();
+        let now = Instant::now();
+        let addr = fake_socket_addr();
+        for i in 0..MAX_PINGS_PER_WINDOW as u64 {
+            assert!(server.handle_ping(addr, i, now).is_some());
+        }
+        assert!(server.handle_ping(addr, 99, now).is_none());
+
+        // another port on the same host shares the limit, but another host doesn't
+        let other_port = SocketAddr::new(addr.ip(), addr.port() + 1);
+        assert!(server.handle_ping(other_port, 99, now).is_none());
+        let other_host: SocketAddr = "5.6.7.8:5678".parse().unwrap();
+        assert!(server.handle_ping(other_host, 99, now).is_some());
+
+        // a new window starts once the old one is over
+        let later = now + PING_WINDOW;
+        assert!(server.handle_ping(addr, 100, later).is_some());
+
+        server.expire_pings(later + PING_WINDOW);
+        assert!(server.pings.is_empty());
+    }
+
+    #[test]
+    fn unsupported_version_replies_are_rate_limited_like_pings() {
+        let mut server = ServerState::new();
+        let now = Instant::now();
+        let addr = fake_socket_addr();
+        let future = WireError::UnsupportedVersion { version: 99 };
+        let malformed = WireError::Malformed {
+            version: PROTOCOL_VERSION,
+            reason:  "no packet count".to_owned(),
+        };
+        assert!(!server.should_reply_unsupported(addr, &malformed, now));
+        assert!(server.should_reply_unsupported(addr, &WireError::MissingHeader, now));
+        for _ in 1..MAX_PINGS_PER_WINDOW {
+            assert!(server.should_reply_unsupported(addr, &future, now));
+        }
+        assert!(!server.should_reply_unsupported(addr, &future, now));
+        assert!(server.handle_ping(addr, 1, now).is_none()); // the same limit
+
+        let later = now + PING_WINDOW;
+        assert!(server.should_reply_unsupported(addr, &future, later));
+    }
+
+    #[test]
+    fn discover_is_only_answered_when_discoverable() {
+        let mut server = ServerState::new();
+        let now = Instant::now();
+        let discover_packet = Packet::Request {
+            sequence:     0,
+            response_ack: None,
+            cookie:       None,
+            action:       RequestAction::Discover,
+        };
+
+        let result = server.decode_packet(fake_socket_addr(), discover_packet.clone());
+        assert!(result.unwrap().is_none());
+
+        server.discovery = true;
+        server.add_new_player("some player".to_owned(), fake_socket_addr());
+        let result = server.decode_packet(fake_socket_addr(), discover_packet);
+        match result {
+            Ok(Some(Packet::Response { code, .. })) => assert_eq!(
+                code,
+                ResponseCode::ServerInfo {
+                    server_name:    DEFAULT_NAME.to_owned(),
+                    server_version: VERSION.to_owned(),
+                    player_count:   1,
+                    room_count:     1, // general
+                }
+            ),
+            _ => panic!("Unexpected result: {:?}", result),
+        }
+
+        // discovery requests count against the ping limit
+        for _ in 1..MAX_PINGS_PER_WINDOW {
+            assert!(server.handle_ping(fake_socket_addr(), 0, now).is_some());
+        }
+        assert!(server.handle_discover(fake_socket_addr(), now).is_none());
+    }
+
+    #[test]
+    fn expired_sessions_have_to_connect_again() {
+        let mut server = ServerState::new();
+        let lifetime = Duration::from_secs(60);
+        server.cookie_life = Some(lifetime);
+        let (old_id, old_cookie, issued) = {
+            let player = server.add_new_player("old timer".to_owned(), fake_socket_addr());
+            (player.player_id, player.cookie.clone(), player.cookie_issued)
+        };
+        server.join_room(old_id, "general");
+        let new_id = {
+            let player = server.add_new_player("newcomer".to_owned(), fake_socket_addr());
+            player.cookie_issued = issued + Duration::from_secs(30);
+            player.player_id
+        };
+
+        server.expire_sessions(issued + lifetime - Duration::from_secs(1));
+        assert_eq!(server.get_player_id_by_cookie(&old_cookie), Some(old_id));
+
+        server.expire_sessions(issued + lifetime);
+        assert_eq!(server.get_player_id_by_cookie(&old_cookie), None);
+        assert!(server.get_player(old_id).is_err());
+        assert!(server.get_player(new_id).is_ok());
+        let request_packet = Packet::Request {
+            sequence:     1,
+            response_ack: None,
+            cookie:       Some(old_cookie.clone()),
+            action:       RequestAction::ListRooms,
+        };
+        let result = server.decode_packet(fake_socket_addr(), request_packet.clone());
+        assert_eq!(result, Err(ServerError::SessionExpired));
+        assert_eq!(
+            ServerError::SessionExpired.response_code(),
+            Some(ResponseCode::Unauthorized {
+                error_msg: "session expired".to_owned(),
+            })
+        );
+
+        // after a while, the old cookie is forgotten like any other that isn't valid
+        server.expire_sessions(issued + lifetime + EXPIRED_COOKIE_MEMORY);
+        assert!(server.old_cookies.is_empty());
+        assert!(server.get_player(new_id).is_err());
+        let result = server.decode_packet(fake_socket_addr(), request_packet);
+        assert_eq!(result, Err(ServerError::InvalidCookie));
+    }
+
+    #[test]
+    fn sessions_never_expire_without_a_lifetime() {
+        let mut server = ServerState::new();
+        server.cookie_life = None;
+        let (player_id, issued) = {
+            let player = server.add_new_player("old timer".to_owned(), fake_socket_addr());
+            (player.player_id, player.cookie_issued)
+        };
+        server.expire_sessions(issued + Duration::from_secs(365 * 24 * 60 * 60));
+        assert!(server.get_player(player_id).is_ok());
+    }
+
+    #[test]
+    fn idle_players_time_out() {
+        let mut server = ServerState::new();
+        server.timeout = Duration::from_secs(10);
+        let (idle_id, last_received) = {
+            let player = server.add_new_player("idle".to_owned(), fake_socket_addr());
+            (player.player_id, player.last_received)
+        };
+        server.join_room(idle_id, "general");
+        let active_id = {
+            let player = server.add_new_player("active".to_owned(), fake_socket_addr());
+            player.last_received = last_received + Duration::from_secs(5);
+            player.player_id
+        };
+
+        server.remove_timed_out_clients(last_received + server.timeout);
+        assert!(server.get_player(idle_id).is_ok());
+
+        server.remove_timed_out_clients(last_received + server.timeout + Duration::from_secs(1));
+        assert!(server.get_player(idle_id).is_err());
+        assert!(!server.network_map.contains_key(&idle_id));
+        assert!(server.rooms[&server.room_map["general"]].player_ids.is_empty());
+        assert!(server.get_player(active_id).is_ok());
+
+        // a KeepAlive counts as hearing from the player
+        let cookie = server.get_player(active_id).unwrap().cookie.clone();
+        let keep_alive = Packet::Request {
+            sequence:     0,
+            response_ack: None,
+            cookie:       Some(cookie),
+            action:       RequestAction::KeepAlive { latest_response_ack: 0 },
+        };
+        let before = Instant::now();
+        assert_eq!(server.decode_packet(fake_socket_addr(), keep_alive), Ok(None));
+        assert!(server.get_player(active_id).unwrap().last_received >= before);
+    }
+
+    #[test]
+    fn cookies_are_long_and_unique() {
+        let mut server = ServerState::new();
+        let cookie = server
+            .add_new_player("first".to_owned(), fake_socket_addr())
+            .cookie
+            .clone();
+        let decoded = base64::decode_config(&cookie, base64::URL_SAFE_NO_PAD).unwrap();
+        assert_eq!(decoded.len(), COOKIE_BYTES);
+
+        // a cookie that is taken, or only recently expired, is thrown away for the next one
+        server.old_cookies.insert("expired".to_owned(), Instant::now());
+        let mut candidates = vec![cookie.clone(), "expired".to_owned(), "fresh".to_owned()].into_iter();
+        assert_eq!(server.unused_cookie(|| candidates.next().unwrap()), "fresh");
+
+        let other_cookie = server
+            .add_new_player("second".to_owned(), fake_socket_addr())
+            .cookie
+            .clone();
+        assert_ne!(cookie, other_cookie);
+    }
+
+    #[test]
+    fn decode_packet_server_packet_is_invalid() {
+        let mut server = ServerState::new();
+        let response_packet = Packet::Response {
+            sequence:    0,
+            request_ack: None,
+            code:        ResponseCode::OK,
+        };
+
+        match server.decode_packet(fake_socket_addr(), response_packet) {
+            Err(ServerError::InvalidPacket { .. }) => {}
+            result => panic!("Unexpected result of decoding a Response: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn decode_packet_client_out_of_date() {
+        let mut server = ServerState::new();
+        let client_version = format!("{}.0.0", <i32>::max_value());
+        let request_packet = Packet::Request {
+            sequence:     0,
+            response_ack: None,
+            cookie:       None,
+            action:       RequestAction::Connect {
+                name:           "some player".to_owned(),
+                client_version: client_version.clone(),
+            },
+        };
+
+        let result = server.decode_packet(fake_socket_addr(), request_packet);
+        assert_eq!(result, Err(ServerError::ClientOutOfDate { client_version }));
+    }
+
+    #[test]
+    fn process_packet_responds_to_invalid_cookie() {
+        let mut server = ServerState::new();
+        let request_packet = Packet::Request {
+            sequence:     1,
+            response_ack: None,
+            cookie:       Some("some invalid cookie".to_owned()),
+            action:       RequestAction::ListPlayers,
+        };
+
+        let responses = server.process_packet((request_packet, fake_socket_addr()));
+        assert_eq!(responses.len(), 1);
+        match responses[0] {
+            (
+                Packet::Response {
+                    code: ResponseCode::Unauthorized { .. },
+                    ..
+                },
+                addr,
+            ) => assert_eq!(addr, fake_socket_addr()),
+            ref response => panic!("Unexpected response to an invalid cookie: {:?}", response),
+        }
+    }
+
+    #[test]
+    fn room_lifecycle_emits_events() {
+        let mut server = ServerState::new();
+        let (emitter, mut receiver) = EventEmitter::channel(64);
+        server.events = emitter;
+        let mut next_event = move || receiver.try_next().ok().flatten().map(|record| record.event);
+
+        assert_eq!(server.create_new_room(None, "some room".to_owned()), ResponseCode::OK);
+        assert_eq!(
+            next_event(),
+            Some(ServerEvent::RoomCreated {
+                room: "some room".to_owned(),
+            })
+        );
+
+        let player_id = server
+            .add_new_player("some player".to_owned(), fake_socket_addr())
+            .player_id;
+        server.join_room(player_id, "some room");
+        assert_eq!(
+            next_event(),
+            Some(ServerEvent::PlayerJoined {
+                room:   "some room".to_owned(),
+                player: "some player".to_owned(),
+            })
+        );
+
+        for _ in 0..3 {
+            assert_eq!(server.handle_chat_message(player_id, "hi".to_owned()), ResponseCode::OK);
+        }
+        server.get_room_mut(player_id).unwrap().start_game().unwrap();
+
+        // The game starting is noticed on the next tick, and chat volume is only reported once the
+        // period is over
+        let start = server.chat_period;
+        server.emit_room_events(start);
+        assert_eq!(
+            next_event(),
+            Some(ServerEvent::GameStarted {
+                room:    "some room".to_owned(),
+                players: 1,
+            })
+        );
+        assert_eq!(next_event(), None);
+
+        server.emit_room_events(start + CHAT_VOLUME_PERIOD);
+        let mut volumes = vec![next_event().unwrap(), next_event().unwrap()];
+        volumes.sort_by_key(|event| format!("{:?}", event));
+        assert_eq!(
+            volumes,
+            vec![
+                ServerEvent::ChatVolume {
+                    room:        "general".to_owned(),
+                    messages:    0,
+                    period_secs: CHAT_VOLUME_PERIOD.as_secs(),
+                },
+                ServerEvent::ChatVolume {
+                    room:        "some room".to_owned(),
+                    messages:    3,
+                    period_secs: CHAT_VOLUME_PERIOD.as_secs(),
+                },
+            ]
+        );
+        assert_eq!(next_event(), None);
+
+        server.leave_room(player_id);
+        assert_eq!(
+            next_event(),
+            Some(ServerEvent::PlayerLeft {
+                room:   "some room".to_owned(),
+                player: "some player".to_owned(),
+            })
+        );
+
+        server.rooms.values_mut().for_each(|room| room.game_running = false);
+        server.emit_room_events(start + CHAT_VOLUME_PERIOD);
+        assert_eq!(
+            next_event(),
+            Some(ServerEvent::GameEnded {
+                room: "some room".to_owned(),
+            })
+        );
+        assert_eq!(next_event(), None);
+    }
+
+    #[test]
+    fn process_packet_answers_ipv6_peers_at_their_address() {
+        let mut server = ServerState::new();
+        // a native IPv6 peer, and an IPv4 peer as seen through a dual-stack socket
+        let v6_addr: SocketAddr = "[2001:db8::7]:5678".parse().unwrap();
+        let mapped_addr: SocketAddr = "[::ffff:1.2.3.4]:5678".parse().unwrap();
+        for (name, &addr) in ["v6 player", "mapped player"].iter().zip(&[v6_addr, mapped_addr]) {
+            let connect = Packet::Request {
+                sequence:     0,
+                response_ack: None,
+                cookie:       None,
+                action:       RequestAction::Connect {
+                    name:           name.to_string(),
+                    client_version: VERSION.to_owned(),
+                },
+            };
+            let responses = server.process_packet((connect, addr));
+            assert_eq!(responses.len(), 1);
+            match responses[0] {
+                (
+                    Packet::Response {
+                        code: ResponseCode::LoggedIn { .. },
+                        ..
+                    },
+                    response_addr,
+                ) => assert_eq!(response_addr, addr),
+                ref response => panic!("Unexpected response to connecting: {:?}", response),
+            }
+        }
+
+        let mut heartbeat_addrs: Vec<SocketAddr> = server.send_heartbeats().into_iter().map(|(_, addr)| addr).collect();
+        heartbeat_addrs.sort();
+        let mut expected = vec![v6_addr, mapped_addr];
+        expected.sort();
+        assert_eq!(heartbeat_addrs, expected);
+    }
+
+    fn connect_packet(name: &str) -> Packet {
+        Packet::Request {
+            sequence:     0,
+            response_ack: None,
+            cookie:       None,
+            action:       RequestAction::Connect {
+                name:           name.to_owned(),
+                client_version: VERSION.to_owned(),
+            },
+        }
+    }
+
+    fn admin_server() -> (ServerState, PlayerID) {
+        let mut server = ServerState::new();
+        server.admin_token = Some("sesame".to_owned());
+        let admin_addr: SocketAddr = "5.6.7.8:5678".parse().unwrap();
+        let admin_id = server.add_new_player("admin".to_owned(), admin_addr).player_id;
+        (server, admin_id)
+    }
+
+    fn admin(server: &mut ServerState, admin_id: PlayerID, command: AdminCommand) -> ResponseCode {
+        let action = RequestAction::Admin {
+            token: "sesame".to_owned(),
+            command,
+        };
+        server.process_request_action(admin_id, action).unwrap()
+    }
+
+    #[test]
+    fn banned_address_connect_is_rejected() {
         let mut server = ServerState::new();
-        let player_id: PlayerID = {
-            let player: &mut Player = server.add_new_player("some player".to_owned(), fake_socket_addr());
-            player.request_ack = Some(1);
-            player.player_id
-        };
-        let pkt: Packet = server.prepare_response(player_id, RequestAction::ListRooms).unwrap();
-        match pkt {
-            Packet::Response {
-                code,
-                sequence,
-                request_ack,
-            } => {
-                if let ResponseCode::RoomList { rooms } = code {
-                    assert_eq!(rooms.len(), 1); // 1 room - general
-                } else {
-                    panic!("`code` is not a RoomList! code is {:?}", code);
-                }
-                assert_eq!(sequence, 1);
-                assert_eq!(request_ack, Some(2));
-            }
-            _ => panic!("Unexpected Packet type on Response path: {:?}", pkt),
+        server.bans.ban(BanTarget::parse("1.2.3.4").unwrap());
+
+        // told once, then ignored
+        let responses = server.process_packet((connect_packet("some player"), fake_socket_addr()));
+        match responses.as_slice() {
+            [(
+                Packet::Response {
+                    code: ResponseCode::Unauthorized { .. },
+                    ..
+                },
+                addr,
+            )] => assert_eq!(*addr, fake_socket_addr()),
+            responses => panic!("Unexpected responses to a banned address: {:?}", responses),
         }
-        let player: &Player = server.get_player(player_id);
-        assert_eq!(player.next_resp_seq, 2);
-    }
+        let responses = server.process_packet((connect_packet("some player"), fake_socket_addr()));
+        assert_eq!(responses, vec![]);
+        assert!(server.players.is_empty());
 
-    #[test]
-    fn validate_client_version_client_is_up_to_date() {
-        assert_eq!(validate_client_version(env!("CARGO_PKG_VERSION").to_owned()), true);
+        // also when reaching a dual-stack socket over IPv4
+        let mapped_addr: SocketAddr = "[::ffff:1.2.3.4]:5678".parse().unwrap();
+        assert_eq!(
+            server.decode_packet(mapped_addr, connect_packet("some player")),
+            Err(ServerError::Banned { notify: false })
+        );
+
+        server.bans.response = BanResponse::Silent;
+        server.bans.ban(BanTarget::parse("some name").unwrap());
+        let other_addr: SocketAddr = "5.6.7.8:5678".parse().unwrap();
+        let responses = server.process_packet((connect_packet("some name"), other_addr));
+        assert_eq!(responses, vec![]);
+        assert!(server.players.is_empty());
     }
 
     #[test]
-    fn validate_client_version_client_is_very_old() {
-        assert_eq!(validate_client_version("0.0.1".to_owned()), true);
+    fn banning_connected_player_evicts_them() {
+        let (mut server, admin_id) = admin_server();
+        server.create_new_room(None, "some room".to_owned());
+        let (player_id, cookie) = {
+            let player = server.add_new_player("some player".to_owned(), fake_socket_addr());
+            (player.player_id, player.cookie.clone())
+        };
+        server.join_room(player_id, "some room");
+
+        let ban = AdminCommand::Ban {
+            target: "some player".to_owned(),
+        };
+        assert_eq!(admin(&mut server, admin_id, ban), ResponseCode::OK);
+        assert!(server.get_player(player_id).is_err());
+        assert_eq!(server.get_player_id_by_cookie(&cookie), None);
+        let room_id = server.room_map["some room"];
+        assert!(server.rooms[&room_id].player_ids.is_empty());
+        assert!(server.get_player(admin_id).is_ok());
+
+        // and they can't come back under the same name
+        let result = server.decode_packet(fake_socket_addr(), connect_packet("some player"));
+        assert_eq!(result, Err(ServerError::Banned { notify: true }));
     }
 
     #[test]
-    fn validate_client_version_client_is_from_the_future() {
+    fn unbanning_restores_access() {
+        let (mut server, admin_id) = admin_server();
+        let ban = AdminCommand::Ban {
+            target: "1.2.3.4".to_owned(),
+        };
+        assert_eq!(admin(&mut server, admin_id, ban), ResponseCode::OK);
+        assert!(server
+            .decode_packet(fake_socket_addr(), connect_packet("some player"))
+            .is_err());
         assert_eq!(
-            validate_client_version(
-                format!("{}.{}.{}", <i32>::max_value(), <i32>::max_value(), <i32>::max_value()).to_owned()
-            ),
-            false
+            admin(&mut server, admin_id, AdminCommand::ListBans),
+            ResponseCode::BanList {
+                addrs: vec!["1.2.3.4".to_owned()],
+                names: vec![],
+            }
         );
+
+        let unban = AdminCommand::Unban {
+            target: "1.2.3.4".to_owned(),
+        };
+        assert_eq!(admin(&mut server, admin_id, unban.clone()), ResponseCode::OK);
+        match server.decode_packet(fake_socket_addr(), connect_packet("some player")) {
+            Ok(Some(Packet::Response {
+                code: ResponseCode::LoggedIn { .. },
+                ..
+            })) => {}
+            result => panic!("Unexpected result of connecting after an unban: {:?}", result),
+        }
+
+        // unbanning again is a mistake
+        match admin(&mut server, admin_id, unban) {
+            ResponseCode::BadRequest { .. } => {}
+            code => panic!("Unexpected response to unbanning twice: {:?}", code),
+        }
     }
 
     #[test]
-    fn decode_packet_update_reply_good_case() {
-        let mut server = ServerState::new();
-        let cookie: String = {
-            let player: &mut Player = server.add_new_player("some player".to_owned(), fake_socket_addr());
-            player.cookie.clone()
+    fn admin_commands_need_the_token() {
+        let (mut server, admin_id) = admin_server();
+        let action = RequestAction::Admin {
+            token:   "open sesame".to_owned(),
+            command: AdminCommand::ListBans,
         };
+        match server.process_request_action(admin_id, action) {
+            Ok(ResponseCode::Unauthorized { .. }) => {}
+            result => panic!("Unexpected result with the wrong token: {:?}", result),
+        }
 
-        // TODO: Move this into a private helper
-        let update_reply_packet = Packet::UpdateReply {
-            cookie:               cookie,
-            last_chat_seq:        Some(0),
-            last_game_update_seq: None,
-            last_full_gen:        None,
-            partial_gen:          None,
-            pong:                 PingPong::pong(0),
+        // with no token configured, nothing is accepted
+        server.admin_token = None;
+        let action = RequestAction::Admin {
+            token:   "".to_owned(),
+            command: AdminCommand::ListBans,
         };
+        match server.process_request_action(admin_id, action) {
+            Ok(ResponseCode::Unauthorized { .. }) => {}
+            result => panic!("Unexpected result with no admin token: {:?}", result),
+        }
+    }
 
-        let result = server.decode_packet(fake_socket_addr(), update_reply_packet);
-        assert!(result.is_ok());
-        assert!(result.unwrap().is_none());
+    #[test]
+    fn admin_cannot_ban_themselves() {
+        let (mut server, admin_id) = admin_server();
+        for target in &["admin", "5.6.7.8", " "] {
+            let ban = AdminCommand::Ban {
+                target: target.to_string(),
+            };
+            match admin(&mut server, admin_id, ban) {
+                ResponseCode::BadRequest { .. } => {}
+                code => panic!("Unexpected response to banning {:?}: {:?}", target, code),
+            }
+        }
+        assert!(server.bans.addrs().is_empty() && server.bans.names().is_empty());
+        assert!(server.get_player(admin_id).is_ok());
     }
 
     #[test]
-    fn decode_packet_update_reply_invalid_cookie() {
+    fn process_packet_ignores_server_packets() {
         let mut server = ServerState::new();
-        {
-            let player: &mut Player = server.add_new_player("some player".to_owned(), fake_socket_addr());
-            player.cookie.clone()
+        let response_packet = Packet::Response {
+            sequence:    0,
+            request_ack: None,
+            code:        ResponseCode::OK,
         };
 
-        let cookie = "CookieMonster".to_owned();
+        let responses = server.process_packet((response_packet, fake_socket_addr()));
+        assert!(responses.is_empty());
+    }
 
-        let update_reply_packet = Packet::UpdateReply {
-            cookie:               cookie,
-            last_chat_seq:        Some(0),
-            last_game_update_seq: None,
-            last_full_gen:        None,
-            partial_gen:          None,
-            pong:                 PingPong::pong(0),
-        };
+    #[test]
+    fn server_error_response_codes() {
+        let unauthorized = ServerError::MissingCookie.response_code();
+        assert!(matches!(unauthorized, Some(ResponseCode::Unauthorized { .. })));
+
+        let bad_request = ServerError::NotImplemented {
+            action: RequestAction::SetClientOptions {
+                key:   "some key".to_owned(),
+                value: None,
+            },
+        }
+        .response_code();
+        assert!(matches!(bad_request, Some(ResponseCode::BadRequest { .. })));
 
-        let result = server.decode_packet(fake_socket_addr(), update_reply_packet);
-        assert!(result.is_err());
+        assert_eq!(
+            ServerError::PlayerNotFound { player_id: PlayerID(0) }.response_code(),
+            None
+        );
     }
 
     #[test]
@@ -2619,11 +6978,13 @@ mod netwayste_server_tests {
                 game_updates,
                 game_update_seq,
                 universe_update,
+                gen_checksum,
                 ping: _,
             } => {
                 assert!(game_updates.is_empty());
                 assert!(game_update_seq.is_none());
                 assert_eq!(universe_update, UniUpdate::NoChange);
+                assert!(gen_checksum.is_none());
                 assert!(!chats.is_empty());
 
                 // All client chat sequence numbers start counting at 1
@@ -2640,6 +7001,38 @@ mod netwayste_server_tests {
         }
     }
 
+    #[test]
+    fn chat_is_delivered_apart_from_ticks() {
+        let mut server = ServerState::new();
+        let room_name = "some_room";
+        server.create_new_room(None, room_name.to_owned());
+        let player_id: PlayerID = {
+            let player: &mut Player = server.add_new_player("some player".to_owned(), fake_socket_addr());
+            player.player_id
+        };
+        server.join_room(player_id, room_name);
+        server.handle_chat_message(player_id, "Message".to_owned());
+
+        // a tick only runs the games; chat has its own timer
+        let tick = server.tick;
+        assert!(server.garbage_collection().iter().all(|(_, pkt)| match pkt {
+            Packet::Update { chats, .. } => chats.is_empty(),
+            _ => true,
+        }));
+        assert_eq!(server.tick, tick + 1);
+
+        let updates = server.deliver_chat();
+        assert_eq!(updates.len(), 1);
+        match updates[0] {
+            (addr, Packet::Update { ref chats, .. }) => {
+                assert_eq!(addr, fake_socket_addr());
+                assert_eq!(chats.len(), 1);
+            }
+            _ => panic!("Unexpected packet in chat delivery!"),
+        }
+        assert_eq!(server.tick, tick + 1);
+    }
+
     #[test]
     fn construct_client_updates_populated_room_returns_updates_after_client_acked() {
         let mut server = ServerState::new();
@@ -2660,7 +7053,7 @@ mod netwayste_server_tests {
 
         // Assume that the client has acknowledged two chats
         {
-            let player: &mut Player = server.get_player_mut(player_id);
+            let player: &mut Player = server.get_player_mut(player_id).unwrap();
             player.update_chat_seq_num(Some(2));
         }
 
@@ -2679,11 +7072,13 @@ mod netwayste_server_tests {
                 game_updates,
                 game_update_seq,
                 universe_update,
+                gen_checksum,
                 ping: _,
             } => {
                 assert!(game_updates.is_empty());
                 assert!(game_update_seq.is_none());
                 assert_eq!(universe_update, UniUpdate::NoChange);
+                assert!(gen_checksum.is_none());
                 assert!(!chats.is_empty());
 
                 assert_eq!(chats.len(), 1);
@@ -2722,12 +7117,12 @@ mod netwayste_server_tests {
         server.join_room(player_id2, room_name);
         let room: &Room = server.get_room(player_id).unwrap();
 
-        let player = (*server.get_player(player_id)).clone();
+        let player = (*server.get_player(player_id).unwrap()).clone();
         let msgs = server.collect_unacknowledged_messages(room, &player).unwrap();
         assert_eq!(msgs.len(), 1);
         assert_eq!(msgs[0].message, "Silver birch against a Swedish sky".to_owned());
 
-        let player = (*server.get_player(player_id2)).clone();
+        let player = (*server.get_player(player_id2).unwrap()).clone();
         let msgs = server.collect_unacknowledged_messages(room, &player).unwrap();
         assert_eq!(msgs.len(), 1);
         assert_eq!(msgs[0].message, "Silver birch against a Swedish sky".to_owned());
@@ -2755,7 +7150,6 @@ mod netwayste_server_tests {
     }
 
     #[test]
-    #[should_panic]
     fn disconnect_get_player_by_id_fails() {
         let mut server = ServerState::new();
         let player_name = "some player".to_owned();
@@ -2765,8 +7159,11 @@ mod netwayste_server_tests {
             player.player_id
         };
 
-        server.handle_disconnect(player_id);
-        server.get_player(player_id);
+        server.handle_disconnect(player_id).unwrap();
+        assert_eq!(
+            server.get_player(player_id),
+            Err(ServerError::PlayerNotFound { player_id })
+        );
     }
 
     #[test]
@@ -2779,7 +7176,7 @@ mod netwayste_server_tests {
             (player.player_id, player.cookie.clone())
         };
 
-        server.handle_disconnect(player_id);
+        server.handle_disconnect(player_id).unwrap();
         assert_eq!(server.get_player_id_by_cookie(cookie.as_str()), None);
     }
 
@@ -2801,7 +7198,7 @@ mod netwayste_server_tests {
             assert_eq!(room.player_ids.contains(&player_id), true);
             room.room_id
         };
-        server.handle_disconnect(player_id);
+        server.handle_disconnect(player_id).unwrap();
         // Cannot go through player_id because the player has been removed
         let room: &Room = server.rooms.get(&room_id).unwrap();
         assert_eq!(room.player_ids.contains(&player_id), false);
@@ -2834,6 +7231,7 @@ mod netwayste_server_tests {
     #[test]
     fn test_clear_transmission_queue_on_ack() {
         let mut server = ServerState::new();
+        let now = Instant::now();
         let player_name = "some player".to_owned();
 
         let player_id: PlayerID = {
@@ -2850,7 +7248,7 @@ mod netwayste_server_tests {
             };
 
             let nm: &mut NetworkManager = server.network_map.get_mut(&player_id).unwrap();
-            nm.tx_packets.buffer_item(pkt.clone());
+            nm.tx_packets.buffer_item(pkt.clone(), now);
         }
 
         server.clear_transmission_queue_on_ack(player_id, None);
@@ -2866,6 +7264,8 @@ mod netwayste_server_tests {
     #[test]
     fn test_resend_expired_tx_packets() {
         let mut server = ServerState::new();
+        let now = Instant::now();
+        let later = now + RETRANSMISSION_THRESHOLD_IN_MS;
         let player_name = "some player".to_owned();
 
         let player_id: PlayerID = {
@@ -2882,15 +7282,12 @@ mod netwayste_server_tests {
             };
 
             let nm: &mut NetworkManager = server.network_map.get_mut(&player_id).unwrap();
-            nm.tx_packets.buffer_item(pkt.clone());
-
-            if i < 3 {
-                let attempt: &mut NetAttempt = nm.tx_packets.attempts.back_mut().unwrap();
-                attempt.time = Instant::now() - Duration::from_secs(i + 1);
-            }
+            // the last two are sent a while after the others
+            let sent_at = if i < 3 { now } else { later };
+            nm.tx_packets.buffer_item(pkt.clone(), sent_at);
         }
 
-        let _expired_packets_vec = server.collect_expired_tx_packets();
+        let _expired_packets_vec = server.collect_expired_tx_packets(later);
 
         for i in 0..5 {
             let nm: &mut NetworkManager = server.network_map.get_mut(&player_id).unwrap();
@@ -2907,6 +7304,7 @@ mod netwayste_server_tests {
     #[test]
     fn test_process_queued_rx_packets_first_non_connect_player_packet() {
         let mut server = ServerState::new();
+        let now = Instant::now();
         let player_name = "some player".to_owned();
 
         let (player_id, player_cookie): (PlayerID, String) = {
@@ -2924,7 +7322,7 @@ mod netwayste_server_tests {
             };
 
             let nm: &mut NetworkManager = server.network_map.get_mut(&player_id).unwrap();
-            nm.rx_packets.buffer_item(pkt.clone());
+            nm.rx_packets.buffer_item(pkt.clone(), now);
 
             assert_eq!(nm.tx_packets.len(), 0);
         }
@@ -2940,6 +7338,7 @@ mod netwayste_server_tests {
     #[test]
     fn test_process_queued_rx_packets_contiguous() {
         let mut server = ServerState::new();
+        let now = Instant::now();
         let player_name = "some player".to_owned();
 
         let (player_id, player_cookie): (PlayerID, String) = {
@@ -2957,7 +7356,7 @@ mod netwayste_server_tests {
             };
 
             let nm: &mut NetworkManager = server.network_map.get_mut(&player_id).unwrap();
-            nm.rx_packets.buffer_item(pkt.clone());
+            nm.rx_packets.buffer_item(pkt.clone(), now);
 
             assert_eq!(nm.tx_packets.len(), 0);
         }
@@ -2973,6 +7372,7 @@ mod netwayste_server_tests {
     #[test]
     fn test_process_queued_rx_packets_swiss_cheese_queue() {
         let mut server = ServerState::new();
+        let now = Instant::now();
         let player_name = "some player".to_owned();
 
         let (player_id, player_cookie): (PlayerID, String) = {
@@ -2990,7 +7390,7 @@ mod netwayste_server_tests {
             };
 
             let nm: &mut NetworkManager = server.network_map.get_mut(&player_id).unwrap();
-            nm.rx_packets.buffer_item(pkt.clone());
+            nm.rx_packets.buffer_item(pkt.clone(), now);
 
             assert_eq!(nm.tx_packets.len(), 0);
         }
@@ -3002,4 +7402,547 @@ mod netwayste_server_tests {
             assert_eq!(nm.tx_packets.len(), 3); // only 2, 3, and 4 are processed
         }
     }
+
+    #[test]
+    fn test_netsim_commands() {
+        let mut config = FaultConfig::default();
+        apply_netsim_command(&mut config, &["drop", "0.25"]).unwrap();
+        apply_netsim_command(&mut config, &["dup", "0.5"]).unwrap();
+        apply_netsim_command(&mut config, &["delay", "100", "50"]).unwrap();
+        assert_eq!(
+            config,
+            FaultConfig {
+                drop_rate:      0.25,
+                duplicate_rate: 0.5,
+                min_delay:      Duration::from_millis(100),
+                max_delay:      Duration::from_millis(150),
+            }
+        );
+        apply_netsim_command(&mut config, &["delay", "20"]).unwrap();
+        assert_eq!(
+            (config.min_delay, config.max_delay),
+            (Duration::from_millis(20), Duration::from_millis(20))
+        );
+
+        // bad commands change nothing
+        let before = config.clone();
+        assert!(apply_netsim_command(&mut config, &["drop", "1.5"]).is_err());
+        assert!(apply_netsim_command(&mut config, &["dup", "lots"]).is_err());
+        assert!(apply_netsim_command(&mut config, &["delay", "-3"]).is_err());
+        assert!(apply_netsim_command(&mut config, &["jitter", "3"]).is_err());
+        apply_netsim_command(&mut config, &[]).unwrap();
+        assert_eq!(config, before);
+
+        apply_netsim_command(&mut config, &["off"]).unwrap();
+        assert_eq!(config, FaultConfig::default());
+    }
+
+    #[test]
+    fn test_netsim_applies_to_both_directions() {
+        let mut netsim = NetSim::new(7);
+        let mut config = FaultConfig::default();
+        config.drop_rate = 1.0;
+        netsim.set_config(config);
+        let packet = Packet::Request {
+            cookie:       None,
+            sequence:     1,
+            response_ack: None,
+            action:       RequestAction::ListPlayers,
+        };
+        let now = Instant::now();
+        for _ in 0..10 {
+            netsim.inbound.push(fake_socket_addr(), packet.clone(), now);
+            netsim.outbound.push(fake_socket_addr(), packet.clone(), now);
+        }
+        assert!(netsim.inbound.drain_ready(now).is_empty());
+        assert!(netsim.outbound.drain_ready(now).is_empty());
+
+        netsim.set_config(FaultConfig::default());
+        netsim.outbound.push(fake_socket_addr(), packet, now);
+        assert_eq!(netsim.outbound.drain_ready(now).len(), 1);
+    }
+
+    #[test]
+    fn busy_tick_needs_a_datagram_per_player() {
+        // Two full rooms, with a game running in each and everyone chatting
+        let mut server = ServerState::new();
+        let mut player_ids = vec![];
+        for room in 0..2 {
+            let room_name = format!("room {}", room);
+            server.create_new_room(None, room_name.clone());
+            for i in 0..MAX_ROOM_PLAYERS {
+                let port = (room * MAX_ROOM_PLAYERS + i + 1) as u16;
+                let addr = SocketAddr::new(fake_socket_addr().ip(), port);
+                let player_id = server.add_new_player(format!("player {}", port), addr).player_id;
+                server.join_room(player_id, &room_name);
+                player_ids.push(player_id);
+            }
+            server
+                .get_room_mut(player_ids[player_ids.len() - 1])
+                .unwrap()
+                .start_game()
+                .unwrap();
+        }
+        assert_eq!(player_ids.len(), 16);
+        for &player_id in &player_ids {
+            server.handle_chat_message(player_id, "good luck, have fun".to_owned());
+        }
+
+        // Everything sent in one tick: universe updates, chat, and heartbeats
+        let mut packets = server.garbage_collection();
+        packets.extend(server.deliver_chat());
+        packets.extend(
+            server
+                .send_heartbeats()
+                .into_iter()
+                .map(|(packet, addr)| (addr, packet)),
+        );
+        assert!(packets.len() >= 3 * 16);
+
+        // Without bundling, every packet is a datagram of its own
+        let mut outbox = Outbox::new();
+        for (addr, packet) in packets.iter().cloned() {
+            outbox.push(addr, packet);
+        }
+        let mut unbundled = 0;
+        outbox.flush(
+            |_, _| 1,
+            |_, _| {
+                unbundled += 1;
+                Ok(())
+            },
+        );
+        assert_eq!(unbundled, packets.len());
+
+        // With it, each player's packets fit in one datagram, and come out in the order they went in
+        let mut expected: HashMap<SocketAddr, Vec<String>> = HashMap::new();
+        for (addr, packet) in packets.iter().cloned() {
+            expected.entry(addr).or_default().push(format!("{:?}", packet));
+            outbox.push(addr, packet);
+        }
+        let mut received: HashMap<SocketAddr, Vec<String>> = HashMap::new();
+        let mut bundled = 0;
+        let sent = outbox.flush(
+            |_, queue| bundle_len(PROTOCOL_VERSION, queue.iter()),
+            |addr, packets| {
+                let datagram = encode_packets(PROTOCOL_VERSION, packets)?;
+                assert!(datagram.len() <= UDP_MTU_SIZE);
+                let (decoded, _) = decode_datagram(&datagram).unwrap();
+                let received = received.entry(addr).or_default();
+                received.extend(decoded.iter().map(|packet| format!("{:?}", packet)));
+                bundled += 1;
+                Ok(())
+            },
+        );
+        assert_eq!(sent, packets.len());
+        assert_eq!(bundled, 16);
+        assert_eq!(received, expected);
+    }
+
+    #[tokio::test]
+    async fn serve_answers_connect() {
+        let udp = bind(Some("127.0.0.1"), Some(0)).await.unwrap();
+        let server_addr = udp.local_addr().unwrap();
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let mut client = UdpFramed::new(client, NetwaystePacketCodec);
+
+        let round_trip = async {
+            let connect = Packet::Request {
+                sequence:     0,
+                response_ack: None,
+                cookie:       None,
+                action:       RequestAction::Connect {
+                    name:           "some player".to_owned(),
+                    client_version: VERSION.to_owned(),
+                },
+            };
+            client.send((connect, server_addr)).await.unwrap();
+            client.next().await.unwrap().unwrap()
+        };
+        let (mut packets, addr) = TokioTime::timeout(Duration::from_secs(5), async {
+            tokio::select! {
+                _ = serve(ServerState::new(), udp, TICK_INTERVAL_IN_MS, None, Fut::stream::pending()) => {
+                    unreachable!("the server stopped")
+                }
+                reply = round_trip => reply,
+            }
+        })
+        .await
+        .expect("no reply from the server");
+
+        assert_eq!(addr, server_addr);
+        assert_eq!(packets.len(), 1);
+        let packet = packets.remove(0);
+        match packet {
+            Packet::Response {
+                sequence: 0,
+                code: ResponseCode::LoggedIn { server_version, .. },
+                ..
+            } => assert_eq!(server_version, VERSION),
+            _ => panic!("Unexpected reply: {:?}", packet),
+        }
+    }
+
+    #[tokio::test]
+    async fn serve_answers_unsupported_versions() {
+        let udp = bind(Some("127.0.0.1"), Some(0)).await.unwrap();
+        let server_addr = udp.local_addr().unwrap();
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+
+        let exchange = async {
+            let mut datagram = PROTOCOL_MAGIC.to_vec();
+            datagram.push(PROTOCOL_VERSION + 1);
+            datagram.extend_from_slice(b"from the future");
+            client.send_to(&datagram, server_addr).await.unwrap();
+            let mut buf = vec![0u8; UDP_MTU_SIZE];
+            let (len, addr) = client.recv_from(&mut buf).await.unwrap();
+            buf.truncate(len);
+            (buf, addr)
+        };
+        let (reply, addr) = TokioTime::timeout(Duration::from_secs(5), async {
+            tokio::select! {
+                _ = serve(ServerState::new(), udp, TICK_INTERVAL_IN_MS, None, Fut::stream::pending()) => {
+                    unreachable!("the server stopped")
+                }
+                reply = exchange => reply,
+            }
+        })
+        .await
+        .expect("no reply from the server");
+
+        assert_eq!(addr, server_addr);
+        assert!(!reply.starts_with(&PROTOCOL_MAGIC));
+        let (mut packets, _) = decode_datagram(&reply).unwrap();
+        match packets.remove(0) {
+            Packet::Response {
+                code: ResponseCode::BadRequest { error_msg },
+                ..
+            } => assert!(error_msg.contains("not supported")),
+            packet => panic!("Unexpected reply: {:?}", packet),
+        }
+    }
+
+    #[tokio::test]
+    async fn serve_answers_previous_version_clients_in_their_version() {
+        let udp = bind(Some("127.0.0.1"), Some(0)).await.unwrap();
+        let server_addr = udp.local_addr().unwrap();
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+
+        let exchange = async {
+            let connect = Packet::Request {
+                sequence:     0,
+                response_ack: None,
+                cookie:       None,
+                action:       RequestAction::Connect {
+                    name:           "old player".to_owned(),
+                    client_version: VERSION.to_owned(),
+                },
+            };
+            let datagram = encode_datagram(MIN_PROTOCOL_VERSION, &connect).unwrap();
+            client.send_to(&datagram, server_addr).await.unwrap();
+            let mut buf = vec![0u8; UDP_MTU_SIZE];
+            let (len, _) = client.recv_from(&mut buf).await.unwrap();
+            buf.truncate(len);
+            buf
+        };
+        let reply = TokioTime::timeout(Duration::from_secs(5), async {
+            tokio::select! {
+                _ = serve(ServerState::new(), udp, TICK_INTERVAL_IN_MS, None, Fut::stream::pending()) => {
+                    unreachable!("the server stopped")
+                }
+                reply = exchange => reply,
+            }
+        })
+        .await
+        .expect("no reply from the server");
+
+        assert_eq!(datagram_version(&reply), Some(MIN_PROTOCOL_VERSION));
+        let (mut packets, _) = decode_datagram(&reply).unwrap();
+        assert_eq!(packets.len(), 1);
+        match packets.remove(0) {
+            Packet::Response {
+                sequence: 0,
+                code: ResponseCode::LoggedIn { server_version, .. },
+                ..
+            } => assert_eq!(server_version, VERSION),
+            packet => panic!("Unexpected reply: {:?}", packet),
+        }
+    }
+
+    #[test]
+    fn peer_versions_are_remembered_until_the_peer_goes_quiet() {
+        let mut server = ServerState::new();
+        let now = Instant::now();
+        let addr = fake_socket_addr();
+        assert_eq!(server.peer_version(addr), PROTOCOL_VERSION);
+
+        server.note_version(addr, MIN_PROTOCOL_VERSION, now);
+        assert_eq!(server.peer_version(addr), MIN_PROTOCOL_VERSION);
+        server.expire_old_peers(now + server.timeout - Duration::from_millis(1));
+        assert_eq!(server.peer_version(addr), MIN_PROTOCOL_VERSION);
+        server.expire_old_peers(now + server.timeout);
+        assert_eq!(server.peer_version(addr), PROTOCOL_VERSION);
+
+        // an upgraded client is answered in the new version right away
+        server.note_version(addr, MIN_PROTOCOL_VERSION, now);
+        server.note_version(addr, PROTOCOL_VERSION, now);
+        assert_eq!(server.peer_version(addr), PROTOCOL_VERSION);
+        assert!(server.old_peers.is_empty());
+    }
+
+    #[tokio::test]
+    async fn serve_handles_five_bots() {
+        let udp = bind(Some("127.0.0.1"), Some(0)).await.unwrap();
+        let config = BotConfig {
+            server:        udp.local_addr().unwrap().to_string(),
+            bots:          5,
+            duration:      Duration::from_secs(3),
+            chat_interval: Duration::from_millis(500),
+            list_interval: Duration::from_millis(300),
+            room_size:     3,
+            timeout:       Duration::from_secs(2),
+        };
+        let report = TokioTime::timeout(Duration::from_secs(10), async {
+            tokio::select! {
+                _ = serve(ServerState::new(), udp, TICK_INTERVAL_IN_MS, None, Fut::stream::pending()) => {
+                    unreachable!("the server stopped")
+                }
+                report = run_bots(config) => report,
+            }
+        })
+        .await
+        .expect("the bots never finished");
+
+        assert_eq!(report.connected, 5);
+        assert_eq!(report.timeouts, 0);
+        assert!(report.chats_sent > 0);
+        // every bot logged in, joined a room, and listed its players at least once
+        assert!(report.rtts.len() >= 15);
+    }
+
+    /// A client that talks to a `ServerState` the way one would over the network. Every packet is
+    /// encoded and decoded in both directions, so that the wire format is exercised too.
+    struct FakeClient {
+        addr:         SocketAddr,
+        cookie:       Option<String>, // set once logged in
+        sequence:     u64,            // of the next request
+        response_ack: Option<u64>,    // sequence of the latest response
+    }
+
+    impl FakeClient {
+        fn new(addr: &str) -> Self {
+            FakeClient {
+                addr:         addr.parse().unwrap(),
+                cookie:       None,
+                sequence:     0,
+                response_ack: None,
+            }
+        }
+
+        fn connect(&mut self, server: &mut ServerState, name: &str) -> Option<ResponseCode> {
+            let action = RequestAction::Connect {
+                name:           name.to_owned(),
+                client_version: VERSION.to_owned(),
+            };
+            self.send(server, action)
+        }
+
+        /// Sends `action` in the next request, returning what the server answered with, if anything.
+        fn send(&mut self, server: &mut ServerState, action: RequestAction) -> Option<ResponseCode> {
+            let request = Packet::Request {
+                sequence: self.sequence,
+                response_ack: self.response_ack,
+                cookie: self.cookie.clone(),
+                action,
+            };
+            self.sequence += 1;
+            let responses = server.process_packet((over_the_wire(&request), self.addr));
+            let response = match responses.as_slice() {
+                [] => return None,
+                [(response, addr)] => {
+                    assert_eq!(*addr, self.addr);
+                    over_the_wire(response)
+                }
+                responses => panic!("Expected at most one response, not {:?}", responses),
+            };
+            match response {
+                Packet::Response { sequence, code, .. } => {
+                    self.response_ack = Some(sequence);
+                    if let ResponseCode::LoggedIn { ref cookie, .. } = code {
+                        self.cookie = Some(cookie.clone());
+                    }
+                    Some(code)
+                }
+                response => panic!("Unexpected response to {:?}: {:?}", request, response),
+            }
+        }
+
+        fn player_id(&self, server: &ServerState) -> Option<PlayerID> {
+            server.get_player_id_by_cookie(self.cookie.as_ref()?)
+        }
+    }
+
+    /// What `packet` looks like after being sent as a datagram.
+    fn over_the_wire(packet: &Packet) -> Packet {
+        let datagram = encode_datagram(PROTOCOL_VERSION, packet).unwrap();
+        let (mut packets, len) = decode_datagram(&datagram).unwrap();
+        assert_eq!(len, datagram.len());
+        assert_eq!(packets.len(), 1);
+        packets.remove(0)
+    }
+
+    fn room_members(server: &ServerState, room_name: &str) -> Vec<PlayerID> {
+        server.rooms[&server.room_map[room_name]].player_ids.clone()
+    }
+
+    fn join_room(room_name: &str) -> RequestAction {
+        RequestAction::JoinRoom {
+            room_name: room_name.to_owned(),
+            password:  None,
+        }
+    }
+
+    fn chat(message: &str) -> RequestAction {
+        RequestAction::ChatMessage {
+            message: message.to_owned(),
+        }
+    }
+
+    #[test]
+    fn lobby_connect_join_chat_and_disconnect() {
+        let mut server = ServerState::new();
+        let mut alice = FakeClient::new("10.0.0.1:5000");
+        let mut bob = FakeClient::new("10.0.0.2:5000");
+
+        match alice.connect(&mut server, "alice") {
+            Some(ResponseCode::LoggedIn { .. }) => {}
+            code => panic!("Unexpected response to connecting: {:?}", code),
+        }
+        match bob.connect(&mut server, "bob") {
+            Some(ResponseCode::LoggedIn { .. }) => {}
+            code => panic!("Unexpected response to connecting: {:?}", code),
+        }
+        assert_eq!(server.players.len(), 2);
+        let (alice_id, bob_id) = (alice.player_id(&server).unwrap(), bob.player_id(&server).unwrap());
+
+        let new_room = RequestAction::NewRoom {
+            room_name:   "some room".to_owned(),
+            password:    None,
+            team_count:  None,
+            max_players: None,
+        };
+        assert_eq!(alice.send(&mut server, new_room), Some(ResponseCode::OK));
+        match alice.send(&mut server, join_room("some room")) {
+            Some(ResponseCode::JoinedRoom { .. }) => {}
+            code => panic!("Unexpected response to joining: {:?}", code),
+        }
+        match bob.send(&mut server, join_room("some room")) {
+            Some(ResponseCode::JoinedRoom { .. }) => {}
+            code => panic!("Unexpected response to joining: {:?}", code),
+        }
+        assert_eq!(room_members(&server, "some room"), vec![alice_id, bob_id]);
+
+        assert_eq!(alice.send(&mut server, chat("hi bob")), Some(ResponseCode::OK));
+        assert_eq!(bob.send(&mut server, chat("hi alice")), Some(ResponseCode::OK));
+        let room_id = server.room_map["some room"];
+        let messages: Vec<&str> = server.rooms[&room_id]
+            .messages
+            .iter()
+            .map(|m| m.message.as_str())
+            .collect();
+        assert_eq!(messages, vec!["hi bob", "hi alice"]);
+
+        // leaving is acknowledged, and bob is told
+        assert_eq!(
+            alice.send(&mut server, RequestAction::Disconnect),
+            Some(ResponseCode::OK)
+        );
+        assert_eq!(server.players.len(), 1);
+        assert_eq!(alice.player_id(&server), None);
+        assert!(!server.network_map.contains_key(&alice_id));
+        assert_eq!(room_members(&server, "some room"), vec![bob_id]);
+        let last_message = server.rooms[&room_id].messages.back().unwrap();
+        assert_eq!(last_message.message, "Player alice has left.");
+
+        // and after that, alice's cookie gets her nowhere
+        match alice.send(&mut server, RequestAction::ListRooms) {
+            Some(ResponseCode::Unauthorized { .. }) => {}
+            code => panic!("Unexpected response to a forgotten cookie: {:?}", code),
+        }
+    }
+
+    #[test]
+    fn repeated_requests_are_answered_again_but_not_redone() {
+        let mut server = ServerState::new();
+        let mut alice = FakeClient::new("10.0.0.1:5000");
+        alice.connect(&mut server, "alice");
+        match alice.send(&mut server, join_room("general")) {
+            Some(ResponseCode::JoinedRoom { .. }) => {}
+            code => panic!("Unexpected response to joining: {:?}", code),
+        }
+        let joined_ack = alice.response_ack;
+        assert_eq!(alice.send(&mut server, chat("hello")), Some(ResponseCode::OK));
+        let chat_ack = alice.response_ack;
+
+        // the response to the chat message is lost, so the client sends it again
+        alice.sequence -= 1;
+        alice.response_ack = joined_ack;
+        assert_eq!(alice.send(&mut server, chat("hello")), Some(ResponseCode::OK));
+        assert_eq!(alice.response_ack, chat_ack);
+        let room_id = server.room_map["general"];
+        assert_eq!(server.rooms[&room_id].messages.len(), 1);
+
+        // once the client has acknowledged the response, a repeat of the request goes unanswered
+        match alice.send(&mut server, RequestAction::ListRooms) {
+            Some(ResponseCode::RoomList { .. }) => {}
+            code => panic!("Unexpected response to listing rooms: {:?}", code),
+        }
+        let chat_sequence = alice.sequence - 2;
+        let (sequence, response_ack) = (alice.sequence, alice.response_ack);
+        alice.sequence = chat_sequence;
+        assert_eq!(alice.send(&mut server, chat("hello")), None);
+        assert_eq!(server.rooms[&room_id].messages.len(), 1);
+
+        // and requests after a gap wait for the missing ones
+        alice.sequence = sequence + 1;
+        alice.response_ack = response_ack;
+        assert_eq!(alice.send(&mut server, chat("second")), None);
+        assert_eq!(server.rooms[&room_id].messages.len(), 1);
+    }
+
+    #[test]
+    fn lobby_refusals() {
+        let mut server = ServerState::new();
+        let mut alice = FakeClient::new("10.0.0.1:5000");
+        let mut impostor = FakeClient::new("10.0.0.2:5000");
+        alice.connect(&mut server, "alice").unwrap();
+
+        match impostor.connect(&mut server, "alice") {
+            Some(ResponseCode::Unauthorized { .. }) => {}
+            code => panic!("Unexpected response to a name that is taken: {:?}", code),
+        }
+        assert_eq!(impostor.cookie, None);
+        assert_eq!(server.players.len(), 1);
+
+        // without a cookie, requests are refused
+        match impostor.send(&mut server, RequestAction::ListRooms) {
+            Some(ResponseCode::Unauthorized { .. }) => {}
+            code => panic!("Unexpected response without a cookie: {:?}", code),
+        }
+
+        server.lobby_rate = 0;
+        match alice.send(&mut server, chat("anyone here?")) {
+            Some(ResponseCode::BadRequest { .. }) => {}
+            code => panic!("Unexpected response to chatting with lobby chat off: {:?}", code),
+        }
+        match alice.send(&mut server, join_room("no such room")) {
+            Some(ResponseCode::BadRequest { .. }) => {}
+            code => panic!("Unexpected response to joining a missing room: {:?}", code),
+        }
+        match alice.send(&mut server, RequestAction::LeaveRoom) {
+            Some(ResponseCode::BadRequest { .. }) => {}
+            code => panic!("Unexpected response to leaving no room: {:?}", code),
+        }
+        assert_eq!(server.players.len(), 1);
+    }
 }
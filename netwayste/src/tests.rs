@@ -17,14 +17,21 @@
 extern crate tokio_test;
 
 use crate::net::*;
+use crate::utils::PingPong;
+use bincode::serialize;
 use std::net::SocketAddr;
-use std::{
-    thread,
-    time::{Duration, Instant},
-};
+use std::time::{Duration, Instant};
 
 mod netwayste_net_tests {
     use super::*;
+    use bytes::BytesMut;
+    use conway::rle::Pattern;
+    use conway::universe::CellState;
+    use conway::universe::GenStateDiff;
+    use proptest::prelude::*;
+    use proptest::{collection, option};
+    use std::net::Ipv4Addr;
+    use tokio_util::codec::{Decoder, Encoder};
 
     fn fake_socket_addr() -> SocketAddr {
         use std::net::{IpAddr, Ipv4Addr};
@@ -124,6 +131,7 @@ mod netwayste_net_tests {
     #[test]
     fn test_buffer_item_queue_is_empty() {
         let mut nm = NetworkManager::new();
+        let now = Instant::now();
         let pkt = Packet::Request {
             sequence:     0,
             response_ack: None,
@@ -131,13 +139,14 @@ mod netwayste_net_tests {
             action:       RequestAction::None,
         };
 
-        nm.tx_packets.buffer_item(pkt);
+        nm.tx_packets.buffer_item(pkt, now);
         assert_eq!(nm.tx_packets.len(), 1);
     }
 
     #[test]
     fn test_buffer_item_sequence_number_reused() {
         let mut nm = NetworkManager::new();
+        let now = Instant::now();
         let pkt = Packet::Request {
             sequence:     0,
             response_ack: None,
@@ -145,7 +154,7 @@ mod netwayste_net_tests {
             action:       RequestAction::None,
         };
 
-        nm.tx_packets.buffer_item(pkt);
+        nm.tx_packets.buffer_item(pkt, now);
         let pkt = Packet::Request {
             sequence:     0,
             response_ack: None,
@@ -153,7 +162,7 @@ mod netwayste_net_tests {
             action:       RequestAction::LeaveRoom,
         };
 
-        nm.tx_packets.buffer_item(pkt);
+        nm.tx_packets.buffer_item(pkt, now);
         let pkt = nm.tx_packets.queue.back().unwrap();
         if let Packet::Request {
             sequence: _,
@@ -169,6 +178,7 @@ mod netwayste_net_tests {
     #[test]
     fn test_buffer_item_basic_sequencing() {
         let mut nm = NetworkManager::new();
+        let now = Instant::now();
         let pkt = Packet::Request {
             sequence:     0,
             response_ack: None,
@@ -176,20 +186,21 @@ mod netwayste_net_tests {
             action:       RequestAction::None,
         };
 
-        nm.tx_packets.buffer_item(pkt);
+        nm.tx_packets.buffer_item(pkt, now);
         let pkt = Packet::Request {
             sequence:     1,
             response_ack: None,
             cookie:       None,
             action:       RequestAction::LeaveRoom,
         };
-        nm.tx_packets.buffer_item(pkt);
+        nm.tx_packets.buffer_item(pkt, now);
         assert_eq!(nm.tx_packets.len(), 2);
     }
 
     #[test]
     fn test_buffer_item_newer_packet_has_smaller_sequence_number() {
         let mut nm = NetworkManager::new();
+        let now = Instant::now();
         let pkt = Packet::Request {
             sequence:     1,
             response_ack: None,
@@ -197,14 +208,14 @@ mod netwayste_net_tests {
             action:       RequestAction::None,
         };
 
-        nm.tx_packets.buffer_item(pkt);
+        nm.tx_packets.buffer_item(pkt, now);
         let pkt = Packet::Request {
             sequence:     0,
             response_ack: None,
             cookie:       None,
             action:       RequestAction::LeaveRoom,
         };
-        nm.tx_packets.buffer_item(pkt);
+        nm.tx_packets.buffer_item(pkt, now);
         assert_eq!(nm.tx_packets.len(), 2);
 
         let pkt = nm.tx_packets.queue.back().unwrap();
@@ -224,6 +235,7 @@ mod netwayste_net_tests {
     #[ignore]
     fn test_buffer_item_max_queue_limit_maintained() {
         let mut nm = NetworkManager::new();
+        let now = Instant::now();
         for index in 0..NETWORK_QUEUE_LENGTH + 5 {
             let pkt = Packet::Request {
                 sequence:     index as u64,
@@ -231,7 +243,7 @@ mod netwayste_net_tests {
                 cookie:       None,
                 action:       RequestAction::None,
             };
-            nm.tx_packets.buffer_item(pkt);
+            nm.tx_packets.buffer_item(pkt, now);
         }
 
         let mut iter = nm.tx_packets.queue.iter();
@@ -252,6 +264,7 @@ mod netwayste_net_tests {
     #[test]
     fn test_buffer_item_basic_contiguous_ascending() {
         let mut nm = NetworkManager::new();
+        let now = Instant::now();
         for index in 0..5 {
             let pkt = Packet::Request {
                 sequence:     index as u64,
@@ -259,7 +272,7 @@ mod netwayste_net_tests {
                 cookie:       None,
                 action:       RequestAction::None,
             };
-            nm.rx_packets.buffer_item(pkt);
+            nm.rx_packets.buffer_item(pkt, now);
         }
 
         let mut iter = nm.rx_packets.queue.iter();
@@ -272,6 +285,7 @@ mod netwayste_net_tests {
     #[test]
     fn test_buffer_item_basic_contiguous_descending() {
         let mut nm = NetworkManager::new();
+        let now = Instant::now();
         for index in (0..5).rev() {
             let pkt = Packet::Request {
                 sequence:     index as u64,
@@ -279,7 +293,7 @@ mod netwayste_net_tests {
                 cookie:       None,
                 action:       RequestAction::None,
             };
-            nm.rx_packets.buffer_item(pkt);
+            nm.rx_packets.buffer_item(pkt, now);
         }
 
         let mut iter = nm.rx_packets.queue.iter();
@@ -292,6 +306,7 @@ mod netwayste_net_tests {
     #[test]
     fn test_buffer_item_basic_sequential_gap_ascending() {
         let mut nm = NetworkManager::new();
+        let now = Instant::now();
         // TODO Replace with (x,y).step_by(z) once stable
         for index in [0, 2, 4, 6, 8, 10].iter() {
             let pkt = Packet::Request {
@@ -300,7 +315,7 @@ mod netwayste_net_tests {
                 cookie:       None,
                 action:       RequestAction::None,
             };
-            nm.rx_packets.buffer_item(pkt);
+            nm.rx_packets.buffer_item(pkt, now);
         }
 
         let mut iter = nm.rx_packets.queue.iter();
@@ -313,6 +328,7 @@ mod netwayste_net_tests {
     #[test]
     fn test_buffer_item_basic_sequential_gap_descending() {
         let mut nm = NetworkManager::new();
+        let now = Instant::now();
         for index in [0, 2, 4, 6, 8, 10].iter().rev() {
             let pkt = Packet::Request {
                 sequence:     *index as u64,
@@ -320,7 +336,7 @@ mod netwayste_net_tests {
                 cookie:       None,
                 action:       RequestAction::None,
             };
-            nm.rx_packets.buffer_item(pkt);
+            nm.rx_packets.buffer_item(pkt, now);
         }
 
         let mut iter = nm.rx_packets.queue.iter();
@@ -333,6 +349,7 @@ mod netwayste_net_tests {
     #[test]
     fn test_buffer_item_basic_random() {
         let mut nm = NetworkManager::new();
+        let now = Instant::now();
         for index in [5, 2, 9, 1, 0, 8, 6].iter() {
             let pkt = Packet::Request {
                 sequence:     *index as u64,
@@ -340,7 +357,7 @@ mod netwayste_net_tests {
                 cookie:       None,
                 action:       RequestAction::None,
             };
-            nm.rx_packets.buffer_item(pkt);
+            nm.rx_packets.buffer_item(pkt, now);
         }
 
         let mut iter = nm.rx_packets.queue.iter();
@@ -353,6 +370,7 @@ mod netwayste_net_tests {
     #[test]
     fn test_buffer_item_butterfly_pattern() {
         let mut nm = NetworkManager::new();
+        let now = Instant::now();
         // This one is fun because it tests the internal edges of (front_slice and back_slice)
         for index in [0, 10, 1, 9, 2, 8, 3, 7, 4, 6, 5].iter() {
             let pkt = Packet::Request {
@@ -361,7 +379,7 @@ mod netwayste_net_tests {
                 cookie:       None,
                 action:       RequestAction::None,
             };
-            nm.rx_packets.buffer_item(pkt);
+            nm.rx_packets.buffer_item(pkt, now);
         }
 
         let mut iter = nm.rx_packets.queue.iter();
@@ -374,6 +392,7 @@ mod netwayste_net_tests {
     #[test]
     fn test_buffer_item_basic_repetition() {
         let mut nm = NetworkManager::new();
+        let now = Instant::now();
         for index in [0, 0, 0, 0, 1, 2, 2, 2, 5].iter() {
             let pkt = Packet::Request {
                 sequence:     *index as u64,
@@ -381,7 +400,7 @@ mod netwayste_net_tests {
                 cookie:       None,
                 action:       RequestAction::None,
             };
-            nm.rx_packets.buffer_item(pkt);
+            nm.rx_packets.buffer_item(pkt, now);
         }
 
         let mut iter = nm.rx_packets.queue.iter();
@@ -394,6 +413,7 @@ mod netwayste_net_tests {
     #[test]
     fn test_buffer_item_advanced_sequential_then_pseudorandom_then_sequential() {
         let mut nm = NetworkManager::new();
+        let now = Instant::now();
 
         for index in 0..5 {
             let pkt = Packet::Request {
@@ -402,7 +422,7 @@ mod netwayste_net_tests {
                 cookie:       None,
                 action:       RequestAction::None,
             };
-            nm.rx_packets.buffer_item(pkt);
+            nm.rx_packets.buffer_item(pkt, now);
         }
 
         for index in [10, 7, 11, 9, 12, 8, 99, 6].iter() {
@@ -412,7 +432,7 @@ mod netwayste_net_tests {
                 cookie:       None,
                 action:       RequestAction::None,
             };
-            nm.rx_packets.buffer_item(pkt);
+            nm.rx_packets.buffer_item(pkt, now);
         }
 
         for index in 13..20 {
@@ -422,7 +442,7 @@ mod netwayste_net_tests {
                 cookie:       None,
                 action:       RequestAction::None,
             };
-            nm.rx_packets.buffer_item(pkt);
+            nm.rx_packets.buffer_item(pkt, now);
         }
 
         let mut iter = nm.rx_packets.queue.iter();
@@ -438,6 +458,7 @@ mod netwayste_net_tests {
     #[test]
     fn test_buffer_item_advanced_reverse_sequential_then_random_then_reverse_sequential() {
         let mut nm = NetworkManager::new();
+        let now = Instant::now();
 
         for index in (0..5).rev() {
             let pkt = Packet::Request {
@@ -446,7 +467,7 @@ mod netwayste_net_tests {
                 cookie:       None,
                 action:       RequestAction::None,
             };
-            nm.rx_packets.buffer_item(pkt);
+            nm.rx_packets.buffer_item(pkt, now);
         }
 
         for index in [10, 7, 11, 9, 12, 8, 99, 6].iter() {
@@ -456,7 +477,7 @@ mod netwayste_net_tests {
                 cookie:       None,
                 action:       RequestAction::None,
             };
-            nm.rx_packets.buffer_item(pkt);
+            nm.rx_packets.buffer_item(pkt, now);
         }
 
         for index in (13..20).rev() {
@@ -466,7 +487,7 @@ mod netwayste_net_tests {
                 cookie:       None,
                 action:       RequestAction::None,
             };
-            nm.rx_packets.buffer_item(pkt);
+            nm.rx_packets.buffer_item(pkt, now);
         }
 
         let mut iter = nm.rx_packets.queue.iter();
@@ -482,6 +503,7 @@ mod netwayste_net_tests {
     #[test]
     fn test_buffer_item_basic_wrapping_case() {
         let mut nm = NetworkManager::new();
+        let now = Instant::now();
         let u64_max = <u64>::max_value();
         let start = u64_max - 5;
 
@@ -492,7 +514,7 @@ mod netwayste_net_tests {
                 cookie:       None,
                 action:       RequestAction::None,
             };
-            nm.rx_packets.buffer_item(pkt);
+            nm.rx_packets.buffer_item(pkt, now);
         }
 
         {
@@ -502,7 +524,7 @@ mod netwayste_net_tests {
                 cookie:       None,
                 action:       RequestAction::None,
             };
-            nm.rx_packets.buffer_item(pkt);
+            nm.rx_packets.buffer_item(pkt, now);
         }
 
         for index in 0..5 {
@@ -512,7 +534,7 @@ mod netwayste_net_tests {
                 cookie:       None,
                 action:       RequestAction::None,
             };
-            nm.rx_packets.buffer_item(pkt);
+            nm.rx_packets.buffer_item(pkt, now);
         }
 
         let mut iter = nm.rx_packets.queue.iter();
@@ -527,6 +549,7 @@ mod netwayste_net_tests {
     #[test]
     fn test_buffer_item_basic_wrapping_case_then_out_of_order() {
         let mut nm = NetworkManager::new();
+        let now = Instant::now();
         let u64_max = <u64>::max_value();
         let start = u64_max - 5;
 
@@ -537,7 +560,7 @@ mod netwayste_net_tests {
                 cookie:       None,
                 action:       RequestAction::None,
             };
-            nm.rx_packets.buffer_item(pkt);
+            nm.rx_packets.buffer_item(pkt, now);
         }
 
         {
@@ -547,7 +570,7 @@ mod netwayste_net_tests {
                 cookie:       None,
                 action:       RequestAction::None,
             };
-            nm.rx_packets.buffer_item(pkt);
+            nm.rx_packets.buffer_item(pkt, now);
         }
 
         for index in [5, 0, 4, 1, 3, 2].iter() {
@@ -557,7 +580,7 @@ mod netwayste_net_tests {
                 cookie:       None,
                 action:       RequestAction::None,
             };
-            nm.rx_packets.buffer_item(pkt);
+            nm.rx_packets.buffer_item(pkt, now);
         }
 
         let mut iter = nm.rx_packets.queue.iter();
@@ -572,6 +595,7 @@ mod netwayste_net_tests {
     #[test]
     fn test_buffer_item_advanced_wrapping_case_everything_out_of_order() {
         let mut nm = NetworkManager::new();
+        let now = Instant::now();
         let u64_max = <u64>::max_value();
         let max_minus_5 = u64_max - 5;
         let max_minus_4 = u64_max - 4;
@@ -603,7 +627,7 @@ mod netwayste_net_tests {
                 cookie:       None,
                 action:       RequestAction::None,
             };
-            nm.rx_packets.buffer_item(pkt);
+            nm.rx_packets.buffer_item(pkt, now);
         }
 
         let mut iter = nm.rx_packets.queue.iter();
@@ -634,6 +658,7 @@ mod netwayste_net_tests {
     #[test]
     fn test_buffer_item_advanced_max_sequence_number_arrives_after_a_wrap() {
         let mut nm = NetworkManager::new();
+        let now = Instant::now();
         let u64_max = <u64>::max_value();
         let max_minus_2 = u64_max - 2;
         let max_minus_1 = u64_max - 1;
@@ -649,7 +674,7 @@ mod netwayste_net_tests {
                 cookie:       None,
                 action:       RequestAction::None,
             };
-            nm.rx_packets.buffer_item(pkt);
+            nm.rx_packets.buffer_item(pkt, now);
         }
 
         let mut iter = nm.rx_packets.queue.iter();
@@ -664,6 +689,7 @@ mod netwayste_net_tests {
     #[test]
     fn test_buffer_item_advanced_oldest_sequence_number_arrived_last() {
         let mut nm = NetworkManager::new();
+        let now = Instant::now();
         let u64_max = <u64>::max_value();
         let max_minus_3 = u64_max - 3;
         let max_minus_2 = u64_max - 2;
@@ -682,7 +708,7 @@ mod netwayste_net_tests {
                 cookie:       None,
                 action:       RequestAction::None,
             };
-            nm.rx_packets.buffer_item(pkt);
+            nm.rx_packets.buffer_item(pkt, now);
         }
 
         let mut iter = nm.rx_packets.queue.iter();
@@ -702,6 +728,7 @@ mod netwayste_net_tests {
     #[test]
     fn test_buffer_item_advanced_wrap_occurs_with_two_item_queue() {
         let mut nm = NetworkManager::new();
+        let now = Instant::now();
         let u64_max = <u64>::max_value();
         let max_minus_3 = u64_max - 3;
         let max_minus_2 = u64_max - 2;
@@ -721,7 +748,7 @@ mod netwayste_net_tests {
                 cookie:       None,
                 action:       RequestAction::None,
             };
-            nm.rx_packets.buffer_item(pkt);
+            nm.rx_packets.buffer_item(pkt, now);
         }
 
         let mut iter = nm.rx_packets.queue.iter();
@@ -741,6 +768,7 @@ mod netwayste_net_tests {
     #[test]
     fn test_buffer_item_advanced_wrap_occurs_with_two_item_queue_in_reverse() {
         let mut nm = NetworkManager::new();
+        let now = Instant::now();
         let u64_max = <u64>::max_value();
         let max_minus_3 = u64_max - 3;
         let max_minus_2 = u64_max - 2;
@@ -760,7 +788,7 @@ mod netwayste_net_tests {
                 cookie:       None,
                 action:       RequestAction::None,
             };
-            nm.rx_packets.buffer_item(pkt);
+            nm.rx_packets.buffer_item(pkt, now);
         }
 
         let mut iter = nm.rx_packets.queue.iter();
@@ -780,6 +808,7 @@ mod netwayste_net_tests {
     #[test]
     fn test_buffer_item_advanced_wrapping_case_max_arrives_first() {
         let mut nm = NetworkManager::new();
+        let now = Instant::now();
         let u64_max = <u64>::max_value();
         let max_minus_5 = u64_max - 5;
         let max_minus_4 = u64_max - 4;
@@ -811,7 +840,7 @@ mod netwayste_net_tests {
                 cookie:       None,
                 action:       RequestAction::None,
             };
-            nm.rx_packets.buffer_item(pkt);
+            nm.rx_packets.buffer_item(pkt, now);
         }
 
         let mut iter = nm.rx_packets.queue.iter();
@@ -842,6 +871,7 @@ mod netwayste_net_tests {
     #[test]
     fn test_buffer_item_advanced_wrapping_case_sequence_number_descending() {
         let mut nm = NetworkManager::new();
+        let now = Instant::now();
         let u64_max = <u64>::max_value();
         let max_minus_5 = u64_max - 5;
         let max_minus_4 = u64_max - 4;
@@ -873,7 +903,7 @@ mod netwayste_net_tests {
                 cookie:       None,
                 action:       RequestAction::None,
             };
-            nm.rx_packets.buffer_item(pkt);
+            nm.rx_packets.buffer_item(pkt, now);
         }
 
         let mut iter = nm.rx_packets.queue.iter();
@@ -904,6 +934,7 @@ mod netwayste_net_tests {
     #[test]
     fn test_buffer_item_advanced_wrapping_case_sequence_number_alternating() {
         let mut nm = NetworkManager::new();
+        let now = Instant::now();
         let u64_max = <u64>::max_value();
         let max_minus_5 = u64_max - 5;
         let max_minus_4 = u64_max - 4;
@@ -935,7 +966,7 @@ mod netwayste_net_tests {
                 cookie:       None,
                 action:       RequestAction::None,
             };
-            nm.rx_packets.buffer_item(pkt);
+            nm.rx_packets.buffer_item(pkt, now);
         }
 
         let mut iter = nm.rx_packets.queue.iter();
@@ -984,6 +1015,7 @@ mod netwayste_net_tests {
     #[test]
     fn test_get_contiguous_packets_count() {
         let mut nm = NetworkManager::new();
+        let now = Instant::now();
         for index in 0..5 {
             let pkt = Packet::Request {
                 sequence:     index as u64,
@@ -991,7 +1023,7 @@ mod netwayste_net_tests {
                 cookie:       None,
                 action:       RequestAction::None,
             };
-            nm.rx_packets.buffer_item(pkt);
+            nm.rx_packets.buffer_item(pkt, now);
         }
         for index in 8..10 {
             let pkt = Packet::Request {
@@ -1000,7 +1032,7 @@ mod netwayste_net_tests {
                 cookie:       None,
                 action:       RequestAction::None,
             };
-            nm.rx_packets.buffer_item(pkt);
+            nm.rx_packets.buffer_item(pkt, now);
         }
 
         let count = nm.rx_packets.get_contiguous_packets_count(0);
@@ -1020,6 +1052,8 @@ mod netwayste_net_tests {
     #[test]
     fn test_get_retransmit_indices() {
         let mut nm = NetworkManager::new();
+        let now = Instant::now();
+        let later = now + RETRANSMISSION_THRESHOLD_IN_MS;
         for i in 0..5 {
             let pkt = Packet::Request {
                 sequence:     i,
@@ -1028,21 +1062,46 @@ mod netwayste_net_tests {
                 action:       RequestAction::None,
             };
 
-            nm.tx_packets.buffer_item(pkt.clone());
-
-            if i < 3 {
-                let attempt: &mut NetAttempt = nm.tx_packets.attempts.back_mut().unwrap();
-                attempt.time = Instant::now() - Duration::from_secs(i + 1);
-            }
+            // the last two are sent a while after the others
+            let sent_at = if i < 3 { now } else { later };
+            nm.tx_packets.buffer_item(pkt.clone(), sent_at);
         }
-        assert_eq!(nm.tx_packets.get_retransmit_indices().len(), 3);
-        thread::sleep(Duration::from_millis(2000));
-        assert_eq!(nm.tx_packets.get_retransmit_indices().len(), 5);
+        assert_eq!(nm.tx_packets.get_retransmit_indices(later).len(), 3);
+        // The rest are due once the retransmission threshold has passed for them too, without waiting for it
+        let even_later = later + RETRANSMISSION_THRESHOLD_IN_MS;
+        assert_eq!(nm.tx_packets.get_retransmit_indices(even_later).len(), 5);
+    }
+
+    #[test]
+    fn test_get_retransmit_indices_waits_between_retries() {
+        let mut nm = NetworkManager::new();
+        let now = Instant::now();
+        let pkt = Packet::Request {
+            sequence:     0,
+            response_ack: None,
+            cookie:       None,
+            action:       RequestAction::None,
+        };
+        nm.tx_packets.buffer_item(pkt, now);
+
+        // However many times it has been resent, a packet just resent isn't due again right away
+        let mut due = now + RETRANSMISSION_THRESHOLD_IN_MS;
+        for _ in 0..10 {
+            let just_before = due - Duration::from_millis(1);
+            assert_eq!(nm.tx_packets.get_retransmit_indices(just_before), Vec::<usize>::new());
+            let indices = nm.tx_packets.get_retransmit_indices(due);
+            assert_eq!(indices, vec![0]);
+            nm.get_expired_tx_packets(fake_socket_addr(), None, &indices, due);
+            assert_eq!(nm.tx_packets.get_retransmit_indices(due), Vec::<usize>::new());
+            due += RETRANSMISSION_THRESHOLD_IN_MS;
+        }
+        assert_eq!(nm.tx_packets.attempts[0].retries, 10);
     }
 
     #[test]
     fn test_get_expired_tx_packets_no_expirations() {
         let mut nm = NetworkManager::new();
+        let now = Instant::now();
 
         for i in 0..5 {
             let pkt = Packet::Request {
@@ -1052,13 +1111,13 @@ mod netwayste_net_tests {
                 action:       RequestAction::None,
             };
 
-            nm.tx_packets.buffer_item(pkt.clone());
+            nm.tx_packets.buffer_item(pkt.clone(), now);
         }
 
-        let indices = nm.tx_packets.get_retransmit_indices();
+        let indices = nm.tx_packets.get_retransmit_indices(now);
 
         let addr = fake_socket_addr();
-        nm.get_expired_tx_packets(addr, None, &indices);
+        nm.get_expired_tx_packets(addr, None, &indices, now);
 
         for i in 0..5 {
             assert_eq!(nm.tx_packets.attempts.get(i).unwrap().retries, 0);
@@ -1068,6 +1127,8 @@ mod netwayste_net_tests {
     #[test]
     fn test_get_expired_tx_packets_basic_retries() {
         let mut nm = NetworkManager::new();
+        let now = Instant::now();
+        let later = now + RETRANSMISSION_THRESHOLD_IN_MS;
 
         for i in 0..5 {
             let pkt = Packet::Request {
@@ -1077,18 +1138,15 @@ mod netwayste_net_tests {
                 action:       RequestAction::None,
             };
 
-            nm.tx_packets.buffer_item(pkt.clone());
-
-            if i < 3 {
-                let attempt: &mut NetAttempt = nm.tx_packets.attempts.back_mut().unwrap();
-                attempt.time = Instant::now() - Duration::from_secs(i + 1);
-            }
+            // the last two are sent a while after the others
+            let sent_at = if i < 3 { now } else { later };
+            nm.tx_packets.buffer_item(pkt.clone(), sent_at);
         }
 
-        let indices = nm.tx_packets.get_retransmit_indices();
+        let indices = nm.tx_packets.get_retransmit_indices(later);
 
         let addr = fake_socket_addr();
-        nm.get_expired_tx_packets(addr, None, &indices);
+        nm.get_expired_tx_packets(addr, None, &indices, later);
 
         for i in 0..3 {
             assert_eq!(nm.tx_packets.attempts.get(i).unwrap().retries, 1);
@@ -1101,6 +1159,8 @@ mod netwayste_net_tests {
     #[test]
     fn test_get_expired_tx_packets_aggressive_retries() {
         let mut nm = NetworkManager::new();
+        let now = Instant::now();
+        let last_round = now + RETRANSMISSION_THRESHOLD_IN_MS * 5;
 
         for i in 0..5 {
             let pkt = Packet::Request {
@@ -1110,25 +1170,19 @@ mod netwayste_net_tests {
                 action:       RequestAction::None,
             };
 
-            nm.tx_packets.buffer_item(pkt.clone());
-
-            if i < 3 {
-                let attempt: &mut NetAttempt = nm.tx_packets.attempts.back_mut().unwrap();
-                attempt.time = Instant::now() - Duration::from_secs(i + 1);
-            }
+            // the last two are sent just in time not to be due by the end
+            let sent_at = if i < 3 { now } else { last_round };
+            nm.tx_packets.buffer_item(pkt.clone(), sent_at);
         }
 
         // After 2 attempts, aggressive mode should kick in
+        let mut due = now;
         for _ in 0..5 {
-            let indices = nm.tx_packets.get_retransmit_indices();
+            due += RETRANSMISSION_THRESHOLD_IN_MS;
+            let indices = nm.tx_packets.get_retransmit_indices(due);
 
             let addr = fake_socket_addr();
-            nm.get_expired_tx_packets(addr, None, &indices);
-
-            for j in 0..indices.len() {
-                let attempt: &mut NetAttempt = nm.tx_packets.attempts.get_mut(j).unwrap();
-                attempt.time = Instant::now() - Duration::from_secs(1u64);
-            }
+            nm.get_expired_tx_packets(addr, None, &indices, due);
         }
 
         for i in 0..3 {
@@ -1179,11 +1233,1193 @@ mod netwayste_net_tests {
         ];
         assert_eq!(bytes, expected);
     }
+
+    // Serialized packets of every protocol version that is still decoded. When the format changes,
+    // add fixtures for the new version next to these instead of replacing them; they must keep
+    // decoding until MIN_PROTOCOL_VERSION moves past them.
+    // Version 1: a single packet after the header
+    const V1_CONNECT_REQUEST: &[u8] = &[
+        0x43, 0x57, 1, // "CW", version 1
+        0, 0, 0, 0, // 0=Request
+        1, 0, 0, 0, 0, 0, 0, 0, // sequence
+        0, // response_ack: None
+        0, // cookie: None
+        1, 0, 0, 0, // 1=Connect
+        2, 0, 0, 0, 0, 0, 0, 0, 97, 98, // name
+        5, 0, 0, 0, 0, 0, 0, 0, 48, 46, 51, 46, 52, // client_version
+    ];
+    const V1_CHAT_REQUEST: &[u8] = &[
+        0x43, 0x57, 1, // "CW", version 1
+        0, 0, 0, 0, // 0=Request
+        7, 0, 0, 0, 0, 0, 0, 0, // sequence
+        1, 3, 0, 0, 0, 0, 0, 0, 0, // response_ack
+        1, 2, 0, 0, 0, 0, 0, 0, 0, 99, 107, // cookie
+        5, 0, 0, 0, // 5=ChatMessage
+        2, 0, 0, 0, 0, 0, 0, 0, 104, 105, // message
+    ];
+    const V1_LOGGED_IN_RESPONSE: &[u8] = &[
+        0x43, 0x57, 1, // "CW", version 1
+        1, 0, 0, 0, // 1=Response
+        0, 0, 0, 0, 0, 0, 0, 0, // sequence
+        1, 0, 0, 0, 0, 0, 0, 0, 0, // request_ack
+        1, 0, 0, 0, // 1=LoggedIn
+        2, 0, 0, 0, 0, 0, 0, 0, 99, 107, // cookie
+        5, 0, 0, 0, 0, 0, 0, 0, 48, 46, 51, 46, 52, // server_version
+    ];
+    const V1_UPDATE_REPLY: &[u8] = &[
+        0x43, 0x57, 1, // "CW", version 1
+        3, 0, 0, 0, // 3=UpdateReply
+        2, 0, 0, 0, 0, 0, 0, 0, 99, 107, // cookie
+        1, 2, 0, 0, 0, 0, 0, 0, 0, // last_chat_seq
+        0, // last_game_update_seq: None
+        1, 9, 0, 0, 0, 0, 0, 0, 0, // last_full_gen
+        0, // partial_gen: None
+        8, 7, 6, 5, 4, 3, 2, 1, // pong.nonce
+    ];
+    const V1_NEW_ROOM_REQUEST: &[u8] = &[
+        0x43, 0x57, 1, // "CW", version 1
+        0, 0, 0, 0, // 0=Request
+        2, 0, 0, 0, 0, 0, 0, 0, // sequence
+        0, // response_ack: None
+        1, 2, 0, 0, 0, 0, 0, 0, 0, 99, 107, // cookie
+        7, 0, 0, 0, // 7=NewRoom
+        2, 0, 0, 0, 0, 0, 0, 0, 114, 109, // room_name
+    ];
+    const V1_ROOM_LIST_RESPONSE: &[u8] = &[
+        0x43, 0x57, 1, // "CW", version 1
+        1, 0, 0, 0, // 1=Response
+        4, 0, 0, 0, 0, 0, 0, 0, // sequence
+        1, 2, 0, 0, 0, 0, 0, 0, 0, // request_ack
+        5, 0, 0, 0, // 5=RoomList
+        1, 0, 0, 0, 0, 0, 0, 0, // one room
+        2, 0, 0, 0, 0, 0, 0, 0, 114, 109, // room_name
+        3,   // player_count
+        1,   // in_progress
+    ];
+    const V1_PLAYER_LIST_RESPONSE: &[u8] = &[
+        0x43, 0x57, 1, // "CW", version 1
+        1, 0, 0, 0, // 1=Response
+        4, 0, 0, 0, 0, 0, 0, 0, // sequence
+        1, 2, 0, 0, 0, 0, 0, 0, 0, // request_ack
+        4, 0, 0, 0, // 4=PlayerList
+        2, 0, 0, 0, 0, 0, 0, 0, // two players
+        2, 0, 0, 0, 0, 0, 0, 0, 97, 98, // player name
+        2, 0, 0, 0, 0, 0, 0, 0, 99, 100, // player name
+        0, 0, 0, 0, 0, 0, 0, 0, // muted: none
+    ];
+
+    // Version 2: a bundle of packets after the header, and rooms with passwords, teams and
+    // player limits
+    const V2_CONNECT_REQUEST: &[u8] = &[
+        0x43, 0x57, 2, // "CW", version 2
+        1, // one packet
+        41, 0, // its length
+        0, 0, 0, 0, // 0=Request
+        1, 0, 0, 0, 0, 0, 0, 0, // sequence
+        0, // response_ack: None
+        0, // cookie: None
+        1, 0, 0, 0, // 1=Connect
+        2, 0, 0, 0, 0, 0, 0, 0, 97, 98, // name
+        5, 0, 0, 0, 0, 0, 0, 0, 48, 46, 51, 46, 52, // client_version
+    ];
+    const V2_LOGGED_IN_RESPONSE: &[u8] = &[
+        0x43, 0x57, 2, // "CW", version 2
+        1, // one packet
+        48, 0, // its length
+        1, 0, 0, 0, // 1=Response
+        0, 0, 0, 0, 0, 0, 0, 0, // sequence
+        1, 0, 0, 0, 0, 0, 0, 0, 0, // request_ack
+        1, 0, 0, 0, // 1=LoggedIn
+        2, 0, 0, 0, 0, 0, 0, 0, 99, 107, // cookie
+        5, 0, 0, 0, 0, 0, 0, 0, 48, 46, 51, 46, 52, // server_version
+    ];
+    const V2_UPDATE_REPLY: &[u8] = &[
+        0x43, 0x57, 2, // "CW", version 2
+        1, // one packet
+        42, 0, // its length
+        3, 0, 0, 0, // 3=UpdateReply
+        2, 0, 0, 0, 0, 0, 0, 0, 99, 107, // cookie
+        1, 2, 0, 0, 0, 0, 0, 0, 0, // last_chat_seq
+        0, // last_game_update_seq: None
+        1, 9, 0, 0, 0, 0, 0, 0, 0, // last_full_gen
+        0, // partial_gen: None
+        8, 7, 6, 5, 4, 3, 2, 1, // pong.nonce
+    ];
+    const V2_NEW_ROOM_REQUEST: &[u8] = &[
+        0x43, 0x57, 2, // "CW", version 2
+        1, // one packet
+        53, 0, // its length
+        0, 0, 0, 0, // 0=Request
+        2, 0, 0, 0, 0, 0, 0, 0, // sequence
+        0, // response_ack: None
+        1, 2, 0, 0, 0, 0, 0, 0, 0, 99, 107, // cookie
+        7, 0, 0, 0, // 7=NewRoom
+        2, 0, 0, 0, 0, 0, 0, 0, 114, 109, // room_name
+        1, 2, 0, 0, 0, 0, 0, 0, 0, 112, 119, // password
+        1, 3, // team_count
+        1, 4, // max_players
+    ];
+    const V2_ROOM_LIST_RESPONSE: &[u8] = &[
+        0x43, 0x57, 2, // "CW", version 2
+        1, // one packet
+        47, 0, // its length
+        1, 0, 0, 0, // 1=Response
+        4, 0, 0, 0, 0, 0, 0, 0, // sequence
+        1, 2, 0, 0, 0, 0, 0, 0, 0, // request_ack
+        5, 0, 0, 0, // 5=RoomList
+        1, 0, 0, 0, 0, 0, 0, 0, // one room
+        2, 0, 0, 0, 0, 0, 0, 0, 114, 109, // room_name
+        3,   // player_count
+        4,   // max_players
+        1,   // in_progress
+        1,   // has_password
+    ];
+    const V2_CHAT_REQUEST: &[u8] = &[
+        0x43, 0x57, 2, // "CW", version 2
+        1, // one packet
+        46, 0, // its length
+        0, 0, 0, 0, // 0=Request
+        7, 0, 0, 0, 0, 0, 0, 0, // sequence
+        1, 3, 0, 0, 0, 0, 0, 0, 0, // response_ack
+        1, 2, 0, 0, 0, 0, 0, 0, 0, 99, 107, // cookie
+        5, 0, 0, 0, // 5=ChatMessage
+        2, 0, 0, 0, 0, 0, 0, 0, 104, 105, // message
+    ];
+    const V2_PLAYER_LIST_RESPONSE: &[u8] = &[
+        0x43, 0x57, 2, // "CW", version 2
+        1, // one packet
+        72, 0, // its length
+        1, 0, 0, 0, // 1=Response
+        4, 0, 0, 0, 0, 0, 0, 0, // sequence
+        1, 2, 0, 0, 0, 0, 0, 0, 0, // request_ack
+        4, 0, 0, 0, // 4=PlayerList
+        2, 0, 0, 0, 0, 0, 0, 0, // two players
+        2, 0, 0, 0, 0, 0, 0, 0, 97, 98, // player name
+        2, 0, 0, 0, 0, 0, 0, 0, 99, 100, // player name
+        0, 0, 0, 0, 0, 0, 0, 0, // muted: none
+        2, 0, 0, 0, 0, 0, 0, 0, // two teams
+        1, 1, // team 1
+        0, // no team yet
+    ];
+    const V2_SET_TEAM_REQUEST: &[u8] = &[
+        0x43, 0x57, 2, // "CW", version 2
+        1, // one packet
+        29, 0, // its length
+        0, 0, 0, 0, // 0=Request
+        9, 0, 0, 0, 0, 0, 0, 0, // sequence
+        0, // response_ack: None
+        1, 2, 0, 0, 0, 0, 0, 0, 0, 99, 107, // cookie
+        22, 0, 0, 0, // 22=SetTeam
+        1, // team
+    ];
+    const V2_LOGGED_IN_AND_PLAYER_LIST: &[u8] = &[
+        0x43, 0x57, 2, // "CW", version 2
+        2, // two packets
+        48, 0, // the first one's length
+        1, 0, 0, 0, // 1=Response
+        0, 0, 0, 0, 0, 0, 0, 0, // sequence
+        1, 0, 0, 0, 0, 0, 0, 0, 0, // request_ack
+        1, 0, 0, 0, // 1=LoggedIn
+        2, 0, 0, 0, 0, 0, 0, 0, 99, 107, // cookie
+        5, 0, 0, 0, 0, 0, 0, 0, 48, 46, 51, 46, 52, // server_version
+        72, 0, // the second one's length
+        1, 0, 0, 0, // 1=Response
+        1, 0, 0, 0, 0, 0, 0, 0, // sequence
+        1, 2, 0, 0, 0, 0, 0, 0, 0, // request_ack
+        4, 0, 0, 0, // 4=PlayerList
+        2, 0, 0, 0, 0, 0, 0, 0, // two players
+        2, 0, 0, 0, 0, 0, 0, 0, 97, 98, // player name
+        2, 0, 0, 0, 0, 0, 0, 0, 99, 100, // player name
+        0, 0, 0, 0, 0, 0, 0, 0, // muted: none
+        2, 0, 0, 0, 0, 0, 0, 0, // two teams
+        1, 1, // team 1
+        0, // no team yet
+    ];
+
+    fn decode_bundle_fixture(fixture: &[u8]) -> Vec<Packet> {
+        let mut src = BytesMut::from(fixture);
+        let packets = NetwaystePacketCodec.decode(&mut src).unwrap().unwrap();
+        assert!(src.is_empty(), "{} bytes left over after {:?}", src.len(), packets);
+        packets
+    }
+
+    fn decode_fixture(fixture: &[u8]) -> Packet {
+        let mut packets = decode_bundle_fixture(fixture);
+        assert_eq!(packets.len(), 1, "expected one packet, not {:?}", packets);
+        packets.remove(0)
+    }
+
+    #[test]
+    fn test_decode_v1_fixtures() {
+        match decode_fixture(V1_CONNECT_REQUEST) {
+            Packet::Request {
+                sequence: 1,
+                response_ack: None,
+                cookie: None,
+                action: RequestAction::Connect { name, client_version },
+            } => assert_eq!((name.as_str(), client_version.as_str()), ("ab", "0.3.4")),
+            packet => panic!("Unexpected packet: {:?}", packet),
+        }
+        match decode_fixture(V1_CHAT_REQUEST) {
+            Packet::Request {
+                sequence: 7,
+                response_ack: Some(3),
+                cookie: Some(cookie),
+                action: RequestAction::ChatMessage { message },
+            } => assert_eq!((cookie.as_str(), message.as_str()), ("ck", "hi")),
+            packet => panic!("Unexpected packet: {:?}", packet),
+        }
+        match decode_fixture(V1_LOGGED_IN_RESPONSE) {
+            Packet::Response {
+                sequence: 0,
+                request_ack: Some(0),
+                code: ResponseCode::LoggedIn { cookie, server_version },
+            } => assert_eq!((cookie.as_str(), server_version.as_str()), ("ck", "0.3.4")),
+            packet => panic!("Unexpected packet: {:?}", packet),
+        }
+        match decode_fixture(V1_UPDATE_REPLY) {
+            Packet::UpdateReply {
+                cookie,
+                last_chat_seq: Some(2),
+                last_game_update_seq: None,
+                last_full_gen: Some(9),
+                partial_gen: None,
+                pong,
+            } => {
+                assert_eq!(cookie, "ck");
+                assert_eq!(pong.nonce, 0x0102030405060708);
+            }
+            packet => panic!("Unexpected packet: {:?}", packet),
+        }
+        match decode_fixture(V1_NEW_ROOM_REQUEST) {
+            Packet::Request {
+                action:
+                    RequestAction::NewRoom {
+                        room_name,
+                        password,
+                        team_count,
+                        max_players,
+                    },
+                ..
+            } => assert_eq!(
+                (room_name.as_str(), password, team_count, max_players),
+                ("rm", None, None, None)
+            ),
+            packet => panic!("Unexpected packet: {:?}", packet),
+        }
+        match decode_fixture(V1_ROOM_LIST_RESPONSE) {
+            Packet::Response {
+                code: ResponseCode::RoomList { rooms },
+                ..
+            } => {
+                assert_eq!(rooms.len(), 1);
+                assert_eq!((rooms[0].player_count, rooms[0].in_progress), (3, true));
+                // version 1 rooms had no passwords, and all took eight players
+                assert!(!rooms[0].has_password);
+                assert_eq!(rooms[0].max_players, 8);
+            }
+            packet => panic!("Unexpected packet: {:?}", packet),
+        }
+        match decode_fixture(V1_PLAYER_LIST_RESPONSE) {
+            Packet::Response {
+                code: ResponseCode::PlayerList { players, teams, .. },
+                ..
+            } => {
+                assert_eq!(players, vec!["ab".to_owned(), "cd".to_owned()]);
+                assert_eq!(teams, vec![None, None]);
+            }
+            packet => panic!("Unexpected packet: {:?}", packet),
+        }
+    }
+
+    #[test]
+    fn test_decode_v2_fixtures() {
+        match decode_fixture(V2_CONNECT_REQUEST) {
+            Packet::Request {
+                sequence: 1,
+                response_ack: None,
+                cookie: None,
+                action: RequestAction::Connect { name, client_version },
+            } => assert_eq!((name.as_str(), client_version.as_str()), ("ab", "0.3.4")),
+            packet => panic!("Unexpected packet: {:?}", packet),
+        }
+        match decode_fixture(V2_LOGGED_IN_RESPONSE) {
+            Packet::Response {
+                sequence: 0,
+                request_ack: Some(0),
+                code: ResponseCode::LoggedIn { cookie, server_version },
+            } => assert_eq!((cookie.as_str(), server_version.as_str()), ("ck", "0.3.4")),
+            packet => panic!("Unexpected packet: {:?}", packet),
+        }
+        match decode_fixture(V2_UPDATE_REPLY) {
+            Packet::UpdateReply {
+                cookie,
+                last_chat_seq: Some(2),
+                last_game_update_seq: None,
+                last_full_gen: Some(9),
+                partial_gen: None,
+                pong,
+            } => {
+                assert_eq!(cookie, "ck");
+                assert_eq!(pong.nonce, 0x0102030405060708);
+            }
+            packet => panic!("Unexpected packet: {:?}", packet),
+        }
+        match decode_fixture(V2_NEW_ROOM_REQUEST) {
+            Packet::Request {
+                action:
+                    RequestAction::NewRoom {
+                        room_name,
+                        password,
+                        team_count,
+                        max_players,
+                    },
+                ..
+            } => assert_eq!(
+                (room_name.as_str(), password.as_deref(), team_count, max_players),
+                ("rm", Some("pw"), Some(3), Some(4))
+            ),
+            packet => panic!("Unexpected packet: {:?}", packet),
+        }
+        match decode_fixture(V2_ROOM_LIST_RESPONSE) {
+            Packet::Response {
+                code: ResponseCode::RoomList { rooms },
+                ..
+            } => {
+                assert_eq!(rooms.len(), 1);
+                assert_eq!((rooms[0].player_count, rooms[0].max_players), (3, 4));
+                assert!(rooms[0].has_password);
+            }
+            packet => panic!("Unexpected packet: {:?}", packet),
+        }
+        match decode_fixture(V2_CHAT_REQUEST) {
+            Packet::Request {
+                action: RequestAction::ChatMessage { message },
+                ..
+            } => assert_eq!(message, "hi"),
+            packet => panic!("Unexpected packet: {:?}", packet),
+        }
+        match decode_fixture(V2_PLAYER_LIST_RESPONSE) {
+            Packet::Response {
+                code: ResponseCode::PlayerList { players, teams, .. },
+                ..
+            } => {
+                assert_eq!(players, vec!["ab".to_owned(), "cd".to_owned()]);
+                assert_eq!(teams, vec![Some(1), None]);
+            }
+            packet => panic!("Unexpected packet: {:?}", packet),
+        }
+        match decode_fixture(V2_SET_TEAM_REQUEST) {
+            Packet::Request {
+                sequence: 9,
+                action: RequestAction::SetTeam { team: 1 },
+                ..
+            } => {}
+            packet => panic!("Unexpected packet: {:?}", packet),
+        }
+
+        // bundled packets come out in the order they went in
+        let packets = decode_bundle_fixture(V2_LOGGED_IN_AND_PLAYER_LIST);
+        assert_eq!(packets.len(), 2);
+        match packets[0] {
+            Packet::Response {
+                sequence: 0,
+                code: ResponseCode::LoggedIn { ref cookie, .. },
+                ..
+            } => assert_eq!(cookie, "ck"),
+            ref packet => panic!("Unexpected packet: {:?}", packet),
+        }
+        match packets[1] {
+            Packet::Response {
+                sequence: 1,
+                code: ResponseCode::PlayerList { ref players, .. },
+                ..
+            } => assert_eq!(players.len(), 2),
+            ref packet => panic!("Unexpected packet: {:?}", packet),
+        }
+    }
+
+    // When PROTOCOL_VERSION is bumped, point this at the new version's fixtures.
+    #[test]
+    fn test_encode_matches_current_version_fixtures() {
+        assert_eq!(PROTOCOL_VERSION, 2);
+        for &fixture in &[
+            V2_CONNECT_REQUEST,
+            V2_LOGGED_IN_RESPONSE,
+            V2_UPDATE_REPLY,
+            V2_NEW_ROOM_REQUEST,
+            V2_ROOM_LIST_RESPONSE,
+            V2_CHAT_REQUEST,
+            V2_PLAYER_LIST_RESPONSE,
+            V2_SET_TEAM_REQUEST,
+        ] {
+            let mut dst = BytesMut::new();
+            NetwaystePacketCodec.encode(decode_fixture(fixture), &mut dst).unwrap();
+            assert_eq!(&dst[..], fixture);
+        }
+    }
+
+    // What the server sends a client that is one version behind
+    #[test]
+    fn test_encode_matches_previous_version_fixtures() {
+        for &fixture in &[
+            V1_CONNECT_REQUEST,
+            V1_CHAT_REQUEST,
+            V1_LOGGED_IN_RESPONSE,
+            V1_UPDATE_REPLY,
+            V1_NEW_ROOM_REQUEST,
+            V1_ROOM_LIST_RESPONSE,
+            V1_PLAYER_LIST_RESPONSE,
+        ] {
+            let datagram = encode_datagram(MIN_PROTOCOL_VERSION, &decode_fixture(fixture)).unwrap();
+            assert_eq!(&datagram[..], fixture);
+        }
+    }
+
+    #[test]
+    fn test_encode_for_version_1() {
+        let response = |sequence, code| Packet::Response {
+            sequence,
+            request_ack: None,
+            code,
+        };
+        let packets = vec![
+            response(
+                1,
+                ResponseCode::RoomFull {
+                    room_name:   "rm".to_owned(),
+                    max_players: 4,
+                },
+            ),
+            response(
+                2,
+                ResponseCode::GameStarting {
+                    room_name:    "rm".to_owned(),
+                    seconds_left: 3,
+                },
+            ),
+        ];
+
+        // one packet to a datagram
+        assert_eq!(bundle_len(1, &packets), 1);
+        assert!(encode_bundle(1, &packets).is_err());
+
+        // a refusal version 1 had no code for is a BadRequest
+        let datagram = encode_datagram(1, &packets[0]).unwrap();
+        assert_eq!(datagram_version(&datagram), Some(1));
+        match decode_fixture(&datagram) {
+            Packet::Response {
+                sequence: 1,
+                code: ResponseCode::BadRequest { error_msg },
+                ..
+            } => assert!(error_msg.contains("full")),
+            packet => panic!("Unexpected packet: {:?}", packet),
+        }
+
+        // and anything else it can't say is an OK, which still takes up its sequence number
+        match decode_fixture(&encode_datagram(1, &packets[1]).unwrap()) {
+            Packet::Response {
+                sequence: 2,
+                code: ResponseCode::OK,
+                ..
+            } => {}
+            packet => panic!("Unexpected packet: {:?}", packet),
+        }
+    }
+
+    #[test]
+    fn test_decode_unsupported_version() {
+        for &version in &[MIN_PROTOCOL_VERSION - 1, PROTOCOL_VERSION + 1] {
+            let mut datagram = V2_CONNECT_REQUEST.to_vec();
+            datagram[2] = version;
+            assert_eq!(
+                decode_datagram(&datagram).unwrap_err(),
+                WireError::UnsupportedVersion { version }
+            );
+        }
+    }
+
+    #[test]
+    fn test_decode_without_header() {
+        // what a client from before protocol versioning would send
+        let headerless = &V2_CONNECT_REQUEST[PROTOCOL_HEADER_LEN + 1 + 2..]; // past the count and length too
+        assert_eq!(decode_datagram(headerless).unwrap_err(), WireError::MissingHeader);
+
+        // The codec drops the datagram, so that the next one can be decoded
+        let mut src = BytesMut::from(headerless);
+        assert!(NetwaystePacketCodec.decode(&mut src).is_err());
+        assert!(src.is_empty());
+    }
+
+    #[test]
+    fn test_unsupported_version_reply_has_no_header() {
+        let datagram = encode_unsupported_version_reply().unwrap();
+        assert!(!datagram.starts_with(&PROTOCOL_MAGIC));
+        let (mut packets, len) = decode_datagram(&datagram).unwrap();
+        assert_eq!(len, datagram.len());
+        match packets.remove(0) {
+            Packet::Response {
+                code: ResponseCode::BadRequest { error_msg },
+                ..
+            } => assert!(error_msg.contains("not supported")),
+            packet => panic!("Unexpected packet: {:?}", packet),
+        }
+
+        // no other response may leave out the header
+        let keep_alive = serialize(&Packet::Response {
+            sequence:    0,
+            request_ack: None,
+            code:        ResponseCode::KeepAlive,
+        })
+        .unwrap();
+        assert_eq!(decode_datagram(&keep_alive).unwrap_err(), WireError::MissingHeader);
+    }
+
+    #[test]
+    fn test_decode_truncated_packet() {
+        let truncated = &V2_CHAT_REQUEST[..V2_CHAT_REQUEST.len() - 1];
+        match decode_datagram(truncated) {
+            Err(WireError::Malformed { version: 2, .. }) => {}
+            result => panic!("Unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_decode_malformed_bundles() {
+        let malformed = |datagram: &[u8]| match decode_datagram(datagram) {
+            Err(WireError::Malformed { version: 2, .. }) => {}
+            result => panic!("Unexpected result: {:?}", result),
+        };
+
+        // cut off in the middle of the second packet, and then in the middle of its length
+        let bundle = V2_LOGGED_IN_AND_PLAYER_LIST;
+        malformed(&bundle[..bundle.len() - 1]);
+        malformed(&bundle[..PROTOCOL_HEADER_LEN + 1 + 2 + 48 + 1]);
+
+        // no packets at all
+        malformed(&[0x43, 0x57, 2]);
+        malformed(&[0x43, 0x57, 2, 0]);
+
+        // a packet's length doesn't match what is in it
+        let mut padded = V2_SET_TEAM_REQUEST.to_vec();
+        padded[PROTOCOL_HEADER_LEN + 1] += 1;
+        padded.push(0);
+        malformed(&padded);
+    }
+
+    #[test]
+    fn test_decode_oversized_datagram() {
+        let mut datagram = V2_CHAT_REQUEST.to_vec();
+        datagram.resize(UDP_MTU_SIZE + 1, 0);
+        assert_eq!(
+            decode_datagram(&datagram).unwrap_err(),
+            WireError::TooLarge { len: UDP_MTU_SIZE + 1 }
+        );
+    }
+
+    #[test]
+    fn test_decode_huge_length_is_refused() {
+        // The message claims to be about 4 GB long. This has to fail without trying to allocate that.
+        let mut datagram = V2_CHAT_REQUEST.to_vec();
+        let message_len_at = datagram.len() - 10;
+        datagram[message_len_at..message_len_at + 8].copy_from_slice(&0xFFFF_FFFFu64.to_le_bytes());
+        match decode_datagram(&datagram) {
+            Err(WireError::Malformed { version: 2, .. }) => {}
+            result => panic!("Unexpected result: {:?}", result),
+        }
+
+        // and the codec goes on to decode the next datagram
+        let mut src = BytesMut::from(&datagram[..]);
+        assert!(NetwaystePacketCodec.decode(&mut src).is_err());
+        assert!(src.is_empty());
+        src.extend_from_slice(V2_CHAT_REQUEST);
+        assert!(NetwaystePacketCodec.decode(&mut src).unwrap().is_some());
+    }
+
+    fn chat_request(sequence: u64, message_len: usize) -> Packet {
+        Packet::Request {
+            sequence,
+            response_ack: None,
+            cookie: Some("cookie".to_owned()),
+            action: RequestAction::ChatMessage {
+                message: "x".repeat(message_len),
+            },
+        }
+    }
+
+    #[test]
+    fn test_bundles_are_split_at_the_mtu() {
+        let packets: Vec<Packet> = (0..10).map(|sequence| chat_request(sequence, 400)).collect();
+        let mut rest = &packets[..];
+        let mut sequences = vec![];
+        let mut datagrams = 0;
+        while !rest.is_empty() {
+            let len = bundle_len(PROTOCOL_VERSION, rest);
+            assert!(len >= 1);
+            let datagram = encode_bundle(PROTOCOL_VERSION, &rest[..len]).unwrap();
+            assert!(datagram.len() <= UDP_MTU_SIZE);
+            if len < rest.len() {
+                // as many as fit were bundled
+                assert!(encode_bundle(PROTOCOL_VERSION, &rest[..len + 1]).unwrap().len() > UDP_MTU_SIZE);
+            }
+            let (decoded, decoded_len) = decode_datagram(&datagram).unwrap();
+            assert_eq!(decoded_len, datagram.len());
+            sequences.extend(decoded.iter().map(|packet| packet.sequence_number()));
+            datagrams += 1;
+            rest = &rest[len..];
+        }
+        assert_eq!(sequences, (0..10).collect::<Vec<u64>>());
+        assert_eq!(datagrams, 4); // three 400 byte messages to a datagram
+    }
+
+    #[test]
+    fn test_bundle_len_edge_cases() {
+        assert_eq!(bundle_len(PROTOCOL_VERSION, &[] as &[Packet]), 0);
+
+        // too big for any datagram, but it goes by itself rather than holding up the rest
+        let packets = vec![chat_request(0, UDP_MTU_SIZE), chat_request(1, 10)];
+        assert_eq!(bundle_len(PROTOCOL_VERSION, &packets), 1);
+
+        // the registrar's packets always go alone
+        let get_status = Packet::GetStatus {
+            ping: PingPong { nonce: 1 },
+        };
+        let packets = vec![chat_request(0, 10), get_status.clone(), chat_request(1, 10)];
+        assert_eq!(bundle_len(PROTOCOL_VERSION, &packets), 1);
+        assert_eq!(bundle_len(PROTOCOL_VERSION, &packets[1..]), 1);
+        assert_eq!(bundle_len(PROTOCOL_VERSION, &packets[2..]), 1);
+        assert!(encode_bundle(PROTOCOL_VERSION, &[get_status]).is_err());
+    }
+
+    #[test]
+    fn test_registrar_packets_have_no_header() {
+        let packet = Packet::GetStatus {
+            ping: PingPong {
+                nonce: 0x123456789ABCDEF0,
+            },
+        };
+        let datagram = encode_datagram(PROTOCOL_VERSION, &packet).unwrap();
+        assert_eq!(datagram, serialize(&packet).unwrap());
+
+        let (mut decoded, len) = decode_datagram(&datagram).unwrap();
+        assert_eq!(len, datagram.len());
+        assert_eq!(decoded.len(), 1);
+        match decoded.remove(0) {
+            Packet::GetStatus { ping } => assert_eq!(ping.nonce, 0x123456789ABCDEF0),
+            packet => panic!("Unexpected packet: {:?}", packet),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bind_invalid_host() {
+        match bind(Some("localhost"), Some(0)).await {
+            Err(e @ NetError::InvalidBindAddress(..)) => assert!(e.to_string().contains("\"localhost\"")),
+            other => panic!("Unexpected result: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bind_error_includes_address() {
+        let udp = bind(Some("127.0.0.1"), Some(0)).await.unwrap();
+        let port = udp.local_addr().unwrap().port();
+        match bind(Some("127.0.0.1"), Some(port)).await {
+            Err(e @ NetError::BindError(..)) => {
+                assert!(e.to_string().contains(&format!("127.0.0.1:{}", port)))
+            }
+            other => panic!("Unexpected result: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bind_dual_stack_accepts_ipv4() {
+        let server = bind(Some("::"), Some(0)).await.unwrap();
+        let port = server.local_addr().unwrap().port();
+        let client = bind(Some("127.0.0.1"), Some(0)).await.unwrap();
+        client.send_to(b"hi", ("127.0.0.1", port)).await.unwrap();
+
+        let mut buf = [0u8; 8];
+        let (len, from) = server.recv_from(&mut buf).await.unwrap();
+        assert_eq!(&buf[..len], b"hi");
+        // IPv4 peers arrive as IPv4-mapped IPv6 addresses, and replies must go back to those
+        match from {
+            SocketAddr::V6(from) => assert_eq!(from.ip().to_ipv4(), Some(Ipv4Addr::LOCALHOST)),
+            SocketAddr::V4(_) => panic!("Expected an IPv4-mapped address but got {}", from),
+        }
+        server.send_to(b"ho", from).await.unwrap();
+        let len = client.recv(&mut buf).await.unwrap();
+        assert_eq!(&buf[..len], b"ho");
+    }
+
+    fn gen_state_diff(gen0: usize, gen1: usize, pattern_len: usize) -> GenStateDiff {
+        let pattern: String = (0..pattern_len).map(|i| if i % 2 == 0 { 'o' } else { 'b' }).collect();
+        GenStateDiff {
+            gen0,
+            gen1,
+            pattern: Pattern(pattern),
+        }
+    }
+
+    #[test]
+    fn split_gen_state_diff_small_diff_is_one_part() {
+        let diff = gen_state_diff(3, 4, 10);
+        let parts = GenStateDiffPart::split(&diff).unwrap();
+        assert_eq!(
+            parts,
+            vec![GenStateDiffPart {
+                part_number:  0,
+                total_parts:  1,
+                gen0:         3,
+                gen1:         4,
+                pattern_part: diff.pattern.0.clone(),
+            }]
+        );
+    }
+
+    #[test]
+    fn split_gen_state_diff_parts_fit_in_mtu() {
+        let diff = gen_state_diff(0, 10, MAX_DIFF_PART_CHARS * 3 + 1);
+        let parts = GenStateDiffPart::split(&diff).unwrap();
+        assert_eq!(parts.len(), 4);
+        for (i, part) in parts.into_iter().enumerate() {
+            assert_eq!(part.part_number as usize, i);
+            assert_eq!(part.total_parts, 4);
+            let packet = Packet::Update {
+                chats:           vec![],
+                game_updates:    vec![],
+                game_update_seq: None,
+                universe_update: UniUpdate::Diff { diff: part },
+                gen_checksum:    Some(GenChecksum {
+                    generation: u64::max_value(),
+                    checksum:   u64::max_value(),
+                }),
+                ping:            PingPong::ping(),
+            };
+            assert!(encode_datagram(PROTOCOL_VERSION, &packet).unwrap().len() <= UDP_MTU_SIZE);
+        }
+    }
+
+    #[test]
+    fn split_gen_state_diff_too_large() {
+        let diff = gen_state_diff(0, 10, MAX_DIFF_PART_CHARS * MAX_DIFF_PARTS + 1);
+        assert_eq!(GenStateDiffPart::split(&diff), None);
+    }
+
+    #[test]
+    fn partial_gen_state_diff_reassembles_out_of_order() {
+        let diff = gen_state_diff(5, 7, MAX_DIFF_PART_CHARS * 2 + 100);
+        let mut parts = GenStateDiffPart::split(&diff).unwrap();
+        let last = parts.pop().unwrap();
+        let mut partial = PartialGenStateDiff::new(&last);
+        assert_eq!(partial.add(last), None);
+        assert_eq!(
+            partial.info(),
+            GenPartInfo {
+                gen0:         5,
+                gen1:         7,
+                have_bitmask: 0b100,
+            }
+        );
+
+        let first = parts.remove(0);
+        assert_eq!(partial.add(first.clone()), None);
+        assert_eq!(partial.add(first), None); // a duplicate doesn't complete it
+        assert_eq!(partial.info().have_bitmask, 0b101);
+
+        assert_eq!(partial.add(parts.remove(0)), Some(diff));
+    }
+
+    #[test]
+    fn partial_gen_state_diff_ignores_other_diffs() {
+        let diff = gen_state_diff(5, 7, MAX_DIFF_PART_CHARS + 1);
+        let other_diff = gen_state_diff(5, 8, MAX_DIFF_PART_CHARS + 1);
+        let parts = GenStateDiffPart::split(&diff).unwrap();
+        let other_parts = GenStateDiffPart::split(&other_diff).unwrap();
+
+        let mut partial = PartialGenStateDiff::new(&parts[0]);
+        assert!(!partial.is_same_diff(&other_parts[1]));
+        assert_eq!(partial.add(other_parts[1].clone()), None);
+        assert_eq!(partial.add(parts[0].clone()), None);
+        assert_eq!(partial.info().have_bitmask, 0b01);
+    }
+
+    // Strategies for generating every kind of Packet, for the round trip test below
+    fn a_string() -> BoxedStrategy<String> {
+        "\\PC{0,12}".boxed()
+    }
+
+    fn a_cell_state() -> BoxedStrategy<CellState> {
+        prop_oneof![
+            Just(CellState::Dead),
+            option::of(any::<usize>()).prop_map(CellState::Alive),
+            Just(CellState::Wall),
+            Just(CellState::Fog),
+        ]
+        .boxed()
+    }
+
+    fn a_client_option_value() -> BoxedStrategy<ClientOptionValue> {
+        let leaf = prop_oneof![
+            any::<bool>().prop_map(|value| ClientOptionValue::Bool { value }),
+            any::<u8>().prop_map(|value| ClientOptionValue::U8 { value }),
+            any::<u16>().prop_map(|value| ClientOptionValue::U16 { value }),
+            any::<u32>().prop_map(|value| ClientOptionValue::U32 { value }),
+            any::<u64>().prop_map(|value| ClientOptionValue::U64 { value }),
+            any::<i8>().prop_map(|value| ClientOptionValue::I8 { value }),
+            any::<i16>().prop_map(|value| ClientOptionValue::I16 { value }),
+            any::<i32>().prop_map(|value| ClientOptionValue::I32 { value }),
+            any::<i64>().prop_map(|value| ClientOptionValue::I64 { value }),
+            a_string().prop_map(|value| ClientOptionValue::Str { value }),
+        ];
+        leaf.prop_recursive(2, 8, 4, |inner| {
+            collection::vec(inner, 0..4).prop_map(|value| ClientOptionValue::List { value })
+        })
+        .boxed()
+    }
+
+    fn an_admin_command() -> BoxedStrategy<AdminCommand> {
+        prop_oneof![
+            a_string().prop_map(|target| AdminCommand::Ban { target }),
+            a_string().prop_map(|target| AdminCommand::Unban { target }),
+            Just(AdminCommand::ListBans),
+        ]
+        .boxed()
+    }
+
+    fn an_invite_failure() -> BoxedStrategy<InviteFailure> {
+        prop_oneof![
+            Just(InviteFailure::NoSuchPlayer),
+            Just(InviteFailure::AlreadyInRoom),
+            Just(InviteFailure::InviterNotInRoom),
+            Just(InviteFailure::UnknownInvite),
+            Just(InviteFailure::Expired),
+            Just(InviteFailure::RoomClosed),
+            Just(InviteFailure::RoomFull),
+            Just(InviteFailure::InviterLeft),
+        ]
+        .boxed()
+    }
+
+    fn an_invite_answer() -> BoxedStrategy<InviteAnswer> {
+        prop_oneof![
+            Just(InviteAnswer::Accepted),
+            Just(InviteAnswer::Declined),
+            Just(InviteAnswer::Expired),
+        ]
+        .boxed()
+    }
+
+    fn a_player_result() -> BoxedStrategy<PlayerResult> {
+        (a_string(), any::<u8>(), any::<u64>(), any::<u64>(), any::<bool>())
+            .prop_map(|(name, team, generations, cells_at_end, disconnected)| PlayerResult {
+                name,
+                team,
+                generations,
+                cells_at_end,
+                disconnected,
+            })
+            .boxed()
+    }
+
+    fn a_team_failure() -> BoxedStrategy<TeamFailure> {
+        prop_oneof![
+            Just(TeamFailure::NotInRoom),
+            Just(TeamFailure::GameRunning),
+            any::<u8>().prop_map(|team_count| TeamFailure::NoSuchTeam { team_count }),
+        ]
+        .boxed()
+    }
+
+    fn a_request_action() -> BoxedStrategy<RequestAction> {
+        prop_oneof![
+            Just(RequestAction::None),
+            (a_string(), a_string()).prop_map(|(name, client_version)| RequestAction::Connect { name, client_version }),
+            Just(RequestAction::Disconnect),
+            any::<u64>().prop_map(|latest_response_ack| RequestAction::KeepAlive { latest_response_ack }),
+            Just(RequestAction::ListPlayers),
+            a_string().prop_map(|message| RequestAction::ChatMessage { message }),
+            Just(RequestAction::ListRooms),
+            (
+                a_string(),
+                option::of(a_string()),
+                option::of(any::<u8>()),
+                option::of(any::<u8>())
+            )
+                .prop_map(
+                    |(room_name, password, team_count, max_players)| RequestAction::NewRoom {
+                        room_name,
+                        password,
+                        team_count,
+                        max_players,
+                    }
+                ),
+            (a_string(), option::of(a_string()))
+                .prop_map(|(room_name, password)| RequestAction::JoinRoom { room_name, password }),
+            Just(RequestAction::LeaveRoom),
+            (a_string(), option::of(a_client_option_value()))
+                .prop_map(|(key, value)| RequestAction::SetClientOptions { key, value }),
+            (any::<i32>(), any::<i32>(), a_string()).prop_map(|(x, y, pattern)| RequestAction::DropPattern {
+                x,
+                y,
+                pattern
+            }),
+            (any::<i32>(), any::<i32>(), any::<u32>(), any::<u32>())
+                .prop_map(|(x, y, w, h)| RequestAction::ClearArea { x, y, w, h }),
+            collection::vec((any::<usize>(), any::<usize>(), a_cell_state()), 0..4)
+                .prop_map(|cells| RequestAction::PlaceCells { cells }),
+            Just(RequestAction::RequestFullState),
+            a_string().prop_map(|name| RequestAction::MutePlayer { name }),
+            a_string().prop_map(|name| RequestAction::UnmutePlayer { name }),
+            (a_string(), an_admin_command()).prop_map(|(token, command)| RequestAction::Admin { token, command }),
+            a_string().prop_map(|room_name| RequestAction::CloseRoom { room_name }),
+            a_string().prop_map(|name| RequestAction::InvitePlayer { name }),
+            a_string().prop_map(|token| RequestAction::AcceptInvite { token }),
+            a_string().prop_map(|token| RequestAction::DeclineInvite { token }),
+            any::<u8>().prop_map(|team| RequestAction::SetTeam { team }),
+            any::<u64>().prop_map(|timestamp| RequestAction::Ping { timestamp }),
+            Just(RequestAction::Discover),
+            any::<bool>().prop_map(|ready| RequestAction::SetReady { ready }),
+            Just(RequestAction::StartGame),
+        ]
+        .boxed()
+    }
+
+    fn a_response_code() -> BoxedStrategy<ResponseCode> {
+        let a_room = (a_string(), any::<u8>(), any::<u8>(), any::<bool>(), any::<bool>()).prop_map(
+            |(room_name, player_count, max_players, in_progress, has_password)| RoomList {
+                room_name,
+                player_count,
+                max_players,
+                in_progress,
+                has_password,
+            },
+        );
+        prop_oneof![
+            Just(ResponseCode::OK),
+            (a_string(), a_string())
+                .prop_map(|(cookie, server_version)| ResponseCode::LoggedIn { cookie, server_version }),
+            a_string().prop_map(|room_name| ResponseCode::JoinedRoom { room_name }),
+            Just(ResponseCode::LeaveRoom),
+            (
+                collection::vec(a_string(), 0..4),
+                collection::vec(a_string(), 0..4),
+                collection::vec(option::of(any::<u8>()), 0..4)
+            )
+                .prop_map(|(players, muted, teams)| ResponseCode::PlayerList { players, muted, teams }),
+            collection::vec(a_room, 0..4).prop_map(|rooms| ResponseCode::RoomList { rooms }),
+            any::<usize>().prop_map(|generation| ResponseCode::CellsPlaced { generation }),
+            a_string().prop_map(|error_msg| ResponseCode::BadRequest { error_msg }),
+            a_string().prop_map(|error_msg| ResponseCode::Unauthorized { error_msg }),
+            a_string().prop_map(|error_msg| ResponseCode::TooManyRequests { error_msg }),
+            (a_string(), any::<u64>())
+                .prop_map(|(error_msg, muted_secs)| ResponseCode::ChatThrottled { error_msg, muted_secs }),
+            a_string().prop_map(|error_msg| ResponseCode::ServerError { error_msg }),
+            a_string().prop_map(|error_msg| ResponseCode::NotConnected { error_msg }),
+            Just(ResponseCode::KeepAlive),
+            (collection::vec(a_string(), 0..4), collection::vec(a_string(), 0..4))
+                .prop_map(|(addrs, names)| ResponseCode::BanList { addrs, names }),
+            (a_string(), a_string(), a_string()).prop_map(|(inviter, room_name, token)| ResponseCode::Invited {
+                inviter,
+                room_name,
+                token
+            }),
+            an_invite_failure().prop_map(|reason| ResponseCode::InviteFailed { reason }),
+            (a_string(), an_invite_answer()).prop_map(|(name, answer)| ResponseCode::InviteAnswered { name, answer }),
+            a_team_failure().prop_map(|reason| ResponseCode::SetTeamFailed { reason }),
+            (a_string(), collection::vec((a_string(), any::<u8>()), 0..4))
+                .prop_map(|(room_name, teams)| ResponseCode::GameStarted { room_name, teams }),
+            (a_string(), any::<u64>(), collection::vec(a_player_result(), 0..4)).prop_map(
+                |(room_name, generations, results)| ResponseCode::GameEnded {
+                    room_name,
+                    generations,
+                    results
+                }
+            ),
+            any::<u64>().prop_map(|timestamp| ResponseCode::Pong { timestamp }),
+            (a_string(), a_string(), any::<u64>(), any::<u64>()).prop_map(
+                |(server_name, server_version, player_count, room_count)| ResponseCode::ServerInfo {
+                    server_name,
+                    server_version,
+                    player_count,
+                    room_count
+                }
+            ),
+            (a_string(), a_string())
+                .prop_map(|(player_name, message)| ResponseCode::LobbyChat { player_name, message }),
+            a_string().prop_map(|room_name| ResponseCode::RoomNameTaken { room_name }),
+            (a_string(), any::<u8>())
+                .prop_map(|(room_name, max_players)| ResponseCode::RoomFull { room_name, max_players }),
+            (a_string(), any::<u64>()).prop_map(|(room_name, seconds_left)| ResponseCode::GameStarting {
+                room_name,
+                seconds_left
+            }),
+            a_string().prop_map(|room_name| ResponseCode::GameStartCancelled { room_name }),
+            (a_string(), a_string()).prop_map(|(room_name, owner)| ResponseCode::OwnerChanged { room_name, owner }),
+        ]
+        .boxed()
+    }
+
+    fn a_player_info() -> BoxedStrategy<PlayerInfo> {
+        (a_string(), option::of(any::<u64>()))
+            .prop_map(|(name, index)| PlayerInfo::new(name, index))
+            .boxed()
+    }
+
+    fn a_game_update() -> BoxedStrategy<GameUpdate> {
+        let a_region = (any::<i32>(), any::<i32>(), any::<u32>(), any::<u32>())
+            .prop_map(|(left, top, width, height)| NetRegion::new(left, top, width, height));
+        let game_options = (
+            any::<u32>(),
+            any::<u32>(),
+            any::<u16>(),
+            collection::vec(a_region, 0..4),
+            any::<u32>(),
+        )
+            .prop_map(|(width, height, history, player_writable, fog_radius)| {
+                GameOptions::new(width, height, history, player_writable, fog_radius)
+            });
+        prop_oneof![
+            a_string().prop_map(|msg| GameUpdate::GameNotification { msg }),
+            game_options.prop_map(|options| GameUpdate::GameStart { options }),
+            collection::vec(a_player_info(), 0..4).prop_map(|players| GameUpdate::PlayerList { players }),
+            (a_player_info(), option::of(a_string()))
+                .prop_map(|(player, old_name)| GameUpdate::PlayerChange { player, old_name }),
+            a_player_info().prop_map(|player| GameUpdate::PlayerJoin { player }),
+            a_string().prop_map(|name| GameUpdate::PlayerLeave { name }),
+            option::of(a_string()).prop_map(|winner| GameUpdate::GameFinish {
+                outcome: GameOutcome { winner },
+            }),
+            Just(GameUpdate::RoomDeleted),
+            (a_string(), any::<u32>()).prop_map(|(room, expire_secs)| GameUpdate::Match { room, expire_secs }),
+        ]
+        .boxed()
+    }
+
+    fn a_uni_update() -> BoxedStrategy<UniUpdate> {
+        let a_diff_part = (any::<u8>(), any::<u8>(), any::<u32>(), any::<u32>(), a_string()).prop_map(
+            |(part_number, total_parts, gen0, gen1, pattern_part)| GenStateDiffPart {
+                part_number,
+                total_parts,
+                gen0,
+                gen1,
+                pattern_part,
+            },
+        );
+        prop_oneof![
+            a_diff_part.prop_map(|diff| UniUpdate::Diff { diff }),
+            Just(UniUpdate::NoChange),
+        ]
+        .boxed()
+    }
+
+    fn a_packet() -> BoxedStrategy<Packet> {
+        let a_chat = (option::of(any::<u64>()), a_string(), a_string()).prop_map(|(chat_seq, player_name, message)| {
+            BroadcastChatMessage {
+                chat_seq,
+                player_name,
+                message,
+            }
+        });
+        let a_gen_checksum =
+            (any::<u64>(), any::<u64>()).prop_map(|(generation, checksum)| GenChecksum { generation, checksum });
+        let a_gen_part_info =
+            (any::<u32>(), any::<u32>(), any::<u32>()).prop_map(|(gen0, gen1, have_bitmask)| GenPartInfo {
+                gen0,
+                gen1,
+                have_bitmask,
+            });
+        prop_oneof![
+            (
+                any::<u64>(),
+                option::of(any::<u64>()),
+                option::of(a_string()),
+                a_request_action()
+            )
+                .prop_map(|(sequence, response_ack, cookie, action)| Packet::Request {
+                    sequence,
+                    response_ack,
+                    cookie,
+                    action,
+                }),
+            (any::<u64>(), option::of(any::<u64>()), a_response_code()).prop_map(|(sequence, request_ack, code)| {
+                Packet::Response {
+                    sequence,
+                    request_ack,
+                    code,
+                }
+            }),
+            (
+                collection::vec(a_chat, 0..4),
+                option::of(any::<u64>()),
+                collection::vec(a_game_update(), 0..4),
+                a_uni_update(),
+                option::of(a_gen_checksum),
+                any::<u64>(),
+            )
+                .prop_map(
+                    |(chats, game_update_seq, game_updates, universe_update, gen_checksum, nonce)| Packet::Update {
+                        chats,
+                        game_update_seq,
+                        game_updates,
+                        universe_update,
+                        gen_checksum,
+                        ping: PingPong { nonce },
+                    }
+                ),
+            (
+                a_string(),
+                option::of(any::<u64>()),
+                option::of(any::<u64>()),
+                option::of(any::<u64>()),
+                option::of(a_gen_part_info),
+                any::<u64>(),
+            )
+                .prop_map(
+                    |(cookie, last_chat_seq, last_game_update_seq, last_full_gen, partial_gen, nonce)| {
+                        Packet::UpdateReply {
+                            cookie,
+                            last_chat_seq,
+                            last_game_update_seq,
+                            last_full_gen,
+                            partial_gen,
+                            pong: PingPong { nonce },
+                        }
+                    }
+                ),
+            any::<u64>().prop_map(|nonce| Packet::GetStatus {
+                ping: PingPong { nonce },
+            }),
+            (any::<u64>(), a_string(), any::<u64>(), any::<u64>(), a_string()).prop_map(
+                |(nonce, server_version, player_count, room_count, server_name)| Packet::Status {
+                    pong: PingPong { nonce },
+                    server_version,
+                    player_count,
+                    room_count,
+                    server_name,
+                }
+            ),
+        ]
+        .boxed()
+    }
+
+    proptest! {
+        // Packet's PartialEq only compares sequence numbers (and so does BroadcastChatMessage's), so
+        // compare the packets field by field through serde_json instead.
+        #[test]
+        fn packet_round_trip(ref packet in a_packet()) {
+            let datagram = encode_datagram(PROTOCOL_VERSION, packet).unwrap();
+            let (decoded, len) = decode_datagram(&datagram).unwrap();
+            prop_assert_eq!(len, datagram.len());
+            prop_assert_eq!(decoded.len(), 1);
+            prop_assert_eq!(serde_json::to_value(&decoded[0]).unwrap(), serde_json::to_value(packet).unwrap());
+        }
+    }
 }
 
 mod netwayste_client_tests {
     use super::*;
     use crate::client::*;
+    use conway::rle::Pattern;
+    use conway::universe::GenStateDiff;
 
     fn create_client_net_state() -> ClientNetState {
         let (nw_server_response, _ggez_server_response) = futures::channel::mpsc::channel::<NetwaysteEvent>(5);
@@ -1291,4 +2527,71 @@ mod netwayste_client_tests {
             seq_num += 1;
         }
     }
+
+    fn diff_parts(gen0: usize, gen1: usize) -> Vec<GenStateDiffPart> {
+        let pattern: String = std::iter::repeat('o').take(MAX_DIFF_PART_CHARS + 1).collect();
+        let diff = GenStateDiff {
+            gen0,
+            gen1,
+            pattern: Pattern(pattern),
+        };
+        GenStateDiffPart::split(&diff).unwrap()
+    }
+
+    #[tokio::test]
+    async fn handle_universe_diff_part_waits_for_all_parts() {
+        let mut client_state = create_client_net_state();
+        let mut parts = diff_parts(0, 3);
+
+        client_state.handle_universe_diff_part(parts.remove(0)).await;
+        assert_eq!(client_state.last_full_gen, None);
+        assert!(client_state.partial_diff.is_some());
+
+        client_state.handle_universe_diff_part(parts.remove(0)).await;
+        assert_eq!(client_state.last_full_gen, Some(3));
+        assert_eq!(client_state.partial_diff, None);
+    }
+
+    #[tokio::test]
+    async fn handle_universe_diff_part_ignores_old_generations() {
+        let mut client_state = create_client_net_state();
+        client_state.last_full_gen = Some(3);
+
+        client_state.handle_universe_diff_part(diff_parts(2, 3).remove(0)).await;
+        assert_eq!(client_state.partial_diff, None);
+    }
+
+    #[tokio::test]
+    async fn handle_universe_diff_part_newer_diff_replaces_partial_diff() {
+        let mut client_state = create_client_net_state();
+
+        client_state.handle_universe_diff_part(diff_parts(0, 3).remove(0)).await;
+        client_state.handle_universe_diff_part(diff_parts(0, 4).remove(1)).await;
+        assert_eq!(client_state.partial_diff.as_ref().unwrap().info().gen1, 4);
+
+        // a straggler from the older diff doesn't replace the newer one
+        client_state.handle_universe_diff_part(diff_parts(0, 3).remove(1)).await;
+        assert_eq!(client_state.partial_diff.as_ref().unwrap().info().gen1, 4);
+        assert_eq!(client_state.last_full_gen, None);
+
+        client_state.handle_universe_diff_part(diff_parts(0, 4).remove(0)).await;
+        assert_eq!(client_state.last_full_gen, Some(4));
+    }
+
+    #[tokio::test]
+    async fn handle_request_full_state_starts_over_from_the_beginning() {
+        let mut client_state = create_client_net_state();
+        client_state.handle_universe_diff_part(diff_parts(0, 3).remove(0)).await;
+        client_state.handle_universe_diff_part(diff_parts(0, 3).remove(1)).await;
+        client_state.handle_universe_diff_part(diff_parts(3, 4).remove(0)).await;
+        assert_eq!(client_state.last_full_gen, Some(3));
+
+        client_state.handle_request_full_state();
+        assert_eq!(client_state.last_full_gen, None);
+        assert_eq!(client_state.partial_diff, None);
+
+        // a diff of a generation we had before is no longer ignored
+        client_state.handle_universe_diff_part(diff_parts(0, 3).remove(0)).await;
+        assert!(client_state.partial_diff.is_some());
+    }
 }
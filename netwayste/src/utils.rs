@@ -15,7 +15,9 @@
  * this program.  If not, see <http://www.gnu.org/licenses/>.
  */
 
+mod netsim;
 mod ping;
 
+pub use netsim::{FaultConfig, FaultInjector};
 pub use ping::LatencyFilter;
 pub use ping::PingPong;
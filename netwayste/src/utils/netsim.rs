@@ -0,0 +1,242 @@
+/*
+ * Copyright (C) 2021 The Conwayste Developers
+ *
+ * This program is free software: you can redistribute it and/or modify it
+ * under the terms of the GNU General Public License as published by the Free
+ * Software Foundation, either version 3 of the License, or (at your option)
+ * any later version.
+ *
+ * This program is distributed in the hope that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of  MERCHANTABILITY or
+ * FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for
+ * more details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+#![allow(dead_code)] // Because this file is pub for server.rs. TODO: Refactor server into crate
+
+//! Fault injection for the transport. Packets pushed into a `FaultInjector` may be dropped,
+//! duplicated, or held back for a random delay before they are drained. This is only active when
+//! built with the `netsim` feature (or for tests); otherwise every packet passes straight through.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+#[cfg(any(test, feature = "netsim"))]
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+/// Per-endpoint fault settings. The default is a perfect network.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FaultConfig {
+    pub drop_rate:      f64, // fraction of packets to drop, 0.0 to 1.0
+    pub duplicate_rate: f64, // fraction of packets to send twice, 0.0 to 1.0
+    pub min_delay:      Duration,
+    pub max_delay:      Duration,
+}
+
+impl Default for FaultConfig {
+    fn default() -> Self {
+        FaultConfig {
+            drop_rate:      0.0,
+            duplicate_rate: 0.0,
+            min_delay:      Duration::from_millis(0),
+            max_delay:      Duration::from_millis(0),
+        }
+    }
+}
+
+/// Sits between the code producing packets and the socket. Items are pushed in with `push` and
+/// pulled out with `drain_ready` once their (possibly simulated) delivery time has arrived.
+#[derive(Debug)]
+pub struct FaultInjector<T> {
+    configs: HashMap<SocketAddr, FaultConfig>,
+    default: FaultConfig,
+    queue:   Vec<(Instant, SocketAddr, T)>, // (release time, endpoint, item)
+    #[cfg(any(test, feature = "netsim"))]
+    rng:     StdRng,
+}
+
+impl<T: Clone> FaultInjector<T> {
+    /// Creates a `FaultInjector` whose random decisions are determined entirely by `seed`, so that
+    /// a failing run can be reproduced.
+    pub fn new(seed: u64) -> Self {
+        #[cfg(not(any(test, feature = "netsim")))]
+        let _ = seed;
+        FaultInjector {
+            configs: HashMap::new(),
+            default: FaultConfig::default(),
+            queue: vec![],
+            #[cfg(any(test, feature = "netsim"))]
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// Sets the fault settings used for endpoints without their own settings.
+    pub fn set_default_config(&mut self, config: FaultConfig) {
+        self.default = config;
+    }
+
+    /// Sets the fault settings for packets to or from `addr`.
+    pub fn set_config(&mut self, addr: SocketAddr, config: FaultConfig) {
+        self.configs.insert(addr, config);
+    }
+
+    pub fn clear_config(&mut self, addr: &SocketAddr) {
+        self.configs.remove(addr);
+    }
+
+    /// Returns the fault settings that apply to `addr`.
+    pub fn config(&self, addr: &SocketAddr) -> &FaultConfig {
+        self.configs.get(addr).unwrap_or(&self.default)
+    }
+
+    /// Number of items waiting to be drained.
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// Queues `item` for delivery to or from `addr`, applying the endpoint's fault settings.
+    #[cfg(any(test, feature = "netsim"))]
+    pub fn push(&mut self, addr: SocketAddr, item: T, now: Instant) {
+        let config = self.configs.get(&addr).unwrap_or(&self.default).clone();
+        if config.drop_rate > 0.0 && self.rng.gen_bool(config.drop_rate.min(1.0)) {
+            trace!("[{}] [NETSIM] dropped packet", addr);
+            return;
+        }
+        let copies = if config.duplicate_rate > 0.0 && self.rng.gen_bool(config.duplicate_rate.min(1.0)) {
+            trace!("[{}] [NETSIM] duplicated packet", addr);
+            2
+        } else {
+            1
+        };
+        for _ in 0..copies {
+            let delay = if config.max_delay > config.min_delay {
+                self.rng.gen_range(config.min_delay..=config.max_delay)
+            } else {
+                config.min_delay
+            };
+            self.queue.push((now + delay, addr, item.clone()));
+        }
+    }
+
+    /// Queues `item` for delivery to or from `addr`. Without the `netsim` feature, nothing is ever
+    /// dropped, duplicated, or delayed.
+    #[cfg(not(any(test, feature = "netsim")))]
+    #[inline]
+    pub fn push(&mut self, addr: SocketAddr, item: T, now: Instant) {
+        self.queue.push((now, addr, item));
+    }
+
+    /// Removes and returns all items whose release time is at or before `now`, in release order.
+    /// Items released at the same time keep the order in which they were pushed.
+    pub fn drain_ready(&mut self, now: Instant) -> Vec<(SocketAddr, T)> {
+        let mut ready = vec![];
+        let mut i = 0;
+        while i < self.queue.len() {
+            if self.queue[i].0 <= now {
+                ready.push(self.queue.remove(i));
+            } else {
+                i += 1;
+            }
+        }
+        ready.sort_by_key(|&(release, _, _)| release); // stable sort
+        ready.into_iter().map(|(_, addr, item)| (addr, item)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{IpAddr, Ipv4Addr};
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::new(IpAddr::V4(Ipv4Addr::new(1, 2, 3, 4)), port)
+    }
+
+    fn push_many(fi: &mut FaultInjector<u32>, count: u32, now: Instant) {
+        for i in 0..count {
+            fi.push(addr(1), i, now);
+        }
+    }
+
+    #[test]
+    fn test_default_config_passes_everything_in_order() {
+        let now = Instant::now();
+        let mut fi = FaultInjector::new(1);
+        push_many(&mut fi, 10, now);
+        let items: Vec<u32> = fi.drain_ready(now).into_iter().map(|(_, i)| i).collect();
+        assert_eq!(items, (0..10).collect::<Vec<u32>>());
+        assert!(fi.is_empty());
+    }
+
+    #[test]
+    fn test_drop_all() {
+        let now = Instant::now();
+        let mut fi = FaultInjector::new(1);
+        fi.set_config(
+            addr(1),
+            FaultConfig {
+                drop_rate: 1.0,
+                ..Default::default()
+            },
+        );
+        push_many(&mut fi, 10, now);
+        fi.push(addr(2), 99, now); // other endpoint unaffected
+        assert_eq!(fi.drain_ready(now), vec![(addr(2), 99)]);
+    }
+
+    #[test]
+    fn test_duplicate_all() {
+        let now = Instant::now();
+        let mut fi = FaultInjector::new(1);
+        fi.set_default_config(FaultConfig {
+            duplicate_rate: 1.0,
+            ..Default::default()
+        });
+        push_many(&mut fi, 3, now);
+        let items: Vec<u32> = fi.drain_ready(now).into_iter().map(|(_, i)| i).collect();
+        assert_eq!(items, vec![0, 0, 1, 1, 2, 2]);
+    }
+
+    #[test]
+    fn test_delay_holds_packets_until_released() {
+        let now = Instant::now();
+        let mut fi = FaultInjector::new(1);
+        fi.set_default_config(FaultConfig {
+            min_delay: Duration::from_millis(50),
+            max_delay: Duration::from_millis(100),
+            ..Default::default()
+        });
+        push_many(&mut fi, 20, now);
+        assert!(fi.drain_ready(now + Duration::from_millis(49)).is_empty());
+        let drained = fi.drain_ready(now + Duration::from_millis(100));
+        assert_eq!(drained.len(), 20);
+        assert!(fi.is_empty());
+    }
+
+    #[test]
+    fn test_same_seed_same_decisions() {
+        let now = Instant::now();
+        let config = FaultConfig {
+            drop_rate:      0.3,
+            duplicate_rate: 0.2,
+            min_delay:      Duration::from_millis(0),
+            max_delay:      Duration::from_millis(200),
+        };
+        let run = |seed| {
+            let mut fi = FaultInjector::new(seed);
+            fi.set_default_config(config.clone());
+            push_many(&mut fi, 100, now);
+            fi.drain_ready(now + Duration::from_millis(200))
+        };
+        assert_eq!(run(42), run(42));
+        assert_ne!(run(42), run(43));
+    }
+}